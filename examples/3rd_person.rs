@@ -615,6 +615,9 @@ fn create_scene_async(
     std::thread::spawn(move || {
         let mut scene = Scene::new();
 
+        // Set ambient light.
+        scene.environment.ambient_color = Color::opaque(80, 80, 80);
+
         let mut resource_manager = resource_manager.lock().unwrap();
 
         context
@@ -707,12 +710,12 @@ fn main() {
     // Initially scene is None, once scene is loaded it'll have actual state.
     let mut game_scene: Option<GameScene> = None;
 
-    // Set ambient light.
-    engine.renderer.set_ambient_color(Color::opaque(80, 80, 80));
-    let mut quality = engine.renderer.get_quality_settings();
-    quality.spot_shadows_distance = 300.0;
-    quality.point_shadows_distance = 300.0;
-    engine.renderer.set_quality_settings(&quality).unwrap();
+    if let Some(renderer) = engine.renderer_mut() {
+        let mut quality = renderer.get_quality_settings();
+        quality.spot_shadows_distance = 300.0;
+        quality.point_shadows_distance = 300.0;
+        renderer.set_quality_settings(&quality).unwrap();
+    }
 
     let clock = Instant::now();
     let fixed_timestep = 1.0 / 60.0;
@@ -793,7 +796,9 @@ fn main() {
                         game_scene.player.update(scene, fixed_timestep);
                     }
 
-                    let fps = engine.renderer.get_statistics().frames_per_second;
+                    let fps = engine
+                        .renderer()
+                        .map_or(0, |renderer| renderer.get_statistics().frames_per_second);
                     let debug_text = format!(
                         "Example 03 - 3rd Person\n[W][S][A][D] - walk, [SPACE] - jump.\nFPS: {}",
                         fps
@@ -832,7 +837,9 @@ fn main() {
                         // It is very important to handle Resized event from window, because
                         // renderer knows nothing about window size - it must be notified
                         // directly when window size has changed.
-                        engine.renderer.set_frame_size(size.into());
+                        if let Some(renderer) = engine.renderer_mut() {
+                            renderer.set_frame_size(size.into());
+                        }
 
                         // Root UI node should be resized too, otherwise progress bar will stay
                         // in wrong position after resize.
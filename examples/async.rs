@@ -132,6 +132,9 @@ fn create_scene_async(
     std::thread::spawn(move || {
         let mut scene = Scene::new();
 
+        // Set ambient light.
+        scene.environment.ambient_color = Color::opaque(200, 200, 200);
+
         let mut resource_manager = resource_manager.lock().unwrap();
 
         // It is important to lock context for short period of time so other thread can
@@ -258,11 +261,6 @@ fn main() {
     let mut model_handle = Handle::NONE;
     let mut walk_animation = Handle::NONE;
 
-    // Set ambient light.
-    engine
-        .renderer
-        .set_ambient_color(Color::opaque(200, 200, 200));
-
     let clock = Instant::now();
     let fixed_timestep = 1.0 / 60.0;
     let mut elapsed_time = 0.0;
@@ -344,7 +342,9 @@ fn main() {
                     }
 
                     // While scene is loading, we will update progress bar.
-                    let fps = engine.renderer.get_statistics().frames_per_second;
+                    let fps = engine
+                        .renderer()
+                        .map_or(0, |renderer| renderer.get_statistics().frames_per_second);
                     let debug_text = format!("Example 02 - Asynchronous Scene Loading\nUse [A][D] keys to rotate model.\nFPS: {}", fps);
                     engine.user_interface.send_message(TextMessage::text(interface.debug_text, MessageDirection::ToWidget,debug_text));
 
@@ -378,7 +378,9 @@ fn main() {
                         // It is very important to handle Resized event from window, because
                         // renderer knows nothing about window size - it must be notified
                         // directly when window size has changed.
-                        engine.renderer.set_frame_size(size.into());
+                        if let Some(renderer) = engine.renderer_mut() {
+                            renderer.set_frame_size(size.into());
+                        }
 
                         // Root UI node should be resized too, otherwise progress bar will stay
                         // in wrong position after resize.
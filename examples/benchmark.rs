@@ -0,0 +1,90 @@
+//! Example 09. Benchmark.
+//!
+//! Difficulty: Easy.
+//!
+//! This example shows how to run standard benchmark. It generates stress scene, flies camera
+//! around it and prints report in JSON format when camera reaches end of its path. Amount
+//! of objects can be passed as command line arguments: `<lights> <particle systems> <characters>`.
+
+extern crate rg3d;
+
+use rg3d::{
+    core::color::Color,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop},
+    gui::node::StubNode,
+    utils::benchmark::{Benchmark, BenchmarkSettings},
+};
+use std::time::Instant;
+
+type GameEngine = rg3d::engine::Engine<(), StubNode>;
+
+fn main() {
+    let event_loop = EventLoop::new();
+
+    let window_builder = rg3d::window::WindowBuilder::new()
+        .with_title("Example - Benchmark")
+        .with_resizable(true);
+
+    let mut engine = GameEngine::new(window_builder, &event_loop).unwrap();
+
+    let mut settings = BenchmarkSettings::default();
+    let args = std::env::args()
+        .skip(1)
+        .map(|arg| arg.parse::<usize>().expect("Amount of objects must be a number!"))
+        .collect::<Vec<_>>();
+    if let Some(&light_count) = args.get(0) {
+        settings.light_count = light_count;
+    }
+    if let Some(&particle_system_count) = args.get(1) {
+        settings.particle_system_count = particle_system_count;
+    }
+    if let Some(&skinned_character_count) = args.get(2) {
+        settings.skinned_character_count = skinned_character_count;
+    }
+
+    let (mut benchmark, scene) = Benchmark::new(settings);
+    let scene_handle = engine.scenes.add(scene);
+
+    engine.scenes[scene_handle].environment.ambient_color = Color::opaque(60, 60, 60);
+
+    let clock = Instant::now();
+    let mut last_time = 0.0;
+
+    event_loop.run(move |event, _, control_flow| match event {
+        Event::MainEventsCleared => {
+            // Benchmark must not be limited by fixed time step, so every frame does single
+            // update with real elapsed time.
+            let time = clock.elapsed().as_secs_f32();
+            let dt = time - last_time;
+            last_time = time;
+
+            let statistics = match engine.renderer() {
+                Some(renderer) => renderer.get_statistics(),
+                None => {
+                    eprintln!("Benchmark requires renderer!");
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+            };
+            if benchmark.update(&mut engine.scenes[scene_handle], &statistics, dt) {
+                println!("{}", benchmark.report().to_json());
+                *control_flow = ControlFlow::Exit;
+                return;
+            }
+
+            engine.update(dt);
+            engine.render(dt).unwrap();
+        }
+        Event::WindowEvent { event, .. } => match event {
+            WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+            WindowEvent::Resized(size) => {
+                if let Some(renderer) = engine.renderer_mut() {
+                    renderer.set_frame_size(size.into());
+                }
+            }
+            _ => (),
+        },
+        _ => *control_flow = ControlFlow::Poll,
+    });
+}
@@ -110,9 +110,7 @@ fn main() {
     let scene_handle = engine.scenes.add(scene);
 
     // Set ambient light.
-    engine
-        .renderer
-        .set_ambient_color(Color::opaque(200, 200, 200));
+    engine.scenes[scene_handle].environment.ambient_color = Color::opaque(200, 200, 200);
 
     let clock = Instant::now();
     let fixed_timestep = 1.0 / 60.0;
@@ -160,7 +158,9 @@ fn main() {
                         .local_transform_mut()
                         .set_rotation(Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), model_angle));
 
-                    let fps = engine.renderer.get_statistics().frames_per_second;
+                    let fps = engine
+                        .renderer()
+                        .map_or(0, |renderer| renderer.get_statistics().frames_per_second);
                     let text = format!(
                         "Example 05 - Scene\nUse [A][D] keys to rotate scene.\nFPS: {}",
                         fps
@@ -199,7 +199,9 @@ fn main() {
                         // It is very important to handle Resized event from window, because
                         // renderer knows nothing about window size - it must be notified
                         // directly when window size has changed.
-                        engine.renderer.set_frame_size(size.into());
+                        if let Some(renderer) = engine.renderer_mut() {
+                            renderer.set_frame_size(size.into());
+                        }
                     }
                     _ => (),
                 }
@@ -75,7 +75,7 @@ struct Interface {
 // complex layout system was borrowed from WPF framework. You can read more here:
 // https://docs.microsoft.com/en-us/dotnet/framework/wpf/advanced/layout
 fn create_ui(engine: &mut GameEngine) -> Interface {
-    let window_width = engine.renderer.get_frame_size().0 as f32;
+    let window_width = engine.frame_size().x;
 
     // Gather all suitable video modes, we'll use them to fill combo box of
     // available resolutions.
@@ -374,9 +374,7 @@ fn main() {
     let scene_handle = engine.scenes.add(scene);
 
     // Set ambient light.
-    engine
-        .renderer
-        .set_ambient_color(Color::opaque(200, 200, 200));
+    engine.scenes[scene_handle].environment.ambient_color = Color::opaque(200, 200, 200);
 
     let clock = Instant::now();
     let fixed_timestep = 1.0 / 60.0;
@@ -424,7 +422,9 @@ fn main() {
                             model_angle.to_radians(),
                         ));
 
-                    let fps = engine.renderer.get_statistics().frames_per_second;
+                    let fps = engine
+                        .renderer()
+                        .map_or(0, |renderer| renderer.get_statistics().frames_per_second);
                     engine.user_interface.send_message(TextMessage::text(
                         interface.debug_text,
                         MessageDirection::ToWidget,
@@ -483,10 +483,12 @@ fn main() {
                                         ));
 
                                         // Due to some weird bug in winit it does not send Resized event.
-                                        engine.renderer.set_frame_size((
-                                            video_mode.size().width,
-                                            video_mode.size().height,
-                                        ));
+                                        if let Some(renderer) = engine.renderer_mut() {
+                                            renderer.set_frame_size((
+                                                video_mode.size().width,
+                                                video_mode.size().height,
+                                            ));
+                                        }
                                     }
                                 }
                             }
@@ -509,7 +511,9 @@ fn main() {
                         // It is very important to handle Resized event from window, because
                         // renderer knows nothing about window size - it must be notified
                         // directly when window size has changed.
-                        engine.renderer.set_frame_size(dbg!(size.into()));
+                        if let Some(renderer) = engine.renderer_mut() {
+                            renderer.set_frame_size(dbg!(size.into()));
+                        }
                     }
                     _ => (),
                 }
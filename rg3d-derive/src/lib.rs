@@ -0,0 +1,194 @@
+//! Derive macros for rg3d game engine.
+//!
+//! `#[derive(Visit)]` generates implementation of `Visit` trait for a struct: every field is
+//! visited in a region of the struct, name of a field in the region is the identifier of the
+//! field (or its index for tuple structs). Fields can be configured with `visit` attribute:
+//!
+//! - `#[visit(rename = "Name")]` - visit field using given name, this keeps compatibility with
+//!   files that were written by hand-written implementation with different names.
+//! - `#[visit(skip)]` - field is not visited, it keeps its value when data is read.
+//! - `#[visit(optional)]` - errors of the field are ignored, so files which were written before
+//!   the field was added can still be read, the field keeps its value in this case.
+//! - `#[visit(since = N)]` - field was added in version `N` (starting from 1) of the struct. If
+//!   at least one field has this option, version of the struct (the largest `N`) is visited as
+//!   `Version` field of the region, data without it has version 0. Field is read only from data
+//!   of version `N` or newer and keeps its value otherwise. Unlike `optional`, errors of the
+//!   field in newer data are still reported.
+//!
+//! Generics must implement `Visit` too. Macro is re-exported by the engine as `rg3d::Visit`, and
+//! generated code refers to the trait using `rg3d` crate, so the engine must be a dependency
+//! with this name.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use rg3d::Visit;
+//!
+//! #[derive(Default, Visit)]
+//! struct Player {
+//!     health: f32,
+//!     #[visit(rename = "Ammo")]
+//!     ammo_count: u32,
+//!     #[visit(since = 1)]
+//!     armor: f32,
+//!     #[visit(skip)]
+//!     frames_since_last_shot: u32,
+//! }
+//! ```
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, spanned::Spanned, Attribute, Data, DeriveInput, Error,
+    GenericParam, Index, Lit, Meta, NestedMeta,
+};
+
+#[derive(Default)]
+struct FieldOptions {
+    name: Option<String>,
+    skip: bool,
+    optional: bool,
+    since: Option<u32>,
+}
+
+impl FieldOptions {
+    fn parse(attributes: &[Attribute]) -> Result<Self, Error> {
+        let mut options = Self::default();
+        for attribute in attributes.iter().filter(|a| a.path.is_ident("visit")) {
+            let list = match attribute.parse_meta()? {
+                Meta::List(list) => list,
+                meta => return Err(Error::new(meta.span(), "expected #[visit(...)]")),
+            };
+            for nested in list.nested.iter() {
+                match nested {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                        options.skip = true
+                    }
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("optional") => {
+                        options.optional = true
+                    }
+                    NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("rename") => {
+                        match &pair.lit {
+                            Lit::Str(name) => options.name = Some(name.value()),
+                            lit => return Err(Error::new(lit.span(), "expected string literal")),
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("since") => {
+                        let version = match &pair.lit {
+                            Lit::Int(version) => version.base10_parse::<u32>()?,
+                            lit => return Err(Error::new(lit.span(), "expected integer literal")),
+                        };
+                        if version == 0 {
+                            return Err(Error::new(pair.lit.span(), "versions start from 1"));
+                        }
+                        options.since = Some(version);
+                    }
+                    other => {
+                        return Err(Error::new(
+                            other.span(),
+                            "unknown visit option, expected `rename = \"...\"`, `skip`, \
+                             `optional` or `since = N`",
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn impl_visit(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "Visit can be derived only for structs",
+            ))
+        }
+    };
+
+    let mut statements = Vec::new();
+    let mut current_version = None;
+    for (index, field) in fields.iter().enumerate() {
+        let options = FieldOptions::parse(&field.attrs)?;
+        if options.skip {
+            continue;
+        }
+        current_version = current_version.max(options.since);
+        let (member, default_name) = match &field.ident {
+            Some(ident) => (
+                quote!(#ident),
+                ident.to_string().trim_start_matches("r#").to_owned(),
+            ),
+            None => {
+                let index = Index::from(index);
+                let name = index.index.to_string();
+                (quote!(#index), name)
+            }
+        };
+        let name = options.name.unwrap_or(default_name);
+        let visit = quote! {
+            ::rg3d::core::visitor::Visit::visit(&mut self.#member, #name, visitor)
+        };
+        let statement = if options.optional {
+            quote!(let _ = #visit;)
+        } else {
+            quote!(#visit?;)
+        };
+        statements.push(match options.since {
+            Some(since) => quote! {
+                if version >= #since {
+                    #statement
+                }
+            },
+            None => statement,
+        });
+    }
+
+    let version = current_version.map(|current_version| {
+        quote! {
+            let mut version: u32 = #current_version;
+            if ::rg3d::core::visitor::Visit::visit(&mut version, "Version", visitor).is_err() {
+                version = 0;
+            }
+        }
+    });
+
+    let mut generics = input.generics.clone();
+    for param in generics.params.iter_mut() {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(::rg3d::core::visitor::Visit));
+        }
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let ident = &input.ident;
+
+    Ok(quote! {
+        impl #impl_generics ::rg3d::core::visitor::Visit for #ident #type_generics #where_clause {
+            fn visit(
+                &mut self,
+                name: &str,
+                visitor: &mut ::rg3d::core::visitor::Visitor,
+            ) -> ::rg3d::core::visitor::VisitResult {
+                visitor.enter_region(name)?;
+                #version
+                #(#statements)*
+                visitor.leave_region()
+            }
+        }
+    })
+}
+
+/// Implements `Visit` trait for a struct, see crate docs.
+#[proc_macro_derive(Visit, attributes(visit))]
+pub fn derive_visit(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match impl_visit(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
@@ -84,18 +84,53 @@
 //! You can use multiple machines to animation single model - for example one machine can be for
 //! locomotion and other is for combat. This means that locomotion machine will take control over
 //! lower body and combat machine will control upper body.
+//!
+//! # Blend spaces
+//!
+//! Blend space is a pose node which blends a set of poses placed on an axis using a Weight
+//! parameter as a position on the axis. For example walk, jog and run animations placed at
+//! 1.0, 3.0 and 6.0 with speed of character as the parameter will give smooth change of gait
+//! when the character accelerates. See `BlendSpace` docs.
+//!
+//! # Conditions
+//!
+//! Besides Rule parameter, transition can have a list of conditions (see `Condition`) and it is
+//! activated only when every condition is met. Conditions allow to describe transitions in data
+//! directly from runtime parameters, for example "Speed is greater than 0.1", instead of
+//! computing separate Rule for every transition in game code.
+//!
+//! # Assets
+//!
+//! Machine can be saved to a file using `Machine::save` and loaded using `Machine::load`, so it
+//! can be made once and used for every character with the same set of animations. Play
+//! animation nodes of such machine should reference animation resources by path (see
+//! `PlayAnimation::from_resource`), every such resource is requested from resource manager and
+//! retargeted to given character when machine is loaded. Saved file contains single machine in
+//! `Machine` region of native binary format of the engine. Common extension is `absm`.
+//!
+//! # Automatic update
+//!
+//! Machine can be updated manually using `Machine::evaluate_pose` and applying its pose to a
+//! graph, or it can be added to `Scene::animation_machines`. In the latter case scene evaluates
+//! pose of the machine and applies it to its graph on every update, so game code only has to
+//! set parameters. Root motion of animations is blended together with poses, so blended motion
+//! of a character can be taken from `Machine::pose` (see `AnimationPose::root_motion`).
 
 use crate::{
     animation::{Animation, AnimationContainer, AnimationPose},
     core::{
-        pool::{Handle, Pool, PoolIterator},
+        pool::{Handle, Pool, PoolIterator, PoolIteratorMut, PoolPairIterator},
         visitor::{Visit, VisitError, VisitResult, Visitor},
     },
-    utils::log::Log,
+    engine::resource_manager::ResourceManager,
+    scene::{graph::Graph, node::Node, Scene},
+    utils::log::{Log, LogCategory},
 };
 use std::{
     cell::{Ref, RefCell},
     collections::{HashMap, VecDeque},
+    fmt::{Debug, Formatter},
+    path::{Path, PathBuf},
 };
 
 /// Specific machine event.
@@ -114,6 +149,9 @@ pub enum Event {
 #[derive(Default)]
 pub struct PlayAnimation {
     pub animation: Handle<Animation>,
+    /// Path to animation resource, animation will be taken from it when machine is loaded
+    /// by `Machine::load`.
+    pub resource: Option<PathBuf>,
     output_pose: RefCell<AnimationPose>,
 }
 
@@ -122,6 +160,17 @@ impl PlayAnimation {
     pub fn new(animation: Handle<Animation>) -> Self {
         Self {
             animation,
+            resource: None,
+            output_pose: Default::default(),
+        }
+    }
+
+    /// Creates new PlayAnimation node which takes animation from resource with given
+    /// path. Such node is bound to an animation when machine is loaded, see module docs.
+    pub fn from_resource<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            animation: Handle::NONE,
+            resource: Some(path.as_ref().to_owned()),
             output_pose: Default::default(),
         }
     }
@@ -132,6 +181,7 @@ impl Visit for PlayAnimation {
         visitor.enter_region(name)?;
 
         self.animation.visit("Animation", visitor)?;
+        let _ = self.resource.visit("Resource", visitor);
 
         visitor.leave_region()
     }
@@ -324,6 +374,110 @@ impl Visit for BlendAnimation {
     }
 }
 
+/// Pose placed at some position on axis of blend space.
+#[derive(Default)]
+pub struct BlendSpacePoint {
+    position: f32,
+    pose_source: Handle<PoseNode>,
+}
+
+impl BlendSpacePoint {
+    /// Creates new point of blend space at given position.
+    pub fn new(position: f32, pose_source: Handle<PoseNode>) -> Self {
+        Self {
+            position,
+            pose_source,
+        }
+    }
+
+    /// Returns position of point on axis of blend space.
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+
+    /// Returns handle of pose node of point.
+    pub fn pose_source(&self) -> Handle<PoseNode> {
+        self.pose_source
+    }
+}
+
+impl Visit for BlendSpacePoint {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.position.visit("Position", visitor)?;
+        self.pose_source.visit("PoseSource", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// One-dimensional blend space. It takes value of Weight parameter as position on its axis
+/// and blends poses of two points which surround the position, weights of the poses depend
+/// on distance to the points. Position is clamped to range of points, so pose of first or
+/// last point is used when parameter is out of range.
+#[derive(Default)]
+pub struct BlendSpace {
+    parameter: String,
+    points: Vec<BlendSpacePoint>,
+    output_pose: RefCell<AnimationPose>,
+}
+
+impl BlendSpace {
+    /// Creates new blend space driven by Weight parameter with given name. Points can be
+    /// passed in any order.
+    pub fn new(parameter: &str, mut points: Vec<BlendSpacePoint>) -> Self {
+        points.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
+        Self {
+            parameter: parameter.to_owned(),
+            points,
+            output_pose: Default::default(),
+        }
+    }
+
+    /// Returns name of Weight parameter which is used as position on axis.
+    pub fn parameter(&self) -> &str {
+        &self.parameter
+    }
+
+    /// Returns points of blend space sorted by their positions.
+    pub fn points(&self) -> &[BlendSpacePoint] {
+        &self.points
+    }
+
+    // Returns indices of points surrounding given position and weight of second one.
+    fn surrounding_points(&self, position: f32) -> Option<(usize, usize, f32)> {
+        let last = self.points.len().checked_sub(1)?;
+        if position <= self.points[0].position {
+            return Some((0, 0, 0.0));
+        }
+        for i in 0..last {
+            let (left, right) = (&self.points[i], &self.points[i + 1]);
+            if position <= right.position {
+                let range = right.position - left.position;
+                let t = if range > std::f32::EPSILON {
+                    (position - left.position) / range
+                } else {
+                    1.0
+                };
+                return Some((i, i + 1, t));
+            }
+        }
+        Some((last, last, 0.0))
+    }
+}
+
+impl Visit for BlendSpace {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.parameter.visit("Parameter", visitor)?;
+        self.points.visit("Points", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
 /// Specialized node that provides animation pose. See documentation for each variant.
 pub enum PoseNode {
     /// See docs for `PlayAnimation`.
@@ -331,6 +485,9 @@ pub enum PoseNode {
 
     /// See docs for `BlendAnimation`.
     BlendAnimations(BlendAnimation),
+
+    /// See docs for `BlendSpace`.
+    BlendSpace(BlendSpace),
 }
 
 impl Default for PoseNode {
@@ -350,10 +507,16 @@ impl PoseNode {
         Self::BlendAnimations(BlendAnimation::new(poses))
     }
 
+    /// Creates new node that blends poses placed on axis of blend space.
+    pub fn make_blend_space(parameter: &str, points: Vec<BlendSpacePoint>) -> Self {
+        Self::BlendSpace(BlendSpace::new(parameter, points))
+    }
+
     fn from_id(id: i32) -> Result<Self, String> {
         match id {
             0 => Ok(Self::PlayAnimation(Default::default())),
             1 => Ok(Self::BlendAnimations(Default::default())),
+            2 => Ok(Self::BlendSpace(Default::default())),
             _ => Err(format!("Invalid pose node id {}", id)),
         }
     }
@@ -362,6 +525,7 @@ impl PoseNode {
         match self {
             Self::PlayAnimation(_) => 0,
             Self::BlendAnimations(_) => 1,
+            Self::BlendSpace(_) => 2,
         }
     }
 }
@@ -371,6 +535,7 @@ macro_rules! static_dispatch {
         match $self {
             PoseNode::PlayAnimation(v) => v.$func($($args),*),
             PoseNode::BlendAnimations(v) => v.$func($($args),*),
+            PoseNode::BlendSpace(v) => v.$func($($args),*),
         }
     };
 }
@@ -395,7 +560,8 @@ pub struct State {
     pose: AnimationPose,
 }
 
-type ParameterContainer = HashMap<String, Parameter>;
+/// Named parameters of machine.
+pub type ParameterContainer = HashMap<String, Parameter>;
 
 trait EvaluatePose {
     fn eval_pose(
@@ -413,10 +579,15 @@ impl EvaluatePose for PlayAnimation {
         _params: &ParameterContainer,
         animations: &AnimationContainer,
     ) -> Ref<AnimationPose> {
-        animations
-            .get(self.animation)
-            .get_pose()
-            .clone_into(&mut self.output_pose.borrow_mut());
+        // Animation could be removed together with its nodes.
+        if animations.pool.is_valid_handle(self.animation) {
+            animations
+                .get(self.animation)
+                .get_pose()
+                .clone_into(&mut self.output_pose.borrow_mut());
+        } else {
+            self.output_pose.borrow_mut().reset();
+        }
         self.output_pose.borrow()
     }
 }
@@ -454,6 +625,33 @@ impl EvaluatePose for BlendAnimation {
     }
 }
 
+impl EvaluatePose for BlendSpace {
+    fn eval_pose(
+        &self,
+        nodes: &Pool<PoseNode>,
+        params: &ParameterContainer,
+        animations: &AnimationContainer,
+    ) -> Ref<AnimationPose> {
+        self.output_pose.borrow_mut().reset();
+        let position = match params.get(&self.parameter) {
+            Some(Parameter::Weight(weight)) => *weight,
+            _ => 0.0,
+        };
+        if let Some((left, right, t)) = self.surrounding_points(position) {
+            for &(index, weight) in [(left, 1.0 - t), (right, t)].iter() {
+                if weight > 0.0 {
+                    let pose_source =
+                        nodes[self.points[index].pose_source].eval_pose(nodes, params, animations);
+                    self.output_pose
+                        .borrow_mut()
+                        .blend_with(&pose_source, weight);
+                }
+            }
+        }
+        self.output_pose.borrow()
+    }
+}
+
 impl EvaluatePose for PoseNode {
     fn eval_pose(
         &self,
@@ -500,6 +698,86 @@ impl Visit for State {
     }
 }
 
+/// Condition of transition, it is checked using parameters of machine. Condition that refers
+/// to missing parameter or parameter of wrong kind is never met.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    /// Rule parameter with given name is true.
+    Rule(String),
+
+    /// Rule parameter with given name is false.
+    NotRule(String),
+
+    /// Weight parameter with given name is greater than given value.
+    Greater(String, f32),
+
+    /// Weight parameter with given name is less than given value.
+    Less(String, f32),
+}
+
+impl Default for Condition {
+    fn default() -> Self {
+        Self::Rule(Default::default())
+    }
+}
+
+impl Condition {
+    fn from_id(id: i32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::Rule(Default::default())),
+            1 => Ok(Self::NotRule(Default::default())),
+            2 => Ok(Self::Greater(Default::default(), 0.0)),
+            3 => Ok(Self::Less(Default::default(), 0.0)),
+            _ => Err(format!("Invalid condition id {}", id)),
+        }
+    }
+
+    fn id(&self) -> i32 {
+        match self {
+            Self::Rule(_) => 0,
+            Self::NotRule(_) => 1,
+            Self::Greater(..) => 2,
+            Self::Less(..) => 3,
+        }
+    }
+
+    /// Checks whether condition is met with given parameters.
+    pub fn is_met(&self, params: &ParameterContainer) -> bool {
+        match self {
+            Self::Rule(id) => matches!(params.get(id), Some(Parameter::Rule(true))),
+            Self::NotRule(id) => matches!(params.get(id), Some(Parameter::Rule(false))),
+            Self::Greater(id, value) => {
+                matches!(params.get(id), Some(Parameter::Weight(weight)) if weight > value)
+            }
+            Self::Less(id, value) => {
+                matches!(params.get(id), Some(Parameter::Weight(weight)) if weight < value)
+            }
+        }
+    }
+}
+
+impl Visit for Condition {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+
+        match self {
+            Self::Rule(param_id) | Self::NotRule(param_id) => param_id.visit("ParamId", visitor)?,
+            Self::Greater(param_id, value) | Self::Less(param_id, value) => {
+                param_id.visit("ParamId", visitor)?;
+                value.visit("Value", visitor)?;
+            }
+        }
+
+        visitor.leave_region()
+    }
+}
+
 /// Transition is a connection between two states with a rule that defines possibility
 /// of actual transition with blending.
 #[derive(Default)]
@@ -512,6 +790,8 @@ pub struct Transition {
     dest: Handle<State>,
     /// Identifier of Rule parameter which defines is transition should be activated or not.
     rule: String,
+    /// Additional conditions which must be met to activate transition.
+    conditions: Vec<Condition>,
     /// 0 - evaluates `src` pose, 1 - `dest`, 0..1 - blends `src` and `dest`
     blend_factor: f32,
 }
@@ -527,6 +807,7 @@ impl Visit for Transition {
         self.dest.visit("Dest", visitor)?;
         self.rule.visit("Rule", visitor)?;
         self.blend_factor.visit("BlendFactor", visitor)?;
+        let _ = self.conditions.visit("Conditions", visitor);
 
         visitor.leave_region()
     }
@@ -547,10 +828,37 @@ impl Transition {
             source: src,
             dest,
             rule: rule.to_owned(),
+            conditions: Default::default(),
             blend_factor: 0.0,
         }
     }
 
+    /// Adds condition which must be met to activate transition. Rule can be empty if
+    /// transition is controlled by conditions only.
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn conditions(&self) -> &[Condition] {
+        &self.conditions
+    }
+
+    /// Returns true if Rule parameter of transition is set (when rule is not empty) and
+    /// every condition is met. Transition without rule and conditions is never activated.
+    pub fn is_satisfied(&self, params: &ParameterContainer) -> bool {
+        if self.rule.is_empty() && self.conditions.is_empty() {
+            return false;
+        }
+        let rule_is_set =
+            self.rule.is_empty() || matches!(params.get(&self.rule), Some(Parameter::Rule(true)));
+        rule_is_set
+            && self
+                .conditions
+                .iter()
+                .all(|condition| condition.is_met(params))
+    }
+
     pub fn name(&self) -> &str {
         self.name.as_str()
     }
@@ -664,6 +972,11 @@ impl Machine {
         self
     }
 
+    /// Returns current value of parameter with given name.
+    pub fn get_parameter(&self, id: &str) -> Option<Parameter> {
+        self.parameters.get(id).copied()
+    }
+
     pub fn set_entry_state(&mut self, entry_state: Handle<State>) {
         self.active_state = entry_state;
         self.entry_state = entry_state;
@@ -718,6 +1031,71 @@ impl Machine {
         &self.transitions
     }
 
+    /// Returns pose evaluated during last update.
+    pub fn pose(&self) -> &AnimationPose {
+        &self.final_pose
+    }
+
+    /// Loads machine asset and binds it to character with given root node. Every animation
+    /// resource used by play animation nodes is requested from resource manager and retargeted
+    /// to the character, so the same asset can be loaded for any amount of characters. This
+    /// method is **blocking**. See module docs.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        root: Handle<Node>,
+        scene: &mut Scene,
+        resource_manager: &mut ResourceManager,
+    ) -> Result<Self, VisitError> {
+        let mut machine = Machine::new();
+        let mut visitor = resource_manager.vfs().load_visitor(path.as_ref())?;
+        machine.visit("Machine", &mut visitor)?;
+
+        // Same resource can be used by multiple nodes, retarget it only once.
+        let mut animations = HashMap::<PathBuf, Handle<Animation>>::new();
+        for node in machine.nodes.iter_mut() {
+            if let PoseNode::PlayAnimation(play_animation) = node {
+                if let Some(resource) = play_animation.resource.as_ref() {
+                    play_animation.animation =
+                        *animations.entry(resource.clone()).or_insert_with(|| {
+                            resource_manager
+                                .request_model(resource)
+                                .and_then(|model| {
+                                    model
+                                        .lock()
+                                        .unwrap()
+                                        .retarget_animations(root, scene)
+                                        .first()
+                                        .copied()
+                                })
+                                .unwrap_or_else(|| {
+                                    Log::err(
+                                        LogCategory::Animation,
+                                        format!(
+                                            "Unable to get animation from {:?} for machine {:?}",
+                                            resource,
+                                            path.as_ref()
+                                        ),
+                                    );
+                                    Handle::NONE
+                                })
+                        });
+                }
+            }
+        }
+
+        machine.active_transition = Handle::NONE;
+        machine.reset();
+
+        Ok(machine)
+    }
+
+    /// Saves machine as asset which can be loaded using `Machine::load`.
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> VisitResult {
+        let mut visitor = Visitor::new();
+        self.visit("Machine", &mut visitor)?;
+        visitor.save_binary(path.as_ref())
+    }
+
     pub fn evaluate_pose(&mut self, animations: &AnimationContainer, dt: f32) -> &AnimationPose {
         self.final_pose.reset();
 
@@ -735,31 +1113,27 @@ impl Machine {
                     {
                         continue;
                     }
-                    if let Some(rule) = self.parameters.get(&transition.rule) {
-                        if let Parameter::Rule(active) = rule {
-                            if *active {
-                                self.events.push(Event::StateLeave(self.active_state));
-                                if self.debug {
-                                    Log::writeln(format!(
-                                        "Leaving state: {}",
-                                        self.states[self.active_state].name
-                                    ));
-                                }
-
-                                self.events.push(Event::StateEnter(transition.source));
-                                if self.debug {
-                                    Log::writeln(format!(
-                                        "Entering state: {}",
-                                        self.states[transition.source].name
-                                    ));
-                                }
-
-                                self.active_state = Handle::NONE;
-                                self.active_transition = handle;
-
-                                break;
-                            }
+                    if transition.is_satisfied(&self.parameters) {
+                        self.events.push(Event::StateLeave(self.active_state));
+                        if self.debug {
+                            Log::debug(
+                                LogCategory::Animation,
+                                format!("Leaving state: {}", self.states[self.active_state].name),
+                            );
+                        }
+
+                        self.events.push(Event::StateEnter(transition.source));
+                        if self.debug {
+                            Log::debug(
+                                LogCategory::Animation,
+                                format!("Entering state: {}", self.states[transition.source].name),
+                            );
                         }
+
+                        self.active_state = Handle::NONE;
+                        self.active_transition = handle;
+
+                        break;
                     }
                 }
             }
@@ -786,10 +1160,13 @@ impl Machine {
                         .push(Event::ActiveStateChanged(self.active_state));
 
                     if self.debug {
-                        Log::writeln(format!(
-                            "Active state changed: {}",
-                            self.states[self.active_state].name
-                        ));
+                        Log::debug(
+                            LogCategory::Animation,
+                            format!(
+                                "Active state changed: {}",
+                                self.states[self.active_state].name
+                            ),
+                        );
                     }
                 }
             } else {
@@ -820,3 +1197,167 @@ impl Visit for Machine {
         visitor.leave_region()
     }
 }
+
+/// Container for machines of a scene. Scene evaluates every machine in the container and
+/// applies its pose to the graph on every update, see module docs.
+#[derive(Default)]
+pub struct MachineContainer {
+    pool: Pool<Machine>,
+}
+
+impl Debug for MachineContainer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MachineContainer: {} machines", self.pool.alive_count())
+    }
+}
+
+impl MachineContainer {
+    /// Creates new empty container.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds new machine to the container.
+    pub fn add(&mut self, machine: Machine) -> Handle<Machine> {
+        self.pool.spawn(machine)
+    }
+
+    /// Removes machine from the container.
+    pub fn remove(&mut self, handle: Handle<Machine>) {
+        self.pool.free(handle);
+    }
+
+    /// Returns true if handle points to existing machine.
+    pub fn is_valid_handle(&self, handle: Handle<Machine>) -> bool {
+        self.pool.is_valid_handle(handle)
+    }
+
+    /// Returns shared reference to machine.
+    pub fn get(&self, handle: Handle<Machine>) -> &Machine {
+        self.pool.borrow(handle)
+    }
+
+    /// Returns mutable reference to machine, it can be used to set parameters.
+    pub fn get_mut(&mut self, handle: Handle<Machine>) -> &mut Machine {
+        self.pool.borrow_mut(handle)
+    }
+
+    /// Returns iterator over machines.
+    pub fn iter(&self) -> PoolIterator<Machine> {
+        self.pool.iter()
+    }
+
+    /// Returns iterator over machines with their handles.
+    pub fn pair_iter(&self) -> PoolPairIterator<Machine> {
+        self.pool.pair_iter()
+    }
+
+    /// Returns mutable iterator over machines.
+    pub fn iter_mut(&mut self) -> PoolIteratorMut<Machine> {
+        self.pool.iter_mut()
+    }
+
+    /// Removes every machine.
+    pub fn clear(&mut self) {
+        self.pool.clear()
+    }
+
+    pub(in crate) fn update(
+        &mut self,
+        animations: &AnimationContainer,
+        graph: &mut Graph,
+        dt: f32,
+    ) {
+        for machine in self.pool.iter_mut() {
+            machine.evaluate_pose(animations, dt).apply(graph);
+        }
+    }
+}
+
+impl Visit for MachineContainer {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.pool.visit("Pool", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        animation::{
+            machine::{
+                BlendSpace, BlendSpacePoint, Condition, Machine, Parameter, PoseNode, State,
+                Transition,
+            },
+            Animation, AnimationContainer,
+        },
+        core::{
+            pool::Handle,
+            visitor::{Visit, Visitor},
+        },
+    };
+
+    #[test]
+    fn blend_space_finds_surrounding_points() {
+        let blend_space = BlendSpace::new(
+            "Speed",
+            vec![
+                BlendSpacePoint::new(6.0, Handle::NONE),
+                BlendSpacePoint::new(1.0, Handle::NONE),
+            ],
+        );
+        assert_eq!(blend_space.surrounding_points(0.0), Some((0, 0, 0.0)));
+        assert_eq!(blend_space.surrounding_points(3.0), Some((0, 1, 0.4)));
+        assert_eq!(blend_space.surrounding_points(10.0), Some((1, 1, 0.0)));
+        assert_eq!(BlendSpace::default().surrounding_points(1.0), None);
+    }
+
+    #[test]
+    fn transition_is_driven_by_conditions() {
+        let mut animations = AnimationContainer::new();
+        let idle_animation = animations.add(Animation::default());
+        let walk_animation = animations.add(Animation::default());
+        let run_animation = animations.add(Animation::default());
+
+        let mut machine = Machine::new();
+        let idle = machine.add_node(PoseNode::make_play_animation(idle_animation));
+        let walk = machine.add_node(PoseNode::make_play_animation(walk_animation));
+        let run = machine.add_node(PoseNode::make_play_animation(run_animation));
+        let locomotion = machine.add_node(PoseNode::make_blend_space(
+            "Speed",
+            vec![BlendSpacePoint::new(1.0, walk), BlendSpacePoint::new(6.0, run)],
+        ));
+        let idle_state = machine.add_state(State::new("Idle", idle));
+        let move_state = machine.add_state(State::new("Move", locomotion));
+        let condition = Condition::Greater("Speed".to_owned(), 0.1);
+        machine.add_transition(
+            Transition::new("Idle->Move", idle_state, move_state, 0.5, "")
+                .with_condition(condition.clone()),
+        );
+
+        machine.set_parameter("Speed", Parameter::Weight(0.0));
+        machine.evaluate_pose(&animations, 0.1);
+        assert_eq!(machine.active_state(), idle_state);
+
+        machine.set_parameter("Speed", Parameter::Weight(3.0));
+        machine.evaluate_pose(&animations, 0.1);
+        assert!(machine.active_transition().is_some());
+        for _ in 0..5 {
+            machine.evaluate_pose(&animations, 0.1);
+        }
+        assert_eq!(machine.active_state(), move_state);
+
+        let path = std::env::temp_dir().join("rg3d-machine-round-trip-test");
+        machine.save(&path).unwrap();
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = Machine::new();
+        loaded.visit("Machine", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(loaded.active_state(), move_state);
+        let transition = loaded.transitions().iter().next().unwrap();
+        assert_eq!(transition.conditions(), &[condition]);
+    }
+}
@@ -1,4 +1,5 @@
 pub mod machine;
+pub mod tween;
 
 use crate::core::pool::Ticket;
 use crate::{
@@ -9,9 +10,10 @@ use crate::{
         },
         visitor::{Visit, VisitResult, Visitor},
     },
+    renderer::surface::SurfaceParameter,
     resource::model::Model,
     scene::{graph::Graph, node::Node},
-    utils::log::Log,
+    utils::log::{Log, LogCategory},
 };
 use std::{
     collections::{HashMap, VecDeque},
@@ -160,6 +162,27 @@ impl Track {
         &self.frames
     }
 
+    // Returns motion of node of the track between two time positions.
+    fn root_motion(&self, from: f32, to: f32, settings: &RootMotionSettings) -> RootMotion {
+        match (self.get_local_pose(from), self.get_local_pose(to)) {
+            (Some(from), Some(to)) => {
+                let mut delta_position = to.position - from.position;
+                if !settings.extract_vertical {
+                    delta_position.y = 0.0;
+                }
+                RootMotion {
+                    delta_position,
+                    delta_rotation: if settings.extract_rotation {
+                        inverse_rotation(&from.rotation) * to.rotation
+                    } else {
+                        Quat::IDENTITY
+                    },
+                }
+            }
+            _ => Default::default(),
+        }
+    }
+
     pub fn get_local_pose(&self, mut time: f32) -> Option<LocalPose> {
         if self.frames.is_empty() {
             return None;
@@ -208,6 +231,260 @@ impl Track {
     }
 }
 
+/// Key frame of a parameter track.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ParameterKeyFrame {
+    pub time: f32,
+    pub value: f32,
+}
+
+impl ParameterKeyFrame {
+    pub fn new(time: f32, value: f32) -> Self {
+        Self { time, value }
+    }
+}
+
+impl Visit for ParameterKeyFrame {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.time.visit("Time", visitor)?;
+        self.value.visit("Value", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Defines which material parameter of which surface is animated by parameter track.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ParameterBinding {
+    /// Handle of mesh node.
+    pub node: Handle<Node>,
+    /// Index of surface of the mesh, `None` means every surface.
+    pub surface: Option<usize>,
+    /// Material parameter of surface.
+    pub parameter: SurfaceParameter,
+}
+
+impl Visit for ParameterBinding {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.node.visit("Node", visitor)?;
+        // Negative index means every surface.
+        let mut surface = self.surface.map(|index| index as i32).unwrap_or(-1);
+        surface.visit("Surface", visitor)?;
+        if visitor.is_reading() {
+            self.surface = if surface < 0 {
+                None
+            } else {
+                Some(surface as usize)
+            };
+        }
+        self.parameter.visit("Parameter", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Parameter track animates scalar material parameter of mesh surfaces, for example
+/// emission strength or dissolve threshold. Values between key frames are linearly
+/// interpolated. Unlike transform tracks, key frames of parameter tracks are stored
+/// in save files, because they are usually created from code.
+#[derive(Clone, Debug)]
+pub struct ParameterTrack {
+    frames: Vec<ParameterKeyFrame>,
+    enabled: bool,
+    max_time: f32,
+    binding: ParameterBinding,
+}
+
+impl Default for ParameterTrack {
+    fn default() -> Self {
+        Self {
+            frames: Vec::new(),
+            enabled: true,
+            max_time: 0.0,
+            binding: Default::default(),
+        }
+    }
+}
+
+impl Visit for ParameterTrack {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.frames.visit("Frames", visitor)?;
+        self.enabled.visit("Enabled", visitor)?;
+        self.max_time.visit("MaxTime", visitor)?;
+        self.binding.visit("Binding", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+impl ParameterTrack {
+    pub fn new(binding: ParameterBinding) -> Self {
+        Self {
+            binding,
+            ..Default::default()
+        }
+    }
+
+    pub fn binding(&self) -> ParameterBinding {
+        self.binding
+    }
+
+    pub fn set_binding(&mut self, binding: ParameterBinding) {
+        self.binding = binding;
+    }
+
+    pub fn add_key_frame(&mut self, key_frame: ParameterKeyFrame) {
+        let index = self
+            .frames
+            .iter()
+            .position(|other| key_frame.time < other.time)
+            .unwrap_or_else(|| self.frames.len());
+        self.frames.insert(index, key_frame);
+        self.max_time = self.max_time.max(key_frame.time);
+    }
+
+    pub fn get_key_frames(&self) -> &[ParameterKeyFrame] {
+        &self.frames
+    }
+
+    pub fn enable(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn get_value(&self, time: f32) -> Option<f32> {
+        let first = self.frames.first()?;
+        if time <= first.time {
+            return Some(first.value);
+        }
+
+        for pair in self.frames.windows(2) {
+            let (left, right) = (&pair[0], &pair[1]);
+            if time <= right.time {
+                let span = right.time - left.time;
+                let t = if span > 0.0 {
+                    (time - left.time) / span
+                } else {
+                    1.0
+                };
+                return Some(left.value + (right.value - left.value) * t);
+            }
+        }
+
+        self.frames.last().map(|k| k.value)
+    }
+}
+
+/// Root motion settings of animation. Root motion is motion of root node (usually hips bone)
+/// of a skeleton. Instead of moving the root node, motion can be extracted from animation and
+/// used to move whole character (for example its rigid body), so feet of the character will
+/// not slide over the ground when speed of movement and speed of animation differ.
+///
+/// ```no_run
+/// use rg3d::{
+///     animation::{Animation, RootMotionSettings},
+///     core::{math::vec3::Vec3, pool::Handle},
+///     scene::node::Node,
+/// };
+///
+/// fn enable_root_motion(animation: &mut Animation, hips: Handle<Node>) {
+///     animation.set_root_motion_settings(Some(RootMotionSettings {
+///         node: hips,
+///         ..Default::default()
+///     }));
+/// }
+///
+/// // Motion is in local space of parent of the root node, it should be transformed to world
+/// // space using global transform of the parent before applying it to character.
+/// fn local_velocity(animation: &Animation, dt: f32) -> Vec3 {
+///     animation
+///         .get_pose()
+///         .root_motion()
+///         .map_or(Vec3::ZERO, |motion| motion.delta_position.scale(1.0 / dt))
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RootMotionSettings {
+    /// Node which motion is extracted.
+    pub node: Handle<Node>,
+    /// Removes extracted motion from pose, so root node stays at its position (and rotation
+    /// if rotation is extracted) from first key frame.
+    pub strip: bool,
+    /// Extracts vertical motion of root node too. It is disabled by default, because vertical
+    /// motion is usually a part of pose (crouching, bobbing while walking).
+    pub extract_vertical: bool,
+    /// Extracts rotation of root node.
+    pub extract_rotation: bool,
+}
+
+impl Default for RootMotionSettings {
+    fn default() -> Self {
+        Self {
+            node: Handle::NONE,
+            strip: true,
+            extract_vertical: false,
+            extract_rotation: true,
+        }
+    }
+}
+
+impl Visit for RootMotionSettings {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.node.visit("Node", visitor)?;
+        self.strip.visit("Strip", visitor)?;
+        self.extract_vertical.visit("ExtractVertical", visitor)?;
+        self.extract_rotation.visit("ExtractRotation", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Motion of root node extracted from animation during last update, see `RootMotionSettings`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RootMotion {
+    /// Change of position in local space of parent of root node.
+    pub delta_position: Vec3,
+    /// Change of rotation relative to previous rotation of root node.
+    pub delta_rotation: Quat,
+}
+
+impl Default for RootMotion {
+    fn default() -> Self {
+        Self {
+            delta_position: Vec3::ZERO,
+            delta_rotation: Quat::IDENTITY,
+        }
+    }
+}
+
+impl RootMotion {
+    fn append(&mut self, other: &RootMotion) {
+        self.delta_position += other.delta_position;
+        self.delta_rotation = self.delta_rotation * other.delta_rotation;
+    }
+
+    fn blend_with(&mut self, other: &RootMotion, weight: f32) {
+        self.delta_position += other.delta_position.scale(weight);
+        self.delta_rotation = self.delta_rotation.nlerp(&other.delta_rotation, weight);
+    }
+}
+
+// Inverse of unit quaternion.
+fn inverse_rotation(rotation: &Quat) -> Quat {
+    Quat::new(-rotation.x, -rotation.y, -rotation.z, rotation.w)
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct AnimationEvent {
     pub signal_id: u64,
@@ -264,6 +541,7 @@ impl Visit for AnimationSignal {
 pub struct Animation {
     // TODO: Extract into separate struct AnimationTimeline
     tracks: Vec<Track>,
+    parameter_tracks: Vec<ParameterTrack>,
     length: f32,
     time_position: f32,
     ///////////////////////////////////////////////////////
@@ -274,6 +552,7 @@ pub struct Animation {
     pose: AnimationPose,
     signals: Vec<AnimationSignal>,
     events: VecDeque<AnimationEvent>,
+    root_motion_settings: Option<RootMotionSettings>,
 }
 
 /// Snapshot of scene node local transform state.
@@ -316,6 +595,8 @@ impl LocalPose {
 #[derive(Default, Debug)]
 pub struct AnimationPose {
     local_poses: HashMap<Handle<Node>, LocalPose>,
+    parameters: HashMap<ParameterBinding, f32>,
+    root_motion: Option<RootMotion>,
 }
 
 impl AnimationPose {
@@ -324,6 +605,8 @@ impl AnimationPose {
         for (handle, local_pose) in self.local_poses.iter() {
             dest.local_poses.insert(*handle, local_pose.clone());
         }
+        dest.parameters.clone_from(&self.parameters);
+        dest.root_motion = self.root_motion;
     }
 
     pub fn blend_with(&mut self, other: &AnimationPose, weight: f32) {
@@ -336,6 +619,14 @@ impl AnimationPose {
                 self.add_local_pose(other_pose.weighted_clone(weight));
             }
         }
+        for (binding, other_value) in other.parameters.iter() {
+            *self.parameters.entry(*binding).or_insert(0.0) += other_value * weight;
+        }
+        if let Some(other_motion) = other.root_motion.as_ref() {
+            self.root_motion
+                .get_or_insert_with(Default::default)
+                .blend_with(other_motion, weight);
+        }
     }
 
     fn add_local_pose(&mut self, local_pose: LocalPose) {
@@ -344,12 +635,29 @@ impl AnimationPose {
 
     pub fn reset(&mut self) {
         self.local_poses.clear();
+        self.parameters.clear();
+        self.root_motion = None;
+    }
+
+    /// Returns root motion extracted from animations of this pose during last update, if
+    /// any of them has root motion settings. See `RootMotionSettings`.
+    pub fn root_motion(&self) -> Option<&RootMotion> {
+        self.root_motion.as_ref()
+    }
+
+    /// Returns value of animated material parameter, if it is animated.
+    pub fn parameter(&self, binding: &ParameterBinding) -> Option<f32> {
+        self.parameters.get(binding).cloned()
     }
 
     pub fn apply(&self, graph: &mut Graph) {
         for (node, local_pose) in self.local_poses.iter() {
             if node.is_none() {
-                Log::writeln("Invalid node handle found for animation pose, most likely it means that animation retargetting failed!".to_owned());
+                Log::warn(
+                    LogCategory::Animation,
+                    "Invalid node handle found for animation pose, most likely it means that \
+                     animation retargetting failed!",
+                );
             } else {
                 graph[*node]
                     .local_transform_mut()
@@ -358,6 +666,25 @@ impl AnimationPose {
                     .set_scale(local_pose.scale);
             }
         }
+        for (binding, value) in self.parameters.iter() {
+            if !graph.is_valid_handle(binding.node) {
+                continue;
+            }
+            if let Node::Mesh(mesh) = &mut graph[binding.node] {
+                match binding.surface {
+                    Some(index) => {
+                        if let Some(surface) = mesh.surfaces_mut().get_mut(index) {
+                            surface.set_parameter(binding.parameter, *value);
+                        }
+                    }
+                    None => {
+                        for surface in mesh.surfaces_mut() {
+                            surface.set_parameter(binding.parameter, *value);
+                        }
+                    }
+                }
+            }
+        }
     }
 }
 
@@ -365,6 +692,7 @@ impl Clone for Animation {
     fn clone(&self) -> Self {
         Self {
             tracks: self.tracks.clone(),
+            parameter_tracks: self.parameter_tracks.clone(),
             speed: self.speed,
             length: self.length,
             time_position: self.time_position,
@@ -374,6 +702,7 @@ impl Clone for Animation {
             pose: Default::default(),
             signals: self.signals.clone(),
             events: Default::default(),
+            root_motion_settings: self.root_motion_settings,
         }
     }
 }
@@ -393,6 +722,21 @@ impl Animation {
         &self.tracks
     }
 
+    /// Adds new track that animates material parameter of mesh surfaces. Length of
+    /// animation will be extended if track is longer.
+    pub fn add_parameter_track(&mut self, track: ParameterTrack) {
+        self.length = self.length.max(track.max_time);
+        self.parameter_tracks.push(track);
+    }
+
+    pub fn get_parameter_tracks(&self) -> &[ParameterTrack] {
+        &self.parameter_tracks
+    }
+
+    pub fn get_parameter_tracks_mut(&mut self) -> &mut [ParameterTrack] {
+        &mut self.parameter_tracks
+    }
+
     pub fn set_time_position(&mut self, time: f32) -> &mut Self {
         if self.looped {
             self.time_position = wrapf(time, 0.0, self.length);
@@ -423,9 +767,33 @@ impl Animation {
             }
         }
 
+        self.pose.root_motion = self.extract_root_motion(current_time_position, new_time_position);
+
         self.set_time_position(new_time_position);
     }
 
+    fn extract_root_motion(&self, from: f32, to: f32) -> Option<RootMotion> {
+        let settings = self.root_motion_settings.as_ref()?;
+        let track = self
+            .tracks
+            .iter()
+            .find(|track| track.get_node() == settings.node)?;
+        if self.looped && self.length > 0.0 && (to > self.length || to < 0.0) {
+            // Animation jumps to its beginning (or to its end if it is played backwards), so
+            // motion consists of motion till the end and motion from the beginning.
+            let (end, beginning) = if to > self.length {
+                (self.length, 0.0)
+            } else {
+                (0.0, self.length)
+            };
+            let mut motion = track.root_motion(from, end, settings);
+            motion.append(&track.root_motion(beginning, wrapf(to, 0.0, self.length), settings));
+            Some(motion)
+        } else {
+            Some(track.root_motion(from, clampf(to, 0.0, self.length), settings))
+        }
+    }
+
     pub fn pop_event(&mut self) -> Option<AnimationEvent> {
         self.events.pop_front()
     }
@@ -480,6 +848,13 @@ impl Animation {
         self.tracks.retain(filter)
     }
 
+    pub fn retain_parameter_tracks<F>(&mut self, filter: F)
+    where
+        F: FnMut(&ParameterTrack) -> bool,
+    {
+        self.parameter_tracks.retain(filter)
+    }
+
     pub fn add_signal(&mut self, signal: AnimationSignal) -> &mut Self {
         self.signals.push(signal);
         self
@@ -580,6 +955,62 @@ impl Animation {
                 }
             }
         }
+        for track in self.parameter_tracks.iter() {
+            if track.is_enabled() {
+                if let Some(value) = track.get_value(self.time_position) {
+                    self.pose.parameters.insert(track.binding, value);
+                }
+            }
+        }
+        self.strip_root_motion();
+    }
+
+    // Moves root node back to its initial position, its motion is applied by user instead.
+    fn strip_root_motion(&mut self) {
+        let settings = match self.root_motion_settings.as_ref() {
+            Some(settings) if settings.strip => settings,
+            _ => return,
+        };
+        let first = self
+            .tracks
+            .iter()
+            .find(|track| track.get_node() == settings.node)
+            .and_then(|track| track.get_key_frames().first());
+        let pose = self.pose.local_poses.get_mut(&settings.node);
+        if let (Some(first), Some(pose)) = (first, pose) {
+            pose.position.x = first.position.x;
+            pose.position.z = first.position.z;
+            if settings.extract_vertical {
+                pose.position.y = first.position.y;
+            }
+            if settings.extract_rotation {
+                pose.rotation = first.rotation;
+            }
+        }
+    }
+
+    /// Sets root motion settings of animation, `None` disables extraction of root motion.
+    /// See `RootMotionSettings` docs.
+    pub fn set_root_motion_settings(&mut self, settings: Option<RootMotionSettings>) {
+        self.root_motion_settings = settings;
+    }
+
+    /// Returns root motion settings of animation.
+    pub fn root_motion_settings(&self) -> Option<&RootMotionSettings> {
+        self.root_motion_settings.as_ref()
+    }
+
+    // Animation is copied along with its nodes, root node must be replaced by its copy.
+    pub(in crate) fn remap_root_motion_node(
+        &mut self,
+        old_new_map: &HashMap<Handle<Node>, Handle<Node>>,
+    ) {
+        if let Some(settings) = self.root_motion_settings.as_mut() {
+            settings.node = old_new_map
+                .get(&settings.node)
+                .copied()
+                .unwrap_or(Handle::NONE);
+        }
     }
 
     pub fn get_pose(&self) -> &AnimationPose {
@@ -591,6 +1022,7 @@ impl Default for Animation {
     fn default() -> Self {
         Self {
             tracks: Vec::new(),
+            parameter_tracks: Vec::new(),
             speed: 1.0,
             length: 0.0,
             time_position: 0.0,
@@ -600,6 +1032,7 @@ impl Default for Animation {
             pose: Default::default(),
             signals: Default::default(),
             events: Default::default(),
+            root_motion_settings: None,
         }
     }
 }
@@ -616,6 +1049,10 @@ impl Visit for Animation {
         self.looped.visit("Looped", visitor)?;
         self.enabled.visit("Enabled", visitor)?;
         self.signals.visit("Signals", visitor)?;
+        let _ = self.parameter_tracks.visit("ParameterTracks", visitor);
+        let _ = self
+            .root_motion_settings
+            .visit("RootMotionSettings", visitor);
 
         visitor.leave_region()
     }
@@ -711,11 +1148,11 @@ impl AnimationContainer {
     }
 
     pub fn resolve(&mut self, graph: &Graph) {
-        Log::writeln("Resolving animations...".to_owned());
+        Log::debug(LogCategory::Animation, "Resolving animations...");
         for animation in self.pool.iter_mut() {
             animation.resolve(graph)
         }
-        Log::writeln("Animations resolved successfully!".to_owned());
+        Log::debug(LogCategory::Animation, "Animations resolved successfully!");
     }
 
     pub fn update_animations(&mut self, dt: f32) {
@@ -723,6 +1160,28 @@ impl AnimationContainer {
             animation.tick(dt);
         }
     }
+
+    /// Updates animations using time scale of animated nodes, see `Base::set_time_scale`.
+    /// Time scale of an animation is taken from node of its first track, animations without
+    /// tracks use unscaled time. Animations of disabled nodes are paused.
+    pub fn update_animations_scaled(&mut self, dt: f32, graph: &Graph) {
+        for animation in self.pool.iter_mut().filter(|anim| anim.enabled) {
+            let time_scale = animation
+                .tracks
+                .first()
+                .map(|track| track.get_node())
+                .filter(|&node| graph.is_valid_handle(node))
+                .map_or(1.0, |node| {
+                    let node = &graph[node];
+                    if node.is_globally_enabled() {
+                        node.global_time_scale()
+                    } else {
+                        0.0
+                    }
+                });
+            animation.tick(dt * time_scale);
+        }
+    }
 }
 
 impl Visit for AnimationContainer {
@@ -738,3 +1197,84 @@ impl Visit for AnimationContainer {
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        animation::{
+            Animation, KeyFrame, ParameterBinding, ParameterKeyFrame, ParameterTrack,
+            RootMotionSettings, Track,
+        },
+        core::{
+            math::{mat4::Mat4, quat::Quat, vec3::Vec3},
+            pool::Handle,
+        },
+        renderer::surface::{Surface, SurfaceParameter, SurfaceSharedData},
+        scene::{base::BaseBuilder, graph::Graph, mesh::MeshBuilder, node::Node},
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn parameter_track_drives_surface_parameter_test() {
+        let mut graph = Graph::new();
+        let mesh = graph.add_node(
+            MeshBuilder::new(BaseBuilder::new())
+                .with_surfaces(vec![Surface::new(Arc::new(Mutex::new(
+                    SurfaceSharedData::make_cube(Mat4::IDENTITY),
+                )))])
+                .build_node(),
+        );
+
+        let mut track = ParameterTrack::new(ParameterBinding {
+            node: mesh,
+            surface: Some(0),
+            parameter: SurfaceParameter::DissolveThreshold,
+        });
+        track.add_key_frame(ParameterKeyFrame::new(0.0, 0.0));
+        track.add_key_frame(ParameterKeyFrame::new(1.0, 0.5));
+
+        let mut animation = Animation::default();
+        animation.add_parameter_track(track);
+        animation.set_time_position(0.5);
+        animation.tick(0.0);
+        animation.get_pose().apply(&mut graph);
+
+        if let Node::Mesh(mesh) = &graph[mesh] {
+            let value = mesh.surfaces()[0].parameter(SurfaceParameter::DissolveThreshold);
+            assert_eq!(value, 0.25);
+        } else {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn root_motion_is_extracted_and_stripped() {
+        let root = Handle::new(1, 1);
+        let mut track = Track::new();
+        track.set_node(root);
+        track.add_key_frame(KeyFrame::new(0.0, Vec3::ZERO, Vec3::UNIT, Quat::IDENTITY));
+        track.add_key_frame(KeyFrame::new(
+            1.0,
+            Vec3::new(0.0, 1.0, 2.0),
+            Vec3::UNIT,
+            Quat::IDENTITY,
+        ));
+        let mut animation = Animation::default();
+        animation.add_track(track);
+        animation.set_root_motion_settings(Some(RootMotionSettings {
+            node: root,
+            ..Default::default()
+        }));
+
+        animation.tick(0.5);
+        let motion = *animation.get_pose().root_motion().unwrap();
+        assert_eq!(motion.delta_position, Vec3::new(0.0, 0.0, 1.0));
+
+        // Animation wraps to its beginning, motion from both sides of the loop is taken.
+        animation.tick(0.75);
+        let motion = *animation.get_pose().root_motion().unwrap();
+        assert_eq!(motion.delta_position, Vec3::new(0.0, 0.0, 1.5));
+        let pose = &animation.get_pose().local_poses[&root];
+        assert_eq!(pose.position, Vec3::new(0.0, 0.5, 0.0));
+    }
+}
@@ -0,0 +1,205 @@
+//! Contains helpers for smooth changes of material parameters from gameplay code.
+//!
+//! Tween is a coroutine which changes material parameter of mesh surfaces from one value
+//! to another over given time using easing function. It is owned by the mesh node, so it
+//! is cancelled automatically when mesh is removed.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     animation::tween::{Easing, SurfaceParameterTween},
+//!     core::pool::Handle,
+//!     renderer::surface::SurfaceParameter,
+//!     scene::{node::Node, Scene},
+//! };
+//!
+//! fn on_shield_hit(scene: &mut Scene, shield: Handle<Node>) {
+//!     // Flash shield and fade it back in a quarter of a second.
+//!     let emission = SurfaceParameter::EmissionStrength;
+//!     let tween = SurfaceParameterTween::new(emission, 0.0, 3.0, 0.125)
+//!         .with_easing(Easing::EaseOut)
+//!         .with_ping_pong(true);
+//!     scene.coroutines.start(shield, tween);
+//! }
+//! ```
+
+use crate::{
+    renderer::surface::SurfaceParameter,
+    scene::{
+        coroutine::{Coroutine, CoroutineContext, CoroutineState},
+        node::Node,
+    },
+};
+
+/// Easing function defines how interpolated value changes over time.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing {
+    /// Constant speed.
+    Linear,
+    /// Slow start and slow end.
+    SmoothStep,
+    /// Slow start, fast end.
+    EaseIn,
+    /// Fast start, slow end.
+    EaseOut,
+}
+
+impl Default for Easing {
+    fn default() -> Self {
+        Easing::Linear
+    }
+}
+
+impl Easing {
+    /// Maps linear time `t` in [0; 1] range into eased time in same range.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::SmoothStep => t * t * (3.0 - 2.0 * t),
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+        }
+    }
+}
+
+/// Interpolates between `from` and `to` using given easing function.
+pub fn ease(from: f32, to: f32, t: f32, easing: Easing) -> f32 {
+    from + (to - from) * easing.apply(t)
+}
+
+/// Changes material parameter of surfaces of owner mesh over time. See module docs.
+#[derive(Clone, Debug)]
+pub struct SurfaceParameterTween {
+    parameter: SurfaceParameter,
+    surface: Option<usize>,
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+    easing: Easing,
+    ping_pong: bool,
+}
+
+impl SurfaceParameterTween {
+    /// Creates new tween which changes parameter of every surface of mesh from `from` to
+    /// `to` in `duration` seconds.
+    pub fn new(parameter: SurfaceParameter, from: f32, to: f32, duration: f32) -> Self {
+        Self {
+            parameter,
+            surface: None,
+            from,
+            to,
+            duration: duration.max(0.0),
+            elapsed: 0.0,
+            easing: Easing::Linear,
+            ping_pong: false,
+        }
+    }
+
+    /// Limits tween to single surface with given index.
+    pub fn with_surface(mut self, index: usize) -> Self {
+        self.surface = Some(index);
+        self
+    }
+
+    /// Sets easing function.
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+
+    /// If set, parameter will return back to `from` value after reaching `to` value,
+    /// so total duration of tween will be doubled.
+    pub fn with_ping_pong(mut self, ping_pong: bool) -> Self {
+        self.ping_pong = ping_pong;
+        self
+    }
+
+    fn value(&self) -> f32 {
+        let t = if self.duration > 0.0 {
+            self.elapsed / self.duration
+        } else {
+            1.0
+        };
+        if self.ping_pong && t > 1.0 {
+            ease(self.to, self.from, t - 1.0, self.easing)
+        } else {
+            ease(self.from, self.to, t, self.easing)
+        }
+    }
+
+    fn total_duration(&self) -> f32 {
+        if self.ping_pong {
+            self.duration * 2.0
+        } else {
+            self.duration
+        }
+    }
+}
+
+impl Coroutine for SurfaceParameterTween {
+    fn resume(&mut self, context: &mut CoroutineContext) -> CoroutineState {
+        self.elapsed = (self.elapsed + context.dt).min(self.total_duration());
+        let value = self.value();
+
+        if let Node::Mesh(mesh) = &mut context.graph[context.owner] {
+            match self.surface {
+                Some(index) => {
+                    if let Some(surface) = mesh.surfaces_mut().get_mut(index) {
+                        surface.set_parameter(self.parameter, value);
+                    }
+                }
+                None => {
+                    for surface in mesh.surfaces_mut() {
+                        surface.set_parameter(self.parameter, value);
+                    }
+                }
+            }
+        } else {
+            return CoroutineState::Finished;
+        }
+
+        if self.elapsed >= self.total_duration() {
+            CoroutineState::Finished
+        } else {
+            CoroutineState::Running
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        animation::tween::{ease, Easing, SurfaceParameterTween},
+        renderer::surface::SurfaceParameter,
+    };
+
+    #[test]
+    fn easing_test() {
+        for &(easing, middle) in &[
+            (Easing::Linear, 0.5),
+            (Easing::SmoothStep, 0.5),
+            (Easing::EaseIn, 0.25),
+            (Easing::EaseOut, 0.75),
+        ] {
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(0.5), middle);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+        }
+        assert_eq!(ease(2.0, 4.0, 0.5, Easing::EaseIn), 2.5);
+    }
+
+    #[test]
+    fn tween_ping_pong_test() {
+        let mut tween =
+            SurfaceParameterTween::new(SurfaceParameter::EmissionStrength, 0.0, 2.0, 1.0)
+                .with_ping_pong(true);
+        for &(elapsed, value) in &[(0.5, 1.0), (1.0, 2.0), (1.5, 1.0), (2.0, 0.0)] {
+            tween.elapsed = elapsed;
+            assert_eq!(tween.value(), value);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Contains support of resource types defined outside of the engine.
+//!
+//! Games often have their own kinds of data (dialogues, navigation data, particle presets,
+//! etc.) which should be loaded and shared the same way as built-in resources. Such types
+//! can be registered in resource manager with a loader function and a list of file
+//! extensions, after that they're requested using `ResourceManager::request_resource` or
+//! `ResourceManager::load_resource_async` and get the same treatment as textures: files are
+//! read using virtual file system, resources are cached by path and unloaded when they're
+//! not used anymore, loading progress is tracked and hot reload replaces their contents
+//! in-place.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::engine::resource_manager::ResourceManager;
+//!
+//! struct Dialogue {
+//!     lines: Vec<String>,
+//! }
+//!
+//! fn register(resource_manager: &mut ResourceManager) {
+//!     resource_manager.register_resource_type(&["dlg"], |_path, data| {
+//!         let text = String::from_utf8(data.to_vec()).map_err(|e| e.to_string())?;
+//!         Ok(Dialogue {
+//!             lines: text.lines().map(|line| line.to_owned()).collect(),
+//!         })
+//!     });
+//! }
+//!
+//! fn first_line(resource_manager: &mut ResourceManager) -> Option<String> {
+//!     let dialogue = resource_manager.request_resource::<Dialogue, _>("data/intro.dlg")?;
+//!     let dialogue = dialogue.lock().unwrap();
+//!     dialogue.lines.first().cloned()
+//! }
+//! ```
+
+use crate::engine::resource_manager::{ResourceManager, TimedEntry};
+use std::{
+    any::Any,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Type alias for Arc<Mutex<T>> to make code less noisy.
+pub type SharedResource<T> = Arc<Mutex<T>>;
+
+/// Shared resource of any registered type, it is `SharedResource<T>` which can be obtained
+/// using `downcast::<Mutex<T>>`.
+pub type AnyResource = Arc<dyn Any + Send + Sync>;
+
+pub(in crate) type Loader =
+    dyn Fn(&Path, &[u8]) -> Result<Box<dyn Any + Send>, String> + Send + Sync;
+
+/// Loaded resource of custom type.
+pub(in crate) struct CustomResource {
+    pub path: PathBuf,
+    pub value: AnyResource,
+}
+
+/// Converts resource to its type. Resources are stored per type, so conversion can't fail.
+pub(in crate) fn downcast<T: Send + 'static>(resource: AnyResource) -> SharedResource<T> {
+    resource
+        .downcast()
+        .expect("Resource is stored in cache of other type!")
+}
+
+fn wrap<T: Send + 'static>(resource: Box<dyn Any + Send>) -> AnyResource {
+    let resource = *resource.downcast::<T>().unwrap();
+    Arc::new(Mutex::new(resource))
+}
+
+fn replace<T: Send + 'static>(shared: &AnyResource, resource: Box<dyn Any + Send>) {
+    let shared = shared.clone().downcast::<Mutex<T>>().unwrap();
+    *shared.lock().unwrap() = *resource.downcast::<T>().unwrap();
+}
+
+/// Registered custom resource type with cache of its resources.
+pub(in crate) struct ResourceType {
+    pub name: &'static str,
+    pub extensions: Vec<String>,
+    pub loader: Arc<Loader>,
+    pub resources: Vec<TimedEntry<CustomResource>>,
+    wrap: fn(Box<dyn Any + Send>) -> AnyResource,
+    replace: fn(&AnyResource, Box<dyn Any + Send>),
+}
+
+impl ResourceType {
+    pub fn new<T, F>(extensions: &[&str], loader: F) -> Self
+    where
+        T: Send + 'static,
+        F: Fn(&Path, &[u8]) -> Result<T, String> + Send + Sync + 'static,
+    {
+        Self {
+            name: std::any::type_name::<T>(),
+            extensions: extensions
+                .iter()
+                .map(|extension| extension.to_lowercase())
+                .collect(),
+            loader: Arc::new(move |path, data| {
+                loader(path, data).map(|resource| Box::new(resource) as Box<dyn Any + Send>)
+            }),
+            resources: Default::default(),
+            wrap: wrap::<T>,
+            replace: replace::<T>,
+        }
+    }
+
+    pub fn supports(&self, path: &Path) -> bool {
+        path.extension().map_or(false, |extension| {
+            let extension = extension.to_string_lossy().to_lowercase();
+            self.extensions.iter().any(|e| *e == extension)
+        })
+    }
+
+    pub fn find(&self, path: &Path) -> Option<AnyResource> {
+        self.resources
+            .iter()
+            .find(|resource| resource.path == path)
+            .map(|resource| resource.value.clone())
+    }
+
+    /// Registers loaded resource, existing instance is returned if the resource was loaded by
+    /// someone else in the meantime.
+    pub fn register(&mut self, path: &Path, resource: Box<dyn Any + Send>) -> AnyResource {
+        if let Some(existing) = self.find(path) {
+            return existing;
+        }
+        let value = (self.wrap)(resource);
+        self.resources.push(TimedEntry {
+            value: CustomResource {
+                path: path.to_owned(),
+                value: value.clone(),
+            },
+            time_to_live: ResourceManager::MAX_RESOURCE_TTL,
+        });
+        value
+    }
+
+    /// Replaces contents of shared resource with new resource of the same type.
+    pub fn replace(&self, shared: &AnyResource, resource: Box<dyn Any + Send>) {
+        (self.replace)(shared, resource)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::custom_resource::ResourceType;
+    use std::{
+        path::Path,
+        sync::{Arc, Mutex},
+    };
+
+    #[test]
+    fn register_and_replace() {
+        let mut resource_type = ResourceType::new(&["TXT"], |_, data| {
+            String::from_utf8(data.to_vec()).map_err(|e| e.to_string())
+        });
+        let path = Path::new("data/a.txt");
+        assert!(resource_type.supports(path));
+        assert!(!resource_type.supports(Path::new("data/a.png")));
+
+        let resource = (resource_type.loader)(path, b"first").unwrap();
+        let shared = resource_type.register(path, resource);
+        let typed = shared.clone().downcast::<Mutex<String>>().unwrap();
+        assert_eq!(*typed.lock().unwrap(), "first");
+
+        let resource = (resource_type.loader)(path, b"second").unwrap();
+        resource_type.replace(&shared, resource);
+        let found = resource_type.find(path).unwrap().downcast::<Mutex<String>>().unwrap();
+        assert!(Arc::ptr_eq(&found, &typed));
+        assert_eq!(*typed.lock().unwrap(), "second");
+    }
+}
@@ -0,0 +1,174 @@
+//! Contains dependency graph queries of resources.
+//!
+//! Resources reference other resources: surfaces of a model use textures, a prefab contains
+//! instances of models, and a scene uses models, prefabs, textures, gradients and cube maps.
+//! `Scene::resource_dependencies` returns every resource that is used by a scene,
+//! `ResourceManager::dependencies` returns resources that are directly used by a loaded
+//! model or prefab, and `ResourceManager::dependents` returns loaded models and prefabs that
+//! directly use given resource. This is enough for tools to find usages of a resource, to
+//! check that a file can be deleted or unloaded without breaking other resources, and to
+//! collect files which must be packed together with a level.
+//!
+//! Dependencies are not stored anywhere, they're collected from contents of resources on
+//! each query, so they're always up to date, for example after hot reload or when a texture
+//! of a surface was replaced. Resources without a path (render targets and textures that
+//! were created in memory) are not included. Sound buffers are not referenced by scenes, so
+//! they're not part of dependency graph.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::engine::{dependencies::ResourceId, resource_manager::ResourceManager};
+//! use std::path::Path;
+//!
+//! fn print_usages(resource_manager: &ResourceManager, texture: &Path) {
+//!     let id = ResourceId::Texture(texture.to_owned());
+//!     for dependent in resource_manager.dependents(&id) {
+//!         println!("{:?} is used by {:?}", texture, dependent);
+//!     }
+//! }
+//! ```
+
+use crate::{
+    renderer::surface::Surface,
+    resource::{cube_map::CubeMapSource, texture::Texture},
+    scene::{environment::SceneEnvironment, graph::Graph, node::Node},
+};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+/// Identifier of a resource in dependency graph, see module docs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResourceId {
+    /// Texture with given path.
+    Texture(PathBuf),
+    /// Model with given path.
+    Model(PathBuf),
+    /// Prefab with given path.
+    Prefab(PathBuf),
+    /// Gradient with given path.
+    Gradient(PathBuf),
+    /// Cube map loaded from given source.
+    CubeMap(CubeMapSource),
+}
+
+/// Collects unique identifiers of resources.
+#[derive(Default)]
+pub(in crate) struct DependencyCollector {
+    ids: Vec<ResourceId>,
+}
+
+impl DependencyCollector {
+    fn add(&mut self, id: ResourceId) {
+        if !self.ids.contains(&id) {
+            self.ids.push(id);
+        }
+    }
+
+    fn add_path(&mut self, path: PathBuf, id: fn(PathBuf) -> ResourceId) {
+        if !path.as_os_str().is_empty() {
+            self.add(id(path));
+        }
+    }
+
+    pub(in crate) fn add_texture(&mut self, texture: Option<Arc<Mutex<Texture>>>) {
+        if let Some(texture) = texture {
+            let path = texture.lock().unwrap().path.clone();
+            self.add_path(path, ResourceId::Texture);
+        }
+    }
+
+    fn add_surfaces(&mut self, surfaces: &[Surface]) {
+        for surface in surfaces {
+            self.add_texture(surface.diffuse_texture());
+            self.add_texture(surface.normal_texture());
+            self.add_texture(surface.lightmap_texture());
+            if let Some(material) = surface.material() {
+                let state = material.lock().unwrap().state();
+                self.add_texture(state.diffuse_texture);
+                self.add_texture(state.normal_texture);
+            }
+        }
+    }
+
+    pub(in crate) fn add_graph(&mut self, graph: &Graph) {
+        for node in graph.linear_iter() {
+            if let Some(model) = node.resource() {
+                let path = model.lock().unwrap().path.clone();
+                self.add_path(path, ResourceId::Model);
+            }
+            if let Some(prefab) = node.prefab() {
+                let path = prefab.lock().unwrap().path.clone();
+                self.add_path(path, ResourceId::Prefab);
+            }
+            match node {
+                Node::Mesh(mesh) => self.add_surfaces(mesh.surfaces()),
+                Node::Scatter(scatter) => self.add_surfaces(scatter.surfaces()),
+                Node::Sprite(sprite) => self.add_texture(sprite.texture()),
+                Node::ParticleSystem(particle_system) => {
+                    self.add_texture(particle_system.texture());
+                    if let Some(gradient) = particle_system.color_over_lifetime_resource() {
+                        let path = gradient.lock().unwrap().path.clone();
+                        self.add_path(path, ResourceId::Gradient);
+                    }
+                }
+                Node::Terrain(terrain) => {
+                    for layer in terrain.texture_layers() {
+                        self.add_texture(layer.diffuse_texture.clone());
+                        self.add_texture(layer.normal_texture.clone());
+                    }
+                }
+                Node::Water(water) => self.add_texture(water.normal_map()),
+                _ => (),
+            }
+        }
+    }
+
+    pub(in crate) fn add_environment(&mut self, environment: &SceneEnvironment) {
+        self.add_texture(environment.skybox.clone());
+        if let Some(cube_map) = environment.sky_cube_map.as_ref() {
+            let source = cube_map.lock().unwrap().source().clone();
+            self.add(ResourceId::CubeMap(source));
+        }
+    }
+
+    pub(in crate) fn into_ids(self) -> Vec<ResourceId> {
+        self.ids
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        engine::dependencies::ResourceId,
+        resource::texture::Texture,
+        scene::{base::BaseBuilder, sprite::SpriteBuilder, Scene},
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn scene_dependencies_are_unique() {
+        let texture = Arc::new(Mutex::new(Texture {
+            path: "grass.png".into(),
+            ..Default::default()
+        }));
+        let in_memory = Arc::new(Mutex::new(Texture::default()));
+
+        let mut scene = Scene::new();
+        for texture in &[texture.clone(), texture.clone(), in_memory] {
+            scene.graph.add_node(
+                SpriteBuilder::new(BaseBuilder::new())
+                    .with_texture(texture.clone())
+                    .build_node(),
+            );
+        }
+        scene.environment.skybox = Some(texture);
+
+        assert_eq!(
+            scene.resource_dependencies(),
+            vec![ResourceId::Texture("grass.png".into())]
+        );
+    }
+}
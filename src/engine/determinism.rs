@@ -0,0 +1,200 @@
+//! Contains deterministic simulation mode.
+//!
+//! In deterministic mode two runs of a game which get the same inputs at the same simulation
+//! steps produce identical state of scenes, which is required for replays and lockstep
+//! networking. Mode is enabled by `Engine::set_deterministic` and it guarantees that:
+//!
+//! - scenes are updated with fixed time step (see `timestep` module docs), so state depends
+//!   on amount of steps instead of frame times. Inputs must be applied in fixed update closure
+//!   of `Engine::update_with` and recorded with index of the step to be replayed correctly.
+//! - engine-wide random number generator is seeded (see `utils::random`), so particles are
+//!   emitted the same way in every run.
+//! - nodes of graphs and particles of particle systems are updated serially in order of their
+//!   handles instead of being updated in parallel by job system, so random numbers are taken
+//!   in the same order.
+//!
+//! Physics does not need special treatment: 3D physics steps bodies in order of their handles,
+//! 2D physics steps bodies in order of their addition, and nodes bound to bodies are synced
+//! independently of each other, so order of hash map of `PhysicsBinder` does not matter.
+//!
+//! Game code must follow the same rules: use `utils::random` for gameplay randomness, do not
+//! use frame time or wall-clock time in simulation and do not iterate over hash maps when
+//! order affects results. Determinism is guaranteed only for the same build running on the
+//! same platform, floating point results can differ between compilers and CPUs.
+//!
+//! # Global state
+//!
+//! Mode flag and seeded generator are process-global, not per engine: every engine and every
+//! scene of the process is affected by `Engine::set_deterministic`. Running multiple engines
+//! in one process (for example a server and a client) is possible only if all of them use the
+//! same mode, and only one of them may simulate at a time, otherwise they take numbers from
+//! the same sequence in unpredictable order.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Settings of deterministic simulation mode.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DeterminismSettings {
+    /// Seed of engine-wide random number generator.
+    pub seed: u64,
+    /// Amount of simulation steps per second.
+    pub update_rate: f32,
+}
+
+impl Default for DeterminismSettings {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            update_rate: 60.0,
+        }
+    }
+}
+
+/// Returns true if deterministic mode is enabled, engine subsystems use it to avoid
+/// non-deterministic optimizations, like parallel update.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub(in crate) fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Mode is process-global, so tests which change it must not run at the same time.
+#[cfg(test)]
+pub(in crate) fn test_lock() -> std::sync::MutexGuard<'static, ()> {
+    lazy_static! {
+        static ref LOCK: std::sync::Mutex<()> = Default::default();
+    }
+    // Failed test must not fail every other test.
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            math::{quat::Quat, vec2::Vec2, vec3::Vec3},
+            numeric_range::NumericRange,
+            pool::Handle,
+        },
+        engine::determinism,
+        scene::{
+            base::BaseBuilder,
+            node::Node,
+            particle_system::{BaseEmitterBuilder, ParticleSystemBuilder, SphereEmitterBuilder},
+            transform::TransformBuilder,
+            Scene,
+        },
+        utils::random,
+    };
+    use rand::Rng;
+
+    fn random_position() -> Vec3 {
+        let mut rng = random::rng();
+        Vec3::new(
+            rng.gen_range(-10.0, 10.0),
+            rng.gen_range(-10.0, 10.0),
+            rng.gen_range(-10.0, 10.0),
+        )
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Outcome {
+        /// Positions of every particle.
+        particles: Vec<Vec3>,
+        /// Nodes found by spatial hash at every step.
+        found: Vec<Vec<Handle<Node>>>,
+        /// Bits of global transforms of every node, floats must be exactly the same.
+        transforms: Vec<u32>,
+    }
+
+    fn simulate(steps: usize) -> Outcome {
+        random::set_seed(Some(123));
+
+        let mut scene = Scene::new();
+        let mut nodes = Vec::new();
+        for _ in 0..4 {
+            let particle_system = ParticleSystemBuilder::new(
+                BaseBuilder::new().with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(random_position())
+                        .build(),
+                ),
+            )
+            .with_emitters(vec![SphereEmitterBuilder::new(
+                BaseEmitterBuilder::new()
+                    .with_max_particles(50)
+                    .with_spawn_rate(200)
+                    .with_x_velocity_range(NumericRange::new(-0.1, 0.1))
+                    .with_y_velocity_range(NumericRange::new(-0.1, 0.1))
+                    .with_z_velocity_range(NumericRange::new(-0.1, 0.1)),
+            )
+            .build()])
+            .build_node();
+            nodes.push(scene.graph.add_node(particle_system));
+        }
+        // Chain of nodes, global transform of each node depends on every previous one.
+        let mut parent = Handle::NONE;
+        for _ in 0..20 {
+            let node = scene.graph.add_node(BaseBuilder::new().build_node());
+            if parent.is_some() {
+                scene.graph.link_nodes(node, parent);
+            }
+            nodes.push(node);
+            parent = node;
+        }
+        for &node in nodes.iter() {
+            scene.spatial_hash.register(&scene.graph, node);
+        }
+
+        let mut found = Vec::new();
+        for _ in 0..steps {
+            for &node in nodes.iter() {
+                let angle = random::rng().gen_range(0.0, std::f32::consts::PI);
+                scene.graph[node]
+                    .local_transform_mut()
+                    .set_position(random_position())
+                    .set_rotation(Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), angle));
+            }
+            scene.update(Vec2::new(1.0, 1.0), 1.0 / 60.0);
+
+            let mut result = Vec::new();
+            scene
+                .spatial_hash
+                .query_radius(Vec3::ZERO, 5.0, &mut result);
+            found.push(result);
+        }
+
+        let mut particles = Vec::new();
+        let mut transforms = Vec::new();
+        for &node in nodes.iter() {
+            if let Node::ParticleSystem(particle_system) = &scene.graph[node] {
+                particles.extend(particle_system.particles().iter().map(|p| p.position));
+            }
+            let transform = scene.graph[node].global_transform();
+            transforms.extend(transform.f.iter().map(|v| v.to_bits()));
+        }
+
+        Outcome {
+            particles,
+            found,
+            transforms,
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_simulation() {
+        let _lock = determinism::test_lock();
+        determinism::set_enabled(true);
+        let first = simulate(30);
+        let second = simulate(30);
+        determinism::set_enabled(false);
+        random::set_seed(None);
+
+        assert!(!first.particles.is_empty());
+        assert_eq!(first, second);
+    }
+}
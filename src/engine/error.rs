@@ -14,6 +14,8 @@ pub enum EngineError {
     ContextCreationError(CreationError),
     /// Runtime OpenGL context error.
     ContextError(ContextError),
+    /// OpenGL renderer was requested, but events loop wasn't given, see `EngineBuilder::build`.
+    NoEventLoop,
 }
 
 impl From<SoundError> for EngineError {
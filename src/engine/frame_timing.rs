@@ -0,0 +1,270 @@
+//! Contains per-frame timings of engine stages and frame rate limiter.
+//!
+//! Engine measures how much time every frame spent in its main stages: update of scenes (and
+//! physics in particular), update of user interfaces, rendering and presenting of the frame.
+//! Timings of last finished frame can be obtained using `Engine::frame_timings` and timings of
+//! last frames using `Engine::frame_timing_history`, which is handy to show performance
+//! overlay in a game. For detailed breakdown of a frame use `utils::profiler`.
+//!
+//! Presenting of a frame usually waits for vertical synchronization, so its time shows how
+//! much time was left idle in the frame rather than how much work was done. Vertical
+//! synchronization can be disabled at creation of engine (see `Engine::new_with_vsync`), in
+//! this case frame rate can be limited by `FrameLimiter` (see `Engine::set_frame_rate_limit`)
+//! which keeps frames evenly paced on any display instead of rendering as fast as possible.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::engine::frame_timing::FrameTimings;
+//!
+//! fn format_timings(timings: &FrameTimings) -> String {
+//!     format!(
+//!         "FPS: {:.0}\nUpdate: {:.2} ms\nRender: {:.2} ms",
+//!         timings.frames_per_second(),
+//!         timings.update.as_secs_f64() * 1000.0,
+//!         timings.render.as_secs_f64() * 1000.0
+//!     )
+//! }
+//! ```
+
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time spent by a frame in main stages of engine.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct FrameTimings {
+    /// Total time of `Engine::update`, it includes every stage of update below.
+    pub update: Duration,
+    /// Time of update of scenes, it includes physics.
+    pub scenes: Duration,
+    /// Time of 3D and 2D physics of every scene.
+    pub physics: Duration,
+    /// Amount of fixed steps of simulation done in the frame, always one if fixed time step
+    /// is disabled.
+    pub steps: u32,
+    /// Time of update of main and viewport user interfaces.
+    pub user_interface: Duration,
+    /// CPU time of `Engine::render` without presenting of the frame.
+    pub render: Duration,
+    /// Time of presenting of the frame (swap of buffers), it includes waiting for vertical
+    /// synchronization.
+    pub present: Duration,
+    /// Time spent by frame rate limiter waiting for next frame.
+    pub limiter_wait: Duration,
+    /// Time between ends of previous and this frame.
+    pub frame: Duration,
+}
+
+impl FrameTimings {
+    /// Returns frame rate calculated from duration of the frame.
+    pub fn frames_per_second(&self) -> f32 {
+        let frame = self.frame.as_secs_f32();
+        if frame > 0.0 {
+            1.0 / frame
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns time of the frame without idle time of presenting and limiter, it shows how
+    /// much time the frame would take without any synchronization.
+    pub fn busy(&self) -> Duration {
+        self.update + self.render
+    }
+}
+
+/// Timings of last frames, from oldest to newest.
+#[derive(Clone, Debug)]
+pub struct FrameTimingHistory {
+    frames: VecDeque<FrameTimings>,
+    capacity: usize,
+}
+
+impl Default for FrameTimingHistory {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl FrameTimingHistory {
+    /// Amount of frames kept by default.
+    pub const DEFAULT_CAPACITY: usize = 120;
+
+    /// Creates new history which keeps given amount of last frames.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Adds timings of a frame, oldest frame is discarded if history is full.
+    pub fn push(&mut self, timings: FrameTimings) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(timings);
+    }
+
+    /// Sets amount of kept frames, oldest frames are discarded if there are too many.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.frames.len() > self.capacity {
+            self.frames.pop_front();
+        }
+    }
+
+    /// Returns amount of kept frames.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns iterator over kept frames, from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &FrameTimings> {
+        self.frames.iter()
+    }
+
+    /// Returns timings of last frame.
+    pub fn last(&self) -> Option<&FrameTimings> {
+        self.frames.back()
+    }
+
+    /// Returns average timings of kept frames, amount of steps is rounded down.
+    pub fn average(&self) -> FrameTimings {
+        let count = self.frames.len() as u32;
+        if count == 0 {
+            return Default::default();
+        }
+        let sum =
+            |f: fn(&FrameTimings) -> Duration| self.frames.iter().map(f).sum::<Duration>() / count;
+        FrameTimings {
+            update: sum(|t| t.update),
+            scenes: sum(|t| t.scenes),
+            physics: sum(|t| t.physics),
+            steps: self.frames.iter().map(|t| t.steps).sum::<u32>() / count,
+            user_interface: sum(|t| t.user_interface),
+            render: sum(|t| t.render),
+            present: sum(|t| t.present),
+            limiter_wait: sum(|t| t.limiter_wait),
+            frame: sum(|t| t.frame),
+        }
+    }
+
+    /// Returns longest frame of kept ones, useful to find hitches which are hidden by average.
+    pub fn longest(&self) -> Option<&FrameTimings> {
+        self.frames.iter().max_by_key(|t| t.frame)
+    }
+
+    /// Removes every kept frame.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// Limits frame rate by waiting at the end of every frame, see module docs.
+///
+/// Frames are scheduled on a fixed grid of deadlines, so a frame that ended a bit late is
+/// compensated by next one and average frame rate matches the limit. If a frame is late by
+/// more than a whole frame, the grid is restarted from current time instead of rendering
+/// several frames without waiting.
+#[derive(Clone, Debug)]
+pub struct FrameLimiter {
+    frame_duration: Option<Duration>,
+    deadline: Instant,
+}
+
+impl Default for FrameLimiter {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl FrameLimiter {
+    /// Threads usually sleep a bit longer than asked, so last part of waiting is done by
+    /// yielding.
+    const SPIN_TIME: Duration = Duration::from_millis(1);
+
+    /// Creates new limiter with given maximum amount of frames per second, `None` disables
+    /// limiting.
+    pub fn new(max_fps: Option<f32>) -> Self {
+        Self {
+            frame_duration: Self::frame_duration(max_fps),
+            deadline: Instant::now(),
+        }
+    }
+
+    fn frame_duration(max_fps: Option<f32>) -> Option<Duration> {
+        max_fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| Duration::from_secs_f32(1.0 / fps))
+    }
+
+    /// Sets maximum amount of frames per second, `None` disables limiting.
+    pub fn set_max_fps(&mut self, max_fps: Option<f32>) {
+        self.frame_duration = Self::frame_duration(max_fps);
+        self.deadline = Instant::now();
+    }
+
+    /// Returns maximum amount of frames per second, if limiting is enabled.
+    pub fn max_fps(&self) -> Option<f32> {
+        self.frame_duration.map(|d| 1.0 / d.as_secs_f32())
+    }
+
+    /// Blocks current thread until next frame must be started and returns time of waiting.
+    /// Returns immediately if limiting is disabled.
+    pub fn wait(&mut self) -> Duration {
+        let frame_duration = match self.frame_duration {
+            Some(frame_duration) => frame_duration,
+            None => return Duration::default(),
+        };
+        let start = Instant::now();
+        let deadline = self.deadline + frame_duration;
+        if start >= deadline {
+            self.deadline = if start - deadline > frame_duration {
+                start
+            } else {
+                deadline
+            };
+            return Duration::default();
+        }
+        let remaining = deadline - start;
+        if remaining > Self::SPIN_TIME {
+            thread::sleep(remaining - Self::SPIN_TIME);
+        }
+        while Instant::now() < deadline {
+            thread::yield_now();
+        }
+        self.deadline = deadline;
+        Instant::now() - start
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::frame_timing::{FrameLimiter, FrameTimingHistory, FrameTimings};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn frame_rate_is_limited() {
+        let mut limiter = FrameLimiter::new(Some(100.0));
+        limiter.wait();
+        let start = Instant::now();
+        limiter.wait();
+        limiter.wait();
+        assert!(start.elapsed() >= Duration::from_millis(19));
+
+        let mut history = FrameTimingHistory::new(2);
+        for ms in [10, 20, 30].iter() {
+            history.push(FrameTimings {
+                frame: Duration::from_millis(*ms),
+                ..Default::default()
+            });
+        }
+        assert_eq!(history.iter().count(), 2);
+        assert_eq!(history.average().frame, Duration::from_millis(25));
+        assert_eq!(history.longest().unwrap().frame, Duration::from_millis(30));
+    }
+}
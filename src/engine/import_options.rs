@@ -0,0 +1,210 @@
+//! Contains per-asset import settings which are stored in sidecar files.
+//!
+//! Settings of a resource (pixel format, color space and filtering of a texture, conversion
+//! options of a model) can be stored next to the resource in a file with the same name and
+//! `.options` extension, for example `wall.png.options` for `wall.png`. Resource manager
+//! reads such file when it loads the resource, so settings travel with asset and don't have
+//! to be repeated at every place where the asset is requested. Settings from the file take
+//! precedence over options passed to `ResourceManager` methods and over global settings of
+//! resource manager; resources without options file are loaded as before.
+//!
+//! Options files are stored in native binary format of the engine (the same as used for
+//! saved games and scenes) and are read using virtual file system, so they can be packed
+//! into archives together with assets. Such files are created by `save` methods, which is
+//! intended to be used by tools.
+//!
+//! # Limitations
+//!
+//! Options are applied only when a resource is loaded, hot reload keeps settings of already
+//! loaded resource. Images are never block-compressed by the engine, so compressed pixel
+//! formats can only come from DDS and KTX2 files, see `texture` module docs.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     engine::import_options::TextureImportOptions,
+//!     resource::texture::TextureKind,
+//! };
+//!
+//! fn mark_as_normal_map(texture: &str) {
+//!     TextureImportOptions {
+//!         kind: Some(TextureKind::RGB8),
+//!         srgb: Some(false),
+//!         sampler: None,
+//!     }
+//!     .save(texture)
+//!     .unwrap();
+//! }
+//! ```
+
+use crate::{
+    core::visitor::{Visit, VisitResult, Visitor},
+    engine::{resource_manager::TextureRequestOptions, vfs::Vfs},
+    resource::{
+        fbx::FbxImportOptions,
+        texture::{TextureKind, TextureSampler},
+    },
+    utils::log::{Log, LogCategory},
+};
+use std::path::{Path, PathBuf};
+
+/// Extension of files with import options, it is appended to full name of resource file.
+pub const EXTENSION: &str = "options";
+
+/// Returns path to options file of resource with given path.
+pub fn options_path<P: AsRef<Path>>(resource_path: P) -> PathBuf {
+    let mut path = resource_path.as_ref().as_os_str().to_owned();
+    path.push(".");
+    path.push(EXTENSION);
+    PathBuf::from(path)
+}
+
+/// Reads options of given resource, returns default options if resource has no options file
+/// or the file is corrupted (reason is printed to log).
+fn load<T: Visit + Default>(resource_path: &Path, vfs: &Vfs) -> T {
+    let path = options_path(resource_path);
+    let mut options = T::default();
+    if vfs.exists(&path) {
+        let result = vfs
+            .load_visitor(&path)
+            .and_then(|mut visitor| options.visit("ImportOptions", &mut visitor));
+        if let Err(e) = result {
+            Log::err(
+                LogCategory::Resource,
+                format!("Unable to load import options {:?}! Reason {}", path, e),
+            );
+            options = T::default();
+        }
+    }
+    options
+}
+
+fn save<T: Visit>(options: &mut T, resource_path: &Path) -> VisitResult {
+    let mut visitor = Visitor::new();
+    options.visit("ImportOptions", &mut visitor)?;
+    visitor.save_binary(options_path(resource_path))
+}
+
+/// Import settings of a texture, every `None` field means that the value is taken from the
+/// request as usual.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct TextureImportOptions {
+    /// Pixel format to which images are converted. Has no effect for DDS, KTX2, HDR and EXR
+    /// files, their pixel format is defined by the file.
+    pub kind: Option<TextureKind>,
+    /// Whether texels of texture are in sRGB color space or not.
+    pub srgb: Option<bool>,
+    /// Sampler settings of texture.
+    pub sampler: Option<TextureSampler>,
+}
+
+impl TextureImportOptions {
+    /// Reads options of texture with given path, see module docs.
+    pub fn load<P: AsRef<Path>>(texture_path: P, vfs: &Vfs) -> Self {
+        load(texture_path.as_ref(), vfs)
+    }
+
+    /// Writes options to options file of texture with given path.
+    pub fn save<P: AsRef<Path>>(mut self, texture_path: P) -> VisitResult {
+        save(&mut self, texture_path.as_ref())
+    }
+
+    /// Overrides requested kind and options with values from this options.
+    pub(in crate) fn apply(
+        &self,
+        kind: TextureKind,
+        options: TextureRequestOptions,
+    ) -> (TextureKind, TextureRequestOptions) {
+        (
+            self.kind.unwrap_or(kind),
+            TextureRequestOptions {
+                sampler: self.sampler.or(options.sampler),
+                srgb: self.srgb.unwrap_or(options.srgb),
+            },
+        )
+    }
+}
+
+impl Visit for TextureImportOptions {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut kind = self.kind.map(|kind| kind.id());
+        kind.visit("KindId", visitor)?;
+        if visitor.is_reading() {
+            self.kind = kind.map(TextureKind::new).transpose()?;
+        }
+        self.srgb.visit("Srgb", visitor)?;
+        self.sampler.visit("Sampler", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Import settings of a model.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModelImportOptions {
+    /// Options of FBX conversion, global options of resource manager are used if this is
+    /// `None`, see `ResourceManager::set_fbx_import_options`.
+    pub fbx: Option<FbxImportOptions>,
+}
+
+impl ModelImportOptions {
+    /// Reads options of model with given path, see module docs.
+    pub fn load<P: AsRef<Path>>(model_path: P, vfs: &Vfs) -> Self {
+        load(model_path.as_ref(), vfs)
+    }
+
+    /// Writes options to options file of model with given path.
+    pub fn save<P: AsRef<Path>>(mut self, model_path: P) -> VisitResult {
+        save(&mut self, model_path.as_ref())
+    }
+}
+
+impl Visit for ModelImportOptions {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.fbx.visit("Fbx", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        engine::{
+            import_options::{options_path, TextureImportOptions},
+            resource_manager::TextureRequestOptions,
+        },
+        resource::texture::TextureKind,
+    };
+    use std::path::Path;
+
+    #[test]
+    fn options_override_request() {
+        assert_eq!(
+            options_path("data/wall.png"),
+            Path::new("data/wall.png.options")
+        );
+
+        let options = TextureImportOptions {
+            kind: Some(TextureKind::R8),
+            srgb: Some(false),
+            sampler: None,
+        };
+        let (kind, request) = options.apply(TextureKind::RGBA8, Default::default());
+        assert_eq!(kind, TextureKind::R8);
+        assert_eq!(
+            request,
+            TextureRequestOptions {
+                sampler: None,
+                srgb: false
+            }
+        );
+        let (kind, _) = TextureImportOptions::default().apply(TextureKind::RGBA8, request);
+        assert_eq!(kind, TextureKind::RGBA8);
+    }
+}
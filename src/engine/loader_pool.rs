@@ -0,0 +1,235 @@
+//! Contains thread pool which is used by resource manager to load resources in background.
+//!
+//! Every background loading of resource manager (`ResourceManager::load_texture_async`,
+//! `ResourceManager::load_model_async`, textures of models, etc.) is a task which is executed
+//! by a loader executor. By default resource manager uses `LoaderPool` with as many threads as
+//! there are logical cores minus one, so loading does not take cores away from main thread.
+//! Number of threads can be changed by `ResourceManager::set_loader_thread_count`, for example
+//! a game can use all cores on a loading screen and a single thread while streaming a level.
+//!
+//! Tasks have priorities: pending tasks with higher priority are started first, tasks with
+//! the same priority are started in order of requests. Priority of new tasks is set by
+//! `ResourceManager::set_load_priority`, tasks which were already queued keep their priority.
+//!
+//! # External thread pools
+//!
+//! Games which already have a thread pool can make resource manager use it by implementing
+//! `LoaderExecutor` for the pool and passing it to `ResourceManager::set_loader_executor`.
+//! `LoaderExecutor` is implemented for `rayon::ThreadPool` and for engine's `JobSystem` (see
+//! `utils::jobs`), both ignore priorities.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::engine::{loader_pool::LoadPriority, resource_manager::ResourceManager};
+//!
+//! fn enter_loading_screen(resource_manager: &mut ResourceManager) {
+//!     resource_manager.set_loader_thread_count(8);
+//!     resource_manager.set_load_priority(LoadPriority::High);
+//! }
+//! ```
+
+use crate::utils::log::{Log, LogCategory};
+use std::{
+    collections::VecDeque,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+};
+
+/// Priority of loading task, see module docs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LoadPriority {
+    /// Task is started only when there are no tasks with higher priority.
+    Low,
+    /// Default priority.
+    Normal,
+    /// Task is started before every task with lower priority.
+    High,
+}
+
+impl Default for LoadPriority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
+impl LoadPriority {
+    fn index(self) -> usize {
+        match self {
+            Self::Low => 0,
+            Self::Normal => 1,
+            Self::High => 2,
+        }
+    }
+}
+
+/// Loading task, it must be executed exactly once.
+pub type LoaderTask = Box<dyn FnOnce() + Send>;
+
+/// Executor of loading tasks, see module docs.
+pub trait LoaderExecutor: Send + Sync {
+    /// Schedules given task for execution on some other thread. Executor must not run task
+    /// on current thread, because resource manager can be locked by the caller.
+    fn execute(&self, task: LoaderTask, priority: LoadPriority);
+}
+
+impl LoaderExecutor for rayon::ThreadPool {
+    fn execute(&self, task: LoaderTask, _priority: LoadPriority) {
+        self.spawn(task);
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    // Indexed by priority.
+    tasks: [VecDeque<LoaderTask>; 3],
+    shutdown: bool,
+}
+
+impl Queue {
+    fn pop(&mut self) -> Option<LoaderTask> {
+        self.tasks.iter_mut().rev().find_map(|tasks| tasks.pop_front())
+    }
+}
+
+#[derive(Default)]
+struct Shared {
+    queue: Mutex<Queue>,
+    condvar: Condvar,
+}
+
+fn worker(shared: Arc<Shared>) {
+    loop {
+        let task = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(task) = queue.pop() {
+                    break task;
+                }
+                if queue.shutdown {
+                    return;
+                }
+                queue = shared.condvar.wait(queue).unwrap();
+            }
+        };
+        // Panic in a loader must not kill the thread, otherwise pool would shrink.
+        if panic::catch_unwind(AssertUnwindSafe(task)).is_err() {
+            Log::err(LogCategory::Resource, "Loading task has panicked!");
+        }
+    }
+}
+
+/// Fixed-size thread pool with prioritized queue of tasks, see module docs. Threads are
+/// stopped when pool is dropped, tasks that were queued before are finished first.
+pub struct LoaderPool {
+    shared: Arc<Shared>,
+    thread_count: usize,
+}
+
+impl LoaderPool {
+    /// Creates new pool with given amount of threads, at least one thread is created.
+    pub fn new(thread_count: usize) -> Self {
+        let thread_count = thread_count.max(1);
+        let shared = Arc::new(Shared::default());
+        for i in 0..thread_count {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name(format!("rg3d-loader-{}", i))
+                .spawn(move || worker(shared))
+                .expect("Unable to create loader thread!");
+        }
+        Self {
+            shared,
+            thread_count,
+        }
+    }
+
+    /// Returns amount of threads which is used by default: amount of logical cores minus one.
+    pub fn default_thread_count() -> usize {
+        rayon::current_num_threads().saturating_sub(1).max(1)
+    }
+
+    /// Returns amount of threads of the pool.
+    pub fn thread_count(&self) -> usize {
+        self.thread_count
+    }
+}
+
+impl Default for LoaderPool {
+    fn default() -> Self {
+        Self::new(Self::default_thread_count())
+    }
+}
+
+impl LoaderExecutor for LoaderPool {
+    fn execute(&self, task: LoaderTask, priority: LoadPriority) {
+        self.shared.queue.lock().unwrap().tasks[priority.index()].push_back(task);
+        self.shared.condvar.notify_one();
+    }
+}
+
+impl Drop for LoaderPool {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().shutdown = true;
+        self.shared.condvar.notify_all();
+    }
+}
+
+/// Executor of resource manager together with current priority, it allows to start a task
+/// without locking resource manager again.
+#[derive(Clone)]
+pub(in crate) struct TaskSpawner {
+    pub(in crate) executor: Arc<dyn LoaderExecutor>,
+    pub(in crate) priority: LoadPriority,
+}
+
+impl TaskSpawner {
+    pub(in crate) fn spawn<F: FnOnce() + Send + 'static>(&self, task: F) {
+        self.executor.execute(Box::new(task), self.priority);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::loader_pool::{LoadPriority, LoaderExecutor, LoaderPool};
+    use std::sync::{mpsc, Arc, Mutex};
+
+    #[test]
+    fn tasks_are_started_by_priority() {
+        let pool = LoaderPool::new(1);
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        // Keep the only thread busy until every task is queued.
+        let (sender, receiver) = mpsc::channel::<()>();
+        pool.execute(
+            Box::new(move || {
+                let _ = receiver.recv();
+            }),
+            LoadPriority::Normal,
+        );
+        for &(name, priority) in &[
+            ("low", LoadPriority::Low),
+            ("normal", LoadPriority::Normal),
+            ("high", LoadPriority::High),
+            ("normal2", LoadPriority::Normal),
+        ] {
+            let order = order.clone();
+            pool.execute(
+                Box::new(move || order.lock().unwrap().push(name)),
+                priority,
+            );
+        }
+        let (done_sender, done) = mpsc::channel();
+        pool.execute(
+            Box::new(move || done_sender.send(()).unwrap()),
+            LoadPriority::Low,
+        );
+        sender.send(()).unwrap();
+        done.recv().unwrap();
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec!["high", "normal", "normal2", "low"]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! Contains loading progress reporting of resource manager.
+//!
+//! Resource manager tracks every resource that is loaded in background (see
+//! `ResourceManager::load_texture_async`, `ResourceManager::load_model_async`,
+//! `ResourceManager::load_sound_buffer_async` and `ResourceManager::request_texture_async`):
+//! its path, state, size of its file and amount of bytes read so far. This information is
+//! enough to draw accurate loading bars during level loads. Textures report progress while
+//! their files are read, models and sound buffers report progress only when they're fully
+//! loaded, but textures requested by models are tracked separately.
+//!
+//! Resource manager is locked while models are loading, so progress should be queried using
+//! `ProgressTracker`, which can be obtained once and used without locking resource manager.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::engine::loading_progress::ProgressTracker;
+//!
+//! fn loading_bar_text(tracker: &ProgressTracker) -> String {
+//!     let progress = tracker.progress();
+//!     format!(
+//!         "Loading {:.0}% ({} resources left)",
+//!         progress.fraction() * 100.0,
+//!         progress.pending_count()
+//!     )
+//! }
+//! ```
+
+use crate::engine::vfs::Vfs;
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// State of a tracked resource.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoadingState {
+    /// Resource is still loading.
+    Pending,
+    /// Resource is loaded successfully.
+    Loaded,
+    /// Resource has failed to load.
+    Failed,
+}
+
+/// Progress of single resource.
+#[derive(Clone, Debug)]
+pub struct ResourceProgress {
+    /// Path of resource file.
+    pub path: PathBuf,
+    /// Current state of resource.
+    pub state: LoadingState,
+    /// Size of resource file in bytes, zero if it is unknown.
+    pub total_bytes: u64,
+    /// Amount of bytes of resource file read so far.
+    pub loaded_bytes: u64,
+}
+
+impl ResourceProgress {
+    /// Returns progress of loading in [0; 1] range.
+    pub fn fraction(&self) -> f32 {
+        match self.state {
+            LoadingState::Loaded | LoadingState::Failed => 1.0,
+            LoadingState::Pending if self.total_bytes > 0 => {
+                (self.loaded_bytes as f64 / self.total_bytes as f64).min(1.0) as f32
+            }
+            LoadingState::Pending => 0.0,
+        }
+    }
+}
+
+/// Snapshot of loading progress of all tracked resources.
+#[derive(Clone, Debug, Default)]
+pub struct LoadingProgress {
+    /// Progress of each tracked resource, in order of requests.
+    pub resources: Vec<ResourceProgress>,
+}
+
+impl LoadingProgress {
+    fn count(&self, state: LoadingState) -> usize {
+        self.resources.iter().filter(|r| r.state == state).count()
+    }
+
+    /// Returns amount of resources which are still loading.
+    pub fn pending_count(&self) -> usize {
+        self.count(LoadingState::Pending)
+    }
+
+    /// Returns amount of loaded resources.
+    pub fn loaded_count(&self) -> usize {
+        self.count(LoadingState::Loaded)
+    }
+
+    /// Returns amount of resources that have failed to load.
+    pub fn failed_count(&self) -> usize {
+        self.count(LoadingState::Failed)
+    }
+
+    /// Returns total size of files of all tracked resources.
+    pub fn total_bytes(&self) -> u64 {
+        self.resources.iter().map(|r| r.total_bytes).sum()
+    }
+
+    /// Returns total amount of bytes read so far.
+    pub fn loaded_bytes(&self) -> u64 {
+        self.resources.iter().map(|r| r.loaded_bytes).sum()
+    }
+
+    /// Returns overall progress in [0; 1] range. Resources are weighted by size of their
+    /// files, so one huge model is not considered as important as one tiny texture.
+    pub fn fraction(&self) -> f32 {
+        let total_bytes = self.total_bytes();
+        if total_bytes > 0 {
+            let done = self
+                .resources
+                .iter()
+                .map(|r| (r.fraction() as f64 * r.total_bytes as f64) as u64)
+                .sum::<u64>();
+            (done as f64 / total_bytes as f64) as f32
+        } else if self.resources.is_empty() {
+            1.0
+        } else {
+            (self.resources.len() - self.pending_count()) as f32 / self.resources.len() as f32
+        }
+    }
+
+    /// Returns true if there are no resources that are still loading.
+    pub fn is_finished(&self) -> bool {
+        self.pending_count() == 0
+    }
+}
+
+/// Progress of single resource which is updated by loader.
+#[derive(Clone)]
+pub(in crate) struct ProgressEntry(Arc<Mutex<ResourceProgress>>);
+
+impl ProgressEntry {
+    pub(in crate) fn add_loaded_bytes(&self, amount: u64) {
+        self.0.lock().unwrap().loaded_bytes += amount;
+    }
+
+    pub(in crate) fn finish(&self, success: bool) {
+        let mut progress = self.0.lock().unwrap();
+        if success {
+            progress.state = LoadingState::Loaded;
+            progress.loaded_bytes = progress.total_bytes;
+        } else {
+            progress.state = LoadingState::Failed;
+        }
+    }
+
+    /// Reads whole file reporting amount of bytes read.
+    pub(in crate) fn read_file(&self, vfs: &Vfs, path: &Path) -> std::io::Result<Vec<u8>> {
+        let mut file = vfs.open(path)?;
+        let mut data = Vec::new();
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            let count = file.read(&mut chunk)?;
+            if count == 0 {
+                break;
+            }
+            data.extend_from_slice(&chunk[..count]);
+            self.add_loaded_bytes(count as u64);
+        }
+        Ok(data)
+    }
+}
+
+/// Shared tracker of loading progress, see module docs. It is cheap to clone, every clone
+/// refers to the same set of resources.
+#[derive(Clone, Default)]
+pub struct ProgressTracker {
+    entries: Arc<Mutex<Vec<ProgressEntry>>>,
+}
+
+impl ProgressTracker {
+    pub(in crate) fn begin<P: AsRef<Path>>(&self, vfs: &Vfs, path: P) -> ProgressEntry {
+        let total_bytes = vfs.file_size(path.as_ref()).unwrap_or_default();
+        let entry = ProgressEntry(Arc::new(Mutex::new(ResourceProgress {
+            path: path.as_ref().to_owned(),
+            state: LoadingState::Pending,
+            total_bytes,
+            loaded_bytes: 0,
+        })));
+        self.entries.lock().unwrap().push(entry.clone());
+        entry
+    }
+
+    /// Returns snapshot of current loading progress.
+    pub fn progress(&self) -> LoadingProgress {
+        LoadingProgress {
+            resources: self
+                .entries
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|entry| entry.0.lock().unwrap().clone())
+                .collect(),
+        }
+    }
+
+    /// Forgets every resource which is not loading anymore. Call it before loading of a new
+    /// level, so progress of the level will not include resources of previous levels.
+    pub fn clear_finished(&self) {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|entry| entry.0.lock().unwrap().state == LoadingState::Pending);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::{loading_progress::ProgressTracker, vfs::Vfs};
+
+    #[test]
+    fn progress_counts_and_fraction() {
+        let tracker = ProgressTracker::default();
+        let vfs = Vfs::new();
+        // Files do not exist, so sizes are unknown and progress is counted by resources.
+        let a = tracker.begin(&vfs, "a.png");
+        let b = tracker.begin(&vfs, "b.fbx");
+        assert_eq!(tracker.progress().pending_count(), 2);
+        assert_eq!(tracker.progress().fraction(), 0.0);
+
+        a.finish(true);
+        b.finish(false);
+        let progress = tracker.progress();
+        assert_eq!(progress.loaded_count(), 1);
+        assert_eq!(progress.failed_count(), 1);
+        assert!(progress.is_finished());
+        assert_eq!(progress.fraction(), 1.0);
+
+        tracker.clear_finished();
+        assert!(tracker.progress().resources.is_empty());
+    }
+}
@@ -0,0 +1,289 @@
+//! Contains typed message bus which is owned by engine.
+//!
+//! Message bus is a sanctioned way for parts of a game to talk to each other without knowing
+//! about each other: collision handling, animation signals, user interface, game systems and
+//! so on. Any `Clone + Send + 'static` type can be a message. Messages are sent using
+//! `MessageSender`, which can be cloned and sent to other threads, so messages can be sent from
+//! anywhere. Sent messages are not delivered immediately - they're kept until `dispatch` which
+//! delivers them to subscriptions in order of sending. Engine dispatches messages at defined
+//! points of a frame:
+//!
+//! - at the beginning of `Engine::update`, so messages sent since last update (from event
+//!   handling, rendering or other threads) are available to game logic,
+//! - after every update of scenes (every fixed step if fixed time step is enabled), so messages
+//!   sent during update of scenes are available before next step and user interface update.
+//!
+//! Subscription can be made for a system (`MessageBus::subscribe`) - it receives every message
+//! of its type, or for a node (`MessageBus::subscribe_node`) - it receives only messages which
+//! are addressed to the node (`MessageSender::send_to`). Subscription keeps received messages
+//! until they're read, so messages must be read regularly. Subscription is cancelled when it is
+//! dropped.
+//!
+//! Engine sends `Contact2DMessage` for every 2D contact of every scene (only if there is a
+//! subscription for them). User interface messages can be forwarded to game logic by sending
+//! them to the bus while polling them from user interface.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::pool::Handle,
+//!     engine::message_bus::{Contact2DMessage, MessageBus},
+//!     scene::node::Node,
+//! };
+//!
+//! #[derive(Clone)]
+//! struct Damage(f32);
+//!
+//! let mut bus = MessageBus::new();
+//! let player = Handle::<Node>::NONE;
+//! let damage = bus.subscribe_node::<Damage>(player);
+//! let contacts = bus.subscribe::<Contact2DMessage>();
+//!
+//! bus.sender().send_to(player, Damage(10.0));
+//! bus.dispatch();
+//!
+//! while let Some(envelope) = damage.recv() {
+//!     println!("Player took {} damage", envelope.message.0);
+//! }
+//! for envelope in contacts.drain() {
+//!     println!("{:?}", envelope.message.contact);
+//! }
+//! ```
+
+use crate::{
+    core::pool::Handle,
+    scene::{dim2::physics::Contact2D, node::Node, Scene},
+};
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, Weak},
+};
+
+/// Message with nodes it is addressed to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Envelope<T> {
+    /// Nodes the message is addressed to, empty if the message is addressed to systems only.
+    pub targets: Vec<Handle<Node>>,
+    /// Message itself.
+    pub message: T,
+}
+
+/// Contact between two 2D bodies which was found during last update of a scene, it is sent by
+/// engine and addressed to nodes of both bodies.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact2DMessage {
+    /// Scene of the bodies.
+    pub scene: Handle<Scene>,
+    /// The contact.
+    pub contact: Contact2D,
+}
+
+type Queue<T> = Mutex<VecDeque<Envelope<T>>>;
+
+/// Sends messages to message bus, see module docs.
+#[derive(Clone, Default)]
+pub struct MessageSender {
+    pending: Arc<Mutex<Vec<(TypeId, Box<dyn Any + Send>)>>>,
+}
+
+impl MessageSender {
+    /// Sends message which is addressed to systems only.
+    pub fn send<T: Clone + Send + 'static>(&self, message: T) {
+        self.send_envelope(Envelope {
+            targets: Vec::new(),
+            message,
+        })
+    }
+
+    /// Sends message which is addressed to given node, systems receive it too.
+    pub fn send_to<T: Clone + Send + 'static>(&self, target: Handle<Node>, message: T) {
+        self.send_envelope(Envelope {
+            targets: vec![target],
+            message,
+        })
+    }
+
+    /// Sends message with any amount of addressees.
+    pub fn send_envelope<T: Clone + Send + 'static>(&self, envelope: Envelope<T>) {
+        self.pending
+            .lock()
+            .unwrap()
+            .push((TypeId::of::<T>(), Box::new(envelope)));
+    }
+}
+
+/// Receives messages of one type, see module docs.
+pub struct Subscription<T> {
+    queue: Arc<Queue<T>>,
+}
+
+impl<T> Subscription<T> {
+    /// Takes oldest received message.
+    pub fn recv(&self) -> Option<Envelope<T>> {
+        self.queue.lock().unwrap().pop_front()
+    }
+
+    /// Takes every received message, from oldest to newest.
+    pub fn drain(&self) -> Vec<Envelope<T>> {
+        self.queue.lock().unwrap().drain(..).collect()
+    }
+
+    /// Returns amount of received messages which weren't taken yet.
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    /// Returns true if there are no received messages.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+trait Subscriber: Send {
+    fn is_alive(&self) -> bool;
+
+    fn deliver(&self, envelope: &dyn Any);
+}
+
+struct SubscriberEntry<T> {
+    queue: Weak<Queue<T>>,
+    node: Option<Handle<Node>>,
+}
+
+impl<T: Clone + Send + 'static> Subscriber for SubscriberEntry<T> {
+    fn is_alive(&self) -> bool {
+        self.queue.strong_count() > 0
+    }
+
+    fn deliver(&self, envelope: &dyn Any) {
+        if let (Some(queue), Some(envelope)) =
+            (self.queue.upgrade(), envelope.downcast_ref::<Envelope<T>>())
+        {
+            if self
+                .node
+                .map_or(true, |node| envelope.targets.contains(&node))
+            {
+                queue.lock().unwrap().push_back(envelope.clone());
+            }
+        }
+    }
+}
+
+/// See module docs.
+#[derive(Default)]
+pub struct MessageBus {
+    sender: MessageSender,
+    subscribers: HashMap<TypeId, Vec<Box<dyn Subscriber>>>,
+}
+
+impl MessageBus {
+    /// Creates new message bus without subscriptions.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns sender of messages to the bus.
+    pub fn sender(&self) -> MessageSender {
+        self.sender.clone()
+    }
+
+    fn add_subscription<T>(&mut self, node: Option<Handle<Node>>) -> Subscription<T>
+    where
+        T: Clone + Send + 'static,
+    {
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        self.subscribers
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .push(Box::new(SubscriberEntry {
+                queue: Arc::downgrade(&queue),
+                node,
+            }));
+        Subscription { queue }
+    }
+
+    /// Subscribes to every message of given type.
+    pub fn subscribe<T: Clone + Send + 'static>(&mut self) -> Subscription<T> {
+        self.add_subscription(None)
+    }
+
+    /// Subscribes to messages of given type which are addressed to given node.
+    pub fn subscribe_node<T: Clone + Send + 'static>(
+        &mut self,
+        node: Handle<Node>,
+    ) -> Subscription<T> {
+        self.add_subscription(Some(node))
+    }
+
+    /// Returns true if there is at least one subscription to messages of given type. It can be
+    /// used to avoid making of messages nobody will receive.
+    pub fn has_subscribers<T: 'static>(&self) -> bool {
+        self.subscribers
+            .get(&TypeId::of::<T>())
+            .map_or(false, |subscribers| {
+                subscribers.iter().any(|subscriber| subscriber.is_alive())
+            })
+    }
+
+    /// Delivers every sent message to subscriptions, messages without subscriptions are
+    /// dropped. Called by engine, see module docs.
+    pub fn dispatch(&mut self) {
+        let pending = std::mem::take(&mut *self.sender.pending.lock().unwrap());
+        for subscribers in self.subscribers.values_mut() {
+            subscribers.retain(|subscriber| subscriber.is_alive());
+        }
+        for (type_id, envelope) in pending {
+            if let Some(subscribers) = self.subscribers.get(&type_id) {
+                for subscriber in subscribers.iter() {
+                    subscriber.deliver(envelope.as_ref());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::pool::{Handle, Pool},
+        engine::message_bus::MessageBus,
+        scene::node::Node,
+    };
+
+    #[test]
+    fn messages_are_delivered_to_subscriptions() {
+        let mut pool = Pool::new();
+        let a: Handle<Node> = pool.spawn(Node::default());
+        let b: Handle<Node> = pool.spawn(Node::default());
+
+        let mut bus = MessageBus::new();
+        let all = bus.subscribe::<u32>();
+        let node_a = bus.subscribe_node::<u32>(a);
+        let strings = bus.subscribe::<String>();
+
+        let sender = bus.sender();
+        std::thread::spawn(move || sender.send(1u32))
+            .join()
+            .unwrap();
+        bus.sender().send_to(a, 2u32);
+        bus.sender().send_to(b, 3u32);
+        assert!(all.is_empty());
+        bus.dispatch();
+
+        let messages = all
+            .drain()
+            .into_iter()
+            .map(|e| e.message)
+            .collect::<Vec<_>>();
+        assert_eq!(messages, vec![1, 2, 3]);
+        assert_eq!(node_a.recv().unwrap().message, 2);
+        assert!(node_a.recv().is_none());
+        assert!(strings.is_empty());
+
+        drop(strings);
+        assert!(!bus.has_subscribers::<String>());
+        assert!(bus.has_subscribers::<u32>());
+    }
+}
@@ -1,44 +1,82 @@
 //! Engine is container for all subsystems (renderer, ui, sound, resource manager). It also
-//! creates a window and an OpenGL context.
+//! creates a window and an OpenGL context. Subsystems can be disabled or configured using
+//! `EngineBuilder`.
+//!
+//! Engine can also be created in headless mode (see `Engine::new_headless`) - without window,
+//! OpenGL context, renderer and sound device. Scenes, physics, resources, user interface and
+//! plugins work as usual, so headless engine can be used for dedicated game servers and for
+//! simulation tests on machines without graphics and sound.
 
 #![warn(missing_docs)]
 
+pub mod custom_resource;
+pub mod dependencies;
+pub mod determinism;
 pub mod error;
+pub mod frame_timing;
+pub mod import_options;
+pub mod loader_pool;
+pub mod loading_progress;
+pub mod message_bus;
+pub mod plugin;
+pub mod resource_handle;
 pub mod resource_manager;
+#[cfg(feature = "scripting")]
+pub mod scripting;
+pub mod timestep;
+pub mod vfs;
+pub mod viewport_ui;
+pub mod visitor_compression;
 
 use crate::{
     core::{
         math::vec2::Vec2,
         visitor::{Visit, VisitResult, Visitor},
     },
-    engine::{error::EngineError, resource_manager::ResourceManager},
+    engine::{
+        determinism::{self, DeterminismSettings},
+        error::EngineError,
+        frame_timing::{FrameLimiter, FrameTimingHistory, FrameTimings},
+        message_bus::{Contact2DMessage, Envelope, MessageBus},
+        plugin::{Plugin, PluginContainer, PluginContext},
+        resource_manager::ResourceManager,
+        timestep::FixedTimestep,
+        viewport_ui::ViewportUi,
+    },
+    event::Event,
     event_loop::EventLoop,
-    gui::{Control, UserInterface},
+    gui::{message::OsEvent, Control, UserInterface},
+    profile_scope,
     renderer::{error::RendererError, Renderer},
+    resource::texture::Texture,
     scene::SceneContainer,
     sound::context::Context,
+    utils::{profiler, random},
     window::{Window, WindowBuilder},
     Api, GlProfile, GlRequest, NotCurrent, PossiblyCurrent, WindowedContext,
 };
 use rg3d_ui::message::MessageData;
 use std::{
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     time::{self, Duration},
 };
 
+#[cfg(feature = "hot_reload")]
+use crate::engine::plugin::PluginError;
+
 /// See module docs.
 pub struct Engine<M: MessageData, C: Control<M, C>> {
-    context: glutin::WindowedContext<PossiblyCurrent>,
-    /// Current renderer. You should call at least [render] method to see your scene on screen.
-    pub renderer: Renderer,
+    context: Option<glutin::WindowedContext<PossiblyCurrent>>,
+    renderer: Option<Renderer>,
     /// User interface allows you to build interface of any kind. UI itself is *not* thread-safe,
     /// but it uses messages to "talk" with outside world and message queue (MPSC) *is* thread-safe
     /// so its sender part can be shared across threads.   
     pub user_interface: UserInterface<M, C>,
-    /// Sound context control all sound sources in the engine. It is wrapped into Arc<Mutex<>>
-    /// because internally sound engine spawns separate thread to mix and send data to sound
-    /// device. For more info see docs for Context.
-    pub sound_context: Arc<Mutex<Context>>,
+    /// User interfaces bound to viewports of cameras, they're drawn below main user interface.
+    /// See `ViewportUi` docs for more info.
+    pub viewport_interfaces: Vec<ViewportUi<M, C>>,
+    sound_context: Option<Arc<Mutex<Context>>>,
     /// Current resource manager. Resource manager wrapped into Arc<Mutex<>> to be able to
     /// use resource manager from any thread, this is useful to load resources from multiple
     /// threads to decrease loading times of your game by utilizing all available power of
@@ -46,16 +84,31 @@ pub struct Engine<M: MessageData, C: Control<M, C>> {
     pub resource_manager: Arc<Mutex<ResourceManager>>,
     /// All available scenes in the engine.
     pub scenes: SceneContainer,
-    /// The time user interface took for internal needs. TODO: This is not the right place
-    /// for such statistics, probably it is best to make separate structure to hold all
-    /// such data.
+    /// Typed message bus between game systems and nodes, engine dispatches its messages
+    /// during update. See `message_bus` module docs for more info.
+    pub message_bus: MessageBus,
+    /// The time user interface took for internal needs. Kept for compatibility, see
+    /// `frame_timings` for timings of every stage of a frame.
     pub ui_time: Duration,
+    fixed_timestep: Option<FixedTimestep>,
+    interpolate_transforms: bool,
+    vsync: bool,
+    frame_limiter: FrameLimiter,
+    current_frame: FrameTimings,
+    frame_timing_history: FrameTimingHistory,
+    last_frame_end: time::Instant,
+    plugins: PluginContainer,
+    // Frame size of headless engine, windowed engine takes it from its window.
+    headless_frame_size: Vec2,
+    user_interface_enabled: bool,
+    physics_enabled: bool,
 }
 
 impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
     /// Creates new instance of engine from given window builder and events loop.
     ///
-    /// Automatically creates all sub-systems (renderer, sound, ui, etc.).
+    /// Automatically creates all sub-systems (renderer, sound, ui, etc.), use `EngineBuilder`
+    /// to choose subsystems and their settings.
     ///
     /// # Examples
     ///
@@ -76,75 +129,692 @@ impl<M: MessageData, C: Control<M, C>> Engine<M, C> {
         window_builder: WindowBuilder,
         events_loop: &EventLoop<()>,
     ) -> Result<Self, EngineError> {
-        let context_wrapper: WindowedContext<NotCurrent> = glutin::ContextBuilder::new()
-            .with_vsync(true)
-            .with_gl_profile(GlProfile::Core)
-            .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
-            .build_windowed(window_builder, events_loop)?;
-
-        let mut context = match unsafe { context_wrapper.make_current() } {
-            Ok(context) => context,
-            Err((_, e)) => return Err(EngineError::from(e)),
-        };
+        Self::new_with_vsync(window_builder, events_loop, true)
+    }
 
-        let client_size = context.window().inner_size();
+    /// Same as `new`, but allows to disable vertical synchronization, which is enabled by
+    /// `new`. Without vertical synchronization frames are presented as soon as they're
+    /// rendered, use `set_frame_rate_limit` to keep frame rate stable. Driver settings can
+    /// override this option.
+    pub fn new_with_vsync(
+        window_builder: WindowBuilder,
+        events_loop: &EventLoop<()>,
+        vsync: bool,
+    ) -> Result<Self, EngineError> {
+        EngineBuilder::new()
+            .with_window_builder(window_builder)
+            .with_vsync(vsync)
+            .build(Some(events_loop))
+    }
 
-        Ok(Self {
-            renderer: Renderer::new(&mut context, client_size.into())?,
+    /// Creates new instance of engine in headless mode - without window, OpenGL context,
+    /// renderer and sound device, see module docs. Cameras and user interface use given frame
+    /// size. `render` draws nothing in headless mode, but it still finishes a frame, so frame
+    /// rate limiter (see `set_frame_rate_limit`) can be used to keep constant rate of updates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rg3d::{core::math::vec2::Vec2, engine::Engine, gui::node::StubNode, scene::Scene};
+    ///
+    /// let mut engine: Engine<(), StubNode> = Engine::new_headless(Vec2::new(800.0, 600.0));
+    /// engine.scenes.add(Scene::new());
+    /// engine.update(1.0 / 60.0);
+    /// ```
+    pub fn new_headless(frame_size: Vec2) -> Self {
+        Self::from_subsystems(None, None, None, frame_size, false)
+    }
+
+    fn from_subsystems(
+        context: Option<WindowedContext<PossiblyCurrent>>,
+        renderer: Option<Renderer>,
+        sound_context: Option<Arc<Mutex<Context>>>,
+        frame_size: Vec2,
+        vsync: bool,
+    ) -> Self {
+        Self {
+            renderer,
             resource_manager: Arc::new(Mutex::new(ResourceManager::new())),
-            sound_context: Context::new()?,
+            sound_context,
             scenes: SceneContainer::new(),
-            user_interface: UserInterface::new(Vec2::new(
-                client_size.width as f32,
-                client_size.height as f32,
-            )),
+            message_bus: MessageBus::new(),
+            user_interface: UserInterface::new(frame_size),
+            viewport_interfaces: Vec::new(),
             ui_time: Default::default(),
+            fixed_timestep: None,
+            interpolate_transforms: true,
+            vsync,
+            frame_limiter: Default::default(),
+            current_frame: Default::default(),
+            frame_timing_history: Default::default(),
+            last_frame_end: time::Instant::now(),
+            plugins: Default::default(),
+            headless_frame_size: frame_size,
+            user_interface_enabled: true,
+            physics_enabled: true,
             context,
-        })
+        }
     }
 
     /// Returns reference to main window. Could be useful to set fullscreen mode, change
     /// size of window, its title, etc.
+    ///
+    /// # Panics
+    ///
+    /// Panics if engine is headless, use `window` if engine can be headless.
     #[inline]
     pub fn get_window(&self) -> &Window {
-        self.context.window()
+        self.window().expect("Headless engine has no window!")
+    }
+
+    /// Returns reference to main window or `None` if engine is headless.
+    pub fn window(&self) -> Option<&Window> {
+        self.context.as_ref().map(|context| context.window())
+    }
+
+    /// Returns true if engine was created without window, renderer and sound device, see
+    /// `new_headless`.
+    pub fn is_headless(&self) -> bool {
+        self.context.is_none()
+    }
+
+    /// Returns current renderer. You should call at least [render] method to see your scene on
+    /// screen. There is no renderer in headless mode.
+    pub fn renderer(&self) -> Option<&Renderer> {
+        self.renderer.as_ref()
+    }
+
+    /// Returns current renderer, see `renderer`.
+    pub fn renderer_mut(&mut self) -> Option<&mut Renderer> {
+        self.renderer.as_mut()
+    }
+
+    /// Returns sound context. Sound context control all sound sources in the engine. It is
+    /// wrapped into Arc<Mutex<>> because internally sound engine spawns separate thread to mix
+    /// and send data to sound device. For more info see docs for Context. There is no sound
+    /// context in headless mode.
+    pub fn sound_context(&self) -> Option<&Arc<Mutex<Context>>> {
+        self.sound_context.as_ref()
+    }
+
+    /// Returns true if user interfaces are updated and drawn, see
+    /// `EngineBuilder::with_user_interface`.
+    pub fn is_user_interface_enabled(&self) -> bool {
+        self.user_interface_enabled
+    }
+
+    /// Enables or disables simulation of 3D and 2D physics of every scene.
+    pub fn set_physics_enabled(&mut self, enabled: bool) {
+        self.physics_enabled = enabled;
+    }
+
+    /// Returns true if physics of scenes is simulated.
+    pub fn is_physics_enabled(&self) -> bool {
+        self.physics_enabled
+    }
+
+    /// Returns size of frame which is used by cameras and user interface: inner size of
+    /// window or frame size which was given to headless engine.
+    pub fn frame_size(&self) -> Vec2 {
+        match self.window() {
+            Some(window) => {
+                let inner_size = window.inner_size();
+                Vec2::new(inner_size.width as f32, inner_size.height as f32)
+            }
+            None => self.headless_frame_size,
+        }
+    }
+
+    /// Sets amount of fixed steps of scene simulation per second, `None` makes engine update
+    /// scenes once per frame with frame time (default behaviour). See `timestep` module docs.
+    pub fn set_fixed_update_rate(&mut self, rate: Option<f32>) {
+        if rate.is_none() {
+            for scene in self.scenes.iter_mut() {
+                scene.graph.restore_simulated_transforms();
+            }
+        }
+        self.fixed_timestep = rate.map(FixedTimestep::new);
+    }
+
+    /// Returns fixed time step of scene simulation if it is enabled.
+    pub fn fixed_timestep(&self) -> Option<&FixedTimestep> {
+        self.fixed_timestep.as_ref()
+    }
+
+    /// Returns fixed time step of scene simulation if it is enabled, it can be used to change
+    /// maximum amount of steps per frame.
+    pub fn fixed_timestep_mut(&mut self) -> Option<&mut FixedTimestep> {
+        self.fixed_timestep.as_mut()
+    }
+
+    /// Enables or disables interpolation of global transforms of nodes between fixed steps,
+    /// see `scene::interpolation` module docs. Enabled by default, has no effect if fixed
+    /// time step is disabled.
+    pub fn set_transform_interpolation(&mut self, enabled: bool) {
+        self.interpolate_transforms = enabled;
+    }
+
+    /// Returns true if global transforms of nodes are interpolated between fixed steps.
+    pub fn is_transform_interpolation_enabled(&self) -> bool {
+        self.interpolate_transforms
+    }
+
+    /// Enables deterministic simulation mode with given settings or disables it, see
+    /// `determinism` module docs. Disabling of the mode also disables fixed time step and
+    /// unseeds engine-wide random number generator. Must be called before scenes are created
+    /// or loaded to get the same state in every run.
+    pub fn set_deterministic(&mut self, settings: Option<DeterminismSettings>) {
+        determinism::set_enabled(settings.is_some());
+        random::set_seed(settings.map(|settings| settings.seed));
+        self.set_fixed_update_rate(settings.map(|settings| settings.update_rate));
+    }
+
+    /// Returns true if deterministic simulation mode is enabled.
+    pub fn is_deterministic(&self) -> bool {
+        determinism::is_enabled()
+    }
+
+    /// Returns true if vertical synchronization was requested at creation of engine.
+    pub fn is_vsync_enabled(&self) -> bool {
+        self.vsync
+    }
+
+    /// Sets maximum amount of frames per second, `render` waits before returning if frame
+    /// was finished too early. `None` disables limiting (default). It is meant to be used
+    /// when vertical synchronization is disabled, see `frame_timing` module docs.
+    pub fn set_frame_rate_limit(&mut self, max_fps: Option<f32>) {
+        self.frame_limiter.set_max_fps(max_fps);
+    }
+
+    /// Returns maximum amount of frames per second, if limiting is enabled.
+    pub fn frame_rate_limit(&self) -> Option<f32> {
+        self.frame_limiter.max_fps()
+    }
+
+    /// Returns timings of last finished frame, see `frame_timing` module docs. Frame is
+    /// finished by `render`.
+    pub fn frame_timings(&self) -> FrameTimings {
+        self.frame_timing_history.last().copied().unwrap_or_default()
+    }
+
+    /// Returns timings of last frames, they can be used to show average frame times.
+    pub fn frame_timing_history(&self) -> &FrameTimingHistory {
+        &self.frame_timing_history
+    }
+
+    /// Returns timings of last frames, it can be used to change amount of kept frames.
+    pub fn frame_timing_history_mut(&mut self) -> &mut FrameTimingHistory {
+        &mut self.frame_timing_history
+    }
+
+    fn plugins_and_context(&mut self) -> (&mut PluginContainer, PluginContext) {
+        let context = PluginContext {
+            scenes: &mut self.scenes,
+            resource_manager: &self.resource_manager,
+            sound_context: self.sound_context.as_ref(),
+            message_bus: &mut self.message_bus,
+            renderer: self.renderer.as_mut(),
+        };
+        (&mut self.plugins, context)
+    }
+
+    /// Adds plugin to engine and initializes it, see `plugin` module docs.
+    pub fn add_plugin(&mut self, plugin: Box<dyn Plugin>) {
+        let (plugins, mut context) = self.plugins_and_context();
+        plugins.add(plugin, &mut context);
+    }
+
+    /// Loads plugin from dynamic library with given path and initializes it. Library will be
+    /// reloaded when it is changed, see `plugin` module docs.
+    #[cfg(feature = "hot_reload")]
+    pub fn load_plugin<P: AsRef<Path>>(&mut self, path: P) -> Result<(), PluginError> {
+        let (plugins, mut context) = self.plugins_and_context();
+        plugins.load(path.as_ref(), &mut context)
+    }
+
+    /// Passes event to every plugin, must be called from event loop if plugins handle events.
+    pub fn process_plugin_event(&mut self, event: &Event<()>) {
+        let (plugins, mut context) = self.plugins_and_context();
+        plugins.on_event(&mut context, event);
+    }
+
+    /// Shuts down and removes every plugin.
+    pub fn remove_plugins(&mut self) {
+        let (plugins, mut context) = self.plugins_and_context();
+        plugins.clear(&mut context);
+    }
+
+    fn update_plugins(&mut self, dt: f32) {
+        let (plugins, mut context) = self.plugins_and_context();
+        plugins.update(&mut context, dt);
     }
 
     /// Performs single update tick with given time delta. Engine internally will perform update
     /// of all scenes, sub-systems, user interface, etc. Must be called in order to get engine
     /// functioning.
     pub fn update(&mut self, dt: f32) {
-        let inner_size = self.context.window().inner_size();
-        let frame_size = Vec2::new(inner_size.width as f32, inner_size.height as f32);
+        self.update_with(dt, |_, _| ())
+    }
+
+    /// Same as `update`, but calls given closure before every update of scenes with time step
+    /// of the update: once per frame with frame time, or before every fixed step with duration
+    /// of the step if fixed time step is enabled. Game logic that must be framerate-independent
+    /// (movement, AI, etc.) should be done in the closure. Plugins are updated right before the
+    /// closure.
+    pub fn update_with<F>(&mut self, dt: f32, mut fixed_update: F)
+    where
+        F: FnMut(&mut SceneContainer, f32),
+    {
+        profile_scope!("Update");
+
+        let update_start = time::Instant::now();
+        let frame_size = self.frame_size();
+
+        self.message_bus.dispatch();
+
+        #[cfg(feature = "hot_reload")]
+        {
+            let (plugins, mut context) = self.plugins_and_context();
+            plugins.reload_changed(&mut context);
+        }
 
         // Resource manager might be locked by some other worker thread and it cannot be updated,
         // engine will try to update it in next frame. Resource update is just controls TTLs of
         // resource so it is not problem to defer update call.
         if let Ok(mut resource_manager) = self.resource_manager.try_lock() {
+            profile_scope!("ResourceManager");
             resource_manager.update(dt);
+
+            let reloaded = resource_manager.take_reloaded_resources();
+            if let Some(renderer) = self.renderer.as_mut() {
+                for texture in reloaded.textures.iter() {
+                    renderer.invalidate_texture(texture);
+                }
+                for cube_map in reloaded.cube_maps.iter() {
+                    renderer.invalidate_cube_map(cube_map);
+                }
+            }
+            if reloaded.scenes_outdated {
+                for scene in self.scenes.iter_mut() {
+                    scene.resolve();
+                }
+            }
         }
 
-        for scene in self.scenes.iter_mut() {
-            scene.update(frame_size, dt);
+        let mut scenes_time = Duration::default();
+        let mut physics_time = Duration::default();
+        let physics_enabled = self.physics_enabled;
+        let mut update_scenes = |scenes: &mut SceneContainer, bus: &mut MessageBus, dt: f32| {
+            let scenes_start = time::Instant::now();
+            for scene in scenes.iter_mut() {
+                scene.update_with_physics(frame_size, dt, physics_enabled);
+                physics_time += scene.physics_time();
+            }
+            scenes_time += scenes_start.elapsed();
+
+            if bus.has_subscribers::<Contact2DMessage>() {
+                let sender = bus.sender();
+                for (handle, scene) in scenes.pair_iter() {
+                    for contact in scene.physics2d.contacts() {
+                        sender.send_envelope(Envelope {
+                            targets: vec![contact.a, contact.b],
+                            message: Contact2DMessage {
+                                scene: handle,
+                                contact: *contact,
+                            },
+                        });
+                    }
+                }
+            }
+            bus.dispatch();
+        };
+
+        let step_count = match self.fixed_timestep.as_mut() {
+            Some(timestep) => {
+                let (step_count, step) = (timestep.advance(dt), timestep.step());
+                let alpha = timestep.alpha();
+                for _ in 0..step_count {
+                    self.update_plugins(step);
+                    fixed_update(&mut self.scenes, step);
+                    for scene in self.scenes.iter_mut() {
+                        scene.graph.save_previous_transforms();
+                    }
+                    update_scenes(&mut self.scenes, &mut self.message_bus, step);
+                }
+                for scene in self.scenes.iter_mut() {
+                    if self.interpolate_transforms {
+                        scene.graph.interpolate_transforms(alpha, frame_size);
+                    } else {
+                        scene.graph.restore_simulated_transforms();
+                    }
+                }
+                step_count
+            }
+            None => {
+                self.update_plugins(dt);
+                fixed_update(&mut self.scenes, dt);
+                update_scenes(&mut self.scenes, &mut self.message_bus, dt);
+                1
+            }
+        };
+        self.current_frame.scenes += scenes_time;
+        self.current_frame.physics += physics_time;
+        self.current_frame.steps += step_count;
+
+        if let Some(sound_context) = self.sound_context.as_ref() {
+            profile_scope!("AudioEnvironment");
+            let mut sound_context = sound_context.lock().unwrap();
+            for scene in self.scenes.iter_mut() {
+                scene.audio_environment.apply(&scene.graph, &mut sound_context);
+            }
         }
 
-        let time = time::Instant::now();
-        self.user_interface.update(frame_size, dt);
-        self.ui_time = time::Instant::now() - time;
+        if self.user_interface_enabled {
+            profile_scope!("UserInterface");
+            let time = time::Instant::now();
+            for viewport_ui in self.viewport_interfaces.iter_mut() {
+                viewport_ui.update(&self.scenes, frame_size, dt);
+            }
+            self.user_interface.update(frame_size, dt);
+            self.ui_time = time::Instant::now() - time;
+            self.current_frame.user_interface += self.ui_time;
+        }
+        self.current_frame.update += update_start.elapsed();
     }
 
     /// Performs rendering of single frame, must be called from your game loop, otherwise you won't
-    /// see anything.
+    /// see anything. Waits if frame rate is limited (see `set_frame_rate_limit`). Finishes
+    /// current frame of profiler and frame timings, see `utils::profiler` and `frame_timing`
+    /// docs. Headless engine draws nothing, but frame is finished as usual.
     #[inline]
     pub fn render(&mut self, dt: f32) -> Result<(), RendererError> {
-        self.user_interface.draw();
-        self.renderer.render_and_swap_buffers(
+        let render_start = time::Instant::now();
+        let result = if let (Some(renderer), Some(context)) =
+            (self.renderer.as_mut(), self.context.as_ref())
+        {
+            profile_scope!("Render");
+            // User interfaces are never drawn when they're disabled, so their drawing contexts
+            // are empty.
+            if self.user_interface_enabled {
+                profile_scope!("UserInterfaceDraw");
+                self.user_interface.draw();
+                for viewport_ui in self.viewport_interfaces.iter_mut() {
+                    viewport_ui.ui.draw();
+                }
+            }
+            let viewport_drawing_contexts = self
+                .viewport_interfaces
+                .iter()
+                .filter(|viewport_ui| viewport_ui.viewport().w > 0 && viewport_ui.viewport().h > 0)
+                .map(|viewport_ui| (viewport_ui.viewport(), viewport_ui.ui.get_drawing_context()))
+                .collect::<Vec<_>>();
+            renderer.render_and_swap_buffers(
+                &self.scenes,
+                &self.user_interface.get_drawing_context(),
+                &viewport_drawing_contexts,
+                context,
+                dt,
+            )
+        } else {
+            Ok(())
+        };
+        let render_time = render_start.elapsed();
+        let present = self.renderer.as_ref().map_or(0.0, |renderer| {
+            let statistics = renderer.get_statistics();
+            (statistics.capped_frame_time - statistics.pure_frame_time).max(0.0)
+        });
+        let present = Duration::from_secs_f32(present).min(render_time);
+        self.current_frame.render = render_time - present;
+        self.current_frame.present = present;
+        self.current_frame.limiter_wait = self.frame_limiter.wait();
+
+        let frame_end = time::Instant::now();
+        self.current_frame.frame = frame_end - self.last_frame_end;
+        self.last_frame_end = frame_end;
+        self.frame_timing_history.push(std::mem::take(&mut self.current_frame));
+
+        profiler::next_frame();
+        result
+    }
+
+    /// Renders current scenes and user interfaces without presenting them on screen and
+    /// returns pixels of the frame, see `Renderer::capture_frame`. Returns error if engine is
+    /// headless.
+    pub fn capture_frame(&mut self) -> Result<Texture, RendererError> {
+        let renderer = self.renderer.as_mut().ok_or(RendererError::NoRenderer)?;
+        if self.user_interface_enabled {
+            self.user_interface.draw();
+            for viewport_ui in self.viewport_interfaces.iter_mut() {
+                viewport_ui.ui.draw();
+            }
+        }
+        let viewport_drawing_contexts = self
+            .viewport_interfaces
+            .iter()
+            .filter(|viewport_ui| viewport_ui.viewport().w > 0 && viewport_ui.viewport().h > 0)
+            .map(|viewport_ui| (viewport_ui.viewport(), viewport_ui.ui.get_drawing_context()))
+            .collect::<Vec<_>>();
+        renderer.capture_frame(
             &self.scenes,
             &self.user_interface.get_drawing_context(),
-            &self.context,
-            dt,
+            &viewport_drawing_contexts,
         )
     }
+
+    /// Passes OS event to every viewport user interface, each of them will decide whether
+    /// event belongs to it or not. Returns true if any of user interfaces processed event.
+    /// Main user interface is not affected, its events must be passed as usual.
+    pub fn process_viewport_ui_event(&mut self, event: &OsEvent) -> bool {
+        let mut processed = false;
+        for viewport_ui in self.viewport_interfaces.iter_mut() {
+            processed |= viewport_ui.process_os_event(event);
+        }
+        processed
+    }
+}
+
+/// Renderer backend of engine, see `EngineBuilder::with_renderer_backend`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RendererBackend {
+    /// OpenGL 3.3 Core renderer which draws into a window.
+    OpenGl,
+    /// No window and no renderer, engine is headless. See module docs.
+    Headless,
+}
+
+impl Default for RendererBackend {
+    fn default() -> Self {
+        Self::OpenGl
+    }
+}
+
+/// Engine builder allows to choose subsystems of engine and their settings. By default it
+/// creates engine with every subsystem, like `Engine::new`. Headless engine has no sound by
+/// default.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rg3d::{
+///     engine::{Engine, EngineBuilder, RendererBackend},
+///     event_loop::EventLoop,
+///     gui::node::StubNode,
+///     window::WindowBuilder,
+/// };
+///
+/// // Game client with custom window.
+/// let event_loop = EventLoop::new();
+/// let client: Engine<(), StubNode> = EngineBuilder::new()
+///     .with_window_builder(WindowBuilder::new().with_title("Game"))
+///     .with_vsync(false)
+///     .with_resource_directory("data", 0)
+///     .build(Some(&event_loop))
+///     .unwrap();
+///
+/// // Dedicated server which does not need anything but simulation.
+/// let server: Engine<(), StubNode> = EngineBuilder::new()
+///     .with_renderer_backend(RendererBackend::Headless)
+///     .with_user_interface(false)
+///     .build(None)
+///     .unwrap();
+/// ```
+pub struct EngineBuilder {
+    window_builder: WindowBuilder,
+    renderer_backend: RendererBackend,
+    vsync: bool,
+    frame_size: Vec2,
+    sound: Option<bool>,
+    user_interface: bool,
+    physics: bool,
+    textures_path: Option<PathBuf>,
+    resource_directories: Vec<(PathBuf, i32)>,
+    plugins: Vec<Box<dyn Plugin>>,
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EngineBuilder {
+    /// Creates new builder with every subsystem enabled, except sound of headless engine.
+    pub fn new() -> Self {
+        Self {
+            window_builder: WindowBuilder::new(),
+            renderer_backend: Default::default(),
+            vsync: true,
+            frame_size: Vec2::new(800.0, 600.0),
+            sound: None,
+            user_interface: true,
+            physics: true,
+            textures_path: None,
+            resource_directories: Default::default(),
+            plugins: Default::default(),
+        }
+    }
+
+    /// Sets parameters of main window (title, size, fullscreen mode and so on). Ignored if
+    /// engine is headless.
+    pub fn with_window_builder(mut self, window_builder: WindowBuilder) -> Self {
+        self.window_builder = window_builder;
+        self
+    }
+
+    /// Sets desired renderer backend.
+    pub fn with_renderer_backend(mut self, backend: RendererBackend) -> Self {
+        self.renderer_backend = backend;
+        self
+    }
+
+    /// Sets whether vertical synchronization should be enabled, see `Engine::new_with_vsync`.
+    pub fn with_vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    /// Sets frame size of headless engine, windowed engine takes it from its window.
+    pub fn with_frame_size(mut self, frame_size: Vec2) -> Self {
+        self.frame_size = frame_size;
+        self
+    }
+
+    /// Sets whether engine should open sound device. Without sound `Engine::sound_context` is
+    /// `None`. By default sound is enabled unless renderer backend is `Headless`, dedicated
+    /// servers and tests usually have no sound device.
+    pub fn with_sound(mut self, sound: bool) -> Self {
+        self.sound = Some(sound);
+        self
+    }
+
+    /// Sets whether main and viewport user interfaces should be updated and drawn.
+    pub fn with_user_interface(mut self, user_interface: bool) -> Self {
+        self.user_interface = user_interface;
+        self
+    }
+
+    /// Sets whether physics of scenes should be simulated, see `Engine::set_physics_enabled`.
+    pub fn with_physics(mut self, physics: bool) -> Self {
+        self.physics = physics;
+        self
+    }
+
+    /// Sets path to directory with textures, see `ResourceManager::set_textures_path`.
+    pub fn with_textures_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.textures_path = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Mounts directory to virtual file system of resource manager, see
+    /// `Vfs::mount_directory`.
+    pub fn with_resource_directory<P: AsRef<Path>>(mut self, path: P, priority: i32) -> Self {
+        self.resource_directories
+            .push((path.as_ref().to_owned(), priority));
+        self
+    }
+
+    /// Adds custom subsystem to engine, it will be initialized right after engine is created.
+    /// See `plugin` module docs.
+    pub fn with_plugin(mut self, plugin: Box<dyn Plugin>) -> Self {
+        self.plugins.push(plugin);
+        self
+    }
+
+    /// Creates new engine. Events loop is required to create window for OpenGL renderer and
+    /// it is ignored if engine is headless.
+    pub fn build<M: MessageData, C: Control<M, C>>(
+        self,
+        events_loop: Option<&EventLoop<()>>,
+    ) -> Result<Engine<M, C>, EngineError> {
+        let (context, renderer, frame_size) = match self.renderer_backend {
+            RendererBackend::OpenGl => {
+                let events_loop = events_loop.ok_or(EngineError::NoEventLoop)?;
+                let context_wrapper: WindowedContext<NotCurrent> = glutin::ContextBuilder::new()
+                    .with_vsync(self.vsync)
+                    .with_srgb(true)
+                    .with_gl_profile(GlProfile::Core)
+                    .with_gl(GlRequest::Specific(Api::OpenGl, (3, 3)))
+                    .build_windowed(self.window_builder, events_loop)?;
+
+                let mut context = match unsafe { context_wrapper.make_current() } {
+                    Ok(context) => context,
+                    Err((_, e)) => return Err(EngineError::from(e)),
+                };
+
+                let client_size = context.window().inner_size();
+                let renderer = Renderer::new(&mut context, client_size.into())?;
+                let frame_size = Vec2::new(client_size.width as f32, client_size.height as f32);
+                (Some(context), Some(renderer), frame_size)
+            }
+            RendererBackend::Headless => (None, None, self.frame_size),
+        };
+        let sound = self
+            .sound
+            .unwrap_or(self.renderer_backend != RendererBackend::Headless);
+        let sound_context = if sound {
+            Some(Context::new()?)
+        } else {
+            None
+        };
+
+        let mut engine =
+            Engine::from_subsystems(context, renderer, sound_context, frame_size, self.vsync);
+        engine.user_interface_enabled = self.user_interface;
+        engine.physics_enabled = self.physics;
+        {
+            let mut resource_manager = engine.resource_manager.lock().unwrap();
+            if let Some(textures_path) = self.textures_path {
+                resource_manager.set_textures_path(textures_path);
+            }
+            for (path, priority) in self.resource_directories {
+                resource_manager.vfs_mut().mount_directory(path, priority);
+            }
+        }
+        for plugin in self.plugins {
+            engine.add_plugin(plugin);
+        }
+        Ok(engine)
+    }
 }
 
 impl<M: MessageData, C: Control<M, C>> Visit for Engine<M, C> {
@@ -152,7 +822,9 @@ impl<M: MessageData, C: Control<M, C>> Visit for Engine<M, C> {
         visitor.enter_region(name)?;
 
         if visitor.is_reading() {
-            self.renderer.flush();
+            if let Some(renderer) = self.renderer.as_mut() {
+                renderer.flush();
+            }
             self.resource_manager.lock().unwrap().update(0.0);
             self.scenes.clear();
         }
@@ -161,7 +833,24 @@ impl<M: MessageData, C: Control<M, C>> Visit for Engine<M, C> {
             .lock()?
             .visit("ResourceManager", visitor)?;
         self.scenes.visit("Scenes", visitor)?;
-        self.sound_context.lock()?.visit("SoundContext", visitor)?;
+        // Headless engine has no sound context, but it still writes the region, so its saves
+        // have the same layout and can be loaded by engine with sound and vice versa.
+        match self.sound_context.as_ref() {
+            Some(sound_context) => {
+                if visitor.is_reading() {
+                    // Saves of headless engine have empty sound context.
+                    let _ = sound_context.lock()?.visit("SoundContext", visitor);
+                } else {
+                    sound_context.lock()?.visit("SoundContext", visitor)?;
+                }
+            }
+            None => {
+                if !visitor.is_reading() {
+                    visitor.enter_region("SoundContext")?;
+                    visitor.leave_region()?;
+                }
+            }
+        }
 
         if visitor.is_reading() {
             self.resource_manager.lock()?.reload_resources();
@@ -173,3 +862,143 @@ impl<M: MessageData, C: Control<M, C>> Visit for Engine<M, C> {
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            math::vec2::Vec2,
+            visitor::{Visit, Visitor},
+        },
+        engine::{
+            plugin::{Plugin, PluginContext},
+            Engine, EngineBuilder, RendererBackend,
+        },
+        event::Event,
+        gui::node::StubNode,
+        scene::Scene,
+        sound::context::Context,
+    };
+    use std::sync::{Arc, Mutex};
+
+    type TestEngine = Engine<(), StubNode>;
+
+    fn frame_size() -> Vec2 {
+        Vec2::new(800.0, 600.0)
+    }
+
+    #[test]
+    fn headless_engine_updates_and_renders() {
+        let mut engine = TestEngine::new_headless(frame_size());
+        engine.scenes.add(Scene::new());
+        engine.update(1.0 / 60.0);
+
+        assert!(engine.is_headless());
+        assert!(engine.renderer().is_none());
+        assert!(engine.sound_context().is_none());
+        assert!(engine.render(1.0 / 60.0).is_ok());
+    }
+
+    #[test]
+    fn headless_save_can_be_loaded() {
+        let path = std::env::temp_dir().join("rg3d-headless-save-test.bin");
+        let mut engine = TestEngine::new_headless(frame_size());
+        engine.scenes.add(Scene::new());
+        let mut visitor = Visitor::new();
+        engine.visit("Engine", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        // Save must have the same layout as saves of engine with sound.
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        visitor.enter_region("Engine").unwrap();
+        visitor.enter_region("SoundContext").unwrap();
+
+        let mut headless = TestEngine::new_headless(frame_size());
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        headless.visit("Engine", &mut visitor).unwrap();
+        assert_eq!(headless.scenes.iter().count(), 1);
+
+        // Sound device could be missing on test machine.
+        if let Ok(sound_context) = Context::new() {
+            let mut with_sound =
+                TestEngine::from_subsystems(None, None, Some(sound_context), frame_size(), false);
+            let mut visitor = Visitor::load_binary(&path).unwrap();
+            with_sound.visit("Engine", &mut visitor).unwrap();
+            assert_eq!(with_sound.scenes.iter().count(), 1);
+        }
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn builder_disables_subsystems() {
+        let directory = std::env::temp_dir().join("rg3d-builder-test");
+        let engine = EngineBuilder::new()
+            .with_renderer_backend(RendererBackend::Headless)
+            .with_physics(false)
+            .with_user_interface(false)
+            .with_resource_directory(&directory, 0)
+            .build::<(), StubNode>(None)
+            .unwrap();
+
+        assert!(engine.is_headless());
+        assert!(!engine.is_physics_enabled());
+        assert!(!engine.is_user_interface_enabled());
+        // Headless engine has no sound by default.
+        assert!(engine.sound_context().is_none());
+        assert!(engine
+            .resource_manager
+            .lock()
+            .unwrap()
+            .vfs()
+            .mounted()
+            .any(|path| path == directory));
+    }
+
+    #[test]
+    fn builder_enables_subsystems_by_default() {
+        let engine = EngineBuilder::new()
+            .with_renderer_backend(RendererBackend::Headless)
+            .build::<(), StubNode>(None)
+            .unwrap();
+
+        assert!(engine.is_physics_enabled());
+        assert!(engine.is_user_interface_enabled());
+        assert!(engine.sound_context().is_none());
+    }
+
+    struct Recorder(Arc<Mutex<Vec<&'static str>>>);
+
+    impl Plugin for Recorder {
+        fn init(&mut self, _context: &mut PluginContext) {
+            self.0.lock().unwrap().push("init");
+        }
+
+        fn update(&mut self, _context: &mut PluginContext, _dt: f32) {
+            self.0.lock().unwrap().push("update");
+        }
+
+        fn on_event(&mut self, _context: &mut PluginContext, _event: &Event<()>) {
+            self.0.lock().unwrap().push("event");
+        }
+
+        fn shutdown(&mut self, _context: &mut PluginContext) {
+            self.0.lock().unwrap().push("shutdown");
+        }
+    }
+
+    #[test]
+    fn plugin_methods_are_called_in_order() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = TestEngine::new_headless(frame_size());
+        engine.add_plugin(Box::new(Recorder(calls.clone())));
+        engine.update(1.0 / 60.0);
+        engine.process_plugin_event(&Event::MainEventsCleared);
+        engine.remove_plugins();
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec!["init", "update", "event", "shutdown"]
+        );
+    }
+}
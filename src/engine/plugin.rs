@@ -0,0 +1,385 @@
+//! Contains plugin interface for game code.
+//!
+//! Plugin is a game module which is driven by engine: engine calls `Plugin::init` when plugin
+//! is added, `Plugin::update` before every update of scenes (every fixed step if fixed time
+//! step is enabled), `Plugin::on_event` for every event passed to `Engine::process_plugin_event`
+//! and `Plugin::shutdown` when plugins are removed. Every method gets `PluginContext` with
+//! access to scenes, resources, sound, renderer and message bus.
+//!
+//! Plugins can be added directly (`Engine::add_plugin`) or, with `hot_reload` feature, loaded
+//! from dynamic libraries (`Engine::load_plugin`). Library of a plugin must be built as `cdylib`
+//! and export its plugin using `export_plugin!` macro. Engine checks libraries of plugins on
+//! every update and reloads a library when it is rebuilt, so gameplay code can be changed
+//! without restarting a game:
+//!
+//! 1. State of the plugin is saved using `Plugin::visit_state`.
+//! 2. New version of the library is loaded and new instance of the plugin is created, then old
+//!    instance is dropped and old library is unloaded. Scenes and resources are owned by
+//!    engine, so they stay as is.
+//! 3. State is loaded into new instance using `Plugin::visit_state`, then `Plugin::on_reload`
+//!    is called instead of `init`.
+//!
+//! Library is copied to temporary directory before loading, so the original file can be
+//! overwritten by compiler while it is loaded.
+//!
+//! # Limitations
+//!
+//! Code of the library is unloaded on reload, so nothing that was created by the plugin and
+//! has code in the library (custom emitters, coroutines, closures, trait objects, message types
+//! of the plugin) can outlive its instance. Plugin must remove such objects when it is dropped,
+//! engine flushes message bus between dropping of the instance and unloading of the library.
+//! Library must be built by the same compiler with the same version of engine as the game.
+//! Library contains its own copy of engine code, so global state of engine (log, profiler,
+//! random number generator) used inside of the plugin is separate from the game's one.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::visitor::{Visit, VisitResult, Visitor},
+//!     engine::plugin::{Plugin, PluginContext},
+//!     export_plugin,
+//! };
+//!
+//! #[derive(Default)]
+//! struct Game {
+//!     time: f32,
+//! }
+//!
+//! impl Plugin for Game {
+//!     fn update(&mut self, _context: &mut PluginContext, dt: f32) {
+//!         self.time += dt;
+//!     }
+//!
+//!     fn visit_state(&mut self, visitor: &mut Visitor) -> VisitResult {
+//!         self.time.visit("Time", visitor)
+//!     }
+//! }
+//!
+//! export_plugin!(Game::default());
+//! ```
+
+use crate::{
+    core::visitor::{VisitResult, Visitor},
+    engine::{message_bus::MessageBus, resource_manager::ResourceManager},
+    event::Event,
+    renderer::Renderer,
+    scene::SceneContainer,
+    sound::context::Context,
+};
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "hot_reload")]
+use crate::{
+    core::visitor::VisitError,
+    utils::log::{Log, LogCategory},
+};
+#[cfg(any(test, feature = "hot_reload"))]
+use std::path::Path;
+#[cfg(feature = "hot_reload")]
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+/// Name of function which is exported by `export_plugin!` macro.
+pub const PLUGIN_ENTRY_SYMBOL: &[u8] = b"rg3d_plugin_entry";
+
+/// Signature of function which is exported by `export_plugin!` macro.
+pub type PluginEntry = unsafe extern "C" fn() -> *mut Box<dyn Plugin>;
+
+/// Engine subsystems available to plugins.
+pub struct PluginContext<'a> {
+    /// Scenes of engine.
+    pub scenes: &'a mut SceneContainer,
+    /// Resource manager of engine.
+    pub resource_manager: &'a Arc<Mutex<ResourceManager>>,
+    /// Sound context of engine, `None` if engine is headless.
+    pub sound_context: Option<&'a Arc<Mutex<Context>>>,
+    /// Message bus of engine.
+    pub message_bus: &'a mut MessageBus,
+    /// Renderer of engine, `None` if engine is headless.
+    pub renderer: Option<&'a mut Renderer>,
+}
+
+/// Game module driven by engine, see module docs.
+pub trait Plugin: 'static {
+    /// Called once when plugin is added to engine.
+    fn init(&mut self, _context: &mut PluginContext) {}
+
+    /// Called before every update of scenes with time step of the update.
+    fn update(&mut self, _context: &mut PluginContext, _dt: f32) {}
+
+    /// Called for every event passed to `Engine::process_plugin_event`.
+    fn on_event(&mut self, _context: &mut PluginContext, _event: &Event<()>) {}
+
+    /// Called once when plugin is removed from engine.
+    fn shutdown(&mut self, _context: &mut PluginContext) {}
+
+    /// Saves or loads state which must survive reloading of plugin's library.
+    fn visit_state(&mut self, _visitor: &mut Visitor) -> VisitResult {
+        Ok(())
+    }
+
+    /// Called instead of `init` when plugin was reloaded and its state was restored.
+    fn on_reload(&mut self, _context: &mut PluginContext) {}
+}
+
+/// Exports plugin from dynamic library, so it can be loaded by `Engine::load_plugin`. Takes
+/// expression which creates instance of the plugin.
+#[macro_export]
+macro_rules! export_plugin {
+    ($constructor:expr) => {
+        #[no_mangle]
+        pub extern "C" fn rg3d_plugin_entry() -> *mut Box<dyn $crate::engine::plugin::Plugin> {
+            let plugin: Box<dyn $crate::engine::plugin::Plugin> = Box::new($constructor);
+            Box::into_raw(Box::new(plugin))
+        }
+    };
+}
+
+/// Error that can occur while loading plugin from dynamic library.
+#[cfg(feature = "hot_reload")]
+#[derive(Debug)]
+pub enum PluginError {
+    /// Unable to read or copy the library.
+    Io(std::io::Error),
+    /// Unable to load the library or to find its entry point.
+    Library(libloading::Error),
+    /// Unable to save or load state of the plugin.
+    State(VisitError),
+}
+
+#[cfg(feature = "hot_reload")]
+impl From<std::io::Error> for PluginError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[cfg(feature = "hot_reload")]
+impl From<libloading::Error> for PluginError {
+    fn from(e: libloading::Error) -> Self {
+        Self::Library(e)
+    }
+}
+
+#[cfg(feature = "hot_reload")]
+impl From<VisitError> for PluginError {
+    fn from(e: VisitError) -> Self {
+        Self::State(e)
+    }
+}
+
+// Copies of libraries must not clash when the same library is loaded several times.
+#[cfg(feature = "hot_reload")]
+fn temporary_path(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "rg3d-plugin-{}-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+        name
+    ))
+}
+
+#[cfg(feature = "hot_reload")]
+struct PluginLibrary {
+    // Option is used to unload library before its copy is removed.
+    library: Option<libloading::Library>,
+    source: PathBuf,
+    copy: PathBuf,
+    modified: SystemTime,
+}
+
+#[cfg(feature = "hot_reload")]
+impl PluginLibrary {
+    fn load(source: &Path) -> Result<(Box<dyn Plugin>, Self), PluginError> {
+        let modified = std::fs::metadata(source)?.modified()?;
+        let name = source
+            .file_name()
+            .map_or_else(Default::default, |name| name.to_string_lossy());
+        let copy = temporary_path(&name);
+        std::fs::copy(source, &copy)?;
+        let mut library = Self {
+            library: None,
+            source: source.to_owned(),
+            copy,
+            modified,
+        };
+        let loaded = libloading::Library::new(&library.copy)?;
+        let plugin = unsafe {
+            let entry = loaded.get::<PluginEntry>(PLUGIN_ENTRY_SYMBOL)?;
+            *Box::from_raw(entry())
+        };
+        library.library = Some(loaded);
+        Ok((plugin, library))
+    }
+
+    fn is_changed(&self) -> bool {
+        std::fs::metadata(&self.source)
+            .and_then(|metadata| metadata.modified())
+            .map_or(false, |modified| modified != self.modified)
+    }
+}
+
+#[cfg(feature = "hot_reload")]
+impl Drop for PluginLibrary {
+    fn drop(&mut self) {
+        self.library.take();
+        let _ = std::fs::remove_file(&self.copy);
+    }
+}
+
+/// Saves state of given plugin to a file, see `Plugin::visit_state`.
+#[cfg(any(test, feature = "hot_reload"))]
+fn save_state(plugin: &mut dyn Plugin, path: &Path) -> VisitResult {
+    let mut visitor = Visitor::new();
+    visitor.enter_region("Plugin")?;
+    plugin.visit_state(&mut visitor)?;
+    visitor.leave_region()?;
+    visitor.save_binary(path)
+}
+
+/// Loads state of given plugin from a file written by `save_state`, file is removed.
+#[cfg(any(test, feature = "hot_reload"))]
+fn load_state(plugin: &mut dyn Plugin, path: &Path) -> VisitResult {
+    let result = Visitor::load_binary(path);
+    let _ = std::fs::remove_file(path);
+    let mut visitor = result?;
+    visitor.enter_region("Plugin")?;
+    plugin.visit_state(&mut visitor)?;
+    visitor.leave_region()
+}
+
+struct PluginInstance {
+    // Plugin must be dropped before its library.
+    plugin: Box<dyn Plugin>,
+    #[cfg(feature = "hot_reload")]
+    library: Option<PluginLibrary>,
+}
+
+#[cfg(feature = "hot_reload")]
+impl PluginInstance {
+    fn reload(&mut self, context: &mut PluginContext) -> Result<(), PluginError> {
+        let source = match self.library.as_mut() {
+            Some(library) if library.is_changed() => {
+                // Library can be written by compiler right now, it will be loaded again when
+                // it is changed next time.
+                library.modified = std::fs::metadata(&library.source)?.modified()?;
+                library.source.clone()
+            }
+            _ => return Ok(()),
+        };
+
+        let state_path = temporary_path("state");
+        save_state(self.plugin.as_mut(), &state_path)?;
+
+        // Nothing must fail between loading of new library and replacing of old one, otherwise
+        // new plugin would be dropped after its library.
+        let (plugin, library) = PluginLibrary::load(&source)?;
+        self.plugin = plugin;
+        // Messages of old plugin must be dropped while its code is still loaded.
+        context.message_bus.dispatch();
+        self.library = Some(library);
+
+        load_state(self.plugin.as_mut(), &state_path)?;
+        self.plugin.on_reload(context);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(in crate) struct PluginContainer {
+    instances: Vec<PluginInstance>,
+}
+
+impl PluginContainer {
+    pub(in crate) fn add(&mut self, mut plugin: Box<dyn Plugin>, context: &mut PluginContext) {
+        plugin.init(context);
+        self.instances.push(PluginInstance {
+            plugin,
+            #[cfg(feature = "hot_reload")]
+            library: None,
+        });
+    }
+
+    #[cfg(feature = "hot_reload")]
+    pub(in crate) fn load(
+        &mut self,
+        path: &Path,
+        context: &mut PluginContext,
+    ) -> Result<(), PluginError> {
+        let (mut plugin, library) = PluginLibrary::load(path)?;
+        plugin.init(context);
+        self.instances.push(PluginInstance {
+            plugin,
+            library: Some(library),
+        });
+        Ok(())
+    }
+
+    #[cfg(feature = "hot_reload")]
+    pub(in crate) fn reload_changed(&mut self, context: &mut PluginContext) {
+        for instance in self.instances.iter_mut() {
+            if let Err(e) = instance.reload(context) {
+                Log::err(
+                    LogCategory::General,
+                    format!("Unable to reload plugin. Reason: {:?}", e),
+                );
+            }
+        }
+    }
+
+    pub(in crate) fn update(&mut self, context: &mut PluginContext, dt: f32) {
+        for instance in self.instances.iter_mut() {
+            instance.plugin.update(context, dt);
+        }
+    }
+
+    pub(in crate) fn on_event(&mut self, context: &mut PluginContext, event: &Event<()>) {
+        for instance in self.instances.iter_mut() {
+            instance.plugin.on_event(context, event);
+        }
+    }
+
+    pub(in crate) fn clear(&mut self, context: &mut PluginContext) {
+        for mut instance in self.instances.drain(..) {
+            instance.plugin.shutdown(context);
+            drop(instance.plugin);
+            context.message_bus.dispatch();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::visitor::{Visit, VisitResult, Visitor},
+        engine::plugin::{load_state, save_state, Plugin},
+    };
+
+    #[derive(Default)]
+    struct Game {
+        time: f32,
+    }
+
+    impl Plugin for Game {
+        fn visit_state(&mut self, visitor: &mut Visitor) -> VisitResult {
+            self.time.visit("Time", visitor)
+        }
+    }
+
+    #[test]
+    fn plugin_state_is_restored_after_reload() {
+        let path = std::env::temp_dir().join("rg3d-plugin-state-test.bin");
+        let mut old = Game { time: 1.5 };
+        save_state(&mut old, &path).unwrap();
+
+        let mut new = Game::default();
+        load_state(&mut new, &path).unwrap();
+        assert_eq!(new.time, 1.5);
+        assert!(!path.exists());
+    }
+}
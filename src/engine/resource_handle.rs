@@ -0,0 +1,162 @@
+//! Contains handles of resources that are loaded in background.
+//!
+//! Handle is returned by asynchronous methods of resource manager (such as
+//! `ResourceManager::load_texture_async`), it does not block the thread that requested a
+//! resource and allows to check state of loading at any time. This is useful for loading
+//! screens: request everything that is needed, then check handles each frame to show
+//! progress and report failures.
+//!
+//! Handle also implements `Future`, so it can be awaited in async code with any executor.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::engine::{
+//!     resource_handle::{ResourceHandle, ResourceState},
+//!     resource_manager::SharedModel,
+//! };
+//!
+//! fn update_loading_screen(handles: &[ResourceHandle<SharedModel>]) -> bool {
+//!     let mut loaded = 0;
+//!     for handle in handles {
+//!         match handle.state() {
+//!             ResourceState::Pending => (),
+//!             ResourceState::Ok(_) => loaded += 1,
+//!             ResourceState::Failed(reason) => println!("Failed to load a model: {}", reason),
+//!         }
+//!     }
+//!     println!("Loaded {} of {}", loaded, handles.len());
+//!     handles.iter().all(|handle| !handle.is_pending())
+//! }
+//! ```
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// State of a resource which is loaded in background.
+#[derive(Clone, Debug)]
+pub enum ResourceState<T> {
+    /// Resource is still loading.
+    Pending,
+    /// Resource is loaded successfully.
+    Ok(T),
+    /// Resource has failed to load, string contains description of the reason.
+    Failed(String),
+}
+
+impl<T> ResourceState<T> {
+    /// Returns true if resource is still loading.
+    pub fn is_pending(&self) -> bool {
+        match self {
+            ResourceState::Pending => true,
+            _ => false,
+        }
+    }
+}
+
+struct HandleData<T> {
+    state: ResourceState<T>,
+    wakers: Vec<Waker>,
+}
+
+/// See module docs.
+pub struct ResourceHandle<T> {
+    shared: Arc<(Mutex<HandleData<T>>, Condvar)>,
+}
+
+impl<T> Clone for ResourceHandle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: Clone> ResourceHandle<T> {
+    pub(in crate) fn pending() -> Self {
+        Self {
+            shared: Arc::new((
+                Mutex::new(HandleData {
+                    state: ResourceState::Pending,
+                    wakers: Vec::new(),
+                }),
+                Condvar::new(),
+            )),
+        }
+    }
+
+    /// Sets final state of the handle and wakes everyone who waits for it.
+    pub(in crate) fn resolve(&self, state: ResourceState<T>) {
+        let (data, condvar) = &*self.shared;
+        let mut data = data.lock().unwrap();
+        data.state = state;
+        for waker in data.wakers.drain(..) {
+            waker.wake();
+        }
+        condvar.notify_all();
+    }
+
+    /// Returns current state of the resource.
+    pub fn state(&self) -> ResourceState<T> {
+        self.shared.0.lock().unwrap().state.clone()
+    }
+
+    /// Returns true if resource is still loading.
+    pub fn is_pending(&self) -> bool {
+        self.shared.0.lock().unwrap().state.is_pending()
+    }
+
+    /// Blocks current thread until resource is loaded or has failed to load.
+    pub fn wait(&self) -> Result<T, String> {
+        let (data, condvar) = &*self.shared;
+        let mut data = data.lock().unwrap();
+        while data.state.is_pending() {
+            data = condvar.wait(data).unwrap();
+        }
+        match &data.state {
+            ResourceState::Ok(resource) => Ok(resource.clone()),
+            ResourceState::Failed(reason) => Err(reason.clone()),
+            ResourceState::Pending => unreachable!(),
+        }
+    }
+}
+
+impl<T: Clone> Future for ResourceHandle<T> {
+    type Output = Result<T, String>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut data = self.shared.0.lock().unwrap();
+        match &data.state {
+            ResourceState::Pending => {
+                data.wakers.push(cx.waker().clone());
+                Poll::Pending
+            }
+            ResourceState::Ok(resource) => Poll::Ready(Ok(resource.clone())),
+            ResourceState::Failed(reason) => Poll::Ready(Err(reason.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::resource_handle::{ResourceHandle, ResourceState};
+
+    #[test]
+    fn wait_for_resolve_from_other_thread() {
+        let handle = ResourceHandle::<u32>::pending();
+        assert!(handle.is_pending());
+
+        let loader_handle = handle.clone();
+        let thread = std::thread::spawn(move || loader_handle.resolve(ResourceState::Ok(42)));
+        assert_eq!(handle.wait(), Ok(42));
+        thread.join().unwrap();
+
+        let failed = ResourceHandle::<u32>::pending();
+        failed.resolve(ResourceState::Failed("no such file".to_owned()));
+        assert_eq!(failed.wait(), Err("no such file".to_owned()));
+    }
+}
@@ -1,16 +1,90 @@
 //! Resource manager controls loading and lifetime of resource in the engine.
+//!
+//! # Hot reload
+//!
+//! Resource manager can watch files of loaded textures, models, prefabs and sound buffers and
+//! reload resources in-place when their files are changed, so changes made in external tools
+//! are visible without restarting the game. Contents of shared resources are replaced, so
+//! every user of a resource sees new version immediately, and scenes are re-synchronized with
+//! reloaded models and prefabs. Hot reload is disabled by default, because it checks
+//! modification time of every file periodically, use `set_hot_reload_enabled` to enable it
+//! during development. Gradients are always reloaded, see `gradient` module docs.
+//!
+//! Shaders are built into the engine, so they are not watched. Nodes added to a model after
+//! its instances were created are not added to the instances.
+//!
+//! # Virtual file system
+//!
+//! Every resource is read using virtual file system of resource manager, so resources can be
+//! packed into archives and overridden by mods, see `vfs` module docs.
+//!
+//! # Custom resources
+//!
+//! Resource types defined by games can be registered in resource manager, see
+//! `custom_resource` module docs.
+//!
+//! # Loading progress
+//!
+//! Resources that are loaded in background are tracked by resource manager, so games can
+//! show loading bars, see `loading_progress` module docs.
+//!
+//! # Unloading and memory budgets
+//!
+//! Resource is unused when nobody except resource manager has a reference to it. Unused
+//! resources are unloaded when they stay unused for `MAX_RESOURCE_TTL` seconds, or
+//! immediately by `unload_unused` (for example when a level is changed). Textures and meshes
+//! can have memory budgets (`set_memory_budgets`): when memory used by them exceeds a budget,
+//! unused textures and models are unloaded in least recently used order until memory usage
+//! fits into the budget. Resources that are in use are never unloaded, so budget can be
+//! exceeded if all resources are used. Current memory usage is returned by `memory_usage`.
+//!
+//! # Loader threads
+//!
+//! Resources are loaded in background by a pool of loader threads, size of the pool and
+//! priorities of loading can be changed, see `loader_pool` module docs.
+//!
+//! # Import options
+//!
+//! Settings of textures and models can be stored in sidecar files next to them, such settings
+//! override options of requests, see `import_options` module docs.
+//!
+//! # Dependencies
+//!
+//! Resource manager can tell which loaded models and prefabs use a resource and which
+//! resources are used by a model or a prefab, see `dependencies` module docs.
 
 use crate::{
     core::visitor::{Visit, VisitResult, Visitor},
-    resource::{model::Model, texture::Texture, texture::TextureKind},
+    engine::{
+        custom_resource::{self, AnyResource, ResourceType, SharedResource},
+        dependencies::ResourceId,
+        import_options::TextureImportOptions,
+        loader_pool::{LoadPriority, LoaderExecutor, LoaderPool, TaskSpawner},
+        loading_progress::{LoadingProgress, ProgressTracker},
+        resource_handle::{ResourceHandle, ResourceState},
+        vfs::Vfs,
+    },
+    resource::{
+        atlas::{TextureAtlas, TextureAtlasBuilder},
+        cube_map::{CubeMap, CubeMapSource},
+        fbx::FbxImportOptions,
+        gradient::GradientResource,
+        model::Model,
+        prefab::Prefab,
+        texture::{Texture, TextureError, TextureKind, TextureSampler},
+        wav::WavMetadata,
+    },
+    scene::node::Node,
     sound::buffer::{DataSource, SoundBuffer},
-    utils::log::Log,
+    utils::log::{Log, LogCategory},
 };
 use std::{
+    any::TypeId,
+    collections::HashMap,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
     sync::{Arc, Mutex},
-    time,
+    time::{self, SystemTime},
 };
 
 /// Resource container with fixed TTL (time-to-live). Resource will be removed
@@ -81,27 +155,211 @@ pub type SharedTexture = Arc<Mutex<Texture>>;
 pub type SharedModel = Arc<Mutex<Model>>;
 /// Type alias for Arc<Mutex<SoundBuffer>> to make code less noisy.
 pub type SharedSoundBuffer = Arc<Mutex<SoundBuffer>>;
+/// Type alias for Arc<Mutex<GradientResource>> to make code less noisy.
+pub type SharedGradient = Arc<Mutex<GradientResource>>;
+/// Type alias for Arc<Mutex<Prefab>> to make code less noisy.
+pub type SharedPrefab = Arc<Mutex<Prefab>>;
+/// Type alias for Arc<Mutex<CubeMap>> to make code less noisy.
+pub type SharedCubeMap = Arc<Mutex<CubeMap>>;
+
+/// Options of newly loaded textures. Textures are shared, so options are applied only when
+/// texture is loaded, settings of already loaded texture can be changed using its methods.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureRequestOptions {
+    /// Sampler settings of texture, default sampler of resource manager is used if this is
+    /// `None`, see `ResourceManager::set_default_texture_sampler`.
+    pub sampler: Option<TextureSampler>,
+    /// Whether texels of texture are in sRGB color space or not. It must be `true` for color
+    /// textures (diffuse maps, sprites, UI images) and `false` for textures with data (normal
+    /// maps, height maps, masks), see `texture` module docs.
+    pub srgb: bool,
+}
+
+impl Default for TextureRequestOptions {
+    fn default() -> Self {
+        Self {
+            sampler: None,
+            srgb: true,
+        }
+    }
+}
+
+/// Amount of memory in bytes used by resources of resource manager.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MemoryUsage {
+    /// Memory used by pixels of all mip levels of textures.
+    pub textures: usize,
+    /// Memory used by vertices and triangles of meshes of models.
+    pub meshes: usize,
+}
+
+/// Memory budgets in bytes of resource categories, see module docs. `None` means that
+/// category is not limited.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MemoryBudgets {
+    /// Maximum amount of memory used by textures.
+    pub textures: Option<usize>,
+    /// Maximum amount of memory used by meshes of models.
+    pub meshes: Option<usize>,
+}
+
+fn is_unused<T>(resource: &Arc<T>) -> bool {
+    Arc::strong_count(resource) == 1
+}
+
+fn texture_memory_usage(texture: &SharedTexture) -> usize {
+    texture.lock().unwrap().bytes.len()
+}
+
+fn model_memory_usage(model: &SharedModel) -> usize {
+    let model = model.lock().unwrap();
+    let mut usage = 0;
+    for node in model.get_scene().graph.linear_iter() {
+        if let Node::Mesh(mesh) = node {
+            for surface in mesh.surfaces() {
+                usage += surface.data().lock().unwrap().memory_usage();
+            }
+        }
+    }
+    usage
+}
+
+/// Removes unused resources in least recently used order until memory used by resources
+/// fits into budget. Returns removed resources.
+fn evict<T>(
+    entries: &mut Vec<TimedEntry<Arc<T>>>,
+    budget: usize,
+    memory_usage: fn(&Arc<T>) -> usize,
+) -> Vec<Arc<T>> {
+    let sizes = entries
+        .iter()
+        .map(|entry| memory_usage(&entry.value))
+        .collect::<Vec<_>>();
+    let mut usage = sizes.iter().sum::<usize>();
+    if usage <= budget {
+        return Vec::new();
+    }
+
+    // Time to live of unused resource is decreasing, so resource with smallest time to live
+    // is the least recently used one.
+    let mut candidates = (0..entries.len())
+        .filter(|&i| is_unused(&entries[i].value))
+        .collect::<Vec<_>>();
+    candidates.sort_by(|&a, &b| {
+        entries[a]
+            .time_to_live
+            .partial_cmp(&entries[b].time_to_live)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut evicted = vec![false; entries.len()];
+    for i in candidates {
+        if usage <= budget {
+            break;
+        }
+        usage -= sizes[i];
+        evicted[i] = true;
+    }
+
+    let mut removed = Vec::new();
+    let mut i = 0;
+    entries.retain(|entry| {
+        let retain = !evicted[i];
+        if !retain {
+            removed.push(entry.value.clone());
+        }
+        i += 1;
+        retain
+    });
+    removed
+}
+
+/// Resources that were reloaded by hot reload and require actions from engine.
+#[derive(Default)]
+pub(in crate) struct ReloadedResources {
+    /// Textures which GPU copies are outdated (texture was reloaded or unloaded).
+    pub textures: Vec<SharedTexture>,
+    /// Cube maps which were unloaded, so their GPU copies must be freed.
+    pub cube_maps: Vec<SharedCubeMap>,
+    /// True if a model or a prefab was reloaded, so scenes must be resolved.
+    pub scenes_outdated: bool,
+}
+
+/// Remembers modification time of given file and returns true if it differs from previous
+/// one. File seen for the first time is not considered changed.
+fn is_file_changed(
+    vfs: &Vfs,
+    modification_times: &mut HashMap<PathBuf, SystemTime>,
+    path: &Path,
+) -> bool {
+    if path.as_os_str().is_empty() {
+        return false;
+    }
+    match vfs.modification_time(path) {
+        Some(modified) => match modification_times.insert(path.to_owned(), modified) {
+            Some(previous) => previous != modified,
+            None => false,
+        },
+        None => false,
+    }
+}
 
 /// See module docs.
 pub struct ResourceManager {
     textures: Vec<TimedEntry<SharedTexture>>,
     models: Vec<TimedEntry<SharedModel>>,
     sound_buffers: Vec<TimedEntry<SharedSoundBuffer>>,
+    gradients: Vec<TimedEntry<SharedGradient>>,
+    prefabs: Vec<TimedEntry<SharedPrefab>>,
+    cube_maps: Vec<TimedEntry<SharedCubeMap>>,
+    // Time left until next check of modification time of gradient files.
+    gradient_reload_timer: f32,
+    hot_reload: bool,
+    // Time left until next check of modification time of files of other resources.
+    hot_reload_timer: f32,
+    modification_times: HashMap<PathBuf, SystemTime>,
+    reloaded: ReloadedResources,
+    progress: ProgressTracker,
+    vfs: Vfs,
+    resource_types: HashMap<TypeId, ResourceType>,
+    default_texture_sampler: TextureSampler,
+    memory_budgets: MemoryBudgets,
+    loader: Arc<dyn LoaderExecutor>,
+    load_priority: LoadPriority,
     /// Path to textures, extensively used for resource files which stores path in weird
     /// format (either relative or absolute) which is obviously not good for engine.
     textures_path: PathBuf,
+    fbx_import_options: FbxImportOptions,
 }
 
 impl ResourceManager {
     /// Lifetime of orphaned resource in seconds (with only one strong ref which is resource manager itself)
     pub const MAX_RESOURCE_TTL: f32 = 20.0;
 
+    /// Interval in seconds between checks of modification time of hot-reloadable resources.
+    pub const HOT_RELOAD_CHECK_INTERVAL: f32 = 1.0;
+
     pub(in crate::engine) fn new() -> Self {
         Self {
             textures: Vec::new(),
             models: Vec::new(),
             sound_buffers: Vec::new(),
+            gradients: Vec::new(),
+            prefabs: Vec::new(),
+            cube_maps: Vec::new(),
+            gradient_reload_timer: Self::HOT_RELOAD_CHECK_INTERVAL,
+            hot_reload: false,
+            hot_reload_timer: Self::HOT_RELOAD_CHECK_INTERVAL,
+            modification_times: Default::default(),
+            reloaded: Default::default(),
+            progress: Default::default(),
+            vfs: Default::default(),
+            resource_types: Default::default(),
+            default_texture_sampler: Default::default(),
+            memory_budgets: Default::default(),
+            loader: Arc::new(LoaderPool::default()),
+            load_priority: Default::default(),
             textures_path: PathBuf::from("data/textures/"),
+            fbx_import_options: Default::default(),
         }
     }
 
@@ -113,33 +371,61 @@ impl ResourceManager {
         &mut self,
         path: P,
         kind: TextureKind,
+    ) -> SharedTexture {
+        self.request_texture_async_with_options(path, kind, Default::default())
+    }
+
+    /// Same as `request_texture_async`, but newly loaded texture will use given options.
+    pub fn request_texture_async_with_options<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        kind: TextureKind,
+        options: TextureRequestOptions,
     ) -> SharedTexture {
         if let Some(texture) = self.find_texture(path.as_ref()) {
             return texture;
         }
 
-        let texture = Arc::new(Mutex::new(Texture::default()));
+        let path = PathBuf::from(path.as_ref());
+        // Loading task could wait in queue, so texture must not be drawn until it is loaded.
+        let texture = Arc::new(Mutex::new(Texture {
+            path: path.clone(),
+            loaded: false,
+            ..Default::default()
+        }));
         self.textures.push(TimedEntry {
             value: texture.clone(),
             time_to_live: Self::MAX_RESOURCE_TTL,
         });
         let result = texture.clone();
 
-        let path = PathBuf::from(path.as_ref());
-        std::thread::spawn(move || {
+        let progress = self.progress.begin(&self.vfs, &path);
+        let vfs = self.vfs.clone();
+        let default_sampler = self.default_texture_sampler;
+        self.task_spawner().spawn(move || {
             if let Ok(mut texture) = texture.lock() {
                 let time = time::Instant::now();
-                match Texture::load_from_file(&path, kind) {
-                    Ok(raw_texture) => {
+                let (kind, options) = TextureImportOptions::load(&path, &vfs).apply(kind, options);
+                let result = progress
+                    .read_file(&vfs, &path)
+                    .map_err(TextureError::from)
+                    .and_then(|data| Texture::load_from_memory(&path, &data, kind));
+                progress.finish(result.is_ok());
+                match result {
+                    Ok(mut raw_texture) => {
+                        raw_texture.sampler = options.sampler.unwrap_or(default_sampler);
+                        raw_texture.srgb = options.srgb;
                         *texture = raw_texture;
-                        Log::writeln(format!(
-                            "Texture {:?} is loaded in {:?}!",
-                            path,
-                            time.elapsed()
-                        ));
+                        Log::info(
+                            LogCategory::Resource,
+                            format!("Texture {:?} is loaded in {:?}!", path, time.elapsed()),
+                        );
                     }
                     Err(e) => {
-                        Log::writeln(format!("Unable to load texture {:?}! Reason {}", path, e));
+                        Log::err(
+                            LogCategory::Resource,
+                            format!("Unable to load texture {:?}! Reason {}", path, e),
+                        );
                     }
                 }
             }
@@ -148,6 +434,300 @@ impl ResourceManager {
         result
     }
 
+    /// Starts loading of texture in background thread and returns handle which can be used to
+    /// track state of loading, see `resource_handle` module docs. Texture is decoded without
+    /// locking resource manager, manager is locked only to start tracking of loading progress
+    /// and to register loaded texture. Existing instance is returned if texture is already
+    /// loaded. Newly loaded texture is treated as color texture, see `request_texture`.
+    pub fn load_texture_async<P: AsRef<Path>>(
+        manager: &Arc<Mutex<Self>>,
+        path: P,
+        kind: TextureKind,
+    ) -> ResourceHandle<SharedTexture> {
+        let handle = ResourceHandle::pending();
+        let result = handle.clone();
+        let path = path.as_ref().to_owned();
+        let (progress, vfs, sampler, spawner) = {
+            let manager = manager.lock().unwrap();
+            (
+                manager.progress.begin(&manager.vfs, &path),
+                manager.vfs.clone(),
+                manager.default_texture_sampler,
+                manager.task_spawner(),
+            )
+        };
+        let manager = manager.clone();
+        spawner.spawn(move || {
+            if let Some(texture) = manager.lock().unwrap().find_texture(&path) {
+                progress.finish(true);
+                handle.resolve(ResourceState::Ok(texture));
+                return;
+            }
+
+            let (kind, options) =
+                TextureImportOptions::load(&path, &vfs).apply(kind, Default::default());
+            let texture = progress
+                .read_file(&vfs, &path)
+                .map_err(TextureError::from)
+                .and_then(|data| Texture::load_from_memory(&path, &data, kind));
+            progress.finish(texture.is_ok());
+            match texture {
+                Ok(mut texture) => {
+                    texture.sampler = options.sampler.unwrap_or(sampler);
+                    texture.srgb = options.srgb;
+                    let mut manager = manager.lock().unwrap();
+                    // Same texture could be loaded by someone else while we were decoding it.
+                    let texture = manager.find_texture(&path).unwrap_or_else(|| {
+                        let texture = Arc::new(Mutex::new(texture));
+                        manager.textures.push(TimedEntry {
+                            value: texture.clone(),
+                            time_to_live: Self::MAX_RESOURCE_TTL,
+                        });
+                        Log::info(
+                            LogCategory::Resource,
+                            format!("Texture {:?} is loaded!", path),
+                        );
+                        texture
+                    });
+                    handle.resolve(ResourceState::Ok(texture));
+                }
+                Err(e) => {
+                    let reason = format!("Unable to load texture {:?}! Reason {}", path, e);
+                    Log::err(LogCategory::Resource, reason.clone());
+                    handle.resolve(ResourceState::Failed(reason));
+                }
+            }
+        });
+        result
+    }
+
+    /// Starts loading of model in background thread and returns handle which can be used to
+    /// track state of loading, see `resource_handle` module docs. Resource manager is locked
+    /// while model is loading (because model requests its textures), so use `try_lock` to
+    /// access resource manager while models are loading to not block current thread. Manager
+    /// is also locked by this method for a short time to start tracking of loading progress.
+    pub fn load_model_async<P: AsRef<Path>>(
+        manager: &Arc<Mutex<Self>>,
+        path: P,
+    ) -> ResourceHandle<SharedModel> {
+        let handle = ResourceHandle::pending();
+        let result = handle.clone();
+        let path = path.as_ref().to_owned();
+        let (progress, spawner) = {
+            let manager = manager.lock().unwrap();
+            (
+                manager.progress.begin(&manager.vfs, &path),
+                manager.task_spawner(),
+            )
+        };
+        let manager = manager.clone();
+        spawner.spawn(move || {
+            let mut manager = manager.lock().unwrap();
+            let model = manager.load_model(&path);
+            progress.finish(model.is_ok());
+            handle.resolve(match model {
+                Ok(model) => ResourceState::Ok(model),
+                Err(reason) => ResourceState::Failed(reason),
+            });
+        });
+        result
+    }
+
+    /// Registers resource type with given loader, resources of this type can be requested from
+    /// files with given extensions, see `custom_resource` module docs. Loader gets path and
+    /// contents of file and returns either resource or description of error. Registering the
+    /// same type again replaces its loader and forgets its loaded resources.
+    pub fn register_resource_type<T, F>(&mut self, extensions: &[&str], loader: F)
+    where
+        T: Send + 'static,
+        F: Fn(&Path, &[u8]) -> Result<T, String> + Send + Sync + 'static,
+    {
+        self.resource_types
+            .insert(TypeId::of::<T>(), ResourceType::new(extensions, loader));
+    }
+
+    /// Returns true if resources of given type can be requested.
+    pub fn is_resource_type_registered<T: Send + 'static>(&self) -> bool {
+        self.resource_types.contains_key(&TypeId::of::<T>())
+    }
+
+    fn load_any_resource(&mut self, type_id: TypeId, path: &Path) -> Result<AnyResource, String> {
+        let vfs = &self.vfs;
+        let resource_type = self
+            .resource_types
+            .get_mut(&type_id)
+            .ok_or_else(|| "Resource type is not registered!".to_owned())?;
+        if let Some(resource) = resource_type.find(path) {
+            return Ok(resource);
+        }
+        if !resource_type.supports(path) {
+            return Err(format!(
+                "Resource {:?} has extension which is not registered for {}!",
+                path, resource_type.name
+            ));
+        }
+        let data = vfs
+            .read(path)
+            .map_err(|e| format!("Unable to load resource {:?}! Reason {}", path, e))?;
+        let resource = (resource_type.loader)(path, &data)
+            .map_err(|e| format!("Unable to load resource {:?}! Reason {}", path, e))?;
+        Log::info(
+            LogCategory::Resource,
+            format!("Resource {:?} is loaded!", path),
+        );
+        Ok(resource_type.register(path, resource))
+    }
+
+    /// Tries to load resource of custom type from given path or get instance of existing, if
+    /// any. This method is **blocking**, so it will block current thread until resource is
+    /// loading. On failure it returns None and prints failure reason to log.
+    pub fn request_resource<T, P>(&mut self, path: P) -> Option<SharedResource<T>>
+    where
+        T: Send + 'static,
+        P: AsRef<Path>,
+    {
+        match self.load_any_resource(TypeId::of::<T>(), path.as_ref()) {
+            Ok(resource) => Some(custom_resource::downcast(resource)),
+            Err(reason) => {
+                Log::err(LogCategory::Resource, reason);
+                None
+            }
+        }
+    }
+
+    /// Tries to load resource of any registered type from given path, type is selected by
+    /// extension of file. Returned resource can be downcasted to `Mutex<T>`. It is useful for
+    /// tools that work with files of any type, games should use `request_resource`.
+    pub fn request_any_resource<P: AsRef<Path>>(&mut self, path: P) -> Option<AnyResource> {
+        let type_id = self
+            .resource_types
+            .iter()
+            .find(|(_, resource_type)| resource_type.supports(path.as_ref()))
+            .map(|(type_id, _)| *type_id);
+        let result = match type_id {
+            Some(type_id) => self.load_any_resource(type_id, path.as_ref()),
+            None => Err(format!(
+                "There is no resource type for {:?} resource!",
+                path.as_ref()
+            )),
+        };
+        match result {
+            Ok(resource) => Some(resource),
+            Err(reason) => {
+                Log::err(LogCategory::Resource, reason);
+                None
+            }
+        }
+    }
+
+    /// Tries to find resource of custom type by its path. Returns None if no such resource
+    /// was found.
+    pub fn find_resource<T, P>(&self, path: P) -> Option<SharedResource<T>>
+    where
+        T: Send + 'static,
+        P: AsRef<Path>,
+    {
+        self.resource_types
+            .get(&TypeId::of::<T>())?
+            .find(path.as_ref())
+            .map(custom_resource::downcast)
+    }
+
+    /// Starts loading of resource of custom type in background thread and returns handle
+    /// which can be used to track state of loading, see `resource_handle` module docs.
+    /// Resource is loaded without locking resource manager, manager is locked only to start
+    /// tracking of loading progress and to register loaded resource.
+    pub fn load_resource_async<T, P>(
+        manager: &Arc<Mutex<Self>>,
+        path: P,
+    ) -> ResourceHandle<SharedResource<T>>
+    where
+        T: Send + 'static,
+        P: AsRef<Path>,
+    {
+        let handle = ResourceHandle::pending();
+        let result = handle.clone();
+        let path = path.as_ref().to_owned();
+        let type_id = TypeId::of::<T>();
+
+        let prepared = {
+            let manager = manager.lock().unwrap();
+            match manager.resource_types.get(&type_id) {
+                Some(resource_type) => {
+                    if let Some(resource) = resource_type.find(&path) {
+                        Err(ResourceState::Ok(custom_resource::downcast(resource)))
+                    } else if !resource_type.supports(&path) {
+                        Err(ResourceState::Failed(format!(
+                            "Resource {:?} has extension which is not registered for {}!",
+                            path, resource_type.name
+                        )))
+                    } else {
+                        Ok((
+                            manager.progress.begin(&manager.vfs, &path),
+                            manager.vfs.clone(),
+                            resource_type.loader.clone(),
+                            manager.task_spawner(),
+                        ))
+                    }
+                }
+                None => Err(ResourceState::Failed(
+                    "Resource type is not registered!".to_owned(),
+                )),
+            }
+        };
+
+        let (progress, vfs, loader, spawner) = match prepared {
+            Ok(prepared) => prepared,
+            Err(state) => {
+                handle.resolve(state);
+                return result;
+            }
+        };
+
+        let manager = manager.clone();
+        spawner.spawn(move || {
+            let resource = progress
+                .read_file(&vfs, &path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| loader(&path, &data));
+            progress.finish(resource.is_ok());
+            match resource {
+                Ok(resource) => {
+                    let mut manager = manager.lock().unwrap();
+                    // Types can't be unregistered, but registering the type again while we
+                    // were loading replaces its cache, so resource goes to the new one.
+                    let resource = manager
+                        .resource_types
+                        .get_mut(&type_id)
+                        .expect("Resource types can't be unregistered!")
+                        .register(&path, resource);
+                    Log::info(
+                        LogCategory::Resource,
+                        format!("Resource {:?} is loaded!", path),
+                    );
+                    handle.resolve(ResourceState::Ok(custom_resource::downcast(resource)));
+                }
+                Err(e) => {
+                    let reason = format!("Unable to load resource {:?}! Reason {}", path, e);
+                    Log::err(LogCategory::Resource, reason.clone());
+                    handle.resolve(ResourceState::Failed(reason));
+                }
+            }
+        });
+        result
+    }
+
+    /// Returns snapshot of loading progress of resources that are loaded in background.
+    pub fn loading_progress(&self) -> LoadingProgress {
+        self.progress.progress()
+    }
+
+    /// Returns tracker of loading progress, which can be used to query progress without
+    /// locking resource manager, see `loading_progress` module docs.
+    pub fn progress_tracker(&self) -> ProgressTracker {
+        self.progress.clone()
+    }
+
     /// Tries to load texture from given path or get instance of existing, if any. This method is
     /// **blocking**, so it will block current thread until texture is loading. On failure it
     /// returns None and prints failure reason to log.
@@ -155,32 +735,75 @@ impl ResourceManager {
     /// # Supported formats
     ///
     /// To load images and decode them, rg3d uses image create which supports following image
-    /// formats: png, tga, bmp, dds, jpg, gif, tiff, dxt.
+    /// formats: png, tga, bmp, jpg, gif, tiff. Compressed textures are loaded from DDS and
+    /// KTX2 files, see `texture` module docs.
+    ///
+    /// Newly loaded texture is treated as color texture (sRGB) and gets default texture
+    /// sampler, use `request_texture_with_options` to load textures with data, such as normal
+    /// maps.
     pub fn request_texture<P: AsRef<Path>>(
         &mut self,
         path: P,
         kind: TextureKind,
+    ) -> Option<SharedTexture> {
+        self.request_texture_with_options(path, kind, Default::default())
+    }
+
+    /// Same as `request_texture`, but newly loaded texture will use given options. Options
+    /// file of the texture overrides given kind and options, see `import_options` module docs.
+    pub fn request_texture_with_options<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        kind: TextureKind,
+        options: TextureRequestOptions,
     ) -> Option<SharedTexture> {
         if let Some(texture) = self.find_texture(path.as_ref()) {
             return Some(texture);
         }
 
-        match Texture::load_from_file(path.as_ref(), kind) {
-            Ok(texture) => {
+        let path = path.as_ref();
+        let (kind, options) = TextureImportOptions::load(path, &self.vfs).apply(kind, options);
+        match Texture::load(path, kind, &self.vfs) {
+            Ok(mut texture) => {
+                texture.sampler = options.sampler.unwrap_or(self.default_texture_sampler);
+                texture.srgb = options.srgb;
                 let shared_texture = Arc::new(Mutex::new(texture));
                 self.textures.push(TimedEntry {
                     value: shared_texture.clone(),
                     time_to_live: Self::MAX_RESOURCE_TTL,
                 });
-                Log::writeln(format!("Texture {} is loaded!", path.as_ref().display()));
+                Log::info(
+                    LogCategory::Resource,
+                    format!("Texture {} is loaded!", path.display()),
+                );
                 Some(shared_texture)
             }
             Err(e) => {
-                Log::writeln(format!(
-                    "Unable to load texture {}! Reason {}",
-                    path.as_ref().display(),
-                    e
-                ));
+                Log::err(
+                    LogCategory::Resource,
+                    format!("Unable to load texture {}! Reason {}", path.display(), e),
+                );
+                None
+            }
+        }
+    }
+
+    /// Loads textures from given paths (or gets existing ones) and packs them into new atlas
+    /// with default settings, see `atlas` module docs. This method is **blocking**. On failure
+    /// it returns None and prints failure reason to log. Use `TextureAtlasBuilder` directly
+    /// to change padding and maximum size of atlas.
+    pub fn request_texture_atlas<P: AsRef<Path>>(&mut self, paths: &[P]) -> Option<TextureAtlas> {
+        let mut builder = TextureAtlasBuilder::new();
+        for path in paths {
+            builder = builder.with_texture(self.request_texture(path, TextureKind::RGBA8)?);
+        }
+        match builder.build() {
+            Ok(atlas) => Some(atlas),
+            Err(e) => {
+                Log::err(
+                    LogCategory::Resource,
+                    format!("Unable to create texture atlas! Reason {}", e),
+                );
                 None
             }
         }
@@ -195,8 +818,12 @@ impl ResourceManager {
     /// Currently only FBX (common format in game industry for storing complex 3d models)
     /// and RGS (native rusty-editor format) formats are supported.
     pub fn request_model<P: AsRef<Path>>(&mut self, path: P) -> Option<SharedModel> {
+        self.load_model(path).ok()
+    }
+
+    fn load_model<P: AsRef<Path>>(&mut self, path: P) -> Result<SharedModel, String> {
         if let Some(model) = self.find_model(path.as_ref()) {
-            return Some(model);
+            return Ok(model);
         }
 
         match Model::load(path.as_ref(), self) {
@@ -207,16 +834,20 @@ impl ResourceManager {
                     value: model.clone(),
                     time_to_live: Self::MAX_RESOURCE_TTL,
                 });
-                Log::writeln(format!("Model {} is loaded!", path.as_ref().display()));
-                Some(model)
+                Log::info(
+                    LogCategory::Resource,
+                    format!("Model {} is loaded!", path.as_ref().display()),
+                );
+                Ok(model)
             }
             Err(e) => {
-                Log::writeln(format!(
+                let reason = format!(
                     "Unable to load model from {:?}! Reason {:?}",
                     path.as_ref(),
                     e
-                ));
-                None
+                );
+                Log::err(LogCategory::Resource, reason.clone());
+                Err(reason)
             }
         }
     }
@@ -227,51 +858,372 @@ impl ResourceManager {
     ///
     /// # Supported formats
     ///
-    /// Currently only WAV (uncompressed) and OGG are supported.
+    /// Currently only WAV (uncompressed) and OGG Vorbis are supported.
+    ///
+    /// # Streaming
+    ///
+    /// Generic buffer is fully decoded when it is loaded, which is slow and takes lots of
+    /// memory for long sounds. Streaming buffer (`stream` is `true`) decodes only a small chunk
+    /// of samples when it is loaded, and next chunks are decoded by sound context while the
+    /// sound is playing, so music tracks should be loaded as streaming OGG Vorbis files.
+    /// Streaming buffer can be used by only one sound source at a time. Use
+    /// `load_sound_buffer_async` to load sound buffers without blocking current thread.
+    ///
+    /// Sound buffers are read from physical files, so files from archives are extracted, see
+    /// `Vfs::extract`. Path of sound buffer is path of physical file.
+    ///
+    /// # Loop regions
+    ///
+    /// Loop regions and cue points of WAV files are not part of sound buffer, they can be read
+    /// using `request_wav_metadata`.
     pub fn request_sound_buffer<P: AsRef<Path>>(
         &mut self,
         path: P,
         stream: bool,
     ) -> Option<SharedSoundBuffer> {
-        if let Some(sound_buffer) = self.find_sound_buffer(path.as_ref()) {
+        let path = match self.vfs.extract(path.as_ref()) {
+            Ok(physical_path) => physical_path,
+            Err(e) => {
+                Log::err(
+                    LogCategory::Sound,
+                    format!(
+                        "Unable to load sound buffer from {:?}! Reason {}",
+                        path.as_ref(),
+                        e
+                    ),
+                );
+                return None;
+            }
+        };
+
+        if let Some(sound_buffer) = self.find_sound_buffer(&path) {
             return Some(sound_buffer);
         }
 
-        match DataSource::from_file(path.as_ref()) {
-            Ok(source) => {
-                let buffer = if stream {
-                    SoundBuffer::new_streaming(source)
-                } else {
-                    SoundBuffer::new_generic(source)
-                };
-                match buffer {
-                    Ok(sound_buffer) => {
-                        self.sound_buffers.push(TimedEntry {
+        match Self::decode_sound_buffer(&path, stream) {
+            Ok(sound_buffer) => {
+                self.sound_buffers.push(TimedEntry {
+                    value: sound_buffer.clone(),
+                    time_to_live: Self::MAX_RESOURCE_TTL,
+                });
+                Log::info(
+                    LogCategory::Sound,
+                    format!("Sound buffer {} is loaded!", path.display()),
+                );
+                Some(sound_buffer)
+            }
+            Err(reason) => {
+                Log::err(LogCategory::Sound, reason);
+                None
+            }
+        }
+    }
+
+    fn decode_sound_buffer(path: &Path, stream: bool) -> Result<SharedSoundBuffer, String> {
+        let source = DataSource::from_file(path)
+            .map_err(|e| format!("Invalid data source {}: {:?}", path.display(), e))?;
+        let buffer = if stream {
+            SoundBuffer::new_streaming(source)
+        } else {
+            SoundBuffer::new_generic(source)
+        };
+        buffer.map_err(|_| format!("Unable to load sound buffer from {}!", path.display()))
+    }
+
+    /// Reads loop regions, cue points and other metadata of WAV file with given path, see `wav`
+    /// module docs. Samples are not decoded, so this method is cheap. On failure it returns None
+    /// and prints failure reason to log.
+    pub fn request_wav_metadata<P: AsRef<Path>>(&self, path: P) -> Option<WavMetadata> {
+        match self.vfs.open(path.as_ref()).and_then(WavMetadata::read) {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+                Log::err(
+                    LogCategory::Sound,
+                    format!(
+                        "Unable to read WAV metadata from {:?}! Reason {}",
+                        path.as_ref(),
+                        e
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    /// Loads sound buffer on separate thread and returns handle that will contain shared
+    /// sound buffer when it is loaded, see `request_sound_buffer` for supported formats and
+    /// streaming. Resource manager is not locked while sound is decoded, so big OGG Vorbis
+    /// files can be loaded in background while the game is running. If sound buffer is already
+    /// loaded, handle will contain existing buffer.
+    pub fn load_sound_buffer_async<P: AsRef<Path>>(
+        manager: &Arc<Mutex<Self>>,
+        path: P,
+        stream: bool,
+    ) -> ResourceHandle<SharedSoundBuffer> {
+        let handle = ResourceHandle::pending();
+        let result = handle.clone();
+        let path = path.as_ref().to_owned();
+        let (progress, vfs, spawner) = {
+            let manager = manager.lock().unwrap();
+            (
+                manager.progress.begin(&manager.vfs, &path),
+                manager.vfs.clone(),
+                manager.task_spawner(),
+            )
+        };
+        let manager = manager.clone();
+        spawner.spawn(move || {
+            let path = match vfs.extract(&path) {
+                Ok(physical_path) => physical_path,
+                Err(e) => {
+                    let reason =
+                        format!("Unable to load sound buffer from {:?}! Reason {}", path, e);
+                    Log::err(LogCategory::Sound, reason.clone());
+                    progress.finish(false);
+                    handle.resolve(ResourceState::Failed(reason));
+                    return;
+                }
+            };
+
+            if let Some(sound_buffer) = manager.lock().unwrap().find_sound_buffer(&path) {
+                progress.finish(true);
+                handle.resolve(ResourceState::Ok(sound_buffer));
+                return;
+            }
+
+            let sound_buffer = Self::decode_sound_buffer(&path, stream);
+            progress.finish(sound_buffer.is_ok());
+            match sound_buffer {
+                Ok(sound_buffer) => {
+                    let mut manager = manager.lock().unwrap();
+                    // Same sound buffer could be loaded by someone else while we were decoding it.
+                    let sound_buffer = manager.find_sound_buffer(&path).unwrap_or_else(|| {
+                        manager.sound_buffers.push(TimedEntry {
                             value: sound_buffer.clone(),
                             time_to_live: Self::MAX_RESOURCE_TTL,
                         });
-                        Log::writeln(format!(
-                            "Sound buffer {} is loaded!",
-                            path.as_ref().display()
-                        ));
-                        Some(sound_buffer)
-                    }
-                    Err(_) => {
-                        Log::writeln(format!(
-                            "Unable to load sound buffer from {}!",
-                            path.as_ref().display()
-                        ));
-                        None
-                    }
+                        Log::info(
+                            LogCategory::Sound,
+                            format!("Sound buffer {} is loaded!", path.display()),
+                        );
+                        sound_buffer
+                    });
+                    handle.resolve(ResourceState::Ok(sound_buffer));
+                }
+                Err(reason) => {
+                    Log::err(LogCategory::Sound, reason.clone());
+                    handle.resolve(ResourceState::Failed(reason));
                 }
             }
+        });
+        result
+    }
+
+    /// Tries to load new color gradient resource from given path or get instance of existing,
+    /// if any. This method is **blocking**, so it will block current thread until gradient is
+    /// loading. On failure it returns None and prints failure reason to log.
+    ///
+    /// Loaded gradients are reloaded automatically when their source file is changed.
+    pub fn request_gradient<P: AsRef<Path>>(&mut self, path: P) -> Option<SharedGradient> {
+        if let Some(gradient) = self.find_gradient(path.as_ref()) {
+            return Some(gradient);
+        }
+
+        match GradientResource::load(path.as_ref(), &self.vfs) {
+            Ok(gradient) => {
+                let gradient = Arc::new(Mutex::new(gradient));
+                self.gradients.push(TimedEntry {
+                    value: gradient.clone(),
+                    time_to_live: Self::MAX_RESOURCE_TTL,
+                });
+                Log::info(
+                    LogCategory::Resource,
+                    format!("Gradient {} is loaded!", path.as_ref().display()),
+                );
+                Some(gradient)
+            }
+            Err(e) => {
+                Log::err(
+                    LogCategory::Resource,
+                    format!(
+                        "Unable to load gradient from {:?}! Reason {:?}",
+                        path.as_ref(),
+                        e
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    /// Tries to load new prefab from given path or get instance of existing, if any. This
+    /// method is **blocking**, so it will block current thread until prefab is loading. On
+    /// failure it returns None and prints failure reason to log.
+    pub fn request_prefab<P: AsRef<Path>>(&mut self, path: P) -> Option<SharedPrefab> {
+        if let Some(prefab) = self.find_prefab(path.as_ref()) {
+            return Some(prefab);
+        }
+
+        match Prefab::load(path.as_ref(), self) {
+            Ok(prefab) => {
+                let prefab = Arc::new(Mutex::new(prefab));
+                prefab.lock().unwrap().self_weak_ref = Some(Arc::downgrade(&prefab));
+                self.prefabs.push(TimedEntry {
+                    value: prefab.clone(),
+                    time_to_live: Self::MAX_RESOURCE_TTL,
+                });
+                Log::info(
+                    LogCategory::Resource,
+                    format!("Prefab {} is loaded!", path.as_ref().display()),
+                );
+                Some(prefab)
+            }
+            Err(e) => {
+                Log::err(
+                    LogCategory::Resource,
+                    format!(
+                        "Unable to load prefab from {:?}! Reason {:?}",
+                        path.as_ref(),
+                        e
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    /// Tries to load new cube map from given source or get instance of existing, if any. This
+    /// method is **blocking**, equirectangular panoramas are converted to cube faces on
+    /// loading, so it can take a while. On failure it returns None and prints failure reason
+    /// to log. See `cube_map` module docs.
+    pub fn request_cube_map(&mut self, source: CubeMapSource) -> Option<SharedCubeMap> {
+        if let Some(cube_map) = self.find_cube_map(&source) {
+            return Some(cube_map);
+        }
+
+        match CubeMap::load(&source, &self.vfs) {
+            Ok(cube_map) => {
+                let cube_map = Arc::new(Mutex::new(cube_map));
+                self.cube_maps.push(TimedEntry {
+                    value: cube_map.clone(),
+                    time_to_live: Self::MAX_RESOURCE_TTL,
+                });
+                Log::info(
+                    LogCategory::Resource,
+                    format!("Cube map {:?} is loaded!", source),
+                );
+                Some(cube_map)
+            }
             Err(e) => {
-                Log::writeln(format!("Invalid data source: {:?}", e));
+                Log::err(
+                    LogCategory::Resource,
+                    format!("Unable to load cube map from {:?}! Reason {}", source, e),
+                );
                 None
             }
         }
     }
 
+    /// Returns amount of memory used by textures and meshes of models.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            textures: self
+                .textures
+                .iter()
+                .map(|texture| texture_memory_usage(&texture.value))
+                .sum(),
+            meshes: self
+                .models
+                .iter()
+                .map(|model| model_memory_usage(&model.value))
+                .sum(),
+        }
+    }
+
+    /// Returns current memory budgets.
+    #[inline]
+    pub fn memory_budgets(&self) -> MemoryBudgets {
+        self.memory_budgets
+    }
+
+    /// Sets new memory budgets, see module docs. Budgets are enforced on every update of
+    /// resource manager.
+    #[inline]
+    pub fn set_memory_budgets(&mut self, budgets: MemoryBudgets) {
+        self.memory_budgets = budgets;
+    }
+
+    /// Immediately unloads every resource that is not used by anyone except resource manager,
+    /// returns amount of unloaded resources. It is useful when a level is unloaded, so memory
+    /// is freed before next level is loaded.
+    pub fn unload_unused(&mut self) -> usize {
+        let mut count = 0;
+        let reloaded = &mut self.reloaded;
+        self.textures.retain(|texture| {
+            let retain = !is_unused(&texture.value);
+            if !retain {
+                // Renderer must free GPU copy of the texture.
+                reloaded.textures.push(texture.value.clone());
+                count += 1;
+            }
+            retain
+        });
+        self.cube_maps.retain(|cube_map| {
+            let retain = !is_unused(&cube_map.value);
+            if !retain {
+                reloaded.cube_maps.push(cube_map.value.clone());
+                count += 1;
+            }
+            retain
+        });
+        let mut retain_used = |used: bool| {
+            if !used {
+                count += 1;
+            }
+            used
+        };
+        self.models.retain(|model| retain_used(!is_unused(&model.value)));
+        self.sound_buffers.retain(|buffer| retain_used(!is_unused(&buffer.value)));
+        self.gradients.retain(|gradient| retain_used(!is_unused(&gradient.value)));
+        self.prefabs.retain(|prefab| retain_used(!is_unused(&prefab.value)));
+        for resource_type in self.resource_types.values_mut() {
+            resource_type
+                .resources
+                .retain(|resource| retain_used(!is_unused(&resource.value.value)));
+        }
+        Log::info(
+            LogCategory::Resource,
+            format!("{} unused resources were unloaded!", count),
+        );
+        count
+    }
+
+    fn enforce_memory_budgets(&mut self) {
+        if let Some(budget) = self.memory_budgets.textures {
+            for texture in evict(&mut self.textures, budget, texture_memory_usage) {
+                Log::info(
+                    LogCategory::Resource,
+                    format!(
+                        "Texture resource {:?} unloaded to fit into memory budget!",
+                        texture.lock().unwrap().path
+                    ),
+                );
+                self.reloaded.textures.push(texture);
+            }
+        }
+        if let Some(budget) = self.memory_budgets.meshes {
+            for model in evict(&mut self.models, budget, model_memory_usage) {
+                Log::info(
+                    LogCategory::Resource,
+                    format!(
+                        "Model resource {:?} unloaded to fit into memory budget!",
+                        model.lock().unwrap().path
+                    ),
+                );
+            }
+        }
+    }
+
     /// Returns shared reference to list of available textures.
     #[inline]
     pub fn textures(&self) -> &[TimedEntry<SharedTexture>] {
@@ -322,6 +1274,131 @@ impl ResourceManager {
         None
     }
 
+    /// Returns shared reference to list of available color gradients.
+    #[inline]
+    pub fn gradients(&self) -> &[TimedEntry<SharedGradient>] {
+        &self.gradients
+    }
+
+    /// Tries to find color gradient by its path. Returns None if no such gradient was found.
+    pub fn find_gradient<P: AsRef<Path>>(&self, path: P) -> Option<SharedGradient> {
+        for gradient in self.gradients.iter() {
+            if gradient.lock().unwrap().path.as_path() == path.as_ref() {
+                return Some(gradient.value.clone());
+            }
+        }
+        None
+    }
+
+    /// Returns shared reference to list of available prefabs.
+    #[inline]
+    pub fn prefabs(&self) -> &[TimedEntry<SharedPrefab>] {
+        &self.prefabs
+    }
+
+    /// Tries to find prefab by its path. Returns None if no such prefab was found.
+    pub fn find_prefab<P: AsRef<Path>>(&self, path: P) -> Option<SharedPrefab> {
+        for prefab in self.prefabs.iter() {
+            if prefab.lock().unwrap().path.as_path() == path.as_ref() {
+                return Some(prefab.value.clone());
+            }
+        }
+        None
+    }
+
+    /// Returns shared reference to list of available cube maps.
+    #[inline]
+    pub fn cube_maps(&self) -> &[TimedEntry<SharedCubeMap>] {
+        &self.cube_maps
+    }
+
+    /// Tries to find cube map by its source. Returns None if no such cube map was found.
+    pub fn find_cube_map(&self, source: &CubeMapSource) -> Option<SharedCubeMap> {
+        for cube_map in self.cube_maps.iter() {
+            if &cube_map.lock().unwrap().source == source {
+                return Some(cube_map.value.clone());
+            }
+        }
+        None
+    }
+
+    /// Returns resources that are directly used by loaded model or prefab with given id.
+    /// Returns empty list for other kinds of resources, because they can't use other
+    /// resources, or if resource is not loaded.
+    pub fn dependencies(&self, id: &ResourceId) -> Vec<ResourceId> {
+        match id {
+            ResourceId::Model(path) => self.find_model(path).map(|model| {
+                model
+                    .lock()
+                    .unwrap()
+                    .get_scene()
+                    .resource_dependencies()
+            }),
+            ResourceId::Prefab(path) => self.find_prefab(path).map(|prefab| {
+                prefab
+                    .lock()
+                    .unwrap()
+                    .get_scene()
+                    .resource_dependencies()
+            }),
+            _ => None,
+        }
+        .unwrap_or_default()
+    }
+
+    /// Returns loaded models and prefabs that directly use resource with given id.
+    pub fn dependents(&self, id: &ResourceId) -> Vec<ResourceId> {
+        let mut dependents = Vec::new();
+        for model in self.models.iter() {
+            let model = model.lock().unwrap();
+            if model.get_scene().resource_dependencies().contains(id) {
+                dependents.push(ResourceId::Model(model.path.clone()));
+            }
+        }
+        for prefab in self.prefabs.iter() {
+            let prefab = prefab.lock().unwrap();
+            if prefab.get_scene().resource_dependencies().contains(id) {
+                dependents.push(ResourceId::Prefab(prefab.path.clone()));
+            }
+        }
+        dependents
+    }
+
+    /// Replaces loader executor with new `LoaderPool` with given amount of threads, see
+    /// `loader_pool` module docs. Resources which are being loaded by previous executor are
+    /// finished by it.
+    pub fn set_loader_thread_count(&mut self, count: usize) {
+        self.loader = Arc::new(LoaderPool::new(count));
+    }
+
+    /// Sets executor which will be used to load resources in background, see `loader_pool`
+    /// module docs.
+    pub fn set_loader_executor(&mut self, executor: Arc<dyn LoaderExecutor>) {
+        self.loader = executor;
+    }
+
+    /// Returns current executor of loading tasks.
+    pub fn loader_executor(&self) -> Arc<dyn LoaderExecutor> {
+        self.loader.clone()
+    }
+
+    /// Sets priority of resources which will be loaded in background after this call.
+    pub fn set_load_priority(&mut self, priority: LoadPriority) {
+        self.load_priority = priority;
+    }
+
+    /// Returns priority of resources which are loaded in background.
+    pub fn load_priority(&self) -> LoadPriority {
+        self.load_priority
+    }
+
+    fn task_spawner(&self) -> TaskSpawner {
+        TaskSpawner {
+            executor: self.loader.clone(),
+            priority: self.load_priority,
+        }
+    }
+
     /// Returns current path where to search texture when loading complex model resources.
     #[inline]
     pub fn textures_path(&self) -> &Path {
@@ -339,6 +1416,133 @@ impl ResourceManager {
         self.textures_path = path.as_ref().to_owned();
     }
 
+    /// Returns current options of conversion of cameras and lights of FBX models.
+    #[inline]
+    pub fn fbx_import_options(&self) -> &FbxImportOptions {
+        &self.fbx_import_options
+    }
+
+    /// Sets new options of conversion of cameras and lights of FBX models. Options are
+    /// used for every model that will be loaded after this call, already loaded models
+    /// are not affected until they are reloaded.
+    #[inline]
+    pub fn set_fbx_import_options(&mut self, options: FbxImportOptions) {
+        self.fbx_import_options = options;
+    }
+
+    /// Returns sampler settings which are given to newly loaded textures.
+    #[inline]
+    pub fn default_texture_sampler(&self) -> TextureSampler {
+        self.default_texture_sampler
+    }
+
+    /// Sets sampler settings which will be given to newly loaded textures, already loaded
+    /// textures keep their settings. Use `Texture::set_sampler` to change settings of
+    /// specific texture.
+    #[inline]
+    pub fn set_default_texture_sampler(&mut self, sampler: TextureSampler) {
+        self.default_texture_sampler = sampler;
+    }
+
+    /// Returns virtual file system which is used to read resources.
+    #[inline]
+    pub fn vfs(&self) -> &Vfs {
+        &self.vfs
+    }
+
+    /// Returns virtual file system which is used to read resources, it can be used to mount
+    /// directories and archives. Already loaded resources are not affected by new mounts
+    /// until they're reloaded.
+    #[inline]
+    pub fn vfs_mut(&mut self) -> &mut Vfs {
+        &mut self.vfs
+    }
+
+    /// Enables or disables hot reload of resources, see module docs.
+    pub fn set_hot_reload_enabled(&mut self, enabled: bool) {
+        self.hot_reload = enabled;
+        self.modification_times.clear();
+        if enabled {
+            // Remember current state of files, so only changes made from now are reloaded.
+            self.check_modified_files();
+        }
+    }
+
+    /// Returns true if hot reload of resources is enabled.
+    pub fn is_hot_reload_enabled(&self) -> bool {
+        self.hot_reload
+    }
+
+    pub(in crate) fn take_reloaded_resources(&mut self) -> ReloadedResources {
+        std::mem::take(&mut self.reloaded)
+    }
+
+    fn check_modified_files(&mut self) {
+        let times = &mut self.modification_times;
+
+        let mut textures = Vec::new();
+        for texture in self.textures.iter() {
+            let changed = {
+                let texture = texture.lock().unwrap();
+                texture.loaded && is_file_changed(&self.vfs, times, &texture.path)
+            };
+            if changed {
+                textures.push(texture.value.clone());
+            }
+        }
+
+        let mut models = Vec::new();
+        for model in self.models.iter() {
+            if is_file_changed(&self.vfs, times, &model.lock().unwrap().path) {
+                models.push(model.value.clone());
+            }
+        }
+
+        let mut prefabs = Vec::new();
+        for prefab in self.prefabs.iter() {
+            if is_file_changed(&self.vfs, times, &prefab.lock().unwrap().path) {
+                prefabs.push(prefab.value.clone());
+            }
+        }
+
+        let mut sound_buffers = Vec::new();
+        for buffer in self.sound_buffers.iter() {
+            if let Some(path) = buffer.lock().unwrap().external_data_path() {
+                if is_file_changed(&self.vfs, times, &path) {
+                    sound_buffers.push(buffer.value.clone());
+                }
+            }
+        }
+
+        let mut custom_resources = Vec::new();
+        for (type_id, resource_type) in self.resource_types.iter() {
+            for resource in resource_type.resources.iter() {
+                if is_file_changed(&self.vfs, times, &resource.path) {
+                    let shared = resource.value.value.clone();
+                    custom_resources.push((*type_id, resource.path.clone(), shared));
+                }
+            }
+        }
+
+        for texture in textures {
+            if self.reload_texture(&texture) {
+                self.reloaded.textures.push(texture);
+            }
+        }
+        for model in models {
+            self.reloaded.scenes_outdated |= self.reload_model(&model);
+        }
+        for prefab in prefabs {
+            self.reloaded.scenes_outdated |= self.reload_prefab(&prefab);
+        }
+        for buffer in sound_buffers {
+            self.reload_sound_buffer(&buffer);
+        }
+        for (type_id, path, resource) in custom_resources {
+            self.reload_custom_resource(type_id, &path, &resource);
+        }
+    }
+
     fn update_textures(&mut self, dt: f32) {
         for texture in self.textures.iter_mut() {
             texture.time_to_live -= dt;
@@ -346,13 +1550,21 @@ impl ResourceManager {
                 texture.time_to_live = Self::MAX_RESOURCE_TTL;
             }
         }
+        let reloaded = &mut self.reloaded;
         self.textures.retain(|texture| {
             let retain = texture.time_to_live > 0.0;
-            if !retain && texture.lock().unwrap().path.exists() {
-                Log::writeln(format!(
-                    "Texture resource {:?} destroyed because it not used anymore!",
-                    texture.lock().unwrap().path
-                ));
+            if !retain {
+                if texture.lock().unwrap().path.exists() {
+                    Log::info(
+                        LogCategory::Resource,
+                        format!(
+                            "Texture resource {:?} destroyed because it not used anymore!",
+                            texture.lock().unwrap().path
+                        ),
+                    );
+                }
+                // Renderer must free GPU copy of the texture.
+                reloaded.textures.push(texture.value.clone());
             }
             retain
         });
@@ -368,10 +1580,35 @@ impl ResourceManager {
         self.models.retain(|model| {
             let retain = model.time_to_live > 0.0;
             if !retain && model.lock().unwrap().path.exists() {
-                Log::writeln(format!(
-                    "Model resource {:?} destroyed because it not used anymore!",
-                    model.lock().unwrap().path.exists()
-                ));
+                Log::info(
+                    LogCategory::Resource,
+                    format!(
+                        "Model resource {:?} destroyed because it not used anymore!",
+                        model.lock().unwrap().path.exists()
+                    ),
+                );
+            }
+            retain
+        });
+    }
+
+    fn update_prefabs(&mut self, dt: f32) {
+        for prefab in self.prefabs.iter_mut() {
+            prefab.time_to_live -= dt;
+            if Arc::strong_count(prefab) > 1 {
+                prefab.time_to_live = Self::MAX_RESOURCE_TTL;
+            }
+        }
+        self.prefabs.retain(|prefab| {
+            let retain = prefab.time_to_live > 0.0;
+            if !retain {
+                Log::info(
+                    LogCategory::Resource,
+                    format!(
+                        "Prefab resource {:?} destroyed because it not used anymore!",
+                        prefab.lock().unwrap().path
+                    ),
+                );
             }
             retain
         });
@@ -388,89 +1625,325 @@ impl ResourceManager {
             let retain = buffer.time_to_live > 0.0;
             if !retain {
                 if let Some(path) = buffer.lock().unwrap().external_data_path().as_ref() {
-                    Log::writeln(format!(
-                        "Sound resource {:?} destroyed because it not used anymore!",
-                        path
-                    ));
+                    Log::info(
+                        LogCategory::Resource,
+                        format!(
+                            "Sound resource {:?} destroyed because it not used anymore!",
+                            path
+                        ),
+                    );
                 }
             }
             retain
         });
     }
 
+    fn update_cube_maps(&mut self, dt: f32) {
+        for cube_map in self.cube_maps.iter_mut() {
+            cube_map.time_to_live -= dt;
+            if Arc::strong_count(cube_map) > 1 {
+                cube_map.time_to_live = Self::MAX_RESOURCE_TTL;
+            }
+        }
+        let reloaded = &mut self.reloaded;
+        self.cube_maps.retain(|cube_map| {
+            let retain = cube_map.time_to_live > 0.0;
+            if !retain {
+                Log::info(
+                    LogCategory::Resource,
+                    format!(
+                        "Cube map resource {:?} destroyed because it not used anymore!",
+                        cube_map.lock().unwrap().source
+                    ),
+                );
+                // Renderer must free GPU copy of the cube map.
+                reloaded.cube_maps.push(cube_map.value.clone());
+            }
+            retain
+        });
+    }
+
+    fn update_gradients(&mut self, dt: f32) {
+        for gradient in self.gradients.iter_mut() {
+            gradient.time_to_live -= dt;
+            if Arc::strong_count(gradient) > 1 {
+                gradient.time_to_live = Self::MAX_RESOURCE_TTL;
+            }
+        }
+        self.gradients.retain(|gradient| {
+            let retain = gradient.time_to_live > 0.0;
+            if !retain {
+                Log::info(
+                    LogCategory::Resource,
+                    format!(
+                        "Gradient resource {:?} destroyed because it not used anymore!",
+                        gradient.lock().unwrap().path
+                    ),
+                );
+            }
+            retain
+        });
+
+        self.gradient_reload_timer -= dt;
+        if self.gradient_reload_timer <= 0.0 {
+            self.gradient_reload_timer = Self::HOT_RELOAD_CHECK_INTERVAL;
+            for gradient in self.gradients.iter() {
+                let mut gradient = gradient.lock().unwrap();
+                let modified = self.vfs.modification_time(&gradient.path);
+                if modified.is_some() && modified != gradient.modified {
+                    Self::reload_gradient(&mut gradient, &self.vfs);
+                }
+            }
+        }
+    }
+
+    fn update_custom_resources(&mut self, dt: f32) {
+        for resource_type in self.resource_types.values_mut() {
+            for resource in resource_type.resources.iter_mut() {
+                resource.time_to_live -= dt;
+                if Arc::strong_count(&resource.value.value) > 1 {
+                    resource.time_to_live = Self::MAX_RESOURCE_TTL;
+                }
+            }
+            resource_type.resources.retain(|resource| {
+                let retain = resource.time_to_live > 0.0;
+                if !retain {
+                    Log::info(
+                        LogCategory::Resource,
+                        format!(
+                            "Resource {:?} destroyed because it not used anymore!",
+                            resource.path
+                        ),
+                    );
+                }
+                retain
+            });
+        }
+    }
+
     pub(in crate) fn update(&mut self, dt: f32) {
         self.update_textures(dt);
         self.update_model(dt);
+        self.update_prefabs(dt);
         self.update_sound_buffers(dt);
+        self.update_gradients(dt);
+        self.update_cube_maps(dt);
+        self.update_custom_resources(dt);
+        self.enforce_memory_budgets();
+
+        if self.hot_reload {
+            self.hot_reload_timer -= dt;
+            if self.hot_reload_timer <= 0.0 {
+                self.hot_reload_timer = Self::HOT_RELOAD_CHECK_INTERVAL;
+                self.check_modified_files();
+            }
+        }
+    }
+
+    /// Reloads given texture in-place, returns true on success.
+    fn reload_texture(&mut self, texture: &SharedTexture) -> bool {
+        let mut old_texture = texture.lock().unwrap();
+        match Texture::load(old_texture.path.as_path(), old_texture.kind, &self.vfs) {
+            Ok(mut new_texture) => {
+                Log::info(
+                    LogCategory::Resource,
+                    format!("Texture {:?} is reloaded!", old_texture.path),
+                );
+                // Settings could be changed by user, they must survive reload.
+                new_texture.sampler = old_texture.sampler;
+                new_texture.srgb = old_texture.srgb;
+                *old_texture = new_texture;
+                true
+            }
+            Err(e) => {
+                Log::err(
+                    LogCategory::Resource,
+                    format!(
+                        "Unable to reload {:?} texture! Reason: {}",
+                        old_texture.path, e
+                    ),
+                );
+                false
+            }
+        }
     }
 
     fn reload_textures(&mut self) {
-        for old_texture in self.textures.iter() {
-            let mut old_texture = old_texture.lock().unwrap();
-            let new_texture =
-                match Texture::load_from_file(old_texture.path.as_path(), old_texture.kind) {
-                    Ok(texture) => texture,
-                    Err(e) => {
-                        Log::writeln(format!(
-                            "Unable to reload {:?} texture! Reason: {}",
-                            old_texture.path, e
-                        ));
-                        continue;
-                    }
-                };
-            old_texture.path = Default::default();
-            *old_texture = new_texture;
+        for texture in self.textures.clone() {
+            self.reload_texture(&texture);
         }
     }
 
-    fn reload_models(&mut self) {
-        for old_model in self.models().to_vec() {
-            let old_model_arc = old_model.clone();
-            let mut old_model = old_model.lock().unwrap();
-            let mut new_model = match Model::load(old_model.path.as_path(), self) {
-                Ok(new_model) => new_model,
-                Err(e) => {
-                    Log::writeln(format!(
+    /// Reloads given model in-place, returns true on success.
+    fn reload_model(&mut self, model: &SharedModel) -> bool {
+        let mut old_model = model.lock().unwrap();
+        let mut new_model = match Model::load(old_model.path.as_path(), self) {
+            Ok(new_model) => new_model,
+            Err(e) => {
+                Log::err(
+                    LogCategory::Resource,
+                    format!(
                         "Unable to reload {:?} model! Reason: {:?}",
                         old_model.path, e
-                    ));
-                    continue;
+                    ),
+                );
+                return false;
+            }
+        };
+        new_model.self_weak_ref = Some(Arc::downgrade(model));
+        *old_model = new_model;
+        true
+    }
+
+    fn reload_models(&mut self) {
+        for model in self.models().to_vec() {
+            self.reload_model(&model);
+        }
+    }
+
+    /// Reloads given prefab in-place, returns true on success.
+    fn reload_prefab(&mut self, prefab: &SharedPrefab) -> bool {
+        // Prefab must not be locked while loading, because it can request nested
+        // prefabs and resource manager will lock every prefab while searching.
+        let path = prefab.lock().unwrap().path.clone();
+        let mut new_prefab = match Prefab::load(path.as_path(), self) {
+            Ok(new_prefab) => new_prefab,
+            Err(e) => {
+                Log::err(
+                    LogCategory::Resource,
+                    format!("Unable to reload {:?} prefab! Reason: {:?}", path, e),
+                );
+                return false;
+            }
+        };
+        new_prefab.self_weak_ref = Some(Arc::downgrade(prefab));
+        *prefab.lock().unwrap() = new_prefab;
+        true
+    }
+
+    fn reload_prefabs(&mut self) {
+        for prefab in self.prefabs().to_vec() {
+            self.reload_prefab(&prefab);
+        }
+    }
+
+    fn reload_sound_buffer(&mut self, sound_buffer: &SharedSoundBuffer) {
+        let mut old_sound_buffer = sound_buffer.lock().unwrap();
+        if let Some(ext_path) = old_sound_buffer.external_data_path() {
+            let data_source = match DataSource::from_file(ext_path.as_path()) {
+                Ok(data_source) => data_source,
+                Err(e) => {
+                    Log::err(
+                        LogCategory::Sound,
+                        format!(
+                            "Unable to reload {:?} sound buffer! Reason: {}",
+                            ext_path, e
+                        ),
+                    );
+                    return;
                 }
             };
-            new_model.self_weak_ref = Some(Arc::downgrade(&old_model_arc));
-            old_model.path = Default::default();
-            *old_model = new_model;
+            let new_sound_buffer = match *old_sound_buffer {
+                SoundBuffer::Generic(_) => SoundBuffer::raw_generic(data_source),
+                SoundBuffer::Streaming(_) => SoundBuffer::raw_streaming(data_source),
+            };
+            match new_sound_buffer {
+                Ok(new_sound_buffer) => *old_sound_buffer = new_sound_buffer,
+                Err(_) => Log::err(
+                    LogCategory::Sound,
+                    format!("Unable to reload {:?} sound buffer!", ext_path),
+                ),
+            }
         }
     }
 
     fn reload_sound_buffers(&mut self) {
-        for old_sound_buffer in self.sound_buffers() {
-            let mut old_sound_buffer = old_sound_buffer.lock().unwrap();
-            if let Some(ext_path) = old_sound_buffer.external_data_path() {
-                if let Ok(data_source) = DataSource::from_file(ext_path.as_path()) {
-                    let new_sound_buffer = match *old_sound_buffer {
-                        SoundBuffer::Generic(_) => SoundBuffer::raw_generic(data_source),
-                        SoundBuffer::Streaming(_) => SoundBuffer::raw_streaming(data_source),
-                    };
-                    let new_sound_buffer = match new_sound_buffer {
-                        Ok(new_sound_buffer) => new_sound_buffer,
-                        Err(_) => {
-                            Log::writeln(format!("Unable to reload {:?} sound buffer!", ext_path));
-                            continue;
-                        }
-                    };
-                    *old_sound_buffer = new_sound_buffer;
-                }
+        for sound_buffer in self.sound_buffers().to_vec() {
+            self.reload_sound_buffer(&sound_buffer);
+        }
+    }
+
+    /// Reloads given resource of custom type in-place, returns true on success.
+    fn reload_custom_resource(&self, type_id: TypeId, path: &Path, resource: &AnyResource) -> bool {
+        let resource_type = match self.resource_types.get(&type_id) {
+            Some(resource_type) => resource_type,
+            None => return false,
+        };
+        let new_resource = self
+            .vfs
+            .read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| (resource_type.loader)(path, &data));
+        match new_resource {
+            Ok(new_resource) => {
+                resource_type.replace(resource, new_resource);
+                Log::info(
+                    LogCategory::Resource,
+                    format!("Resource {:?} is reloaded!", path),
+                );
+                true
+            }
+            Err(e) => {
+                Log::err(
+                    LogCategory::Resource,
+                    format!("Unable to reload {:?} resource! Reason: {}", path, e),
+                );
+                false
             }
         }
     }
 
+    fn reload_custom_resources(&mut self) {
+        let mut resources = Vec::new();
+        for (type_id, resource_type) in self.resource_types.iter() {
+            for resource in resource_type.resources.iter() {
+                resources.push((*type_id, resource.path.clone(), resource.value.value.clone()));
+            }
+        }
+        for (type_id, path, resource) in resources {
+            self.reload_custom_resource(type_id, &path, &resource);
+        }
+    }
+
+    fn reload_gradient(gradient: &mut GradientResource, vfs: &Vfs) {
+        match GradientResource::load(gradient.path.as_path(), vfs) {
+            Ok(new_gradient) => {
+                Log::info(
+                    LogCategory::Resource,
+                    format!("Gradient {:?} is reloaded!", gradient.path),
+                );
+                *gradient = new_gradient;
+            }
+            Err(e) => {
+                // Keep last valid gradient and remember modification time, otherwise
+                // we'd try to reload broken file over and over again.
+                gradient.modified = vfs.modification_time(&gradient.path);
+                Log::err(
+                    LogCategory::Resource,
+                    format!(
+                        "Unable to reload {:?} gradient! Reason: {:?}",
+                        gradient.path, e
+                    ),
+                );
+            }
+        }
+    }
+
+    fn reload_gradients(&mut self) {
+        for gradient in self.gradients.iter() {
+            Self::reload_gradient(&mut gradient.lock().unwrap(), &self.vfs);
+        }
+    }
+
     /// Reloads all loaded resources. Normally it should never be called, because it is **very** heavy
     /// method!
     pub fn reload_resources(&mut self) {
         self.reload_textures();
         self.reload_models();
+        self.reload_prefabs();
         self.reload_sound_buffers();
+        self.reload_gradients();
+        self.reload_custom_resources();
     }
 }
 
@@ -481,7 +1954,170 @@ impl Visit for ResourceManager {
         self.textures.visit("Textures", visitor)?;
         self.models.visit("Models", visitor)?;
         self.sound_buffers.visit("SoundBuffers", visitor)?;
+        let _ = self.gradients.visit("Gradients", visitor);
+        let _ = self.prefabs.visit("Prefabs", visitor);
 
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            color::Color,
+            color_gradient::{ColorGradient, GradientPoint},
+            visitor::{Visit, Visitor},
+        },
+        engine::resource_manager::{evict, texture_memory_usage, ResourceManager, TimedEntry},
+        resource::{
+            gradient::GradientResource,
+            texture::{Texture, TextureKind},
+        },
+        scene::{base::BaseBuilder, node::Node, particle_system::ParticleSystemBuilder, Scene},
+        sound::buffer::SoundBuffer,
+    };
+    use std::{
+        path::Path,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    fn write_png(path: &Path, size: u32) {
+        image::RgbaImage::new(size, size).save(path).unwrap();
+    }
+
+    /// Overwrites image and waits until modification time of the file changes, some file
+    /// systems have coarse timestamps.
+    fn rewrite_png(path: &Path, size: u32) {
+        let modified = || std::fs::metadata(path).unwrap().modified().unwrap();
+        let previous = modified();
+        loop {
+            write_png(path, size);
+            if modified() != previous {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn gradient_of_particle_system_is_restored_on_load() {
+        let gradient_path = std::env::temp_dir().join("rg3d-restore-gradient-test.rgg");
+        let scene_path = std::env::temp_dir().join("rg3d-restore-gradient-test.rgs");
+        let mut gradient = ColorGradient::new();
+        gradient.add_point(GradientPoint::new(0.0, Color::from_rgba(255, 0, 0, 255)));
+        let mut resource = GradientResource::new(&gradient_path, gradient);
+        resource.save().unwrap();
+
+        let mut scene = Scene::new();
+        let particle_system = scene.graph.add_node(
+            ParticleSystemBuilder::new(BaseBuilder::new())
+                .with_color_over_lifetime_resource(Arc::new(Mutex::new(resource)))
+                .build_node(),
+        );
+        let mut visitor = Visitor::new();
+        scene.visit("Scene", &mut visitor).unwrap();
+        visitor.save_binary(&scene_path).unwrap();
+
+        let mut resource_manager = ResourceManager::new();
+        let loaded = Scene::from_file(&scene_path, &mut resource_manager).unwrap();
+        let restored = match &loaded.graph[particle_system] {
+            Node::ParticleSystem(particle_system) => {
+                particle_system.color_over_lifetime_resource().unwrap()
+            }
+            _ => panic!("Node must be particle system!"),
+        };
+        let _ = std::fs::remove_file(&scene_path);
+        let _ = std::fs::remove_file(&gradient_path);
+
+        // Restored resource must be the one that is tracked by resource manager, so it will
+        // be hot reloaded.
+        let tracked = resource_manager.find_gradient(&gradient_path).unwrap();
+        assert!(Arc::ptr_eq(&restored, &tracked));
+        assert_eq!(restored.lock().unwrap().gradient().points().len(), 1);
+    }
+
+    #[test]
+    fn hot_reload_reloads_only_changed_textures() {
+        let dir = std::env::temp_dir().join("rg3d-hot-reload-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let changed_path = dir.join("changed.png");
+        let unchanged_path = dir.join("unchanged.png");
+        write_png(&changed_path, 2);
+        write_png(&unchanged_path, 2);
+
+        let mut resource_manager = ResourceManager::new();
+        let changed = resource_manager
+            .request_texture(&changed_path, TextureKind::RGBA8)
+            .unwrap();
+        let unchanged = resource_manager
+            .request_texture(&unchanged_path, TextureKind::RGBA8)
+            .unwrap();
+        resource_manager.set_hot_reload_enabled(true);
+
+        resource_manager.update(ResourceManager::HOT_RELOAD_CHECK_INTERVAL);
+        assert!(resource_manager
+            .take_reloaded_resources()
+            .textures
+            .is_empty());
+
+        rewrite_png(&changed_path, 4);
+        resource_manager.update(ResourceManager::HOT_RELOAD_CHECK_INTERVAL);
+        let reloaded = resource_manager.take_reloaded_resources().textures;
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(reloaded.len(), 1);
+        assert!(Arc::ptr_eq(&reloaded[0], &changed));
+        assert_eq!(changed.lock().unwrap().width, 4);
+        assert_eq!(unchanged.lock().unwrap().width, 2);
+    }
+
+    #[test]
+    fn evict_least_recently_used_textures() {
+        let make_entry = |time_to_live: f32| TimedEntry {
+            value: Arc::new(Mutex::new(
+                Texture::from_bytes(4, 4, TextureKind::R8, vec![0; 16]).unwrap(),
+            )),
+            time_to_live,
+        };
+        let mut entries = vec![make_entry(5.0), make_entry(1.0), make_entry(3.0)];
+        let used = entries[1].value.clone();
+
+        // Used texture must stay even if it is the least recently used one.
+        let removed = evict(&mut entries, 20, texture_memory_usage);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(entries.len(), 1);
+        assert!(Arc::ptr_eq(&entries[0].value, &used));
+
+        assert!(evict(&mut entries, 0, texture_memory_usage).is_empty());
+    }
+
+    #[test]
+    fn ogg_vorbis_sound_buffer_is_loaded() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/silence.ogg");
+
+        let generic = ResourceManager::decode_sound_buffer(&path, false).unwrap();
+        match &*generic.lock().unwrap() {
+            SoundBuffer::Generic(generic) => assert!(!generic.samples().is_empty()),
+            _ => panic!("Sound buffer must be generic!"),
+        };
+        let streaming = ResourceManager::decode_sound_buffer(&path, true).unwrap();
+        match &*streaming.lock().unwrap() {
+            SoundBuffer::Streaming(_) => (),
+            _ => panic!("Sound buffer must be streaming!"),
+        };
+
+        let manager = Arc::new(Mutex::new(ResourceManager::new()));
+        let loaded = ResourceManager::load_sound_buffer_async(&manager, &path, false)
+            .wait()
+            .unwrap();
+        // Buffer loaded in background must be tracked, so it won't be decoded again.
+        let requested = manager
+            .lock()
+            .unwrap()
+            .request_sound_buffer(&path, false)
+            .unwrap();
+        assert!(Arc::ptr_eq(&loaded, &requested));
+    }
+}
@@ -0,0 +1,890 @@
+//! Contains Lua scripting subsystem (requires `scripting` feature).
+//!
+//! Scripting allows designers to write behavior of nodes without recompiling the game. Script
+//! is attached to a node by path (`Base::set_script`), so it is saved with the scene and is
+//! kept in instances of models. Scripts are run by `ScriptPlugin`, which must be added to
+//! engine using `Engine::add_plugin`. Script files are read through virtual file system of
+//! resource manager, so they can be packed into archives.
+//!
+//! Script is a Lua chunk which returns a table with callbacks, every callback is optional:
+//!
+//! - `init(self)` - called once when script is attached to a node, before its first update.
+//! - `update(self, dt)` - called on every update of the scene with the time step.
+//! - `on_event(self, event)` - called for every input event passed to engine (see
+//!   `Engine::process_plugin_event`), `event.kind` is one of `"key"`, `"mouse_button"`,
+//!   `"cursor_moved"`, `"mouse_wheel"` or `"mouse_motion"`.
+//! - `on_message(self, name, payload)` - called for every message sent to the node by
+//!   `send_message(node, name, payload)` during previous update.
+//! - `on_reload(self)` - called when script file was changed and reloaded.
+//! - `on_destroy(self)` - called when node was removed or script was detached.
+//!
+//! Every node gets its own instance of the table (`self`), instance contains handle of its
+//! node in `self.node` and keeps any state script puts into it. Instance keeps its state when
+//! script file is reloaded, only callbacks are replaced.
+//!
+//! Scripts can use following functions, they work with scene of the script and take nodes as
+//! handles (`nil` means no node):
+//!
+//! - `graph.find(name)`, `graph.find_in(root, name)`, `graph.with_tag(tag)`,
+//!   `graph.is_valid(node)`, `graph.remove(node)`, `graph.link(child, parent)`.
+//! - `node.name`, `node.set_name`, `node.position`, `node.set_position`, `node.move`,
+//!   `node.global_position`, `node.look_vector`, `node.rotate`, `node.set_rotation` (axis and
+//!   angle in radians), `node.scale`, `node.set_scale`, `node.visible`, `node.set_visible`,
+//!   `node.enabled`, `node.set_enabled`, `node.has_tag`, `node.add_tag`, `node.remove_tag`,
+//!   `node.parent`, `node.children`. Vectors are passed as three numbers.
+//! - `resources.instantiate_model(path)` - loads model and instantiates it in the scene.
+//! - `log.info(text)`, `log.warn(text)`, `log.err(text)`.
+//! - `send_message(node, name, payload)`.
+//!
+//! Errors of scripts are written to the log, failed script does not stop other scripts. Game
+//! can register its own functions using `ScriptPlugin::lua` before plugin is added to engine.
+//!
+//! # Example
+//!
+//! ```lua
+//! local Door = {}
+//!
+//! function Door:init()
+//!     self.opened = false
+//! end
+//!
+//! function Door:update(dt)
+//!     if self.opened then
+//!         node.rotate(self.node, 0, 1, 0, dt)
+//!     end
+//! end
+//!
+//! function Door:on_message(name, payload)
+//!     if name == "open" then
+//!         self.opened = true
+//!     end
+//! end
+//!
+//! return Door
+//! ```
+
+use crate::{
+    core::{
+        math::{quat::Quat, vec3::Vec3},
+        pool::Handle,
+    },
+    engine::{
+        plugin::{Plugin, PluginContext},
+        resource_manager::ResourceManager,
+    },
+    event::{DeviceEvent, ElementState, Event, MouseScrollDelta, WindowEvent},
+    scene::{node::Node, Scene},
+    utils::log::{Log, LogCategory},
+};
+use rlua::{Context, Lua, RegistryKey, Scope, Table, ToLuaMulti, Value};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+// Handles are passed to scripts as integers which contain both index and generation, so
+// scripts can't access node that took place of removed one.
+fn encode_handle(handle: Handle<Node>) -> Option<i64> {
+    if handle.is_none() {
+        None
+    } else {
+        Some(((handle.generation() as i64) << 32) | handle.index() as i64)
+    }
+}
+
+fn decode_handle(value: i64) -> Handle<Node> {
+    Handle::new(value as u32, (value >> 32) as u32)
+}
+
+enum InputEvent {
+    Key { key: String, pressed: bool },
+    MouseButton { button: String, pressed: bool },
+    CursorMoved { x: f64, y: f64 },
+    MouseWheel { x: f32, y: f32 },
+    MouseMotion { dx: f64, dy: f64 },
+}
+
+impl InputEvent {
+    fn from_event(event: &Event<()>) -> Option<Self> {
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::KeyboardInput { input, .. } => {
+                    input.virtual_keycode.map(|key| InputEvent::Key {
+                        key: format!("{:?}", key),
+                        pressed: input.state == ElementState::Pressed,
+                    })
+                }
+                WindowEvent::MouseInput { state, button, .. } => Some(InputEvent::MouseButton {
+                    button: format!("{:?}", button),
+                    pressed: *state == ElementState::Pressed,
+                }),
+                WindowEvent::CursorMoved { position, .. } => Some(InputEvent::CursorMoved {
+                    x: position.x,
+                    y: position.y,
+                }),
+                WindowEvent::MouseWheel { delta, .. } => match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        Some(InputEvent::MouseWheel { x: *x, y: *y })
+                    }
+                    _ => None,
+                },
+                _ => None,
+            },
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => Some(InputEvent::MouseMotion {
+                dx: delta.0,
+                dy: delta.1,
+            }),
+            _ => None,
+        }
+    }
+
+    fn to_table<'lua>(&self, ctx: Context<'lua>) -> rlua::Result<Table<'lua>> {
+        let table = ctx.create_table()?;
+        match self {
+            InputEvent::Key { key, pressed } => {
+                table.set("kind", "key")?;
+                table.set("key", key.as_str())?;
+                table.set("pressed", *pressed)?;
+            }
+            InputEvent::MouseButton { button, pressed } => {
+                table.set("kind", "mouse_button")?;
+                table.set("button", button.as_str())?;
+                table.set("pressed", *pressed)?;
+            }
+            InputEvent::CursorMoved { x, y } => {
+                table.set("kind", "cursor_moved")?;
+                table.set("x", *x)?;
+                table.set("y", *y)?;
+            }
+            InputEvent::MouseWheel { x, y } => {
+                table.set("kind", "mouse_wheel")?;
+                table.set("x", *x)?;
+                table.set("y", *y)?;
+            }
+            InputEvent::MouseMotion { dx, dy } => {
+                table.set("kind", "mouse_motion")?;
+                table.set("dx", *dx)?;
+                table.set("dy", *dy)?;
+            }
+        }
+        Ok(table)
+    }
+}
+
+enum Callback {
+    Update(f32),
+    Event(InputEvent),
+}
+
+struct ScriptModule {
+    // Metatable of instances, its `__index` is replaced when script is reloaded so instances
+    // get new callbacks and keep their state.
+    metatable: RegistryKey,
+    modified: Option<SystemTime>,
+}
+
+struct ScriptInstance {
+    path: PathBuf,
+    table: RegistryKey,
+}
+
+struct PendingMessage {
+    scene: Handle<Scene>,
+    target: Handle<Node>,
+    name: String,
+    payload: RegistryKey,
+}
+
+// Data which is shared by functions available to scripts while scripts of a scene are run.
+struct ScriptApi<'a> {
+    scene: RefCell<&'a mut Scene>,
+    scene_handle: Handle<Scene>,
+    resource_manager: &'a Arc<Mutex<ResourceManager>>,
+    outgoing: RefCell<Vec<PendingMessage>>,
+}
+
+impl<'a> ScriptApi<'a> {
+    fn with_node<R>(&self, handle: i64, func: impl FnOnce(&mut Node) -> R) -> rlua::Result<R> {
+        let handle = decode_handle(handle);
+        let mut scene = self.scene.borrow_mut();
+        if scene.graph.is_valid_handle(handle) {
+            Ok(func(&mut scene.graph[handle]))
+        } else {
+            Err(rlua::Error::RuntimeError(format!(
+                "Invalid node handle {:?}",
+                handle
+            )))
+        }
+    }
+
+    fn install<'lua, 'scope>(
+        &'scope self,
+        ctx: Context<'lua>,
+        scope: &Scope<'lua, 'scope>,
+    ) -> rlua::Result<()> {
+        let globals = ctx.globals();
+
+        let graph = ctx.create_table()?;
+        graph.set(
+            "find",
+            scope.create_function(move |_, name: String| {
+                let scene = self.scene.borrow();
+                Ok(encode_handle(scene.graph.find_by_name_from_root(&name)))
+            })?,
+        )?;
+        graph.set(
+            "find_in",
+            scope.create_function(move |_, (root, name): (i64, String)| {
+                let scene = self.scene.borrow();
+                Ok(encode_handle(
+                    scene.graph.find_by_name(decode_handle(root), &name),
+                ))
+            })?,
+        )?;
+        graph.set(
+            "with_tag",
+            scope.create_function(move |_, tag: String| {
+                let scene = self.scene.borrow();
+                Ok(scene
+                    .graph
+                    .nodes_with_tag(&tag)
+                    .filter_map(encode_handle)
+                    .collect::<Vec<_>>())
+            })?,
+        )?;
+        graph.set(
+            "is_valid",
+            scope.create_function(move |_, handle: i64| {
+                Ok(self
+                    .scene
+                    .borrow()
+                    .graph
+                    .is_valid_handle(decode_handle(handle)))
+            })?,
+        )?;
+        graph.set(
+            "remove",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |_| ())?;
+                self.scene
+                    .borrow_mut()
+                    .graph
+                    .remove_node(decode_handle(handle));
+                Ok(())
+            })?,
+        )?;
+        graph.set(
+            "link",
+            scope.create_function(move |_, (child, parent): (i64, i64)| {
+                self.with_node(child, |_| ())?;
+                self.with_node(parent, |_| ())?;
+                self.scene
+                    .borrow_mut()
+                    .graph
+                    .link_nodes(decode_handle(child), decode_handle(parent));
+                Ok(())
+            })?,
+        )?;
+        globals.set("graph", graph)?;
+
+        let node = ctx.create_table()?;
+        node.set(
+            "name",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |node| node.name().to_owned())
+            })?,
+        )?;
+        node.set(
+            "set_name",
+            scope.create_function(move |_, (handle, name): (i64, String)| {
+                self.with_node(handle, |node| {
+                    node.set_name(name);
+                })
+            })?,
+        )?;
+        node.set(
+            "position",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |node| {
+                    let p = node.local_transform().position();
+                    (p.x, p.y, p.z)
+                })
+            })?,
+        )?;
+        node.set(
+            "set_position",
+            scope.create_function(move |_, (handle, x, y, z): (i64, f32, f32, f32)| {
+                self.with_node(handle, |node| {
+                    node.local_transform_mut().set_position(Vec3::new(x, y, z));
+                })
+            })?,
+        )?;
+        node.set(
+            "move",
+            scope.create_function(move |_, (handle, x, y, z): (i64, f32, f32, f32)| {
+                self.with_node(handle, |node| {
+                    node.local_transform_mut().offset(Vec3::new(x, y, z));
+                })
+            })?,
+        )?;
+        node.set(
+            "global_position",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |node| {
+                    let p = node.global_position();
+                    (p.x, p.y, p.z)
+                })
+            })?,
+        )?;
+        node.set(
+            "look_vector",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |node| {
+                    let v = node.look_vector();
+                    (v.x, v.y, v.z)
+                })
+            })?,
+        )?;
+        node.set(
+            "rotate",
+            scope.create_function(
+                move |_, (handle, x, y, z, angle): (i64, f32, f32, f32, f32)| {
+                    self.with_node(handle, |node| {
+                        let transform = node.local_transform_mut();
+                        let rotation = Quat::from_axis_angle(Vec3::new(x, y, z), angle);
+                        transform.set_rotation(transform.rotation() * rotation);
+                    })
+                },
+            )?,
+        )?;
+        node.set(
+            "set_rotation",
+            scope.create_function(
+                move |_, (handle, x, y, z, angle): (i64, f32, f32, f32, f32)| {
+                    self.with_node(handle, |node| {
+                        node.local_transform_mut()
+                            .set_rotation(Quat::from_axis_angle(Vec3::new(x, y, z), angle));
+                    })
+                },
+            )?,
+        )?;
+        node.set(
+            "scale",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |node| {
+                    let s = node.local_transform().scale();
+                    (s.x, s.y, s.z)
+                })
+            })?,
+        )?;
+        node.set(
+            "set_scale",
+            scope.create_function(move |_, (handle, x, y, z): (i64, f32, f32, f32)| {
+                self.with_node(handle, |node| {
+                    node.local_transform_mut().set_scale(Vec3::new(x, y, z));
+                })
+            })?,
+        )?;
+        node.set(
+            "visible",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |node| node.visibility())
+            })?,
+        )?;
+        node.set(
+            "set_visible",
+            scope.create_function(move |_, (handle, visible): (i64, bool)| {
+                self.with_node(handle, |node| {
+                    node.set_visibility(visible);
+                })
+            })?,
+        )?;
+        node.set(
+            "enabled",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |node| node.is_node_enabled())
+            })?,
+        )?;
+        node.set(
+            "set_enabled",
+            scope.create_function(move |_, (handle, enabled): (i64, bool)| {
+                self.with_node(handle, |node| {
+                    node.set_node_enabled(enabled);
+                })
+            })?,
+        )?;
+        node.set(
+            "has_tag",
+            scope.create_function(move |_, (handle, tag): (i64, String)| {
+                self.with_node(handle, |node| node.has_tag(&tag))
+            })?,
+        )?;
+        node.set(
+            "add_tag",
+            scope.create_function(move |_, (handle, tag): (i64, String)| {
+                self.with_node(handle, |node| {
+                    node.add_tag(tag);
+                })
+            })?,
+        )?;
+        node.set(
+            "remove_tag",
+            scope.create_function(move |_, (handle, tag): (i64, String)| {
+                self.with_node(handle, |node| node.remove_tag(&tag))
+            })?,
+        )?;
+        node.set(
+            "parent",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |node| encode_handle(node.parent()))
+            })?,
+        )?;
+        node.set(
+            "children",
+            scope.create_function(move |_, handle: i64| {
+                self.with_node(handle, |node| {
+                    node.children()
+                        .iter()
+                        .filter_map(|child| encode_handle(*child))
+                        .collect::<Vec<_>>()
+                })
+            })?,
+        )?;
+        globals.set("node", node)?;
+
+        let resources = ctx.create_table()?;
+        resources.set(
+            "instantiate_model",
+            scope.create_function(move |_, path: String| {
+                let model = self.resource_manager.lock().unwrap().request_model(&path);
+                Ok(model.and_then(|model| {
+                    let mut scene = self.scene.borrow_mut();
+                    encode_handle(model.lock().unwrap().instantiate(&mut scene).root)
+                }))
+            })?,
+        )?;
+        globals.set("resources", resources)?;
+
+        let log = ctx.create_table()?;
+        log.set(
+            "info",
+            scope.create_function(|_, text: String| {
+                Log::info(LogCategory::General, text);
+                Ok(())
+            })?,
+        )?;
+        log.set(
+            "warn",
+            scope.create_function(|_, text: String| {
+                Log::warn(LogCategory::General, text);
+                Ok(())
+            })?,
+        )?;
+        log.set(
+            "err",
+            scope.create_function(|_, text: String| {
+                Log::err(LogCategory::General, text);
+                Ok(())
+            })?,
+        )?;
+        globals.set("log", log)?;
+
+        globals.set(
+            "send_message",
+            scope.create_function(move |ctx, (target, name, payload): (i64, String, Value)| {
+                self.outgoing.borrow_mut().push(PendingMessage {
+                    scene: self.scene_handle,
+                    target: decode_handle(target),
+                    name,
+                    payload: ctx.create_registry_value(payload)?,
+                });
+                Ok(())
+            })?,
+        )?;
+
+        Ok(())
+    }
+}
+
+fn call_method<'lua, A: ToLuaMulti<'lua>>(
+    instance: &Table<'lua>,
+    name: &str,
+    args: A,
+) -> rlua::Result<()> {
+    match instance.get::<_, Value>(name)? {
+        Value::Function(func) => func.call::<_, ()>((instance.clone(), args)),
+        Value::Nil => Ok(()),
+        _ => Err(rlua::Error::RuntimeError(format!(
+            "{} must be a function",
+            name
+        ))),
+    }
+}
+
+fn log_error(path: &Path, callback: &str, error: rlua::Error) {
+    Log::err(
+        LogCategory::General,
+        format!(
+            "Script {} failed in {}. Reason: {}",
+            path.display(),
+            callback,
+            error
+        ),
+    );
+}
+
+// Loads script and returns table with its callbacks, empty table is returned on failure, so
+// nodes with broken script keep working and get callbacks when script is fixed.
+fn load_callbacks<'lua>(
+    ctx: Context<'lua>,
+    resource_manager: &Arc<Mutex<ResourceManager>>,
+    path: &Path,
+) -> rlua::Result<Table<'lua>> {
+    let source = match resource_manager.lock().unwrap().vfs().read(path) {
+        Ok(source) => source,
+        Err(e) => {
+            Log::err(
+                LogCategory::Resource,
+                format!("Unable to read script {}. Reason: {}", path.display(), e),
+            );
+            return ctx.create_table();
+        }
+    };
+    let name = path.to_string_lossy();
+    match ctx
+        .load(&source)
+        .set_name(&*name)
+        .and_then(|chunk| chunk.eval::<Table>())
+    {
+        Ok(callbacks) => Ok(callbacks),
+        Err(e) => {
+            log_error(path, "loading", e);
+            ctx.create_table()
+        }
+    }
+}
+
+/// Runs Lua scripts attached to nodes of every scene, see module docs.
+pub struct ScriptPlugin {
+    lua: Lua,
+    modules: HashMap<PathBuf, ScriptModule>,
+    instances: HashMap<(Handle<Scene>, Handle<Node>), ScriptInstance>,
+    messages: Vec<PendingMessage>,
+    reloaded: HashSet<PathBuf>,
+    hot_reload: bool,
+}
+
+impl Default for ScriptPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScriptPlugin {
+    /// Creates new scripting plugin with hot reload of scripts enabled.
+    pub fn new() -> Self {
+        Self {
+            lua: Lua::new(),
+            modules: Default::default(),
+            instances: Default::default(),
+            messages: Default::default(),
+            reloaded: Default::default(),
+            hot_reload: true,
+        }
+    }
+
+    /// Returns Lua state of scripts, can be used to register custom functions for scripts.
+    pub fn lua(&self) -> &Lua {
+        &self.lua
+    }
+
+    /// Sets whether scripts should be reloaded when their files are changed. Checking files
+    /// is cheap, but can be disabled in release builds.
+    pub fn set_hot_reload(&mut self, enabled: bool) {
+        self.hot_reload = enabled;
+    }
+
+    /// Returns true if scripts are reloaded when their files are changed.
+    pub fn is_hot_reload_enabled(&self) -> bool {
+        self.hot_reload
+    }
+
+    fn reload_changed_modules(&mut self, resource_manager: &Arc<Mutex<ResourceManager>>) {
+        let Self {
+            lua,
+            modules,
+            reloaded,
+            ..
+        } = self;
+        lua.context(|ctx| {
+            for (path, module) in modules.iter_mut() {
+                let modified = resource_manager
+                    .lock()
+                    .unwrap()
+                    .vfs()
+                    .modification_time(path);
+                if modified == module.modified {
+                    continue;
+                }
+                module.modified = modified;
+                let result = load_callbacks(ctx, resource_manager, path).and_then(|callbacks| {
+                    ctx.registry_value::<Table>(&module.metatable)?
+                        .set("__index", callbacks)
+                });
+                match result {
+                    Ok(_) => {
+                        reloaded.insert(path.clone());
+                        Log::info(
+                            LogCategory::Resource,
+                            format!("Script {} is reloaded!", path.display()),
+                        );
+                    }
+                    Err(e) => log_error(path, "reloading", e),
+                }
+            }
+        });
+    }
+
+    fn run_scene(
+        &mut self,
+        scene_handle: Handle<Scene>,
+        scene: &mut Scene,
+        resource_manager: &Arc<Mutex<ResourceManager>>,
+        callback: &Callback,
+    ) {
+        let Self {
+            lua,
+            modules,
+            instances,
+            messages,
+            reloaded,
+            ..
+        } = self;
+
+        // Nodes are visited in order of their handles, so scripts are run in the same order
+        // every time.
+        let scripted = scene
+            .graph
+            .pair_iter()
+            .filter_map(|(handle, node)| node.script().map(|path| (handle, path.to_owned())))
+            .collect::<Vec<_>>();
+
+        let incoming = match callback {
+            Callback::Update(_) => {
+                let (incoming, rest) = std::mem::take(messages)
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|message| message.scene == scene_handle);
+                *messages = rest;
+                incoming
+            }
+            Callback::Event(_) => Vec::new(),
+        };
+
+        let api = ScriptApi {
+            scene: RefCell::new(scene),
+            scene_handle,
+            resource_manager,
+            outgoing: Default::default(),
+        };
+
+        lua.context(|ctx| {
+            ctx.scope(|scope| {
+                if let Err(e) = api.install(ctx, scope) {
+                    Log::err(
+                        LogCategory::General,
+                        format!("Unable to prepare scripts. Reason: {}", e),
+                    );
+                    return;
+                }
+
+                if let Callback::Update(_) = callback {
+                    let stale = instances
+                        .iter()
+                        .filter(|((scene, node), instance)| {
+                            *scene == scene_handle
+                                && !scripted
+                                    .iter()
+                                    .any(|(h, path)| h == node && *path == instance.path)
+                        })
+                        .map(|(key, _)| *key)
+                        .collect::<Vec<_>>();
+                    for key in stale {
+                        let instance = instances.remove(&key).unwrap();
+                        if let Err(e) = ctx
+                            .registry_value::<Table>(&instance.table)
+                            .and_then(|table| call_method(&table, "on_destroy", ()))
+                        {
+                            log_error(&instance.path, "on_destroy", e);
+                        }
+                    }
+
+                    for (node, path) in scripted.iter() {
+                        let key = (scene_handle, *node);
+                        if instances.contains_key(&key) {
+                            continue;
+                        }
+                        let result = (|| -> rlua::Result<()> {
+                            if !modules.contains_key(path) {
+                                let metatable = ctx.create_table()?;
+                                metatable
+                                    .set("__index", load_callbacks(ctx, resource_manager, path)?)?;
+                                modules.insert(
+                                    path.clone(),
+                                    ScriptModule {
+                                        metatable: ctx.create_registry_value(metatable)?,
+                                        modified: resource_manager
+                                            .lock()
+                                            .unwrap()
+                                            .vfs()
+                                            .modification_time(path),
+                                    },
+                                );
+                            }
+                            let table = ctx.create_table()?;
+                            table.set("node", encode_handle(*node))?;
+                            table.set_metatable(Some(
+                                ctx.registry_value::<Table>(&modules[path].metatable)?,
+                            ));
+                            instances.insert(
+                                key,
+                                ScriptInstance {
+                                    path: path.clone(),
+                                    table: ctx.create_registry_value(table.clone())?,
+                                },
+                            );
+                            call_method(&table, "init", ())
+                        })();
+                        if let Err(e) = result {
+                            log_error(path, "init", e);
+                        }
+                    }
+                }
+
+                for (node, path) in scripted.iter() {
+                    let instance = match instances.get(&(scene_handle, *node)) {
+                        Some(instance) => instance,
+                        None => continue,
+                    };
+                    let table = match ctx.registry_value::<Table>(&instance.table) {
+                        Ok(table) => table,
+                        Err(e) => {
+                            log_error(path, "update", e);
+                            continue;
+                        }
+                    };
+                    let (name, result) = match callback {
+                        Callback::Update(dt) => {
+                            if reloaded.contains(path) {
+                                if let Err(e) = call_method(&table, "on_reload", ()) {
+                                    log_error(path, "on_reload", e);
+                                }
+                            }
+                            for message in incoming.iter().filter(|m| m.target == *node) {
+                                if let Err(e) = ctx
+                                    .registry_value::<Value>(&message.payload)
+                                    .and_then(|payload| {
+                                        call_method(
+                                            &table,
+                                            "on_message",
+                                            (message.name.as_str(), payload),
+                                        )
+                                    })
+                                {
+                                    log_error(path, "on_message", e);
+                                }
+                            }
+                            ("update", call_method(&table, "update", *dt))
+                        }
+                        Callback::Event(event) => (
+                            "on_event",
+                            event
+                                .to_table(ctx)
+                                .and_then(|event| call_method(&table, "on_event", event)),
+                        ),
+                    };
+                    if let Err(e) = result {
+                        log_error(path, name, e);
+                    }
+                }
+            });
+        });
+
+        messages.extend(api.outgoing.into_inner());
+    }
+
+    fn run(&mut self, context: &mut PluginContext, callback: Callback) {
+        let resource_manager = context.resource_manager.clone();
+        let scenes = context
+            .scenes
+            .pair_iter()
+            .map(|(handle, _)| handle)
+            .collect::<Vec<_>>();
+        for handle in scenes {
+            self.run_scene(
+                handle,
+                &mut context.scenes[handle],
+                &resource_manager,
+                &callback,
+            );
+        }
+    }
+}
+
+impl Plugin for ScriptPlugin {
+    fn update(&mut self, context: &mut PluginContext, dt: f32) {
+        if self.hot_reload {
+            self.reload_changed_modules(context.resource_manager);
+        }
+
+        // Scripts of removed scenes are dropped without callbacks, their nodes are gone.
+        let scenes = &context.scenes;
+        self.instances
+            .retain(|(scene, _), _| scenes.is_valid_handle(*scene));
+        self.messages
+            .retain(|message| scenes.is_valid_handle(message.scene));
+
+        self.run(context, Callback::Update(dt));
+        self.reloaded.clear();
+        self.lua.context(|ctx| ctx.expire_registry_values());
+    }
+
+    fn on_event(&mut self, context: &mut PluginContext, event: &Event<()>) {
+        if let Some(event) = InputEvent::from_event(event) {
+            self.run(context, Callback::Event(event));
+        }
+    }
+
+    fn shutdown(&mut self, _context: &mut PluginContext) {
+        self.instances.clear();
+        self.messages.clear();
+        self.modules.clear();
+        self.lua.context(|ctx| ctx.expire_registry_values());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::pool::{Handle, Pool},
+        engine::scripting::{decode_handle, encode_handle},
+        scene::node::Node,
+    };
+
+    #[test]
+    fn handles_survive_round_trip() {
+        let mut pool = Pool::new();
+        let a: Handle<Node> = pool.spawn(Node::default());
+        pool.free(a);
+        let b: Handle<Node> = pool.spawn(Node::default());
+        assert_eq!(a.index(), b.index());
+        assert_ne!(encode_handle(a), encode_handle(b));
+        assert_eq!(decode_handle(encode_handle(b).unwrap()), b);
+        assert_eq!(encode_handle(Handle::NONE), None);
+    }
+}
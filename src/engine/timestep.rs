@@ -0,0 +1,101 @@
+//! Contains fixed time step of scene simulation.
+//!
+//! By default engine updates scenes once per frame with frame time, so physics, particles and
+//! game logic behave differently at different frame rates: fast objects can pass through walls
+//! at low frame rate, particles fly further at high one, etc. With fixed time step (see
+//! `Engine::set_fixed_update_rate`) scenes are updated with the same time step regardless of
+//! frame rate: time of frames is accumulated and scenes are updated as many times as there are
+//! whole steps in accumulated time. Rest of time is used to interpolate global transforms of
+//! nodes between last two steps (see `scene::interpolation`), so motion stays smooth even if
+//! frame rate is higher than rate of simulation.
+//!
+//! If a frame takes too long (for example when window is dragged), amount of steps is limited
+//! by `max_steps`, otherwise simulation would take even more time in next frame and engine
+//! would never catch up. Time beyond the limit is dropped, so simulation slows down instead.
+
+/// See module docs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixedTimestep {
+    step: f32,
+    accumulator: f32,
+    max_steps: u32,
+    last_step_count: u32,
+}
+
+impl FixedTimestep {
+    /// Default maximum amount of steps per frame.
+    pub const DEFAULT_MAX_STEPS: u32 = 5;
+
+    /// Creates new time step with given amount of steps per second.
+    pub fn new(rate: f32) -> Self {
+        Self {
+            step: 1.0 / rate.max(std::f32::EPSILON),
+            accumulator: 0.0,
+            max_steps: Self::DEFAULT_MAX_STEPS,
+            last_step_count: 0,
+        }
+    }
+
+    /// Returns amount of steps per second.
+    pub fn rate(&self) -> f32 {
+        1.0 / self.step
+    }
+
+    /// Returns duration of a step in seconds.
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    /// Sets maximum amount of steps per frame, see module docs.
+    pub fn set_max_steps(&mut self, max_steps: u32) {
+        self.max_steps = max_steps.max(1);
+    }
+
+    /// Returns maximum amount of steps per frame.
+    pub fn max_steps(&self) -> u32 {
+        self.max_steps
+    }
+
+    /// Adds frame time to accumulated time and returns amount of steps that must be done.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        self.accumulator += dt.max(0.0);
+        let mut count = 0;
+        while self.accumulator >= self.step && count < self.max_steps {
+            self.accumulator -= self.step;
+            count += 1;
+        }
+        if count == self.max_steps {
+            self.accumulator = self.accumulator.min(self.step);
+        }
+        self.last_step_count = count;
+        count
+    }
+
+    /// Returns amount of steps done at last `advance`.
+    pub fn last_step_count(&self) -> u32 {
+        self.last_step_count
+    }
+
+    /// Returns part of a step which was accumulated but not simulated yet, in [0; 1] range.
+    /// It is used to interpolate transforms between last two steps.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.step).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::timestep::FixedTimestep;
+
+    #[test]
+    fn steps_are_accumulated() {
+        let mut timestep = FixedTimestep::new(50.0);
+        assert_eq!(timestep.advance(0.01), 0);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(timestep.advance(0.035), 2);
+        assert!((timestep.alpha() - 0.25).abs() < 1e-4);
+        // Long frame is clamped to maximum amount of steps.
+        assert_eq!(timestep.advance(10.0), FixedTimestep::DEFAULT_MAX_STEPS);
+        assert!(timestep.alpha() <= 1.0);
+    }
+}
@@ -0,0 +1,497 @@
+//! Contains virtual file system which is used by resource manager to read resources.
+//!
+//! Virtual file system (VFS) maps virtual paths (such as `data/textures/wall.png`) to files
+//! in mounted sources. Source can be a directory or a packed archive, so shipped games can
+//! pack assets into a few files instead of thousands of small files. Every source has a
+//! priority, sources with higher priority override files of sources with lower priority,
+//! which allows to add mods or patches without touching original files: mount a mod with
+//! higher priority and its files will be used instead of original ones. If priorities are
+//! equal, source mounted later wins.
+//!
+//! Files which are not found in mounted sources are read from file system directly, so VFS
+//! without mounts behaves exactly as plain file system.
+//!
+//! # Archives
+//!
+//! Archives are ZIP files (commonly with `.zip` or `.pak` extension), entries must be either
+//! stored or compressed with Deflate. Encrypted entries and ZIP64 archives are not supported.
+//! Paths of entries are used as virtual paths, so archive with `data/textures/wall.png` entry
+//! provides `data/textures/wall.png` file.
+//!
+//! Some resources (sound buffers, native scenes and prefabs) must be read from physical file,
+//! such files are extracted from archives into temporary directory when they're requested,
+//! see `Vfs::extract`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::engine::resource_manager::ResourceManager;
+//!
+//! fn mount_game_data(resource_manager: &mut ResourceManager) {
+//!     let vfs = resource_manager.vfs_mut();
+//!     vfs.mount_archive("data.pak", 0).unwrap();
+//!     // Files of the mod override files of the game.
+//!     vfs.mount_directory("mods/better_textures", 10);
+//! }
+//! ```
+
+use crate::{
+    core::visitor::{VisitError, Visitor},
+    engine::visitor_compression::{self, VisitorCompression},
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
+
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIZE: u64 = 22;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+const FLAG_ENCRYPTED: u16 = 0x1;
+
+fn invalid_data<S: Into<String>>(message: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Converts path to form which is used as key of archive entries: components separated by
+/// `/` without `.` and `..` components.
+fn normalize<P: AsRef<Path>>(path: P) -> String {
+    let mut components = Vec::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::Normal(name) => components.push(name.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                components.pop();
+            }
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => (),
+        }
+    }
+    components.join("/")
+}
+
+struct ArchiveEntry {
+    local_header_offset: u64,
+    compressed_size: u64,
+    size: u64,
+    method: u16,
+    flags: u16,
+}
+
+struct Archive {
+    path: PathBuf,
+    entries: HashMap<String, ArchiveEntry>,
+}
+
+impl Archive {
+    fn open(path: &Path) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let file_size = file.seek(SeekFrom::End(0))?;
+        if file_size < END_OF_CENTRAL_DIRECTORY_SIZE {
+            return Err(invalid_data("file is too small to be an archive"));
+        }
+
+        // End of central directory record is followed by a comment of variable size, so it
+        // must be searched backwards.
+        let tail_size = file_size.min(END_OF_CENTRAL_DIRECTORY_SIZE + u16::max_value() as u64);
+        let mut tail = vec![0; tail_size as usize];
+        file.seek(SeekFrom::Start(file_size - tail_size))?;
+        file.read_exact(&mut tail)?;
+        let record_position = (0..=(tail.len() - END_OF_CENTRAL_DIRECTORY_SIZE as usize))
+            .rev()
+            .find(|&i| tail[i..(i + 4)] == END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes())
+            .ok_or_else(|| invalid_data("end of central directory is not found"))?;
+
+        let mut record = Cursor::new(&tail[(record_position + 4)..]);
+        let _disk = record.read_u16::<LittleEndian>()?;
+        let _central_directory_disk = record.read_u16::<LittleEndian>()?;
+        let _disk_entry_count = record.read_u16::<LittleEndian>()?;
+        let entry_count = record.read_u16::<LittleEndian>()?;
+        let central_directory_size = record.read_u32::<LittleEndian>()?;
+        let central_directory_offset = record.read_u32::<LittleEndian>()?;
+        if central_directory_offset == u32::max_value() {
+            return Err(invalid_data("ZIP64 archives are not supported"));
+        }
+
+        let mut central_directory = vec![0; central_directory_size as usize];
+        file.seek(SeekFrom::Start(central_directory_offset as u64))?;
+        file.read_exact(&mut central_directory)?;
+        let mut reader = Cursor::new(central_directory);
+
+        let mut entries = HashMap::new();
+        for _ in 0..entry_count {
+            if reader.read_u32::<LittleEndian>()? != CENTRAL_DIRECTORY_SIGNATURE {
+                return Err(invalid_data("corrupted central directory"));
+            }
+            let _version_made_by = reader.read_u16::<LittleEndian>()?;
+            let _version_needed = reader.read_u16::<LittleEndian>()?;
+            let flags = reader.read_u16::<LittleEndian>()?;
+            let method = reader.read_u16::<LittleEndian>()?;
+            let _modification_time = reader.read_u16::<LittleEndian>()?;
+            let _modification_date = reader.read_u16::<LittleEndian>()?;
+            let _crc = reader.read_u32::<LittleEndian>()?;
+            let compressed_size = reader.read_u32::<LittleEndian>()?;
+            let size = reader.read_u32::<LittleEndian>()?;
+            let name_length = reader.read_u16::<LittleEndian>()?;
+            let extra_length = reader.read_u16::<LittleEndian>()?;
+            let comment_length = reader.read_u16::<LittleEndian>()?;
+            let _disk_start = reader.read_u16::<LittleEndian>()?;
+            let _internal_attributes = reader.read_u16::<LittleEndian>()?;
+            let _external_attributes = reader.read_u32::<LittleEndian>()?;
+            let local_header_offset = reader.read_u32::<LittleEndian>()?;
+            let mut name = vec![0; name_length as usize];
+            reader.read_exact(&mut name)?;
+            reader.seek(SeekFrom::Current(extra_length as i64 + comment_length as i64))?;
+
+            if compressed_size == u32::max_value()
+                || size == u32::max_value()
+                || local_header_offset == u32::max_value()
+            {
+                return Err(invalid_data("ZIP64 archives are not supported"));
+            }
+
+            let name = String::from_utf8_lossy(&name).into_owned();
+            // Directories are not needed, they're implied by paths of files.
+            if !name.ends_with('/') {
+                entries.insert(
+                    normalize(&name),
+                    ArchiveEntry {
+                        local_header_offset: local_header_offset as u64,
+                        compressed_size: compressed_size as u64,
+                        size: size as u64,
+                        method,
+                        flags,
+                    },
+                );
+            }
+        }
+
+        Ok(Self {
+            path: path.to_owned(),
+            entries,
+        })
+    }
+
+    fn read(&self, entry: &ArchiveEntry) -> io::Result<Vec<u8>> {
+        if entry.flags & FLAG_ENCRYPTED != 0 {
+            return Err(invalid_data("encrypted archive entries are not supported"));
+        }
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(entry.local_header_offset))?;
+        if file.read_u32::<LittleEndian>()? != LOCAL_HEADER_SIGNATURE {
+            return Err(invalid_data("corrupted local header of archive entry"));
+        }
+        // Local header has its own lengths of name and extra field, they're not always equal
+        // to ones in central directory.
+        file.seek(SeekFrom::Current(22))?;
+        let name_length = file.read_u16::<LittleEndian>()?;
+        let extra_length = file.read_u16::<LittleEndian>()?;
+        file.seek(SeekFrom::Current(name_length as i64 + extra_length as i64))?;
+
+        let mut compressed = vec![0; entry.compressed_size as usize];
+        file.read_exact(&mut compressed)?;
+
+        let data = match entry.method {
+            METHOD_STORED => compressed,
+            METHOD_DEFLATE => inflate::inflate_bytes(&compressed).map_err(invalid_data)?,
+            method => {
+                return Err(invalid_data(format!(
+                    "compression method {} is not supported",
+                    method
+                )))
+            }
+        };
+        if data.len() as u64 != entry.size {
+            return Err(invalid_data("size of unpacked archive entry is invalid"));
+        }
+        Ok(data)
+    }
+}
+
+#[derive(Clone)]
+enum Source {
+    Directory(PathBuf),
+    Archive(Arc<Archive>),
+}
+
+impl Source {
+    fn path(&self) -> &Path {
+        match self {
+            Source::Directory(path) => path,
+            Source::Archive(archive) => &archive.path,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Mount {
+    priority: i32,
+    source: Source,
+}
+
+enum Location<'a> {
+    File(PathBuf),
+    Archive(&'a Archive, &'a ArchiveEntry),
+}
+
+/// See module docs. VFS is cheap to clone, archives are shared between clones.
+#[derive(Clone, Default)]
+pub struct Vfs {
+    // Sorted by priority in descending order.
+    mounts: Vec<Mount>,
+}
+
+impl Vfs {
+    /// Creates new VFS without mounted sources.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn add_mount(&mut self, priority: i32, source: Source) {
+        let index = self
+            .mounts
+            .iter()
+            .position(|mount| mount.priority <= priority)
+            .unwrap_or_else(|| self.mounts.len());
+        self.mounts.insert(index, Mount { priority, source });
+    }
+
+    /// Mounts directory with given priority. Virtual paths are relative to the directory.
+    pub fn mount_directory<P: AsRef<Path>>(&mut self, path: P, priority: i32) {
+        self.add_mount(priority, Source::Directory(path.as_ref().to_owned()));
+    }
+
+    /// Mounts archive with given priority, see module docs. Only directory of archive is read
+    /// here, entries are read when they're requested.
+    pub fn mount_archive<P: AsRef<Path>>(&mut self, path: P, priority: i32) -> io::Result<()> {
+        let archive = Archive::open(path.as_ref())?;
+        self.add_mount(priority, Source::Archive(Arc::new(archive)));
+        Ok(())
+    }
+
+    /// Unmounts directory or archive with given path. Returns true if it was mounted.
+    pub fn unmount<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        let count = self.mounts.len();
+        self.mounts.retain(|mount| mount.source.path() != path.as_ref());
+        self.mounts.len() != count
+    }
+
+    /// Returns iterator over paths of mounted sources in order of their priority.
+    pub fn mounted(&self) -> impl Iterator<Item = &Path> {
+        self.mounts.iter().map(|mount| mount.source.path())
+    }
+
+    fn locate(&self, path: &Path) -> Option<Location> {
+        let name = normalize(path);
+        for mount in self.mounts.iter() {
+            match &mount.source {
+                Source::Directory(directory) => {
+                    let file_path = directory.join(&name);
+                    if file_path.is_file() {
+                        return Some(Location::File(file_path));
+                    }
+                }
+                Source::Archive(archive) => {
+                    if let Some(entry) = archive.entries.get(&name) {
+                        return Some(Location::Archive(archive, entry));
+                    }
+                }
+            }
+        }
+        if path.is_file() {
+            Some(Location::File(path.to_owned()))
+        } else {
+            None
+        }
+    }
+
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{:?} is not found in virtual file system", path),
+        )
+    }
+
+    /// Returns true if file with given virtual path exists.
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.locate(path.as_ref()).is_some()
+    }
+
+    /// Reads whole file with given virtual path.
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<u8>> {
+        match self.locate(path.as_ref()) {
+            Some(Location::File(file_path)) => std::fs::read(file_path),
+            Some(Location::Archive(archive, entry)) => archive.read(entry),
+            None => Err(Self::not_found(path.as_ref())),
+        }
+    }
+
+    /// Opens file with given virtual path for reading. Archive entries are unpacked into
+    /// memory first.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<Box<dyn Read + Send>> {
+        match self.locate(path.as_ref()) {
+            Some(Location::File(file_path)) => Ok(Box::new(File::open(file_path)?)),
+            Some(Location::Archive(archive, entry)) => {
+                Ok(Box::new(Cursor::new(archive.read(entry)?)))
+            }
+            None => Err(Self::not_found(path.as_ref())),
+        }
+    }
+
+    /// Returns size of file with given virtual path, for archive entries it is size of
+    /// unpacked entry.
+    pub fn file_size<P: AsRef<Path>>(&self, path: P) -> Option<u64> {
+        match self.locate(path.as_ref())? {
+            Location::File(file_path) => std::fs::metadata(file_path).ok().map(|m| m.len()),
+            Location::Archive(_, entry) => Some(entry.size),
+        }
+    }
+
+    /// Returns modification time of file with given virtual path. Modification time of
+    /// archive entry is modification time of the archive.
+    pub fn modification_time<P: AsRef<Path>>(&self, path: P) -> Option<SystemTime> {
+        let physical_path = match self.locate(path.as_ref())? {
+            Location::File(file_path) => file_path,
+            Location::Archive(archive, _) => archive.path.clone(),
+        };
+        std::fs::metadata(physical_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Returns path of physical file with contents of file with given virtual path. Archive
+    /// entries are extracted into temporary directory, so this method should be used only for
+    /// resources that can't be read from memory. Entry is extracted again only if archive is
+    /// newer than extracted file, so files which are still in use are not overwritten.
+    pub fn extract<P: AsRef<Path>>(&self, path: P) -> io::Result<PathBuf> {
+        match self.locate(path.as_ref()) {
+            Some(Location::File(file_path)) => Ok(file_path),
+            Some(Location::Archive(archive, entry)) => {
+                let archive_name = archive.path.file_name().unwrap_or_default();
+                let extracted_path = std::env::temp_dir()
+                    .join("rg3d-vfs")
+                    .join(archive_name)
+                    .join(normalize(path.as_ref()));
+                let modified = |path: &Path| {
+                    std::fs::metadata(path)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                };
+                let is_up_to_date = match std::fs::metadata(&extracted_path) {
+                    Ok(metadata) => {
+                        metadata.len() == entry.size
+                            && modified(&extracted_path) >= modified(&archive.path)
+                    }
+                    Err(_) => false,
+                };
+                if !is_up_to_date {
+                    if let Some(directory) = extracted_path.parent() {
+                        std::fs::create_dir_all(directory)?;
+                    }
+                    std::fs::write(&extracted_path, archive.read(entry)?)?;
+                }
+                Ok(extracted_path)
+            }
+            None => Err(Self::not_found(path.as_ref())),
+        }
+    }
+
+    /// Creates visitor from file with given virtual path. Compressed files are detected and
+    /// unpacked automatically, see `visitor_compression` module docs.
+    pub fn load_visitor<P: AsRef<Path>>(&self, path: P) -> Result<Visitor, VisitError> {
+        let mut reader = self.open(path.as_ref())?;
+        let mut header = Vec::new();
+        reader.by_ref().take(16).read_to_end(&mut header)?;
+        match visitor_compression::compression_of(&header) {
+            VisitorCompression::None => Visitor::load_binary(self.extract(path)?),
+            _ => {
+                let mut data = header;
+                reader.read_to_end(&mut data)?;
+                visitor_compression::load_compressed(&data)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::vfs::Vfs;
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    // Writes archive with stored (not compressed) entries.
+    fn write_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut central_directory = Vec::new();
+        for (name, contents) in entries {
+            let offset = data.len() as u32;
+            data.write_u32::<LittleEndian>(0x0403_4b50).unwrap();
+            for _ in 0..11 {
+                data.write_u16::<LittleEndian>(0).unwrap();
+            }
+            data.write_u16::<LittleEndian>(name.len() as u16).unwrap();
+            data.write_u16::<LittleEndian>(0).unwrap();
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(contents);
+
+            let cd = &mut central_directory;
+            cd.write_u32::<LittleEndian>(0x0201_4b50).unwrap();
+            for _ in 0..8 {
+                cd.write_u16::<LittleEndian>(0).unwrap();
+            }
+            cd.write_u32::<LittleEndian>(contents.len() as u32).unwrap();
+            cd.write_u32::<LittleEndian>(contents.len() as u32).unwrap();
+            cd.write_u16::<LittleEndian>(name.len() as u16).unwrap();
+            for _ in 0..4 {
+                cd.write_u16::<LittleEndian>(0).unwrap();
+            }
+            cd.write_u32::<LittleEndian>(0).unwrap();
+            cd.write_u32::<LittleEndian>(offset).unwrap();
+            cd.extend_from_slice(name.as_bytes());
+        }
+        let central_directory_offset = data.len() as u32;
+        data.extend_from_slice(&central_directory);
+        data.write_u32::<LittleEndian>(0x0605_4b50).unwrap();
+        data.write_u16::<LittleEndian>(0).unwrap();
+        data.write_u16::<LittleEndian>(0).unwrap();
+        data.write_u16::<LittleEndian>(entries.len() as u16).unwrap();
+        data.write_u16::<LittleEndian>(entries.len() as u16).unwrap();
+        data.write_u32::<LittleEndian>(central_directory.len() as u32).unwrap();
+        data.write_u32::<LittleEndian>(central_directory_offset).unwrap();
+        data.write_u16::<LittleEndian>(0).unwrap();
+        data
+    }
+
+    #[test]
+    fn archive_and_directory_priorities() {
+        let root = std::env::temp_dir().join("rg3d-vfs-test");
+        let mod_dir = root.join("mod");
+        std::fs::create_dir_all(mod_dir.join("data")).unwrap();
+        std::fs::write(mod_dir.join("data/a.txt"), b"mod").unwrap();
+        let archive_path = root.join("data.pak");
+        let archive = write_archive(&[("data/a.txt", b"game"), ("data/b.txt", b"only game")]);
+        std::fs::write(&archive_path, archive).unwrap();
+
+        let mut vfs = Vfs::new();
+        vfs.mount_archive(&archive_path, 0).unwrap();
+        assert_eq!(vfs.read("data/a.txt").unwrap(), b"game");
+        assert_eq!(vfs.read("./data/../data/b.txt").unwrap(), b"only game");
+        assert_eq!(vfs.file_size("data/b.txt"), Some(9));
+        assert!(!vfs.exists("data/c.txt"));
+
+        vfs.mount_directory(&mod_dir, 10);
+        assert_eq!(vfs.read("data/a.txt").unwrap(), b"mod");
+        assert_eq!(vfs.read("data/b.txt").unwrap(), b"only game");
+        assert_eq!(std::fs::read(vfs.extract("data/b.txt").unwrap()).unwrap(), b"only game");
+
+        assert!(vfs.unmount(&mod_dir));
+        assert_eq!(vfs.read("data/a.txt").unwrap(), b"game");
+    }
+}
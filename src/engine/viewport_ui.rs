@@ -0,0 +1,149 @@
+//! Viewport user interface is a user interface bound to a viewport of some camera.
+//!
+//! It is useful for split-screen games where each player should have its own HUD: every
+//! layer is laid out using size of camera viewport, drawn inside it, and receives mouse
+//! input only when cursor is inside of the viewport. Keyboard input is routed only to a
+//! layer that was clicked last, so players won't type into each other's widgets.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::engine::{Engine, viewport_ui::ViewportUi};
+//! use rg3d::gui::node::StubNode;
+//! use rg3d::scene::{Scene, node::Node};
+//! use rg3d::core::pool::Handle;
+//!
+//! fn add_player_hud(
+//!     engine: &mut Engine<(), StubNode>,
+//!     scene: Handle<Scene>,
+//!     camera: Handle<Node>
+//! ) -> usize {
+//!     engine.viewport_interfaces.push(ViewportUi::new(scene, camera));
+//!     engine.viewport_interfaces.len() - 1
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::{vec2::Vec2, Rect},
+        pool::Handle,
+    },
+    gui::{
+        message::{ButtonState, MessageData, OsEvent},
+        Control, UserInterface,
+    },
+    scene::{node::Node, Scene, SceneContainer},
+};
+
+/// See module docs.
+pub struct ViewportUi<M: MessageData, C: Control<M, C>> {
+    /// User interface of the layer. Its screen size is the size of camera viewport.
+    pub ui: UserInterface<M, C>,
+    scene: Handle<Scene>,
+    camera: Handle<Node>,
+    viewport: Rect<i32>,
+    frame_height: f32,
+    cursor_inside: bool,
+    has_focus: bool,
+}
+
+impl<M: MessageData, C: Control<M, C>> ViewportUi<M, C> {
+    /// Creates new user interface bound to viewport of given camera from given scene.
+    pub fn new(scene: Handle<Scene>, camera: Handle<Node>) -> Self {
+        Self {
+            ui: UserInterface::new(Vec2::new(1.0, 1.0)),
+            scene,
+            camera,
+            viewport: Rect::new(0, 0, 0, 0),
+            frame_height: 0.0,
+            cursor_inside: false,
+            has_focus: false,
+        }
+    }
+
+    /// Returns handle of scene of the camera.
+    pub fn scene(&self) -> Handle<Scene> {
+        self.scene
+    }
+
+    /// Returns handle of the camera to which viewport user interface is bound to.
+    pub fn camera(&self) -> Handle<Node> {
+        self.camera
+    }
+
+    /// Binds user interface to other camera.
+    pub fn set_camera(&mut self, scene: Handle<Scene>, camera: Handle<Node>) {
+        self.scene = scene;
+        self.camera = camera;
+    }
+
+    /// Returns viewport rectangle in pixels (in the same coordinate system as camera
+    /// viewport) that was calculated at last update. It will be empty if camera does not
+    /// exist anymore or it is disabled.
+    pub fn viewport(&self) -> Rect<i32> {
+        self.viewport
+    }
+
+    /// Returns true if layer will receive keyboard input.
+    pub fn has_focus(&self) -> bool {
+        self.has_focus
+    }
+
+    /// Converts position in window coordinates to position in local coordinates of layer.
+    /// Camera viewport has origin at bottom left corner of window, but cursor position has
+    /// origin at top left corner.
+    pub fn window_to_local(&self, position: Vec2) -> Vec2 {
+        let top = self.frame_height - (self.viewport.y + self.viewport.h) as f32;
+        Vec2::new(position.x - self.viewport.x as f32, position.y - top)
+    }
+
+    fn contains_local(&self, position: Vec2) -> bool {
+        position.x >= 0.0
+            && position.y >= 0.0
+            && position.x < self.viewport.w as f32
+            && position.y < self.viewport.h as f32
+    }
+
+    /// Passes OS event to user interface if the event belongs to viewport of the layer.
+    /// Cursor position is converted to local coordinates of layer. Returns true if event
+    /// was passed to user interface and was processed by it.
+    pub fn process_os_event(&mut self, event: &OsEvent) -> bool {
+        match event {
+            OsEvent::CursorMoved { position } => {
+                let local = self.window_to_local(*position);
+                self.cursor_inside = self.contains_local(local);
+                self.ui
+                    .process_os_event(&OsEvent::CursorMoved { position: local })
+            }
+            OsEvent::MouseInput { state, .. } => {
+                if let ButtonState::Pressed = state {
+                    self.has_focus = self.cursor_inside;
+                }
+                self.cursor_inside && self.ui.process_os_event(event)
+            }
+            OsEvent::MouseWheel(..) => self.cursor_inside && self.ui.process_os_event(event),
+            OsEvent::KeyboardModifiers(_) => self.ui.process_os_event(event),
+            _ => self.has_focus && self.ui.process_os_event(event),
+        }
+    }
+
+    pub(in crate) fn update(&mut self, scenes: &SceneContainer, frame_size: Vec2, dt: f32) {
+        self.frame_height = frame_size.y;
+        self.viewport = Rect::new(0, 0, 0, 0);
+        if scenes.is_valid_handle(self.scene) {
+            let graph = &scenes[self.scene].graph;
+            if graph.is_valid_handle(self.camera) {
+                if let Node::Camera(camera) = &graph[self.camera] {
+                    if camera.is_enabled() {
+                        self.viewport = camera.viewport_pixels(frame_size);
+                    }
+                }
+            }
+        }
+
+        let size = Vec2::new(self.viewport.w as f32, self.viewport.h as f32);
+        if size.x > 0.0 && size.y > 0.0 {
+            self.ui.update(size, dt);
+        }
+    }
+}
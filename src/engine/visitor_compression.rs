@@ -0,0 +1,137 @@
+//! Contains compression of files that are written by `Visitor`.
+//!
+//! Native scenes and saved games contain meshes, particles and other big arrays, so their
+//! files can take hundreds of megabytes. Such files compress very well, `save_visitor` can
+//! write visitor with LZ4 compression, which is fast enough to not slow down saving and
+//! loading noticeably. Compressed files start with a magic header, so they're detected
+//! automatically by `Vfs::load_visitor` (which is used to load scenes, prefabs, gradients and
+//! import options), and there is no need to know how a file was saved to load it. Files that
+//! were written by `Visitor::save_binary` are loaded as before.
+//!
+//! Visitor can only read files, so compressed file is unpacked into temporary directory while
+//! it is loaded.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::visitor::{Visit, VisitResult, Visitor},
+//!     engine::visitor_compression::{self, VisitorCompression},
+//!     scene::Scene,
+//! };
+//!
+//! fn save_level(scene: &mut Scene) -> VisitResult {
+//!     let mut visitor = Visitor::new();
+//!     scene.visit("Scene", &mut visitor)?;
+//!     visitor_compression::save_visitor(&visitor, "level.rgs", VisitorCompression::Lz4)
+//! }
+//! ```
+
+use crate::core::visitor::{VisitError, VisitResult, Visitor};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const LZ4_MAGIC: &[u8; 8] = b"RG3D-LZ4";
+
+/// Compression of visitor files, see module docs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VisitorCompression {
+    /// File is written as is, exactly as `Visitor::save_binary` does it.
+    None,
+    /// File is compressed using LZ4.
+    Lz4,
+}
+
+impl Default for VisitorCompression {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+// Files of different loads must not clash when they're loaded from different threads.
+fn temporary_path() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    std::env::temp_dir().join(format!(
+        "rg3d-visitor-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
+
+/// Writes visitor to file with given path using given compression.
+pub fn save_visitor<P: AsRef<Path>>(
+    visitor: &Visitor,
+    path: P,
+    compression: VisitorCompression,
+) -> VisitResult {
+    match compression {
+        VisitorCompression::None => visitor.save_binary(path),
+        VisitorCompression::Lz4 => {
+            let uncompressed_path = temporary_path();
+            let result = visitor
+                .save_binary(&uncompressed_path)
+                .and_then(|_| std::fs::read(&uncompressed_path).map_err(VisitError::from));
+            let _ = std::fs::remove_file(&uncompressed_path);
+            let mut data = LZ4_MAGIC.to_vec();
+            data.extend(lz4_flex::compress_prepend_size(&result?));
+            Ok(std::fs::write(path, data)?)
+        }
+    }
+}
+
+/// Returns compression of visitor file with given contents, only first bytes of the file are
+/// required.
+pub fn compression_of(data: &[u8]) -> VisitorCompression {
+    if data.starts_with(LZ4_MAGIC) {
+        VisitorCompression::Lz4
+    } else {
+        VisitorCompression::None
+    }
+}
+
+/// Creates visitor from contents of compressed file.
+pub(in crate) fn load_compressed(data: &[u8]) -> Result<Visitor, VisitError> {
+    let uncompressed = match compression_of(data) {
+        VisitorCompression::None => {
+            return Err(VisitError::from("Visitor file is not compressed".to_owned()))
+        }
+        VisitorCompression::Lz4 => {
+            lz4_flex::decompress_size_prepended(&data[LZ4_MAGIC.len()..])
+                .map_err(|e| format!("Damaged LZ4 visitor file: {:?}", e))?
+        }
+    };
+    let uncompressed_path = temporary_path();
+    std::fs::write(&uncompressed_path, uncompressed)?;
+    let visitor = Visitor::load_binary(&uncompressed_path);
+    let _ = std::fs::remove_file(&uncompressed_path);
+    visitor
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::visitor::{Visit, Visitor},
+        engine::visitor_compression::{self, VisitorCompression},
+    };
+
+    #[test]
+    fn compressed_visitor_round_trip() {
+        let path = std::env::temp_dir().join("rg3d-compressed-visitor-test");
+        let mut values = vec![42u32; 10_000];
+        let mut visitor = Visitor::new();
+        values.visit("Values", &mut visitor).unwrap();
+        visitor_compression::save_visitor(&visitor, &path, VisitorCompression::Lz4).unwrap();
+
+        let data = std::fs::read(&path).unwrap();
+        assert_eq!(visitor_compression::compression_of(&data), VisitorCompression::Lz4);
+        assert!(data.len() < 10_000);
+
+        let mut visitor = visitor_compression::load_compressed(&data).unwrap();
+        let mut loaded = Vec::<u32>::new();
+        loaded.visit("Values", &mut visitor).unwrap();
+        assert_eq!(loaded, values);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -16,7 +16,8 @@
 //!     - Text box
 //!     - List box
 //!     - Window
-//! - Fonts - TTF Loader (compound characters are not supported yet)
+//! - Fonts - TTF Loader (compound characters are not supported yet), glyphs are drawn using signed
+//!   distance fields, so text stays sharp when scaled
 //! - Built-in save/load using object visitor - save/load state of engine in one call.
 //! - Skinning
 //! - Animation blending - allows you to blend your animations as you want to, i.e. idle animation can be blended with walk.
@@ -44,9 +45,12 @@ extern crate glutin;
 extern crate image;
 extern crate inflate;
 extern crate lexical;
+extern crate lz4_flex;
 extern crate rand;
 #[macro_use]
 extern crate lazy_static;
+// Code generated by derive macros refers to the engine as `rg3d`.
+extern crate self as rg3d;
 
 #[cfg(test)]
 extern crate imageproc;
@@ -61,6 +65,8 @@ pub mod utils;
 pub use glutin::*;
 
 pub use rg3d_core as core;
+pub use rg3d_derive::Visit;
 pub use rg3d_physics as physics;
 pub use rg3d_sound as sound;
 pub use rg3d_ui as gui;
+
@@ -0,0 +1,469 @@
+//! Automatic exposure renderer measures brightness of high dynamic range frame and maps
+//! it into displayable range.
+//!
+//! It is done in three steps: at first every pixel of a sparse grid over the frame is
+//! drawn as a point into 1D histogram of brightness, then histogram is reduced into
+//! average brightness which is smoothly blended with brightness of previous frame, and
+//! finally frame is tone mapped into back buffer using adapted exposure. Adapted exposure
+//! never leaves GPU, each camera has its own pair of 1x1 textures which are swapped
+//! every frame.
+
+use crate::{
+    core::{
+        color::Color,
+        math::{mat4::Mat4, vec2::Vec2, vec3::Vec3, Rect},
+        pool::Handle,
+        scope_profile,
+    },
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{
+                Attachment, AttachmentKind, BackBuffer, CullFace, DrawParameters, FrameBuffer,
+                FrameBufferTrait,
+            },
+            geometry_buffer::{
+                AttributeDefinition, AttributeKind, ElementKind, GeometryBuffer,
+                GeometryBufferKind,
+            },
+            gl,
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::{Coordinate, GpuTexture, GpuTextureKind, PixelKind, WrapMode},
+            state::State,
+        },
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics, TextureCache,
+    },
+    scene::{camera::AutoExposure, node::Node},
+};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+/// Amount of bins in brightness histogram.
+const BIN_COUNT: usize = 64;
+
+/// Size of grid of samples taken from the frame. There is no need to measure every pixel,
+/// brightness changes smoothly over most of the frame.
+const SAMPLE_GRID_SIZE: usize = 64;
+
+/// Portion of darkest samples that are ignored when average brightness is calculated.
+const LOW_PERCENT: f32 = 0.1;
+
+/// Portion of samples (starting from darkest) after which brighter samples are ignored.
+const HIGH_PERCENT: f32 = 0.9;
+
+struct HistogramShader {
+    program: GpuProgram,
+    frame_texture: UniformLocation,
+    metering_mask: UniformLocation,
+    min_ev: UniformLocation,
+    max_ev: UniformLocation,
+    bin_count: UniformLocation,
+}
+
+impl HistogramShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/auto_exposure_histogram_fs.glsl");
+        let vertex_source = include_str!("shaders/auto_exposure_histogram_vs.glsl");
+        let program =
+            GpuProgram::from_source("AutoExposureHistogramShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            frame_texture: program.uniform_location("frameTexture")?,
+            metering_mask: program.uniform_location("meteringMask")?,
+            min_ev: program.uniform_location("minEv")?,
+            max_ev: program.uniform_location("maxEv")?,
+            bin_count: program.uniform_location("binCount")?,
+            program,
+        })
+    }
+}
+
+struct AdaptationShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    histogram: UniformLocation,
+    previous_exposure: UniformLocation,
+    bin_count: UniformLocation,
+    min_ev: UniformLocation,
+    max_ev: UniformLocation,
+    low_percent: UniformLocation,
+    high_percent: UniformLocation,
+    adaptation: UniformLocation,
+    first_frame: UniformLocation,
+}
+
+impl AdaptationShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/auto_exposure_adapt_fs.glsl");
+        let vertex_source = include_str!("shaders/flat_vs.glsl");
+        let program = GpuProgram::from_source(
+            "AutoExposureAdaptationShader",
+            vertex_source,
+            fragment_source,
+        )?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            histogram: program.uniform_location("histogram")?,
+            previous_exposure: program.uniform_location("previousExposure")?,
+            bin_count: program.uniform_location("binCount")?,
+            min_ev: program.uniform_location("minEv")?,
+            max_ev: program.uniform_location("maxEv")?,
+            low_percent: program.uniform_location("lowPercent")?,
+            high_percent: program.uniform_location("highPercent")?,
+            adaptation: program.uniform_location("adaptation")?,
+            first_frame: program.uniform_location("firstFrame")?,
+            program,
+        })
+    }
+}
+
+struct ToneMapShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    frame_texture: UniformLocation,
+    exposure_texture: UniformLocation,
+    key_value: UniformLocation,
+}
+
+impl ToneMapShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/auto_exposure_tonemap_fs.glsl");
+        let vertex_source = include_str!("shaders/flat_vs.glsl");
+        let program =
+            GpuProgram::from_source("AutoExposureToneMapShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            frame_texture: program.uniform_location("frameTexture")?,
+            exposure_texture: program.uniform_location("exposureTexture")?,
+            key_value: program.uniform_location("keyValue")?,
+            program,
+        })
+    }
+}
+
+fn make_f32_framebuffer(
+    state: &mut State,
+    width: usize,
+    height: usize,
+) -> Result<FrameBuffer, RendererError> {
+    let mut texture = GpuTexture::new(
+        state,
+        GpuTextureKind::Rectangle { width, height },
+        PixelKind::F32,
+        None,
+    )?;
+    texture
+        .bind_mut(state, 0)
+        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+    FrameBuffer::new(
+        state,
+        None,
+        vec![Attachment {
+            kind: AttachmentKind::Color,
+            texture: Rc::new(RefCell::new(texture)),
+        }],
+    )
+}
+
+fn texture_of(framebuffer: &FrameBuffer) -> Rc<RefCell<GpuTexture>> {
+    framebuffer.color_attachments()[0].texture.clone()
+}
+
+/// Adapted exposure of a camera. Exposure of previous frame is read from one buffer
+/// while new exposure is written to another.
+struct Adaptation {
+    exposure: [FrameBuffer; 2],
+    current: usize,
+    first_frame: bool,
+}
+
+impl Adaptation {
+    fn new(state: &mut State) -> Result<Self, RendererError> {
+        let mut exposure = [
+            make_f32_framebuffer(state, 1, 1)?,
+            make_f32_framebuffer(state, 1, 1)?,
+        ];
+        for framebuffer in exposure.iter_mut() {
+            framebuffer.clear(
+                state,
+                Rect::new(0, 0, 1, 1),
+                Some(Color::from_rgba(0, 0, 0, 0)),
+                None,
+                None,
+            );
+        }
+        Ok(Self {
+            exposure,
+            current: 0,
+            first_frame: true,
+        })
+    }
+}
+
+pub struct AutoExposureRenderer {
+    histogram_shader: HistogramShader,
+    adaptation_shader: AdaptationShader,
+    tone_map_shader: ToneMapShader,
+    histogram: FrameBuffer,
+    samples: GeometryBuffer<Vec2>,
+    quad: SurfaceSharedData,
+    adaptations: HashMap<Handle<Node>, Adaptation>,
+}
+
+pub(in crate) struct AutoExposureRenderContext<'a, 'b> {
+    pub state: &'a mut State,
+    pub backbuffer: &'a mut BackBuffer,
+    pub viewport: Rect<i32>,
+    pub frame_texture: Rc<RefCell<GpuTexture>>,
+    pub camera_handle: Handle<Node>,
+    pub settings: &'b AutoExposure,
+    pub dt: f32,
+    pub blend: bool,
+    pub white_dummy: Rc<RefCell<GpuTexture>>,
+    pub texture_cache: &'a mut TextureCache,
+    pub geom_cache: &'a mut GeometryCache,
+}
+
+impl AutoExposureRenderer {
+    pub fn new(state: &mut State) -> Result<Self, RendererError> {
+        let samples = GeometryBuffer::new(GeometryBufferKind::StaticDraw, ElementKind::Point);
+
+        let mut tex_coords = Vec::with_capacity(SAMPLE_GRID_SIZE * SAMPLE_GRID_SIZE);
+        for y in 0..SAMPLE_GRID_SIZE {
+            for x in 0..SAMPLE_GRID_SIZE {
+                tex_coords.push(Vec2::new(
+                    (x as f32 + 0.5) / SAMPLE_GRID_SIZE as f32,
+                    (y as f32 + 0.5) / SAMPLE_GRID_SIZE as f32,
+                ));
+            }
+        }
+        let points = (0..tex_coords.len() as u32).collect::<Vec<_>>();
+
+        samples
+            .bind(state)
+            .describe_attributes(vec![AttributeDefinition {
+                kind: AttributeKind::Float2,
+                normalized: false,
+            }])?
+            .set_vertices(&tex_coords)
+            .set_points(&points);
+
+        Ok(Self {
+            histogram_shader: HistogramShader::new()?,
+            adaptation_shader: AdaptationShader::new()?,
+            tone_map_shader: ToneMapShader::new()?,
+            histogram: make_f32_framebuffer(state, BIN_COUNT, 1)?,
+            samples,
+            quad: SurfaceSharedData::make_unit_xy_quad(),
+            adaptations: Default::default(),
+        })
+    }
+
+    /// Forgets adapted exposure of every camera, cameras will adapt instantly next frame.
+    pub fn clear(&mut self) {
+        self.adaptations.clear();
+    }
+
+    pub(in crate) fn render(
+        &mut self,
+        args: AutoExposureRenderContext,
+    ) -> Result<RenderPassStatistics, RendererError> {
+        scope_profile!();
+
+        let mut statistics = RenderPassStatistics::default();
+
+        let AutoExposureRenderContext {
+            state,
+            backbuffer,
+            viewport,
+            frame_texture,
+            camera_handle,
+            settings,
+            dt,
+            blend,
+            white_dummy,
+            texture_cache,
+            geom_cache,
+        } = args;
+
+        let min_ev = settings.min_ev.min(settings.max_ev - 0.01);
+        let max_ev = settings.max_ev;
+
+        let metering_mask = settings
+            .metering_mask
+            .clone()
+            .and_then(|mask| texture_cache.get(state, mask))
+            .unwrap_or(white_dummy);
+
+        // Step 1 - build histogram of brightness.
+        let histogram_viewport = Rect::new(0, 0, BIN_COUNT as i32, 1);
+        self.histogram.clear(
+            state,
+            histogram_viewport,
+            Some(Color::from_rgba(0, 0, 0, 0)),
+            None,
+            None,
+        );
+
+        state.set_blend_func(gl::ONE, gl::ONE);
+        state.set_program_point_size(false);
+
+        statistics += self.histogram.draw(
+            &self.samples,
+            state,
+            histogram_viewport,
+            &self.histogram_shader.program,
+            DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: true,
+            },
+            &[
+                (
+                    self.histogram_shader.frame_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: frame_texture.clone(),
+                    },
+                ),
+                (
+                    self.histogram_shader.metering_mask,
+                    UniformValue::Sampler {
+                        index: 1,
+                        texture: metering_mask,
+                    },
+                ),
+                (self.histogram_shader.min_ev, UniformValue::Float(min_ev)),
+                (self.histogram_shader.max_ev, UniformValue::Float(max_ev)),
+                (
+                    self.histogram_shader.bin_count,
+                    UniformValue::Integer(BIN_COUNT as i32),
+                ),
+            ],
+        );
+
+        // Step 2 - blend average brightness with brightness of previous frame.
+        if !self.adaptations.contains_key(&camera_handle) {
+            self.adaptations.insert(camera_handle, Adaptation::new(state)?);
+        }
+        let adaptation = self.adaptations.get_mut(&camera_handle).unwrap();
+
+        let previous = texture_of(&adaptation.exposure[adaptation.current]);
+        adaptation.current = (adaptation.current + 1) % 2;
+        let first_frame = std::mem::replace(&mut adaptation.first_frame, false);
+
+        let unit_viewport = Rect::new(0, 0, 1, 1);
+        statistics += adaptation.exposure[adaptation.current].draw(
+            geom_cache.get(state, &self.quad),
+            state,
+            unit_viewport,
+            &self.adaptation_shader.program,
+            DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: false,
+            },
+            &[
+                (
+                    self.adaptation_shader.wvp_matrix,
+                    UniformValue::Mat4(Mat4::ortho(0.0, 1.0, 1.0, 0.0, -1.0, 1.0)),
+                ),
+                (
+                    self.adaptation_shader.histogram,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: texture_of(&self.histogram),
+                    },
+                ),
+                (
+                    self.adaptation_shader.previous_exposure,
+                    UniformValue::Sampler {
+                        index: 1,
+                        texture: previous,
+                    },
+                ),
+                (
+                    self.adaptation_shader.bin_count,
+                    UniformValue::Integer(BIN_COUNT as i32),
+                ),
+                (self.adaptation_shader.min_ev, UniformValue::Float(min_ev)),
+                (self.adaptation_shader.max_ev, UniformValue::Float(max_ev)),
+                (
+                    self.adaptation_shader.low_percent,
+                    UniformValue::Float(LOW_PERCENT),
+                ),
+                (
+                    self.adaptation_shader.high_percent,
+                    UniformValue::Float(HIGH_PERCENT),
+                ),
+                (
+                    self.adaptation_shader.adaptation,
+                    UniformValue::Float(1.0 - (-dt * settings.adaptation_speed.max(0.0)).exp()),
+                ),
+                (
+                    self.adaptation_shader.first_frame,
+                    UniformValue::Bool(first_frame),
+                ),
+            ],
+        );
+
+        // Step 3 - tone map frame into back buffer using adapted exposure.
+        if blend {
+            state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        }
+
+        statistics += backbuffer.draw(
+            geom_cache.get(state, &self.quad),
+            state,
+            viewport,
+            &self.tone_map_shader.program,
+            DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: true,
+                stencil_test: false,
+                depth_test: false,
+                blend,
+            },
+            &[
+                (
+                    self.tone_map_shader.wvp_matrix,
+                    UniformValue::Mat4(
+                        Mat4::ortho(0.0, viewport.w as f32, viewport.h as f32, 0.0, -1.0, 1.0)
+                            * Mat4::scale(Vec3::new(viewport.w as f32, viewport.h as f32, 0.0)),
+                    ),
+                ),
+                (
+                    self.tone_map_shader.frame_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: frame_texture,
+                    },
+                ),
+                (
+                    self.tone_map_shader.exposure_texture,
+                    UniformValue::Sampler {
+                        index: 1,
+                        texture: texture_of(&adaptation.exposure[adaptation.current]),
+                    },
+                ),
+                (
+                    self.tone_map_shader.key_value,
+                    UniformValue::Float(settings.key_value),
+                ),
+            ],
+        );
+
+        Ok(statistics)
+    }
+}
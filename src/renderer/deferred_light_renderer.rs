@@ -1,9 +1,10 @@
 use crate::{
     core::{
         color::Color,
-        math::{frustum::Frustum, mat4::Mat4, vec3::Vec3, Rect},
+        math::{mat4::Mat4, vec3::Vec3, Rect},
         scope_profile,
     },
+    engine::resource_manager::TimedEntry,
     renderer::{
         error::RendererError,
         flat_shader::FlatShader,
@@ -11,7 +12,10 @@ use crate::{
             framebuffer::{CullFace, DrawParameters, FrameBufferTrait},
             gl,
             gpu_program::{GpuProgram, UniformLocation, UniformValue},
-            gpu_texture::GpuTexture,
+            gpu_texture::{
+                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MininificationFilter,
+                PixelKind, WrapMode,
+            },
             state::{ColorMask, State, StencilFunc, StencilOp},
         },
         gbuffer::GBuffer,
@@ -21,11 +25,12 @@ use crate::{
         },
         ssao::ScreenSpaceAmbientOcclusionRenderer,
         surface::SurfaceSharedData,
-        GeometryCache, QualitySettings, RenderPassStatistics, TextureCache,
+        CubeMapCache, GeometryCache, QualitySettings, RenderPassStatistics, TextureCache,
     },
-    scene::{camera::Camera, light::Light, node::Node, Scene},
+    scene::{camera::Camera, light::Light, light_probe::LightProbeGrid, node::Node, Scene},
+    utils::log::{Log, LogCategory},
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 struct AmbientLightShader {
     program: GpuProgram,
@@ -53,6 +58,173 @@ impl AmbientLightShader {
     }
 }
 
+struct EnvironmentShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    depth_texture: UniformLocation,
+    sky_texture: UniformLocation,
+    sky_cube_map: UniformLocation,
+    sky_is_cube_map: UniformLocation,
+    inv_view_proj_matrix: UniformLocation,
+    camera_position: UniformLocation,
+    sky_enabled: UniformLocation,
+    fog_enabled: UniformLocation,
+    fog_color: UniformLocation,
+    fog_start: UniformLocation,
+    fog_end: UniformLocation,
+}
+
+impl EnvironmentShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/environment_fs.glsl");
+        let vertex_source = include_str!("shaders/ambient_light_vs.glsl");
+        let program =
+            GpuProgram::from_source("EnvironmentShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            depth_texture: program.uniform_location("depthTexture")?,
+            sky_texture: program.uniform_location("skyTexture")?,
+            sky_cube_map: program.uniform_location("skyCubeMap")?,
+            sky_is_cube_map: program.uniform_location("skyIsCubeMap")?,
+            inv_view_proj_matrix: program.uniform_location("invViewProj")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            sky_enabled: program.uniform_location("skyEnabled")?,
+            fog_enabled: program.uniform_location("fogEnabled")?,
+            fog_color: program.uniform_location("fogColor")?,
+            fog_start: program.uniform_location("fogStart")?,
+            fog_end: program.uniform_location("fogEnd")?,
+            program,
+        })
+    }
+}
+
+struct LocalProbeShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    depth_texture: UniformLocation,
+    diffuse_texture: UniformLocation,
+    ao_sampler: UniformLocation,
+    ambient_texture: UniformLocation,
+    inv_view_proj_matrix: UniformLocation,
+    inv_probe_world: UniformLocation,
+    half_extents: UniformLocation,
+    blend_distance: UniformLocation,
+    probe_color: UniformLocation,
+}
+
+impl LocalProbeShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/local_probe_fs.glsl");
+        let vertex_source = include_str!("shaders/ambient_light_vs.glsl");
+        let program = GpuProgram::from_source("LocalProbeShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            depth_texture: program.uniform_location("depthTexture")?,
+            diffuse_texture: program.uniform_location("diffuseTexture")?,
+            ao_sampler: program.uniform_location("aoSampler")?,
+            ambient_texture: program.uniform_location("ambientTexture")?,
+            inv_view_proj_matrix: program.uniform_location("invViewProj")?,
+            inv_probe_world: program.uniform_location("invProbeWorld")?,
+            half_extents: program.uniform_location("halfExtents")?,
+            blend_distance: program.uniform_location("blendDistance")?,
+            probe_color: program.uniform_location("probeColor")?,
+            program,
+        })
+    }
+}
+
+struct LightProbeGridShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    depth_texture: UniformLocation,
+    diffuse_texture: UniformLocation,
+    normal_texture: UniformLocation,
+    ao_sampler: UniformLocation,
+    ambient_texture: UniformLocation,
+    coefficients: [UniformLocation; 3],
+    inv_view_proj_matrix: UniformLocation,
+    inv_grid_world: UniformLocation,
+    half_extents: UniformLocation,
+    grid_resolution: UniformLocation,
+    blend_distance: UniformLocation,
+    intensity: UniformLocation,
+}
+
+impl LightProbeGridShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/light_probe_grid_fs.glsl");
+        let vertex_source = include_str!("shaders/ambient_light_vs.glsl");
+        let program =
+            GpuProgram::from_source("LightProbeGridShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            depth_texture: program.uniform_location("depthTexture")?,
+            diffuse_texture: program.uniform_location("diffuseTexture")?,
+            normal_texture: program.uniform_location("normalTexture")?,
+            ao_sampler: program.uniform_location("aoSampler")?,
+            ambient_texture: program.uniform_location("ambientTexture")?,
+            coefficients: [
+                program.uniform_location("redCoefficients")?,
+                program.uniform_location("greenCoefficients")?,
+                program.uniform_location("blueCoefficients")?,
+            ],
+            inv_view_proj_matrix: program.uniform_location("invViewProj")?,
+            inv_grid_world: program.uniform_location("invGridWorld")?,
+            half_extents: program.uniform_location("halfExtents")?,
+            grid_resolution: program.uniform_location("gridResolution")?,
+            blend_distance: program.uniform_location("blendDistance")?,
+            intensity: program.uniform_location("intensity")?,
+            program,
+        })
+    }
+}
+
+/// Creates three volume textures (one per color channel) with convolved spherical harmonics
+/// of every probe of a grid, each texel holds 4 coefficients of a probe.
+fn create_probe_grid_textures(
+    state: &mut State,
+    grid: &LightProbeGrid,
+) -> Result<[Rc<RefCell<GpuTexture>>; 3], RendererError> {
+    let [width, height, depth] = grid.resolution();
+    let mut channels = [Vec::new(), Vec::new(), Vec::new()];
+    for probe in grid.probes() {
+        let coefficients = probe.irradiance_coefficients();
+        for (channel, bytes) in channels.iter_mut().enumerate() {
+            for coefficient in coefficients.iter() {
+                let value = [coefficient.x, coefficient.y, coefficient.z][channel];
+                bytes.extend_from_slice(&value.to_ne_bytes());
+            }
+        }
+    }
+
+    let mut make_texture = |bytes: &[u8]| -> Result<_, RendererError> {
+        let mut texture = GpuTexture::new(
+            state,
+            GpuTextureKind::Volume {
+                width,
+                height,
+                depth,
+            },
+            PixelKind::RGBA32F,
+            Some(bytes),
+        )?;
+        texture
+            .bind_mut(state, 0)
+            .set_minification_filter(MininificationFilter::Linear)
+            .set_magnification_filter(MagnificationFilter::Linear)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::R, WrapMode::ClampToEdge);
+        Ok(Rc::new(RefCell::new(texture)))
+    };
+
+    Ok([
+        make_texture(&channels[0])?,
+        make_texture(&channels[1])?,
+        make_texture(&channels[2])?,
+    ])
+}
+
 struct SpotLightShader {
     program: GpuProgram,
     wvp_matrix: UniformLocation,
@@ -183,6 +355,14 @@ pub struct DeferredLightRenderer {
     point_light_shader: PointLightShader,
     directional_light_shader: DirectionalLightShader,
     ambient_light_shader: AmbientLightShader,
+    local_probe_shader: LocalProbeShader,
+    light_probe_grid_shader: LightProbeGridShader,
+    // Textures are keyed by revisions of grids, see `LightProbeGrid::revision`.
+    probe_grid_textures: HashMap<u64, TimedEntry<[Rc<RefCell<GpuTexture>>; 3]>>,
+    environment_shader: EnvironmentShader,
+    // Samplers of different types can't use same texture unit, so cube map sampler of
+    // environment shader needs its own stub.
+    black_dummy_cube: Rc<RefCell<GpuTexture>>,
     quad: SurfaceSharedData,
     sphere: SurfaceSharedData,
     flat_shader: FlatShader,
@@ -197,9 +377,9 @@ pub(in crate) struct DeferredRendererContext<'a> {
     pub camera: &'a Camera,
     pub gbuffer: &'a mut GBuffer,
     pub white_dummy: Rc<RefCell<GpuTexture>>,
-    pub ambient_color: Color,
     pub settings: &'a QualitySettings,
     pub textures: &'a mut TextureCache,
+    pub cube_maps: &'a mut CubeMapCache,
     pub geometry_cache: &'a mut GeometryCache,
 }
 
@@ -219,6 +399,19 @@ impl DeferredLightRenderer {
             point_light_shader: PointLightShader::new()?,
             directional_light_shader: DirectionalLightShader::new()?,
             ambient_light_shader: AmbientLightShader::new()?,
+            local_probe_shader: LocalProbeShader::new()?,
+            light_probe_grid_shader: LightProbeGridShader::new()?,
+            probe_grid_textures: Default::default(),
+            environment_shader: EnvironmentShader::new()?,
+            black_dummy_cube: Rc::new(RefCell::new(GpuTexture::new(
+                state,
+                GpuTextureKind::Cube {
+                    width: 1,
+                    height: 1,
+                },
+                PixelKind::RGBA8,
+                Some(&[0; 24]),
+            )?)),
             quad: SurfaceSharedData::make_unit_xy_quad(),
             sphere: SurfaceSharedData::make_sphere(6, 6, 1.0),
             flat_shader: FlatShader::new()?,
@@ -264,6 +457,17 @@ impl DeferredLightRenderer {
         Ok(())
     }
 
+    pub fn update(&mut self, dt: f32) {
+        for entry in self.probe_grid_textures.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.probe_grid_textures.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.probe_grid_textures.clear();
+    }
+
     #[must_use]
     pub(in crate) fn render(&mut self, args: DeferredRendererContext) -> RenderPassStatistics {
         scope_profile!();
@@ -276,14 +480,14 @@ impl DeferredLightRenderer {
             camera,
             gbuffer,
             white_dummy,
-            ambient_color,
             settings,
             textures,
+            cube_maps,
             geometry_cache,
         } = args;
 
         let viewport = Rect::new(0, 0, gbuffer.width, gbuffer.height);
-        let frustum = Frustum::from(camera.view_projection_matrix()).unwrap();
+        let frustum = camera.frustum();
 
         let frame_matrix = Mat4::ortho(0.0, viewport.w as f32, viewport.h as f32, 0.0, -1.0, 1.0)
             * Mat4::scale(Vec3::new(viewport.w as f32, viewport.h as f32, 0.0));
@@ -333,7 +537,7 @@ impl DeferredLightRenderer {
                 ),
                 (
                     self.ambient_light_shader.ambient_color,
-                    UniformValue::Color(ambient_color),
+                    UniformValue::Vec4(scene.environment.ambient_light()),
                 ),
                 (
                     self.ambient_light_shader.diffuse_texture,
@@ -364,6 +568,241 @@ impl DeferredLightRenderer {
         );
 
         state.set_blend(true);
+        state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        // Light probe grids replace ambient lighting inside their volumes. They're drawn
+        // before local probes, so local probes of moving interiors override them.
+        for node in scene.graph.linear_iter() {
+            let grid = match node {
+                Node::LightProbeGrid(grid)
+                    if grid.global_visibility() && camera.sees_layers(grid.global_layers()) =>
+                {
+                    grid
+                }
+                _ => continue,
+            };
+
+            let revision = grid.revision();
+            if !self.probe_grid_textures.contains_key(&revision) {
+                match create_probe_grid_textures(state, grid) {
+                    Ok(textures) => {
+                        self.probe_grid_textures.insert(
+                            revision,
+                            TimedEntry {
+                                value: textures,
+                                time_to_live: 0.0,
+                            },
+                        );
+                    }
+                    Err(e) => {
+                        Log::err(
+                            LogCategory::Renderer,
+                            format!("Unable to upload light probe grid! Reason: {:?}", e),
+                        );
+                        continue;
+                    }
+                }
+            }
+            let entry = self.probe_grid_textures.get_mut(&revision).unwrap();
+            // Textures of grids that weren't rendered for some time will be destroyed.
+            entry.time_to_live = 20.0;
+            let [red, green, blue] = &entry.value;
+
+            let [width, height, depth] = grid.resolution();
+
+            statistics += gbuffer.final_frame.draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &self.light_probe_grid_shader.program,
+                DrawParameters {
+                    cull_face: CullFace::Back,
+                    culling: false,
+                    color_write: Default::default(),
+                    depth_write: false,
+                    stencil_test: false,
+                    depth_test: false,
+                    blend: true,
+                },
+                &[
+                    (
+                        self.light_probe_grid_shader.wvp_matrix,
+                        UniformValue::Mat4(frame_matrix),
+                    ),
+                    (
+                        self.light_probe_grid_shader.depth_texture,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: gbuffer.depth(),
+                        },
+                    ),
+                    (
+                        self.light_probe_grid_shader.diffuse_texture,
+                        UniformValue::Sampler {
+                            index: 1,
+                            texture: gbuffer.diffuse_texture(),
+                        },
+                    ),
+                    (
+                        self.light_probe_grid_shader.normal_texture,
+                        UniformValue::Sampler {
+                            index: 2,
+                            texture: gbuffer.normal_texture(),
+                        },
+                    ),
+                    (
+                        self.light_probe_grid_shader.ao_sampler,
+                        UniformValue::Sampler {
+                            index: 3,
+                            texture: if settings.use_ssao {
+                                self.ssao_renderer.ao_map()
+                            } else {
+                                white_dummy.clone()
+                            },
+                        },
+                    ),
+                    (
+                        self.light_probe_grid_shader.ambient_texture,
+                        UniformValue::Sampler {
+                            index: 4,
+                            texture: gbuffer.ambient_texture(),
+                        },
+                    ),
+                    (
+                        self.light_probe_grid_shader.coefficients[0],
+                        UniformValue::Sampler {
+                            index: 5,
+                            texture: red.clone(),
+                        },
+                    ),
+                    (
+                        self.light_probe_grid_shader.coefficients[1],
+                        UniformValue::Sampler {
+                            index: 6,
+                            texture: green.clone(),
+                        },
+                    ),
+                    (
+                        self.light_probe_grid_shader.coefficients[2],
+                        UniformValue::Sampler {
+                            index: 7,
+                            texture: blue.clone(),
+                        },
+                    ),
+                    (
+                        self.light_probe_grid_shader.inv_view_proj_matrix,
+                        UniformValue::Mat4(inv_view_projection),
+                    ),
+                    (
+                        self.light_probe_grid_shader.inv_grid_world,
+                        UniformValue::Mat4(grid.global_transform().inverse().unwrap_or_default()),
+                    ),
+                    (
+                        self.light_probe_grid_shader.half_extents,
+                        UniformValue::Vec3(grid.size().scale(0.5)),
+                    ),
+                    (
+                        self.light_probe_grid_shader.grid_resolution,
+                        UniformValue::Vec3(Vec3::new(width as f32, height as f32, depth as f32)),
+                    ),
+                    (
+                        self.light_probe_grid_shader.blend_distance,
+                        UniformValue::Float(grid.blend_distance()),
+                    ),
+                    (
+                        self.light_probe_grid_shader.intensity,
+                        UniformValue::Float(grid.intensity()),
+                    ),
+                ],
+            );
+        }
+
+        // Local light probes replace ambient lighting inside their volumes.
+        for node in scene.graph.linear_iter() {
+            let probe = match node.local_probe() {
+                Some(probe)
+                    if node.global_visibility() && camera.sees_layers(node.global_layers()) =>
+                {
+                    probe
+                }
+                _ => continue,
+            };
+
+            statistics += gbuffer.final_frame.draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &self.local_probe_shader.program,
+                DrawParameters {
+                    cull_face: CullFace::Back,
+                    culling: false,
+                    color_write: Default::default(),
+                    depth_write: false,
+                    stencil_test: false,
+                    depth_test: false,
+                    blend: true,
+                },
+                &[
+                    (
+                        self.local_probe_shader.wvp_matrix,
+                        UniformValue::Mat4(frame_matrix),
+                    ),
+                    (
+                        self.local_probe_shader.depth_texture,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: gbuffer.depth(),
+                        },
+                    ),
+                    (
+                        self.local_probe_shader.diffuse_texture,
+                        UniformValue::Sampler {
+                            index: 1,
+                            texture: gbuffer.diffuse_texture(),
+                        },
+                    ),
+                    (
+                        self.local_probe_shader.ao_sampler,
+                        UniformValue::Sampler {
+                            index: 2,
+                            texture: if settings.use_ssao {
+                                self.ssao_renderer.ao_map()
+                            } else {
+                                white_dummy.clone()
+                            },
+                        },
+                    ),
+                    (
+                        self.local_probe_shader.ambient_texture,
+                        UniformValue::Sampler {
+                            index: 3,
+                            texture: gbuffer.ambient_texture(),
+                        },
+                    ),
+                    (
+                        self.local_probe_shader.inv_view_proj_matrix,
+                        UniformValue::Mat4(inv_view_projection),
+                    ),
+                    (
+                        self.local_probe_shader.inv_probe_world,
+                        UniformValue::Mat4(node.global_transform().inverse().unwrap_or_default()),
+                    ),
+                    (
+                        self.local_probe_shader.half_extents,
+                        UniformValue::Vec3(probe.half_extents()),
+                    ),
+                    (
+                        self.local_probe_shader.blend_distance,
+                        UniformValue::Float(probe.blend_distance()),
+                    ),
+                    (
+                        self.local_probe_shader.probe_color,
+                        UniformValue::Color(probe.ambient_color()),
+                    ),
+                ],
+            );
+        }
+
         state.set_blend_func(gl::ONE, gl::ONE);
 
         for light in scene.graph.linear_iter().filter_map(|node| {
@@ -373,19 +812,12 @@ impl DeferredLightRenderer {
                 None
             }
         }) {
-            if !light.global_visibility() {
+            if !light.global_visibility() || !camera.sees_layers(light.global_layers()) {
                 continue;
             }
 
-            let raw_radius = match light {
-                Light::Spot(spot_light) => spot_light.distance(),
-                Light::Point(point_light) => point_light.radius(),
-                Light::Directional(_) => std::f32::MAX,
-            };
-
             let light_position = light.global_position();
-            let light_radius_scale = light.local_transform().scale().max_value();
-            let light_radius = light_radius_scale * raw_radius;
+            let light_radius = light.bounding_radius();
             let light_r_inflate = 1.05 * light_radius;
             let light_radius_vec = Vec3::new(light_r_inflate, light_r_inflate, light_r_inflate);
             let emit_direction = light.up_vector().normalized().unwrap_or(Vec3::LOOK);
@@ -753,6 +1185,98 @@ impl DeferredLightRenderer {
             }
         }
 
+        // Sky and fog are drawn on top of lit scene.
+        let environment = &scene.environment;
+        let sky_cube_map = environment
+            .sky_cube_map
+            .clone()
+            .and_then(|cube_map| cube_maps.get(state, cube_map));
+        // Cube map has priority over equirectangular texture.
+        let sky_texture = if sky_cube_map.is_some() {
+            None
+        } else {
+            environment
+                .skybox
+                .clone()
+                .and_then(|skybox| textures.get(state, skybox))
+        };
+        let sky_enabled = sky_texture.is_some() || sky_cube_map.is_some();
+        if sky_enabled || environment.fog.is_some() {
+            let fog = environment.fog.unwrap_or_default();
+            state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            statistics += gbuffer.final_frame.draw(
+                geometry_cache.get(state, &self.quad),
+                state,
+                viewport,
+                &self.environment_shader.program,
+                DrawParameters {
+                    cull_face: CullFace::Back,
+                    culling: false,
+                    color_write: Default::default(),
+                    depth_write: false,
+                    stencil_test: false,
+                    depth_test: false,
+                    blend: true,
+                },
+                &[
+                    (
+                        self.environment_shader.wvp_matrix,
+                        UniformValue::Mat4(frame_matrix),
+                    ),
+                    (
+                        self.environment_shader.depth_texture,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: gbuffer.depth(),
+                        },
+                    ),
+                    (
+                        self.environment_shader.sky_enabled,
+                        UniformValue::Bool(sky_enabled),
+                    ),
+                    (
+                        self.environment_shader.sky_texture,
+                        UniformValue::Sampler {
+                            index: 1,
+                            texture: sky_texture.unwrap_or(white_dummy),
+                        },
+                    ),
+                    (
+                        self.environment_shader.sky_is_cube_map,
+                        UniformValue::Bool(sky_cube_map.is_some()),
+                    ),
+                    (
+                        self.environment_shader.sky_cube_map,
+                        UniformValue::Sampler {
+                            index: 2,
+                            texture: sky_cube_map.unwrap_or_else(|| self.black_dummy_cube.clone()),
+                        },
+                    ),
+                    (
+                        self.environment_shader.inv_view_proj_matrix,
+                        UniformValue::Mat4(inv_view_projection),
+                    ),
+                    (
+                        self.environment_shader.camera_position,
+                        UniformValue::Vec3(camera.global_position()),
+                    ),
+                    (
+                        self.environment_shader.fog_enabled,
+                        UniformValue::Bool(environment.fog.is_some()),
+                    ),
+                    (
+                        self.environment_shader.fog_color,
+                        UniformValue::Color(fog.color),
+                    ),
+                    (
+                        self.environment_shader.fog_start,
+                        UniformValue::Float(fog.start),
+                    ),
+                    (self.environment_shader.fog_end, UniformValue::Float(fog.end)),
+                ],
+            );
+        }
+
         statistics
     }
 }
@@ -54,6 +54,8 @@ pub enum RendererError {
     FailedToConstructFBO,
     /// Internal context error.
     Context(ContextError),
+    /// Engine has no renderer, because it is headless.
+    NoRenderer,
 }
 
 impl From<NulError> for RendererError {
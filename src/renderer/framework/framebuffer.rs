@@ -224,6 +224,16 @@ pub struct DrawPartContext<'a, 'b, 'c, 'd, T> {
     pub count: usize,
 }
 
+pub struct DrawInstancedContext<'a, 'b, 'c, 'd, T> {
+    pub state: &'a mut State,
+    pub viewport: Rect<i32>,
+    pub geometry: &'a GeometryBuffer<T>,
+    pub program: &'b GpuProgram,
+    pub params: DrawParameters,
+    pub uniforms: &'c [(UniformLocation, UniformValue<'d>)],
+    pub instance_count: usize,
+}
+
 pub trait FrameBufferTrait {
     fn id(&self) -> u32;
 
@@ -296,6 +306,22 @@ pub trait FrameBufferTrait {
             .bind(args.state)
             .draw_part(args.offset, args.count)
     }
+
+    fn draw_instanced<T>(&mut self, args: DrawInstancedContext<T>) -> DrawCallStatistics {
+        scope_profile!();
+
+        pre_draw(
+            self.id(),
+            args.state,
+            args.viewport,
+            args.program,
+            args.params,
+            args.uniforms,
+        );
+        args.geometry
+            .bind(args.state)
+            .draw_instanced(args.instance_count)
+    }
 }
 
 impl FrameBufferTrait for FrameBuffer {
@@ -11,7 +11,7 @@ use crate::{
         },
         TriangleDefinition,
     },
-    utils::log::Log,
+    utils::log::{Log, LogCategory},
 };
 use std::{cell::Cell, ffi::c_void, marker::PhantomData, mem::size_of};
 
@@ -142,6 +142,7 @@ pub enum GeometryBufferKind {
 pub enum ElementKind {
     Triangle,
     Line,
+    Point,
 }
 
 impl ElementKind {
@@ -149,6 +150,7 @@ impl ElementKind {
         match self {
             ElementKind::Triangle => 3,
             ElementKind::Line => 2,
+            ElementKind::Point => 1,
         }
     }
 }
@@ -249,6 +251,20 @@ impl<'a, T> GeometryBufferBinding<'a, T> {
         self
     }
 
+    pub fn set_points(self, points: &[u32]) -> Self {
+        scope_profile!();
+
+        assert_eq!(self.buffer.element_kind, ElementKind::Point);
+        self.buffer.element_count.set(points.len());
+
+        let size = (points.len() * size_of::<u32>()) as isize;
+        let data = points.as_ptr() as *const c_void;
+
+        unsafe { self.set_elements(data, size) }
+
+        self
+    }
+
     unsafe fn set_elements(&self, elements: *const c_void, size: isize) {
         scope_profile!();
 
@@ -288,6 +304,7 @@ impl<'a, T> GeometryBufferBinding<'a, T> {
         match self.buffer.element_kind {
             ElementKind::Triangle => gl::TRIANGLES,
             ElementKind::Line => gl::LINES,
+            ElementKind::Point => gl::POINTS,
         }
     }
 
@@ -305,6 +322,31 @@ impl<'a, T> GeometryBufferBinding<'a, T> {
         }
     }
 
+    /// Draws whole buffer given amount of times, shader can distinguish instances by
+    /// `gl_InstanceID`.
+    pub fn draw_instanced(&self, instance_count: usize) -> DrawCallStatistics {
+        scope_profile!();
+
+        let index_per_element = self.buffer.element_kind.index_per_element();
+        let index_count = self.buffer.element_count.get() * index_per_element;
+
+        if index_count > 0 && instance_count > 0 {
+            unsafe {
+                gl::DrawElementsInstanced(
+                    self.mode(),
+                    index_count as i32,
+                    gl::UNSIGNED_INT,
+                    std::ptr::null(),
+                    instance_count as i32,
+                );
+            }
+        }
+
+        DrawCallStatistics {
+            triangles: self.buffer.element_count.get() * instance_count,
+        }
+    }
+
     unsafe fn draw_internal(&self, start_index: usize, index_count: usize) {
         scope_profile!();
 
@@ -332,10 +374,13 @@ where
             let mut ebo = 0;
             gl::GenBuffers(1, &mut ebo);
 
-            Log::writeln(format!(
-                "GL geometry buffer was created - VBO: {}, EBO: {}, VAO: {}!",
-                vbo, ebo, vao
-            ));
+            Log::debug(
+                LogCategory::Renderer,
+                format!(
+                    "GL geometry buffer was created - VBO: {}, EBO: {}, VAO: {}!",
+                    vbo, ebo, vao
+                ),
+            );
 
             Self {
                 vertex_array_object: vao,
@@ -369,10 +414,13 @@ where
 impl<T> Drop for GeometryBuffer<T> {
     fn drop(&mut self) {
         unsafe {
-            Log::writeln(format!(
-                "GL geometry buffer was destroyed - VBO: {}, EBO: {}, VAO: {}!",
-                self.vertex_buffer_object, self.element_buffer_object, self.vertex_array_object
-            ));
+            Log::debug(
+                LogCategory::Renderer,
+                format!(
+                    "GL geometry buffer was destroyed - VBO: {}, EBO: {}, VAO: {}!",
+                    self.vertex_buffer_object, self.element_buffer_object, self.vertex_array_object
+                ),
+            );
 
             gl::DeleteBuffers(1, &self.vertex_buffer_object);
             gl::DeleteBuffers(1, &self.element_buffer_object);
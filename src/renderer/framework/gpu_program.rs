@@ -14,7 +14,7 @@ use crate::{
             state::State,
         },
     },
-    utils::log::Log,
+    utils::log::{Log, LogCategory},
 };
 use std::{cell::RefCell, ffi::CString, marker::PhantomData, rc::Rc};
 
@@ -79,16 +79,16 @@ fn create_shader(name: String, actual_type: GLuint, source: &str) -> Result<GLui
                 buffer.as_mut_ptr() as *mut i8,
             );
             let compilation_message = String::from_utf8_unchecked(buffer);
-            Log::writeln(format!(
-                "Failed to compile {} shader: {}",
-                name, compilation_message
-            ));
+            Log::err(
+                LogCategory::Renderer,
+                format!("Failed to compile {} shader: {}", name, compilation_message),
+            );
             Err(RendererError::ShaderCompilationFailed {
                 shader_name: name,
                 error_message: compilation_message,
             })
         } else {
-            Log::writeln(format!("Shader {} compiled!", name));
+            Log::debug(LogCategory::Renderer, format!("Shader {} compiled!", name));
             Ok(shader)
         }
     }
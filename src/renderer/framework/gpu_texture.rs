@@ -7,11 +7,22 @@ use crate::{
         error::RendererError,
         framework::{gl, gl::types::GLuint, state::State},
     },
-    resource::texture::TextureKind,
-    utils::log::Log,
+    resource::texture::{
+        TextureKind, TextureMagnificationFilter, TextureMinificationFilter, TextureWrapMode,
+    },
+    utils::log::{Log, LogCategory},
 };
 use std::{ffi::c_void, marker::PhantomData};
 
+// S3TC formats are provided by extension which is not included in generated bindings, but
+// it is supported by every desktop GPU.
+const COMPRESSED_RGBA_S3TC_DXT1_EXT: u32 = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3_EXT: u32 = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT: u32 = 0x8C4D;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT: u32 = 0x8C4E;
+const COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT: u32 = 0x8C4F;
+
 #[derive(Copy, Clone)]
 pub enum GpuTextureKind {
     Line {
@@ -41,17 +52,74 @@ impl GpuTextureKind {
             Self::Volume { .. } => gl::TEXTURE_3D,
         }
     }
+
+    /// Returns size of given mip level, each level is two times smaller than previous one.
+    fn mip_level(self, level: usize) -> Self {
+        let reduce = |size: usize| (size >> level).max(1);
+        match self {
+            Self::Line { length } => Self::Line {
+                length: reduce(length),
+            },
+            Self::Rectangle { width, height } => Self::Rectangle {
+                width: reduce(width),
+                height: reduce(height),
+            },
+            Self::Cube { width, height } => Self::Cube {
+                width: reduce(width),
+                height: reduce(height),
+            },
+            Self::Volume {
+                width,
+                height,
+                depth,
+            } => Self::Volume {
+                width: reduce(width),
+                height: reduce(height),
+                depth: reduce(depth),
+            },
+        }
+    }
+
+    /// Returns amount of bytes required to store texture of this size in given format.
+    fn byte_count(self, pixel_kind: PixelKind) -> usize {
+        match self {
+            Self::Line { length } => pixel_kind.image_size_bytes(length, 1),
+            Self::Rectangle { width, height } => pixel_kind.image_size_bytes(width, height),
+            Self::Cube { width, height } => 6 * pixel_kind.image_size_bytes(width, height),
+            Self::Volume {
+                width,
+                height,
+                depth,
+            } => depth * pixel_kind.image_size_bytes(width, height),
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
 pub enum PixelKind {
     F32,
+    RGB32F,
+    RGBA32F,
+    RGBA16F,
     D32,
     D24S8,
     RGBA8,
     RGB8,
     RG8,
     R8,
+    BC1,
+    BC2,
+    BC3,
+    BC4,
+    BC5,
+    BC6H,
+    BC7,
+    SRGBA8,
+    SRGB8,
+    BC1SRGB,
+    BC2SRGB,
+    BC3SRGB,
+    BC7SRGB,
 }
 
 impl From<TextureKind> for PixelKind {
@@ -60,6 +128,48 @@ impl From<TextureKind> for PixelKind {
             TextureKind::R8 => Self::R8,
             TextureKind::RGB8 => Self::RGB8,
             TextureKind::RGBA8 => Self::RGBA8,
+            TextureKind::BC1 => Self::BC1,
+            TextureKind::BC2 => Self::BC2,
+            TextureKind::BC3 => Self::BC3,
+            TextureKind::BC4 => Self::BC4,
+            TextureKind::BC5 => Self::BC5,
+            TextureKind::BC6H => Self::BC6H,
+            TextureKind::BC7 => Self::BC7,
+            TextureKind::RGB32F => Self::RGB32F,
+            TextureKind::RGBA32F => Self::RGBA32F,
+        }
+    }
+}
+
+impl From<TextureMinificationFilter> for MininificationFilter {
+    fn from(filter: TextureMinificationFilter) -> Self {
+        match filter {
+            TextureMinificationFilter::Nearest => Self::Nearest,
+            TextureMinificationFilter::NearestMipNearest => Self::NearestMipNearest,
+            TextureMinificationFilter::NearestMipLinear => Self::NearestMip,
+            TextureMinificationFilter::Linear => Self::Linear,
+            TextureMinificationFilter::LinearMipNearest => Self::LinearMipNearest,
+            TextureMinificationFilter::LinearMipLinear => Self::LinearMip,
+        }
+    }
+}
+
+impl From<TextureMagnificationFilter> for MagnificationFilter {
+    fn from(filter: TextureMagnificationFilter) -> Self {
+        match filter {
+            TextureMagnificationFilter::Nearest => Self::Nearest,
+            TextureMagnificationFilter::Linear => Self::Linear,
+        }
+    }
+}
+
+impl From<TextureWrapMode> for WrapMode {
+    fn from(wrap_mode: TextureWrapMode) -> Self {
+        match wrap_mode {
+            TextureWrapMode::Repeat => Self::Repeat,
+            TextureWrapMode::MirroredRepeat => Self::MirroredRepeat,
+            TextureWrapMode::ClampToEdge => Self::ClampToEdge,
+            TextureWrapMode::ClampToBorder => Self::ClampToBorder,
         }
     }
 }
@@ -72,10 +182,24 @@ pub struct GpuTexture {
 }
 
 impl PixelKind {
+    /// Returns size of a pixel in bytes or, for compressed formats, size of a block of
+    /// pixels.
     fn size_bytes(self) -> usize {
         match self {
-            Self::RGBA8 | Self::D24S8 | Self::D32 | Self::F32 => 4,
-            Self::RGB8 => 3,
+            Self::BC2
+            | Self::BC3
+            | Self::BC5
+            | Self::BC6H
+            | Self::BC7
+            | Self::BC2SRGB
+            | Self::BC3SRGB
+            | Self::BC7SRGB => 16,
+            Self::BC1 | Self::BC4 | Self::BC1SRGB => 8,
+            Self::RGBA32F => 16,
+            Self::RGB32F => 12,
+            Self::RGBA16F => 8,
+            Self::RGBA8 | Self::SRGBA8 | Self::D24S8 | Self::D32 | Self::F32 => 4,
+            Self::RGB8 | Self::SRGB8 => 3,
             Self::RG8 => 2,
             Self::R8 => 1,
         }
@@ -83,9 +207,71 @@ impl PixelKind {
 
     fn unpack_alignment(self) -> i32 {
         match self {
-            Self::RGBA8 | Self::RGB8 | Self::D24S8 | Self::D32 | Self::F32 => 4,
+            Self::RGBA16F => 8,
+            Self::RGBA8
+            | Self::RGB8
+            | Self::SRGBA8
+            | Self::SRGB8
+            | Self::D24S8
+            | Self::D32
+            | Self::F32
+            | Self::RGB32F
+            | Self::RGBA32F => 4,
             Self::RG8 => 2,
-            Self::R8 => 1,
+            Self::R8
+            | Self::BC1
+            | Self::BC2
+            | Self::BC3
+            | Self::BC4
+            | Self::BC5
+            | Self::BC6H
+            | Self::BC7
+            | Self::BC1SRGB
+            | Self::BC2SRGB
+            | Self::BC3SRGB
+            | Self::BC7SRGB => 1,
+        }
+    }
+
+    pub fn is_compressed(self) -> bool {
+        match self {
+            Self::BC1
+            | Self::BC2
+            | Self::BC3
+            | Self::BC4
+            | Self::BC5
+            | Self::BC6H
+            | Self::BC7
+            | Self::BC1SRGB
+            | Self::BC2SRGB
+            | Self::BC3SRGB
+            | Self::BC7SRGB => true,
+            _ => false,
+        }
+    }
+
+    /// Returns sRGB version of the format, so GPU will convert texels from sRGB to linear
+    /// color space when they're fetched. Formats without sRGB version are returned as is,
+    /// they either store data (like normals) or have only one or two channels.
+    pub fn to_srgb(self) -> Self {
+        match self {
+            Self::RGBA8 => Self::SRGBA8,
+            Self::RGB8 => Self::SRGB8,
+            Self::BC1 => Self::BC1SRGB,
+            Self::BC2 => Self::BC2SRGB,
+            Self::BC3 => Self::BC3SRGB,
+            Self::BC7 => Self::BC7SRGB,
+            _ => self,
+        }
+    }
+
+    /// Returns amount of bytes required to store image of given size, compressed formats
+    /// store pixels in blocks of 4x4 pixels.
+    fn image_size_bytes(self, width: usize, height: usize) -> usize {
+        if self.is_compressed() {
+            ((width + 3) / 4) * ((height + 3) / 4) * self.size_bytes()
+        } else {
+            width * height * self.size_bytes()
         }
     }
 }
@@ -108,7 +294,10 @@ impl MagnificationFilter {
 #[derive(Copy, Clone)]
 pub enum MininificationFilter {
     Nearest,
+    NearestMipNearest,
+    NearestMip,
     Linear,
+    LinearMipNearest,
     LinearMip,
 }
 
@@ -116,7 +305,10 @@ impl MininificationFilter {
     pub fn into_gl_value(self) -> i32 {
         (match self {
             Self::Nearest => gl::NEAREST,
+            Self::NearestMipNearest => gl::NEAREST_MIPMAP_NEAREST,
+            Self::NearestMip => gl::NEAREST_MIPMAP_LINEAR,
             Self::Linear => gl::LINEAR,
+            Self::LinearMipNearest => gl::LINEAR_MIPMAP_NEAREST,
             Self::LinearMip => gl::LINEAR_MIPMAP_LINEAR,
         }) as i32
     }
@@ -127,6 +319,7 @@ pub enum WrapMode {
     Repeat,
     ClampToEdge,
     ClampToBorder,
+    MirroredRepeat,
 }
 
 impl WrapMode {
@@ -135,6 +328,7 @@ impl WrapMode {
             Self::Repeat => gl::REPEAT,
             Self::ClampToEdge => gl::CLAMP_TO_EDGE,
             Self::ClampToBorder => gl::CLAMP_TO_BORDER,
+            Self::MirroredRepeat => gl::MIRRORED_REPEAT,
         }) as i32
     }
 }
@@ -143,6 +337,7 @@ impl WrapMode {
 pub enum Coordinate {
     S,
     T,
+    R,
 }
 
 impl Coordinate {
@@ -150,6 +345,7 @@ impl Coordinate {
         match self {
             Self::S => gl::TEXTURE_WRAP_S,
             Self::T => gl::TEXTURE_WRAP_T,
+            Self::R => gl::TEXTURE_WRAP_R,
         }
     }
 }
@@ -182,11 +378,17 @@ impl CubeMapFace {
 }
 
 impl<'a> TextureBinding<'a> {
-    pub fn set_max_anisotropy(self) -> Self {
+    /// Sets anisotropy level, it is clamped to [1; max] range where max is maximum level
+    /// supported by hardware. Level 1 means that anisotropic filtering is disabled.
+    pub fn set_anisotropy(self, level: f32) -> Self {
         unsafe {
-            let mut aniso = 0.0;
-            gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut aniso);
-            gl::TexParameterf(gl::TEXTURE_2D, gl::TEXTURE_MAX_ANISOTROPY_EXT, aniso);
+            let mut max = 1.0;
+            gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY_EXT, &mut max);
+            gl::TexParameterf(
+                self.texture.kind.to_texture_target(),
+                gl::TEXTURE_MAX_ANISOTROPY_EXT,
+                level.max(1.0).min(max.max(1.0)),
+            );
         }
         self
     }
@@ -261,18 +463,28 @@ impl GpuTexture {
         pixel_kind: PixelKind,
         data: Option<&[u8]>,
     ) -> Result<Self, RendererError> {
-        let bytes_per_pixel = pixel_kind.size_bytes();
+        Self::new_with_mips(state, kind, pixel_kind, 1, data)
+    }
 
-        let desired_byte_count = match kind {
-            GpuTextureKind::Line { length } => length * bytes_per_pixel,
-            GpuTextureKind::Rectangle { width, height } => width * height * bytes_per_pixel,
-            GpuTextureKind::Cube { width, height } => 6 * width * height * bytes_per_pixel,
-            GpuTextureKind::Volume {
-                width,
-                height,
-                depth,
-            } => width * height * depth * bytes_per_pixel,
-        };
+    /// Creates new GPU texture of specified kind with given amount of mip levels.
+    ///
+    /// # Data layout
+    ///
+    /// `bytes` should contain mip levels one after another starting from the largest one,
+    /// each level of Cube texture contains all 6 faces in the same order as in `new`. Data
+    /// of compressed formats is uploaded as is, without decompression.
+    pub fn new_with_mips(
+        state: &mut State,
+        kind: GpuTextureKind,
+        pixel_kind: PixelKind,
+        mip_count: usize,
+        data: Option<&[u8]>,
+    ) -> Result<Self, RendererError> {
+        let mip_count = mip_count.max(1);
+
+        let desired_byte_count = (0..mip_count)
+            .map(|level| kind.mip_level(level).byte_count(pixel_kind))
+            .sum::<usize>();
 
         if let Some(data) = data {
             if data.len() != desired_byte_count {
@@ -293,6 +505,9 @@ impl GpuTexture {
 
             let (type_, format, internal_format) = match pixel_kind {
                 PixelKind::F32 => (gl::FLOAT, gl::RED, gl::R32F),
+                PixelKind::RGB32F => (gl::FLOAT, gl::RGB, gl::RGB32F),
+                PixelKind::RGBA32F => (gl::FLOAT, gl::RGBA, gl::RGBA32F),
+                PixelKind::RGBA16F => (gl::HALF_FLOAT, gl::RGBA, gl::RGBA16F),
                 PixelKind::D32 => (gl::FLOAT, gl::DEPTH_COMPONENT, gl::DEPTH_COMPONENT),
                 PixelKind::D24S8 => (
                     gl::UNSIGNED_INT_24_8,
@@ -303,92 +518,151 @@ impl GpuTexture {
                 PixelKind::RGB8 => (gl::UNSIGNED_BYTE, gl::RGB, gl::RGB8),
                 PixelKind::RG8 => (gl::UNSIGNED_BYTE, gl::RG, gl::RG8),
                 PixelKind::R8 => (gl::UNSIGNED_BYTE, gl::RED, gl::R8),
+                PixelKind::SRGBA8 => (gl::UNSIGNED_BYTE, gl::RGBA, gl::SRGB8_ALPHA8),
+                PixelKind::SRGB8 => (gl::UNSIGNED_BYTE, gl::RGB, gl::SRGB8),
+                // Type and format are not used for compressed formats.
+                PixelKind::BC1 => (0, 0, COMPRESSED_RGBA_S3TC_DXT1_EXT),
+                PixelKind::BC2 => (0, 0, COMPRESSED_RGBA_S3TC_DXT3_EXT),
+                PixelKind::BC3 => (0, 0, COMPRESSED_RGBA_S3TC_DXT5_EXT),
+                PixelKind::BC4 => (0, 0, gl::COMPRESSED_RED_RGTC1),
+                PixelKind::BC5 => (0, 0, gl::COMPRESSED_RG_RGTC2),
+                PixelKind::BC6H => (0, 0, gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT),
+                PixelKind::BC7 => (0, 0, gl::COMPRESSED_RGBA_BPTC_UNORM),
+                PixelKind::BC1SRGB => (0, 0, COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT),
+                PixelKind::BC2SRGB => (0, 0, COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT),
+                PixelKind::BC3SRGB => (0, 0, COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT),
+                PixelKind::BC7SRGB => (0, 0, gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM),
             };
 
             gl::PixelStorei(gl::UNPACK_ALIGNMENT, pixel_kind.unpack_alignment());
 
-            let pixels = match data {
-                None => std::ptr::null(),
-                Some(data) => data.as_ptr() as *const c_void,
-            };
-
-            match kind {
-                GpuTextureKind::Line { length } => {
-                    gl::TexImage1D(
-                        gl::TEXTURE_1D,
-                        0,
-                        internal_format as i32,
-                        length as i32,
-                        0,
-                        format,
-                        type_,
-                        pixels,
-                    );
-                }
-                GpuTextureKind::Rectangle { width, height } => {
-                    gl::TexImage2D(
-                        gl::TEXTURE_2D,
-                        0,
-                        internal_format as i32,
-                        width as i32,
-                        height as i32,
-                        0,
-                        format,
-                        type_,
-                        pixels,
-                    );
-                }
-                GpuTextureKind::Cube { width, height } => {
-                    for face in 0..6 {
-                        let bytes_per_face = width * height * bytes_per_pixel;
-
-                        let begin = face * bytes_per_face;
-                        let end = (face + 1) * bytes_per_face;
-
-                        let face_pixels = match data {
-                            None => std::ptr::null(),
-                            Some(data) => data[begin..end].as_ptr() as *const c_void,
-                        };
-
-                        gl::TexImage2D(
-                            gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+            let mut offset = 0;
+            for level in 0..mip_count {
+                let level_kind = kind.mip_level(level);
+                let level_size = level_kind.byte_count(pixel_kind);
+                let level_data = data.map(|data| &data[offset..(offset + level_size)]);
+                offset += level_size;
+
+                let image = |target: u32, image_data: Option<&[u8]>, dims: [usize; 3]| {
+                    let image_size = image_data.map_or(0, |d| d.len()) as i32;
+                    let pixels = match image_data {
+                        None => std::ptr::null(),
+                        Some(image_data) => image_data.as_ptr() as *const c_void,
+                    };
+                    let [width, height, depth] = dims;
+                    let level = level as i32;
+                    let internal_format = internal_format as i32;
+                    match (target, pixel_kind.is_compressed()) {
+                        (gl::TEXTURE_1D, false) => gl::TexImage1D(
+                            target,
+                            level,
+                            internal_format,
+                            width as i32,
                             0,
-                            internal_format as i32,
+                            format,
+                            type_,
+                            pixels,
+                        ),
+                        (gl::TEXTURE_1D, true) => gl::CompressedTexImage1D(
+                            target,
+                            level,
+                            internal_format as u32,
+                            width as i32,
+                            0,
+                            image_size,
+                            pixels,
+                        ),
+                        (gl::TEXTURE_3D, false) => gl::TexImage3D(
+                            target,
+                            level,
+                            internal_format,
                             width as i32,
                             height as i32,
+                            depth as i32,
                             0,
                             format,
                             type_,
-                            face_pixels,
-                        );
+                            pixels,
+                        ),
+                        (gl::TEXTURE_3D, true) => gl::CompressedTexImage3D(
+                            target,
+                            level,
+                            internal_format as u32,
+                            width as i32,
+                            height as i32,
+                            depth as i32,
+                            0,
+                            image_size,
+                            pixels,
+                        ),
+                        (_, false) => gl::TexImage2D(
+                            target,
+                            level,
+                            internal_format,
+                            width as i32,
+                            height as i32,
+                            0,
+                            format,
+                            type_,
+                            pixels,
+                        ),
+                        (_, true) => gl::CompressedTexImage2D(
+                            target,
+                            level,
+                            internal_format as u32,
+                            width as i32,
+                            height as i32,
+                            0,
+                            image_size,
+                            pixels,
+                        ),
+                    }
+                };
+
+                match level_kind {
+                    GpuTextureKind::Line { length } => {
+                        image(gl::TEXTURE_1D, level_data, [length, 1, 1]);
+                    }
+                    GpuTextureKind::Rectangle { width, height } => {
+                        image(gl::TEXTURE_2D, level_data, [width, height, 1]);
+                    }
+                    GpuTextureKind::Cube { width, height } => {
+                        let bytes_per_face = level_size / 6;
+                        for face in 0..6 {
+                            let begin = face * bytes_per_face;
+                            let end = (face + 1) * bytes_per_face;
+                            let face_data = level_data.map(|data| &data[begin..end]);
+                            image(
+                                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                                face_data,
+                                [width, height, 1],
+                            );
+                        }
+                    }
+                    GpuTextureKind::Volume {
+                        width,
+                        height,
+                        depth,
+                    } => {
+                        image(gl::TEXTURE_3D, level_data, [width, height, depth]);
                     }
-                }
-                GpuTextureKind::Volume {
-                    width,
-                    height,
-                    depth,
-                } => {
-                    gl::TexImage3D(
-                        gl::TEXTURE_3D,
-                        0,
-                        internal_format as i32,
-                        width as i32,
-                        height as i32,
-                        depth as i32,
-                        0,
-                        format,
-                        type_,
-                        pixels,
-                    );
                 }
             }
 
             gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
             gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            if mip_count > 1 {
+                // Mip chains from files can be incomplete (for example end at 4x4 level for
+                // compressed formats), such textures can't be sampled without this limit.
+                gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, mip_count as i32 - 1);
+            }
 
             state.set_texture(0, target, 0);
 
-            Log::writeln(format!("GL texture {} was created!", texture));
+            Log::debug(
+                LogCategory::Renderer,
+                format!("GL texture {} was created!", texture),
+            );
 
             Ok(Self {
                 texture,
@@ -419,7 +693,10 @@ impl GpuTexture {
 impl Drop for GpuTexture {
     fn drop(&mut self) {
         unsafe {
-            Log::writeln(format!("GL texture {} was destroyed!", self.texture));
+            Log::debug(
+                LogCategory::Renderer,
+                format!("GL texture {} was destroyed!", self.texture),
+            );
 
             gl::DeleteTextures(1, &self.texture);
         }
@@ -1,6 +1,6 @@
 use crate::{
     renderer::framework::gl::types::{GLchar, GLenum, GLsizei, GLuint},
-    utils::log::Log,
+    utils::log::{Log, LogCategory, LogLevel},
 };
 use std::ffi::CStr;
 
@@ -33,10 +33,13 @@ pub fn check_gl_error_internal(line: u32, file: &str) {
                 _ => "Unknown",
             };
 
-            Log::writeln(format!(
-                "{} error has occurred! At line {} in file {}, stability is not guaranteed!",
-                code, line, file
-            ));
+            Log::err(
+                LogCategory::Renderer,
+                format!(
+                    "{} error has occurred! At line {} in file {}, stability is not guaranteed!",
+                    code, line, file
+                ),
+            );
 
             if gl::GetDebugMessageLog::is_loaded() {
                 let mut max_message_length = 0;
@@ -77,8 +80,9 @@ pub fn check_gl_error_internal(line: u32, file: &str) {
                 );
 
                 if message_count == 0 {
-                    Log::writeln(
-                        "Debug info is not available - run with OpenGL debug flag!".to_owned(),
+                    Log::warn(
+                        LogCategory::Renderer,
+                        "Debug info is not available - run with OpenGL debug flag!",
                     );
                 }
 
@@ -119,21 +123,30 @@ pub fn check_gl_error_internal(line: u32, file: &str) {
                         _ => "Unknown",
                     };
 
+                    let level = match severity {
+                        gl::DEBUG_SEVERITY_HIGH => LogLevel::Error,
+                        gl::DEBUG_SEVERITY_MEDIUM | gl::DEBUG_SEVERITY_LOW => LogLevel::Warning,
+                        _ => LogLevel::Information,
+                    };
+
                     let str_msg = CStr::from_ptr(message);
 
-                    Log::writeln(format!("OpenGL message\nSource: {}\nType: {}\nId: {}\nSeverity: {}\nMessage: {:?}\n",
-                                         source_str,
-                                         type_str,
-                                         id,
-                                         severity_str,
-                                         str_msg));
+                    Log::log(
+                        level,
+                        LogCategory::Renderer,
+                        format!(
+                            "OpenGL message\nSource: {}\nType: {}\nId: {}\nSeverity: {}\n\
+                             Message: {:?}",
+                            source_str, type_str, id, severity_str, str_msg
+                        ),
+                    );
 
                     message = message.add(len);
                 }
             } else {
-                Log::writeln(
-                    "Debug info is not available - glGetDebugMessageLog is not available!"
-                        .to_owned(),
+                Log::warn(
+                    LogCategory::Renderer,
+                    "Debug info is not available - glGetDebugMessageLog is not available!",
                 );
             }
         }
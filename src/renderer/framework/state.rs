@@ -15,6 +15,7 @@ pub struct State {
     depth_write: bool,
     color_write: ColorMask,
     stencil_test: bool,
+    program_point_size: bool,
     cull_face: CullFace,
     culling: bool,
     stencil_mask: u32,
@@ -133,6 +134,7 @@ impl State {
             depth_write: true,
             color_write: Default::default(),
             stencil_test: false,
+            program_point_size: false,
             cull_face: CullFace::Back,
             culling: false,
             stencil_mask: 0xFFFF_FFFF,
@@ -247,6 +249,20 @@ impl State {
         }
     }
 
+    pub fn set_program_point_size(&mut self, program_point_size: bool) {
+        if self.program_point_size != program_point_size {
+            self.program_point_size = program_point_size;
+
+            unsafe {
+                if self.program_point_size {
+                    gl::Enable(gl::PROGRAM_POINT_SIZE);
+                } else {
+                    gl::Disable(gl::PROGRAM_POINT_SIZE);
+                }
+            }
+        }
+    }
+
     pub fn set_cull_face(&mut self, cull_face: CullFace) {
         if self.cull_face != cull_face {
             self.cull_face = cull_face;
@@ -1,24 +1,33 @@
 use crate::{
     core::{
         color::Color,
-        math::{frustum::Frustum, mat4::Mat4, Rect},
+        math::{mat4::Mat4, vec3::Vec3, Rect},
+        pool::Handle,
         scope_profile,
     },
     renderer::{
         error::RendererError,
         framework::{
             framebuffer::{
-                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+                Attachment, AttachmentKind, CullFace, DrawInstancedContext, DrawParameters,
+                FrameBuffer, FrameBufferTrait,
             },
             gpu_program::{GpuProgram, UniformLocation, UniformValue},
             gpu_texture::{Coordinate, GpuTexture, GpuTextureKind, PixelKind, WrapMode},
             state::State,
         },
-        GeometryCache, RenderPassStatistics, TextureCache,
+        surface::MAX_ACTIVE_MORPH_TARGETS,
+        GeometryCache, MorphTargetCache, RenderPassStatistics, TerrainCache, TextureCache,
     },
-    scene::{camera::Camera, graph::Graph, node::Node},
+    renderer::material::MaterialState,
+    scene::{camera::Camera, graph::Graph, node::Node, terrain::Terrain},
+};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    sync::Arc,
 };
-use std::{cell::RefCell, rc::Rc};
 
 struct GBufferShader {
     program: GpuProgram,
@@ -30,6 +39,14 @@ struct GBufferShader {
     normal_texture: UniformLocation,
     lightmap_texture: UniformLocation,
     diffuse_color: UniformLocation,
+    emission_strength: UniformLocation,
+    dissolve_threshold: UniformLocation,
+    uv_offset: UniformLocation,
+    morph_texture: UniformLocation,
+    morph_target_count: UniformLocation,
+    morph_vertex_count: UniformLocation,
+    morph_target_indices: UniformLocation,
+    morph_target_weights: UniformLocation,
 }
 
 impl GBufferShader {
@@ -46,6 +63,89 @@ impl GBufferShader {
             normal_texture: program.uniform_location("normalTexture")?,
             lightmap_texture: program.uniform_location("lightmapTexture")?,
             diffuse_color: program.uniform_location("diffuseColor")?,
+            emission_strength: program.uniform_location("emissionStrength")?,
+            dissolve_threshold: program.uniform_location("dissolveThreshold")?,
+            uv_offset: program.uniform_location("uvOffset")?,
+            morph_texture: program.uniform_location("morphTexture")?,
+            morph_target_count: program.uniform_location("morphTargetCount")?,
+            morph_vertex_count: program.uniform_location("morphVertexCount")?,
+            morph_target_indices: program.uniform_location("morphTargetIndices")?,
+            morph_target_weights: program.uniform_location("morphTargetWeights")?,
+            program,
+        })
+    }
+}
+
+struct TerrainShader {
+    program: GpuProgram,
+    world_matrix: UniformLocation,
+    wvp_matrix: UniformLocation,
+    diffuse_textures: [UniformLocation; Terrain::MAX_LAYERS],
+    normal_textures: [UniformLocation; Terrain::MAX_LAYERS],
+    tile_sizes: UniformLocation,
+    splat_map: UniformLocation,
+}
+
+impl TerrainShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/terrain_fs.glsl");
+        let vertex_source = include_str!("shaders/terrain_vs.glsl");
+        let program = GpuProgram::from_source("TerrainShader", vertex_source, fragment_source)?;
+        let layer_locations = |name: &str| -> Result<_, RendererError> {
+            Ok([
+                program.uniform_location(&format!("{}[0]", name))?,
+                program.uniform_location(&format!("{}[1]", name))?,
+                program.uniform_location(&format!("{}[2]", name))?,
+                program.uniform_location(&format!("{}[3]", name))?,
+            ])
+        };
+        Ok(Self {
+            world_matrix: program.uniform_location("worldMatrix")?,
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            diffuse_textures: layer_locations("diffuseTextures")?,
+            normal_textures: layer_locations("normalTextures")?,
+            tile_sizes: program.uniform_location("tileSizes")?,
+            splat_map: program.uniform_location("splatMap")?,
+            program,
+        })
+    }
+}
+
+/// Amount of instances drawn in a single draw call. Instance matrices are passed as uniforms,
+/// and GL 3.3 guarantees only 1024 uniform components for vertex shader.
+/// Must be in sync with MAX_INSTANCES in scatter_vs.glsl.
+const MAX_SCATTER_INSTANCES: usize = 32;
+
+struct ScatterShader {
+    program: GpuProgram,
+    view_projection: UniformLocation,
+    instance_matrices: UniformLocation,
+    camera_position: UniformLocation,
+    fade_start: UniformLocation,
+    fade_end: UniformLocation,
+    diffuse_texture: UniformLocation,
+    normal_texture: UniformLocation,
+    diffuse_color: UniformLocation,
+    emission_strength: UniformLocation,
+    uv_offset: UniformLocation,
+}
+
+impl ScatterShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/scatter_fs.glsl");
+        let vertex_source = include_str!("shaders/scatter_vs.glsl");
+        let program = GpuProgram::from_source("ScatterShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            view_projection: program.uniform_location("viewProjection")?,
+            instance_matrices: program.uniform_location("instanceMatrices")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            fade_start: program.uniform_location("fadeStart")?,
+            fade_end: program.uniform_location("fadeEnd")?,
+            diffuse_texture: program.uniform_location("diffuseTexture")?,
+            normal_texture: program.uniform_location("normalTexture")?,
+            diffuse_color: program.uniform_location("diffuseColor")?,
+            emission_strength: program.uniform_location("emissionStrength")?,
+            uv_offset: program.uniform_location("uvOffset")?,
             program,
         })
     }
@@ -55,9 +155,16 @@ pub struct GBuffer {
     framebuffer: FrameBuffer,
     pub final_frame: FrameBuffer,
     shader: GBufferShader,
+    terrain_shader: TerrainShader,
+    scatter_shader: ScatterShader,
     bone_matrices: Vec<Mat4>,
+    instance_matrices: Vec<Mat4>,
+    active_morph_targets: Vec<(usize, f32)>,
+    material_states: HashMap<usize, MaterialState>,
     pub width: i32,
     pub height: i32,
+    /// True if final frame is stored in high dynamic range.
+    pub hdr: bool,
 }
 
 pub(in crate) struct GBufferRenderContext<'a, 'b> {
@@ -68,10 +175,18 @@ pub(in crate) struct GBufferRenderContext<'a, 'b> {
     pub normal_dummy: Rc<RefCell<GpuTexture>>,
     pub texture_cache: &'a mut TextureCache,
     pub geom_cache: &'a mut GeometryCache,
+    pub morph_cache: &'a mut MorphTargetCache,
+    pub terrain_cache: &'a mut TerrainCache,
+    pub culled: &'b HashSet<Handle<Node>>,
 }
 
 impl GBuffer {
-    pub fn new(state: &mut State, width: usize, height: usize) -> Result<Self, RendererError> {
+    pub fn new(
+        state: &mut State,
+        width: usize,
+        height: usize,
+        hdr: bool,
+    ) -> Result<Self, RendererError> {
         let mut depth_stencil_texture = GpuTexture::new(
             state,
             GpuTextureKind::Rectangle { width, height },
@@ -85,10 +200,11 @@ impl GBuffer {
 
         let depth_stencil = Rc::new(RefCell::new(depth_stencil_texture));
 
+        // Diffuse color is stored in sRGB color space to not lose precision in dark colors.
         let mut diffuse_texture = GpuTexture::new(
             state,
             GpuTextureKind::Rectangle { width, height },
-            PixelKind::RGBA8,
+            PixelKind::SRGBA8,
             None,
         )?;
         diffuse_texture
@@ -143,7 +259,11 @@ impl GBuffer {
         let frame_texture = GpuTexture::new(
             state,
             GpuTextureKind::Rectangle { width, height },
-            PixelKind::RGBA8,
+            if hdr {
+                PixelKind::RGBA16F
+            } else {
+                PixelKind::SRGBA8
+            },
             None,
         )?;
 
@@ -162,9 +282,15 @@ impl GBuffer {
         Ok(Self {
             framebuffer,
             shader: GBufferShader::new()?,
+            terrain_shader: TerrainShader::new()?,
+            scatter_shader: ScatterShader::new()?,
             bone_matrices: Vec::new(),
+            instance_matrices: Vec::new(),
+            active_morph_targets: Vec::new(),
+            material_states: HashMap::new(),
             width: width as i32,
             height: height as i32,
+            hdr,
             final_frame: opt_framebuffer,
         })
     }
@@ -203,9 +329,12 @@ impl GBuffer {
             normal_dummy,
             texture_cache,
             geom_cache,
+            morph_cache,
+            terrain_cache,
+            culled,
         } = args;
 
-        let frustum = Frustum::from(camera.view_projection_matrix()).unwrap();
+        let frustum = camera.frustum();
 
         let viewport = Rect::new(0, 0, self.width, self.height);
         self.framebuffer.clear(
@@ -218,18 +347,27 @@ impl GBuffer {
 
         let initial_view_projection = camera.view_projection_matrix();
 
-        'mesh_loop: for mesh in graph.linear_iter().filter_map(|node| {
-            if let Node::Mesh(mesh) = node {
-                Some(mesh)
+        // Surfaces are drawn in batches by root material, so surfaces with instances of
+        // the same material are drawn one after another and state of each material is
+        // calculated only once per frame.
+        let mut batches = Vec::new();
+        // Only nodes near the frustum are checked, bounds tree is updated by scene update.
+        let mut candidates = Vec::new();
+        graph.bounds_tree().query_frustum(&frustum, &mut candidates);
+        candidates.retain(|&handle| graph.is_valid_handle(handle));
+        candidates.sort_unstable_by_key(|handle| handle.index());
+        'mesh_loop: for (handle, mesh) in candidates.iter().filter_map(|&handle| {
+            if let Node::Mesh(mesh) = &graph[handle] {
+                Some((handle, mesh))
             } else {
                 None
             }
         }) {
-            if !mesh.is_intersect_frustum(graph, &frustum) {
+            if culled.contains(&handle) || !mesh.is_intersect_frustum(graph, &frustum) {
                 continue 'mesh_loop;
             }
 
-            if !mesh.global_visibility() {
+            if !mesh.global_visibility() || !camera.sees_layers(mesh.global_layers()) {
                 continue 'mesh_loop;
             }
 
@@ -242,50 +380,273 @@ impl GBuffer {
             };
 
             for surface in mesh.surfaces().iter() {
-                let is_skinned = !surface.bones.is_empty();
+                let batch_key = surface.material().map_or(0, |material| {
+                    let root = material.lock().unwrap().root();
+                    Arc::as_ptr(&root.unwrap_or(material)) as usize
+                });
+                batches.push((batch_key, mesh, surface, view_projection));
+            }
+        }
+        // Sort is stable, so order of surfaces inside batch is preserved.
+        batches.sort_by_key(|(batch_key, ..)| *batch_key);
+
+        self.material_states.clear();
+        for (_, mesh, surface, view_projection) in batches {
+            let material_state = surface.material().map(|material| {
+                self.material_states
+                    .entry(Arc::as_ptr(&material) as usize)
+                    .or_insert_with(|| material.lock().unwrap().state())
+                    .clone()
+            });
+            let material_state = surface.combined_state(material_state.as_ref());
+
+            let is_skinned = !surface.bones.is_empty();
+
+            let world = if is_skinned {
+                Mat4::IDENTITY
+            } else {
+                mesh.global_transform()
+            };
+            let mvp = view_projection * world;
 
-                let world = if is_skinned {
-                    Mat4::IDENTITY
-                } else {
-                    mesh.global_transform()
-                };
-                let mvp = view_projection * world;
-
-                let diffuse_texture = if let Some(texture) = surface.diffuse_texture() {
-                    if let Some(texture) = texture_cache.get(state, texture) {
-                        texture
-                    } else {
-                        white_dummy.clone()
-                    }
+            let diffuse_texture = if let Some(texture) = material_state.diffuse_texture {
+                if let Some(texture) = texture_cache.get(state, texture) {
+                    texture
                 } else {
                     white_dummy.clone()
-                };
+                }
+            } else {
+                white_dummy.clone()
+            };
 
-                let normal_texture = if let Some(texture) = surface.normal_texture() {
-                    if let Some(texture) = texture_cache.get(state, texture) {
-                        texture
-                    } else {
-                        normal_dummy.clone()
-                    }
+            let normal_texture = if let Some(texture) = material_state.normal_texture {
+                if let Some(texture) = texture_cache.get(state, texture) {
+                    texture
                 } else {
                     normal_dummy.clone()
-                };
+                }
+            } else {
+                normal_dummy.clone()
+            };
 
-                let lightmap_texture = if let Some(texture) = surface.lightmap_texture() {
-                    if let Some(texture) = texture_cache.get(state, texture) {
-                        texture
-                    } else {
-                        white_dummy.clone()
-                    }
+            let lightmap_texture = if let Some(texture) = surface.lightmap_texture() {
+                if let Some(texture) = texture_cache.get(state, texture) {
+                    texture
                 } else {
                     white_dummy.clone()
-                };
+                }
+            } else {
+                white_dummy.clone()
+            };
+
+            let data = surface.data();
+            let data = data.lock().unwrap();
+
+            // Shader supports only fixed amount of active morph targets, surface gives
+            // the most influential ones.
+            let mut morph_target_indices = [0; MAX_ACTIVE_MORPH_TARGETS];
+            let mut morph_target_weights = [0.0; MAX_ACTIVE_MORPH_TARGETS];
+            let (morph_texture, morph_target_count) = match morph_cache.get(state, &data) {
+                Some(morph_texture) => {
+                    surface.active_morph_targets(&mut self.active_morph_targets);
+                    for (i, &(index, weight)) in self.active_morph_targets.iter().enumerate() {
+                        morph_target_indices[i] = index as i32;
+                        morph_target_weights[i] = weight;
+                    }
+                    (morph_texture, self.active_morph_targets.len())
+                }
+                None => (white_dummy.clone(), 0),
+            };
+            let morph_vertex_count = data.get_vertices().len();
+
+            statistics += self.framebuffer.draw(
+                geom_cache.get(state, &data),
+                state,
+                viewport,
+                &self.shader.program,
+                DrawParameters {
+                    cull_face: CullFace::Back,
+                    culling: true,
+                    color_write: Default::default(),
+                    depth_write: true,
+                    stencil_test: false,
+                    depth_test: true,
+                    blend: false,
+                },
+                &[
+                    (
+                        self.shader.diffuse_texture,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: diffuse_texture,
+                        },
+                    ),
+                    (
+                        self.shader.normal_texture,
+                        UniformValue::Sampler {
+                            index: 1,
+                            texture: normal_texture,
+                        },
+                    ),
+                    (
+                        self.shader.lightmap_texture,
+                        UniformValue::Sampler {
+                            index: 2,
+                            texture: lightmap_texture,
+                        },
+                    ),
+                    (self.shader.wvp_matrix, UniformValue::Mat4(mvp)),
+                    (self.shader.world_matrix, UniformValue::Mat4(world)),
+                    (
+                        self.shader.use_skeletal_animation,
+                        UniformValue::Bool(is_skinned),
+                    ),
+                    (
+                        self.shader.diffuse_color,
+                        UniformValue::Color(material_state.color),
+                    ),
+                    (
+                        self.shader.emission_strength,
+                        UniformValue::Float(material_state.emission_strength),
+                    ),
+                    (
+                        self.shader.dissolve_threshold,
+                        UniformValue::Float(material_state.dissolve_threshold),
+                    ),
+                    (
+                        self.shader.uv_offset,
+                        UniformValue::Vec2(material_state.uv_offset),
+                    ),
+                    (
+                        self.shader.morph_texture,
+                        UniformValue::Sampler {
+                            index: 3,
+                            texture: morph_texture,
+                        },
+                    ),
+                    (
+                        self.shader.morph_target_count,
+                        UniformValue::Integer(morph_target_count as i32),
+                    ),
+                    (
+                        self.shader.morph_vertex_count,
+                        UniformValue::Integer(morph_vertex_count as i32),
+                    ),
+                    (
+                        self.shader.morph_target_indices,
+                        UniformValue::IntegerArray(&morph_target_indices),
+                    ),
+                    (
+                        self.shader.morph_target_weights,
+                        UniformValue::FloatArray(&morph_target_weights),
+                    ),
+                    (
+                        self.shader.bone_matrices,
+                        UniformValue::Mat4Array({
+                            self.bone_matrices.clear();
+                            for &bone_handle in surface.bones.iter() {
+                                let bone_node = &graph[bone_handle];
+                                self.bone_matrices.push(
+                                    bone_node.global_transform()
+                                        * bone_node.inv_bind_pose_transform(),
+                                );
+                            }
+                            &self.bone_matrices
+                        }),
+                    ),
+                ],
+            );
+        }
+
+        for (handle, terrain) in candidates.iter().filter_map(|&handle| {
+            if let Node::Terrain(terrain) = &graph[handle] {
+                Some((handle, terrain))
+            } else {
+                None
+            }
+        }) {
+            if !terrain.global_visibility()
+                || !camera.sees_layers(terrain.global_layers())
+                || culled.contains(&handle)
+            {
+                continue;
+            }
+
+            let gpu_data = match terrain_cache.get(state, terrain) {
+                Some(gpu_data) => gpu_data,
+                None => continue,
+            };
+
+            // Missing layers are filled with dummies, they have zero weight in splat map
+            // anyway.
+            let mut layer_textures = Vec::with_capacity(Terrain::MAX_LAYERS);
+            let mut tile_sizes = [1.0; Terrain::MAX_LAYERS];
+            for (i, tile_size) in tile_sizes.iter_mut().enumerate() {
+                let layer = terrain.texture_layers().get(i);
+                let diffuse_texture = layer
+                    .and_then(|layer| layer.diffuse_texture.clone())
+                    .and_then(|texture| texture_cache.get(state, texture))
+                    .unwrap_or_else(|| white_dummy.clone());
+                let normal_texture = layer
+                    .and_then(|layer| layer.normal_texture.clone())
+                    .and_then(|texture| texture_cache.get(state, texture))
+                    .unwrap_or_else(|| normal_dummy.clone());
+                layer_textures.push((diffuse_texture, normal_texture));
+                if let Some(layer) = layer {
+                    *tile_size = layer.tile_size.max(std::f32::EPSILON);
+                }
+            }
+
+            let world = terrain.global_transform();
+            let wvp = initial_view_projection * world;
+            let camera_position = camera.global_position();
+
+            for (index, chunk) in terrain.chunks().iter().enumerate() {
+                let bounding_box = terrain.chunk_bounding_box(chunk);
+                if !frustum.is_intersects_aabb_transform(&bounding_box, &world) {
+                    continue;
+                }
+
+                let center =
+                    world.transform_vector((bounding_box.min + bounding_box.max).scale(0.5));
+                let lod = terrain.lod_at_distance(camera_position.distance(&center));
+
+                let mut uniforms = Vec::with_capacity(2 * Terrain::MAX_LAYERS + 4);
+                uniforms.push((self.terrain_shader.world_matrix, UniformValue::Mat4(world)));
+                uniforms.push((self.terrain_shader.wvp_matrix, UniformValue::Mat4(wvp)));
+                uniforms.push((
+                    self.terrain_shader.tile_sizes,
+                    UniformValue::FloatArray(&tile_sizes),
+                ));
+                uniforms.push((
+                    self.terrain_shader.splat_map,
+                    UniformValue::Sampler {
+                        index: 2 * Terrain::MAX_LAYERS,
+                        texture: gpu_data.splat_map(),
+                    },
+                ));
+                for (i, (diffuse_texture, normal_texture)) in layer_textures.iter().enumerate() {
+                    uniforms.push((
+                        self.terrain_shader.diffuse_textures[i],
+                        UniformValue::Sampler {
+                            index: i,
+                            texture: diffuse_texture.clone(),
+                        },
+                    ));
+                    uniforms.push((
+                        self.terrain_shader.normal_textures[i],
+                        UniformValue::Sampler {
+                            index: Terrain::MAX_LAYERS + i,
+                            texture: normal_texture.clone(),
+                        },
+                    ));
+                }
 
                 statistics += self.framebuffer.draw(
-                    geom_cache.get(state, &surface.data().lock().unwrap()),
+                    gpu_data.chunk_geometry(state, terrain, index, lod),
                     state,
                     viewport,
-                    &self.shader.program,
+                    &self.terrain_shader.program,
                     DrawParameters {
                         cull_face: CullFace::Back,
                         culling: true,
@@ -295,57 +656,142 @@ impl GBuffer {
                         depth_test: true,
                         blend: false,
                     },
-                    &[
-                        (
-                            self.shader.diffuse_texture,
-                            UniformValue::Sampler {
-                                index: 0,
-                                texture: diffuse_texture,
-                            },
-                        ),
-                        (
-                            self.shader.normal_texture,
-                            UniformValue::Sampler {
-                                index: 1,
-                                texture: normal_texture,
-                            },
-                        ),
-                        (
-                            self.shader.lightmap_texture,
-                            UniformValue::Sampler {
-                                index: 2,
-                                texture: lightmap_texture,
-                            },
-                        ),
-                        (self.shader.wvp_matrix, UniformValue::Mat4(mvp)),
-                        (self.shader.world_matrix, UniformValue::Mat4(world)),
-                        (
-                            self.shader.use_skeletal_animation,
-                            UniformValue::Bool(is_skinned),
-                        ),
-                        (
-                            self.shader.diffuse_color,
-                            UniformValue::Color(surface.color()),
-                        ),
-                        (
-                            self.shader.bone_matrices,
-                            UniformValue::Mat4Array({
-                                self.bone_matrices.clear();
-                                for &bone_handle in surface.bones.iter() {
-                                    let bone_node = &graph[bone_handle];
-                                    self.bone_matrices.push(
-                                        bone_node.global_transform()
-                                            * bone_node.inv_bind_pose_transform(),
-                                    );
-                                }
-                                &self.bone_matrices
-                            }),
-                        ),
-                    ],
+                    &uniforms,
                 );
             }
         }
 
+        let camera_position = camera.global_position();
+        for (handle, scatter) in graph.pair_iter().filter_map(|(handle, node)| {
+            if let Node::Scatter(scatter) = node {
+                Some((handle, scatter))
+            } else {
+                None
+            }
+        }) {
+            if !scatter.global_visibility()
+                || !camera.sees_layers(scatter.global_layers())
+                || culled.contains(&handle)
+            {
+                continue;
+            }
+
+            let world = scatter.global_transform();
+            // Largest scale along basis vectors, bounding sphere must enclose instance
+            // even if node is scaled non-uniformly.
+            let node_scale = Vec3::new(world.f[0], world.f[1], world.f[2])
+                .len()
+                .max(Vec3::new(world.f[4], world.f[5], world.f[6]).len())
+                .max(Vec3::new(world.f[8], world.f[9], world.f[10]).len());
+            let bounding_radius = scatter.bounding_radius() * node_scale;
+
+            // Instances which are either fully faded out or outside of frustum are culled
+            // on CPU, so GPU gets only potentially visible ones.
+            self.instance_matrices.clear();
+            for instance in scatter.instances() {
+                let instance_world = world * instance.local_transform();
+                let position = instance_world.position();
+                let radius = bounding_radius * instance.scale;
+                if camera_position.distance(&position) > scatter.fade_end() + radius
+                    || !frustum.is_intersects_sphere(position, radius)
+                {
+                    continue;
+                }
+                self.instance_matrices.push(instance_world);
+            }
+
+            if self.instance_matrices.is_empty() {
+                continue;
+            }
+
+            for surface in scatter.surfaces() {
+                let material_state = surface
+                    .material()
+                    .map(|material| material.lock().unwrap().state());
+                let material_state = surface.combined_state(material_state.as_ref());
+
+                let diffuse_texture = material_state
+                    .diffuse_texture
+                    .and_then(|texture| texture_cache.get(state, texture))
+                    .unwrap_or_else(|| white_dummy.clone());
+                let normal_texture = material_state
+                    .normal_texture
+                    .and_then(|texture| texture_cache.get(state, texture))
+                    .unwrap_or_else(|| normal_dummy.clone());
+
+                let data = surface.data();
+                let data = data.lock().unwrap();
+                let geometry = geom_cache.get(state, &data);
+
+                for instances in self.instance_matrices.chunks(MAX_SCATTER_INSTANCES) {
+                    statistics += self.framebuffer.draw_instanced(DrawInstancedContext {
+                        state,
+                        viewport,
+                        geometry,
+                        program: &self.scatter_shader.program,
+                        params: DrawParameters {
+                            cull_face: CullFace::Back,
+                            culling: true,
+                            color_write: Default::default(),
+                            depth_write: true,
+                            stencil_test: false,
+                            depth_test: true,
+                            blend: false,
+                        },
+                        uniforms: &[
+                            (
+                                self.scatter_shader.view_projection,
+                                UniformValue::Mat4(initial_view_projection),
+                            ),
+                            (
+                                self.scatter_shader.instance_matrices,
+                                UniformValue::Mat4Array(instances),
+                            ),
+                            (
+                                self.scatter_shader.camera_position,
+                                UniformValue::Vec3(camera_position),
+                            ),
+                            (
+                                self.scatter_shader.fade_start,
+                                UniformValue::Float(scatter.fade_start()),
+                            ),
+                            (
+                                self.scatter_shader.fade_end,
+                                UniformValue::Float(scatter.fade_end()),
+                            ),
+                            (
+                                self.scatter_shader.diffuse_texture,
+                                UniformValue::Sampler {
+                                    index: 0,
+                                    texture: diffuse_texture.clone(),
+                                },
+                            ),
+                            (
+                                self.scatter_shader.normal_texture,
+                                UniformValue::Sampler {
+                                    index: 1,
+                                    texture: normal_texture.clone(),
+                                },
+                            ),
+                            (
+                                self.scatter_shader.diffuse_color,
+                                UniformValue::Color(material_state.color),
+                            ),
+                            (
+                                self.scatter_shader.emission_strength,
+                                UniformValue::Float(material_state.emission_strength),
+                            ),
+                            (
+                                self.scatter_shader.uv_offset,
+                                UniformValue::Vec2(material_state.uv_offset),
+                            ),
+                        ],
+                        instance_count: instances.len(),
+                    });
+                }
+            }
+        }
+
         statistics
     }
 }
@@ -0,0 +1,273 @@
+//! Contains shared materials and material instances.
+//!
+//! By default every surface stores its own textures, color and material parameters. This
+//! is fine for a few surfaces, but when there are hundreds of surfaces which look almost the
+//! same (crates with different tint, rocks with different moss amount, etc.) it becomes hard
+//! to manage them - to change a texture of every crate you have to visit every surface.
+//!
+//! Material is a shared set of such properties which can be assigned to any amount of
+//! surfaces. Material instance is a lightweight material which has base material and
+//! overrides only a subset of properties, every other property is taken from base, so
+//! changes of base material are automatically picked up by every instance. Instances can
+//! be based on other instances, forming chains of any depth.
+//!
+//! Renderer sorts surfaces by their root material, so surfaces that use instances of the
+//! same material are drawn one after another without excessive changes of textures.
+//!
+//! # Surface properties
+//!
+//! Surface with material still has its own color and material parameters: color of surface
+//! is multiplied with color of material and parameters of surface are added to parameters
+//! of material. So parameter tracks and tweens of surfaces keep working as usual.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::color::Color,
+//!     renderer::material::{Material, SharedMaterial},
+//! };
+//! use std::sync::{Arc, Mutex};
+//!
+//! fn make_tinted_crate(base: SharedMaterial) -> SharedMaterial {
+//!     let mut instance = Material::instance_of(base);
+//!     instance.set_color(Color::from_rgba(255, 200, 200, 255));
+//!     Arc::new(Mutex::new(instance))
+//! }
+//! ```
+
+use crate::{
+    core::{
+        color::Color,
+        math::vec2::Vec2,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    resource::texture::Texture,
+};
+use std::sync::{Arc, Mutex};
+
+/// Shared material, every surface that uses material keeps reference to it.
+pub type SharedMaterial = Arc<Mutex<Material>>;
+
+/// Property of material that can be overridden in material instance.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MaterialProperty {
+    /// Diffuse texture.
+    DiffuseTexture,
+    /// Normal map texture.
+    NormalTexture,
+    /// Color.
+    Color,
+    /// Emission strength, see `SurfaceParameter::EmissionStrength`.
+    EmissionStrength,
+    /// Dissolve threshold, see `SurfaceParameter::DissolveThreshold`.
+    DissolveThreshold,
+    /// Offset of texture coordinates.
+    UvOffset,
+}
+
+impl MaterialProperty {
+    fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
+
+/// Final values of properties of material with respect to base materials.
+#[derive(Clone, Debug)]
+pub struct MaterialState {
+    /// Diffuse texture.
+    pub diffuse_texture: Option<Arc<Mutex<Texture>>>,
+    /// Normal map texture.
+    pub normal_texture: Option<Arc<Mutex<Texture>>>,
+    /// Color.
+    pub color: Color,
+    /// Emission strength.
+    pub emission_strength: f32,
+    /// Dissolve threshold.
+    pub dissolve_threshold: f32,
+    /// Offset of texture coordinates.
+    pub uv_offset: Vec2,
+}
+
+impl Default for MaterialState {
+    fn default() -> Self {
+        Self {
+            diffuse_texture: None,
+            normal_texture: None,
+            color: Color::WHITE,
+            emission_strength: 0.0,
+            dissolve_threshold: 0.0,
+            uv_offset: Vec2::ZERO,
+        }
+    }
+}
+
+/// See module docs.
+#[derive(Debug, Default)]
+pub struct Material {
+    base: Option<SharedMaterial>,
+    overrides: u32,
+    state: MaterialState,
+}
+
+impl Material {
+    /// Creates new material without base material. Every property of such material is
+    /// considered overridden.
+    pub fn new() -> Self {
+        Self {
+            base: None,
+            overrides: std::u32::MAX,
+            state: Default::default(),
+        }
+    }
+
+    /// Creates new instance of given material. Instance has no overridden properties, so
+    /// it looks exactly like its base until some property is changed.
+    pub fn instance_of(base: SharedMaterial) -> Self {
+        Self {
+            base: Some(base),
+            overrides: 0,
+            state: Default::default(),
+        }
+    }
+
+    /// Returns base material if this material is an instance.
+    pub fn base(&self) -> Option<SharedMaterial> {
+        self.base.clone()
+    }
+
+    /// Returns first material in chain of base materials, or None if this material has no
+    /// base material. Renderer uses root materials to batch surfaces.
+    pub fn root(&self) -> Option<SharedMaterial> {
+        let mut root = self.base.clone()?;
+        loop {
+            let next = root.lock().unwrap().base.clone();
+            match next {
+                Some(next) => root = next,
+                None => return Some(root),
+            }
+        }
+    }
+
+    /// Returns true if given property is overridden in this material. Materials without
+    /// base material override every property.
+    pub fn is_overridden(&self, property: MaterialProperty) -> bool {
+        self.base.is_none() || self.overrides & property.bit() != 0
+    }
+
+    /// Makes material to take value of given property from base material again. Does
+    /// nothing for materials without base material.
+    pub fn revert(&mut self, property: MaterialProperty) {
+        self.overrides &= !property.bit();
+    }
+
+    /// Sets new diffuse texture and marks it as overridden.
+    pub fn set_diffuse_texture(&mut self, texture: Option<Arc<Mutex<Texture>>>) {
+        self.state.diffuse_texture = texture;
+        self.overrides |= MaterialProperty::DiffuseTexture.bit();
+    }
+
+    /// Sets new normal map texture and marks it as overridden.
+    pub fn set_normal_texture(&mut self, texture: Option<Arc<Mutex<Texture>>>) {
+        self.state.normal_texture = texture;
+        self.overrides |= MaterialProperty::NormalTexture.bit();
+    }
+
+    /// Sets new color and marks it as overridden.
+    pub fn set_color(&mut self, color: Color) {
+        self.state.color = color;
+        self.overrides |= MaterialProperty::Color.bit();
+    }
+
+    /// Sets new emission strength and marks it as overridden.
+    pub fn set_emission_strength(&mut self, strength: f32) {
+        self.state.emission_strength = strength.max(0.0);
+        self.overrides |= MaterialProperty::EmissionStrength.bit();
+    }
+
+    /// Sets new dissolve threshold and marks it as overridden.
+    pub fn set_dissolve_threshold(&mut self, threshold: f32) {
+        self.state.dissolve_threshold = threshold.max(0.0).min(1.0);
+        self.overrides |= MaterialProperty::DissolveThreshold.bit();
+    }
+
+    /// Sets new offset of texture coordinates and marks it as overridden.
+    pub fn set_uv_offset(&mut self, offset: Vec2) {
+        self.state.uv_offset = offset;
+        self.overrides |= MaterialProperty::UvOffset.bit();
+    }
+
+    /// Calculates final values of every property, not overridden properties are taken
+    /// from base materials. This method locks every base material in the chain.
+    pub fn state(&self) -> MaterialState {
+        let mut state = match self.base.as_ref() {
+            Some(base) => base.lock().unwrap().state(),
+            None => return self.state.clone(),
+        };
+        if self.is_overridden(MaterialProperty::DiffuseTexture) {
+            state.diffuse_texture = self.state.diffuse_texture.clone();
+        }
+        if self.is_overridden(MaterialProperty::NormalTexture) {
+            state.normal_texture = self.state.normal_texture.clone();
+        }
+        if self.is_overridden(MaterialProperty::Color) {
+            state.color = self.state.color;
+        }
+        if self.is_overridden(MaterialProperty::EmissionStrength) {
+            state.emission_strength = self.state.emission_strength;
+        }
+        if self.is_overridden(MaterialProperty::DissolveThreshold) {
+            state.dissolve_threshold = self.state.dissolve_threshold;
+        }
+        if self.is_overridden(MaterialProperty::UvOffset) {
+            state.uv_offset = self.state.uv_offset;
+        }
+        state
+    }
+}
+
+impl Visit for Material {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Base", visitor)?;
+        self.overrides.visit("Overrides", visitor)?;
+        self.state.diffuse_texture.visit("DiffuseTexture", visitor)?;
+        self.state.normal_texture.visit("NormalTexture", visitor)?;
+        self.state.color.visit("Color", visitor)?;
+        self.state.emission_strength.visit("EmissionStrength", visitor)?;
+        self.state.dissolve_threshold.visit("DissolveThreshold", visitor)?;
+        self.state.uv_offset.visit("UvOffset", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::color::Color,
+        renderer::material::{Material, MaterialProperty},
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn material_instance_test() {
+        let base = Arc::new(Mutex::new(Material::new()));
+        base.lock().unwrap().set_emission_strength(1.0);
+
+        let mut instance = Material::instance_of(base.clone());
+        instance.set_color(Color::from_rgba(255, 0, 0, 255));
+
+        // Changes of base must be picked up by instance.
+        base.lock().unwrap().set_emission_strength(2.0);
+        base.lock().unwrap().set_color(Color::from_rgba(0, 255, 0, 255));
+        let state = instance.state();
+        assert_eq!(state.emission_strength, 2.0);
+        assert_eq!(state.color, Color::from_rgba(255, 0, 0, 255));
+
+        instance.revert(MaterialProperty::Color);
+        assert_eq!(instance.state().color, Color::from_rgba(0, 255, 0, 255));
+        assert!(Arc::ptr_eq(&instance.root().unwrap(), &base));
+    }
+}
@@ -10,6 +10,7 @@
 
 pub mod debug_renderer;
 pub mod error;
+pub mod material;
 pub mod surface;
 
 // Framework wraps all OpenGL calls so it has to be unsafe. Rest of renderer
@@ -18,16 +19,20 @@ pub mod surface;
 #[allow(unsafe_code)]
 mod framework;
 
+mod auto_exposure;
 mod blur;
 mod deferred_light_renderer;
 mod flat_shader;
 mod gbuffer;
 mod light_volume;
+mod outline_renderer;
 mod particle_system_renderer;
+mod point_cloud_renderer;
 mod shadow_map_renderer;
 mod sprite_renderer;
 mod ssao;
 mod ui_renderer;
+mod water_renderer;
 
 use crate::{
     core::{
@@ -39,6 +44,7 @@ use crate::{
     engine::resource_manager::TimedEntry,
     gui::draw::DrawingContext,
     renderer::{
+        auto_exposure::{AutoExposureRenderContext, AutoExposureRenderer},
         debug_renderer::DebugRenderer,
         deferred_light_renderer::{DeferredLightRenderer, DeferredRendererContext},
         error::RendererError,
@@ -52,23 +58,33 @@ use crate::{
             gl,
             gpu_program::UniformValue,
             gpu_texture::{
-                GpuTexture, GpuTextureKind, MagnificationFilter, MininificationFilter, PixelKind,
+                Coordinate, GpuTexture, GpuTextureKind, MagnificationFilter, MininificationFilter,
+                PixelKind, WrapMode,
             },
             state::State,
         },
         gbuffer::{GBuffer, GBufferRenderContext},
+        outline_renderer::{OutlineRenderContext, OutlineRenderer},
         particle_system_renderer::{ParticleSystemRenderContext, ParticleSystemRenderer},
+        point_cloud_renderer::{PointCloudRenderContext, PointCloudRenderer},
         sprite_renderer::{SpriteRenderContext, SpriteRenderer},
         surface::SurfaceSharedData,
         ui_renderer::{UiRenderContext, UiRenderer},
+        water_renderer::{WaterRenderContext, WaterRenderer},
     },
-    resource::texture::{Texture, TextureKind},
-    scene::{node::Node, SceneContainer},
+    resource::{
+        basis::{self, GpuCompressionSupport},
+        cube_map::CubeMap,
+        texture::{Texture, TextureKind, TextureSampler},
+    },
+    scene::{camera::ClearMode, node::Node, terrain::Terrain, SceneContainer},
+    utils::log::{Log, LogCategory},
 };
 use glutin::PossiblyCurrent;
 use std::{
     cell::RefCell,
     collections::HashMap,
+    ffi::CStr,
     rc::Rc,
     sync::{Arc, Mutex},
     time,
@@ -163,6 +179,10 @@ pub struct QualitySettings {
     /// Global switch to enable or disable light scattering. Each light can have
     /// its own scatter switch, but this one is able to globally disable scatter.
     pub light_scatter_enabled: bool,
+
+    /// Whether to render planar reflections of water surfaces or not. Each reflection
+    /// requires additional pass over the scene (in half resolution) for each camera.
+    pub water_reflections_enabled: bool,
 }
 
 impl Default for QualitySettings {
@@ -182,6 +202,8 @@ impl Default for QualitySettings {
             ssao_radius: 0.5,
 
             light_scatter_enabled: true,
+
+            water_reflections_enabled: true,
         }
     }
 }
@@ -243,6 +265,10 @@ pub struct Renderer {
     flat_shader: FlatShader,
     sprite_renderer: SpriteRenderer,
     particle_system_renderer: ParticleSystemRenderer,
+    point_cloud_renderer: PointCloudRenderer,
+    water_renderer: WaterRenderer,
+    outline_renderer: OutlineRenderer,
+    auto_exposure_renderer: AutoExposureRenderer,
     /// Dummy white one pixel texture which will be used as stub when rendering
     /// something without texture specified.
     white_dummy: Rc<RefCell<GpuTexture>>,
@@ -253,15 +279,69 @@ pub struct Renderer {
     statistics: Statistics,
     quad: SurfaceSharedData,
     frame_size: (u32, u32),
-    ambient_color: Color,
     quality_settings: QualitySettings,
     /// Debug renderer instance can be used for debugging purposes
     pub debug_renderer: DebugRenderer,
+    // Transient storage for geometry of custom nodes, it is cleared for each camera.
+    custom_node_renderer: DebugRenderer,
     /// Camera to G-buffer mapping.
     gbuffers: HashMap<Handle<Node>, GBuffer>,
+    /// Camera to G-buffer mapping for reflections of water surfaces.
+    reflection_gbuffers: HashMap<Handle<Node>, GBuffer>,
     backbuffer_clear_color: Color,
     texture_cache: TextureCache,
+    cube_map_cache: CubeMapCache,
     geometry_cache: GeometryCache,
+    morph_cache: MorphTargetCache,
+    terrain_cache: TerrainCache,
+}
+
+/// Creates static geometry buffer with standard vertex layout and uploads given surface
+/// data into it.
+fn create_surface_geometry_buffer(
+    state: &mut State,
+    data: &SurfaceSharedData,
+) -> GeometryBuffer<surface::Vertex> {
+    let geometry_buffer =
+        GeometryBuffer::new(GeometryBufferKind::StaticDraw, ElementKind::Triangle);
+
+    geometry_buffer
+        .bind(state)
+        .describe_attributes(vec![
+            AttributeDefinition {
+                kind: AttributeKind::Float3,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::Float2,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::Float2,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::Float3,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::Float4,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::Float4,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::UnsignedByte4,
+                normalized: false,
+            },
+        ])
+        .unwrap()
+        .set_vertices(data.vertices.as_slice())
+        .set_triangles(data.triangles());
+
+    geometry_buffer
 }
 
 #[derive(Default)]
@@ -279,54 +359,247 @@ impl GeometryCache {
 
         let key = (data as *const _) as usize;
 
-        let geometry_buffer = self.map.entry(key).or_insert_with(|| {
-            let geometry_buffer =
-                GeometryBuffer::new(GeometryBufferKind::StaticDraw, ElementKind::Triangle);
+        let geometry_buffer = self.map.entry(key).or_insert_with(|| TimedEntry {
+            value: create_surface_geometry_buffer(state, data),
+            time_to_live: 20.0,
+        });
 
-            geometry_buffer
-                .bind(state)
-                .describe_attributes(vec![
-                    AttributeDefinition {
-                        kind: AttributeKind::Float3,
-                        normalized: false,
-                    },
-                    AttributeDefinition {
-                        kind: AttributeKind::Float2,
-                        normalized: false,
-                    },
-                    AttributeDefinition {
-                        kind: AttributeKind::Float2,
-                        normalized: false,
-                    },
-                    AttributeDefinition {
-                        kind: AttributeKind::Float3,
-                        normalized: false,
-                    },
-                    AttributeDefinition {
-                        kind: AttributeKind::Float4,
-                        normalized: false,
-                    },
-                    AttributeDefinition {
-                        kind: AttributeKind::Float4,
-                        normalized: false,
-                    },
-                    AttributeDefinition {
-                        kind: AttributeKind::UnsignedByte4,
-                        normalized: false,
+        geometry_buffer.time_to_live = 20.0;
+        geometry_buffer
+    }
+
+    fn update(&mut self, dt: f32) {
+        for entry in self.map.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.map.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+    }
+}
+
+struct MorphTexture {
+    texture: Rc<RefCell<GpuTexture>>,
+    target_count: usize,
+}
+
+/// Width of texture with morph target deltas. Deltas are stored row by row, so texture
+/// does not hit texture size limits when mesh has lots of vertices.
+pub(in crate) const MORPH_TEXTURE_WIDTH: usize = 2048;
+
+/// Keeps textures with deltas of morph targets of surfaces. Deltas are stored as
+/// [target0 positions, target0 normals, target1 positions, ...], each block has one
+/// texel per vertex.
+#[derive(Default)]
+pub(in crate) struct MorphTargetCache {
+    map: HashMap<usize, TimedEntry<MorphTexture>>,
+}
+
+impl MorphTargetCache {
+    fn get(
+        &mut self,
+        state: &mut State,
+        data: &SurfaceSharedData,
+    ) -> Option<Rc<RefCell<GpuTexture>>> {
+        scope_profile!();
+
+        let morph_targets = data.morph_targets();
+        if morph_targets.is_empty() {
+            return None;
+        }
+
+        let key = (data as *const _) as usize;
+
+        // Morph targets can be added after data was uploaded to GPU.
+        if let Some(entry) = self.map.get(&key) {
+            if entry.target_count != morph_targets.len() {
+                self.map.remove(&key);
+            }
+        }
+
+        if !self.map.contains_key(&key) {
+            let texel_count = morph_targets.len() * 2 * data.get_vertices().len();
+            let height = (texel_count + MORPH_TEXTURE_WIDTH - 1) / MORPH_TEXTURE_WIDTH;
+            let mut bytes = Vec::with_capacity(MORPH_TEXTURE_WIDTH * height * 12);
+            for morph_target in morph_targets {
+                for delta in morph_target
+                    .position_deltas
+                    .iter()
+                    .chain(morph_target.normal_deltas.iter())
+                {
+                    for component in [delta.x, delta.y, delta.z].iter() {
+                        bytes.extend_from_slice(&component.to_ne_bytes());
+                    }
+                }
+            }
+            // Pad last row.
+            bytes.resize(MORPH_TEXTURE_WIDTH * height * 12, 0);
+
+            let texture = match GpuTexture::new(
+                state,
+                GpuTextureKind::Rectangle {
+                    width: MORPH_TEXTURE_WIDTH,
+                    height,
+                },
+                PixelKind::RGB32F,
+                Some(&bytes),
+            ) {
+                Ok(texture) => texture,
+                Err(e) => {
+                    Log::err(
+                        LogCategory::Renderer,
+                        format!("Unable to upload morph targets! Reason: {:?}", e),
+                    );
+                    return None;
+                }
+            };
+
+            self.map.insert(
+                key,
+                TimedEntry {
+                    value: MorphTexture {
+                        texture: Rc::new(RefCell::new(texture)),
+                        target_count: morph_targets.len(),
                     },
-                ])
-                .unwrap()
-                .set_vertices(data.vertices.as_slice())
-                .set_triangles(data.triangles());
+                    time_to_live: 20.0,
+                },
+            );
+        }
 
-            TimedEntry {
-                value: geometry_buffer,
+        let entry = self.map.get_mut(&key).unwrap();
+        entry.time_to_live = 20.0;
+        Some(entry.texture.clone())
+    }
+
+    fn update(&mut self, dt: f32) {
+        for entry in self.map.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.map.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+    }
+}
+
+struct TerrainChunkGeometry {
+    version: u64,
+    lods: Vec<Option<GeometryBuffer<surface::Vertex>>>,
+}
+
+/// GPU data of a terrain: geometry for levels of detail of each chunk and splat map.
+pub(in crate) struct TerrainGpuData {
+    chunks: Vec<TerrainChunkGeometry>,
+    splat_map: Rc<RefCell<GpuTexture>>,
+    splat_map_version: u64,
+}
+
+fn create_splat_map_texture(
+    state: &mut State,
+    terrain: &Terrain,
+) -> Result<Rc<RefCell<GpuTexture>>, RendererError> {
+    let (width, height) = terrain.resolution();
+    let mut texture = GpuTexture::new(
+        state,
+        GpuTextureKind::Rectangle { width, height },
+        PixelKind::RGBA8,
+        Some(terrain.splat_map()),
+    )?;
+    texture
+        .bind_mut(state, 0)
+        .set_minification_filter(MininificationFilter::Linear)
+        .set_magnification_filter(MagnificationFilter::Linear)
+        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+    Ok(Rc::new(RefCell::new(texture)))
+}
+
+impl TerrainGpuData {
+    pub(in crate) fn splat_map(&self) -> Rc<RefCell<GpuTexture>> {
+        self.splat_map.clone()
+    }
+
+    /// Returns geometry of given chunk at given level of detail, geometry is regenerated
+    /// if chunk was modified.
+    pub(in crate) fn chunk_geometry(
+        &mut self,
+        state: &mut State,
+        terrain: &Terrain,
+        index: usize,
+        lod: usize,
+    ) -> &mut GeometryBuffer<surface::Vertex> {
+        let chunk = &terrain.chunks()[index];
+        let geometry = &mut self.chunks[index];
+        if geometry.version != chunk.version {
+            geometry.version = chunk.version;
+            geometry.lods.clear();
+        }
+        if geometry.lods.len() <= lod {
+            geometry.lods.resize_with(lod + 1, || None);
+        }
+        geometry.lods[lod].get_or_insert_with(|| {
+            create_surface_geometry_buffer(state, &terrain.build_chunk_geometry(chunk, lod))
+        })
+    }
+}
+
+/// Keeps GPU data of terrains. Terrains are identified by unique id, so modified terrains
+/// are detected by versions of their chunks and splat map.
+#[derive(Default)]
+pub(in crate) struct TerrainCache {
+    map: HashMap<u64, TimedEntry<TerrainGpuData>>,
+}
+
+impl TerrainCache {
+    fn get(&mut self, state: &mut State, terrain: &Terrain) -> Option<&mut TerrainGpuData> {
+        scope_profile!();
+
+        let key = terrain.id();
+
+        let is_outdated = match self.map.get(&key) {
+            Some(entry) => entry.splat_map_version != terrain.splat_map_version(),
+            None => true,
+        };
+
+        if is_outdated {
+            let splat_map = match create_splat_map_texture(state, terrain) {
+                Ok(splat_map) => splat_map,
+                Err(e) => {
+                    Log::err(
+                        LogCategory::Renderer,
+                        format!("Unable to upload terrain splat map! Reason: {:?}", e),
+                    );
+                    return None;
+                }
+            };
+            let entry = self.map.entry(key).or_insert_with(|| TimedEntry {
+                value: TerrainGpuData {
+                    chunks: Default::default(),
+                    splat_map: splat_map.clone(),
+                    splat_map_version: 0,
+                },
                 time_to_live: 20.0,
-            }
-        });
+            });
+            entry.splat_map = splat_map;
+            entry.splat_map_version = terrain.splat_map_version();
+        }
 
-        geometry_buffer.time_to_live = 20.0;
-        geometry_buffer
+        let entry = self.map.get_mut(&key).unwrap();
+        entry.time_to_live = 20.0;
+        if entry.chunks.len() != terrain.chunks().len() {
+            entry.chunks = terrain
+                .chunks()
+                .iter()
+                .map(|_| TerrainChunkGeometry {
+                    version: 0,
+                    lods: Default::default(),
+                })
+                .collect();
+        }
+        Some(&mut entry.value)
     }
 
     fn update(&mut self, dt: f32) {
@@ -341,9 +614,98 @@ impl GeometryCache {
     }
 }
 
+struct CachedTexture {
+    gpu_texture: Rc<RefCell<GpuTexture>>,
+    // Sampler which was applied to GPU texture, None for render targets which have their own
+    // settings.
+    sampler: Option<TextureSampler>,
+    has_mips: bool,
+    srgb: bool,
+    revision: u64,
+}
+
 #[derive(Default)]
 pub(in crate) struct TextureCache {
-    map: HashMap<usize, TimedEntry<Rc<RefCell<GpuTexture>>>>,
+    map: HashMap<usize, TimedEntry<CachedTexture>>,
+}
+
+fn apply_sampler(
+    state: &mut State,
+    gpu_texture: &mut GpuTexture,
+    sampler: TextureSampler,
+    has_mips: bool,
+) {
+    // Mip filter on texture without mips makes texture incomplete, so it'd be black.
+    let min_filter = if has_mips {
+        sampler.min_filter
+    } else {
+        sampler.min_filter.without_mip_maps()
+    };
+    gpu_texture
+        .bind_mut(state, 0)
+        .set_minification_filter(min_filter.into())
+        .set_magnification_filter(sampler.mag_filter.into())
+        .set_wrap(Coordinate::S, sampler.s_wrap_mode.into())
+        .set_wrap(Coordinate::T, sampler.t_wrap_mode.into())
+        .set_anisotropy(sampler.anisotropy);
+}
+
+/// Checks extensions of current OpenGL context for compressed texture formats.
+fn query_gpu_compression_support() -> GpuCompressionSupport {
+    let mut support = GpuCompressionSupport::default();
+    unsafe {
+        let mut count = 0;
+        gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count);
+        for i in 0..count.max(0) as u32 {
+            let name = gl::GetStringi(gl::EXTENSIONS, i);
+            if name.is_null() {
+                continue;
+            }
+            match CStr::from_ptr(name as *const _).to_bytes() {
+                b"GL_EXT_texture_compression_s3tc" => support.s3tc = true,
+                b"GL_ARB_texture_compression_bptc" => support.bptc = true,
+                _ => (),
+            }
+        }
+    }
+    support
+}
+
+fn upload_texture(state: &mut State, texture: &Texture) -> CachedTexture {
+    let kind = GpuTextureKind::Rectangle {
+        width: texture.width as usize,
+        height: texture.height as usize,
+    };
+    let pixel_kind = if texture.srgb {
+        PixelKind::from(texture.kind).to_srgb()
+    } else {
+        PixelKind::from(texture.kind)
+    };
+    let mut gpu_texture = GpuTexture::new_with_mips(
+        state,
+        kind,
+        pixel_kind,
+        texture.mip_count as usize,
+        Some(texture.bytes.as_slice()),
+    )
+    .unwrap();
+    // Mip levels are generated only for uncompressed textures without mips, compressed
+    // data must be uploaded as is.
+    let generate_mips = texture.mip_count == 1
+        && !texture.kind.is_compressed()
+        && texture.sampler.generate_mip_maps;
+    if generate_mips {
+        gpu_texture.bind_mut(state, 0).generate_mip_maps();
+    }
+    let has_mips = generate_mips || texture.mip_count > 1;
+    apply_sampler(state, &mut gpu_texture, texture.sampler, has_mips);
+    CachedTexture {
+        gpu_texture: Rc::new(RefCell::new(gpu_texture)),
+        sampler: Some(texture.sampler),
+        has_mips,
+        srgb: texture.srgb,
+        revision: texture.revision,
+    }
 }
 
 impl TextureCache {
@@ -354,35 +716,35 @@ impl TextureCache {
     ) -> Option<Rc<RefCell<GpuTexture>>> {
         scope_profile!();
 
-        if texture.lock().unwrap().loaded {
-            let key = (&*texture as *const _) as usize;
-            let gpu_texture = self.map.entry(key).or_insert_with(move || {
-                let texture = texture.lock().unwrap();
-                let kind = GpuTextureKind::Rectangle {
-                    width: texture.width as usize,
-                    height: texture.height as usize,
-                };
-                let mut gpu_texture = GpuTexture::new(
-                    state,
-                    kind,
-                    PixelKind::from(texture.kind),
-                    Some(texture.bytes.as_slice()),
-                )
-                .unwrap();
-                gpu_texture
-                    .bind_mut(state, 0)
-                    .generate_mip_maps()
-                    .set_minification_filter(MininificationFilter::LinearMip)
-                    .set_magnification_filter(MagnificationFilter::Linear)
-                    .set_max_anisotropy();
-                TimedEntry {
-                    value: Rc::new(RefCell::new(gpu_texture)),
-                    time_to_live: 20.0,
-                }
+        let key = (&*texture as *const _) as usize;
+        let texture = texture.lock().unwrap();
+        if texture.loaded {
+            let entry = self.map.entry(key).or_insert_with(|| TimedEntry {
+                value: upload_texture(state, &texture),
+                time_to_live: 20.0,
             });
+            if let Some(sampler) = entry.sampler {
+                if sampler.generate_mip_maps != texture.sampler.generate_mip_maps
+                    || entry.srgb != texture.srgb
+                    || entry.revision != texture.revision
+                {
+                    // Mip maps must be generated or removed, pixel format must be changed or
+                    // pixels were modified, so texture is uploaded again.
+                    entry.value = upload_texture(state, &texture);
+                } else if sampler != texture.sampler {
+                    let has_mips = entry.has_mips;
+                    apply_sampler(
+                        state,
+                        &mut entry.gpu_texture.borrow_mut(),
+                        texture.sampler,
+                        has_mips,
+                    );
+                    entry.sampler = Some(texture.sampler);
+                }
+            }
             // Texture won't be destroyed while it used.
-            gpu_texture.time_to_live = 20.0;
-            Some(gpu_texture.value.clone())
+            entry.time_to_live = 20.0;
+            Some(entry.gpu_texture.clone())
         } else {
             None
         }
@@ -395,6 +757,79 @@ impl TextureCache {
         self.map.retain(|_, v| v.time_to_live > 0.0);
     }
 
+    fn remove(&mut self, texture: &Arc<Mutex<Texture>>) {
+        let key = (&**texture as *const _) as usize;
+        self.map.remove(&key);
+    }
+
+    fn clear(&mut self) {
+        self.map.clear();
+    }
+}
+
+#[derive(Default)]
+pub(in crate) struct CubeMapCache {
+    map: HashMap<usize, TimedEntry<Rc<RefCell<GpuTexture>>>>,
+}
+
+fn upload_cube_map(state: &mut State, cube_map: &CubeMap) -> Rc<RefCell<GpuTexture>> {
+    let kind = GpuTextureKind::Cube {
+        width: cube_map.size as usize,
+        height: cube_map.size as usize,
+    };
+    let pixel_kind = if cube_map.srgb {
+        PixelKind::from(cube_map.kind).to_srgb()
+    } else {
+        PixelKind::from(cube_map.kind)
+    };
+    let mut gpu_texture =
+        GpuTexture::new(state, kind, pixel_kind, Some(cube_map.bytes.as_slice())).unwrap();
+    gpu_texture
+        .bind_mut(state, 0)
+        .generate_mip_maps()
+        .set_minification_filter(MininificationFilter::LinearMip)
+        .set_magnification_filter(MagnificationFilter::Linear)
+        .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::T, WrapMode::ClampToEdge)
+        .set_wrap(Coordinate::R, WrapMode::ClampToEdge);
+    Rc::new(RefCell::new(gpu_texture))
+}
+
+impl CubeMapCache {
+    fn get(
+        &mut self,
+        state: &mut State,
+        cube_map: Arc<Mutex<CubeMap>>,
+    ) -> Option<Rc<RefCell<GpuTexture>>> {
+        scope_profile!();
+
+        let key = (&*cube_map as *const _) as usize;
+        let cube_map = cube_map.lock().unwrap();
+        if cube_map.is_loaded() {
+            let entry = self.map.entry(key).or_insert_with(|| TimedEntry {
+                value: upload_cube_map(state, &cube_map),
+                time_to_live: 20.0,
+            });
+            // Cube map won't be destroyed while it used.
+            entry.time_to_live = 20.0;
+            Some(entry.value.clone())
+        } else {
+            None
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        for entry in self.map.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.map.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    fn remove(&mut self, cube_map: &Arc<Mutex<CubeMap>>) {
+        let key = (&**cube_map as *const _) as usize;
+        self.map.remove(&key);
+    }
+
     fn clear(&mut self) {
         self.map.clear();
     }
@@ -407,6 +842,19 @@ impl Renderer {
     ) -> Result<Self, RendererError> {
         gl::load_with(|symbol| context.get_proc_address(symbol) as *const _);
 
+        // Lighting is done in linear color space, so values written to sRGB render targets
+        // (frame textures and back buffer) must be converted to sRGB color space. It does not
+        // affect render targets with linear formats.
+        unsafe {
+            gl::Enable(gl::FRAMEBUFFER_SRGB);
+            // Filtering of cube maps must take texels of adjacent faces into account, otherwise
+            // edges of faces are visible on sky.
+            gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+        }
+
+        // Basis textures are transcoded to the best format which is supported by GPU.
+        basis::set_gpu_compression_support(query_gpu_compression_support());
+
         let settings = QualitySettings::default();
         let mut state = State::new();
 
@@ -416,7 +864,7 @@ impl Renderer {
             deferred_light_renderer: DeferredLightRenderer::new(&mut state, frame_size, &settings)?,
             flat_shader: FlatShader::new()?,
             statistics: Statistics::default(),
-            sprite_renderer: SpriteRenderer::new()?,
+            sprite_renderer: SpriteRenderer::new(&mut state)?,
             white_dummy: Rc::new(RefCell::new(GpuTexture::new(
                 &mut state,
                 GpuTextureKind::Rectangle {
@@ -438,27 +886,25 @@ impl Renderer {
             quad: SurfaceSharedData::make_unit_xy_quad(),
             ui_renderer: UiRenderer::new(&mut state)?,
             particle_system_renderer: ParticleSystemRenderer::new(&mut state)?,
-            ambient_color: Color::opaque(100, 100, 100),
+            point_cloud_renderer: PointCloudRenderer::new()?,
+            water_renderer: WaterRenderer::new(&mut state)?,
+            outline_renderer: OutlineRenderer::new()?,
+            auto_exposure_renderer: AutoExposureRenderer::new(&mut state)?,
             quality_settings: settings,
             debug_renderer: DebugRenderer::new(&mut state)?,
+            custom_node_renderer: DebugRenderer::new(&mut state)?,
             gbuffers: Default::default(),
+            reflection_gbuffers: Default::default(),
             backbuffer_clear_color: Color::from_rgba(0, 0, 0, 0),
             texture_cache: Default::default(),
+            cube_map_cache: Default::default(),
             geometry_cache: Default::default(),
+            morph_cache: Default::default(),
+            terrain_cache: Default::default(),
             state,
         })
     }
 
-    /// Sets new ambient color. Ambient color is used to imitate ambient lighting.
-    pub fn set_ambient_color(&mut self, color: Color) {
-        self.ambient_color = color;
-    }
-
-    /// Returns current ambient color.
-    pub fn get_ambient_color(&self) -> Color {
-        self.ambient_color
-    }
-
     /// Returns statistics for last frame.
     pub fn get_statistics(&self) -> Statistics {
         self.statistics
@@ -469,6 +915,17 @@ impl Renderer {
         self.backbuffer_clear_color = color;
     }
 
+    /// Sets thickness (in pixels) of outline around nodes with outline color set.
+    /// See `Base::set_outline` for more info.
+    pub fn set_outline_thickness(&mut self, thickness: u32) {
+        self.outline_renderer.set_thickness(thickness);
+    }
+
+    /// Returns current thickness of outline in pixels.
+    pub fn outline_thickness(&self) -> u32 {
+        self.outline_renderer.thickness()
+    }
+
     /// Sets new frame size, should be called when received a Resize event.
     ///
     /// # Notes
@@ -483,6 +940,9 @@ impl Renderer {
         self.frame_size.1 = new_size.1.max(1);
         // Invalidate all g-buffers.
         self.gbuffers.clear();
+        self.reflection_gbuffers.clear();
+        self.water_renderer.clear();
+        self.auto_exposure_renderer.clear();
     }
 
     /// Returns current (width, height) pair of back buffer size.
@@ -512,13 +972,89 @@ impl Renderer {
     /// performance lag!
     pub fn flush(&mut self) {
         self.texture_cache.clear();
+        self.cube_map_cache.clear();
         self.geometry_cache.clear();
+        self.morph_cache.clear();
+        self.terrain_cache.clear();
+        self.point_cloud_renderer.clear();
+        self.deferred_light_renderer.clear();
+    }
+
+    /// Removes GPU copy of given texture, so it will be uploaded again when it is used next
+    /// time. It is used when contents of a texture were changed, for example by hot reload.
+    pub fn invalidate_texture(&mut self, texture: &Arc<Mutex<Texture>>) {
+        self.texture_cache.remove(texture);
+    }
+
+    /// Removes GPU copy of given cube map. It is used when a cube map is unloaded by resource
+    /// manager.
+    pub fn invalidate_cube_map(&mut self, cube_map: &Arc<Mutex<CubeMap>>) {
+        self.cube_map_cache.remove(cube_map);
+    }
+
+    /// Renders a frame without presenting it on screen and returns its pixels as RGBA8 texture
+    /// in sRGB color space, rows are stored from top to bottom. It is intended for screenshots,
+    /// photo modes and automated rendering tests, use `Engine::capture_frame` to capture current
+    /// scenes and user interfaces. Captured frame can be saved using `Texture::save`.
+    ///
+    /// # Notes
+    ///
+    /// Pixels are read from GPU synchronously, so it stalls CPU until GPU has finished the
+    /// frame. Don't call this method every frame.
+    pub fn capture_frame(
+        &mut self,
+        scenes: &SceneContainer,
+        drawing_context: &DrawingContext,
+        viewport_drawing_contexts: &[(Rect<i32>, &DrawingContext)],
+    ) -> Result<Texture, RendererError> {
+        scope_profile!();
+
+        self.render_frame(scenes, drawing_context, viewport_drawing_contexts, 0.0)?;
+
+        let (width, height) = self.frame_size;
+        let row_size = width as usize * 4;
+        let mut pixels = vec![0u8; row_size * height as usize];
+        self.state.set_framebuffer(self.backbuffer.id());
+        unsafe {
+            gl::ReadBuffer(gl::BACK);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+        check_gl_error!();
+
+        // OpenGL stores rows from bottom to top.
+        let mut bytes = Vec::with_capacity(pixels.len());
+        for row in pixels.chunks(row_size).rev() {
+            bytes.extend_from_slice(row);
+        }
+
+        Ok(Texture {
+            path: Default::default(),
+            width,
+            height,
+            bytes,
+            mip_count: 1,
+            kind: TextureKind::RGBA8,
+            loaded: true,
+            sampler: Default::default(),
+            srgb: true,
+            revision: 0,
+        })
     }
 
     fn render_frame(
         &mut self,
         scenes: &SceneContainer,
         drawing_context: &DrawingContext,
+        viewport_drawing_contexts: &[(Rect<i32>, &DrawingContext)],
         dt: f32,
     ) -> Result<(), RendererError> {
         scope_profile!();
@@ -532,6 +1068,11 @@ impl Renderer {
         // Update caches - this will remove timed out resources.
         self.geometry_cache.update(dt);
         self.texture_cache.update(dt);
+        self.cube_map_cache.update(dt);
+        self.morph_cache.update(dt);
+        self.terrain_cache.update(dt);
+        self.point_cloud_renderer.update(dt);
+        self.deferred_light_renderer.update(dt);
 
         self.statistics.begin_frame();
 
@@ -550,32 +1091,112 @@ impl Renderer {
         for scene in scenes.iter() {
             let graph = &scene.graph;
 
-            for (camera_handle, camera) in graph.pair_iter().filter_map(|(handle, node)| {
-                if let Node::Camera(camera) = node {
-                    Some((handle, camera))
-                } else {
+            let mut cameras = graph
+                .pair_iter()
+                .filter_map(|(handle, node)| {
+                    if let Node::Camera(camera) = node {
+                        if camera.is_enabled() && camera.is_globally_enabled() {
+                            return Some((handle, camera));
+                        }
+                    }
                     None
-                }
-            }) {
-                if !camera.is_enabled() {
-                    continue;
-                }
+                })
+                .collect::<Vec<_>>();
+            // Sort is stable, so cameras with same render order will keep their order.
+            cameras.sort_by_key(|(_, camera)| camera.render_order());
 
+            for (camera_handle, camera) in cameras {
                 let viewport = camera.viewport_pixels(Vec2::new(frame_width, frame_height));
 
+                // Automatic exposure works only when scene is rendered on screen, render
+                // targets are always in low dynamic range.
+                let auto_exposure = if scene.render_target.is_none() {
+                    camera.auto_exposure()
+                } else {
+                    None
+                };
+                let hdr = auto_exposure.is_some();
+
+                let culled = scene.portals.culled_nodes(graph, camera);
+
                 let state = &mut self.state;
+
+                // Reflection of water is rendered first by mirrored camera, water renderer
+                // will use its frame later on.
+                let reflection_plane = if self.quality_settings.water_reflections_enabled {
+                    water_renderer::reflection_plane(graph, camera, &culled)
+                } else {
+                    None
+                };
+                let reflection = if let Some(plane) = reflection_plane {
+                    let reflection_camera = camera.make_reflection(plane);
+                    let width = (viewport.w / 2).max(1);
+                    let height = (viewport.h / 2).max(1);
+                    let reflection_gbuffer = self
+                        .reflection_gbuffers
+                        .entry(camera_handle)
+                        .and_modify(|buf| {
+                            if buf.width != width || buf.height != height || buf.hdr != hdr {
+                                *buf = GBuffer::new(state, width as usize, height as usize, hdr)
+                                    .unwrap();
+                            }
+                        })
+                        .or_insert_with(|| {
+                            GBuffer::new(state, width as usize, height as usize, hdr).unwrap()
+                        });
+
+                    self.statistics += reflection_gbuffer.fill(GBufferRenderContext {
+                        state,
+                        graph,
+                        camera: &reflection_camera,
+                        white_dummy: self.white_dummy.clone(),
+                        normal_dummy: self.normal_dummy.clone(),
+                        texture_cache: &mut self.texture_cache,
+                        geom_cache: &mut self.geometry_cache,
+                        morph_cache: &mut self.morph_cache,
+                        terrain_cache: &mut self.terrain_cache,
+                        culled: &culled,
+                    });
+
+                    // Screen space effects are sized for full frame and barely visible in
+                    // distorted reflection anyway.
+                    let reflection_settings = QualitySettings {
+                        use_ssao: false,
+                        light_scatter_enabled: false,
+                        ..self.quality_settings
+                    };
+
+                    self.statistics += self
+                        .deferred_light_renderer
+                        .render(DeferredRendererContext {
+                            state,
+                            scene,
+                            camera: &reflection_camera,
+                            gbuffer: reflection_gbuffer,
+                            white_dummy: self.white_dummy.clone(),
+                            settings: &reflection_settings,
+                            textures: &mut self.texture_cache,
+                            cube_maps: &mut self.cube_map_cache,
+                            geometry_cache: &mut self.geometry_cache,
+                        });
+
+                    Some(reflection_gbuffer.frame_texture())
+                } else {
+                    None
+                };
                 let gbuffer = self
                     .gbuffers
                     .entry(camera_handle)
                     .and_modify(|buf| {
-                        if buf.width != viewport.w || buf.height != viewport.h {
+                        if buf.width != viewport.w || buf.height != viewport.h || buf.hdr != hdr {
                             let width = (viewport.w as usize).max(1);
                             let height = (viewport.h as usize).max(1);
-                            *buf = GBuffer::new(state, width, height).unwrap();
+                            *buf = GBuffer::new(state, width, height, hdr).unwrap();
                         }
                     })
                     .or_insert_with(|| {
-                        GBuffer::new(state, viewport.w as usize, viewport.h as usize).unwrap()
+                        GBuffer::new(state, viewport.w as usize, viewport.h as usize, hdr)
+                            .unwrap()
                     });
 
                 // If we specified a texture to draw to, we have to register it in texture cache
@@ -588,7 +1209,13 @@ impl Renderer {
                     self.texture_cache.map.insert(
                         key,
                         TimedEntry {
-                            value: gbuffer.frame_texture(),
+                            value: CachedTexture {
+                                gpu_texture: gbuffer.frame_texture(),
+                                sampler: None,
+                                has_mips: false,
+                                srgb: false,
+                                revision: 0,
+                            },
                             time_to_live: std::f32::INFINITY,
                         },
                     );
@@ -610,6 +1237,9 @@ impl Renderer {
                     normal_dummy: self.normal_dummy.clone(),
                     texture_cache: &mut self.texture_cache,
                     geom_cache: &mut self.geometry_cache,
+                    morph_cache: &mut self.morph_cache,
+                    terrain_cache: &mut self.terrain_cache,
+                    culled: &culled,
                 });
 
                 self.statistics += self
@@ -620,12 +1250,35 @@ impl Renderer {
                         camera,
                         gbuffer,
                         white_dummy: self.white_dummy.clone(),
-                        ambient_color: self.ambient_color,
                         settings: &self.quality_settings,
                         textures: &mut self.texture_cache,
+                        cube_maps: &mut self.cube_map_cache,
                         geometry_cache: &mut self.geometry_cache,
                     });
 
+                self.statistics += self.point_cloud_renderer.render(PointCloudRenderContext {
+                    state,
+                    framebuffer: &mut gbuffer.final_frame,
+                    graph,
+                    camera,
+                    viewport,
+                    culled: &culled,
+                })?;
+
+                self.statistics += self.water_renderer.render(WaterRenderContext {
+                    state,
+                    gbuffer,
+                    graph,
+                    camera,
+                    viewport,
+                    culled: &culled,
+                    reflection,
+                    white_dummy: self.white_dummy.clone(),
+                    normal_dummy: self.normal_dummy.clone(),
+                    textures: &mut self.texture_cache,
+                    geom_cache: &mut self.geometry_cache,
+                })?;
+
                 let depth = gbuffer.depth();
 
                 self.statistics +=
@@ -641,6 +1294,7 @@ impl Renderer {
                             frame_height,
                             viewport,
                             texture_cache: &mut self.texture_cache,
+                            culled: &culled,
                         });
 
                 self.statistics += self.sprite_renderer.render(SpriteRenderContext {
@@ -651,60 +1305,133 @@ impl Renderer {
                     white_dummy: self.white_dummy.clone(),
                     viewport,
                     textures: &mut self.texture_cache,
-                    geom_map: &mut self.geometry_cache,
+                    culled: &culled,
+                })?;
+
+                self.statistics += self.outline_renderer.render(OutlineRenderContext {
+                    state,
+                    framebuffer: &mut gbuffer.final_frame,
+                    graph,
+                    camera,
+                    viewport,
+                    geom_cache: &mut self.geometry_cache,
                 });
 
+                self.custom_node_renderer.clear_lines();
+                for (handle, node) in graph.pair_iter() {
+                    if let Node::Custom(custom) = node {
+                        if custom.global_visibility()
+                            && camera.sees_layers(custom.global_layers())
+                            && !culled.contains(&handle)
+                        {
+                            custom.render(camera, &mut self.custom_node_renderer);
+                        }
+                    }
+                }
+                self.statistics += self.custom_node_renderer.render(
+                    state,
+                    viewport,
+                    &mut gbuffer.final_frame,
+                    camera,
+                );
+
                 self.statistics +=
                     self.debug_renderer
                         .render(state, viewport, &mut gbuffer.final_frame, camera);
 
                 // Finally render everything into back buffer.
                 if scene.render_target.is_none() {
-                    self.statistics.geometry += self.backbuffer.draw(
-                        self.geometry_cache.get(state, &self.quad),
-                        state,
-                        viewport,
-                        &self.flat_shader.program,
-                        DrawParameters {
-                            cull_face: CullFace::Back,
-                            culling: false,
-                            color_write: Default::default(),
-                            depth_write: true,
-                            stencil_test: false,
-                            depth_test: false,
-                            blend: false,
-                        },
-                        &[
-                            (
-                                self.flat_shader.wvp_matrix,
-                                UniformValue::Mat4({
-                                    Mat4::ortho(
-                                        0.0,
-                                        viewport.w as f32,
-                                        viewport.h as f32,
-                                        0.0,
-                                        -1.0,
-                                        1.0,
-                                    ) * Mat4::scale(Vec3::new(
-                                        viewport.w as f32,
-                                        viewport.h as f32,
-                                        0.0,
-                                    ))
-                                }),
-                            ),
-                            (
-                                self.flat_shader.diffuse_texture,
-                                UniformValue::Sampler {
-                                    index: 0,
-                                    texture: gbuffer.frame_texture(),
-                                },
-                            ),
-                        ],
-                    );
+                    let blend = match camera.clear_mode() {
+                        ClearMode::Overwrite => false,
+                        ClearMode::Color(color) => {
+                            self.backbuffer
+                                .clear(state, viewport, Some(color), None, None);
+                            true
+                        }
+                        ClearMode::Transparent => true,
+                    };
+
+                    if let Some(auto_exposure) = auto_exposure {
+                        self.statistics +=
+                            self.auto_exposure_renderer
+                                .render(AutoExposureRenderContext {
+                                    state,
+                                    backbuffer: &mut self.backbuffer,
+                                    viewport,
+                                    frame_texture: gbuffer.frame_texture(),
+                                    camera_handle,
+                                    settings: auto_exposure,
+                                    dt,
+                                    blend,
+                                    white_dummy: self.white_dummy.clone(),
+                                    texture_cache: &mut self.texture_cache,
+                                    geom_cache: &mut self.geometry_cache,
+                                })?;
+                    } else {
+                        if blend {
+                            state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                        }
+
+                        self.statistics.geometry += self.backbuffer.draw(
+                            self.geometry_cache.get(state, &self.quad),
+                            state,
+                            viewport,
+                            &self.flat_shader.program,
+                            DrawParameters {
+                                cull_face: CullFace::Back,
+                                culling: false,
+                                color_write: Default::default(),
+                                depth_write: true,
+                                stencil_test: false,
+                                depth_test: false,
+                                blend,
+                            },
+                            &[
+                                (
+                                    self.flat_shader.wvp_matrix,
+                                    UniformValue::Mat4({
+                                        Mat4::ortho(
+                                            0.0,
+                                            viewport.w as f32,
+                                            viewport.h as f32,
+                                            0.0,
+                                            -1.0,
+                                            1.0,
+                                        ) * Mat4::scale(Vec3::new(
+                                            viewport.w as f32,
+                                            viewport.h as f32,
+                                            0.0,
+                                        ))
+                                    }),
+                                ),
+                                (
+                                    self.flat_shader.diffuse_texture,
+                                    UniformValue::Sampler {
+                                        index: 0,
+                                        texture: gbuffer.frame_texture(),
+                                    },
+                                ),
+                            ],
+                        );
+                    }
                 }
             }
         }
 
+        // Render viewport user interfaces, each in its own viewport.
+        for &(viewport, drawing_context) in viewport_drawing_contexts {
+            self.statistics += self.ui_renderer.render(UiRenderContext {
+                state: &mut self.state,
+                viewport,
+                backbuffer: &mut self.backbuffer,
+                frame_width: viewport.w as f32,
+                frame_height: viewport.h as f32,
+                drawing_context,
+                white_dummy: self.white_dummy.clone(),
+                texture_cache: &mut self.texture_cache,
+            })?;
+        }
+
         // Render UI on top of everything.
         self.statistics += self.ui_renderer.render(UiRenderContext {
             state: &mut self.state,
@@ -724,12 +1451,13 @@ impl Renderer {
         &mut self,
         scenes: &SceneContainer,
         drawing_context: &DrawingContext,
+        viewport_drawing_contexts: &[(Rect<i32>, &DrawingContext)],
         context: &glutin::WindowedContext<PossiblyCurrent>,
         dt: f32,
     ) -> Result<(), RendererError> {
         scope_profile!();
 
-        self.render_frame(scenes, drawing_context, dt)?;
+        self.render_frame(scenes, drawing_context, viewport_drawing_contexts, dt)?;
 
         self.statistics.end_frame();
         context.swap_buffers()?;
@@ -0,0 +1,328 @@
+//! Outline renderer draws colored outline around nodes which have outline color set.
+//!
+//! Outline is made in two steps: at first every outlined mesh is drawn into separate
+//! mask texture using its outline color, then mask is dilated in full screen pass and
+//! only pixels around covered ones are written to the frame.
+
+use crate::{
+    core::{
+        color::Color,
+        math::{mat4::Mat4, vec2::Vec2, vec3::Vec3, Rect},
+        scope_profile,
+    },
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{
+                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+            },
+            gl,
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::{Coordinate, GpuTexture, GpuTextureKind, PixelKind, WrapMode},
+            state::State,
+        },
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics,
+    },
+    scene::{camera::Camera, graph::Graph, node::Node},
+    utils::log::{Log, LogCategory},
+};
+use std::{cell::RefCell, rc::Rc};
+
+struct MaskShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    use_skeletal_animation: UniformLocation,
+    bone_matrices: UniformLocation,
+    outline_color: UniformLocation,
+}
+
+impl MaskShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/outline_mask_fs.glsl");
+        let vertex_source = include_str!("shaders/outline_mask_vs.glsl");
+        let program = GpuProgram::from_source("OutlineMaskShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            use_skeletal_animation: program.uniform_location("useSkeletalAnimation")?,
+            bone_matrices: program.uniform_location("boneMatrices")?,
+            outline_color: program.uniform_location("outlineColor")?,
+            program,
+        })
+    }
+}
+
+struct OutlineShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    mask_texture: UniformLocation,
+    inverse_size: UniformLocation,
+    thickness: UniformLocation,
+}
+
+impl OutlineShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/outline_fs.glsl");
+        let vertex_source = include_str!("shaders/flat_vs.glsl");
+        let program = GpuProgram::from_source("OutlineShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            mask_texture: program.uniform_location("maskTexture")?,
+            inverse_size: program.uniform_location("inverseSize")?,
+            thickness: program.uniform_location("thickness")?,
+            program,
+        })
+    }
+}
+
+struct Mask {
+    framebuffer: FrameBuffer,
+    width: i32,
+    height: i32,
+}
+
+impl Mask {
+    fn new(state: &mut State, width: usize, height: usize) -> Result<Self, RendererError> {
+        let mut texture = GpuTexture::new(
+            state,
+            GpuTextureKind::Rectangle { width, height },
+            PixelKind::RGBA8,
+            None,
+        )?;
+        texture
+            .bind_mut(state, 0)
+            .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+            .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+
+        Ok(Self {
+            framebuffer: FrameBuffer::new(
+                state,
+                None,
+                vec![Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: Rc::new(RefCell::new(texture)),
+                }],
+            )?,
+            width: width as i32,
+            height: height as i32,
+        })
+    }
+
+    fn texture(&self) -> Rc<RefCell<GpuTexture>> {
+        self.framebuffer.color_attachments()[0].texture.clone()
+    }
+}
+
+pub struct OutlineRenderer {
+    mask_shader: MaskShader,
+    outline_shader: OutlineShader,
+    mask: Option<Mask>,
+    quad: SurfaceSharedData,
+    bone_matrices: Vec<Mat4>,
+    thickness: u32,
+}
+
+pub(in crate) struct OutlineRenderContext<'a, 'b, 'c> {
+    pub state: &'a mut State,
+    pub framebuffer: &'b mut FrameBuffer,
+    pub graph: &'c Graph,
+    pub camera: &'c Camera,
+    pub viewport: Rect<i32>,
+    pub geom_cache: &'a mut GeometryCache,
+}
+
+impl OutlineRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            mask_shader: MaskShader::new()?,
+            outline_shader: OutlineShader::new()?,
+            mask: None,
+            quad: SurfaceSharedData::make_unit_xy_quad(),
+            bone_matrices: Vec::new(),
+            thickness: 2,
+        })
+    }
+
+    pub fn set_thickness(&mut self, thickness: u32) {
+        self.thickness = thickness.max(1);
+    }
+
+    pub fn thickness(&self) -> u32 {
+        self.thickness
+    }
+
+    #[must_use]
+    pub(in crate) fn render(&mut self, args: OutlineRenderContext) -> RenderPassStatistics {
+        scope_profile!();
+
+        let mut statistics = RenderPassStatistics::default();
+
+        let OutlineRenderContext {
+            state,
+            framebuffer,
+            graph,
+            camera,
+            viewport,
+            geom_cache,
+        } = args;
+
+        // Fast path - most of the time nothing is outlined.
+        if !graph.linear_iter().any(|node| {
+            if let Node::Mesh(mesh) = node {
+                mesh.outline().is_some()
+                    && mesh.global_visibility()
+                    && camera.sees_layers(mesh.global_layers())
+            } else {
+                false
+            }
+        }) {
+            return statistics;
+        }
+
+        let width = (viewport.w as usize).max(1);
+        let height = (viewport.h as usize).max(1);
+        let mask = match self.mask.take() {
+            Some(mask) if mask.width == width as i32 && mask.height == height as i32 => mask,
+            _ => match Mask::new(state, width, height) {
+                Ok(mask) => mask,
+                Err(e) => {
+                    Log::err(
+                        LogCategory::Renderer,
+                        format!("Unable to create outline mask! Reason: {:?}", e),
+                    );
+                    return statistics;
+                }
+            },
+        };
+        let mask = self.mask.get_or_insert(mask);
+
+        let mask_viewport = Rect::new(0, 0, mask.width, mask.height);
+        mask.framebuffer.clear(
+            state,
+            mask_viewport,
+            Some(Color::from_rgba(0, 0, 0, 0)),
+            None,
+            None,
+        );
+
+        let frustum = camera.frustum();
+
+        for mesh in graph.linear_iter().filter_map(|node| {
+            if let Node::Mesh(mesh) = node {
+                Some(mesh)
+            } else {
+                None
+            }
+        }) {
+            let outline_color = match mesh.outline() {
+                Some(color) => color,
+                None => continue,
+            };
+
+            if !mesh.global_visibility()
+                || !camera.sees_layers(mesh.global_layers())
+                || !mesh.is_intersect_frustum(graph, &frustum)
+            {
+                continue;
+            }
+
+            for surface in mesh.surfaces().iter() {
+                let is_skinned = !surface.bones.is_empty();
+
+                let world = if is_skinned {
+                    Mat4::IDENTITY
+                } else {
+                    mesh.global_transform()
+                };
+
+                statistics += mask.framebuffer.draw(
+                    geom_cache.get(state, &surface.data().lock().unwrap()),
+                    state,
+                    mask_viewport,
+                    &self.mask_shader.program,
+                    DrawParameters {
+                        cull_face: CullFace::Back,
+                        culling: true,
+                        color_write: Default::default(),
+                        depth_write: false,
+                        stencil_test: false,
+                        depth_test: false,
+                        blend: false,
+                    },
+                    &[
+                        (
+                            self.mask_shader.wvp_matrix,
+                            UniformValue::Mat4(camera.view_projection_matrix() * world),
+                        ),
+                        (
+                            self.mask_shader.use_skeletal_animation,
+                            UniformValue::Bool(is_skinned),
+                        ),
+                        (
+                            self.mask_shader.outline_color,
+                            UniformValue::Color(outline_color),
+                        ),
+                        (
+                            self.mask_shader.bone_matrices,
+                            UniformValue::Mat4Array({
+                                self.bone_matrices.clear();
+                                for &bone_handle in surface.bones.iter() {
+                                    let bone_node = &graph[bone_handle];
+                                    self.bone_matrices.push(
+                                        bone_node.global_transform()
+                                            * bone_node.inv_bind_pose_transform(),
+                                    );
+                                }
+                                &self.bone_matrices
+                            }),
+                        ),
+                    ],
+                );
+            }
+        }
+
+        state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        statistics += framebuffer.draw(
+            geom_cache.get(state, &self.quad),
+            state,
+            mask_viewport,
+            &self.outline_shader.program,
+            DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: true,
+            },
+            &[
+                (
+                    self.outline_shader.wvp_matrix,
+                    UniformValue::Mat4(
+                        Mat4::ortho(0.0, width as f32, height as f32, 0.0, -1.0, 1.0)
+                            * Mat4::scale(Vec3::new(width as f32, height as f32, 0.0)),
+                    ),
+                ),
+                (
+                    self.outline_shader.mask_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: mask.texture(),
+                    },
+                ),
+                (
+                    self.outline_shader.inverse_size,
+                    UniformValue::Vec2(Vec2::new(1.0 / width as f32, 1.0 / height as f32)),
+                ),
+                (
+                    self.outline_shader.thickness,
+                    UniformValue::Integer(self.thickness as i32),
+                ),
+            ],
+        );
+
+        statistics
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     core::{
         math::{vec2::Vec2, Rect},
+        pool::Handle,
         scope_profile,
     },
     renderer::{
@@ -17,9 +18,14 @@ use crate::{
         },
         RenderPassStatistics, TextureCache,
     },
-    scene::{camera::Camera, graph::Graph, node::Node, particle_system},
+    scene::{
+        camera::Camera,
+        graph::Graph,
+        node::Node,
+        particle_system::{self, RenderMode},
+    },
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
 
 struct ParticleSystemShader {
     program: GpuProgram,
@@ -53,10 +59,42 @@ impl ParticleSystemShader {
     }
 }
 
+struct PointSpriteShader {
+    program: GpuProgram,
+    view_projection_matrix: UniformLocation,
+    world_matrix: UniformLocation,
+    point_scale: UniformLocation,
+    diffuse_texture: UniformLocation,
+    depth_buffer_texture: UniformLocation,
+    inv_screen_size: UniformLocation,
+    proj_params: UniformLocation,
+}
+
+impl PointSpriteShader {
+    fn new() -> Result<Self, RendererError> {
+        let vertex_source = include_str!("shaders/particle_point_vs.glsl");
+        let fragment_source = include_str!("shaders/particle_point_fs.glsl");
+        let program =
+            GpuProgram::from_source("PointSpriteShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            view_projection_matrix: program.uniform_location("viewProjectionMatrix")?,
+            world_matrix: program.uniform_location("worldMatrix")?,
+            point_scale: program.uniform_location("pointScale")?,
+            diffuse_texture: program.uniform_location("diffuseTexture")?,
+            depth_buffer_texture: program.uniform_location("depthBufferTexture")?,
+            inv_screen_size: program.uniform_location("invScreenSize")?,
+            proj_params: program.uniform_location("projParams")?,
+            program,
+        })
+    }
+}
+
 pub struct ParticleSystemRenderer {
     shader: ParticleSystemShader,
+    point_sprite_shader: PointSpriteShader,
     draw_data: particle_system::DrawData,
     geometry_buffer: GeometryBuffer<particle_system::Vertex>,
+    point_buffer: GeometryBuffer<particle_system::Vertex>,
     sorted_particles: Vec<u32>,
 }
 
@@ -71,40 +109,54 @@ pub(in crate) struct ParticleSystemRenderContext<'a, 'b, 'c> {
     pub frame_height: f32,
     pub viewport: Rect<i32>,
     pub texture_cache: &'a mut TextureCache,
+    pub culled: &'c HashSet<Handle<Node>>,
+}
+
+fn describe_vertex(
+    geometry_buffer: &GeometryBuffer<particle_system::Vertex>,
+    state: &mut State,
+) -> Result<(), RendererError> {
+    geometry_buffer.bind(state).describe_attributes(vec![
+        AttributeDefinition {
+            kind: AttributeKind::Float3,
+            normalized: false,
+        },
+        AttributeDefinition {
+            kind: AttributeKind::Float2,
+            normalized: false,
+        },
+        AttributeDefinition {
+            kind: AttributeKind::Float,
+            normalized: false,
+        },
+        AttributeDefinition {
+            kind: AttributeKind::Float,
+            normalized: false,
+        },
+        AttributeDefinition {
+            kind: AttributeKind::UnsignedByte4,
+            normalized: true,
+        },
+    ])?;
+    Ok(())
 }
 
 impl ParticleSystemRenderer {
     pub fn new(state: &mut State) -> Result<Self, RendererError> {
         let geometry_buffer =
             GeometryBuffer::new(GeometryBufferKind::DynamicDraw, ElementKind::Triangle);
+        describe_vertex(&geometry_buffer, state)?;
 
-        geometry_buffer.bind(state).describe_attributes(vec![
-            AttributeDefinition {
-                kind: AttributeKind::Float3,
-                normalized: false,
-            },
-            AttributeDefinition {
-                kind: AttributeKind::Float2,
-                normalized: false,
-            },
-            AttributeDefinition {
-                kind: AttributeKind::Float,
-                normalized: false,
-            },
-            AttributeDefinition {
-                kind: AttributeKind::Float,
-                normalized: false,
-            },
-            AttributeDefinition {
-                kind: AttributeKind::UnsignedByte4,
-                normalized: true,
-            },
-        ])?;
+        let point_buffer =
+            GeometryBuffer::new(GeometryBufferKind::DynamicDraw, ElementKind::Point);
+        describe_vertex(&point_buffer, state)?;
 
         Ok(Self {
             shader: ParticleSystemShader::new()?,
+            point_sprite_shader: PointSpriteShader::new()?,
             draw_data: Default::default(),
             geometry_buffer,
+            point_buffer,
             sorted_particles: Vec::new(),
         })
     }
@@ -126,6 +178,7 @@ impl ParticleSystemRenderer {
             frame_height,
             viewport,
             texture_cache,
+            culled,
         } = args;
 
         state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
@@ -135,69 +188,35 @@ impl ParticleSystemRenderer {
         let camera_up = inv_view.up();
         let camera_side = inv_view.side();
 
-        for node in graph.linear_iter() {
+        for (handle, node) in graph.pair_iter() {
             let particle_system = if let Node::ParticleSystem(particle_system) = node {
                 particle_system
             } else {
                 continue;
             };
 
+            if !particle_system.is_globally_enabled()
+                || !camera.sees_layers(particle_system.global_layers())
+                || culled.contains(&handle)
+            {
+                continue;
+            }
+
             particle_system.generate_draw_data(
                 &mut self.sorted_particles,
                 &mut self.draw_data,
                 &camera.global_position(),
             );
 
-            self.geometry_buffer
-                .bind(state)
-                .set_triangles(self.draw_data.triangles())
-                .set_vertices(self.draw_data.vertices());
-
-            let uniforms = [
-                (
-                    self.shader.depth_buffer_texture,
-                    UniformValue::Sampler {
-                        index: 0,
-                        texture: depth.clone(),
-                    },
-                ),
-                (
-                    self.shader.diffuse_texture,
-                    UniformValue::Sampler {
-                        index: 1,
-                        texture: if let Some(texture) = particle_system.texture() {
-                            if let Some(texture) = texture_cache.get(state, texture) {
-                                texture
-                            } else {
-                                white_dummy.clone()
-                            }
-                        } else {
-                            white_dummy.clone()
-                        },
-                    },
-                ),
-                (
-                    self.shader.camera_side_vector,
-                    UniformValue::Vec3(camera_side),
-                ),
-                (self.shader.camera_up_vector, UniformValue::Vec3(camera_up)),
-                (
-                    self.shader.view_projection_matrix,
-                    UniformValue::Mat4(camera.view_projection_matrix()),
-                ),
-                (
-                    self.shader.world_matrix,
-                    UniformValue::Mat4(node.global_transform()),
-                ),
-                (
-                    self.shader.inv_screen_size,
-                    UniformValue::Vec2(Vec2::new(1.0 / frame_width, 1.0 / frame_height)),
-                ),
-                (
-                    self.shader.proj_params,
-                    UniformValue::Vec2(Vec2::new(camera.z_far(), camera.z_near())),
-                ),
-            ];
+            let diffuse_texture = if let Some(texture) = particle_system.texture() {
+                if let Some(texture) = texture_cache.get(state, texture) {
+                    texture
+                } else {
+                    white_dummy.clone()
+                }
+            } else {
+                white_dummy.clone()
+            };
 
             let draw_params = DrawParameters {
                 cull_face: CullFace::Front,
@@ -209,14 +228,118 @@ impl ParticleSystemRenderer {
                 blend: true,
             };
 
-            statistics += framebuffer.draw(
-                &self.geometry_buffer,
-                state,
-                viewport,
-                &self.shader.program,
-                draw_params,
-                &uniforms,
-            );
+            match particle_system.render_mode() {
+                RenderMode::Billboards => {
+                    self.geometry_buffer
+                        .bind(state)
+                        .set_triangles(self.draw_data.triangles())
+                        .set_vertices(self.draw_data.vertices());
+
+                    let uniforms = [
+                        (
+                            self.shader.depth_buffer_texture,
+                            UniformValue::Sampler {
+                                index: 0,
+                                texture: depth.clone(),
+                            },
+                        ),
+                        (
+                            self.shader.diffuse_texture,
+                            UniformValue::Sampler {
+                                index: 1,
+                                texture: diffuse_texture,
+                            },
+                        ),
+                        (
+                            self.shader.camera_side_vector,
+                            UniformValue::Vec3(camera_side),
+                        ),
+                        (self.shader.camera_up_vector, UniformValue::Vec3(camera_up)),
+                        (
+                            self.shader.view_projection_matrix,
+                            UniformValue::Mat4(camera.view_projection_matrix()),
+                        ),
+                        (
+                            self.shader.world_matrix,
+                            UniformValue::Mat4(node.global_transform()),
+                        ),
+                        (
+                            self.shader.inv_screen_size,
+                            UniformValue::Vec2(Vec2::new(1.0 / frame_width, 1.0 / frame_height)),
+                        ),
+                        (
+                            self.shader.proj_params,
+                            UniformValue::Vec2(Vec2::new(camera.z_far(), camera.z_near())),
+                        ),
+                    ];
+
+                    statistics += framebuffer.draw(
+                        &self.geometry_buffer,
+                        state,
+                        viewport,
+                        &self.shader.program,
+                        draw_params,
+                        &uniforms,
+                    );
+                }
+                RenderMode::PointSprites => {
+                    self.point_buffer
+                        .bind(state)
+                        .set_points(self.draw_data.points())
+                        .set_vertices(self.draw_data.vertices());
+
+                    let shader = &self.point_sprite_shader;
+
+                    // Scale factor to convert size of particle in world units into size of
+                    // point in pixels, it will be divided by depth in vertex shader.
+                    let point_scale = camera.projection_matrix().f[5] * viewport.h as f32;
+
+                    let uniforms = [
+                        (
+                            shader.depth_buffer_texture,
+                            UniformValue::Sampler {
+                                index: 0,
+                                texture: depth.clone(),
+                            },
+                        ),
+                        (
+                            shader.diffuse_texture,
+                            UniformValue::Sampler {
+                                index: 1,
+                                texture: diffuse_texture,
+                            },
+                        ),
+                        (
+                            shader.view_projection_matrix,
+                            UniformValue::Mat4(camera.view_projection_matrix()),
+                        ),
+                        (
+                            shader.world_matrix,
+                            UniformValue::Mat4(node.global_transform()),
+                        ),
+                        (shader.point_scale, UniformValue::Float(point_scale)),
+                        (
+                            shader.inv_screen_size,
+                            UniformValue::Vec2(Vec2::new(1.0 / frame_width, 1.0 / frame_height)),
+                        ),
+                        (
+                            shader.proj_params,
+                            UniformValue::Vec2(Vec2::new(camera.z_far(), camera.z_near())),
+                        ),
+                    ];
+
+                    state.set_program_point_size(true);
+
+                    statistics += framebuffer.draw(
+                        &self.point_buffer,
+                        state,
+                        viewport,
+                        &shader.program,
+                        draw_params,
+                        &uniforms,
+                    );
+                }
+            }
         }
 
         statistics
@@ -0,0 +1,184 @@
+use crate::{
+    core::{math::Rect, pool::Handle, scope_profile},
+    engine::resource_manager::TimedEntry,
+    renderer::{
+        error::RendererError,
+        framework::{
+            framebuffer::{CullFace, DrawParameters, FrameBuffer, FrameBufferTrait},
+            geometry_buffer::{
+                AttributeDefinition, AttributeKind, ElementKind, GeometryBuffer, GeometryBufferKind,
+            },
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            state::State,
+        },
+        RenderPassStatistics,
+    },
+    scene::{camera::Camera, graph::Graph, node::Node, point_cloud::CloudPoint},
+};
+use std::collections::{HashMap, HashSet};
+
+struct PointCloudShader {
+    program: GpuProgram,
+    wvp_matrix: UniformLocation,
+    point_size: UniformLocation,
+}
+
+impl PointCloudShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/point_cloud_fs.glsl");
+        let vertex_source = include_str!("shaders/point_cloud_vs.glsl");
+        let program =
+            GpuProgram::from_source("PointCloudShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            wvp_matrix: program.uniform_location("worldViewProjection")?,
+            point_size: program.uniform_location("pointSize")?,
+            program,
+        })
+    }
+}
+
+fn describe_vertex(
+    geometry_buffer: &GeometryBuffer<CloudPoint>,
+    state: &mut State,
+) -> Result<(), RendererError> {
+    geometry_buffer.bind(state).describe_attributes(vec![
+        AttributeDefinition {
+            kind: AttributeKind::Float3,
+            normalized: false,
+        },
+        AttributeDefinition {
+            kind: AttributeKind::UnsignedByte4,
+            normalized: true,
+        },
+    ])?;
+    Ok(())
+}
+
+pub struct PointCloudRenderer {
+    shader: PointCloudShader,
+    // Buffers are keyed by revisions of point clouds, see `PointCloud::revision`.
+    buffers: HashMap<u64, TimedEntry<GeometryBuffer<CloudPoint>>>,
+    indices: Vec<u32>,
+}
+
+pub(in crate) struct PointCloudRenderContext<'a, 'b, 'c> {
+    pub state: &'a mut State,
+    pub framebuffer: &'b mut FrameBuffer,
+    pub graph: &'c Graph,
+    pub camera: &'c Camera,
+    pub viewport: Rect<i32>,
+    pub culled: &'c HashSet<Handle<Node>>,
+}
+
+impl PointCloudRenderer {
+    pub fn new() -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: PointCloudShader::new()?,
+            buffers: Default::default(),
+            indices: Default::default(),
+        })
+    }
+
+    pub(in crate) fn render(
+        &mut self,
+        args: PointCloudRenderContext,
+    ) -> Result<RenderPassStatistics, RendererError> {
+        scope_profile!();
+
+        let mut statistics = RenderPassStatistics::default();
+
+        let PointCloudRenderContext {
+            state,
+            framebuffer,
+            graph,
+            camera,
+            viewport,
+            culled,
+        } = args;
+
+        let view_projection = camera.view_projection_matrix();
+
+        for (handle, node) in graph.pair_iter() {
+            let cloud = if let Node::PointCloud(cloud) = node {
+                cloud
+            } else {
+                continue;
+            };
+
+            if cloud.points().is_empty()
+                || !cloud.global_visibility()
+                || !camera.sees_layers(cloud.global_layers())
+                || culled.contains(&handle)
+            {
+                continue;
+            }
+
+            let revision = cloud.revision();
+            if !self.buffers.contains_key(&revision) {
+                let buffer =
+                    GeometryBuffer::new(GeometryBufferKind::StaticDraw, ElementKind::Point);
+                describe_vertex(&buffer, state)?;
+
+                self.indices.clear();
+                self.indices.extend(0..cloud.points().len() as u32);
+                buffer
+                    .bind(state)
+                    .set_vertices(cloud.points())
+                    .set_points(&self.indices);
+
+                self.buffers.insert(
+                    revision,
+                    TimedEntry {
+                        value: buffer,
+                        time_to_live: 0.0,
+                    },
+                );
+            }
+
+            let entry = self.buffers.get_mut(&revision).unwrap();
+            // Buffers of clouds that weren't rendered for some time will be destroyed.
+            entry.time_to_live = 20.0;
+
+            state.set_program_point_size(true);
+
+            statistics += framebuffer.draw(
+                &entry.value,
+                state,
+                viewport,
+                &self.shader.program,
+                DrawParameters {
+                    cull_face: CullFace::Back,
+                    culling: false,
+                    color_write: Default::default(),
+                    depth_write: true,
+                    stencil_test: false,
+                    depth_test: true,
+                    blend: false,
+                },
+                &[
+                    (
+                        self.shader.wvp_matrix,
+                        UniformValue::Mat4(view_projection * cloud.global_transform()),
+                    ),
+                    (
+                        self.shader.point_size,
+                        UniformValue::Float(cloud.point_size()),
+                    ),
+                ],
+            );
+        }
+
+        Ok(statistics)
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for entry in self.buffers.values_mut() {
+            entry.time_to_live -= dt;
+        }
+        self.buffers.retain(|_, v| v.time_to_live > 0.0);
+    }
+
+    pub fn clear(&mut self) {
+        self.buffers.clear();
+    }
+}
@@ -1,5 +1,8 @@
 #version 330 core
 
+// Must be in sync with MAX_EMISSION_STRENGTH in surface.rs and g-buffer shader.
+const float maxEmissionStrength = 4.0;
+
 uniform sampler2D diffuseTexture;
 uniform sampler2D aoSampler;
 uniform sampler2D ambientTexture;
@@ -11,7 +14,11 @@ in vec2 texCoord;
 void main()
 {
     float ambientOcclusion =  texture(aoSampler, texCoord).r;
-    FragColor = ambientColor * texture(diffuseTexture, texCoord) ;
-    FragColor.rgb *= texture(ambientTexture, texCoord).rgb;
+    vec4 diffuse = texture(diffuseTexture, texCoord);
+    vec4 ambient = texture(ambientTexture, texCoord);
+    FragColor = ambientColor * diffuse;
+    FragColor.rgb *= ambient.rgb;
     FragColor.rgb *= ambientOcclusion;
-}
\ No newline at end of file
+    // Emission does not depend on lighting, so it is added after occlusion.
+    FragColor.rgb += diffuse.rgb * ambient.a * maxEmissionStrength;
+}
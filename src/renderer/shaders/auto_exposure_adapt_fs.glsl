@@ -0,0 +1,47 @@
+#version 330 core
+
+uniform sampler2D histogram;
+uniform sampler2D previousExposure;
+uniform int binCount;
+uniform float minEv;
+uniform float maxEv;
+uniform float lowPercent;
+uniform float highPercent;
+uniform float adaptation;
+uniform bool firstFrame;
+
+out vec4 FragColor;
+
+in vec2 texCoord;
+
+void main()
+{
+    float total = 0.0;
+    for (int i = 0; i < binCount; ++i)
+    {
+        total += texelFetch(histogram, ivec2(i, 0), 0).r;
+    }
+
+    // Darkest and brightest samples are ignored, so small light sources or deep
+    // shadows won't make exposure jump.
+    float low = total * lowPercent;
+    float high = total * highPercent;
+    float accumulated = 0.0;
+    float sum = 0.0;
+    float count = 0.0;
+    for (int i = 0; i < binCount; ++i)
+    {
+        float value = texelFetch(histogram, ivec2(i, 0), 0).r;
+        float inRange = max(0.0, min(accumulated + value, high) - max(accumulated, low));
+        accumulated += value;
+        float ev = minEv + float(i) / float(binCount - 1) * (maxEv - minEv);
+        sum += ev * inRange;
+        count += inRange;
+    }
+
+    float previous = texelFetch(previousExposure, ivec2(0, 0), 0).r;
+    float target = count > 0.0 ? clamp(sum / count, minEv, maxEv) : previous;
+    float ev = firstFrame ? target : mix(previous, target, adaptation);
+
+    FragColor = vec4(ev, 0.0, 0.0, 0.0);
+}
@@ -0,0 +1,10 @@
+#version 330 core
+
+in float weight;
+
+out vec4 FragColor;
+
+void main()
+{
+    FragColor = vec4(weight, 0.0, 0.0, 0.0);
+}
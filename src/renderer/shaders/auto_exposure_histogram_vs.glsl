@@ -0,0 +1,24 @@
+#version 330 core
+
+layout(location = 0) in vec2 vertexTexCoord;
+
+uniform sampler2D frameTexture;
+uniform sampler2D meteringMask;
+uniform float minEv;
+uniform float maxEv;
+uniform int binCount;
+
+out float weight;
+
+void main()
+{
+    vec3 color = texture(frameTexture, vertexTexCoord).rgb;
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    float ev = clamp(log2(max(luminance, 0.00001)), minEv, maxEv);
+    float bin = floor((ev - minEv) / (maxEv - minEv) * float(binCount - 1) + 0.5);
+
+    // Each sample is a point which lands in the center of its bin.
+    gl_Position = vec4((bin + 0.5) / float(binCount) * 2.0 - 1.0, 0.0, 0.0, 1.0);
+    gl_PointSize = 1.0;
+    weight = texture(meteringMask, vertexTexCoord).r;
+}
@@ -0,0 +1,17 @@
+#version 330 core
+
+uniform sampler2D frameTexture;
+uniform sampler2D exposureTexture;
+uniform float keyValue;
+
+out vec4 FragColor;
+
+in vec2 texCoord;
+
+void main()
+{
+    vec4 frame = texture(frameTexture, texCoord);
+    float ev = texelFetch(exposureTexture, ivec2(0, 0), 0).r;
+    vec3 color = frame.rgb * keyValue / exp2(ev);
+    FragColor = vec4(vec3(1.0) - exp(-color), frame.a);
+}
@@ -0,0 +1,57 @@
+#version 330 core
+
+const float PI = 3.14159265359;
+
+uniform sampler2D depthTexture;
+uniform sampler2D skyTexture;
+uniform samplerCube skyCubeMap;
+uniform bool skyIsCubeMap;
+uniform mat4 invViewProj;
+uniform vec3 cameraPosition;
+uniform bool skyEnabled;
+uniform bool fogEnabled;
+uniform vec4 fogColor;
+uniform float fogStart;
+uniform float fogEnd;
+
+out vec4 FragColor;
+in vec2 texCoord;
+
+void main()
+{
+    float depth = texture(depthTexture, texCoord).r;
+    vec3 worldPosition = S_UnProject(vec3(texCoord, depth), invViewProj);
+
+    if (depth >= 1.0)
+    {
+        // Background, sky is not affected by fog.
+        if (!skyEnabled)
+        {
+            discard;
+        }
+
+        vec3 dir = normalize(worldPosition - cameraPosition);
+        if (skyIsCubeMap)
+        {
+            FragColor = vec4(texture(skyCubeMap, dir).rgb, 1.0);
+        }
+        else
+        {
+            // Sky texture is equirectangular, so direction is converted to spherical coordinates.
+            vec2 uv = vec2(atan(dir.z, dir.x) / (2.0 * PI) + 0.5,
+                           acos(clamp(dir.y, -1.0, 1.0)) / PI);
+            FragColor = vec4(texture(skyTexture, uv).rgb, 1.0);
+        }
+    }
+    else
+    {
+        if (!fogEnabled)
+        {
+            discard;
+        }
+
+        float distance = length(worldPosition - cameraPosition);
+        float factor = clamp((distance - fogStart) / max(fogEnd - fogStart, 0.0001), 0.0, 1.0);
+        FragColor = vec4(fogColor.rgb, fogColor.a * factor);
+    }
+}
@@ -4,11 +4,17 @@ layout(location = 0) out vec4 outColor;
 layout(location = 1) out vec4 outNormal;
 layout(location = 2) out vec4 outAmbient;
 
+// Must be in sync with MAX_EMISSION_STRENGTH in surface.rs and ambient light shader.
+const float maxEmissionStrength = 4.0;
+
 uniform sampler2D diffuseTexture;
 uniform sampler2D normalTexture;
 uniform sampler2D specularTexture;
 uniform sampler2D lightmapTexture;
 uniform vec4 diffuseColor;
+uniform float emissionStrength;
+uniform float dissolveThreshold;
+uniform vec2 uvOffset;
 
 in vec3 normal;
 in vec2 texCoord;
@@ -16,14 +22,32 @@ in vec3 tangent;
 in vec3 binormal;
 in vec2 secondTexCoord;
 
+float Hash(vec2 p)
+{
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+float DissolveNoise(vec2 p)
+{
+    vec2 i = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+    return mix(mix(Hash(i), Hash(i + vec2(1.0, 0.0)), u.x),
+               mix(Hash(i + vec2(0.0, 1.0)), Hash(i + vec2(1.0, 1.0)), u.x), u.y);
+}
+
 void main()
 {
-    outColor = diffuseColor * texture(diffuseTexture, texCoord);
+    if (DissolveNoise(texCoord * 32.0) < dissolveThreshold) discard;
+    vec2 uv = texCoord + uvOffset;
+    outColor = S_SRGBToLinear(diffuseColor) * texture(diffuseTexture, uv);
     if (outColor.a < 0.5) discard;
     outColor.a = 1;
-    vec4 n = normalize(texture(normalTexture, texCoord) * 2.0 - 1.0);
+    vec4 n = normalize(texture(normalTexture, uv) * 2.0 - 1.0);
     mat3 tangentSpace = mat3(tangent, binormal, normal);
     outNormal.xyz = normalize(tangentSpace * n.xyz) * 0.5 + 0.5;
-    outNormal.w = texture(specularTexture, texCoord).r;
-    outAmbient = vec4(texture(lightmapTexture, secondTexCoord).rgb, 1.0);
+    outNormal.w = texture(specularTexture, uv).r;
+    // Emission strength is packed into alpha channel of ambient buffer.
+    outAmbient = vec4(texture(lightmapTexture, secondTexCoord).rgb,
+                      emissionStrength / maxEmissionStrength);
 }
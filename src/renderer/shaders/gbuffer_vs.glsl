@@ -12,6 +12,11 @@ uniform mat4 worldMatrix;
 uniform mat4 worldViewProjection;
 uniform bool useSkeletalAnimation;
 uniform mat4 boneMatrices[60];
+uniform sampler2D morphTexture;
+uniform int morphTargetCount;
+uniform int morphVertexCount;
+uniform int morphTargetIndices[4];
+uniform float morphTargetWeights[4];
 
 out vec3 normal;
 out vec2 texCoord;
@@ -19,14 +24,30 @@ out vec3 tangent;
 out vec3 binormal;
 out vec2 secondTexCoord;
 
+vec3 FetchMorphDelta(int index)
+{
+    int width = textureSize(morphTexture, 0).x;
+    return texelFetch(morphTexture, ivec2(index % width, index / width), 0).xyz;
+}
+
 void main()
 {
+    // Morph targets are applied before skinning, so skinning works with morphed shape.
+    vec3 morphedPosition = vertexPosition;
+    vec3 morphedNormal = vertexNormal;
+    for (int i = 0; i < morphTargetCount; ++i)
+    {
+        int base = morphTargetIndices[i] * 2 * morphVertexCount + gl_VertexID;
+        morphedPosition += FetchMorphDelta(base) * morphTargetWeights[i];
+        morphedNormal += FetchMorphDelta(base + morphVertexCount) * morphTargetWeights[i];
+    }
+
     vec4 localPosition = vec4(0);
     vec3 localNormal = vec3(0);
     vec3 localTangent = vec3(0);
     if (useSkeletalAnimation)
     {
-        vec4 vertex = vec4(vertexPosition, 1.0);
+        vec4 vertex = vec4(morphedPosition, 1.0);
 
         int i0 = int(boneIndices.x);
         int i1 = int(boneIndices.y);
@@ -38,10 +59,10 @@ void main()
         localPosition += boneMatrices[i2] * vertex * boneWeights.z;
         localPosition += boneMatrices[i3] * vertex * boneWeights.w;
 
-        localNormal += mat3(boneMatrices[i0]) * vertexNormal * boneWeights.x;
-        localNormal += mat3(boneMatrices[i1]) * vertexNormal * boneWeights.y;
-        localNormal += mat3(boneMatrices[i2]) * vertexNormal * boneWeights.z;
-        localNormal += mat3(boneMatrices[i3]) * vertexNormal * boneWeights.w;
+        localNormal += mat3(boneMatrices[i0]) * morphedNormal * boneWeights.x;
+        localNormal += mat3(boneMatrices[i1]) * morphedNormal * boneWeights.y;
+        localNormal += mat3(boneMatrices[i2]) * morphedNormal * boneWeights.z;
+        localNormal += mat3(boneMatrices[i3]) * morphedNormal * boneWeights.w;
 
         localTangent += mat3(boneMatrices[i0]) * vertexTangent.xyz * boneWeights.x;
         localTangent += mat3(boneMatrices[i1]) * vertexTangent.xyz * boneWeights.y;
@@ -50,8 +71,8 @@ void main()
     }
     else
     {
-        localPosition = vec4(vertexPosition, 1.0);
-        localNormal = vertexNormal;
+        localPosition = vec4(morphedPosition, 1.0);
+        localNormal = morphedNormal;
         localTangent = vertexTangent.xyz;
     }
     gl_Position = worldViewProjection * localPosition;
@@ -0,0 +1,59 @@
+#version 330 core
+
+// Must be in sync with MAX_EMISSION_STRENGTH in surface.rs and g-buffer shader.
+const float maxEmissionStrength = 4.0;
+
+uniform sampler2D depthTexture;
+uniform sampler2D diffuseTexture;
+uniform sampler2D normalTexture;
+uniform sampler2D aoSampler;
+uniform sampler2D ambientTexture;
+// Convolved spherical harmonics of each color channel, see
+// `SphericalHarmonics::irradiance_coefficients`.
+uniform sampler3D redCoefficients;
+uniform sampler3D greenCoefficients;
+uniform sampler3D blueCoefficients;
+uniform mat4 invViewProj;
+uniform mat4 invGridWorld;
+uniform vec3 halfExtents;
+uniform vec3 gridResolution;
+uniform float blendDistance;
+uniform float intensity;
+
+out vec4 FragColor;
+in vec2 texCoord;
+
+void main()
+{
+    vec3 worldPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
+    vec3 localPosition = (invGridWorld * vec4(worldPosition, 1.0)).xyz;
+
+    // Distance from fragment to box volume, zero inside of volume.
+    vec3 q = abs(localPosition) - halfExtents;
+    float outsideDistance = length(max(q, vec3(0.0)));
+    float weight = 1.0 - clamp(outsideDistance / max(blendDistance, 0.0001), 0.0, 1.0);
+    if (weight <= 0.0)
+    {
+        discard;
+    }
+
+    // Probes are in centers of texels, hardware trilinear filtering blends 8 closest ones.
+    vec3 t = clamp(localPosition / max(2.0 * halfExtents, vec3(0.0001)) + 0.5, 0.0, 1.0);
+    vec3 uvw = (0.5 + t * (gridResolution - 1.0)) / gridResolution;
+
+    vec3 n = normalize(texture(normalTexture, texCoord).xyz * 2.0 - 1.0);
+    vec4 basis = vec4(1.0, n.y, n.z, n.x);
+    vec3 irradiance = vec3(
+        dot(texture(redCoefficients, uvw), basis),
+        dot(texture(greenCoefficients, uvw), basis),
+        dot(texture(blueCoefficients, uvw), basis));
+    irradiance = max(irradiance, vec3(0.0)) * intensity;
+
+    // Same as in ambient light shader, but with lighting from probes.
+    float ambientOcclusion = texture(aoSampler, texCoord).r;
+    vec4 diffuse = texture(diffuseTexture, texCoord);
+    vec4 ambient = texture(ambientTexture, texCoord);
+    FragColor.rgb = irradiance * diffuse.rgb * ambient.rgb * ambientOcclusion;
+    FragColor.rgb += diffuse.rgb * ambient.a * maxEmissionStrength;
+    FragColor.a = weight;
+}
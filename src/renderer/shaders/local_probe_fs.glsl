@@ -0,0 +1,40 @@
+#version 330 core
+
+// Must be in sync with MAX_EMISSION_STRENGTH in surface.rs and g-buffer shader.
+const float maxEmissionStrength = 4.0;
+
+uniform sampler2D depthTexture;
+uniform sampler2D diffuseTexture;
+uniform sampler2D aoSampler;
+uniform sampler2D ambientTexture;
+uniform mat4 invViewProj;
+uniform mat4 invProbeWorld;
+uniform vec3 halfExtents;
+uniform float blendDistance;
+uniform vec4 probeColor;
+
+out vec4 FragColor;
+in vec2 texCoord;
+
+void main()
+{
+    vec3 worldPosition = S_UnProject(vec3(texCoord, texture(depthTexture, texCoord).r), invViewProj);
+    vec3 localPosition = (invProbeWorld * vec4(worldPosition, 1.0)).xyz;
+
+    // Distance from fragment to box volume, zero inside of volume.
+    vec3 q = abs(localPosition) - halfExtents;
+    float outsideDistance = length(max(q, vec3(0.0)));
+    float weight = 1.0 - clamp(outsideDistance / max(blendDistance, 0.0001), 0.0, 1.0);
+    if (weight <= 0.0)
+    {
+        discard;
+    }
+
+    // Same as in ambient light shader, but with color of probe.
+    float ambientOcclusion = texture(aoSampler, texCoord).r;
+    vec4 diffuse = texture(diffuseTexture, texCoord);
+    vec4 ambient = texture(ambientTexture, texCoord);
+    FragColor.rgb = probeColor.rgb * diffuse.rgb * ambient.rgb * ambientOcclusion;
+    FragColor.rgb += diffuse.rgb * ambient.a * maxEmissionStrength;
+    FragColor.a = weight;
+}
@@ -0,0 +1,39 @@
+#version 330 core
+
+uniform sampler2D maskTexture;
+uniform vec2 inverseSize;
+uniform int thickness;
+
+out vec4 FragColor;
+
+in vec2 texCoord;
+
+void main()
+{
+    // Pixels covered by outlined objects are left untouched.
+    if (texture(maskTexture, texCoord).a > 0.0) {
+        discard;
+    }
+
+    // Dilate mask - find nearest covered pixel around current one.
+    vec4 color = vec4(0.0);
+    float nearest = float(thickness * thickness) + 1.0;
+    for (int y = -thickness; y <= thickness; ++y) {
+        for (int x = -thickness; x <= thickness; ++x) {
+            float sqrDistance = float(x * x + y * y);
+            if (sqrDistance < nearest) {
+                vec4 neighbour = texture(maskTexture, texCoord + vec2(x, y) * inverseSize);
+                if (neighbour.a > 0.0) {
+                    nearest = sqrDistance;
+                    color = neighbour;
+                }
+            }
+        }
+    }
+
+    if (color.a == 0.0) {
+        discard;
+    }
+
+    FragColor = color;
+}
\ No newline at end of file
@@ -0,0 +1,10 @@
+#version 330 core
+
+uniform vec4 outlineColor;
+
+out vec4 FragColor;
+
+void main()
+{
+    FragColor = outlineColor;
+}
\ No newline at end of file
@@ -0,0 +1,34 @@
+#version 330 core
+
+uniform sampler2D diffuseTexture;
+uniform sampler2D depthBufferTexture;
+uniform vec2 invScreenSize;
+uniform vec2 projParams;
+
+out vec4 FragColor;
+in vec4 color;
+in float rotation;
+
+float toProjSpace(float z)
+{
+    float far = projParams.x;
+    float near = projParams.y;
+    return (far * near) / (far - z * (far + near));
+}
+
+vec2 rotateVec2(vec2 v, float angle)
+{
+    float c = cos(angle);
+    float s = sin(angle);
+    mat2 m = mat2(c, -s, s, c);
+    return m * v;
+}
+
+void main()
+{
+    vec2 texCoord = rotateVec2(gl_PointCoord - 0.5, rotation) + 0.5;
+    float sceneDepth = toProjSpace(texture(depthBufferTexture, gl_FragCoord.xy * invScreenSize).r);
+    float depthOpacity = clamp((sceneDepth - gl_FragCoord.z / gl_FragCoord.w) * 2.0f, 0.0, 1.0);
+    FragColor = color * texture(diffuseTexture, texCoord).r;
+    FragColor.a *= depthOpacity;
+}
\ No newline at end of file
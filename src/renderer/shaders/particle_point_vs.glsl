@@ -0,0 +1,22 @@
+#version 330 core
+
+layout(location = 0) in vec3 vertexPosition;
+layout(location = 2) in float particleSize;
+layout(location = 3) in float particleRotation;
+layout(location = 4) in vec4 vertexColor;
+
+uniform mat4 viewProjectionMatrix;
+uniform mat4 worldMatrix;
+uniform float pointScale;
+
+out vec4 color;
+out float rotation;
+
+void main()
+{
+    color = vertexColor;
+    rotation = particleRotation;
+    gl_Position = viewProjectionMatrix * worldMatrix * vec4(vertexPosition, 1.0);
+    // Point size is in pixels, so it must be attenuated by distance to camera.
+    gl_PointSize = particleSize * pointScale / gl_Position.w;
+}
\ No newline at end of file
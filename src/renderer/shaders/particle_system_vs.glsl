@@ -24,7 +24,7 @@ vec2 rotateVec2(vec2 v, float angle)
 
 void main()
 {
-    color = vertexColor;
+    color = S_SRGBToLinear(vertexColor);
     texCoord = vertexTexCoord;
     vec2 vertexOffset = rotateVec2(vertexTexCoord * 2.0 - 1.0, particleRotation);
     vec4 worldPosition = worldMatrix * vec4(vertexPosition, 1.0);
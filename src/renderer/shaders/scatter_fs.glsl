@@ -0,0 +1,49 @@
+#version 330 core
+
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec4 outNormal;
+layout(location = 2) out vec4 outAmbient;
+
+// Must be in sync with MAX_EMISSION_STRENGTH in surface.rs and ambient light shader.
+const float maxEmissionStrength = 4.0;
+
+uniform sampler2D diffuseTexture;
+uniform sampler2D normalTexture;
+uniform vec4 diffuseColor;
+uniform float emissionStrength;
+uniform vec2 uvOffset;
+
+in vec3 normal;
+in vec2 texCoord;
+in vec3 tangent;
+in vec3 binormal;
+in float fade;
+
+// Ordered dithering threshold, g-buffer has no blending so fading is done by discarding
+// more and more pixels.
+float BayerThreshold(vec2 fragCoord)
+{
+    const float matrix[16] = float[](
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0
+    );
+    ivec2 p = ivec2(mod(fragCoord, 4.0));
+    return (matrix[p.y * 4 + p.x] + 0.5) / 16.0;
+}
+
+void main()
+{
+    if (fade < BayerThreshold(gl_FragCoord.xy)) discard;
+    vec2 uv = texCoord + uvOffset;
+    outColor = diffuseColor * texture(diffuseTexture, uv);
+    if (outColor.a < 0.5) discard;
+    outColor.a = 1;
+    vec4 n = normalize(texture(normalTexture, uv) * 2.0 - 1.0);
+    mat3 tangentSpace = mat3(tangent, binormal, normal);
+    outNormal.xyz = normalize(tangentSpace * n.xyz) * 0.5 + 0.5;
+    outNormal.w = 0.0;
+    // Scatters have no lightmaps, ambient light is not modulated.
+    outAmbient = vec4(1.0, 1.0, 1.0, emissionStrength / maxEmissionStrength);
+}
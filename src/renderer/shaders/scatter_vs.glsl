@@ -0,0 +1,36 @@
+#version 330 core
+
+// Must be in sync with MAX_SCATTER_INSTANCES in gbuffer.rs.
+const int MAX_INSTANCES = 32;
+
+layout(location = 0) in vec3 vertexPosition;
+layout(location = 1) in vec2 vertexTexCoord;
+layout(location = 2) in vec2 vertexSecondTexCoord;
+layout(location = 3) in vec3 vertexNormal;
+layout(location = 4) in vec4 vertexTangent;
+
+uniform mat4 viewProjection;
+uniform mat4 instanceMatrices[MAX_INSTANCES];
+uniform vec3 cameraPosition;
+uniform float fadeStart;
+uniform float fadeEnd;
+
+out vec3 normal;
+out vec2 texCoord;
+out vec3 tangent;
+out vec3 binormal;
+out float fade;
+
+void main()
+{
+    mat4 worldMatrix = instanceMatrices[gl_InstanceID];
+    gl_Position = viewProjection * worldMatrix * vec4(vertexPosition, 1.0);
+    normal = normalize(mat3(worldMatrix) * vertexNormal);
+    tangent = normalize(mat3(worldMatrix) * vertexTangent.xyz);
+    binormal = normalize(vertexTangent.w * cross(tangent, normal));
+    texCoord = vertexTexCoord;
+
+    // Whole instance fades at once, so fade is calculated from its origin.
+    float distance = length(worldMatrix[3].xyz - cameraPosition);
+    fade = 1.0 - smoothstep(fadeStart, max(fadeEnd, fadeStart + 0.001), distance);
+}
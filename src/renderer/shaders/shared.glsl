@@ -149,4 +149,14 @@ bool S_RaySphereIntersection(vec3 origin, vec3 dir, vec3 center, float radius, o
     float b = 2.0 * dot(dir, d);
     float c = dot(d, d) - radius * radius;
     return S_SolveQuadraticEq(a, b, c, minT, maxT);
+}
+
+// Converts color from sRGB to linear color space. Colors which are set by user (vertex colors,
+// material colors, UI brushes) are in sRGB color space, but lighting and blending are done in
+// linear color space.
+vec4 S_SRGBToLinear(vec4 color)
+{
+    vec3 low = color.rgb / 12.92;
+    vec3 high = pow((color.rgb + 0.055) / 1.055, vec3(2.4));
+    return vec4(mix(high, low, vec3(lessThanEqual(color.rgb, vec3(0.04045)))), color.a);
 }
\ No newline at end of file
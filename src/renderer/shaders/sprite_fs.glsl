@@ -1,11 +1,11 @@
 #version 330 core
 
 uniform sampler2D diffuseTexture;
-uniform vec4 color;
 
 out vec4 FragColor;
 
 in vec2 texCoord;
+in vec4 color;
 
 void main()
 {
@@ -0,0 +1,45 @@
+#version 330 core
+
+layout(location = 0) out vec4 outColor;
+layout(location = 1) out vec4 outNormal;
+layout(location = 2) out vec4 outAmbient;
+
+// Samplers in arrays can be indexed only by constant expressions in GLSL 3.30,
+// so layers are sampled one by one.
+uniform sampler2D diffuseTextures[4];
+uniform sampler2D normalTextures[4];
+uniform sampler2D splatMap;
+uniform float tileSizes[4];
+
+in vec3 normal;
+in vec2 texCoord;
+in vec3 tangent;
+in vec3 binormal;
+in vec2 splatTexCoord;
+
+void main()
+{
+    vec4 weights = texture(splatMap, splatTexCoord);
+    float total = weights.r + weights.g + weights.b + weights.a;
+    weights = total > 0.0001 ? weights / total : vec4(1.0, 0.0, 0.0, 0.0);
+
+    vec2 uv0 = texCoord / tileSizes[0];
+    vec2 uv1 = texCoord / tileSizes[1];
+    vec2 uv2 = texCoord / tileSizes[2];
+    vec2 uv3 = texCoord / tileSizes[3];
+
+    outColor = texture(diffuseTextures[0], uv0) * weights.r
+        + texture(diffuseTextures[1], uv1) * weights.g
+        + texture(diffuseTextures[2], uv2) * weights.b
+        + texture(diffuseTextures[3], uv3) * weights.a;
+    outColor.a = 1.0;
+
+    vec3 n = (texture(normalTextures[0], uv0).xyz * 2.0 - 1.0) * weights.r
+        + (texture(normalTextures[1], uv1).xyz * 2.0 - 1.0) * weights.g
+        + (texture(normalTextures[2], uv2).xyz * 2.0 - 1.0) * weights.b
+        + (texture(normalTextures[3], uv3).xyz * 2.0 - 1.0) * weights.a;
+    mat3 tangentSpace = mat3(tangent, binormal, normal);
+    outNormal.xyz = normalize(tangentSpace * normalize(n)) * 0.5 + 0.5;
+    outNormal.w = 0.0;
+    outAmbient = vec4(1.0, 1.0, 1.0, 0.0);
+}
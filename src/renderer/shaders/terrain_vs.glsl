@@ -0,0 +1,26 @@
+#version 330 core
+
+layout(location = 0) in vec3 vertexPosition;
+layout(location = 1) in vec2 vertexTexCoord;
+layout(location = 2) in vec2 vertexSecondTexCoord;
+layout(location = 3) in vec3 vertexNormal;
+layout(location = 4) in vec4 vertexTangent;
+
+uniform mat4 worldMatrix;
+uniform mat4 worldViewProjection;
+
+out vec3 normal;
+out vec2 texCoord;
+out vec3 tangent;
+out vec3 binormal;
+out vec2 splatTexCoord;
+
+void main()
+{
+    gl_Position = worldViewProjection * vec4(vertexPosition, 1.0);
+    normal = normalize(mat3(worldMatrix) * vertexNormal);
+    tangent = normalize(mat3(worldMatrix) * vertexTangent.xyz);
+    binormal = normalize(vertexTangent.w * cross(tangent, normal));
+    texCoord = vertexTexCoord;
+    splatTexCoord = vertexSecondTexCoord;
+}
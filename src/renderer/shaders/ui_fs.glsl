@@ -50,7 +50,7 @@ void main()
 
     if (brushType == 0) {
         // Solid color
-        fragColor = solidColor;
+        fragColor = S_SRGBToLinear(solidColor);
     } else {
         // Gradient brush
         float t = 0.0;
@@ -68,11 +68,16 @@ void main()
         float delta = gradientStops[next] - gradientStops[current];
         float mix_factor = (t - gradientStops[current]) / delta;
         fragColor = mix(gradientColors[current], gradientColors[next], mix_factor);
+        fragColor = S_SRGBToLinear(fragColor);
     }
 
     if (isFont)
     {
-        fragColor.a *= texture(diffuseTexture, texCoord).r;
+        // Font atlas is a signed distance field, 0.5 is edge of glyph. Edge is smoothed
+        // across one pixel of screen at any scale.
+        float distance = texture(diffuseTexture, texCoord).r;
+        float width = 0.5 * fwidth(distance);
+        fragColor.a *= smoothstep(0.5 - width, 0.5 + width, distance);
     }
     else
     {
@@ -12,6 +12,6 @@ out vec4 color;
 void main()
 {
     texCoord = vertexTexCoord;
-    color = vertexColor;
+    color = S_SRGBToLinear(vertexColor);
     gl_Position = worldViewProjection * vec4(vertexPosition, 1.0);
 }
\ No newline at end of file
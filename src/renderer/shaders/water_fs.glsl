@@ -0,0 +1,65 @@
+#version 330 core
+
+uniform sampler2D normalTexture;
+uniform sampler2D refractionTexture;
+uniform sampler2D reflectionTexture;
+uniform sampler2D depthTexture;
+uniform mat4 invViewProj;
+uniform vec3 cameraPosition;
+uniform float normalTiling;
+uniform vec2 normalOffsetA;
+uniform vec2 normalOffsetB;
+uniform vec4 shallowColor;
+uniform vec4 deepColor;
+uniform float absorptionDepth;
+uniform float reflectionStrength;
+uniform float distortion;
+uniform bool reflectionEnabled;
+
+in vec3 worldPosition;
+in vec3 waveNormal;
+in vec4 clipPosition;
+
+out vec4 FragColor;
+
+void main()
+{
+    // Two layers of ripples scrolled in different directions to hide tiling.
+    vec2 uv = worldPosition.xz / normalTiling;
+    vec3 rippleA = texture(normalTexture, uv + normalOffsetA).xyz * 2.0 - 1.0;
+    vec3 rippleB = texture(normalTexture, uv * 0.77 + normalOffsetB).xyz * 2.0 - 1.0;
+    vec3 ripple = normalize(rippleA + rippleB);
+
+    // Tangent space of horizontal surface: X maps to X, Y maps to Z, Z maps to Y.
+    vec3 normal = normalize(waveNormal + vec3(ripple.x, 0.0, ripple.y));
+
+    vec2 screenPos = clipPosition.xy / clipPosition.w * 0.5 + 0.5;
+    vec2 offset = ripple.xy * distortion;
+
+    // Depth absorption - the more water light passes through, the closer it gets to deep color.
+    float sceneDepth = texture(depthTexture, screenPos).r;
+    vec3 scenePosition = S_UnProject(vec3(screenPos, sceneDepth), invViewProj);
+    float thickness = distance(worldPosition, scenePosition);
+    float absorption = clamp(thickness / absorptionDepth, 0.0, 1.0);
+
+    // Distortion fades out near shore, otherwise objects above water leak into refraction.
+    vec2 refractionCoords = clamp(screenPos + offset * absorption, 0.001, 0.999);
+    vec3 refraction = texture(refractionTexture, refractionCoords).rgb;
+    vec3 waterColor = mix(S_SRGBToLinear(shallowColor).rgb, S_SRGBToLinear(deepColor).rgb, absorption);
+    vec3 color = mix(refraction, waterColor, absorption * deepColor.a);
+
+    if (reflectionEnabled)
+    {
+        vec3 viewDir = normalize(cameraPosition - worldPosition);
+        float cosTheta = max(dot(viewDir, normal), 0.0);
+        // Schlick approximation with reflectance of water at normal incidence.
+        float fresnel = 0.02 + 0.98 * pow(1.0 - cosTheta, 5.0);
+
+        // Reflection is rendered by mirrored camera, so it is flipped vertically.
+        vec2 reflectionCoords = clamp(vec2(screenPos.x, 1.0 - screenPos.y) + offset, 0.001, 0.999);
+        vec3 reflection = texture(reflectionTexture, reflectionCoords).rgb;
+        color = mix(color, reflection, clamp(fresnel, 0.0, 1.0) * reflectionStrength);
+    }
+
+    FragColor = vec4(color, 1.0);
+}
@@ -0,0 +1,43 @@
+#version 330 core
+
+const int MAX_WAVES = 4;
+
+layout(location = 0) in vec3 vertexPosition;
+
+uniform mat4 viewProjectionMatrix;
+uniform vec3 origin;
+uniform vec2 size;
+uniform float time;
+uniform int waveCount;
+// xy - normalized direction, z - amplitude, w - speed.
+uniform vec4 waves[MAX_WAVES];
+uniform float waveNumbers[MAX_WAVES];
+
+out vec3 worldPosition;
+out vec3 waveNormal;
+out vec4 clipPosition;
+
+void main()
+{
+    // Grid is a unit square in XZ plane centered at origin.
+    vec3 position = origin + vec3(vertexPosition.x * size.x, 0.0, vertexPosition.z * size.y);
+
+    // Must be in sync with Wave::offset_at.
+    float dhdx = 0.0;
+    float dhdz = 0.0;
+    for (int i = 0; i < waveCount; ++i)
+    {
+        vec4 wave = waves[i];
+        float k = waveNumbers[i];
+        float phase = k * (dot(wave.xy, position.xz) - wave.w * time);
+        position.y += wave.z * sin(phase);
+        float slope = wave.z * k * cos(phase);
+        dhdx += slope * wave.x;
+        dhdz += slope * wave.y;
+    }
+
+    worldPosition = position;
+    waveNormal = normalize(vec3(-dhdx, 1.0, -dhdz));
+    clipPosition = viewProjectionMatrix * vec4(position, 1.0);
+    gl_Position = clipPosition;
+}
@@ -115,7 +115,7 @@ impl SpotShadowMapRenderer {
 
         for node in graph.linear_iter() {
             if let Node::Mesh(mesh) = node {
-                if !node.global_visibility() {
+                if !node.global_visibility() || !node.global_cast_shadows() {
                     continue;
                 }
 
@@ -135,7 +135,7 @@ impl SpotShadowMapRenderer {
                     };
                     let mvp = *light_view_projection * world;
 
-                    let diffuse_texture = if let Some(texture) = surface.diffuse_texture() {
+                    let diffuse_texture = if let Some(texture) = surface.actual_diffuse_texture() {
                         if let Some(texture) = textures.get(state, texture) {
                             texture
                         } else {
@@ -424,7 +424,7 @@ impl PointShadowMapRenderer {
 
             for node in graph.linear_iter() {
                 if let Node::Mesh(mesh) = node {
-                    if !node.global_visibility() {
+                    if !node.global_visibility() || !node.global_cast_shadows() {
                         continue;
                     }
 
@@ -444,15 +444,16 @@ impl PointShadowMapRenderer {
                         };
                         let mvp = light_view_projection_matrix * world;
 
-                        let diffuse_texture = if let Some(texture) = surface.diffuse_texture() {
-                            if let Some(texture) = texture_cache.get(state, texture) {
-                                texture
+                        let diffuse_texture =
+                            if let Some(texture) = surface.actual_diffuse_texture() {
+                                if let Some(texture) = texture_cache.get(state, texture) {
+                                    texture
+                                } else {
+                                    white_dummy.clone()
+                                }
                             } else {
                                 white_dummy.clone()
-                            }
-                        } else {
-                            white_dummy.clone()
-                        };
+                            };
 
                         statistics += self.framebuffer.draw(
                             geom_cache.get(state, &surface.data().lock().unwrap()),
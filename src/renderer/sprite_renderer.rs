@@ -1,55 +1,84 @@
 use crate::{
-    core::{math::Rect, scope_profile},
+    core::{
+        color::Color,
+        math::{vec2::Vec2, vec3::Vec3, Rect, TriangleDefinition},
+        pool::Handle,
+        scope_profile,
+    },
     renderer::{
         error::RendererError,
         framework::{
-            framebuffer::{CullFace, DrawParameters, FrameBuffer, FrameBufferTrait},
+            framebuffer::{
+                CullFace, DrawParameters, DrawPartContext, FrameBuffer, FrameBufferTrait,
+            },
+            geometry_buffer::{
+                AttributeDefinition, AttributeKind, ElementKind, GeometryBuffer, GeometryBufferKind,
+            },
             gl,
             gpu_program::{GpuProgram, UniformLocation, UniformValue},
             gpu_texture::GpuTexture,
             state::State,
         },
-        surface::SurfaceSharedData,
-        GeometryCache, RenderPassStatistics, TextureCache,
+        RenderPassStatistics, TextureCache,
     },
-    scene::{camera::Camera, graph::Graph, node::Node},
+    resource::texture::Texture,
+    scene::{camera::Camera, graph::Graph, node::Node, sprite::Sprite},
+};
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    rc::Rc,
+    sync::{Arc, Mutex},
 };
-use std::{cell::RefCell, rc::Rc};
 
 struct SpriteShader {
     program: GpuProgram,
     view_projection_matrix: UniformLocation,
-    world_matrix: UniformLocation,
     camera_side_vector: UniformLocation,
     camera_up_vector: UniformLocation,
-    color: UniformLocation,
     diffuse_texture: UniformLocation,
-    size: UniformLocation,
-    rotation: UniformLocation,
 }
 
 impl SpriteShader {
     pub fn new() -> Result<Self, RendererError> {
         let fragment_source = include_str!("shaders/sprite_fs.glsl");
         let vertex_source = include_str!("shaders/sprite_vs.glsl");
-        let program = GpuProgram::from_source("FlatShader", vertex_source, fragment_source)?;
+        let program = GpuProgram::from_source("SpriteShader", vertex_source, fragment_source)?;
         Ok(Self {
             view_projection_matrix: program.uniform_location("viewProjectionMatrix")?,
-            world_matrix: program.uniform_location("worldMatrix")?,
             camera_side_vector: program.uniform_location("cameraSideVector")?,
             camera_up_vector: program.uniform_location("cameraUpVector")?,
-            size: program.uniform_location("size")?,
             diffuse_texture: program.uniform_location("diffuseTexture")?,
-            color: program.uniform_location("color")?,
-            rotation: program.uniform_location("rotation")?,
             program,
         })
     }
 }
 
+/// OpenGL expects this structure packed as in C.
+#[repr(C)]
+struct Vertex {
+    position: Vec3,
+    tex_coord: Vec2,
+    size: f32,
+    rotation: f32,
+    color: Color,
+    atlas_tex_coord: Vec2,
+}
+
+/// Set of sprites with same texture, which can be drawn in one draw call.
+struct Batch {
+    texture: Option<Arc<Mutex<Texture>>>,
+    // Range of triangles in shared geometry buffer.
+    start: usize,
+    count: usize,
+}
+
 pub struct SpriteRenderer {
     shader: SpriteShader,
-    surface: SurfaceSharedData,
+    geometry_buffer: GeometryBuffer<Vertex>,
+    vertices: Vec<Vertex>,
+    triangles: Vec<TriangleDefinition>,
+    batches: Vec<Batch>,
 }
 
 pub(in crate) struct SpriteRenderContext<'a, 'b, 'c> {
@@ -60,21 +89,148 @@ pub(in crate) struct SpriteRenderContext<'a, 'b, 'c> {
     pub white_dummy: Rc<RefCell<GpuTexture>>,
     pub viewport: Rect<i32>,
     pub textures: &'a mut TextureCache,
-    pub geom_map: &'a mut GeometryCache,
+    pub culled: &'c HashSet<Handle<Node>>,
+}
+
+fn texture_key(texture: &Option<Arc<Mutex<Texture>>>) -> usize {
+    texture
+        .as_ref()
+        .map_or(0, |texture| (&**texture as *const _) as usize)
 }
 
 impl SpriteRenderer {
-    pub fn new() -> Result<Self, RendererError> {
-        let surface = SurfaceSharedData::make_collapsed_xy_quad();
+    pub fn new(state: &mut State) -> Result<Self, RendererError> {
+        let geometry_buffer =
+            GeometryBuffer::new(GeometryBufferKind::DynamicDraw, ElementKind::Triangle);
+
+        geometry_buffer.bind(state).describe_attributes(vec![
+            AttributeDefinition {
+                kind: AttributeKind::Float3,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::Float2,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::Float,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::Float,
+                normalized: false,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::UnsignedByte4,
+                normalized: true,
+            },
+            AttributeDefinition {
+                kind: AttributeKind::Float2,
+                normalized: false,
+            },
+        ])?;
 
         Ok(Self {
             shader: SpriteShader::new()?,
-            surface,
+            geometry_buffer,
+            vertices: Default::default(),
+            triangles: Default::default(),
+            batches: Default::default(),
         })
     }
 
-    #[must_use]
-    pub(in crate) fn render(&mut self, args: SpriteRenderContext) -> RenderPassStatistics {
+    /// Puts every sprite of graph into single vertex buffer. Sprites are transparent, so they're
+    /// sorted by sort layer and then back-to-front, and only adjacent sprites with the same
+    /// texture are merged into one batch.
+    fn build_batches(
+        &mut self,
+        graph: &Graph,
+        camera: &Camera,
+        culled: &HashSet<Handle<Node>>,
+    ) {
+        scope_profile!();
+
+        self.vertices.clear();
+        self.triangles.clear();
+        self.batches.clear();
+
+        let camera_position = camera.global_position();
+        let mut sprites = graph
+            .pair_iter()
+            .filter_map(|(handle, node)| {
+                if let Node::Sprite(sprite) = node {
+                    if sprite.global_visibility()
+                        && camera.sees_layers(sprite.global_layers())
+                        && !culled.contains(&handle)
+                    {
+                        let sqr_distance = camera_position.sqr_distance(&sprite.global_position());
+                        return Some((sprite.sort_layer(), sqr_distance, sprite));
+                    }
+                }
+                None
+            })
+            .collect::<Vec<(i32, f32, &Sprite)>>();
+        // Sort layers must be drawn in order, sprites of same layer are drawn from farthest
+        // to closest one. Sort is stable, so sprites at the same distance keep graph order.
+        sprites.sort_by(|(a_layer, a_distance, _), (b_layer, b_distance, _)| {
+            a_layer.cmp(b_layer).then(
+                b_distance
+                    .partial_cmp(a_distance)
+                    .unwrap_or(std::cmp::Ordering::Equal),
+            )
+        });
+
+        let mut last_texture = None;
+        for (_, _, sprite) in sprites {
+            let texture = texture_key(&sprite.texture());
+            if last_texture != Some(texture) {
+                last_texture = Some(texture);
+                self.batches.push(Batch {
+                    texture: sprite.texture(),
+                    start: self.triangles.len(),
+                    count: 0,
+                });
+            }
+
+            let position = sprite.global_position();
+            let region = sprite.current_region();
+            let base_index = self.vertices.len() as u32;
+            for &tex_coord in [
+                Vec2::new(0.0, 0.0),
+                Vec2::new(1.0, 0.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(0.0, 1.0),
+            ]
+            .iter()
+            {
+                self.vertices.push(Vertex {
+                    position,
+                    tex_coord,
+                    size: sprite.size(),
+                    rotation: sprite.rotation(),
+                    color: sprite.color(),
+                    atlas_tex_coord: region.map(tex_coord),
+                });
+            }
+            self.triangles.push(TriangleDefinition([
+                base_index,
+                base_index + 1,
+                base_index + 2,
+            ]));
+            self.triangles.push(TriangleDefinition([
+                base_index,
+                base_index + 2,
+                base_index + 3,
+            ]));
+
+            self.batches.last_mut().unwrap().count += 2;
+        }
+    }
+
+    pub(in crate) fn render(
+        &mut self,
+        args: SpriteRenderContext,
+    ) -> Result<RenderPassStatistics, RendererError> {
         scope_profile!();
 
         let mut statistics = RenderPassStatistics::default();
@@ -87,9 +243,20 @@ impl SpriteRenderer {
             white_dummy,
             viewport,
             textures,
-            geom_map,
+            culled,
         } = args;
 
+        self.build_batches(graph, camera, culled);
+
+        if self.batches.is_empty() {
+            return Ok(statistics);
+        }
+
+        self.geometry_buffer
+            .bind(state)
+            .set_triangles(&self.triangles)
+            .set_vertices(&self.vertices);
+
         state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
         let inv_view = camera.inv_view_matrix().unwrap();
@@ -97,14 +264,8 @@ impl SpriteRenderer {
         let camera_up = inv_view.up();
         let camera_side = inv_view.side();
 
-        for node in graph.linear_iter() {
-            let sprite = if let Node::Sprite(sprite) = node {
-                sprite
-            } else {
-                continue;
-            };
-
-            let diffuse_texture = if let Some(texture) = sprite.texture() {
+        for batch in self.batches.iter() {
+            let diffuse_texture = if let Some(texture) = batch.texture.clone() {
                 if let Some(texture) = textures.get(state, texture) {
                     texture
                 } else {
@@ -114,12 +275,31 @@ impl SpriteRenderer {
                 white_dummy.clone()
             };
 
-            statistics += framebuffer.draw(
-                geom_map.get(state, &self.surface),
+            let uniforms = [
+                (
+                    self.shader.diffuse_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: diffuse_texture,
+                    },
+                ),
+                (
+                    self.shader.view_projection_matrix,
+                    UniformValue::Mat4(camera.view_projection_matrix()),
+                ),
+                (self.shader.camera_up_vector, UniformValue::Vec3(camera_up)),
+                (
+                    self.shader.camera_side_vector,
+                    UniformValue::Vec3(camera_side),
+                ),
+            ];
+
+            statistics += framebuffer.draw_part(DrawPartContext {
                 state,
                 viewport,
-                &self.shader.program,
-                DrawParameters {
+                geometry: &mut self.geometry_buffer,
+                program: &mut self.shader.program,
+                params: DrawParameters {
                     cull_face: CullFace::Back,
                     culling: true,
                     color_write: Default::default(),
@@ -128,34 +308,12 @@ impl SpriteRenderer {
                     depth_test: true,
                     blend: true,
                 },
-                &[
-                    (
-                        self.shader.diffuse_texture,
-                        UniformValue::Sampler {
-                            index: 0,
-                            texture: diffuse_texture,
-                        },
-                    ),
-                    (
-                        self.shader.view_projection_matrix,
-                        UniformValue::Mat4(camera.view_projection_matrix()),
-                    ),
-                    (
-                        self.shader.world_matrix,
-                        UniformValue::Mat4(node.global_transform()),
-                    ),
-                    (self.shader.camera_up_vector, UniformValue::Vec3(camera_up)),
-                    (
-                        self.shader.camera_side_vector,
-                        UniformValue::Vec3(camera_side),
-                    ),
-                    (self.shader.size, UniformValue::Float(sprite.size())),
-                    (self.shader.color, UniformValue::Color(sprite.color())),
-                    (self.shader.rotation, UniformValue::Float(sprite.rotation())),
-                ],
-            );
+                uniforms: &uniforms,
+                offset: batch.start,
+                count: batch.count,
+            })?;
         }
 
-        statistics
+        Ok(statistics)
     }
 }
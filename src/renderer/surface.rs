@@ -3,6 +3,27 @@
 //!
 //! Surfaces can use same data source across many instances, this is memory optimization for
 //! to be able to re-use data when you need to draw same mesh in many places.
+//!
+//! # Morph targets
+//!
+//! Surface data can contain set of morph targets (also known as blend shapes), each
+//! morph target stores offsets of positions and normals of every vertex. Weights of
+//! morph targets are stored in surface, so every instance can have its own shape.
+//! Morph targets are applied on GPU before skinning, only `MAX_ACTIVE_MORPH_TARGETS`
+//! targets with greatest weights are applied at once.
+//!
+//! # Material parameters
+//!
+//! Every surface has a small set of scalar material parameters (emission strength, dissolve
+//! threshold and texture coordinates offset) which are cheap to change every frame. They can
+//! be animated by parameter tracks of animations or from gameplay code using tweens, which
+//! allows to make effects like shield hits or dissolves without custom render passes. See
+//! `SurfaceParameter` docs for more info.
+//!
+//! # Materials
+//!
+//! Textures, color and material parameters can be shared between many surfaces using
+//! materials and material instances, see `material` module docs.
 
 use crate::{
     core::{
@@ -11,12 +32,14 @@ use crate::{
         pool::{ErasedHandle, Handle},
         visitor::{Visit, VisitResult, Visitor},
     },
+    renderer::material::{MaterialState, SharedMaterial},
     resource::texture::Texture,
     scene::node::Node,
     utils::raw_mesh::{RawMesh, RawMeshBuilder},
 };
 use std::{
     hash::{Hash, Hasher},
+    mem::size_of,
     sync::{Arc, Mutex},
 };
 
@@ -121,6 +144,7 @@ impl Hash for Vertex {
 pub struct SurfaceSharedData {
     pub(in crate) vertices: Vec<Vertex>,
     pub(in crate) triangles: Vec<TriangleDefinition>,
+    morph_targets: Vec<MorphTarget>,
     // If true - indicates that surface was generated and does not have reference
     // resource. Procedural data will be serialized.
     is_procedural: bool,
@@ -131,6 +155,7 @@ impl Default for SurfaceSharedData {
         Self {
             vertices: Default::default(),
             triangles: Default::default(),
+            morph_targets: Default::default(),
             is_procedural: false,
         }
     }
@@ -146,6 +171,7 @@ impl SurfaceSharedData {
         Self {
             vertices,
             triangles,
+            morph_targets: Default::default(),
             is_procedural,
         }
     }
@@ -156,6 +182,7 @@ impl SurfaceSharedData {
         Self {
             vertices: raw.vertices,
             triangles: raw.triangles,
+            morph_targets: Default::default(),
             is_procedural,
         }
     }
@@ -171,12 +198,65 @@ impl SurfaceSharedData {
         &mut self.vertices
     }
 
+    /// Releases excessive memory of vertex and index buffers. Returns amount of bytes
+    /// that were given back to allocator.
+    pub fn shrink_to_fit(&mut self) -> usize {
+        let old_size = self.vertices.capacity() * size_of::<Vertex>()
+            + self.triangles.capacity() * size_of::<TriangleDefinition>();
+        self.vertices.shrink_to_fit();
+        self.triangles.shrink_to_fit();
+        old_size
+            - (self.vertices.capacity() * size_of::<Vertex>()
+                + self.triangles.capacity() * size_of::<TriangleDefinition>())
+    }
+
+    /// Adds new morph target. Returns error if amount of deltas in morph target does not
+    /// match amount of vertices.
+    pub fn add_morph_target(&mut self, morph_target: MorphTarget) -> Result<(), MorphTarget> {
+        if morph_target.position_deltas.len() != self.vertices.len()
+            || morph_target.normal_deltas.len() != self.vertices.len()
+        {
+            Err(morph_target)
+        } else {
+            self.morph_targets.push(morph_target);
+            Ok(())
+        }
+    }
+
+    /// Returns shared reference to array of morph targets.
+    #[inline]
+    pub fn morph_targets(&self) -> &[MorphTarget] {
+        &self.morph_targets
+    }
+
+    /// Returns index of morph target with given name, if any.
+    pub fn find_morph_target<S: AsRef<str>>(&self, name: S) -> Option<usize> {
+        self.morph_targets
+            .iter()
+            .position(|morph_target| morph_target.name == name.as_ref())
+    }
+
     /// Return shared reference to triangles array.
     #[inline]
     pub fn triangles(&self) -> &[TriangleDefinition] {
         self.triangles.as_slice()
     }
 
+    /// Returns amount of memory in bytes that is used by vertices, triangles and morph
+    /// targets of surface.
+    pub fn memory_usage(&self) -> usize {
+        self.vertices.len() * std::mem::size_of::<Vertex>()
+            + self.triangles.len() * std::mem::size_of::<TriangleDefinition>()
+            + self
+                .morph_targets
+                .iter()
+                .map(|target| {
+                    (target.position_deltas.len() + target.normal_deltas.len())
+                        * std::mem::size_of::<Vec3>()
+                })
+                .sum::<usize>()
+    }
+
     /// Calculates tangents of surface. Tangents are needed for correct lighting, you will
     /// get incorrect lighting if tangents of your surface are invalid! When engine loads
     /// a mesh from "untrusted" source, it automatically calculates tangents for you, so
@@ -988,11 +1068,14 @@ impl Visit for SurfaceSharedData {
         if visitor.is_reading() || (self.is_procedural && !visitor.is_reading()) {
             self.vertices.visit("Vertices", visitor)?;
             self.triangles.visit("Triangles", visitor)?;
+            let _ = self.morph_targets.visit("MorphTargets", visitor);
         } else {
             let mut dummy = Vec::<Vertex>::new();
             dummy.visit("Vertices", visitor)?;
             let mut dummy = Vec::<TriangleDefinition>::new();
             dummy.visit("Triangles", visitor)?;
+            let mut dummy = Vec::<MorphTarget>::new();
+            dummy.visit("MorphTargets", visitor)?;
         }
 
         self.is_procedural.visit("IsProcedural", visitor)?;
@@ -1001,6 +1084,33 @@ impl Visit for SurfaceSharedData {
     }
 }
 
+/// Maximum amount of morph targets that can be applied to a surface at once. If there are
+/// more targets with non-zero weight, only the ones with greatest weights will be applied.
+pub const MAX_ACTIVE_MORPH_TARGETS: usize = 4;
+
+/// Morph target (blend shape) is a set of offsets of vertices from their initial positions.
+#[derive(Clone, Debug, Default)]
+pub struct MorphTarget {
+    /// Name of morph target, can be used to find morph target by name.
+    pub name: String,
+    /// Offsets of positions, one for each vertex of surface.
+    pub position_deltas: Vec<Vec3>,
+    /// Offsets of normals, one for each vertex of surface.
+    pub normal_deltas: Vec<Vec3>,
+}
+
+impl Visit for MorphTarget {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.name.visit("Name", visitor)?;
+        self.position_deltas.visit("PositionDeltas", visitor)?;
+        self.normal_deltas.visit("NormalDeltas", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
 /// Vertex weight is a pair of (bone; weight) that affects vertex.
 #[derive(Copy, Clone, Debug)]
 pub struct VertexWeight {
@@ -1107,6 +1217,73 @@ pub struct Surface {
     /// Array of handle to scene nodes which are used as bones.
     pub bones: Vec<Handle<Node>>,
     color: Color,
+    morph_weights: Vec<f32>,
+    emission_strength: f32,
+    dissolve_threshold: f32,
+    uv_offset: Vec2,
+    material: Option<SharedMaterial>,
+}
+
+/// Maximal emission strength of a surface, greater values will be clamped.
+pub const MAX_EMISSION_STRENGTH: f32 = 4.0;
+
+/// Scalar material parameter of a surface which can be animated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SurfaceParameter {
+    /// Defines how much surface glows with its own color, glow does not depend on lighting.
+    /// Zero means no glow, value is clamped to [0; MAX_EMISSION_STRENGTH] range.
+    EmissionStrength,
+
+    /// Portion of surface which is dissolved (not drawn) using procedural noise pattern.
+    /// Zero means that surface is fully visible, one - fully dissolved.
+    DissolveThreshold,
+
+    /// Horizontal offset of texture coordinates, useful for scrolling textures.
+    UvOffsetU,
+
+    /// Vertical offset of texture coordinates, useful for scrolling textures.
+    UvOffsetV,
+}
+
+impl SurfaceParameter {
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(SurfaceParameter::EmissionStrength),
+            1 => Ok(SurfaceParameter::DissolveThreshold),
+            2 => Ok(SurfaceParameter::UvOffsetU),
+            3 => Ok(SurfaceParameter::UvOffsetV),
+            _ => Err(format!("Invalid surface parameter id {}!", id)),
+        }
+    }
+
+    fn id(self) -> u32 {
+        match self {
+            SurfaceParameter::EmissionStrength => 0,
+            SurfaceParameter::DissolveThreshold => 1,
+            SurfaceParameter::UvOffsetU => 2,
+            SurfaceParameter::UvOffsetV => 3,
+        }
+    }
+}
+
+impl Default for SurfaceParameter {
+    fn default() -> Self {
+        SurfaceParameter::EmissionStrength
+    }
+}
+
+impl Visit for SurfaceParameter {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+
+        visitor.leave_region()
+    }
 }
 
 /// Shallow copy of surface.
@@ -1125,6 +1302,11 @@ impl Clone for Surface {
             vertex_weights: Vec::new(), // Intentionally not copied.
             color: self.color,
             lightmap_texture: self.lightmap_texture.clone(),
+            morph_weights: self.morph_weights.clone(),
+            emission_strength: self.emission_strength,
+            dissolve_threshold: self.dissolve_threshold,
+            uv_offset: self.uv_offset,
+            material: self.material.clone(),
         }
     }
 }
@@ -1141,6 +1323,11 @@ impl Surface {
             vertex_weights: Vec::new(),
             color: Color::WHITE,
             lightmap_texture: None,
+            morph_weights: Vec::new(),
+            emission_strength: 0.0,
+            dissolve_threshold: 0.0,
+            uv_offset: Vec2::ZERO,
+            material: None,
         }
     }
 
@@ -1203,6 +1390,169 @@ impl Surface {
     pub fn bones(&self) -> &[Handle<Node>] {
         &self.bones
     }
+
+    /// Sets weight of morph target with given index. Weight is usually in [0; 1] range,
+    /// but any value is allowed. Does nothing if there is no such morph target.
+    pub fn set_morph_weight(&mut self, index: usize, weight: f32) {
+        let count = self.data().lock().unwrap().morph_targets.len();
+        if index < count {
+            if self.morph_weights.len() < count {
+                self.morph_weights.resize(count, 0.0);
+            }
+            self.morph_weights[index] = weight;
+        }
+    }
+
+    /// Returns weight of morph target with given index. Returns zero if there is no
+    /// such morph target.
+    pub fn morph_weight(&self, index: usize) -> f32 {
+        self.morph_weights.get(index).cloned().unwrap_or(0.0)
+    }
+
+    /// Returns shared reference to array of weights of morph targets.
+    pub fn morph_weights(&self) -> &[f32] {
+        &self.morph_weights
+    }
+
+    /// Sets new emission strength. See `SurfaceParameter::EmissionStrength`.
+    #[inline]
+    pub fn set_emission_strength(&mut self, strength: f32) {
+        self.emission_strength = strength.max(0.0).min(MAX_EMISSION_STRENGTH);
+    }
+
+    /// Returns current emission strength.
+    #[inline]
+    pub fn emission_strength(&self) -> f32 {
+        self.emission_strength
+    }
+
+    /// Sets new dissolve threshold. See `SurfaceParameter::DissolveThreshold`.
+    #[inline]
+    pub fn set_dissolve_threshold(&mut self, threshold: f32) {
+        self.dissolve_threshold = threshold.max(0.0).min(1.0);
+    }
+
+    /// Returns current dissolve threshold.
+    #[inline]
+    pub fn dissolve_threshold(&self) -> f32 {
+        self.dissolve_threshold
+    }
+
+    /// Sets new offset of texture coordinates.
+    #[inline]
+    pub fn set_uv_offset(&mut self, offset: Vec2) {
+        self.uv_offset = offset;
+    }
+
+    /// Returns current offset of texture coordinates.
+    #[inline]
+    pub fn uv_offset(&self) -> Vec2 {
+        self.uv_offset
+    }
+
+    /// Sets value of given material parameter.
+    pub fn set_parameter(&mut self, parameter: SurfaceParameter, value: f32) {
+        match parameter {
+            SurfaceParameter::EmissionStrength => self.set_emission_strength(value),
+            SurfaceParameter::DissolveThreshold => self.set_dissolve_threshold(value),
+            SurfaceParameter::UvOffsetU => self.uv_offset.x = value,
+            SurfaceParameter::UvOffsetV => self.uv_offset.y = value,
+        }
+    }
+
+    /// Returns value of given material parameter.
+    pub fn parameter(&self, parameter: SurfaceParameter) -> f32 {
+        match parameter {
+            SurfaceParameter::EmissionStrength => self.emission_strength,
+            SurfaceParameter::DissolveThreshold => self.dissolve_threshold,
+            SurfaceParameter::UvOffsetU => self.uv_offset.x,
+            SurfaceParameter::UvOffsetV => self.uv_offset.y,
+        }
+    }
+
+    /// Sets new material of surface or removes material if `None` is given. Textures of
+    /// material are used instead of textures of surface. See `material` module docs.
+    #[inline]
+    pub fn set_material(&mut self, material: Option<SharedMaterial>) {
+        self.material = material;
+    }
+
+    /// Returns current material of surface.
+    #[inline]
+    pub fn material(&self) -> Option<SharedMaterial> {
+        self.material.clone()
+    }
+
+    /// Combines properties of surface with given state of material of the surface. Given
+    /// state must be calculated from material of this surface.
+    pub(in crate) fn combined_state(
+        &self,
+        material_state: Option<&MaterialState>,
+    ) -> MaterialState {
+        match material_state {
+            Some(material_state) => {
+                let modulate = |a: u8, b: u8| ((a as u32 * b as u32) / 255) as u8;
+                let a = material_state.color;
+                let b = self.color;
+                MaterialState {
+                    diffuse_texture: material_state.diffuse_texture.clone(),
+                    normal_texture: material_state.normal_texture.clone(),
+                    color: Color::from_rgba(
+                        modulate(a.r, b.r),
+                        modulate(a.g, b.g),
+                        modulate(a.b, b.b),
+                        modulate(a.a, b.a),
+                    ),
+                    emission_strength: (material_state.emission_strength
+                        + self.emission_strength)
+                        .min(MAX_EMISSION_STRENGTH),
+                    dissolve_threshold: (material_state.dissolve_threshold
+                        + self.dissolve_threshold)
+                        .min(1.0),
+                    uv_offset: material_state.uv_offset + self.uv_offset,
+                }
+            }
+            None => MaterialState {
+                diffuse_texture: self.diffuse_texture.clone(),
+                normal_texture: self.normal_texture.clone(),
+                color: self.color,
+                emission_strength: self.emission_strength,
+                dissolve_threshold: self.dissolve_threshold,
+                uv_offset: self.uv_offset,
+            },
+        }
+    }
+
+    /// Returns diffuse texture which should be used for rendering - either texture of
+    /// material (if any) or texture of surface.
+    pub(in crate) fn actual_diffuse_texture(&self) -> Option<Arc<Mutex<Texture>>> {
+        match self.material.as_ref() {
+            Some(material) => material.lock().unwrap().state().diffuse_texture,
+            None => self.diffuse_texture.clone(),
+        }
+    }
+
+    /// Collects (index, weight) pairs of morph targets that must be applied to surface.
+    /// There will be at most `MAX_ACTIVE_MORPH_TARGETS` pairs - if there are more
+    /// targets with non-zero weights, the ones with greatest weights will be used.
+    pub(in crate) fn active_morph_targets(&self, active: &mut Vec<(usize, f32)>) {
+        active.clear();
+        active.extend(
+            self.morph_weights
+                .iter()
+                .enumerate()
+                .filter(|(_, weight)| **weight != 0.0)
+                .map(|(index, weight)| (index, *weight)),
+        );
+        if active.len() > MAX_ACTIVE_MORPH_TARGETS {
+            active.sort_by(|a, b| {
+                b.1.abs()
+                    .partial_cmp(&a.1.abs())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            active.truncate(MAX_ACTIVE_MORPH_TARGETS);
+        }
+    }
 }
 
 impl Visit for Surface {
@@ -1219,6 +1569,11 @@ impl Visit for Surface {
         // Try to get lightmap texture but don't care if it is missing, it can
         // be missing on previous versions.
         let _ = self.lightmap_texture.visit("LightmapTexture", visitor);
+        let _ = self.morph_weights.visit("MorphWeights", visitor);
+        let _ = self.emission_strength.visit("EmissionStrength", visitor);
+        let _ = self.dissolve_threshold.visit("DissolveThreshold", visitor);
+        let _ = self.uv_offset.visit("UvOffset", visitor);
+        let _ = self.material.visit("Material", visitor);
 
         visitor.leave_region()
     }
@@ -1232,6 +1587,7 @@ pub struct SurfaceBuilder {
     lightmap_texture: Option<Arc<Mutex<Texture>>>,
     bones: Vec<Handle<Node>>,
     color: Color,
+    material: Option<SharedMaterial>,
 }
 
 impl SurfaceBuilder {
@@ -1244,6 +1600,7 @@ impl SurfaceBuilder {
             lightmap_texture: None,
             bones: Default::default(),
             color: Color::WHITE,
+            material: None,
         }
     }
 
@@ -1277,6 +1634,12 @@ impl SurfaceBuilder {
         self
     }
 
+    /// Sets desired material, see `material` module docs.
+    pub fn with_material(mut self, material: SharedMaterial) -> Self {
+        self.material = Some(material);
+        self
+    }
+
     /// Creates new instance of surface.
     pub fn build(self) -> Surface {
         Surface {
@@ -1287,6 +1650,11 @@ impl SurfaceBuilder {
             vertex_weights: Default::default(),
             bones: self.bones,
             color: self.color,
+            morph_weights: Vec::new(),
+            emission_strength: 0.0,
+            dissolve_threshold: 0.0,
+            uv_offset: Vec2::ZERO,
+            material: self.material,
         }
     }
 }
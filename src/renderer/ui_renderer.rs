@@ -1,13 +1,13 @@
 use crate::{
     core::{
         color::Color,
-        math::{mat4::Mat4, vec2::Vec2, vec4::Vec4, Rect},
+        math::{mat4::Mat4, vec2::Vec2, vec4::Vec4, Rect, TriangleDefinition},
         scope_profile,
     },
     gui::{
         self,
         brush::Brush,
-        draw::{CommandKind, CommandTexture, DrawingContext, SharedTexture},
+        draw::{Command, CommandKind, CommandTexture, DrawingContext, SharedTexture},
     },
     renderer::{
         error::RendererError,
@@ -26,13 +26,127 @@ use crate::{
         RenderPassStatistics, TextureCache,
     },
     resource::texture::{Texture, TextureKind},
+    utils::sdf,
 };
 use std::{
     cell::RefCell,
+    ops::Range,
     rc::Rc,
     sync::{Arc, Mutex},
 };
 
+/// Distance (in pixels of font atlas) at which values of glyph distance field reach zero.
+///
+/// Atlas is packed by the UI crate and glyphs may be closer to each other than the spread.
+/// This is fine: distance field is built for the whole atlas, so a pixel outside of a glyph
+/// may only get a smaller distance to the edge of its neighbour, but it never becomes inside
+/// of the glyph. Shader only needs exact distances near the edge (0.5), so neighbours can
+/// only affect text that is minified so much that its edge smoothing spans more texels than
+/// the gap between glyphs.
+const FONT_SDF_SPREAD: f32 = 4.0;
+
+fn is_same_texture(a: &CommandTexture, b: &CommandTexture) -> bool {
+    match (a, b) {
+        (CommandTexture::Texture(a), CommandTexture::Texture(b)) => {
+            Arc::as_ptr(&a.0) as *const u8 == Arc::as_ptr(&b.0) as *const u8
+        }
+        (CommandTexture::Font(a), CommandTexture::Font(b)) => Arc::ptr_eq(&a.0, &b.0),
+        _ => std::mem::discriminant(a) == std::mem::discriminant(b),
+    }
+}
+
+/// Checks if `next` command can be drawn in the same draw call as `prev` command.
+/// This is possible only for geometry with same render state. Gradient brushes use
+/// bounds of a command so they can't be batched.
+fn can_batch(prev: &Command, next: &Command) -> bool {
+    let brushes_match = match (&prev.brush, &next.brush) {
+        (Brush::Solid(a), Brush::Solid(b)) => a == b,
+        _ => false,
+    };
+
+    matches!(prev.kind, CommandKind::Geometry)
+        && matches!(next.kind, CommandKind::Geometry)
+        && prev.nesting == next.nesting
+        && brushes_match
+        && is_same_texture(&prev.texture, &next.texture)
+}
+
+/// Range of triangles that is drawn in one draw call with render state of given command.
+#[derive(Clone, Debug, PartialEq)]
+struct Batch {
+    command: usize,
+    triangles: Range<usize>,
+}
+
+/// Groups commands into batches. Returns triangles that are reordered so triangles of each
+/// batch lie in continuous range, and batches in drawing order.
+///
+/// Geometry command is moved back in drawing order to the latest batch with same render state
+/// if it does not overlap anything that is drawn in between, so result looks exactly the same
+/// as if commands were drawn one-by-one. Commands are never moved across clipping commands,
+/// each clipping command changes stencil buffer and forms its own batch.
+fn build_batches(
+    commands: &[Command],
+    triangles: &[TriangleDefinition],
+) -> (Vec<TriangleDefinition>, Vec<Batch>) {
+    // Commands of each group and total bounds of their geometry.
+    let mut groups: Vec<(Vec<usize>, Vec2, Vec2)> = Vec::new();
+    // Groups before this one must not be changed, they're separated by clipping command.
+    let mut first_open_group = 0;
+
+    for (index, command) in commands.iter().enumerate() {
+        let (min, max) = (command.bounds.min, command.bounds.max);
+
+        let mut target = None;
+        if let CommandKind::Geometry = command.kind {
+            for (group_index, (group, group_min, group_max)) in
+                groups.iter().enumerate().skip(first_open_group).rev()
+            {
+                if can_batch(&commands[group[0]], command) {
+                    target = Some(group_index);
+                    break;
+                }
+                let overlaps = min.x < group_max.x
+                    && group_min.x < max.x
+                    && min.y < group_max.y
+                    && group_min.y < max.y;
+                if overlaps {
+                    break;
+                }
+            }
+        }
+
+        if let Some(target) = target {
+            let (group, group_min, group_max) = &mut groups[target];
+            group.push(index);
+            *group_min = Vec2::new(group_min.x.min(min.x), group_min.y.min(min.y));
+            *group_max = Vec2::new(group_max.x.max(max.x), group_max.y.max(max.y));
+        } else {
+            groups.push((vec![index], min, max));
+            if let CommandKind::Clip = command.kind {
+                first_open_group = groups.len();
+            }
+        }
+    }
+
+    let mut batched_triangles = Vec::with_capacity(triangles.len());
+    let batches = groups
+        .into_iter()
+        .map(|(group, _, _)| {
+            let start = batched_triangles.len();
+            for &index in group.iter() {
+                batched_triangles.extend_from_slice(&triangles[commands[index].triangles.clone()]);
+            }
+            Batch {
+                command: group[0],
+                triangles: start..batched_triangles.len(),
+            }
+        })
+        .collect();
+
+    (batched_triangles, batches)
+}
+
 struct UiShader {
     program: GpuProgram,
     wvp_matrix: UniformLocation,
@@ -137,15 +251,23 @@ impl UiRenderer {
 
         state.set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
 
+        let commands = drawing_context.get_commands();
+
+        // Widgets produce lots of tiny commands with same state, there is no need to issue
+        // separate draw call for each of them.
+        let (triangles, batches) = build_batches(commands, drawing_context.get_triangles());
+
         let geometry_buffer = self.geometry_buffer.bind(state);
 
         geometry_buffer
-            .set_triangles(drawing_context.get_triangles())
+            .set_triangles(&triangles)
             .set_vertices(drawing_context.get_vertices());
 
         let ortho = Mat4::ortho(0.0, frame_width, frame_height, 0.0, -1.0, 1.0);
 
-        for cmd in drawing_context.get_commands() {
+        for batch in batches {
+            let cmd = &commands[batch.command];
+
             let mut diffuse_texture = white_dummy.clone();
             let mut is_font_texture = false;
             let mut color_write = true;
@@ -181,12 +303,20 @@ impl UiRenderer {
                         CommandTexture::Font(font_arc) => {
                             let mut font = font_arc.0.lock().unwrap();
                             if font.texture.is_none() {
-                                let size = font.atlas_size() as u32;
-                                if let Ok(tex) = Texture::from_bytes(
+                                let size = font.atlas_size() as usize;
+                                // Glyphs are drawn using distance field, so they stay sharp
+                                // when text is scaled.
+                                let pixels = sdf::coverage_to_sdf(
+                                    font.atlas_pixels(),
                                     size,
                                     size,
+                                    FONT_SDF_SPREAD,
+                                );
+                                if let Ok(tex) = Texture::from_bytes(
+                                    size as u32,
+                                    size as u32,
                                     TextureKind::R8,
-                                    font.atlas_pixels().to_vec(),
+                                    pixels,
                                 ) {
                                     font.texture = Some(SharedTexture(Arc::new(Mutex::new(tex))));
                                 }
@@ -336,10 +466,105 @@ impl UiRenderer {
                 program: &mut self.shader.program,
                 params,
                 uniforms: &uniforms,
-                offset: cmd.triangles.start,
-                count: cmd.triangles.end - cmd.triangles.start,
+                offset: batch.triangles.start,
+                count: batch.triangles.end - batch.triangles.start,
             })?;
         }
         Ok(statistics)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            color::Color,
+            math::{Rect, TriangleDefinition},
+        },
+        gui::{
+            brush::Brush,
+            draw::{CommandKind, CommandTexture, DrawingContext, SharedTexture},
+        },
+        renderer::ui_renderer::{build_batches, can_batch, Batch},
+        resource::texture::Texture,
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn rect(ctx: &mut DrawingContext, x: f32, texture: CommandTexture) {
+        ctx.push_rect_filled(&Rect::new(x, 0.0, 10.0, 10.0), None);
+        ctx.commit(CommandKind::Geometry, Brush::Solid(Color::WHITE), texture);
+    }
+
+    fn texture() -> CommandTexture {
+        CommandTexture::Texture(SharedTexture(Arc::new(Mutex::new(Texture::default()))))
+    }
+
+    #[test]
+    fn can_batch_checks_render_state() {
+        let a = texture();
+        let mut ctx = DrawingContext::new();
+        rect(&mut ctx, 0.0, a.clone());
+        rect(&mut ctx, 20.0, a.clone());
+        rect(&mut ctx, 40.0, texture());
+        ctx.push_rect_filled(&Rect::new(60.0, 0.0, 10.0, 10.0), None);
+        ctx.commit(CommandKind::Geometry, Brush::Solid(Color::RED), a);
+
+        let commands = ctx.get_commands();
+        assert!(can_batch(&commands[0], &commands[1]));
+        assert!(!can_batch(&commands[1], &commands[2]));
+        assert!(!can_batch(&commands[1], &commands[3]));
+    }
+
+    #[test]
+    fn batches_are_grouped_by_texture_without_changing_overlapping_order() {
+        let (a, b) = (texture(), texture());
+        let mut ctx = DrawingContext::new();
+        // Icon and label pairs of a toolbar: A B A B, nothing overlaps.
+        rect(&mut ctx, 0.0, a.clone());
+        rect(&mut ctx, 20.0, b.clone());
+        rect(&mut ctx, 40.0, a.clone());
+        rect(&mut ctx, 60.0, b.clone());
+        // Overlaps last B, so it must be drawn after it.
+        rect(&mut ctx, 65.0, a);
+
+        let (triangles, batches) = build_batches(ctx.get_commands(), ctx.get_triangles());
+        assert_eq!(triangles.len(), ctx.get_triangles().len());
+        assert_eq!(
+            batches,
+            vec![
+                Batch {
+                    command: 0,
+                    triangles: 0..4,
+                },
+                Batch {
+                    command: 1,
+                    triangles: 4..8,
+                },
+                Batch {
+                    command: 4,
+                    triangles: 8..10,
+                },
+            ]
+        );
+        // Triangles of third command are moved right after triangles of first one.
+        let indices = |triangles: &[TriangleDefinition]| {
+            triangles.iter().map(|triangle| triangle.0).collect::<Vec<_>>()
+        };
+        assert_eq!(
+            indices(&triangles[2..4]),
+            indices(&ctx.get_triangles()[ctx.get_commands()[2].triangles.clone()])
+        );
+    }
+
+    #[test]
+    fn batches_are_not_merged_across_clipping() {
+        let a = texture();
+        let mut ctx = DrawingContext::new();
+        rect(&mut ctx, 0.0, a.clone());
+        ctx.commit_clip_rect(&Rect::new(0.0, 0.0, 100.0, 100.0));
+        rect(&mut ctx, 20.0, a);
+
+        let (_, batches) = build_batches(ctx.get_commands(), ctx.get_triangles());
+        assert_eq!(batches.len(), 3);
+    }
+}
@@ -0,0 +1,494 @@
+use crate::{
+    core::{
+        math::{mat4::Mat4, vec2::Vec2, vec3::Vec3, vec4::Vec4, Rect, TriangleDefinition},
+        pool::Handle,
+        scope_profile,
+    },
+    renderer::{
+        error::RendererError,
+        flat_shader::FlatShader,
+        framework::{
+            framebuffer::{
+                Attachment, AttachmentKind, CullFace, DrawParameters, FrameBuffer, FrameBufferTrait,
+            },
+            geometry_buffer::{
+                AttributeDefinition, AttributeKind, ElementKind, GeometryBuffer, GeometryBufferKind,
+            },
+            gpu_program::{GpuProgram, UniformLocation, UniformValue},
+            gpu_texture::{Coordinate, GpuTexture, GpuTextureKind, PixelKind, WrapMode},
+            state::State,
+        },
+        gbuffer::GBuffer,
+        surface::SurfaceSharedData,
+        GeometryCache, RenderPassStatistics, TextureCache,
+    },
+    scene::{
+        camera::Camera,
+        graph::Graph,
+        node::Node,
+        water::{Water, MAX_WAVES},
+    },
+};
+use std::{cell::RefCell, collections::HashSet, f32::consts::PI, rc::Rc};
+
+/// Amount of cells of water grid along each axis.
+const GRID_SIZE: usize = 64;
+
+struct WaterShader {
+    program: GpuProgram,
+    view_projection_matrix: UniformLocation,
+    origin: UniformLocation,
+    size: UniformLocation,
+    time: UniformLocation,
+    wave_count: UniformLocation,
+    waves: UniformLocation,
+    wave_numbers: UniformLocation,
+    normal_texture: UniformLocation,
+    refraction_texture: UniformLocation,
+    reflection_texture: UniformLocation,
+    depth_texture: UniformLocation,
+    inv_view_proj: UniformLocation,
+    camera_position: UniformLocation,
+    normal_tiling: UniformLocation,
+    normal_offset_a: UniformLocation,
+    normal_offset_b: UniformLocation,
+    shallow_color: UniformLocation,
+    deep_color: UniformLocation,
+    absorption_depth: UniformLocation,
+    reflection_strength: UniformLocation,
+    distortion: UniformLocation,
+    reflection_enabled: UniformLocation,
+}
+
+impl WaterShader {
+    fn new() -> Result<Self, RendererError> {
+        let fragment_source = include_str!("shaders/water_fs.glsl");
+        let vertex_source = include_str!("shaders/water_vs.glsl");
+        let program = GpuProgram::from_source("WaterShader", vertex_source, fragment_source)?;
+        Ok(Self {
+            view_projection_matrix: program.uniform_location("viewProjectionMatrix")?,
+            origin: program.uniform_location("origin")?,
+            size: program.uniform_location("size")?,
+            time: program.uniform_location("time")?,
+            wave_count: program.uniform_location("waveCount")?,
+            waves: program.uniform_location("waves")?,
+            wave_numbers: program.uniform_location("waveNumbers")?,
+            normal_texture: program.uniform_location("normalTexture")?,
+            refraction_texture: program.uniform_location("refractionTexture")?,
+            reflection_texture: program.uniform_location("reflectionTexture")?,
+            depth_texture: program.uniform_location("depthTexture")?,
+            inv_view_proj: program.uniform_location("invViewProj")?,
+            camera_position: program.uniform_location("cameraPosition")?,
+            normal_tiling: program.uniform_location("normalTiling")?,
+            normal_offset_a: program.uniform_location("normalOffsetA")?,
+            normal_offset_b: program.uniform_location("normalOffsetB")?,
+            shallow_color: program.uniform_location("shallowColor")?,
+            deep_color: program.uniform_location("deepColor")?,
+            absorption_depth: program.uniform_location("absorptionDepth")?,
+            reflection_strength: program.uniform_location("reflectionStrength")?,
+            distortion: program.uniform_location("distortion")?,
+            reflection_enabled: program.uniform_location("reflectionEnabled")?,
+            program,
+        })
+    }
+}
+
+/// Copy of frame behind water surfaces, water cannot sample frame it is drawn into.
+struct RefractionBuffer {
+    framebuffer: FrameBuffer,
+    width: i32,
+    height: i32,
+    hdr: bool,
+}
+
+impl RefractionBuffer {
+    fn new(state: &mut State, width: i32, height: i32, hdr: bool) -> Result<Self, RendererError> {
+        let frame = {
+            let kind = GpuTextureKind::Rectangle {
+                width: width as usize,
+                height: height as usize,
+            };
+            let pixel_kind = if hdr {
+                PixelKind::RGBA16F
+            } else {
+                PixelKind::RGBA8
+            };
+            let mut texture = GpuTexture::new(state, kind, pixel_kind, None)?;
+            texture
+                .bind_mut(state, 0)
+                .set_wrap(Coordinate::S, WrapMode::ClampToEdge)
+                .set_wrap(Coordinate::T, WrapMode::ClampToEdge);
+            texture
+        };
+
+        Ok(Self {
+            framebuffer: FrameBuffer::new(
+                state,
+                None,
+                vec![Attachment {
+                    kind: AttachmentKind::Color,
+                    texture: Rc::new(RefCell::new(frame)),
+                }],
+            )?,
+            width,
+            height,
+            hdr,
+        })
+    }
+
+    fn texture(&self) -> Rc<RefCell<GpuTexture>> {
+        self.framebuffer.color_attachments()[0].texture.clone()
+    }
+}
+
+pub struct WaterRenderer {
+    shader: WaterShader,
+    flat_shader: FlatShader,
+    grid: GeometryBuffer<Vec3>,
+    quad: SurfaceSharedData,
+    refraction: Option<RefractionBuffer>,
+}
+
+pub(in crate) struct WaterRenderContext<'a, 'b, 'c> {
+    pub state: &'a mut State,
+    pub gbuffer: &'b mut GBuffer,
+    pub graph: &'c Graph,
+    pub camera: &'c Camera,
+    pub viewport: Rect<i32>,
+    pub culled: &'c HashSet<Handle<Node>>,
+    /// Frame rendered by reflection camera, see `Camera::make_reflection`.
+    pub reflection: Option<Rc<RefCell<GpuTexture>>>,
+    pub white_dummy: Rc<RefCell<GpuTexture>>,
+    pub normal_dummy: Rc<RefCell<GpuTexture>>,
+    pub textures: &'a mut TextureCache,
+    pub geom_cache: &'a mut GeometryCache,
+}
+
+fn is_water_visible(
+    handle: Handle<Node>,
+    water: &Water,
+    camera: &Camera,
+    culled: &HashSet<Handle<Node>>,
+) -> bool {
+    water.global_visibility()
+        && camera.sees_layers(water.global_layers())
+        && !culled.contains(&handle)
+}
+
+/// Returns height of plane which should be used to render reflection for given camera. It
+/// is the closest visible water with enabled reflections which is below the camera.
+pub(in crate) fn reflection_plane(
+    graph: &Graph,
+    camera: &Camera,
+    culled: &HashSet<Handle<Node>>,
+) -> Option<f32> {
+    let camera_position = camera.global_position();
+    graph
+        .pair_iter()
+        .filter_map(|(handle, node)| {
+            if let Node::Water(water) = node {
+                let position = water.global_position();
+                if water.reflections_enabled()
+                    && position.y < camera_position.y
+                    && is_water_visible(handle, water, camera, culled)
+                {
+                    return Some(position);
+                }
+            }
+            None
+        })
+        .min_by(|a, b| {
+            let da = a.sqr_distance(&camera_position);
+            let db = b.sqr_distance(&camera_position);
+            da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|position| position.y)
+}
+
+fn make_grid(state: &mut State) -> Result<GeometryBuffer<Vec3>, RendererError> {
+    let mut vertices = Vec::with_capacity((GRID_SIZE + 1) * (GRID_SIZE + 1));
+    for z in 0..=GRID_SIZE {
+        for x in 0..=GRID_SIZE {
+            vertices.push(Vec3::new(
+                x as f32 / GRID_SIZE as f32 - 0.5,
+                0.0,
+                z as f32 / GRID_SIZE as f32 - 0.5,
+            ));
+        }
+    }
+
+    let mut triangles = Vec::with_capacity(GRID_SIZE * GRID_SIZE * 2);
+    let stride = (GRID_SIZE + 1) as u32;
+    for z in 0..GRID_SIZE as u32 {
+        for x in 0..GRID_SIZE as u32 {
+            let i = z * stride + x;
+            triangles.push(TriangleDefinition([i, i + stride, i + stride + 1]));
+            triangles.push(TriangleDefinition([i, i + stride + 1, i + 1]));
+        }
+    }
+
+    let grid = GeometryBuffer::new(GeometryBufferKind::StaticDraw, ElementKind::Triangle);
+    grid.bind(state)
+        .describe_attributes(vec![AttributeDefinition {
+            kind: AttributeKind::Float3,
+            normalized: false,
+        }])?
+        .set_vertices(&vertices)
+        .set_triangles(&triangles);
+    Ok(grid)
+}
+
+impl WaterRenderer {
+    pub fn new(state: &mut State) -> Result<Self, RendererError> {
+        Ok(Self {
+            shader: WaterShader::new()?,
+            flat_shader: FlatShader::new()?,
+            grid: make_grid(state)?,
+            quad: SurfaceSharedData::make_unit_xy_quad(),
+            refraction: None,
+        })
+    }
+
+    /// Copies current frame into refraction buffer, buffer is re-created if it does not
+    /// match g-buffer.
+    fn copy_frame(
+        &mut self,
+        state: &mut State,
+        gbuffer: &GBuffer,
+        geom_cache: &mut GeometryCache,
+    ) -> Result<Rc<RefCell<GpuTexture>>, RendererError> {
+        let is_outdated = self.refraction.as_ref().map_or(true, |refraction| {
+            refraction.width != gbuffer.width
+                || refraction.height != gbuffer.height
+                || refraction.hdr != gbuffer.hdr
+        });
+        if is_outdated {
+            self.refraction = Some(RefractionBuffer::new(
+                state,
+                gbuffer.width,
+                gbuffer.height,
+                gbuffer.hdr,
+            )?);
+        }
+        let refraction = self.refraction.as_mut().unwrap();
+
+        let viewport = Rect::new(0, 0, gbuffer.width, gbuffer.height);
+        refraction.framebuffer.draw(
+            geom_cache.get(state, &self.quad),
+            state,
+            viewport,
+            &self.flat_shader.program,
+            DrawParameters {
+                cull_face: CullFace::Back,
+                culling: false,
+                color_write: Default::default(),
+                depth_write: false,
+                stencil_test: false,
+                depth_test: false,
+                blend: false,
+            },
+            &[
+                (
+                    self.flat_shader.wvp_matrix,
+                    UniformValue::Mat4(
+                        Mat4::ortho(0.0, viewport.w as f32, viewport.h as f32, 0.0, -1.0, 1.0)
+                            * Mat4::scale(Vec3::new(viewport.w as f32, viewport.h as f32, 0.0)),
+                    ),
+                ),
+                (
+                    self.flat_shader.diffuse_texture,
+                    UniformValue::Sampler {
+                        index: 0,
+                        texture: gbuffer.frame_texture(),
+                    },
+                ),
+            ],
+        );
+
+        Ok(refraction.texture())
+    }
+
+    pub(in crate) fn render(
+        &mut self,
+        args: WaterRenderContext,
+    ) -> Result<RenderPassStatistics, RendererError> {
+        scope_profile!();
+
+        let mut statistics = RenderPassStatistics::default();
+
+        let WaterRenderContext {
+            state,
+            gbuffer,
+            graph,
+            camera,
+            viewport,
+            culled,
+            reflection,
+            white_dummy,
+            normal_dummy,
+            textures,
+            geom_cache,
+        } = args;
+
+        let waters = graph
+            .pair_iter()
+            .filter_map(|(handle, node)| {
+                if let Node::Water(water) = node {
+                    if is_water_visible(handle, water, camera, culled) {
+                        return Some(water);
+                    }
+                }
+                None
+            })
+            .collect::<Vec<_>>();
+
+        if waters.is_empty() {
+            return Ok(statistics);
+        }
+
+        let refraction = self.copy_frame(state, gbuffer, geom_cache)?;
+        let depth = gbuffer.depth();
+        let view_projection = camera.view_projection_matrix();
+        let inv_view_proj = view_projection.inverse().unwrap_or_default();
+        let camera_position = camera.global_position();
+
+        for water in waters {
+            let mut waves = [Vec4::default(); MAX_WAVES];
+            let mut wave_numbers = [0.0; MAX_WAVES];
+            for (i, wave) in water.waves().iter().take(MAX_WAVES).enumerate() {
+                let direction = wave.normalized_direction();
+                waves[i] = Vec4::new(direction.x, direction.y, wave.amplitude, wave.speed);
+                wave_numbers[i] = 2.0 * PI / wave.wavelength.max(std::f32::EPSILON);
+            }
+            let wave_count = water.waves().len().min(MAX_WAVES) as i32;
+
+            let (half_width, half_depth) = water.world_half_extents();
+
+            let time = water.time();
+            let flow = water.flow();
+            let normal_offset_a = Vec2::new(flow.x * time, flow.y * time);
+            let normal_offset_b = Vec2::new(
+                (flow.x * 0.6 + flow.y * 0.4) * time,
+                (flow.y * 0.6 - flow.x * 0.4) * time,
+            );
+
+            let normal_texture = water
+                .normal_map()
+                .and_then(|texture| textures.get(state, texture))
+                .unwrap_or_else(|| normal_dummy.clone());
+
+            statistics += gbuffer.final_frame.draw(
+                &self.grid,
+                state,
+                viewport,
+                &self.shader.program,
+                DrawParameters {
+                    cull_face: CullFace::Back,
+                    culling: false,
+                    color_write: Default::default(),
+                    // Depth is sampled by the shader, so it must stay untouched.
+                    depth_write: false,
+                    stencil_test: false,
+                    depth_test: true,
+                    blend: false,
+                },
+                &[
+                    (
+                        self.shader.view_projection_matrix,
+                        UniformValue::Mat4(view_projection),
+                    ),
+                    (
+                        self.shader.origin,
+                        UniformValue::Vec3(water.global_position()),
+                    ),
+                    (
+                        self.shader.size,
+                        UniformValue::Vec2(Vec2::new(half_width * 2.0, half_depth * 2.0)),
+                    ),
+                    (self.shader.time, UniformValue::Float(time)),
+                    (self.shader.wave_count, UniformValue::Integer(wave_count)),
+                    (self.shader.waves, UniformValue::Vec4Array(&waves)),
+                    (
+                        self.shader.wave_numbers,
+                        UniformValue::FloatArray(&wave_numbers),
+                    ),
+                    (
+                        self.shader.normal_texture,
+                        UniformValue::Sampler {
+                            index: 0,
+                            texture: normal_texture,
+                        },
+                    ),
+                    (
+                        self.shader.refraction_texture,
+                        UniformValue::Sampler {
+                            index: 1,
+                            texture: refraction.clone(),
+                        },
+                    ),
+                    (
+                        self.shader.reflection_texture,
+                        UniformValue::Sampler {
+                            index: 2,
+                            texture: reflection.clone().unwrap_or_else(|| white_dummy.clone()),
+                        },
+                    ),
+                    (
+                        self.shader.depth_texture,
+                        UniformValue::Sampler {
+                            index: 3,
+                            texture: depth.clone(),
+                        },
+                    ),
+                    (self.shader.inv_view_proj, UniformValue::Mat4(inv_view_proj)),
+                    (
+                        self.shader.camera_position,
+                        UniformValue::Vec3(camera_position),
+                    ),
+                    (
+                        self.shader.normal_tiling,
+                        UniformValue::Float(water.normal_map_tiling()),
+                    ),
+                    (
+                        self.shader.normal_offset_a,
+                        UniformValue::Vec2(normal_offset_a),
+                    ),
+                    (
+                        self.shader.normal_offset_b,
+                        UniformValue::Vec2(normal_offset_b),
+                    ),
+                    (
+                        self.shader.shallow_color,
+                        UniformValue::Color(water.shallow_color()),
+                    ),
+                    (
+                        self.shader.deep_color,
+                        UniformValue::Color(water.deep_color()),
+                    ),
+                    (
+                        self.shader.absorption_depth,
+                        UniformValue::Float(water.absorption_depth()),
+                    ),
+                    (
+                        self.shader.reflection_strength,
+                        UniformValue::Float(water.reflection_strength()),
+                    ),
+                    (
+                        self.shader.distortion,
+                        UniformValue::Float(water.distortion()),
+                    ),
+                    (
+                        self.shader.reflection_enabled,
+                        UniformValue::Bool(water.reflections_enabled() && reflection.is_some()),
+                    ),
+                ],
+            );
+        }
+
+        Ok(statistics)
+    }
+
+    pub fn clear(&mut self) {
+        self.refraction = None;
+    }
+}
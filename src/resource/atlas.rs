@@ -0,0 +1,378 @@
+//! Contains runtime texture atlas packing.
+//!
+//! Every texture bind is a state change for GPU, so scenes with lots of sprites, particle
+//! systems or UI elements which use many small textures are slow to render even if each
+//! texture is tiny. Texture atlas combines many small textures into one big texture, so
+//! all of them can be drawn without switching textures. Each packed texture is described by
+//! its region (`AtlasRegion`) in normalized texture coordinates of the atlas, which can be
+//! assigned to sprites directly (`Sprite::set_region`) or used to remap texture coordinates
+//! of anything else using `AtlasRegion::map`.
+//!
+//! Packed textures are surrounded by padding filled with their edge pixels, so filtering
+//! and mip maps won't bleed colors of neighbour textures into each other.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     engine::resource_manager::ResourceManager,
+//!     scene::{base::BaseBuilder, node::Node, sprite::SpriteBuilder},
+//! };
+//!
+//! fn make_coin(resource_manager: &mut ResourceManager) -> Option<Node> {
+//!     let atlas = resource_manager
+//!         .request_texture_atlas(&["data/coin.png", "data/gem.png", "data/heart.png"])?;
+//!     Some(
+//!         SpriteBuilder::new(BaseBuilder::new())
+//!             .with_texture(atlas.texture())
+//!             .with_region(atlas.find_region("data/coin.png")?)
+//!             .build_node(),
+//!     )
+//! }
+//! ```
+
+use crate::{
+    core::{math::vec2::Vec2, rectpack::RectPacker},
+    resource::texture::{Texture, TextureKind},
+    scene::sprite::AtlasRegion,
+};
+use std::{
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Error which can occur when textures are packed into atlas.
+#[derive(Debug)]
+pub enum AtlasError {
+    /// There are no textures to pack.
+    Empty,
+    /// Texture is not loaded yet, string contains path of texture.
+    NotLoaded(PathBuf),
+    /// Compressed textures can't be packed, string contains path of texture.
+    CompressedTexture(PathBuf),
+    /// HDR textures can't be packed, string contains path of texture.
+    HdrTexture(PathBuf),
+    /// Textures do not fit into atlas of maximum size.
+    TooBig {
+        /// Maximum size of atlas.
+        max_size: u32,
+    },
+}
+
+impl Display for AtlasError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtlasError::Empty => write!(f, "There are no textures to pack into atlas"),
+            AtlasError::NotLoaded(path) => write!(f, "Texture {:?} is not loaded yet", path),
+            AtlasError::CompressedTexture(path) => write!(
+                f,
+                "Compressed texture {:?} can't be packed into atlas",
+                path
+            ),
+            AtlasError::HdrTexture(path) => {
+                write!(f, "HDR texture {:?} can't be packed into atlas", path)
+            }
+            AtlasError::TooBig { max_size } => write!(
+                f,
+                "Textures do not fit into atlas of {}x{} size",
+                max_size, max_size
+            ),
+        }
+    }
+}
+
+/// Texture with packed textures, see module docs.
+#[derive(Debug, Clone)]
+pub struct TextureAtlas {
+    texture: Arc<Mutex<Texture>>,
+    regions: Vec<AtlasRegion>,
+    paths: Vec<PathBuf>,
+}
+
+impl TextureAtlas {
+    /// Returns texture of atlas.
+    pub fn texture(&self) -> Arc<Mutex<Texture>> {
+        self.texture.clone()
+    }
+
+    /// Returns regions of packed textures in order in which textures were added to builder.
+    pub fn regions(&self) -> &[AtlasRegion] {
+        &self.regions
+    }
+
+    /// Returns region of packed texture with given path. Returns None if no such texture
+    /// was packed.
+    pub fn find_region<P: AsRef<Path>>(&self, path: P) -> Option<AtlasRegion> {
+        self.paths
+            .iter()
+            .position(|p| p == path.as_ref())
+            .map(|i| self.regions[i])
+    }
+}
+
+/// Texture atlas builder allows you to construct atlas in declarative manner.
+/// This is typical implementation of Builder pattern.
+pub struct TextureAtlasBuilder {
+    textures: Vec<Arc<Mutex<Texture>>>,
+    padding: u32,
+    max_size: u32,
+}
+
+impl Default for TextureAtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns pixel of texture in RGBA8 format, coordinates are clamped to size of texture.
+fn fetch_rgba(texture: &Texture, x: i64, y: i64) -> [u8; 4] {
+    let x = x.max(0).min(texture.width as i64 - 1) as usize;
+    let y = y.max(0).min(texture.height as i64 - 1) as usize;
+    let index = y * texture.width as usize + x;
+    match texture.kind {
+        TextureKind::R8 => {
+            let v = texture.bytes[index];
+            [v, v, v, 255]
+        }
+        TextureKind::RGB8 => {
+            let p = &texture.bytes[(index * 3)..(index * 3 + 3)];
+            [p[0], p[1], p[2], 255]
+        }
+        _ => {
+            let p = &texture.bytes[(index * 4)..(index * 4 + 4)];
+            [p[0], p[1], p[2], p[3]]
+        }
+    }
+}
+
+impl TextureAtlasBuilder {
+    /// Creates new builder with 2 pixels of padding and 4096 pixels of maximum size.
+    pub fn new() -> Self {
+        Self {
+            textures: Default::default(),
+            padding: 2,
+            max_size: 4096,
+        }
+    }
+
+    /// Adds texture to pack. Texture must be loaded and must not be compressed or HDR.
+    /// Texture which is added more than once is packed only once.
+    pub fn with_texture(mut self, texture: Arc<Mutex<Texture>>) -> Self {
+        self.textures.push(texture);
+        self
+    }
+
+    /// Adds textures to pack, see `with_texture`.
+    pub fn with_textures(mut self, textures: Vec<Arc<Mutex<Texture>>>) -> Self {
+        self.textures.extend(textures);
+        self
+    }
+
+    /// Sets desired amount of pixels around each texture.
+    pub fn with_padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets desired maximum width and height of atlas texture.
+    pub fn with_max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Packs textures into atlas. Atlas texture is square RGBA8 texture with size of power
+    /// of two, smallest size which fits all textures is selected.
+    pub fn build(self) -> Result<TextureAtlas, AtlasError> {
+        if self.textures.is_empty() {
+            return Err(AtlasError::Empty);
+        }
+
+        // Same texture can be added many times, but it must be locked and packed once.
+        let mut unique = Vec::<&Arc<Mutex<Texture>>>::new();
+        let mut unique_indices = Vec::with_capacity(self.textures.len());
+        for texture in self.textures.iter() {
+            match unique.iter().position(|other| Arc::ptr_eq(other, texture)) {
+                Some(index) => unique_indices.push(index),
+                None => {
+                    unique_indices.push(unique.len());
+                    unique.push(texture);
+                }
+            }
+        }
+        let textures = unique
+            .iter()
+            .map(|texture| texture.lock().unwrap())
+            .collect::<Vec<_>>();
+        for texture in textures.iter() {
+            if !texture.loaded {
+                return Err(AtlasError::NotLoaded(texture.path.clone()));
+            }
+            if texture.kind.is_compressed() {
+                return Err(AtlasError::CompressedTexture(texture.path.clone()));
+            }
+            if texture.kind.is_hdr() {
+                return Err(AtlasError::HdrTexture(texture.path.clone()));
+            }
+        }
+
+        let padding = self.padding as usize;
+        let padded_size = |texture: &Texture| {
+            (
+                texture.width as usize + 2 * padding,
+                texture.height as usize + 2 * padding,
+            )
+        };
+
+        // Big textures are packed first, it gives much denser packing.
+        let mut order = (0..textures.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| {
+            let (w, h) = padded_size(&textures[i]);
+            std::cmp::Reverse(w.max(h))
+        });
+
+        let area = textures
+            .iter()
+            .map(|texture| {
+                let (w, h) = padded_size(texture);
+                w * h
+            })
+            .sum::<usize>();
+        let max_side = textures
+            .iter()
+            .map(|texture| {
+                let (w, h) = padded_size(texture);
+                w.max(h)
+            })
+            .max()
+            .unwrap_or_default();
+        let mut size = ((area as f64).sqrt().ceil() as usize)
+            .max(max_side)
+            .next_power_of_two();
+
+        let positions = loop {
+            if size > self.max_size as usize {
+                return Err(AtlasError::TooBig {
+                    max_size: self.max_size,
+                });
+            }
+            let mut packer = RectPacker::new(size, size);
+            let mut positions = vec![(0, 0); textures.len()];
+            let fits = order.iter().all(|&i| {
+                let (w, h) = padded_size(&textures[i]);
+                match packer.find_free(w, h) {
+                    Some(rect) => {
+                        positions[i] = (rect.x, rect.y);
+                        true
+                    }
+                    None => false,
+                }
+            });
+            if fits {
+                break positions;
+            }
+            size *= 2;
+        };
+
+        let mut bytes = vec![0; size * size * 4];
+        let mut regions = Vec::with_capacity(textures.len());
+        for (texture, &(x, y)) in textures.iter().zip(positions.iter()) {
+            let (w, h) = padded_size(texture);
+            for row in 0..h {
+                for column in 0..w {
+                    // Padding is filled with edge pixels of texture.
+                    let pixel = fetch_rgba(
+                        texture,
+                        column as i64 - padding as i64,
+                        row as i64 - padding as i64,
+                    );
+                    let index = ((y + row) * size + x + column) * 4;
+                    bytes[index..(index + 4)].copy_from_slice(&pixel);
+                }
+            }
+            let min = Vec2::new(
+                (x + padding) as f32 / size as f32,
+                (y + padding) as f32 / size as f32,
+            );
+            regions.push(AtlasRegion {
+                min,
+                max: Vec2::new(
+                    min.x + texture.width as f32 / size as f32,
+                    min.y + texture.height as f32 / size as f32,
+                ),
+            });
+        }
+
+        let mut atlas_texture =
+            Texture::from_bytes(size as u32, size as u32, TextureKind::RGBA8, bytes)
+                .expect("Size of atlas data must match its size!");
+        // Texels are copied as is, so atlas has color space of packed textures.
+        atlas_texture.srgb = textures.iter().all(|texture| texture.srgb);
+
+        Ok(TextureAtlas {
+            texture: Arc::new(Mutex::new(atlas_texture)),
+            regions: unique_indices.iter().map(|&i| regions[i]).collect(),
+            paths: unique_indices
+                .iter()
+                .map(|&i| textures[i].path.clone())
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resource::{
+        atlas::TextureAtlasBuilder,
+        texture::{Texture, TextureKind},
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn make_texture(width: u32, height: u32, value: u8) -> Arc<Mutex<Texture>> {
+        let bytes = vec![value; (width * height) as usize];
+        let texture = Texture::from_bytes(width, height, TextureKind::R8, bytes).unwrap();
+        Arc::new(Mutex::new(texture))
+    }
+
+    #[test]
+    fn pack_textures_without_overlapping() {
+        let atlas = TextureAtlasBuilder::new()
+            .with_texture(make_texture(16, 16, 10))
+            .with_texture(make_texture(8, 30, 20))
+            .with_texture(make_texture(5, 5, 30))
+            .with_padding(1)
+            .build()
+            .unwrap();
+
+        let texture = atlas.texture();
+        let texture = texture.lock().unwrap();
+        assert!(texture.width.is_power_of_two());
+        let size = texture.width as f32;
+
+        let regions = atlas.regions();
+        assert_eq!(regions.len(), 3);
+        for (i, a) in regions.iter().enumerate() {
+            for b in regions[(i + 1)..].iter() {
+                let separated = a.max.x <= b.min.x
+                    || b.max.x <= a.min.x
+                    || a.max.y <= b.min.y
+                    || b.max.y <= a.min.y;
+                assert!(separated);
+            }
+        }
+
+        // Center of each region must contain pixels of its texture.
+        for (region, value) in regions.iter().zip([10u8, 20, 30].iter()) {
+            let x = ((region.min.x + region.max.x) * 0.5 * size) as usize;
+            let y = ((region.min.y + region.max.y) * 0.5 * size) as usize;
+            let index = (y * texture.width as usize + x) * 4;
+            assert_eq!(&texture.bytes[index..(index + 4)], &[*value, *value, *value, 255]);
+        }
+
+        assert!(TextureAtlasBuilder::new()
+            .with_texture(make_texture(64, 64, 0))
+            .with_max_size(32)
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,204 @@
+//! Contains support of Basis Universal textures.
+//!
+//! Basis Universal is a supercompressed texture format: the same file (`.basis` file or KTX2
+//! file with Basis data) is transcoded at load time to a format which is supported by GPU of
+//! the platform, so one shipped texture works on every target and stays small on disk.
+//!
+//! Transcoder of Basis Universal is a big library, so it is not built into the engine. Games
+//! which use such textures register a transcoder using `set_transcoder`, for example a thin
+//! wrapper over bindings to the reference transcoder. When Basis texture is loaded, engine
+//! selects target format using compressed formats supported by GPU (they're detected by
+//! renderer when it is created): BC7 if BPTC compression is supported, BC3 or BC1 (depending
+//! on presence of alpha) if S3TC compression is supported, uncompressed RGBA8 otherwise.
+//! Loading of Basis textures fails with `TextureError::UnsupportedFormat` if there is no
+//! transcoder.
+
+use crate::resource::texture::{Texture, TextureError, TextureKind};
+use std::sync::{Arc, Mutex};
+
+/// File that contains Basis data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BasisContainer {
+    /// `.basis` file.
+    Basis,
+    /// KTX2 file with BasisLZ or UASTC data.
+    Ktx2,
+}
+
+/// Description of an image in Basis file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BasisImageInfo {
+    /// Width of the largest mip level.
+    pub width: u32,
+    /// Height of the largest mip level.
+    pub height: u32,
+    /// Whether image has alpha channel or not.
+    pub has_alpha: bool,
+}
+
+/// Transcoder of Basis Universal textures, see module docs.
+pub trait BasisTranscoder: Send + Sync {
+    /// Returns description of first image of given file.
+    fn image_info(&self, data: &[u8], container: BasisContainer)
+        -> Result<BasisImageInfo, String>;
+
+    /// Transcodes every mip level of first image of given file to given format (BC1, BC3, BC7
+    /// or RGBA8). Levels must be returned starting from the largest one.
+    fn transcode(
+        &self,
+        data: &[u8],
+        container: BasisContainer,
+        target: TextureKind,
+    ) -> Result<Vec<Vec<u8>>, String>;
+}
+
+/// Compressed texture formats supported by GPU.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GpuCompressionSupport {
+    /// BC1, BC2 and BC3 formats.
+    pub s3tc: bool,
+    /// BC7 format.
+    pub bptc: bool,
+}
+
+impl GpuCompressionSupport {
+    /// Returns format to which Basis texture will be transcoded.
+    pub fn basis_target(self, has_alpha: bool) -> TextureKind {
+        if self.bptc {
+            TextureKind::BC7
+        } else if self.s3tc {
+            if has_alpha {
+                TextureKind::BC3
+            } else {
+                TextureKind::BC1
+            }
+        } else {
+            TextureKind::RGBA8
+        }
+    }
+}
+
+lazy_static! {
+    static ref TRANSCODER: Mutex<Option<Arc<dyn BasisTranscoder>>> = Mutex::new(None);
+    static ref GPU_COMPRESSION_SUPPORT: Mutex<GpuCompressionSupport> =
+        Mutex::new(Default::default());
+}
+
+/// Sets transcoder which will be used to load Basis textures, None removes current one.
+pub fn set_transcoder(transcoder: Option<Arc<dyn BasisTranscoder>>) {
+    *TRANSCODER.lock().unwrap() = transcoder;
+}
+
+/// Returns compressed formats supported by GPU. Nothing is supported until renderer is
+/// created.
+pub fn gpu_compression_support() -> GpuCompressionSupport {
+    *GPU_COMPRESSION_SUPPORT.lock().unwrap()
+}
+
+pub(in crate) fn set_gpu_compression_support(support: GpuCompressionSupport) {
+    *GPU_COMPRESSION_SUPPORT.lock().unwrap() = support;
+}
+
+/// Loads texture from contents of Basis file using registered transcoder.
+pub(in crate) fn load(data: &[u8], container: BasisContainer) -> Result<Texture, TextureError> {
+    let transcoder = TRANSCODER.lock().unwrap().clone().ok_or_else(|| {
+        TextureError::UnsupportedFormat(
+            "Basis Universal texture, there is no transcoder".to_owned(),
+        )
+    })?;
+
+    let info = transcoder
+        .image_info(data, container)
+        .map_err(TextureError::InvalidData)?;
+    if info.width == 0 || info.height == 0 {
+        return Err(TextureError::InvalidData("Basis texture has zero size".to_owned()));
+    }
+
+    let kind = gpu_compression_support().basis_target(info.has_alpha);
+    let levels = transcoder
+        .transcode(data, container, kind)
+        .map_err(TextureError::InvalidData)?;
+    if levels.is_empty() {
+        return Err(TextureError::InvalidData("Basis texture has no mip levels".to_owned()));
+    }
+
+    let mip_count = levels.len() as u32;
+    let mut bytes =
+        Vec::with_capacity(kind.mip_chain_size_bytes(info.width, info.height, mip_count));
+    for (level, level_data) in levels.iter().enumerate() {
+        let expected_length = kind.image_size_bytes(
+            (info.width >> level).max(1),
+            (info.height >> level).max(1),
+        );
+        if level_data.len() != expected_length {
+            return Err(TextureError::InvalidData(format!(
+                "transcoded mip level {} has size {}, expected {}",
+                level,
+                level_data.len(),
+                expected_length
+            )));
+        }
+        bytes.extend_from_slice(level_data);
+    }
+
+    Ok(Texture {
+        path: Default::default(),
+        width: info.width,
+        height: info.height,
+        bytes,
+        mip_count,
+        kind,
+        loaded: true,
+        sampler: Default::default(),
+        srgb: false,
+        revision: 0,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resource::{
+        basis::{self, BasisContainer, BasisImageInfo, BasisTranscoder, GpuCompressionSupport},
+        texture::TextureKind,
+    };
+    use std::sync::Arc;
+
+    struct SolidColor;
+
+    impl BasisTranscoder for SolidColor {
+        fn image_info(&self, _: &[u8], _: BasisContainer) -> Result<BasisImageInfo, String> {
+            Ok(BasisImageInfo {
+                width: 2,
+                height: 2,
+                has_alpha: false,
+            })
+        }
+
+        fn transcode(
+            &self,
+            _: &[u8],
+            _: BasisContainer,
+            target: TextureKind,
+        ) -> Result<Vec<Vec<u8>>, String> {
+            assert_eq!(target, TextureKind::RGBA8);
+            Ok(vec![vec![255; 16], vec![255; 4]])
+        }
+    }
+
+    #[test]
+    fn transcode_to_supported_format() {
+        let support = GpuCompressionSupport {
+            s3tc: true,
+            bptc: false,
+        };
+        assert_eq!(support.basis_target(false), TextureKind::BC1);
+        assert_eq!(support.basis_target(true), TextureKind::BC3);
+
+        // Renderer is not created in tests, so nothing is supported.
+        basis::set_transcoder(Some(Arc::new(SolidColor)));
+        let texture = basis::load(&[], BasisContainer::Basis).unwrap();
+        assert_eq!(texture.kind(), TextureKind::RGBA8);
+        assert_eq!(texture.mip_count(), 2);
+        assert_eq!(texture.bytes.len(), 20);
+    }
+}
@@ -0,0 +1,592 @@
+//! Contains cube map resource.
+//!
+//! Cube map is a texture made of six square faces of a cube, it is sampled by direction
+//! instead of texture coordinates. Cube maps are used for sky (see
+//! `SceneEnvironment::sky_cube_map`) and as a source of distant lighting when light probes are
+//! baked, because, unlike equirectangular panoramas, they have no distortion near poles and
+//! no seam.
+//!
+//! # Sources
+//!
+//! Cube map can be made from:
+//!
+//! - Six separate face images in `+X, -X, +Y, -Y, +Z, -Z` order (`CubeMap::from_faces`).
+//! - Single image with faces placed in a cross layout (`CubeMap::from_cross`). Horizontal
+//!   cross (4x3 faces) has `+Y` above and `-Y` below `+Z` face, and `-X, +Z, +X, -Z` faces in
+//!   the middle row. Vertical cross (3x4 faces) has same first three rows, and `-Z` face
+//!   rotated by 180 degrees in the last row.
+//! - Equirectangular panorama (`CubeMap::from_equirectangular`), it is converted to cube
+//!   faces of given size when cube map is created. Conversion is done on CPU, so it is slow
+//!   for big faces and should be done once, when a level is loaded.
+//!
+//! Faces use OpenGL cube map convention. Faces must not be compressed, only R8, RGB8, RGBA8,
+//! RGB32F and RGBA32F textures can be used; mip levels of source textures are ignored, cube
+//! map has its own mip levels generated by renderer. Resource manager loads cube maps from
+//! any of these sources using `ResourceManager::request_cube_map`, images are loaded as
+//! RGBA8 textures, HDR and EXR images as float textures. Cube maps made of 8-bit images are
+//! sRGB, float cube maps are linear.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     engine::resource_manager::ResourceManager, resource::cube_map::CubeMapSource,
+//!     scene::Scene,
+//! };
+//!
+//! fn set_sky(scene: &mut Scene, resource_manager: &mut ResourceManager) {
+//!     scene.environment.sky_cube_map =
+//!         resource_manager.request_cube_map(CubeMapSource::Equirectangular {
+//!             path: "data/sky.hdr".into(),
+//!             face_size: 512,
+//!         });
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::vec3::Vec3,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    engine::vfs::Vfs,
+    resource::texture::{Texture, TextureError, TextureKind},
+};
+use std::{
+    f32::consts::PI,
+    fmt::{Display, Formatter},
+    path::PathBuf,
+};
+
+/// Face of cube map.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CubeMapFace {
+    /// Face which is pierced by +X axis.
+    PositiveX,
+    /// Face which is pierced by -X axis.
+    NegativeX,
+    /// Face which is pierced by +Y axis.
+    PositiveY,
+    /// Face which is pierced by -Y axis.
+    NegativeY,
+    /// Face which is pierced by +Z axis.
+    PositiveZ,
+    /// Face which is pierced by -Z axis.
+    NegativeZ,
+}
+
+impl CubeMapFace {
+    /// All faces in order in which they're stored in cube map.
+    pub const ALL: [CubeMapFace; 6] = [
+        CubeMapFace::PositiveX,
+        CubeMapFace::NegativeX,
+        CubeMapFace::PositiveY,
+        CubeMapFace::NegativeY,
+        CubeMapFace::PositiveZ,
+        CubeMapFace::NegativeZ,
+    ];
+
+    /// Returns direction (not normalized) from center of cube to a point of face with given
+    /// texture coordinates in [0; 1] range, (0; 0) is top left corner of face image.
+    pub fn direction(self, u: f32, v: f32) -> Vec3 {
+        let s = 2.0 * u - 1.0;
+        let t = 2.0 * v - 1.0;
+        match self {
+            CubeMapFace::PositiveX => Vec3::new(1.0, -t, -s),
+            CubeMapFace::NegativeX => Vec3::new(-1.0, -t, s),
+            CubeMapFace::PositiveY => Vec3::new(s, 1.0, t),
+            CubeMapFace::NegativeY => Vec3::new(s, -1.0, -t),
+            CubeMapFace::PositiveZ => Vec3::new(s, -t, 1.0),
+            CubeMapFace::NegativeZ => Vec3::new(-s, -t, -1.0),
+        }
+    }
+
+    /// Returns face which is hit by given direction and texture coordinates of the hit point
+    /// on the face. It is inverse of `direction`.
+    pub fn from_direction(direction: Vec3) -> (CubeMapFace, f32, f32) {
+        let (x, y, z) = (direction.x, direction.y, direction.z);
+        let (face, major, s, t) = if x.abs() >= y.abs() && x.abs() >= z.abs() {
+            if x >= 0.0 {
+                (CubeMapFace::PositiveX, x, -z, -y)
+            } else {
+                (CubeMapFace::NegativeX, -x, z, -y)
+            }
+        } else if y.abs() >= z.abs() {
+            if y >= 0.0 {
+                (CubeMapFace::PositiveY, y, x, z)
+            } else {
+                (CubeMapFace::NegativeY, -y, x, -z)
+            }
+        } else if z >= 0.0 {
+            (CubeMapFace::PositiveZ, z, x, -y)
+        } else {
+            (CubeMapFace::NegativeZ, -z, -x, -y)
+        };
+        let major = major.max(std::f32::EPSILON);
+        (face, (s / major + 1.0) * 0.5, (t / major + 1.0) * 0.5)
+    }
+
+    fn index(self) -> usize {
+        match self {
+            CubeMapFace::PositiveX => 0,
+            CubeMapFace::NegativeX => 1,
+            CubeMapFace::PositiveY => 2,
+            CubeMapFace::NegativeY => 3,
+            CubeMapFace::PositiveZ => 4,
+            CubeMapFace::NegativeZ => 5,
+        }
+    }
+}
+
+/// Describes files from which cube map is loaded, see module docs. Resource manager uses it
+/// to identify loaded cube maps, and it is the only thing which is saved with a scene.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CubeMapSource {
+    /// Six face images in `+X, -X, +Y, -Y, +Z, -Z` order.
+    Faces([PathBuf; 6]),
+    /// Single image with faces placed in horizontal or vertical cross layout.
+    Cross(PathBuf),
+    /// Equirectangular panorama which is converted to faces of given size.
+    Equirectangular {
+        /// Path to panorama image.
+        path: PathBuf,
+        /// Size of each face in pixels.
+        face_size: u32,
+    },
+}
+
+impl Default for CubeMapSource {
+    fn default() -> Self {
+        CubeMapSource::Cross(PathBuf::new())
+    }
+}
+
+impl CubeMapSource {
+    fn new(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(CubeMapSource::Faces(Default::default())),
+            1 => Ok(CubeMapSource::Cross(Default::default())),
+            2 => Ok(CubeMapSource::Equirectangular {
+                path: Default::default(),
+                face_size: 0,
+            }),
+            _ => Err(format!("Invalid cube map source {}!", id)),
+        }
+    }
+
+    fn id(&self) -> u32 {
+        match self {
+            CubeMapSource::Faces(_) => 0,
+            CubeMapSource::Cross(_) => 1,
+            CubeMapSource::Equirectangular { .. } => 2,
+        }
+    }
+}
+
+impl Visit for CubeMapSource {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = CubeMapSource::new(id)?;
+        }
+        match self {
+            CubeMapSource::Faces(paths) => {
+                for (i, path) in paths.iter_mut().enumerate() {
+                    path.visit(&format!("Face{}", i), visitor)?;
+                }
+            }
+            CubeMapSource::Cross(path) => path.visit("Path", visitor)?,
+            CubeMapSource::Equirectangular { path, face_size } => {
+                path.visit("Path", visitor)?;
+                face_size.visit("FaceSize", visitor)?;
+            }
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// An error that can occur when cube map is created.
+#[derive(Debug)]
+pub enum CubeMapError {
+    /// Source texture failed to load.
+    Texture(TextureError),
+    /// Compressed textures can't be used for cube maps, string contains path of texture.
+    CompressedTexture(PathBuf),
+    /// Face is not square or differs from other faces in size or kind, string contains path
+    /// of texture.
+    FaceMismatch(PathBuf),
+    /// Size of cross image does not match neither horizontal nor vertical cross layout.
+    InvalidCrossLayout {
+        /// Width of image.
+        width: u32,
+        /// Height of image.
+        height: u32,
+    },
+}
+
+impl Display for CubeMapError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CubeMapError::Texture(e) => write!(f, "Texture error: {}", e),
+            CubeMapError::CompressedTexture(path) => write!(
+                f,
+                "Compressed texture {:?} can't be used for cube map",
+                path
+            ),
+            CubeMapError::FaceMismatch(path) => write!(
+                f,
+                "Texture {:?} is not square or differs from other faces",
+                path
+            ),
+            CubeMapError::InvalidCrossLayout { width, height } => write!(
+                f,
+                "Image of {}x{} size is neither horizontal nor vertical cross",
+                width, height
+            ),
+        }
+    }
+}
+
+impl From<TextureError> for CubeMapError {
+    fn from(e: TextureError) -> Self {
+        CubeMapError::Texture(e)
+    }
+}
+
+/// Returns amount of channels and size of channel in bytes for kinds of textures which can be
+/// used for cube maps.
+fn pixel_layout(kind: TextureKind) -> Option<(usize, usize)> {
+    match kind {
+        TextureKind::R8 => Some((1, 1)),
+        TextureKind::RGB8 => Some((3, 1)),
+        TextureKind::RGBA8 => Some((4, 1)),
+        TextureKind::RGB32F => Some((3, 4)),
+        TextureKind::RGBA32F => Some((4, 4)),
+        _ => None,
+    }
+}
+
+fn read_channel(bytes: &[u8], offset: usize, channel_size: usize) -> f32 {
+    if channel_size == 1 {
+        bytes[offset] as f32 / 255.0
+    } else {
+        let mut value = [0; 4];
+        value.copy_from_slice(&bytes[offset..offset + 4]);
+        f32::from_ne_bytes(value)
+    }
+}
+
+fn write_channel(bytes: &mut Vec<u8>, value: f32, channel_size: usize) {
+    if channel_size == 1 {
+        bytes.push((value.max(0.0).min(1.0) * 255.0 + 0.5) as u8);
+    } else {
+        bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct CubeMap {
+    pub(in crate) source: CubeMapSource,
+    pub(in crate) size: u32,
+    pub(in crate) kind: TextureKind,
+    /// All six faces one after another in `CubeMapFace::ALL` order.
+    pub(in crate) bytes: Vec<u8>,
+    pub(in crate) srgb: bool,
+}
+
+impl Default for CubeMap {
+    fn default() -> Self {
+        Self {
+            source: Default::default(),
+            size: 0,
+            kind: TextureKind::RGBA8,
+            bytes: Vec::new(),
+            srgb: false,
+        }
+    }
+}
+
+impl Visit for CubeMap {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        // Only source is saved, faces are loaded again by resource manager.
+        self.source.visit("Source", visitor)?;
+        self.srgb.visit("Srgb", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+impl CubeMap {
+    /// Loads cube map from files of given source using virtual file system.
+    pub(in crate) fn load(source: &CubeMapSource, vfs: &Vfs) -> Result<Self, CubeMapError> {
+        let load = |path: &PathBuf| Texture::load(path, TextureKind::RGBA8, vfs);
+        let mut cube_map = match source {
+            CubeMapSource::Faces(paths) => {
+                let mut faces = Vec::with_capacity(paths.len());
+                for path in paths.iter() {
+                    faces.push(load(path)?);
+                }
+                Self::from_faces([
+                    &faces[0], &faces[1], &faces[2], &faces[3], &faces[4], &faces[5],
+                ])?
+            }
+            CubeMapSource::Cross(path) => Self::from_cross(&load(path)?)?,
+            CubeMapSource::Equirectangular { path, face_size } => {
+                Self::from_equirectangular(&load(path)?, *face_size)?
+            }
+        };
+        cube_map.source = source.clone();
+        cube_map.srgb = !cube_map.kind.is_hdr() && cube_map.kind != TextureKind::R8;
+        Ok(cube_map)
+    }
+
+    /// Creates cube map from six square face textures of same size and kind, in
+    /// `CubeMapFace::ALL` order. Cube map is sRGB if first face is sRGB.
+    pub fn from_faces(faces: [&Texture; 6]) -> Result<Self, CubeMapError> {
+        let first = faces[0];
+        let (channels, channel_size) = pixel_layout(first.kind)
+            .ok_or_else(|| CubeMapError::CompressedTexture(first.path.clone()))?;
+        let face_size_bytes = (first.width * first.width) as usize * channels * channel_size;
+        let mut bytes = Vec::with_capacity(6 * face_size_bytes);
+        for face in faces.iter() {
+            if face.kind != first.kind
+                || face.width != first.width
+                || face.height != first.width
+                || face.bytes.len() < face_size_bytes
+            {
+                return Err(CubeMapError::FaceMismatch(face.path.clone()));
+            }
+            bytes.extend_from_slice(&face.bytes[..face_size_bytes]);
+        }
+        Ok(Self {
+            source: Default::default(),
+            size: first.width,
+            kind: first.kind,
+            bytes,
+            srgb: first.srgb,
+        })
+    }
+
+    /// Creates cube map from image with faces placed in horizontal or vertical cross layout,
+    /// see module docs. Layout is determined by size of image.
+    pub fn from_cross(texture: &Texture) -> Result<Self, CubeMapError> {
+        let (channels, channel_size) = pixel_layout(texture.kind)
+            .ok_or_else(|| CubeMapError::CompressedTexture(texture.path.clone()))?;
+        let pixel_size = channels * channel_size;
+        let (width, height) = (texture.width, texture.height);
+        let (size, vertical) = if width * 3 == height * 4 {
+            (width / 4, false)
+        } else if width * 4 == height * 3 {
+            (width / 3, true)
+        } else {
+            return Err(CubeMapError::InvalidCrossLayout { width, height });
+        };
+        if size == 0 || texture.bytes.len() < texture.kind.image_size_bytes(width, height) {
+            return Err(CubeMapError::InvalidCrossLayout { width, height });
+        }
+
+        // Column and row of each face in the cross and whether face is rotated by 180 degrees.
+        let negative_z = if vertical { (1, 3, true) } else { (3, 1, false) };
+        let cells = [(2, 1, false), (0, 1, false), (1, 0, false), (1, 2, false), (1, 1, false)];
+
+        let size = size as usize;
+        let mut bytes = Vec::with_capacity(6 * size * size * pixel_size);
+        for &(column, row, rotated) in cells.iter().chain(std::iter::once(&negative_z)) {
+            for y in 0..size {
+                for x in 0..size {
+                    let (x, y) = if rotated {
+                        (size - 1 - x, size - 1 - y)
+                    } else {
+                        (x, y)
+                    };
+                    let offset =
+                        ((row * size + y) * width as usize + column * size + x) * pixel_size;
+                    bytes.extend_from_slice(&texture.bytes[offset..offset + pixel_size]);
+                }
+            }
+        }
+
+        Ok(Self {
+            source: Default::default(),
+            size: size as u32,
+            kind: texture.kind,
+            bytes,
+            srgb: texture.srgb,
+        })
+    }
+
+    /// Creates cube map with faces of given size from equirectangular panorama, this is the
+    /// same mapping that is used for `SceneEnvironment::skybox` textures. Each texel of faces
+    /// is bilinearly sampled from the panorama.
+    pub fn from_equirectangular(texture: &Texture, face_size: u32) -> Result<Self, CubeMapError> {
+        let (channels, channel_size) = pixel_layout(texture.kind)
+            .ok_or_else(|| CubeMapError::CompressedTexture(texture.path.clone()))?;
+        let (width, height) = (texture.width as usize, texture.height as usize);
+        if width == 0
+            || height == 0
+            || face_size == 0
+            || texture.bytes.len() < texture.kind.image_size_bytes(texture.width, texture.height)
+        {
+            return Err(CubeMapError::Texture(TextureError::InvalidData(format!(
+                "Panorama {:?} or faces have zero size",
+                texture.path
+            ))));
+        }
+
+        let pixel_size = channels * channel_size;
+        let fetch = |x: usize, y: usize, channel: usize| {
+            read_channel(
+                &texture.bytes,
+                (y * width + x) * pixel_size + channel * channel_size,
+                channel_size,
+            )
+        };
+
+        let size = face_size as usize;
+        let mut bytes = Vec::with_capacity(6 * size * size * pixel_size);
+        for face in CubeMapFace::ALL.iter() {
+            for y in 0..size {
+                for x in 0..size {
+                    let u = (x as f32 + 0.5) / size as f32;
+                    let v = (y as f32 + 0.5) / size as f32;
+                    let d = face.direction(u, v);
+                    let d = d.scale(1.0 / d.len());
+                    // Must be in sync with environment shader.
+                    let eu = d.z.atan2(d.x) / (2.0 * PI) + 0.5;
+                    let ev = d.y.max(-1.0).min(1.0).acos() / PI;
+
+                    // Bilinear filtering, panorama wraps horizontally.
+                    let px = eu * width as f32 - 0.5;
+                    let py = (ev * height as f32 - 0.5).max(0.0).min((height - 1) as f32);
+                    let (fx, fy) = (px - px.floor(), py - py.floor());
+                    let x0 = (px.floor() as isize).rem_euclid(width as isize) as usize;
+                    let x1 = (x0 + 1) % width;
+                    let y0 = py.floor() as usize;
+                    let y1 = (y0 + 1).min(height - 1);
+                    for channel in 0..channels {
+                        let top = fetch(x0, y0, channel) * (1.0 - fx) + fetch(x1, y0, channel) * fx;
+                        let bottom =
+                            fetch(x0, y1, channel) * (1.0 - fx) + fetch(x1, y1, channel) * fx;
+                        write_channel(&mut bytes, top * (1.0 - fy) + bottom * fy, channel_size);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            source: Default::default(),
+            size: face_size,
+            kind: texture.kind,
+            bytes,
+            srgb: texture.srgb,
+        })
+    }
+
+    /// Returns source of cube map. Cube maps which were not loaded by resource manager have
+    /// default source, such cube maps can't be restored when a scene is loaded.
+    pub fn source(&self) -> &CubeMapSource {
+        &self.source
+    }
+
+    /// Returns true if cube map has faces. Cube map which was loaded from a save file has no
+    /// faces until resources of the scene are restored by resource manager.
+    pub fn is_loaded(&self) -> bool {
+        !self.bytes.is_empty()
+    }
+
+    /// Returns size of each face in pixels.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns kind of faces.
+    pub fn kind(&self) -> TextureKind {
+        self.kind
+    }
+
+    /// Returns true if cube map is in sRGB color space, see `texture` module docs.
+    pub fn is_srgb(&self) -> bool {
+        self.srgb
+    }
+
+    /// Returns pixels of given face.
+    pub fn face_data(&self, face: CubeMapFace) -> &[u8] {
+        let face_size_bytes = self.bytes.len() / 6;
+        let begin = face.index() * face_size_bytes;
+        &self.bytes[begin..begin + face_size_bytes]
+    }
+
+    /// Returns color in linear color space which cube map has in given direction (nearest
+    /// texel). Cube maps with one channel are grayscale. Returns zero vector if cube map is
+    /// not loaded.
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let (channels, channel_size) = match pixel_layout(self.kind) {
+            Some(layout) if self.is_loaded() => layout,
+            _ => return Vec3::ZERO,
+        };
+        let (face, u, v) = CubeMapFace::from_direction(direction);
+        let size = self.size as usize;
+        let x = ((u * size as f32) as usize).min(size - 1);
+        let y = ((v * size as f32) as usize).min(size - 1);
+        let data = self.face_data(face);
+        let offset = (y * size + x) * channels * channel_size;
+        let channel = |i: usize| {
+            let offset = offset + i.min(channels - 1) * channel_size;
+            let value = read_channel(data, offset, channel_size);
+            if self.srgb {
+                srgb_to_linear(value)
+            } else {
+                value
+            }
+        };
+        Vec3::new(channel(0), channel(1), channel(2))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resource::{
+        cube_map::{CubeMap, CubeMapFace},
+        texture::{Texture, TextureKind},
+    };
+
+    #[test]
+    fn face_direction_round_trip() {
+        for &face in CubeMapFace::ALL.iter() {
+            for &(u, v) in [(0.5, 0.5), (0.1, 0.3), (0.8, 0.6)].iter() {
+                let (hit_face, hit_u, hit_v) = CubeMapFace::from_direction(face.direction(u, v));
+                assert_eq!(hit_face, face);
+                assert!((hit_u - u).abs() < 1.0e-5 && (hit_v - v).abs() < 1.0e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn horizontal_cross_faces() {
+        // 4x3 cross of 1x1 faces, each cell has its own value.
+        let bytes = (0..12).collect::<Vec<u8>>();
+        let texture = Texture::from_bytes(4, 3, TextureKind::R8, bytes).unwrap();
+        let cube_map = CubeMap::from_cross(&texture).unwrap();
+        assert_eq!(cube_map.size(), 1);
+        assert_eq!(cube_map.face_data(CubeMapFace::PositiveX), &[6]);
+        assert_eq!(cube_map.face_data(CubeMapFace::NegativeX), &[4]);
+        assert_eq!(cube_map.face_data(CubeMapFace::PositiveY), &[1]);
+        assert_eq!(cube_map.face_data(CubeMapFace::NegativeY), &[9]);
+        assert_eq!(cube_map.face_data(CubeMapFace::PositiveZ), &[5]);
+        assert_eq!(cube_map.face_data(CubeMapFace::NegativeZ), &[7]);
+    }
+}
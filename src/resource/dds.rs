@@ -0,0 +1,184 @@
+//! Loader of block-compressed textures from DirectDraw Surface (DDS) files. Format is
+//! described here:
+//! https://docs.microsoft.com/en-us/windows/win32/direct3ddds/dx-graphics-dds-pguide
+
+use crate::resource::texture::{Texture, TextureError, TextureKind};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+const MAGIC: &[u8; 4] = b"DDS ";
+const HEADER_SIZE: u32 = 124;
+const FLAG_MIPMAP_COUNT: u32 = 0x20000;
+const PIXEL_FORMAT_FLAG_FOURCC: u32 = 0x4;
+const CAPS2_CUBEMAP: u32 = 0x200;
+const CAPS2_VOLUME: u32 = 0x200000;
+
+fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+fn kind_from_dxgi_format(format: u32) -> Result<TextureKind, TextureError> {
+    // Both UNORM and SRGB variants are accepted.
+    match format {
+        71 | 72 => Ok(TextureKind::BC1),
+        74 | 75 => Ok(TextureKind::BC2),
+        77 | 78 => Ok(TextureKind::BC3),
+        80 => Ok(TextureKind::BC4),
+        83 => Ok(TextureKind::BC5),
+        95 => Ok(TextureKind::BC6H),
+        98 | 99 => Ok(TextureKind::BC7),
+        _ => Err(TextureError::UnsupportedFormat(format!(
+            "DXGI format {} in DDS file",
+            format
+        ))),
+    }
+}
+
+/// Loads texture from contents of DDS file. Only block-compressed 2D textures are supported.
+pub(in crate) fn load(data: &[u8]) -> Result<Texture, TextureError> {
+    let mut reader = Cursor::new(data);
+
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(TextureError::InvalidData("not a DDS file".to_owned()));
+    }
+
+    let header_size = reader.read_u32::<LittleEndian>()?;
+    if header_size != HEADER_SIZE {
+        return Err(TextureError::InvalidData(format!(
+            "invalid size of DDS header {}",
+            header_size
+        )));
+    }
+    let flags = reader.read_u32::<LittleEndian>()?;
+    let height = reader.read_u32::<LittleEndian>()?;
+    let width = reader.read_u32::<LittleEndian>()?;
+    let _pitch_or_linear_size = reader.read_u32::<LittleEndian>()?;
+    let _depth = reader.read_u32::<LittleEndian>()?;
+    let mip_count = reader.read_u32::<LittleEndian>()?;
+    // Skip reserved fields and size of pixel format.
+    reader.seek(SeekFrom::Current(11 * 4 + 4))?;
+    let pixel_format_flags = reader.read_u32::<LittleEndian>()?;
+    let four_cc = reader.read_u32::<LittleEndian>()?;
+    // Skip bit count and masks of uncompressed formats, and first caps.
+    reader.seek(SeekFrom::Current(5 * 4 + 4))?;
+    let caps2 = reader.read_u32::<LittleEndian>()?;
+    // Skip rest of caps and reserved field.
+    reader.seek(SeekFrom::Current(3 * 4))?;
+
+    if caps2 & CAPS2_CUBEMAP != 0 {
+        return Err(TextureError::UnsupportedFormat(
+            "cube maps in DDS files are not supported".to_owned(),
+        ));
+    }
+    if caps2 & CAPS2_VOLUME != 0 {
+        return Err(TextureError::UnsupportedFormat(
+            "volume textures in DDS files are not supported".to_owned(),
+        ));
+    }
+    if pixel_format_flags & PIXEL_FORMAT_FLAG_FOURCC == 0 {
+        return Err(TextureError::UnsupportedFormat(
+            "uncompressed DDS files are not supported, use other image format".to_owned(),
+        ));
+    }
+
+    let kind = if four_cc == fourcc(b"DXT1") {
+        TextureKind::BC1
+    } else if four_cc == fourcc(b"DXT2") || four_cc == fourcc(b"DXT3") {
+        TextureKind::BC2
+    } else if four_cc == fourcc(b"DXT4") || four_cc == fourcc(b"DXT5") {
+        TextureKind::BC3
+    } else if four_cc == fourcc(b"ATI1") || four_cc == fourcc(b"BC4U") {
+        TextureKind::BC4
+    } else if four_cc == fourcc(b"ATI2") || four_cc == fourcc(b"BC5U") {
+        TextureKind::BC5
+    } else if four_cc == fourcc(b"DX10") {
+        let dxgi_format = reader.read_u32::<LittleEndian>()?;
+        let _resource_dimension = reader.read_u32::<LittleEndian>()?;
+        let _misc_flags = reader.read_u32::<LittleEndian>()?;
+        let array_size = reader.read_u32::<LittleEndian>()?;
+        let _misc_flags2 = reader.read_u32::<LittleEndian>()?;
+        if array_size > 1 {
+            return Err(TextureError::UnsupportedFormat(
+                "texture arrays in DDS files are not supported".to_owned(),
+            ));
+        }
+        kind_from_dxgi_format(dxgi_format)?
+    } else {
+        return Err(TextureError::UnsupportedFormat(format!(
+            "DDS pixel format {:?}",
+            String::from_utf8_lossy(&four_cc.to_le_bytes())
+        )));
+    };
+
+    if width == 0 || height == 0 {
+        return Err(TextureError::InvalidData("DDS texture has zero size".to_owned()));
+    }
+
+    let mip_count = if flags & FLAG_MIPMAP_COUNT != 0 {
+        mip_count.max(1)
+    } else {
+        1
+    };
+
+    let data_offset = reader.position() as usize;
+    let size = kind.mip_chain_size_bytes(width, height, mip_count);
+    let bytes = data
+        .get(data_offset..(data_offset + size))
+        .ok_or_else(|| {
+            TextureError::InvalidData(format!(
+                "DDS file is truncated, expected {} bytes of pixels, got {}",
+                size,
+                data.len() - data_offset
+            ))
+        })?
+        .to_vec();
+
+    Ok(Texture {
+        path: Default::default(),
+        width,
+        height,
+        bytes,
+        mip_count,
+        kind,
+        loaded: true,
+        sampler: Default::default(),
+        srgb: false,
+        revision: 0,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resource::{dds, texture::TextureKind};
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    #[test]
+    fn load_dxt5_with_mips() {
+        let mut data = b"DDS ".to_vec();
+        let mut header = [0u32; 31];
+        header[0] = 124;
+        header[1] = 0x1 | 0x2 | 0x4 | 0x1000 | 0x20000;
+        header[2] = 8; // Height
+        header[3] = 8; // Width
+        header[6] = 4; // Mip count
+        header[18] = 32; // Size of pixel format
+        header[19] = 0x4; // Four CC flag
+        header[20] = u32::from_le_bytes(*b"DXT5");
+        for value in header.iter() {
+            data.write_u32::<LittleEndian>(*value).unwrap();
+        }
+        // 8x8, 4x4, 2x2 and 1x1 levels take 4, 1, 1 and 1 blocks.
+        data.resize(data.len() + 7 * 16, 0xAA);
+
+        let texture = dds::load(&data).unwrap();
+        assert_eq!(texture.kind(), TextureKind::BC3);
+        assert_eq!(texture.mip_count(), 4);
+        assert_eq!(texture.bytes.len(), 7 * 16);
+
+        // Truncated mip chain must be reported.
+        data.truncate(data.len() - 16);
+        assert!(dds::load(&data).is_err());
+    }
+}
@@ -0,0 +1,359 @@
+//! Loader of OpenEXR images. Format is described here:
+//! https://www.openexr.com/documentation/openexrfilelayout.pdf
+//!
+//! Only single-part scanline files with uncompressed, ZIPS or ZIP compressed data are
+//! supported. R, G, B and A channels are loaded (Y channel is used for grayscale images),
+//! other channels are ignored.
+
+use crate::resource::texture::{Texture, TextureError, TextureKind};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{BufRead, Cursor, Read, Seek, SeekFrom};
+
+const MAGIC: u32 = 20_000_630;
+const FLAG_TILED: u32 = 0x200;
+const FLAG_DEEP: u32 = 0x800;
+const FLAG_MULTI_PART: u32 = 0x1000;
+const PIXEL_TYPE_UINT: i32 = 0;
+const PIXEL_TYPE_HALF: i32 = 1;
+const PIXEL_TYPE_FLOAT: i32 = 2;
+const COMPRESSION_NONE: u8 = 0;
+const COMPRESSION_ZIPS: u8 = 2;
+const COMPRESSION_ZIP: u8 = 3;
+
+fn invalid_data<S: Into<String>>(message: S) -> TextureError {
+    TextureError::InvalidData(message.into())
+}
+
+struct Channel {
+    name: String,
+    pixel_type: i32,
+}
+
+impl Channel {
+    fn size_bytes(&self) -> usize {
+        if self.pixel_type == PIXEL_TYPE_HALF {
+            2
+        } else {
+            4
+        }
+    }
+}
+
+/// Converts 16-bit float to 32-bit float.
+fn half_to_f32(half: u16) -> f32 {
+    let sign = ((half >> 15) as u32) << 31;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    let bits = match exponent {
+        0 => {
+            if mantissa == 0 {
+                sign
+            } else {
+                // Denormalized half is normalized float.
+                let mut exponent = 127 - 15 + 1;
+                let mut mantissa = mantissa;
+                while mantissa & 0x400 == 0 {
+                    mantissa <<= 1;
+                    exponent -= 1;
+                }
+                sign | (exponent << 23) | ((mantissa & 0x3ff) << 13)
+            }
+        }
+        // Infinity or NaN.
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+fn read_string<R: BufRead>(reader: &mut R) -> Result<String, TextureError> {
+    let mut bytes = Vec::new();
+    reader.read_until(0, &mut bytes)?;
+    if bytes.pop() != Some(0) {
+        return Err(invalid_data("unterminated string in EXR header"));
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn read_channels(data: &[u8]) -> Result<Vec<Channel>, TextureError> {
+    let mut reader = Cursor::new(data);
+    let mut channels = Vec::new();
+    loop {
+        let name = read_string(&mut reader)?;
+        if name.is_empty() {
+            return Ok(channels);
+        }
+        let pixel_type = reader.read_i32::<LittleEndian>()?;
+        // Linear flag and reserved bytes.
+        reader.seek(SeekFrom::Current(4))?;
+        let x_sampling = reader.read_i32::<LittleEndian>()?;
+        let y_sampling = reader.read_i32::<LittleEndian>()?;
+        if x_sampling != 1 || y_sampling != 1 {
+            return Err(TextureError::UnsupportedFormat(
+                "EXR file with subsampled channels".to_owned(),
+            ));
+        }
+        if !(PIXEL_TYPE_UINT..=PIXEL_TYPE_FLOAT).contains(&pixel_type) {
+            return Err(invalid_data(format!("invalid EXR pixel type {}", pixel_type)));
+        }
+        channels.push(Channel { name, pixel_type });
+    }
+}
+
+/// Undoes reordering and delta encoding that is applied to data before ZIP compression.
+fn unpredict(mut data: Vec<u8>) -> Vec<u8> {
+    for i in 1..data.len() {
+        data[i] = data[i - 1].wrapping_add(data[i]).wrapping_sub(128);
+    }
+    // First half of the data contains even bytes, second half contains odd bytes.
+    let (even, odd) = data.split_at((data.len() + 1) / 2);
+    let mut result = Vec::with_capacity(data.len());
+    for (i, &byte) in even.iter().enumerate() {
+        result.push(byte);
+        if let Some(&byte) = odd.get(i) {
+            result.push(byte);
+        }
+    }
+    result
+}
+
+fn read_value(data: &mut Cursor<&[u8]>, pixel_type: i32) -> Result<f32, TextureError> {
+    Ok(match pixel_type {
+        PIXEL_TYPE_UINT => data.read_u32::<LittleEndian>()? as f32,
+        PIXEL_TYPE_HALF => half_to_f32(data.read_u16::<LittleEndian>()?),
+        _ => data.read_f32::<LittleEndian>()?,
+    })
+}
+
+/// Loads texture from contents of EXR file, pixels are stored as `RGBA32F` if file has alpha
+/// channel or as `RGB32F` otherwise.
+pub(in crate) fn load(data: &[u8]) -> Result<Texture, TextureError> {
+    let mut reader = Cursor::new(data);
+
+    if reader.read_u32::<LittleEndian>()? != MAGIC {
+        return Err(invalid_data("not an EXR file"));
+    }
+    let version = reader.read_u32::<LittleEndian>()?;
+    if version & (FLAG_TILED | FLAG_DEEP | FLAG_MULTI_PART) != 0 {
+        return Err(TextureError::UnsupportedFormat(
+            "tiled, deep or multi-part EXR file".to_owned(),
+        ));
+    }
+
+    let mut channels = None;
+    let mut compression = None;
+    let mut data_window = None;
+    loop {
+        let name = read_string(&mut reader)?;
+        if name.is_empty() {
+            break;
+        }
+        let _type_name = read_string(&mut reader)?;
+        let size = reader.read_i32::<LittleEndian>()?;
+        if size < 0 {
+            return Err(invalid_data("invalid size of EXR attribute"));
+        }
+        let mut value = vec![0; size as usize];
+        reader.read_exact(&mut value)?;
+        match name.as_str() {
+            "channels" => channels = Some(read_channels(&value)?),
+            "compression" => compression = value.first().cloned(),
+            "dataWindow" => {
+                let mut window = Cursor::new(&value);
+                let mut coordinates = [0; 4];
+                for coordinate in coordinates.iter_mut() {
+                    *coordinate = window.read_i32::<LittleEndian>()?;
+                }
+                data_window = Some(coordinates);
+            }
+            _ => (),
+        }
+    }
+
+    let channels = channels.ok_or_else(|| invalid_data("EXR file has no channels"))?;
+    let compression = compression.ok_or_else(|| invalid_data("EXR file has no compression"))?;
+    let [x_min, y_min, x_max, y_max] =
+        data_window.ok_or_else(|| invalid_data("EXR file has no data window"))?;
+    if x_max < x_min || y_max < y_min {
+        return Err(invalid_data("invalid data window of EXR file"));
+    }
+    let width = (x_max as i64 - x_min as i64 + 1) as usize;
+    let height = (y_max as i64 - y_min as i64 + 1) as usize;
+
+    let lines_per_block = match compression {
+        COMPRESSION_NONE | COMPRESSION_ZIPS => 1,
+        COMPRESSION_ZIP => 16,
+        _ => {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "EXR compression {}",
+                compression
+            )))
+        }
+    };
+
+    // Output channel for each channel of file, None if channel is ignored.
+    let find = |name: &str| channels.iter().any(|channel| channel.name == name);
+    let has_alpha = find("A");
+    let grayscale = !find("R") && !find("G") && !find("B") && find("Y");
+    let targets = channels
+        .iter()
+        .map(|channel| match channel.name.as_str() {
+            "R" => Some(vec![0]),
+            "G" => Some(vec![1]),
+            "B" => Some(vec![2]),
+            "A" => Some(vec![3]),
+            "Y" if grayscale => Some(vec![0, 1, 2]),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let components = if has_alpha { 4 } else { 3 };
+    let mut pixels = vec![0.0f32; width * height * components];
+    if has_alpha {
+        // Pixels without alpha channel are opaque.
+        for pixel in pixels.chunks_mut(4) {
+            pixel[3] = 1.0;
+        }
+    }
+
+    let line_size = channels
+        .iter()
+        .map(|channel| channel.size_bytes() * width)
+        .sum::<usize>();
+    let block_count = (height + lines_per_block - 1) / lines_per_block;
+    let mut offsets = Vec::with_capacity(block_count);
+    for _ in 0..block_count {
+        offsets.push(reader.read_u64::<LittleEndian>()?);
+    }
+
+    for offset in offsets {
+        reader.seek(SeekFrom::Start(offset))?;
+        let y = reader.read_i32::<LittleEndian>()? as i64 - y_min as i64;
+        let size = reader.read_i32::<LittleEndian>()?;
+        if y < 0 || y as usize >= height || size < 0 {
+            return Err(invalid_data("invalid block of EXR file"));
+        }
+        let y = y as usize;
+        let line_count = lines_per_block.min(height - y);
+        let mut block = vec![0; size as usize];
+        reader.read_exact(&mut block)?;
+
+        // Data is stored as is if compression does not make it smaller.
+        let expected_size = line_size * line_count;
+        let block = if compression == COMPRESSION_NONE || block.len() == expected_size {
+            block
+        } else {
+            unpredict(inflate::inflate_bytes_zlib(&block).map_err(invalid_data)?)
+        };
+        if block.len() != expected_size {
+            return Err(invalid_data("invalid size of EXR block"));
+        }
+
+        let mut block = Cursor::new(block.as_slice());
+        for line in y..(y + line_count) {
+            for (channel, targets) in channels.iter().zip(targets.iter()) {
+                for x in 0..width {
+                    let value = read_value(&mut block, channel.pixel_type)?;
+                    if let Some(targets) = targets {
+                        let pixel = (line * width + x) * components;
+                        for &target in targets {
+                            pixels[pixel + target] = value;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut bytes = Vec::with_capacity(pixels.len() * 4);
+    for value in pixels {
+        bytes.extend_from_slice(&value.to_ne_bytes());
+    }
+
+    Ok(Texture {
+        path: Default::default(),
+        width: width as u32,
+        height: height as u32,
+        bytes,
+        mip_count: 1,
+        kind: if has_alpha {
+            TextureKind::RGBA32F
+        } else {
+            TextureKind::RGB32F
+        },
+        loaded: true,
+        sampler: Default::default(),
+        srgb: false,
+        revision: 0,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resource::{
+        exr::{self, half_to_f32},
+        texture::TextureKind,
+    };
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    fn write_attribute(data: &mut Vec<u8>, name: &str, type_name: &str, value: &[u8]) {
+        data.extend_from_slice(name.as_bytes());
+        data.push(0);
+        data.extend_from_slice(type_name.as_bytes());
+        data.push(0);
+        data.write_i32::<LittleEndian>(value.len() as i32).unwrap();
+        data.extend_from_slice(value);
+    }
+
+    #[test]
+    fn load_uncompressed_half_rgb() {
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2.0f32.powi(-24));
+
+        let mut data = Vec::new();
+        data.write_u32::<LittleEndian>(20_000_630).unwrap();
+        data.write_u32::<LittleEndian>(2).unwrap();
+
+        // Channels are stored in alphabetical order.
+        let mut channels = Vec::new();
+        for name in &["B", "G", "R"] {
+            channels.extend_from_slice(name.as_bytes());
+            channels.push(0);
+            channels.write_i32::<LittleEndian>(1).unwrap();
+            channels.write_i32::<LittleEndian>(0).unwrap();
+            channels.write_i32::<LittleEndian>(1).unwrap();
+            channels.write_i32::<LittleEndian>(1).unwrap();
+        }
+        channels.push(0);
+        write_attribute(&mut data, "channels", "chlist", &channels);
+        write_attribute(&mut data, "compression", "compression", &[0]);
+        let mut window = Vec::new();
+        for value in &[0, 0, 1, 0] {
+            window.write_i32::<LittleEndian>(*value).unwrap();
+        }
+        write_attribute(&mut data, "dataWindow", "box2i", &window);
+        data.push(0);
+
+        // One scanline of 2 pixels: 3 channels by 2 values of 2 bytes.
+        let offset = data.len() as u64 + 8;
+        data.write_u64::<LittleEndian>(offset).unwrap();
+        data.write_i32::<LittleEndian>(0).unwrap();
+        data.write_i32::<LittleEndian>(12).unwrap();
+        // B, G and R of both pixels.
+        for value in &[0x0000u16, 0x3c00, 0x3800, 0x3800, 0x3c00, 0x4000] {
+            data.write_u16::<LittleEndian>(*value).unwrap();
+        }
+
+        let texture = exr::load(&data).unwrap();
+        assert_eq!(texture.kind(), TextureKind::RGB32F);
+        assert_eq!(texture.width, 2);
+        assert_eq!(texture.height, 1);
+        let pixels = texture
+            .bytes
+            .chunks(4)
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect::<Vec<_>>();
+        assert_eq!(pixels, vec![1.0, 0.5, 0.0, 2.0, 0.5, 1.0]);
+    }
+}
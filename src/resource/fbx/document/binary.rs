@@ -1,7 +1,10 @@
 use crate::{
     core::pool::{Handle, Pool},
     resource::fbx::{
-        document::{attribute::FbxAttribute, FbxDocument, FbxNode, FbxNodeContainer},
+        document::{
+            attribute::FbxAttribute, FbxDocument, FbxNode, FbxNodeContainer,
+            MIN_SUPPORTED_VERSION,
+        },
         error::FbxError,
     },
 };
@@ -25,6 +28,40 @@ where
     }
 }
 
+/// Layout of node records, it depends on version of file.
+#[derive(Copy, Clone)]
+enum RecordFormat {
+    /// Offsets and counts are 32-bit, used by FBX 7.4 and older.
+    Narrow,
+    /// Offsets and counts are 64-bit, used since FBX 7.5 (FBX 2016) to support large files.
+    Wide,
+}
+
+impl RecordFormat {
+    fn from_version(version: i32) -> Self {
+        if version >= 7500 {
+            RecordFormat::Wide
+        } else {
+            RecordFormat::Narrow
+        }
+    }
+
+    fn read_offset<R: Read>(self, file: &mut R) -> Result<u64, FbxError> {
+        match self {
+            RecordFormat::Narrow => Ok(u64::from(file.read_u32::<LittleEndian>()?)),
+            RecordFormat::Wide => Ok(file.read_u64::<LittleEndian>()?),
+        }
+    }
+
+    /// Size of null record which marks end of list of nested nodes.
+    fn null_record_size(self) -> u64 {
+        match self {
+            RecordFormat::Narrow => 13,
+            RecordFormat::Wide => 25,
+        }
+    }
+}
+
 fn read_array<R>(type_code: u8, file: &mut R) -> Result<Vec<FbxAttribute>, FbxError>
 where
     R: Read,
@@ -38,7 +75,7 @@ where
         for _ in 0..length {
             array.push(read_attribute(type_code, file)?);
         }
-    } else {
+    } else if encoding == 1 {
         let mut compressed = Vec::with_capacity(compressed_length);
         unsafe { compressed.set_len(compressed_length) };
         file.read_exact(compressed.as_mut_slice())?;
@@ -47,6 +84,11 @@ where
         for _ in 0..length {
             array.push(read_attribute(type_code, &mut cursor)?);
         }
+    } else {
+        return Err(FbxError::UnsupportedFeature(format!(
+            "array encoding {}",
+            encoding
+        )));
     }
 
     Ok(array)
@@ -76,18 +118,29 @@ where
 /// https://code.blender.org/2013/08/fbx-binary-file-format-specification/
 /// In case of success returns Ok(valid_handle), in case if no more nodes
 /// are present returns Ok(none_handle), in case of error returns some FbxError.
-fn read_binary_node<R>(file: &mut R, pool: &mut Pool<FbxNode>) -> Result<Handle<FbxNode>, FbxError>
+fn read_binary_node<R>(
+    file: &mut R,
+    pool: &mut Pool<FbxNode>,
+    format: RecordFormat,
+    total_length: u64,
+) -> Result<Handle<FbxNode>, FbxError>
 where
     R: Read + Seek,
 {
-    let end_offset = u64::from(file.read_u32::<LittleEndian>()?);
+    let end_offset = format.read_offset(file)?;
     if end_offset == 0 {
         // Footer found. We're done.
         return Ok(Handle::NONE);
     }
+    if end_offset > total_length {
+        return Err(FbxError::Custom(Box::new(format!(
+            "Corrupted binary FBX: node record ends at {}, but file length is {}",
+            end_offset, total_length
+        ))));
+    }
 
-    let num_attrib = file.read_u32::<LittleEndian>()? as usize;
-    let _attrib_list_len = file.read_u32::<LittleEndian>()?;
+    let num_attrib = format.read_offset(file)? as usize;
+    let _attrib_list_len = format.read_offset(file)?;
 
     // Read name.
     let name_len = file.read_u8()? as usize;
@@ -125,14 +178,15 @@ where
                 let length = i64::from(file.read_u32::<LittleEndian>()?);
                 file.seek(SeekFrom::Current(length))?;
             }
-            _ => (),
+            _ => return Err(FbxError::UnknownAttributeType(type_code)),
         }
     }
 
     if file.seek(SeekFrom::Current(0))? < end_offset {
-        let null_record_position = end_offset - 13;
+        let null_record_size = format.null_record_size();
+        let null_record_position = end_offset.saturating_sub(null_record_size);
         while file.seek(SeekFrom::Current(0))? < null_record_position {
-            let child_handle = read_binary_node(file, pool)?;
+            let child_handle = read_binary_node(file, pool, format, total_length)?;
             if child_handle.is_none() {
                 return Ok(child_handle);
             }
@@ -141,8 +195,9 @@ where
         }
 
         // Check if we have a null-record
-        let mut null_record = [0; 13];
-        file.read_exact(&mut null_record)?;
+        let mut null_record = [0; 25];
+        let null_record = &mut null_record[..null_record_size as usize];
+        file.read_exact(null_record)?;
         if !null_record.iter().all(|i| *i == 0) {
             return Err(FbxError::InvalidNullRecord);
         }
@@ -162,11 +217,13 @@ where
     let mut temp = [0; 23];
     file.read_exact(&mut temp)?;
 
-    // Verify version.
+    // Verify version. Newer versions are checked when scene is read, here we only need to
+    // know layout of records.
     let version = file.read_u32::<LittleEndian>()? as i32;
-    if version < 7100 || version > 7400 {
+    if version < MIN_SUPPORTED_VERSION {
         return Err(FbxError::UnsupportedVersion(version));
     }
+    let format = RecordFormat::from_version(version);
 
     let mut nodes = Pool::new();
     let mut root = FbxNode::default();
@@ -176,7 +233,7 @@ where
     // FBX document can have multiple root nodes, so we must read the file
     // until the end.
     while file.seek(SeekFrom::Current(0))? < total_length {
-        let root_child = read_binary_node(file, &mut nodes)?;
+        let root_child = read_binary_node(file, &mut nodes, format, total_length)?;
         if root_child.is_none() {
             break;
         }
@@ -189,3 +246,97 @@ where
         root: root_handle,
     })
 }
+
+#[cfg(test)]
+mod test {
+    use crate::resource::fbx::document::binary::read_binary;
+    use byteorder::{LittleEndian, WriteBytesExt};
+    use std::io::Cursor;
+
+    fn write_offset(buffer: &mut Vec<u8>, wide: bool, value: u64) {
+        if wide {
+            buffer.write_u64::<LittleEndian>(value).unwrap();
+        } else {
+            buffer.write_u32::<LittleEndian>(value as u32).unwrap();
+        }
+    }
+
+    fn patch_offset(buffer: &mut Vec<u8>, wide: bool, position: usize) {
+        let end = buffer.len() as u64;
+        let mut patch = Vec::new();
+        write_offset(&mut patch, wide, end);
+        buffer[position..position + patch.len()].copy_from_slice(&patch);
+    }
+
+    /// Writes node record with given raw properties and children written by `children`.
+    fn write_node<F>(
+        buffer: &mut Vec<u8>,
+        wide: bool,
+        name: &str,
+        properties: (u64, &[u8]),
+        children: F,
+    ) where
+        F: FnOnce(&mut Vec<u8>),
+    {
+        let start = buffer.len();
+        write_offset(buffer, wide, 0);
+        write_offset(buffer, wide, properties.0);
+        write_offset(buffer, wide, properties.1.len() as u64);
+        buffer.push(name.len() as u8);
+        buffer.extend_from_slice(name.as_bytes());
+        buffer.extend_from_slice(properties.1);
+        let children_start = buffer.len();
+        children(buffer);
+        if buffer.len() != children_start {
+            buffer.extend_from_slice(&[0; 25][..if wide { 25 } else { 13 }]);
+        }
+        patch_offset(buffer, wide, start);
+    }
+
+    fn make_document(version: u32) -> Vec<u8> {
+        let wide = version >= 7500;
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(b"Kaydara FBX Binary  \0\x1a\0");
+        buffer.write_u32::<LittleEndian>(version).unwrap();
+
+        let mut version_property = vec![b'I'];
+        version_property.write_i32::<LittleEndian>(version as i32).unwrap();
+        write_node(&mut buffer, wide, "FBXHeaderExtension", (0, &[]), |buffer| {
+            write_node(buffer, wide, "FBXVersion", (1, &version_property), |_| {});
+        });
+
+        let mut array_property = vec![b'd'];
+        array_property.write_u32::<LittleEndian>(3).unwrap();
+        array_property.write_u32::<LittleEndian>(0).unwrap();
+        array_property.write_u32::<LittleEndian>(24).unwrap();
+        for value in &[1.0f64, 2.0, 3.0] {
+            array_property.write_f64::<LittleEndian>(*value).unwrap();
+        }
+        write_node(&mut buffer, wide, "Vertices", (1, &array_property), |_| {});
+
+        // Footer.
+        buffer.extend_from_slice(&[0; 25][..if wide { 25 } else { 13 }]);
+        buffer
+    }
+
+    #[test]
+    fn read_narrow_and_wide_records() {
+        for &version in &[7400, 7500, 7700] {
+            let document = read_binary(&mut Cursor::new(make_document(version))).unwrap();
+            let nodes = document.nodes();
+
+            let fbx_version = nodes.get_by_name(document.root(), "FBXVersion").unwrap();
+            assert_eq!(fbx_version.get_attrib(0).unwrap().as_i32().unwrap(), version as i32);
+
+            let vertices = nodes.get_by_name(document.root(), "Vertices").unwrap();
+            let array = nodes.get(vertices.children()[0]);
+            assert_eq!(array.attrib_count(), 3);
+            assert_eq!(array.get_attrib(2).unwrap().as_f64().unwrap(), 3.0);
+        }
+    }
+
+    #[test]
+    fn reject_old_version() {
+        assert!(read_binary(&mut Cursor::new(make_document(6100))).is_err());
+    }
+}
@@ -8,12 +8,34 @@ use crate::{
         pool::{Handle, Pool},
     },
     resource::fbx::{document::attribute::FbxAttribute, error::FbxError},
+    utils::log::{Log, LogCategory},
 };
-use std::{
-    fs::File,
-    io::{BufReader, Read},
-    path::Path,
-};
+use std::io::Cursor;
+
+/// Oldest version of FBX which can be loaded. Older versions have different structure.
+pub const MIN_SUPPORTED_VERSION: i32 = 7100;
+
+/// Newest version of FBX which is known to be loaded correctly.
+pub const MAX_KNOWN_VERSION: i32 = 7700;
+
+/// Checks whether file of given version can be loaded. Versions newer than known ones are
+/// allowed, but a warning is written to the log.
+pub fn check_version(version: i32) -> Result<(), FbxError> {
+    if version < MIN_SUPPORTED_VERSION {
+        Err(FbxError::UnsupportedVersion(version))
+    } else {
+        if version > MAX_KNOWN_VERSION {
+            Log::warn(
+                LogCategory::Resource,
+                format!(
+                    "FBX version {} is newer than latest known version {}, \
+                 loading may fail or give incorrect results.",
+                version, MAX_KNOWN_VERSION
+            ));
+        }
+        Ok(())
+    }
+}
 
 pub struct FbxNode {
     name: String,
@@ -118,21 +140,16 @@ pub struct FbxDocument {
     nodes: FbxNodeContainer,
 }
 
-fn is_binary<P: AsRef<Path>>(path: P) -> Result<bool, FbxError> {
-    let mut file = File::open(path)?;
-    let mut magic = [0; 18];
-    file.read_exact(&mut magic)?;
-    let fbx_magic = b"Kaydara FBX Binary";
-    Ok(magic == *fbx_magic)
+fn is_binary(data: &[u8]) -> bool {
+    // Data can be shorter than magic, it will be treated as ASCII file then.
+    data.starts_with(b"Kaydara FBX Binary")
 }
 
 impl FbxDocument {
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<FbxDocument, FbxError> {
-        let is_bin = is_binary(path.as_ref())?;
-
-        let mut reader = BufReader::new(File::open(path)?);
+    pub fn from_memory(data: &[u8]) -> Result<FbxDocument, FbxError> {
+        let mut reader = Cursor::new(data);
 
-        if is_bin {
+        if is_binary(data) {
             binary::read_binary(&mut reader)
         } else {
             ascii::read_ascii(&mut reader)
@@ -1,5 +1,6 @@
 //! Contains all possible errors that can occur during FBX parsing and conversion.
 
+use crate::resource::fbx::document;
 use std::fmt::Formatter;
 
 /// See module docs.
@@ -15,8 +16,11 @@ pub enum FbxError {
     InvalidString,
     /// Arbitrary error that can have any meaning.
     Custom(Box<String>),
-    /// Version is not supported. Keep in mind that binary FBX 7500 is still not supported!
+    /// Version is not supported, see `fbx` module docs for the list of supported versions.
     UnsupportedVersion(i32),
+    /// File uses a feature which is not supported by the loader, the string describes
+    /// the feature.
+    UnsupportedFeature(String),
     /// Internal handle is invalid.
     InvalidPoolHandle,
     /// Attempt to "cast" enum to unexpected variant.
@@ -43,7 +47,16 @@ impl std::fmt::Display for FbxError {
             FbxError::InvalidNullRecord => write!(f, "Invalid null record"),
             FbxError::InvalidString => write!(f, "Invalid string"),
             FbxError::Custom(err) => write!(f, "{}", err),
-            FbxError::UnsupportedVersion(ver) => write!(f, "Unsupported version {}", ver),
+            FbxError::UnsupportedVersion(ver) => write!(
+                f,
+                "Unsupported FBX version {}. Only FBX {} and newer is supported, re-export \
+                 the file using newer version of FBX.",
+                ver,
+                document::MIN_SUPPORTED_VERSION
+            ),
+            FbxError::UnsupportedFeature(feature) => {
+                write!(f, "Unsupported FBX feature: {}", feature)
+            }
             FbxError::InvalidPoolHandle => write!(f, "Invalid pool handle."),
             FbxError::UnexpectedType => write!(
                 f,
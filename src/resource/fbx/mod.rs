@@ -5,6 +5,23 @@
 //!
 //! Normally you should never use methods from this module directly, use resource manager to load
 //! models and create their instances.
+//!
+//! # Cameras and lights
+//!
+//! Cameras and lights authored in 3d modelling software are imported as corresponding engine
+//! nodes, so lighting of a level blocked out in Blender (for example) does not have to be
+//! recreated by hand. Units and conventions of FBX are different from the engine ones, so
+//! conversion can be tweaked by `FbxImportOptions` which can be set in resource manager,
+//! see `ResourceManager::set_fbx_import_options`. Options of a particular model can be stored
+//! in its options file, see `import_options` module docs.
+//!
+//! # Supported versions
+//!
+//! Both ASCII and binary FBX files of versions from 7.1 (FBX 2011) up to 7.7 (FBX 2020) are
+//! supported. Files of newer versions are loaded too, but with a warning in the log, since
+//! their layout can differ. FBX 6.x and older files have completely different structure of
+//! scene and are rejected with `FbxError::UnsupportedVersion`, such files must be re-exported
+//! using newer version of FBX.
 
 mod document;
 pub mod error;
@@ -29,8 +46,12 @@ use crate::{
             vec4::Vec4,
         },
         pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    engine::{
+        import_options::ModelImportOptions,
+        resource_manager::{ResourceManager, TextureRequestOptions},
     },
-    engine::resource_manager::ResourceManager,
     renderer::surface::{Surface, SurfaceSharedData, Vertex, VertexWeightSet},
     resource::{
         fbx::{
@@ -44,10 +65,81 @@ use crate::{
         texture::TextureKind,
     },
     scene::{base::Base, graph::Graph, mesh::Mesh, node::Node, Scene},
-    utils::{log::Log, raw_mesh::RawMeshBuilder},
+    utils::{
+        log::{Log, LogCategory},
+        raw_mesh::RawMeshBuilder,
+    },
 };
 use std::cmp::Ordering;
 
+/// Defines how field of view of FBX camera is converted to field of view of engine camera.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FbxFovConversion {
+    /// Field of view is converted to vertical field of view (engine cameras use vertical
+    /// fov) using aperture mode, aspect ratio and film size of FBX camera.
+    Vertical,
+    /// `FieldOfView` property of FBX camera is taken as is.
+    AsIs,
+}
+
+/// Options of conversion of FBX cameras and lights to engine nodes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FbxImportOptions {
+    /// Whether to create light nodes from FBX lights or not. Models with lights will be
+    /// imported as base nodes if this is `false`.
+    pub import_lights: bool,
+    /// Whether to create camera nodes from FBX cameras or not. Models with cameras will be
+    /// imported as base nodes if this is `false`.
+    pub import_cameras: bool,
+    /// Multiplier for color of lights. FBX lights have intensity in percents which is
+    /// baked into color of engine lights (they have no intensity), so with default scale
+    /// light with 100% intensity will have exactly the color from FBX.
+    pub light_intensity_scale: f32,
+    /// Multiplier for distances - radii of lights and clipping planes of cameras. For
+    /// example FBX files from 3ds Max often use centimeters, so 0.01 can be used to
+    /// convert them to meters.
+    pub distance_scale: f32,
+    /// See `FbxFovConversion` docs.
+    pub fov_conversion: FbxFovConversion,
+}
+
+impl Default for FbxImportOptions {
+    fn default() -> Self {
+        Self {
+            import_lights: true,
+            import_cameras: true,
+            light_intensity_scale: 1.0,
+            distance_scale: 1.0,
+            fov_conversion: FbxFovConversion::Vertical,
+        }
+    }
+}
+
+impl Visit for FbxImportOptions {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.import_lights.visit("ImportLights", visitor)?;
+        self.import_cameras.visit("ImportCameras", visitor)?;
+        self.light_intensity_scale.visit("LightIntensityScale", visitor)?;
+        self.distance_scale.visit("DistanceScale", visitor)?;
+        let mut fov_conversion = match self.fov_conversion {
+            FbxFovConversion::Vertical => 0u32,
+            FbxFovConversion::AsIs => 1,
+        };
+        fov_conversion.visit("FovConversion", visitor)?;
+        if visitor.is_reading() {
+            self.fov_conversion = match fov_conversion {
+                0 => FbxFovConversion::Vertical,
+                1 => FbxFovConversion::AsIs,
+                _ => return Err(format!("Invalid fov conversion {}", fov_conversion).into()),
+            };
+        }
+
+        visitor.leave_region()
+    }
+}
+
 /// Input angles in degrees
 fn quat_from_euler(euler: Vec3) -> Quat {
     Quat::from_euler(
@@ -239,11 +331,19 @@ fn create_surfaces(
                 let path = texture.get_file_path();
                 if let Some(filename) = path.file_name() {
                     let diffuse_path = resource_manager.textures_path().join(&filename);
+                    // Normal maps store directions, so they must stay in linear color space.
+                    let options = TextureRequestOptions {
+                        srgb: !matches!(name.as_str(), "Bump" | "NormalMap"),
+                        ..Default::default()
+                    };
                     // Here we will load *every* texture as RGBA8, this probably is overkill,
                     // that will lead to higher memory consumption, but this will remove
                     // problems with transparent textures (like mesh texture, etc.)
-                    let texture = resource_manager
-                        .request_texture_async(diffuse_path.as_path(), TextureKind::RGBA8);
+                    let texture = resource_manager.request_texture_async_with_options(
+                        diffuse_path.as_path(),
+                        TextureKind::RGBA8,
+                        options,
+                    );
                     match name.as_str() {
                         "AmbientColor" => (), // TODO: Add ambient occlusion (AO) map support.
                         "DiffuseColor" => surface.set_diffuse_texture(texture),
@@ -348,13 +448,21 @@ fn convert_model(
     graph: &mut Graph,
     animations: &mut AnimationContainer,
     animation_handle: Handle<Animation>,
+    options: &FbxImportOptions,
 ) -> Result<Handle<Node>, FbxError> {
+    // FBX cameras look along X axis, while engine cameras look along Z axis.
+    let mut post_rotation = quat_from_euler(model.post_rotation);
+
     // Create node with correct kind.
     let mut node = if !model.geoms.is_empty() {
         Node::Mesh(convert_mesh(fbx_scene, resource_manager, model)?)
-    } else if model.light.is_some() {
+    } else if model.light.is_some() && options.import_lights {
         let fbx_light_component = fbx_scene.get(model.light);
-        Node::Light(fbx_light_component.as_light()?.convert())
+        Node::Light(fbx_light_component.as_light()?.convert(options))
+    } else if model.camera.is_some() && options.import_cameras {
+        let fbx_camera_component = fbx_scene.get(model.camera);
+        post_rotation = post_rotation * quat_from_euler(Vec3::new(0.0, 90.0, 0.0));
+        Node::Camera(fbx_camera_component.as_camera()?.convert(options))
     } else {
         Node::Base(Base::default())
     };
@@ -365,7 +473,7 @@ fn convert_model(
         .set_rotation(node_local_rotation)
         .set_scale(model.scale)
         .set_position(model.translation)
-        .set_post_rotation(quat_from_euler(model.post_rotation))
+        .set_post_rotation(post_rotation)
         .set_pre_rotation(quat_from_euler(model.pre_rotation))
         .set_rotation_offset(model.rotation_offset)
         .set_rotation_pivot(model.rotation_pivot)
@@ -453,6 +561,7 @@ fn convert(
     fbx_scene: &FbxScene,
     resource_manager: &mut ResourceManager,
     scene: &mut Scene,
+    options: &FbxImportOptions,
 ) -> Result<Handle<Node>, FbxError> {
     let root = scene.graph.add_node(Node::Base(Base::default()));
     let animation_handle = scene.animations.add(Animation::default());
@@ -466,6 +575,7 @@ fn convert(
                 &mut scene.graph,
                 &mut scene.animations,
                 animation_handle,
+                options,
             )?;
             scene.graph.link_nodes(node, root);
             fbx_model_to_node_map.insert(component_handle, node);
@@ -537,10 +647,13 @@ pub fn load_to_scene<P: AsRef<Path>>(
 ) -> Result<Handle<Node>, FbxError> {
     let start_time = Instant::now();
 
-    Log::writeln(format!("Trying to load {:?}", path.as_ref()));
+    Log::debug(
+        LogCategory::Resource,
+        format!("Trying to load {:?}", path.as_ref()),
+    );
 
     let now = Instant::now();
-    let fbx = FbxDocument::new(path.as_ref())?;
+    let fbx = FbxDocument::from_memory(&resource_manager.vfs().read(path.as_ref())?)?;
     let parsing_time = now.elapsed().as_millis();
 
     let now = Instant::now();
@@ -548,11 +661,25 @@ pub fn load_to_scene<P: AsRef<Path>>(
     let dom_prepare_time = now.elapsed().as_millis();
 
     let now = Instant::now();
-    let result = convert(&fbx_scene, resource_manager, scene);
+    // Options file of the model overrides global options.
+    let options = ModelImportOptions::load(path.as_ref(), resource_manager.vfs())
+        .fbx
+        .unwrap_or_else(|| resource_manager.fbx_import_options().clone());
+    let result = convert(&fbx_scene, resource_manager, scene, &options);
     let conversion_time = now.elapsed().as_millis();
 
-    Log::writeln(format!("FBX {:?} loaded in {} ms\n\t- Parsing - {} ms\n\t- DOM Prepare - {} ms\n\t- Conversion - {} ms",
-                         path.as_ref(), start_time.elapsed().as_millis(), parsing_time, dom_prepare_time, conversion_time));
+    Log::info(
+        LogCategory::Resource,
+        format!(
+            "FBX {:?} loaded in {} ms\n\t- Parsing - {} ms\n\t- DOM Prepare - {} ms\n\t\
+             - Conversion - {} ms",
+            path.as_ref(),
+            start_time.elapsed().as_millis(),
+            parsing_time,
+            dom_prepare_time,
+            conversion_time
+        ),
+    );
 
     result
 }
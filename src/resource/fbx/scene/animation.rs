@@ -8,7 +8,7 @@ use crate::{
         quat_from_euler,
         scene::{FbxComponent, FbxScene, FBX_TIME_UNIT},
     },
-    utils::log::Log,
+    utils::log::{Log, LogCategory},
 };
 
 pub struct FbxTimeValuePair {
@@ -51,7 +51,10 @@ impl FbxAnimationCurve {
 
     fn eval(&self, time: f32) -> f32 {
         if self.keys.is_empty() {
-            Log::writeln("FBX: Trying to evaluate curve with no keys!".to_owned());
+            Log::warn(
+                LogCategory::Resource,
+                "FBX: Trying to evaluate curve with no keys!",
+            );
 
             return 0.0;
         }
@@ -0,0 +1,115 @@
+use crate::{
+    core::pool::Handle,
+    resource::fbx::{
+        document::{FbxNode, FbxNodeContainer},
+        FbxFovConversion, FbxImportOptions,
+    },
+    scene::{
+        base::BaseBuilder,
+        camera::{Camera, CameraBuilder},
+    },
+};
+
+pub enum FbxApertureMode {
+    HorizontalAndVertical = 0,
+    Horizontal = 1,
+    Vertical = 2,
+    FocalLength = 3,
+}
+
+pub struct FbxCamera {
+    aperture_mode: FbxApertureMode,
+    // All angles are in degrees.
+    field_of_view: f32,
+    field_of_view_y: f32,
+    focal_length: f32,
+    // Film size is in inches.
+    film_height: f32,
+    aspect_width: f32,
+    aspect_height: f32,
+    near_plane: f32,
+    far_plane: f32,
+}
+
+impl FbxCamera {
+    pub(in crate::resource::fbx) fn read(
+        camera_node_handle: Handle<FbxNode>,
+        nodes: &FbxNodeContainer,
+    ) -> Result<Self, String> {
+        let mut camera = Self {
+            aperture_mode: FbxApertureMode::Vertical,
+            field_of_view: 40.0,
+            field_of_view_y: 40.0,
+            focal_length: 35.0,
+            film_height: 0.612,
+            aspect_width: 16.0,
+            aspect_height: 9.0,
+            near_plane: 0.025,
+            far_plane: 2048.0,
+        };
+
+        let props = nodes.get_by_name(camera_node_handle, "Properties70")?;
+        for prop_handle in props.children() {
+            let prop = nodes.get(*prop_handle);
+            match prop.get_attrib(0)?.as_string().as_str() {
+                "FieldOfView" => camera.field_of_view = prop.get_attrib(4)?.as_f64()? as f32,
+                "FieldOfViewY" => camera.field_of_view_y = prop.get_attrib(4)?.as_f64()? as f32,
+                "FocalLength" => camera.focal_length = prop.get_attrib(4)?.as_f64()? as f32,
+                "FilmHeight" => camera.film_height = prop.get_attrib(4)?.as_f64()? as f32,
+                "AspectWidth" => camera.aspect_width = prop.get_attrib(4)?.as_f64()? as f32,
+                "AspectHeight" => camera.aspect_height = prop.get_attrib(4)?.as_f64()? as f32,
+                "NearPlane" => camera.near_plane = prop.get_attrib(4)?.as_f64()? as f32,
+                "FarPlane" => camera.far_plane = prop.get_attrib(4)?.as_f64()? as f32,
+                "ApertureMode" => {
+                    camera.aperture_mode = match prop.get_attrib(4)?.as_i32()? {
+                        0 => FbxApertureMode::HorizontalAndVertical,
+                        1 => FbxApertureMode::Horizontal,
+                        3 => FbxApertureMode::FocalLength,
+                        _ => FbxApertureMode::Vertical,
+                    };
+                }
+                _ => (),
+            }
+        }
+
+        Ok(camera)
+    }
+
+    fn aspect_ratio(&self) -> f32 {
+        if self.aspect_height > 0.0 {
+            self.aspect_width / self.aspect_height
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns vertical field of view in radians, engine cameras use vertical fov.
+    fn vertical_fov(&self) -> f32 {
+        match self.aperture_mode {
+            FbxApertureMode::HorizontalAndVertical => self.field_of_view_y.to_radians(),
+            FbxApertureMode::Horizontal => {
+                let half_horizontal = self.field_of_view.to_radians() * 0.5;
+                2.0 * (half_horizontal.tan() / self.aspect_ratio()).atan()
+            }
+            FbxApertureMode::Vertical => self.field_of_view.to_radians(),
+            FbxApertureMode::FocalLength => {
+                // Film size is in inches, focal length is in millimeters.
+                let half_film_height = self.film_height * 25.4 * 0.5;
+                2.0 * (half_film_height / self.focal_length.max(std::f32::EPSILON)).atan()
+            }
+        }
+    }
+
+    pub fn convert(&self, options: &FbxImportOptions) -> Camera {
+        let fov = match options.fov_conversion {
+            FbxFovConversion::Vertical => self.vertical_fov(),
+            FbxFovConversion::AsIs => self.field_of_view.to_radians(),
+        };
+
+        CameraBuilder::new(BaseBuilder::new())
+            .with_fov(fov)
+            .with_z_near(self.near_plane * options.distance_scale)
+            .with_z_far(self.far_plane * options.distance_scale)
+            .build()
+    }
+}
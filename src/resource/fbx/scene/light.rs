@@ -1,11 +1,14 @@
 use crate::{
     core::{color::Color, pool::Handle},
-    resource::fbx::document::{FbxNode, FbxNodeContainer},
+    resource::fbx::{
+        document::{FbxNode, FbxNodeContainer},
+        FbxImportOptions,
+    },
     scene::{
         base::BaseBuilder,
         light::{BaseLightBuilder, DirectionalLight, Light, PointLightBuilder, SpotLightBuilder},
     },
-    utils::log::Log,
+    utils::log::{Log, LogCategory},
 };
 
 pub enum FbxLightType {
@@ -19,6 +22,8 @@ pub enum FbxLightType {
 pub struct FbxLight {
     actual_type: FbxLightType,
     color: Color,
+    // In percents, 100 is "normal" intensity.
+    intensity: f32,
     radius: f32,
     hotspot_cone_angle: f32,
     falloff_cone_angle_delta: f32,
@@ -32,6 +37,7 @@ impl FbxLight {
         let mut light = Self {
             actual_type: FbxLightType::Point,
             color: Color::WHITE,
+            intensity: 100.0,
             radius: 10.0,
             hotspot_cone_angle: 90.0f32.to_radians(),
             falloff_cone_angle_delta: 5.0f32.to_radians(),
@@ -41,6 +47,7 @@ impl FbxLight {
         for prop_handle in props.children() {
             let prop = nodes.get(*prop_handle);
             match prop.get_attrib(0)?.as_string().as_str() {
+                "Intensity" => light.intensity = prop.get_attrib(4)?.as_f64()? as f32,
                 "DecayStart" => light.radius = prop.get_attrib(4)?.as_f64()? as f32,
                 "Color" => {
                     let r = (prop.get_attrib(4)?.as_f64()? * 255.0) as u8;
@@ -65,10 +72,13 @@ impl FbxLight {
                         3 => FbxLightType::Area,
                         4 => FbxLightType::Volume,
                         _ => {
-                            Log::writeln(format!(
-                                "FBX: Unknown light type {}, fallback to Point!",
-                                type_code
-                            ));
+                            Log::warn(
+                                LogCategory::Resource,
+                                format!(
+                                    "FBX: Unknown light type {}, fallback to Point!",
+                                    type_code
+                                ),
+                            );
                             FbxLightType::Point
                         }
                     };
@@ -80,27 +90,31 @@ impl FbxLight {
         Ok(light)
     }
 
-    pub fn convert(&self) -> Light {
+    fn scaled_color(&self, options: &FbxImportOptions) -> Color {
+        let k = self.intensity / 100.0 * options.light_intensity_scale;
+        let scale = |c: u8| (c as f32 * k).max(0.0).min(255.0) as u8;
+        Color::from_rgba(scale(self.color.r), scale(self.color.g), scale(self.color.b), 255)
+    }
+
+    pub fn convert(&self, options: &FbxImportOptions) -> Light {
+        let color = self.scaled_color(options);
+        let radius = self.radius * options.distance_scale;
         match self.actual_type {
             FbxLightType::Point | FbxLightType::Area | FbxLightType::Volume => Light::Point(
-                PointLightBuilder::new(
-                    BaseLightBuilder::new(BaseBuilder::new()).with_color(self.color.to_opaque()),
-                )
-                .with_radius(self.radius)
-                .build(),
+                PointLightBuilder::new(BaseLightBuilder::new(BaseBuilder::new()).with_color(color))
+                    .with_radius(radius)
+                    .build(),
             ),
             FbxLightType::Spot => Light::Spot(
-                SpotLightBuilder::new(
-                    BaseLightBuilder::new(BaseBuilder::new()).with_color(self.color.to_opaque()),
-                )
-                .with_distance(self.radius)
-                .with_hotspot_cone_angle(self.hotspot_cone_angle)
-                .with_falloff_angle_delta(self.falloff_cone_angle_delta)
-                .build(),
+                SpotLightBuilder::new(BaseLightBuilder::new(BaseBuilder::new()).with_color(color))
+                    .with_distance(radius)
+                    .with_hotspot_cone_angle(self.hotspot_cone_angle)
+                    .with_falloff_angle_delta(self.falloff_cone_angle_delta)
+                    .build(),
             ),
             FbxLightType::Directional => Light::Directional(DirectionalLight::from(
                 BaseLightBuilder::new(BaseBuilder::new())
-                    .with_color(self.color.to_opaque())
+                    .with_color(color)
                     .build(),
             )),
         }
@@ -4,10 +4,11 @@ use crate::{
         pool::{Handle, Pool, PoolPairIterator},
     },
     resource::fbx::{
-        document::{attribute::FbxAttribute, FbxDocument, FbxNode, FbxNodeContainer},
+        document::{self, attribute::FbxAttribute, FbxDocument, FbxNode, FbxNodeContainer},
         error::FbxError,
         scene::{
             animation::{FbxAnimationCurve, FbxAnimationCurveNode},
+            camera::FbxCamera,
             geometry::FbxGeometry,
             light::FbxLight,
             model::FbxModel,
@@ -18,6 +19,7 @@ use crate::{
 use std::collections::HashMap;
 
 pub mod animation;
+pub mod camera;
 pub mod geometry;
 pub mod light;
 pub mod model;
@@ -40,9 +42,7 @@ impl FbxScene {
         let header_handle = nodes.find(document.root(), "FBXHeaderExtension")?;
         let version = nodes.get_by_name(header_handle, "FBXVersion")?;
         let version = version.get_attrib(0)?.as_i32()?;
-        if version < 7100 && version > 7400 {
-            return Err(FbxError::UnsupportedVersion(version));
-        }
+        document::check_version(version)?;
 
         // Read objects
         let objects_node = nodes.get_by_name(document.root(), "Objects")?;
@@ -72,9 +72,20 @@ impl FbxScene {
                     )?));
                 }
                 "NodeAttribute" => {
-                    if object.attrib_count() > 2 && object.get_attrib(2)?.as_string() == "Light" {
-                        component_handle = components
-                            .spawn(FbxComponent::Light(FbxLight::read(*object_handle, nodes)?));
+                    if object.attrib_count() > 2 {
+                        match object.get_attrib(2)?.as_string().as_str() {
+                            "Light" => {
+                                component_handle = components.spawn(FbxComponent::Light(
+                                    FbxLight::read(*object_handle, nodes)?,
+                                ));
+                            }
+                            "Camera" => {
+                                component_handle = components.spawn(FbxComponent::Camera(
+                                    FbxCamera::read(*object_handle, nodes)?,
+                                ));
+                            }
+                            _ => (),
+                        }
                     }
                 }
                 "AnimationCurve" => {
@@ -151,6 +162,7 @@ fn link_child_with_parent_component(
             FbxComponent::Material(_) => model.materials.push(child_handle),
             FbxComponent::AnimationCurveNode(_) => model.animation_curve_nodes.push(child_handle),
             FbxComponent::Light(_) => model.light = child_handle,
+            FbxComponent::Camera(_) => model.camera = child_handle,
             FbxComponent::Model(_) => model.children.push(child_handle),
             _ => (),
         },
@@ -195,6 +207,7 @@ pub enum FbxComponent {
     SubDeformer(FbxSubDeformer),
     Texture(FbxTexture),
     Light(FbxLight),
+    Camera(FbxCamera),
     Model(Box<FbxModel>),
     Material(FbxMaterial),
     AnimationCurveNode(FbxAnimationCurveNode),
@@ -219,6 +232,7 @@ impl FbxComponent {
     define_as!(self, as_sub_deformer, FbxSubDeformer, SubDeformer);
     define_as!(self, as_texture, FbxTexture, Texture);
     define_as!(self, as_light, FbxLight, Light);
+    define_as!(self, as_camera, FbxCamera, Camera);
     define_as!(self, as_material, FbxMaterial, Material);
     define_as!(self, as_geometry, FbxGeometry, Geometry);
 }
@@ -33,6 +33,8 @@ pub struct FbxModel {
     pub children: Vec<Handle<FbxComponent>>,
     /// Handle to light component
     pub light: Handle<FbxComponent>,
+    /// Handle to camera component
+    pub camera: Handle<FbxComponent>,
 }
 
 impl FbxModel {
@@ -72,6 +74,7 @@ impl FbxModel {
             animation_curve_nodes: Vec::new(),
             children: Vec::new(),
             light: Handle::NONE,
+            camera: Handle::NONE,
         };
 
         let properties70_node_handle = nodes.find(model_node_handle, "Properties70")?;
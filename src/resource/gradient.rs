@@ -0,0 +1,425 @@
+//! Color gradient resource allows to share same color gradient across many consumers
+//! (particle systems for example) and tweak it without recompiling.
+//!
+//! # File format
+//!
+//! Gradient is stored in native binary format of the engine (the same as used for
+//! saved games and scenes) and contains single `ColorGradient` in `Gradient` region.
+//! Such file can be created by `GradientResource::save`.
+//!
+//! # Hot reload
+//!
+//! Resource manager periodically checks modification time of gradient files and reloads
+//! gradients that were changed, so changes made by external tools will be visible
+//! without restarting the game.
+//!
+//! # Color spaces
+//!
+//! `ColorGradient::get_color` blends colors as raw RGBA, blending of saturated colors with
+//! different hues gives muddy gray colors in the middle (red and green give dark olive).
+//! `color_at` can blend colors in other color spaces, see `GradientColorSpace`; particle
+//! systems use it for color over lifetime, see `ParticleSystem::set_gradient_color_space`.
+//!
+//! # Presets
+//!
+//! There is a small set of preset gradients in `presets` module which can be used as a
+//! starting point for typical effects.
+
+use crate::{
+    core::{
+        color::Color,
+        color_gradient::ColorGradient,
+        visitor::{Visit, VisitError, VisitResult, Visitor},
+    },
+    engine::vfs::Vfs,
+};
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// See module docs.
+#[derive(Debug, Clone)]
+pub struct GradientResource {
+    pub(in crate) path: PathBuf,
+    pub(in crate) modified: Option<SystemTime>,
+    gradient: ColorGradient,
+}
+
+impl Default for GradientResource {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            modified: None,
+            gradient: ColorGradient::new(),
+        }
+    }
+}
+
+impl Visit for GradientResource {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.path.visit("Path", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+pub(in crate) fn modification_time<P: AsRef<Path>>(path: P) -> Option<SystemTime> {
+    std::fs::metadata(path.as_ref())
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl GradientResource {
+    /// Creates new gradient resource with given gradient and path. Path will be used to
+    /// save resource.
+    pub fn new<P: AsRef<Path>>(path: P, gradient: ColorGradient) -> Self {
+        Self {
+            path: path.as_ref().to_owned(),
+            modified: None,
+            gradient,
+        }
+    }
+
+    pub(in crate) fn load<P: AsRef<Path>>(path: P, vfs: &Vfs) -> Result<Self, VisitError> {
+        let mut gradient = ColorGradient::new();
+        let mut visitor = vfs.load_visitor(path.as_ref())?;
+        gradient.visit("Gradient", &mut visitor)?;
+        Ok(Self {
+            path: path.as_ref().to_owned(),
+            modified: vfs.modification_time(path.as_ref()),
+            gradient,
+        })
+    }
+
+    /// Tries to save gradient into source file.
+    pub fn save(&mut self) -> VisitResult {
+        let mut visitor = Visitor::new();
+        self.gradient.visit("Gradient", &mut visitor)?;
+        visitor.save_binary(&self.path)?;
+        self.modified = modification_time(&self.path);
+        Ok(())
+    }
+
+    /// Returns path to source file.
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Sets new path to source file.
+    pub fn set_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.path = path.as_ref().to_owned();
+    }
+
+    /// Returns shared reference to inner gradient.
+    pub fn gradient(&self) -> &ColorGradient {
+        &self.gradient
+    }
+
+    /// Returns mutable reference to inner gradient. Changes will be visible to every user
+    /// of the resource, but won't be written to disk until `save` is called.
+    pub fn gradient_mut(&mut self) -> &mut ColorGradient {
+        &mut self.gradient
+    }
+}
+
+/// Color space in which colors of gradient points are blended.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GradientColorSpace {
+    /// Components are blended as is, same as `ColorGradient::get_color`.
+    Rgba,
+    /// Hue, saturation and value are blended, hue goes by the shortest way around color
+    /// wheel. Keeps colors saturated, but brightness can change unevenly.
+    Hsv,
+    /// Colors are blended in OkLab perceptual color space, gives smooth change of both
+    /// brightness and hue.
+    OkLab,
+}
+
+impl Default for GradientColorSpace {
+    fn default() -> Self {
+        GradientColorSpace::Rgba
+    }
+}
+
+impl GradientColorSpace {
+    fn id(self) -> u32 {
+        match self {
+            GradientColorSpace::Rgba => 0,
+            GradientColorSpace::Hsv => 1,
+            GradientColorSpace::OkLab => 2,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(GradientColorSpace::Rgba),
+            1 => Ok(GradientColorSpace::Hsv),
+            2 => Ok(GradientColorSpace::OkLab),
+            _ => Err(format!("Invalid gradient color space {}", id)),
+        }
+    }
+}
+
+impl Visit for GradientColorSpace {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Returns color of gradient at given location, colors of points are blended in given color
+/// space.
+pub fn color_at(gradient: &ColorGradient, location: f32, color_space: GradientColorSpace) -> Color {
+    let points = gradient.points();
+    if color_space == GradientColorSpace::Rgba || points.len() < 2 {
+        return gradient.get_color(location);
+    }
+    for pair in points.windows(2) {
+        let (left, right) = (&pair[0], &pair[1]);
+        if location >= left.location() && location <= right.location() {
+            let span = right.location() - left.location();
+            let k = if span > 0.0 {
+                (location - left.location()) / span
+            } else {
+                0.0
+            };
+            return blend(left.color(), right.color(), k, color_space);
+        }
+    }
+    // Location is out of gradient, use color of nearest point.
+    gradient.get_color(location)
+}
+
+fn to_floats(color: Color) -> [f32; 4] {
+    let float = |value: u8| f32::from(value) / 255.0;
+    [float(color.r), float(color.g), float(color.b), float(color.a)]
+}
+
+fn from_floats(components: [f32; 4]) -> Color {
+    let byte = |value: f32| (value.max(0.0).min(1.0) * 255.0).round() as u8;
+    Color::from_rgba(
+        byte(components[0]),
+        byte(components[1]),
+        byte(components[2]),
+        byte(components[3]),
+    )
+}
+
+fn lerp(a: f32, b: f32, k: f32) -> f32 {
+    a + (b - a) * k
+}
+
+fn blend(from: Color, to: Color, k: f32, color_space: GradientColorSpace) -> Color {
+    let (from, to) = (to_floats(from), to_floats(to));
+    let alpha = lerp(from[3], to[3], k);
+    let [r, g, b] = match color_space {
+        GradientColorSpace::Rgba => [
+            lerp(from[0], to[0], k),
+            lerp(from[1], to[1], k),
+            lerp(from[2], to[2], k),
+        ],
+        GradientColorSpace::Hsv => {
+            let (ha, sa, va) = rgb_to_hsv(from[0], from[1], from[2]);
+            let (hb, sb, vb) = rgb_to_hsv(to[0], to[1], to[2]);
+            // Hue of gray is undefined, take hue of other color to not pass through
+            // unrelated hues.
+            let ha = if sa > 0.0 { ha } else { hb };
+            let hb = if sb > 0.0 { hb } else { ha };
+            let mut delta = hb - ha;
+            if delta > 180.0 {
+                delta -= 360.0;
+            } else if delta < -180.0 {
+                delta += 360.0;
+            }
+            let hue = (ha + delta * k).rem_euclid(360.0);
+            hsv_to_rgb(hue, lerp(sa, sb, k), lerp(va, vb, k))
+        }
+        GradientColorSpace::OkLab => {
+            let la = linear_to_oklab(srgb_to_linear_rgb(from));
+            let lb = linear_to_oklab(srgb_to_linear_rgb(to));
+            let mixed = [
+                lerp(la[0], lb[0], k),
+                lerp(la[1], lb[1], k),
+                lerp(la[2], lb[2], k),
+            ];
+            let linear = oklab_to_linear(mixed);
+            [
+                linear_to_srgb(linear[0]),
+                linear_to_srgb(linear[1]),
+                linear_to_srgb(linear[2]),
+            ]
+        }
+    };
+    from_floats([r, g, b, alpha])
+}
+
+fn rgb_to_hsv(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let hue = if delta <= 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max > 0.0 { delta / max } else { 0.0 };
+    (hue, saturation, max)
+}
+
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let c = value * saturation;
+    let h = hue / 60.0;
+    let x = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = value - c;
+    [r + m, g + m, b + m]
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.003_130_8 {
+        value * 12.92
+    } else {
+        1.055 * value.max(0.0).powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_to_linear_rgb(color: [f32; 4]) -> [f32; 3] {
+    [
+        srgb_to_linear(color[0]),
+        srgb_to_linear(color[1]),
+        srgb_to_linear(color[2]),
+    ]
+}
+
+// Matrices are taken from the reference implementation of OkLab by Björn Ottosson.
+fn linear_to_oklab(c: [f32; 3]) -> [f32; 3] {
+    let l = (0.412_221_46 * c[0] + 0.536_332_55 * c[1] + 0.051_445_995 * c[2]).cbrt();
+    let m = (0.211_903_5 * c[0] + 0.680_699_5 * c[1] + 0.107_396_96 * c[2]).cbrt();
+    let s = (0.088_302_46 * c[0] + 0.281_718_85 * c[1] + 0.629_978_7 * c[2]).cbrt();
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+fn oklab_to_linear(c: [f32; 3]) -> [f32; 3] {
+    let l = c[0] + 0.396_337_78 * c[1] + 0.215_803_76 * c[2];
+    let m = c[0] - 0.105_561_346 * c[1] - 0.063_854_17 * c[2];
+    let s = c[0] - 0.089_484_18 * c[1] - 1.291_485_5 * c[2];
+    let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+    [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ]
+}
+
+/// Set of predefined gradients for typical effects.
+pub mod presets {
+    use crate::core::{
+        color::Color,
+        color_gradient::{ColorGradient, GradientPoint},
+    };
+
+    fn make(points: &[(f32, Color)]) -> ColorGradient {
+        let mut gradient = ColorGradient::new();
+        for &(location, color) in points {
+            gradient.add_point(GradientPoint::new(location, color));
+        }
+        gradient
+    }
+
+    /// Bright yellow core which turns into red and then fades into dark smoke.
+    pub fn fire() -> ColorGradient {
+        make(&[
+            (0.00, Color::from_rgba(255, 255, 160, 0)),
+            (0.10, Color::from_rgba(255, 220, 80, 255)),
+            (0.40, Color::from_rgba(255, 120, 20, 220)),
+            (0.75, Color::from_rgba(180, 30, 10, 120)),
+            (1.00, Color::from_rgba(40, 40, 40, 0)),
+        ])
+    }
+
+    /// Gray smoke which slowly appears, lightens and fades out.
+    pub fn smoke() -> ColorGradient {
+        make(&[
+            (0.00, Color::from_rgba(150, 150, 150, 0)),
+            (0.05, Color::from_rgba(150, 150, 150, 220)),
+            (0.85, Color::from_rgba(255, 255, 255, 180)),
+            (1.00, Color::from_rgba(255, 255, 255, 0)),
+        ])
+    }
+
+    /// Short white-hot flash that quickly turns orange and disappears.
+    pub fn spark() -> ColorGradient {
+        make(&[
+            (0.00, Color::from_rgba(255, 255, 255, 255)),
+            (0.20, Color::from_rgba(255, 230, 150, 255)),
+            (0.60, Color::from_rgba(255, 140, 30, 200)),
+            (1.00, Color::from_rgba(200, 60, 0, 0)),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{
+            color::Color,
+            color_gradient::{ColorGradient, GradientPoint},
+        },
+        resource::gradient::{self, GradientColorSpace},
+    };
+
+    #[test]
+    fn blend_in_color_spaces() {
+        let mut gradient = ColorGradient::new();
+        gradient.add_point(GradientPoint::new(0.0, Color::from_rgba(255, 0, 0, 255)));
+        gradient.add_point(GradientPoint::new(1.0, Color::from_rgba(0, 0, 255, 0)));
+
+        let hsv = gradient::color_at(&gradient, 0.5, GradientColorSpace::Hsv);
+        assert_eq!((hsv.r, hsv.g, hsv.b, hsv.a), (255, 0, 255, 128));
+
+        // Ends must be exact after round trip through OkLab.
+        let start = gradient::color_at(&gradient, 0.0, GradientColorSpace::OkLab);
+        assert_eq!((start.r, start.g, start.b), (255, 0, 0));
+        let end = gradient::color_at(&gradient, 1.0, GradientColorSpace::OkLab);
+        assert_eq!((end.r, end.g, end.b), (0, 0, 255));
+
+        // Middle of OkLab blend is brighter than muddy middle of RGBA blend.
+        let rgba = gradient::color_at(&gradient, 0.5, GradientColorSpace::Rgba);
+        let oklab = gradient::color_at(&gradient, 0.5, GradientColorSpace::OkLab);
+        let sum = |color: Color| u32::from(color.r) + u32::from(color.g) + u32::from(color.b);
+        assert!(sum(oklab) > sum(rgba));
+    }
+}
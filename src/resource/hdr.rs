@@ -0,0 +1,33 @@
+//! Loader of Radiance HDR (RGBE) images. Format is described here:
+//! https://www.graphics.cornell.edu/~bjw/rgbe.html
+
+use crate::resource::texture::{Texture, TextureError, TextureKind};
+use image::hdr::HdrDecoder;
+use std::io::Cursor;
+
+/// Loads texture from contents of Radiance HDR file, pixels are stored as `RGB32F`.
+pub(in crate) fn load(data: &[u8]) -> Result<Texture, TextureError> {
+    let decoder = HdrDecoder::new(Cursor::new(data))?;
+    let metadata = decoder.metadata();
+    let pixels = decoder.read_image_hdr()?;
+
+    let mut bytes = Vec::with_capacity(pixels.len() * 12);
+    for pixel in pixels {
+        for component in pixel.0.iter() {
+            bytes.extend_from_slice(&component.to_ne_bytes());
+        }
+    }
+
+    Ok(Texture {
+        path: Default::default(),
+        width: metadata.width,
+        height: metadata.height,
+        bytes,
+        mip_count: 1,
+        kind: TextureKind::RGB32F,
+        loaded: true,
+        sampler: Default::default(),
+        srgb: false,
+        revision: 0,
+    })
+}
@@ -0,0 +1,158 @@
+//! Loader of textures from KTX2 files. Format is described here:
+//! https://github.khronos.org/KTX-Specification/
+
+use crate::resource::{
+    basis::{self, BasisContainer},
+    texture::{Texture, TextureError, TextureKind},
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read};
+
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+fn kind_from_vk_format(format: u32) -> Result<TextureKind, TextureError> {
+    // Both UNORM and SRGB variants are accepted.
+    match format {
+        9 | 15 => Ok(TextureKind::R8),
+        23 | 29 => Ok(TextureKind::RGB8),
+        37 | 43 => Ok(TextureKind::RGBA8),
+        131..=134 => Ok(TextureKind::BC1),
+        135 | 136 => Ok(TextureKind::BC2),
+        137 | 138 => Ok(TextureKind::BC3),
+        139 => Ok(TextureKind::BC4),
+        141 => Ok(TextureKind::BC5),
+        143 => Ok(TextureKind::BC6H),
+        145 | 146 => Ok(TextureKind::BC7),
+        106 => Ok(TextureKind::RGB32F),
+        109 => Ok(TextureKind::RGBA32F),
+        _ => Err(TextureError::UnsupportedFormat(format!(
+            "Vulkan format {} in KTX2 file",
+            format
+        ))),
+    }
+}
+
+/// Loads texture from contents of KTX2 file. Only 2D textures without supercompression are
+/// supported, files with Basis Universal data are transcoded, see `basis` module docs.
+pub(in crate) fn load(data: &[u8]) -> Result<Texture, TextureError> {
+    let mut reader = Cursor::new(data);
+
+    let mut identifier = [0; 12];
+    reader.read_exact(&mut identifier)?;
+    if identifier != IDENTIFIER {
+        return Err(TextureError::InvalidData("not a KTX2 file".to_owned()));
+    }
+
+    let vk_format = reader.read_u32::<LittleEndian>()?;
+    // Files with Basis data have no format, it is selected when they're transcoded.
+    if vk_format == 0 {
+        return basis::load(data, BasisContainer::Ktx2);
+    }
+    let _type_size = reader.read_u32::<LittleEndian>()?;
+    let width = reader.read_u32::<LittleEndian>()?;
+    let height = reader.read_u32::<LittleEndian>()?;
+    let depth = reader.read_u32::<LittleEndian>()?;
+    let layer_count = reader.read_u32::<LittleEndian>()?;
+    let face_count = reader.read_u32::<LittleEndian>()?;
+    let level_count = reader.read_u32::<LittleEndian>()?;
+    let supercompression_scheme = reader.read_u32::<LittleEndian>()?;
+
+    let kind = kind_from_vk_format(vk_format)?;
+    if supercompression_scheme != 0 {
+        return Err(TextureError::UnsupportedFormat(format!(
+            "KTX2 supercompression scheme {}",
+            supercompression_scheme
+        )));
+    }
+    if face_count != 1 {
+        return Err(TextureError::UnsupportedFormat(
+            "cube maps in KTX2 files are not supported".to_owned(),
+        ));
+    }
+    if layer_count > 1 || depth > 1 {
+        return Err(TextureError::UnsupportedFormat(
+            "texture arrays and volume textures in KTX2 files are not supported".to_owned(),
+        ));
+    }
+    if width == 0 || height == 0 {
+        return Err(TextureError::InvalidData("KTX2 texture has zero size".to_owned()));
+    }
+
+    // Skip index of data format descriptor, key/value data and supercompression data.
+    reader.set_position(reader.position() + 4 * 4 + 2 * 8);
+
+    // Zero level count means that mip levels should be generated, so file has only one.
+    let mip_count = level_count.max(1);
+    let mut bytes = Vec::with_capacity(kind.mip_chain_size_bytes(width, height, mip_count));
+    for level in 0..mip_count {
+        let offset = reader.read_u64::<LittleEndian>()? as usize;
+        let length = reader.read_u64::<LittleEndian>()? as usize;
+        let _uncompressed_length = reader.read_u64::<LittleEndian>()?;
+
+        let expected_length =
+            kind.image_size_bytes((width >> level).max(1), (height >> level).max(1));
+        if length != expected_length {
+            return Err(TextureError::InvalidData(format!(
+                "KTX2 mip level {} has size {}, expected {}",
+                level, length, expected_length
+            )));
+        }
+        let level_data = data.get(offset..(offset + length)).ok_or_else(|| {
+            TextureError::InvalidData(format!("KTX2 mip level {} is out of file", level))
+        })?;
+        bytes.extend_from_slice(level_data);
+    }
+
+    Ok(Texture {
+        path: Default::default(),
+        width,
+        height,
+        bytes,
+        mip_count,
+        kind,
+        loaded: true,
+        sampler: Default::default(),
+        srgb: false,
+        revision: 0,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resource::{ktx2, ktx2::IDENTIFIER, texture::TextureKind};
+    use byteorder::{LittleEndian, WriteBytesExt};
+
+    #[test]
+    fn load_bc7_with_mips() {
+        let mut data = IDENTIFIER.to_vec();
+        // Format, type size, width, height, depth, layers, faces, levels, supercompression.
+        for value in &[145u32, 1, 4, 4, 0, 0, 1, 3, 0] {
+            data.write_u32::<LittleEndian>(*value).unwrap();
+        }
+        for _ in 0..4 {
+            data.write_u32::<LittleEndian>(0).unwrap();
+        }
+        for _ in 0..2 {
+            data.write_u64::<LittleEndian>(0).unwrap();
+        }
+        // Level index, levels are stored from smallest to largest one. Every level of 4x4
+        // texture takes one block.
+        let level_index_end = data.len() as u64 + 3 * 24;
+        for level in 0..3u64 {
+            data.write_u64::<LittleEndian>(level_index_end + (2 - level) * 16).unwrap();
+            data.write_u64::<LittleEndian>(16).unwrap();
+            data.write_u64::<LittleEndian>(16).unwrap();
+        }
+        for level in (0..3u8).rev() {
+            data.extend_from_slice(&[level; 16]);
+        }
+
+        let texture = ktx2::load(&data).unwrap();
+        assert_eq!(texture.kind(), TextureKind::BC7);
+        assert_eq!(texture.mip_count(), 3);
+        assert_eq!(&texture.bytes[0..16], &[0; 16]);
+        assert_eq!(&texture.bytes[32..48], &[2; 16]);
+    }
+}
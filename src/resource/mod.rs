@@ -2,6 +2,16 @@
 
 //!
 
+pub mod atlas;
+pub mod basis;
+pub mod cube_map;
+mod dds;
+mod exr;
 pub mod fbx;
+pub mod gradient;
+mod hdr;
+mod ktx2;
 pub mod model;
+pub mod prefab;
 pub mod texture;
+pub mod wav;
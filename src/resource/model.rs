@@ -17,18 +17,36 @@
 //!
 //! Currently only FBX (common format in game industry for storing complex 3d models)
 //! and RGS (native rusty-editor format) formats are supported.
+//!
+//! # Overrides
+//!
+//! Properties of instances (position, visibility, etc.) that were changed after
+//! instantiation are saved as overrides, every other property is taken from the model
+//! when a saved scene is loaded. So changes in a model are reflected in every instance
+//! without losing per-instance modifications. See `scene::base` module docs for more info.
+//! Reusable parts of scenes should be saved as prefabs, see `prefab` module docs.
+//!
+//! # Instantiation options
+//!
+//! `Model::instantiate_with_options` allows to customize instances without post-processing
+//! of instantiated nodes: animations can be skipped, every surface can get a material, root
+//! of instance can be scaled, and physics of model scene can be left out. See
+//! `InstantiationOptions` for more info.
 use crate::{
-    animation::Animation,
+    animation::{Animation, RootMotionSettings},
     core::{
+        math::vec3::Vec3,
         pool::Handle,
         visitor::{Visit, VisitError, VisitResult, Visitor},
     },
     engine::resource_manager::ResourceManager,
+    renderer::material::SharedMaterial,
     resource::{fbx, fbx::error::FbxError},
     scene::{node::Node, Scene},
-    utils::log::Log,
+    utils::log::{Log, LogCategory},
 };
 use std::{
+    collections::HashMap,
     path::{Path, PathBuf},
     sync::{Arc, Mutex, Weak},
 };
@@ -74,6 +92,37 @@ pub struct ModelInstance {
     pub animations: Vec<Handle<Animation>>,
 }
 
+/// Options of model instantiation, see `Model::instantiate_with_options`.
+#[derive(Clone, Debug)]
+pub struct InstantiationOptions {
+    /// Whether animations of model should be retargeted to instance or not. Default is `true`.
+    pub animations: bool,
+    /// Material which is assigned to every surface of instantiated meshes, None means that
+    /// surfaces keep materials from model. Default is `None`.
+    ///
+    /// Surfaces of instances are taken from model when a saved scene is loaded, so material
+    /// must be assigned again after loading.
+    pub material: Option<SharedMaterial>,
+    /// Whether rigid bodies which are bound to nodes of model scene (3D and 2D) should be
+    /// copied and bound to instantiated nodes or not. 3D bodies keep positions they have in
+    /// model scene. Default is `true`.
+    pub physics: bool,
+    /// Scale of root node of instance, it is multiplied with scale of root node of model.
+    /// Default is `(1, 1, 1)`.
+    pub root_scale: Vec3,
+}
+
+impl Default for InstantiationOptions {
+    fn default() -> Self {
+        Self {
+            animations: true,
+            material: None,
+            physics: true,
+            root_scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
 fn upgrade_self_weak_ref(self_weak_ref: &Option<Weak<Mutex<Model>>>) -> Arc<Mutex<Model>> {
     // This .expect will never be triggered in normal conditions because there is only
     // one way to get resource - through resource manager which always returns Arc and
@@ -151,7 +200,14 @@ impl Model {
     /// Tries to instantiate model from given resource. Does not retarget available
     /// animations from model to its instance. Can be helpful if you only need geometry.
     pub fn instantiate_geometry(&self, dest_scene: &mut Scene) -> Handle<Node> {
-        let (root, _) = self.scene.graph.copy_node(
+        self.copy_graph(dest_scene).0
+    }
+
+    fn copy_graph(
+        &self,
+        dest_scene: &mut Scene,
+    ) -> (Handle<Node>, HashMap<Handle<Node>, Handle<Node>>) {
+        let (root, old_new_map) = self.scene.graph.copy_node(
             self.scene.graph.get_root(),
             &mut dest_scene.graph,
             &mut |_, _| true,
@@ -170,17 +226,65 @@ impl Model {
             stack.extend_from_slice(node.children());
         }
 
-        root
+        (root, old_new_map)
     }
 
     /// Tries to instantiate model from given resource.
     /// Returns root handle to node of model instance along with available animations
     pub fn instantiate(&self, dest_scene: &mut Scene) -> ModelInstance {
-        let root = self.instantiate_geometry(dest_scene);
-        ModelInstance {
-            root,
-            animations: self.retarget_animations(root, dest_scene),
+        self.instantiate_with_options(dest_scene, Default::default())
+    }
+
+    /// Same as `instantiate`, but instance is customized using given options, see
+    /// `InstantiationOptions`.
+    pub fn instantiate_with_options(
+        &self,
+        dest_scene: &mut Scene,
+        options: InstantiationOptions,
+    ) -> ModelInstance {
+        let (root, old_new_map) = self.copy_graph(dest_scene);
+
+        let transform = dest_scene.graph[root].local_transform_mut();
+        let scale = transform.scale();
+        transform.set_scale(Vec3::new(
+            scale.x * options.root_scale.x,
+            scale.y * options.root_scale.y,
+            scale.z * options.root_scale.z,
+        ));
+
+        if let Some(material) = options.material {
+            for &node in old_new_map.values() {
+                if let Node::Mesh(mesh) = &mut dest_scene.graph[node] {
+                    for surface in mesh.surfaces_mut() {
+                        surface.set_material(Some(material.clone()));
+                    }
+                }
+            }
+        }
+
+        if options.physics {
+            for (&model_node, &instance_node) in old_new_map.iter() {
+                let body = self.scene.physics_binder.body_of(model_node);
+                if body.is_some() && self.scene.physics.is_valid_body_handle(body) {
+                    let body = self.scene.physics.borrow_body(body).clone();
+                    let body = dest_scene.physics.add_body(body);
+                    dest_scene.physics_binder.bind(instance_node, body);
+                }
+                if let Some(body) = self.scene.physics2d.body_of(model_node) {
+                    let mut body = *body;
+                    body.node = instance_node;
+                    dest_scene.physics2d.add_body(body);
+                }
+            }
         }
+
+        let animations = if options.animations {
+            self.retarget_animations(root, dest_scene)
+        } else {
+            Vec::new()
+        };
+
+        ModelInstance { root, animations }
     }
 
     /// Tries to retarget animations from given model resource to a node hierarchy starting
@@ -226,16 +330,30 @@ impl Model {
                 // Find instantiated node that corresponds to node in resource
                 let instance_node = dest_scene.graph.find_by_name(root, ref_node.name());
                 if instance_node.is_none() {
-                    Log::writeln(format!(
-                        "Failed to retarget animation {:?} for node {}",
-                        self.path,
-                        ref_node.name()
-                    ));
+                    Log::warn(
+                        LogCategory::Resource,
+                        format!(
+                            "Failed to retarget animation {:?} for node {}",
+                            self.path,
+                            ref_node.name()
+                        ),
+                    );
                 }
                 // One-to-one track mapping so there is [i] indexing.
                 anim_copy.get_tracks_mut()[i].set_node(instance_node);
             }
 
+            // Root node of root motion is found by name too.
+            if let Some(settings) = ref_anim.root_motion_settings() {
+                if self.scene.graph.is_valid_handle(settings.node) {
+                    let ref_node = &self.scene.graph[settings.node];
+                    anim_copy.set_root_motion_settings(Some(RootMotionSettings {
+                        node: dest_scene.graph.find_by_name(root, ref_node.name()),
+                        ..*settings
+                    }));
+                }
+            }
+
             animation_handles.push(dest_scene.animations.add(anim_copy));
         }
 
@@ -255,3 +373,94 @@ impl Model {
         self.scene.graph.find_by_name_from_root(name)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        animation::{Animation, Track},
+        core::math::{mat4::Mat4, vec3::Vec3},
+        physics::{
+            convex_shape::{ConvexShape, SphereShape},
+            rigid_body::RigidBody,
+        },
+        renderer::{
+            material::Material,
+            surface::{Surface, SurfaceSharedData},
+        },
+        resource::model::{InstantiationOptions, Model},
+        scene::{base::BaseBuilder, mesh::MeshBuilder, node::Node, Scene},
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn make_model() -> Arc<Mutex<Model>> {
+        let mut scene = Scene::new();
+        let mesh = scene.graph.add_node(
+            MeshBuilder::new(BaseBuilder::new().with_name("Mesh"))
+                .with_surfaces(vec![Surface::new(Arc::new(Mutex::new(
+                    SurfaceSharedData::make_cube(Mat4::IDENTITY),
+                )))])
+                .build_node(),
+        );
+        let body = scene
+            .physics
+            .add_body(RigidBody::new(ConvexShape::Sphere(SphereShape::new(0.5))));
+        scene.physics_binder.bind(mesh, body);
+        let mut track = Track::new();
+        track.set_node(mesh);
+        let mut animation = Animation::default();
+        animation.add_track(track);
+        scene.animations.add(animation);
+
+        let model = Arc::new(Mutex::new(Model {
+            self_weak_ref: None,
+            path: Default::default(),
+            scene,
+        }));
+        model.lock().unwrap().self_weak_ref = Some(Arc::downgrade(&model));
+        model
+    }
+
+    #[test]
+    fn instantiate_with_default_options() {
+        let model = make_model();
+        let mut dest = Scene::new();
+        let instance = model.lock().unwrap().instantiate(&mut dest);
+
+        let mesh = dest.graph.find_by_name(instance.root, "Mesh");
+        assert_eq!(instance.animations.len(), 1);
+        assert!(dest.physics_binder.body_of(mesh).is_some());
+        assert_eq!(
+            dest.graph[instance.root].local_transform().scale(),
+            Vec3::new(1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn instantiate_with_custom_options() {
+        let model = make_model();
+        let mut dest = Scene::new();
+        let options = InstantiationOptions {
+            animations: false,
+            material: Some(Arc::new(Mutex::new(Material::new()))),
+            physics: false,
+            root_scale: Vec3::new(2.0, 2.0, 2.0),
+        };
+        let instance = model
+            .lock()
+            .unwrap()
+            .instantiate_with_options(&mut dest, options);
+
+        let mesh = dest.graph.find_by_name(instance.root, "Mesh");
+        assert!(instance.animations.is_empty());
+        assert!(dest.physics_binder.body_of(mesh).is_none());
+        assert_eq!(
+            dest.graph[instance.root].local_transform().scale(),
+            Vec3::new(2.0, 2.0, 2.0)
+        );
+        if let Node::Mesh(mesh) = &dest.graph[mesh] {
+            assert!(mesh.surfaces()[0].material().is_some());
+        } else {
+            panic!("Node must be mesh!");
+        }
+    }
+}
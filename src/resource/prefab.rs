@@ -0,0 +1,206 @@
+//! Contains all data structures and method to work with prefab resources.
+//!
+//! Prefab is a reusable piece of a scene - a hierarchy of nodes (for example door with
+//! trigger and sounds) with animations of these nodes. Unlike model resources, which are
+//! made in 3d modelling software, prefabs are made from existing scene nodes by
+//! `Scene::save_prefab` and can contain anything that a scene can contain, including
+//! instances of models and other prefabs (nested prefabs).
+//!
+//! # Overrides
+//!
+//! Every instance of prefab keeps reference to prefab and to its original node in prefab.
+//! Properties that were changed in an instance are saved as overrides, every other
+//! property is taken from prefab when saved scene is loaded. So if you change a prefab,
+//! changes will be propagated to every instance without losing per-instance changes. See
+//! `scene::base` module docs for more info.
+//!
+//! # File format
+//!
+//! Prefab is stored in native binary format of the engine and contains single scene in
+//! `Prefab` region. Common extension for prefabs is `rgp`.
+use crate::{
+    animation::Animation,
+    core::{
+        pool::Handle,
+        visitor::{Visit, VisitError, VisitResult, Visitor},
+    },
+    engine::resource_manager::ResourceManager,
+    scene::{node::Node, Scene},
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+};
+
+/// See module docs.
+#[derive(Debug)]
+pub struct Prefab {
+    // enable_shared_from_this trick from C++
+    pub(in crate) self_weak_ref: Option<Weak<Mutex<Prefab>>>,
+    pub(in crate) path: PathBuf,
+    scene: Scene,
+}
+
+impl Default for Prefab {
+    fn default() -> Self {
+        Self {
+            self_weak_ref: None,
+            path: PathBuf::new(),
+            scene: Scene::new(),
+        }
+    }
+}
+
+impl Visit for Prefab {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.self_weak_ref.visit("SelfWeakRef", visitor)?;
+        self.path.visit("Path", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Prefab instance is a combination of handle to root node of instance in a scene,
+/// and list of all animations from prefab which were instantiated on a scene.
+pub struct PrefabInstance {
+    /// Handle of root node of instance.
+    pub root: Handle<Node>,
+
+    /// List of instantiated animations that were inside prefab.
+    pub animations: Vec<Handle<Animation>>,
+}
+
+impl Prefab {
+    pub(in crate) fn load<P: AsRef<Path>>(
+        path: P,
+        resource_manager: &mut ResourceManager,
+    ) -> Result<Prefab, VisitError> {
+        let mut scene = Scene::default();
+        let mut visitor = resource_manager.vfs().load_visitor(path.as_ref())?;
+        visitor.enter_region("Prefab")?;
+        scene.visit("Scene", &mut visitor)?;
+        visitor.leave_region()?;
+
+        scene.restore_resources(resource_manager);
+        scene.resolve();
+
+        Ok(Prefab {
+            self_weak_ref: None,
+            path: path.as_ref().to_owned(),
+            scene,
+        })
+    }
+
+    /// Saves given scene as prefab. Scene must contain single node attached to root
+    /// of a graph, see `Scene::make_prefab`.
+    pub(in crate) fn save<P: AsRef<Path>>(scene: &mut Scene, path: P) -> VisitResult {
+        let mut visitor = Visitor::new();
+        visitor.enter_region("Prefab")?;
+        scene.visit("Scene", &mut visitor)?;
+        visitor.leave_region()?;
+        visitor.save_binary(path.as_ref())
+    }
+
+    /// Returns handle of root node of prefab in prefab's scene.
+    pub fn root(&self) -> Handle<Node> {
+        let graph = &self.scene.graph;
+        graph[graph.get_root()]
+            .children()
+            .first()
+            .copied()
+            .unwrap_or(Handle::NONE)
+    }
+
+    /// Creates copy of prefab's nodes and animations in given scene. Returned root is
+    /// attached to root of destination scene. Each instantiated node remembers its
+    /// original node in prefab, so prefab changes will be propagated to instances when
+    /// a saved scene is loaded.
+    pub fn instantiate(&self, dest_scene: &mut Scene) -> PrefabInstance {
+        let root = self.root();
+        if root.is_none() {
+            return PrefabInstance {
+                root: Handle::NONE,
+                animations: Vec::new(),
+            };
+        }
+
+        let (instance_root, old_new_map) =
+            self.scene
+                .graph
+                .copy_node(root, &mut dest_scene.graph, &mut |_, _| true);
+
+        // This .expect will never be triggered in normal conditions because there is only
+        // one way to get prefab - through resource manager which always returns Arc and
+        // sets correct self ref.
+        let this = self
+            .self_weak_ref
+            .as_ref()
+            .and_then(|self_weak_ref| self_weak_ref.upgrade())
+            .expect("Prefab self weak ref must be valid!");
+
+        for (&prefab_node, &instance_node) in old_new_map.iter() {
+            let node = &mut dest_scene.graph[instance_node];
+            node.prefab = Some(this.clone());
+            node.prefab_original = prefab_node;
+            // Copying sets original handle to node of prefab, but nodes of prefab can be
+            // instances of models too - such nodes must keep their original in model.
+            node.original = self.scene.graph[prefab_node].original;
+        }
+
+        let mut animations = Vec::new();
+        for animation in self.scene.animations.iter() {
+            let mut animation = animation.clone();
+            animation.retain_tracks(|track| old_new_map.contains_key(&track.get_node()));
+            for track in animation.get_tracks_mut() {
+                track.set_node(old_new_map[&track.get_node()]);
+            }
+            animation
+                .retain_parameter_tracks(|track| old_new_map.contains_key(&track.binding().node));
+            for track in animation.get_parameter_tracks_mut() {
+                let mut binding = track.binding();
+                binding.node = old_new_map[&binding.node];
+                track.set_binding(binding);
+            }
+            animation.remap_root_motion_node(&old_new_map);
+            animations.push(dest_scene.animations.add(animation));
+        }
+
+        PrefabInstance {
+            root: instance_root,
+            animations,
+        }
+    }
+
+    /// Returns list of paths of every model resource and prefab that is used by this
+    /// prefab. Textures and sounds are not included.
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        let mut dependencies = Vec::new();
+        for node in self.scene.graph.linear_iter() {
+            let path = if let Some(prefab) = node.prefab() {
+                prefab.lock().unwrap().path.clone()
+            } else if let Some(model) = node.resource() {
+                model.lock().unwrap().path.clone()
+            } else {
+                continue;
+            };
+            if !dependencies.contains(&path) {
+                dependencies.push(path);
+            }
+        }
+        dependencies
+    }
+
+    /// Returns path to source file.
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Returns shared reference to internal scene, there is no way to obtain
+    /// mutable reference to inner scene because prefab is immutable source
+    /// of data.
+    pub fn get_scene(&self) -> &Scene {
+        &self.scene
+    }
+}
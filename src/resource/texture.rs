@@ -1,13 +1,65 @@
 //! Texture is an image that used to fill faces to add details to them.
 //!
 //! In most cases textures are just 2D images, however there are some exclusions to that -
-//! for example cube maps, that may be used for environment mapping. For now only 2D textures
-//! are supported.
+//! for example cube maps, that may be used for environment mapping. Cube maps are separate
+//! resources, see `cube_map` module docs.
 //!
 //! # Supported formats
 //!
 //! To load images and decode them, rg3d uses image create which supports following image
-//! formats: png, tga, bmp, dds, jpg, gif, tiff, dxt.
+//! formats: png, tga, bmp, jpg, gif, tiff.
+//!
+//! # Compressed textures
+//!
+//! DDS and KTX2 files are loaded by the engine itself. Block-compressed (BC1-BC7) data from
+//! such files is uploaded to GPU as is, without decompression, so compressed textures take
+//! 4-8 times less video memory than RGBA8 textures and load faster. Mip levels are taken
+//! from the file too, if there are any, otherwise compressed texture has only one level.
+//! Uncompressed R8, RGB8, RGBA8, RGB32F and RGBA32F data is supported in KTX2 files as well.
+//! Cube maps, texture arrays, volume textures and supercompressed KTX2 files are not
+//! supported yet.
+//!
+//! # Basis Universal
+//!
+//! `.basis` files and KTX2 files with Basis Universal data are transcoded at load time to
+//! the best compressed format supported by GPU, transcoder must be provided by the game,
+//! see `basis` module docs.
+//!
+//! # HDR images
+//!
+//! Radiance HDR (`.hdr`) and OpenEXR (`.exr`) images are loaded as float textures (`RGB32F`
+//! or `RGBA32F`) without clamping of colors, so they can be used for HDR skyboxes and
+//! image-based lighting. Only scanline EXR files with uncompressed, ZIPS or ZIP compressed
+//! data are supported, tiled and multi-part files are not. HDR textures are always linear.
+//!
+//! # Sampling
+//!
+//! Every texture has its own sampler settings (`TextureSampler`): minification and
+//! magnification filters, wrap modes and anisotropy level. Mip maps are generated
+//! automatically when texture is uploaded to GPU (unless it has its own mip levels or is
+//! compressed), and default filtering uses them with maximum anisotropy, so distant surfaces
+//! do not shimmer. Pixel art or UI textures may want nearest filtering without mip maps.
+//! Sampler can be changed at any time using `Texture::set_sampler`, default sampler of newly
+//! loaded textures is set by `ResourceManager::set_default_texture_sampler`.
+//!
+//! # Color space
+//!
+//! Renderer does lighting in linear color space, but most images (diffuse maps, UI images,
+//! sprites) are authored in sRGB color space, so their texels must be converted to linear
+//! color space when they're fetched. Such textures must be marked as sRGB textures, which is
+//! done by resource manager for every requested texture by default. Textures with data (normal
+//! maps, height maps, masks, etc.) must stay linear, otherwise their values will be distorted,
+//! they should be requested with `TextureRequestOptions::srgb` set to `false` or marked using
+//! `Texture::set_srgb`. Textures with one or two channels (R8, BC4, BC5) and HDR textures are
+//! always linear.
+//!
+//! # Procedural textures
+//!
+//! Textures can be created from pixels which are generated by a game (noise maps, gradients,
+//! minimap images, video frames, etc.) using `Texture::from_bytes`. Pixels of uncompressed
+//! textures can be changed later using `Texture::update_region`, renderer uploads texture to
+//! GPU again next time it is used. Upload is not cheap for large textures, so it is better to
+//! update a texture once per frame instead of many small updates.
 //!
 //! # Render target
 //!
@@ -16,9 +68,19 @@
 //! will automatically provide you info about metrics of texture, but it won't give you
 //! access to pixels of render target.
 
-use crate::core::visitor::{Visit, VisitResult, Visitor};
-use image::{ColorType, GenericImageView, ImageError};
-use std::path::{Path, PathBuf};
+use crate::{
+    core::visitor::{Visit, VisitResult, Visitor},
+    engine::vfs::Vfs,
+    resource::{
+        basis::{self, BasisContainer},
+        dds, exr, hdr, ktx2,
+    },
+};
+use image::{ColorType, GenericImageView, ImageError, ImageFormat};
+use std::{
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
 
 /// See module docs.
 #[derive(Debug)]
@@ -26,9 +88,16 @@ pub struct Texture {
     pub(in crate) path: PathBuf,
     pub(in crate) width: u32,
     pub(in crate) height: u32,
+    /// All mip levels one after another, starting from the largest one.
     pub(in crate) bytes: Vec<u8>,
+    pub(in crate) mip_count: u32,
     pub(in crate) kind: TextureKind,
     pub(in crate) loaded: bool,
+    pub(in crate) sampler: TextureSampler,
+    pub(in crate) srgb: bool,
+    /// Incremented on every modification of pixels, renderer uses it to find out that GPU
+    /// texture must be updated.
+    pub(in crate) revision: u64,
 }
 
 impl Default for Texture {
@@ -38,8 +107,12 @@ impl Default for Texture {
             width: 0,
             height: 0,
             bytes: Vec::new(),
+            mip_count: 1,
             kind: TextureKind::RGBA8,
             loaded: true,
+            sampler: Default::default(),
+            srgb: false,
+            revision: 0,
         }
     }
 }
@@ -55,6 +128,185 @@ impl Visit for Texture {
         }
 
         self.path.visit("Path", visitor)?;
+        let _ = self.sampler.visit("Sampler", visitor);
+        let _ = self.srgb.visit("Srgb", visitor);
+
+        visitor.leave_region()
+    }
+}
+
+/// Filter which is used when texture is drawn smaller than its actual size. Filters with
+/// mip maps select one (`MipNearest`) or blend two (`MipLinear`) nearest mip levels.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TextureMinificationFilter {
+    /// Nearest pixel of the largest mip level, fastest and the most noisy filter.
+    Nearest,
+    /// Nearest pixel of nearest mip level.
+    NearestMipNearest,
+    /// Nearest pixels of two nearest mip levels blended together.
+    NearestMipLinear,
+    /// Bilinear filtering of the largest mip level.
+    Linear,
+    /// Bilinear filtering of nearest mip level.
+    LinearMipNearest,
+    /// Trilinear filtering, the smoothest filter.
+    LinearMipLinear,
+}
+
+impl TextureMinificationFilter {
+    fn new(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::Nearest),
+            1 => Ok(Self::NearestMipNearest),
+            2 => Ok(Self::NearestMipLinear),
+            3 => Ok(Self::Linear),
+            4 => Ok(Self::LinearMipNearest),
+            5 => Ok(Self::LinearMipLinear),
+            _ => Err(format!("Invalid minification filter {}!", id)),
+        }
+    }
+
+    fn id(self) -> u32 {
+        match self {
+            Self::Nearest => 0,
+            Self::NearestMipNearest => 1,
+            Self::NearestMipLinear => 2,
+            Self::Linear => 3,
+            Self::LinearMipNearest => 4,
+            Self::LinearMipLinear => 5,
+        }
+    }
+
+    /// Returns true if filter uses mip maps.
+    pub fn uses_mip_maps(self) -> bool {
+        match self {
+            Self::Nearest | Self::Linear => false,
+            _ => true,
+        }
+    }
+
+    /// Returns same filter without mip maps.
+    pub fn without_mip_maps(self) -> Self {
+        match self {
+            Self::Nearest | Self::NearestMipNearest | Self::NearestMipLinear => Self::Nearest,
+            Self::Linear | Self::LinearMipNearest | Self::LinearMipLinear => Self::Linear,
+        }
+    }
+}
+
+/// Filter which is used when texture is drawn bigger than its actual size.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TextureMagnificationFilter {
+    /// Nearest pixel, gives blocky look which is good for pixel art.
+    Nearest,
+    /// Bilinear filtering.
+    Linear,
+}
+
+impl TextureMagnificationFilter {
+    fn new(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::Nearest),
+            1 => Ok(Self::Linear),
+            _ => Err(format!("Invalid magnification filter {}!", id)),
+        }
+    }
+
+    fn id(self) -> u32 {
+        match self {
+            Self::Nearest => 0,
+            Self::Linear => 1,
+        }
+    }
+}
+
+/// Defines what happens with texture coordinates outside of [0; 1] range.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TextureWrapMode {
+    /// Texture is repeated.
+    Repeat,
+    /// Texture is repeated, every other copy is mirrored.
+    MirroredRepeat,
+    /// Edge pixels are stretched.
+    ClampToEdge,
+    /// Transparent black color is used.
+    ClampToBorder,
+}
+
+impl TextureWrapMode {
+    fn new(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Self::Repeat),
+            1 => Ok(Self::MirroredRepeat),
+            2 => Ok(Self::ClampToEdge),
+            3 => Ok(Self::ClampToBorder),
+            _ => Err(format!("Invalid wrap mode {}!", id)),
+        }
+    }
+
+    fn id(self) -> u32 {
+        match self {
+            Self::Repeat => 0,
+            Self::MirroredRepeat => 1,
+            Self::ClampToEdge => 2,
+            Self::ClampToBorder => 3,
+        }
+    }
+}
+
+/// Defines how texture is sampled by GPU, see module docs.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TextureSampler {
+    /// Filter which is used when texture is drawn smaller than its actual size.
+    pub min_filter: TextureMinificationFilter,
+    /// Filter which is used when texture is drawn bigger than its actual size.
+    pub mag_filter: TextureMagnificationFilter,
+    /// Wrap mode along horizontal axis.
+    pub s_wrap_mode: TextureWrapMode,
+    /// Wrap mode along vertical axis.
+    pub t_wrap_mode: TextureWrapMode,
+    /// Level of anisotropic filtering, it is clamped to maximum level supported by hardware.
+    /// 1.0 disables anisotropic filtering.
+    pub anisotropy: f32,
+    /// Whether mip maps should be generated when texture is uploaded to GPU. Has no effect
+    /// for textures with own mip levels and for compressed textures. If texture has no mip
+    /// maps, minification filter falls back to its variant without mip maps.
+    pub generate_mip_maps: bool,
+}
+
+impl Default for TextureSampler {
+    fn default() -> Self {
+        Self {
+            min_filter: TextureMinificationFilter::LinearMipLinear,
+            mag_filter: TextureMagnificationFilter::Linear,
+            s_wrap_mode: TextureWrapMode::Repeat,
+            t_wrap_mode: TextureWrapMode::Repeat,
+            anisotropy: 16.0,
+            generate_mip_maps: true,
+        }
+    }
+}
+
+impl Visit for TextureSampler {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut min_filter = self.min_filter.id();
+        min_filter.visit("MinFilter", visitor)?;
+        let mut mag_filter = self.mag_filter.id();
+        mag_filter.visit("MagFilter", visitor)?;
+        let mut s_wrap_mode = self.s_wrap_mode.id();
+        s_wrap_mode.visit("SWrapMode", visitor)?;
+        let mut t_wrap_mode = self.t_wrap_mode.id();
+        t_wrap_mode.visit("TWrapMode", visitor)?;
+        if visitor.is_reading() {
+            self.min_filter = TextureMinificationFilter::new(min_filter)?;
+            self.mag_filter = TextureMagnificationFilter::new(mag_filter)?;
+            self.s_wrap_mode = TextureWrapMode::new(s_wrap_mode)?;
+            self.t_wrap_mode = TextureWrapMode::new(t_wrap_mode)?;
+        }
+        self.anisotropy.visit("Anisotropy", visitor)?;
+        self.generate_mip_maps.visit("GenerateMipMaps", visitor)?;
 
         visitor.leave_region()
     }
@@ -69,49 +321,200 @@ pub enum TextureKind {
     RGB8,
     /// Red, green, blue, and alpha components, each by 1 byte.
     RGBA8,
+    /// Block-compressed RGB with optional 1-bit alpha, also known as DXT1.
+    BC1,
+    /// Block-compressed RGBA with explicit 4-bit alpha, also known as DXT3.
+    BC2,
+    /// Block-compressed RGBA with interpolated alpha, also known as DXT5.
+    BC3,
+    /// Block-compressed single red channel.
+    BC4,
+    /// Block-compressed red and green channels, mostly used for normal maps.
+    BC5,
+    /// Block-compressed unsigned high dynamic range RGB.
+    BC6H,
+    /// High quality block-compressed RGBA.
+    BC7,
+    /// Red, green, and blue components, each is 32-bit float. Used for HDR images.
+    RGB32F,
+    /// Red, green, blue, and alpha components, each is 32-bit float. Used for HDR images.
+    RGBA32F,
 }
 
 impl TextureKind {
-    fn new(id: u32) -> Result<Self, String> {
+    pub(in crate) fn new(id: u32) -> Result<Self, String> {
         match id {
             0 => Ok(Self::R8),
             1 => Ok(Self::RGB8),
             2 => Ok(Self::RGBA8),
+            3 => Ok(Self::BC1),
+            4 => Ok(Self::BC2),
+            5 => Ok(Self::BC3),
+            6 => Ok(Self::BC4),
+            7 => Ok(Self::BC5),
+            8 => Ok(Self::BC6H),
+            9 => Ok(Self::BC7),
+            10 => Ok(Self::RGB32F),
+            11 => Ok(Self::RGBA32F),
             _ => Err(format!("Invalid texture kind {}!", id)),
         }
     }
 
-    fn id(self) -> u32 {
+    pub(in crate) fn id(self) -> u32 {
         match self {
             Self::R8 => 0,
             Self::RGB8 => 1,
             Self::RGBA8 => 2,
+            Self::BC1 => 3,
+            Self::BC2 => 4,
+            Self::BC3 => 5,
+            Self::BC4 => 6,
+            Self::BC5 => 7,
+            Self::BC6H => 8,
+            Self::BC7 => 9,
+            Self::RGB32F => 10,
+            Self::RGBA32F => 11,
         }
     }
 
-    fn bytes_per_pixel(&self) -> u32 {
+    /// Returns true if texture of this kind stores pixels in compressed blocks.
+    pub fn is_compressed(self) -> bool {
+        match self {
+            Self::R8 | Self::RGB8 | Self::RGBA8 | Self::RGB32F | Self::RGBA32F => false,
+            _ => true,
+        }
+    }
+
+    /// Returns true if texture of this kind stores high dynamic range colors as floats.
+    pub fn is_hdr(self) -> bool {
+        matches!(self, Self::RGB32F | Self::RGBA32F)
+    }
+
+    /// Returns amount of bytes required to store image of given size. Compressed kinds
+    /// store pixels in blocks of 4x4 pixels, so size is rounded up to size of a block.
+    pub fn image_size_bytes(self, width: u32, height: u32) -> usize {
+        let (width, height) = (width as usize, height as usize);
         match self {
-            Self::R8 => 1,
-            Self::RGB8 => 3,
-            Self::RGBA8 => 4,
+            Self::R8 => width * height,
+            Self::RGB8 => width * height * 3,
+            Self::RGBA8 => width * height * 4,
+            Self::RGB32F => width * height * 12,
+            Self::RGBA32F => width * height * 16,
+            Self::BC1 | Self::BC4 => ((width + 3) / 4) * ((height + 3) / 4) * 8,
+            Self::BC2 | Self::BC3 | Self::BC5 | Self::BC6H | Self::BC7 => {
+                ((width + 3) / 4) * ((height + 3) / 4) * 16
+            }
         }
     }
+
+    /// Returns amount of bytes required to store given amount of mip levels of image of
+    /// given size.
+    pub fn mip_chain_size_bytes(self, width: u32, height: u32, mip_count: u32) -> usize {
+        (0..mip_count)
+            .map(|level| self.image_size_bytes((width >> level).max(1), (height >> level).max(1)))
+            .sum()
+    }
+}
+
+/// An error that can occur during texture loading.
+#[derive(Debug)]
+pub enum TextureError {
+    /// Image crate failed to load or decode an image.
+    Image(ImageError),
+    /// An input/output error has occurred.
+    Io(std::io::Error),
+    /// File has format which is not supported, string contains description.
+    UnsupportedFormat(String),
+    /// File is damaged or truncated, string contains description.
+    InvalidData(String),
+}
+
+impl Display for TextureError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            TextureError::Image(e) => write!(f, "Image error: {}", e),
+            TextureError::Io(e) => write!(f, "Io error: {}", e),
+            TextureError::UnsupportedFormat(e) => write!(f, "Unsupported format: {}", e),
+            TextureError::InvalidData(e) => write!(f, "Invalid data: {}", e),
+        }
+    }
+}
+
+impl From<ImageError> for TextureError {
+    fn from(e: ImageError) -> Self {
+        TextureError::Image(e)
+    }
+}
+
+impl From<std::io::Error> for TextureError {
+    fn from(e: std::io::Error) -> Self {
+        TextureError::Io(e)
+    }
 }
 
 impl Texture {
-    pub(in crate) fn load_from_file<P: AsRef<Path>>(
+    /// Loads texture from file of virtual file system. Kind of textures from DDS, KTX2, HDR
+    /// and EXR files is defined by the file, given kind is used for images of other formats
+    /// which are converted to it.
+    pub(in crate) fn load<P: AsRef<Path>>(
         path: P,
         kind: TextureKind,
-    ) -> Result<Self, image::ImageError> {
-        let dyn_img = image::open(path.as_ref())?;
+        vfs: &Vfs,
+    ) -> Result<Self, TextureError> {
+        Self::load_from_memory(path.as_ref(), &vfs.read(path.as_ref())?, kind)
+    }
+
+    /// Decodes texture from contents of its file, path is used to determine format of file.
+    pub(in crate) fn load_from_memory<P: AsRef<Path>>(
+        path: P,
+        data: &[u8],
+        kind: TextureKind,
+    ) -> Result<Self, TextureError> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("dds") => {
+                let mut texture = dds::load(data)?;
+                texture.path = path.as_ref().to_path_buf();
+                return Ok(texture);
+            }
+            Some("ktx2") => {
+                let mut texture = ktx2::load(data)?;
+                texture.path = path.as_ref().to_path_buf();
+                return Ok(texture);
+            }
+            Some("hdr") => {
+                let mut texture = hdr::load(data)?;
+                texture.path = path.as_ref().to_path_buf();
+                return Ok(texture);
+            }
+            Some("exr") => {
+                let mut texture = exr::load(data)?;
+                texture.path = path.as_ref().to_path_buf();
+                return Ok(texture);
+            }
+            Some("basis") => {
+                let mut texture = basis::load(data, BasisContainer::Basis)?;
+                texture.path = path.as_ref().to_path_buf();
+                return Ok(texture);
+            }
+            _ => (),
+        }
+
+        let format = ImageFormat::from_path(path.as_ref())?;
+        let dyn_img = image::load_from_memory_with_format(data, format)?;
 
         let width = dyn_img.width();
         let height = dyn_img.height();
 
-        let bytes = match kind {
-            TextureKind::R8 => dyn_img.to_luma().into_raw(),
-            TextureKind::RGB8 => dyn_img.to_rgb().into_raw(),
-            TextureKind::RGBA8 => dyn_img.to_rgba().into_raw(),
+        let (kind, bytes) = match kind {
+            TextureKind::R8 => (kind, dyn_img.to_luma().into_raw()),
+            TextureKind::RGB8 => (kind, dyn_img.to_rgb().into_raw()),
+            // Images are never compressed or converted to floats by the engine, so such kind
+            // (which could be stored in a saved scene) means RGBA8.
+            _ => (TextureKind::RGBA8, dyn_img.to_rgba().into_raw()),
         };
 
         Ok(Self {
@@ -119,58 +522,209 @@ impl Texture {
             width,
             height,
             bytes,
+            mip_count: 1,
             path: path.as_ref().to_path_buf(),
             loaded: true,
+            sampler: Default::default(),
+            srgb: false,
+            revision: 0,
         })
     }
 
-    /// Creates new texture instance from given parameters.
+    /// Creates new texture of given size and kind from given pixels (one mip level), see
+    /// module docs. Fails if amount of bytes does not match size and kind of texture.
     pub fn from_bytes(
         width: u32,
         height: u32,
         kind: TextureKind,
         bytes: Vec<u8>,
-    ) -> Result<Self, ()> {
-        let bpp = kind.bytes_per_pixel();
-        let required_bytes = width * height * bpp;
-        if required_bytes != bytes.len() as u32 {
-            Err(())
+    ) -> Result<Self, TextureError> {
+        let expected_length = kind.image_size_bytes(width, height);
+        if expected_length != bytes.len() {
+            Err(TextureError::InvalidData(format!(
+                "{}x{} {:?} texture must have {} bytes, got {}",
+                width,
+                height,
+                kind,
+                expected_length,
+                bytes.len()
+            )))
         } else {
             Ok(Self {
                 path: Default::default(),
                 width,
                 height,
                 bytes,
+                mip_count: 1,
                 kind,
                 loaded: true,
+                sampler: Default::default(),
+                srgb: false,
+                revision: 0,
             })
         }
     }
 
+    /// Replaces pixels of given rectangle of the texture, `pixels` must contain rows of the
+    /// rectangle one after another in pixel format of the texture. Mip levels stored in the
+    /// texture are discarded, they're generated on GPU again if sampler uses them. Only
+    /// uncompressed textures can be updated.
+    pub fn update_region(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        pixels: &[u8],
+    ) -> Result<(), TextureError> {
+        if self.kind.is_compressed() {
+            return Err(TextureError::UnsupportedFormat(format!(
+                "update of compressed {:?} texture",
+                self.kind
+            )));
+        }
+        if x.saturating_add(width) > self.width || y.saturating_add(height) > self.height {
+            return Err(TextureError::InvalidData(format!(
+                "region {}x{} at ({}, {}) is out of bounds of {}x{} texture",
+                width,
+                height,
+                x,
+                y,
+                self.width,
+                self.height
+            )));
+        }
+        let expected_length = self.kind.image_size_bytes(width, height);
+        if pixels.len() != expected_length {
+            return Err(TextureError::InvalidData(format!(
+                "region {}x{} must have {} bytes, got {}",
+                width,
+                height,
+                expected_length,
+                pixels.len()
+            )));
+        }
+        if pixels.is_empty() {
+            return Ok(());
+        }
+
+        if self.mip_count > 1 {
+            self.bytes.truncate(self.kind.image_size_bytes(self.width, self.height));
+            self.mip_count = 1;
+        }
+
+        let pixel_size = self.kind.image_size_bytes(1, 1);
+        let row_size = width as usize * pixel_size;
+        for (row, source) in pixels.chunks(row_size).enumerate() {
+            let start = ((y as usize + row) * self.width as usize + x as usize) * pixel_size;
+            self.bytes[start..start + row_size].copy_from_slice(source);
+        }
+        self.revision += 1;
+
+        Ok(())
+    }
+
+    /// Returns width of the texture.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns height of the texture.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns pixels of the texture, all mip levels one after another, starting from the
+    /// largest one.
+    pub fn data(&self) -> &[u8] {
+        &self.bytes
+    }
+
     /// Returns true if texture is loaded. This is hacky method to support poorman's async
     /// texture loading. This will be changed in future. For now this is a TODO.
     pub fn is_loaded(&self) -> bool {
         self.loaded
     }
 
+    /// Returns sampler settings of texture.
+    pub fn sampler(&self) -> TextureSampler {
+        self.sampler
+    }
+
+    /// Sets new sampler settings of texture, see module docs. Settings are applied to GPU
+    /// texture next time texture is used by renderer.
+    pub fn set_sampler(&mut self, sampler: TextureSampler) {
+        self.sampler = sampler;
+    }
+
+    /// Returns true if texels of texture are in sRGB color space, see module docs.
+    pub fn is_srgb(&self) -> bool {
+        self.srgb
+    }
+
+    /// Sets whether texels of texture are in sRGB color space or not, see module docs. Texture
+    /// will be uploaded to GPU again next time it is used by renderer.
+    pub fn set_srgb(&mut self, srgb: bool) {
+        self.srgb = srgb;
+    }
+
     /// Sets new path to source file.
     pub fn set_path<P: AsRef<Path>>(&mut self, path: &P) {
         self.path = path.as_ref().to_owned();
     }
 
-    /// Tries to save internal buffer into source file.
-    pub fn save(&self) -> Result<(), ImageError> {
+    /// Returns amount of mip levels stored in the texture. Mip levels of textures with only
+    /// one level are generated on GPU, if texture is not compressed.
+    pub fn mip_count(&self) -> u32 {
+        self.mip_count
+    }
+
+    /// Returns pixel format of the texture.
+    pub fn kind(&self) -> TextureKind {
+        self.kind
+    }
+
+    /// Tries to save base mip level of the texture into image file with given path, format of
+    /// the file is defined by its extension (png, tga, bmp, etc.). Only R8, RGB8 and RGBA8
+    /// textures can be saved. Path of the texture is not changed.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), ImageError> {
         let color_type = match self.kind {
             TextureKind::R8 => ColorType::L8,
             TextureKind::RGB8 => ColorType::Rgb8,
             TextureKind::RGBA8 => ColorType::Rgba8,
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Only R8, RGB8 and RGBA8 textures can be saved",
+                )
+                .into())
+            }
         };
+        let base_level_size = self.kind.image_size_bytes(self.width, self.height);
         image::save_buffer(
-            &self.path,
-            self.bytes.as_ref(),
+            path,
+            &self.bytes[..base_level_size],
             self.width,
             self.height,
             color_type,
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::resource::texture::{Texture, TextureKind};
+
+    #[test]
+    fn update_region_of_procedural_texture() {
+        assert!(Texture::from_bytes(2, 2, TextureKind::RGB8, vec![0; 4]).is_err());
+
+        let mut texture = Texture::from_bytes(3, 2, TextureKind::R8, vec![0; 6]).unwrap();
+        texture.update_region(1, 0, 2, 2, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(texture.data(), &[0, 1, 2, 0, 3, 4]);
+        assert_eq!(texture.revision, 1);
+
+        assert!(texture.update_region(2, 1, 2, 1, &[5, 6]).is_err());
+        assert_eq!(texture.revision, 1);
+    }
+}
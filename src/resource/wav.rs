@@ -0,0 +1,315 @@
+//! Contains reader of loop regions, cue points and other metadata of WAV files.
+//!
+//! Sound editors store loop points of a sound in `smpl` chunk of WAV file, and markers in `cue `
+//! chunk (with names in `labl` chunks of `LIST` chunk). This allows to keep a music track with
+//! intro and looped part, or an ambience with seamless loop, in one file: a game plays sound
+//! from the beginning and jumps back to start of a loop region when playback reaches its end.
+//! Sound sources of sound context loop whole buffer, so jumping is done by a game using loop
+//! regions of the file. Positions are measured in sample frames (one sample of every channel),
+//! use `WavMetadata::frame_to_seconds` to convert them to time.
+//!
+//! Metadata is read without decoding samples, samples are skipped, so reading is cheap even for
+//! long files. Use `ResourceManager::request_wav_metadata` to read metadata of a sound file.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::engine::resource_manager::ResourceManager;
+//!
+//! fn print_loops(resource_manager: &ResourceManager) {
+//!     if let Some(metadata) = resource_manager.request_wav_metadata("data/music.wav") {
+//!         for region in metadata.loops.iter() {
+//!             println!(
+//!                 "loop from {}s to {}s",
+//!                 metadata.frame_to_seconds(region.start),
+//!                 metadata.frame_to_seconds(region.end)
+//!             );
+//!         }
+//!     }
+//! }
+//! ```
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{self, Cursor, Read};
+
+// Chunks with metadata are small, larger chunks are skipped to not allocate memory for damaged
+// files.
+const MAX_METADATA_CHUNK_SIZE: u32 = 1024 * 1024;
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+/// Direction of playback of a loop region.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LoopKind {
+    /// Region is played from start to end.
+    Forward,
+    /// Region is played forward and then backward.
+    PingPong,
+    /// Region is played from end to start.
+    Backward,
+}
+
+/// Looped part of a sound.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LoopRegion {
+    /// Identifier of cue point of the region, editors use it to name regions.
+    pub cue_id: u32,
+    /// Direction of playback.
+    pub kind: LoopKind,
+    /// First frame of the region.
+    pub start: u32,
+    /// Frame after last frame of the region.
+    pub end: u32,
+    /// How many times region must be played, zero means infinite looping.
+    pub play_count: u32,
+}
+
+/// Marker at some position of a sound.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CuePoint {
+    /// Identifier of the cue point.
+    pub id: u32,
+    /// Position of the cue point in frames.
+    pub position: u32,
+    /// Name of the cue point, empty if there is no name.
+    pub label: String,
+}
+
+/// Metadata of WAV file, see module docs.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WavMetadata {
+    /// Amount of channels.
+    pub channel_count: u16,
+    /// Amount of frames per second.
+    pub sample_rate: u32,
+    /// Total amount of frames.
+    pub frame_count: u32,
+    /// Loop regions from `smpl` chunk.
+    pub loops: Vec<LoopRegion>,
+    /// Cue points from `cue ` chunk.
+    pub cues: Vec<CuePoint>,
+}
+
+impl WavMetadata {
+    /// Reads metadata of WAV file from given reader.
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut id = [0; 4];
+        reader.read_exact(&mut id)?;
+        let _riff_size = reader.read_u32::<LittleEndian>()?;
+        let mut format = [0; 4];
+        reader.read_exact(&mut format)?;
+        if &id != b"RIFF" || &format != b"WAVE" {
+            return Err(invalid_data("not a WAV file"));
+        }
+
+        let mut metadata = Self::default();
+        let mut block_align = 0;
+        let mut labels = Vec::new();
+        loop {
+            match reader.read_exact(&mut id) {
+                Ok(_) => (),
+                // Chunks can't be split, so end of file here is end of the list of chunks.
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            let size = reader.read_u32::<LittleEndian>()?;
+            // Chunks are aligned to two bytes.
+            let padded_size = u64::from(size) + u64::from(size % 2);
+
+            let is_metadata = matches!(&id, b"fmt " | b"smpl" | b"cue " | b"LIST");
+            if &id == b"data" {
+                metadata.frame_count = if block_align != 0 {
+                    size / u32::from(block_align)
+                } else {
+                    0
+                };
+            } else if is_metadata && size <= MAX_METADATA_CHUNK_SIZE {
+                let mut data = vec![0; size as usize];
+                reader.read_exact(&mut data)?;
+                if size % 2 == 1 {
+                    // Pad byte of last chunk is often missing.
+                    let _ = reader.read_u8();
+                }
+                let mut chunk = Cursor::new(data);
+                match &id {
+                    b"fmt " => {
+                        let _format_tag = chunk.read_u16::<LittleEndian>()?;
+                        metadata.channel_count = chunk.read_u16::<LittleEndian>()?;
+                        metadata.sample_rate = chunk.read_u32::<LittleEndian>()?;
+                        let _bytes_per_second = chunk.read_u32::<LittleEndian>()?;
+                        block_align = chunk.read_u16::<LittleEndian>()?;
+                    }
+                    b"smpl" => metadata.loops = read_loops(&mut chunk)?,
+                    b"cue " => metadata.cues = read_cues(&mut chunk)?,
+                    _ => read_labels(&mut chunk, &mut labels)?,
+                }
+                continue;
+            }
+            io::copy(&mut reader.by_ref().take(padded_size), &mut io::sink())?;
+        }
+
+        for (cue_id, label) in labels {
+            if let Some(cue) = metadata.cues.iter_mut().find(|cue| cue.id == cue_id) {
+                cue.label = label;
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    /// Converts position in frames to position in seconds.
+    pub fn frame_to_seconds(&self, frame: u32) -> f32 {
+        if self.sample_rate == 0 {
+            0.0
+        } else {
+            (f64::from(frame) / f64::from(self.sample_rate)) as f32
+        }
+    }
+}
+
+fn read_loops(chunk: &mut Cursor<Vec<u8>>) -> io::Result<Vec<LoopRegion>> {
+    // Skip manufacturer, product, sample period, MIDI note and pitch, SMPTE format and offset.
+    chunk.set_position(7 * 4);
+    let loop_count = chunk.read_u32::<LittleEndian>()?;
+    let _sampler_data_size = chunk.read_u32::<LittleEndian>()?;
+    let mut loops = Vec::new();
+    for _ in 0..loop_count {
+        let cue_id = chunk.read_u32::<LittleEndian>()?;
+        let kind = match chunk.read_u32::<LittleEndian>()? {
+            1 => LoopKind::PingPong,
+            2 => LoopKind::Backward,
+            // Other values are defined by manufacturers, forward looping is the safest.
+            _ => LoopKind::Forward,
+        };
+        let start = chunk.read_u32::<LittleEndian>()?;
+        // End is inclusive in the file.
+        let end = chunk.read_u32::<LittleEndian>()?.saturating_add(1);
+        let _fraction = chunk.read_u32::<LittleEndian>()?;
+        let play_count = chunk.read_u32::<LittleEndian>()?;
+        if start >= end {
+            return Err(invalid_data("loop region has no frames"));
+        }
+        loops.push(LoopRegion {
+            cue_id,
+            kind,
+            start,
+            end,
+            play_count,
+        });
+    }
+    Ok(loops)
+}
+
+fn read_cues(chunk: &mut Cursor<Vec<u8>>) -> io::Result<Vec<CuePoint>> {
+    let cue_count = chunk.read_u32::<LittleEndian>()?;
+    let mut cues = Vec::new();
+    for _ in 0..cue_count {
+        let id = chunk.read_u32::<LittleEndian>()?;
+        let _play_order_position = chunk.read_u32::<LittleEndian>()?;
+        let _data_chunk_id = chunk.read_u32::<LittleEndian>()?;
+        let _chunk_start = chunk.read_u32::<LittleEndian>()?;
+        let _block_start = chunk.read_u32::<LittleEndian>()?;
+        let position = chunk.read_u32::<LittleEndian>()?;
+        cues.push(CuePoint {
+            id,
+            position,
+            label: String::new(),
+        });
+    }
+    Ok(cues)
+}
+
+fn read_labels(chunk: &mut Cursor<Vec<u8>>, labels: &mut Vec<(u32, String)>) -> io::Result<()> {
+    let mut list_type = [0; 4];
+    chunk.read_exact(&mut list_type)?;
+    if &list_type != b"adtl" {
+        return Ok(());
+    }
+    let mut id = [0; 4];
+    while chunk.read_exact(&mut id).is_ok() {
+        let size = chunk.read_u32::<LittleEndian>()?;
+        let mut data = vec![0; size as usize];
+        chunk.read_exact(&mut data)?;
+        if size % 2 == 1 {
+            let _ = chunk.read_u8();
+        }
+        if &id == b"labl" && data.len() >= 4 {
+            let cue_id = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+            let text = data[4..].split(|&byte| byte == 0).next().unwrap_or_default();
+            labels.push((cue_id, String::from_utf8_lossy(text).into_owned()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resource::wav::{CuePoint, LoopKind, LoopRegion, WavMetadata};
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn words(words: &[u32]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_le_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn read_loop_regions_and_cues() {
+        // Mono 16-bit PCM at 44100 Hz with 100 frames.
+        let mut format = vec![1, 0, 1, 0];
+        format.extend(words(&[44100, 88200]));
+        format.extend_from_slice(&[2, 0, 16, 0]);
+
+        let mut sampler = words(&[0; 7]);
+        sampler.extend(words(&[1, 0]));
+        sampler.extend(words(&[7, 0, 20, 79, 0, 0]));
+
+        let mut cues = words(&[1]);
+        cues.extend(words(&[7, 0, u32::from_le_bytes(*b"data"), 0, 0, 20]));
+
+        let mut labels = b"adtl".to_vec();
+        let mut label = words(&[7]);
+        label.extend_from_slice(b"loop\0");
+        labels.extend(chunk(b"labl", &label));
+
+        let mut body = b"WAVE".to_vec();
+        body.extend(chunk(b"fmt ", &format));
+        body.extend(chunk(b"data", &[0; 200]));
+        body.extend(chunk(b"smpl", &sampler));
+        body.extend(chunk(b"cue ", &cues));
+        body.extend(chunk(b"LIST", &labels));
+        let file = chunk(b"RIFF", &body);
+
+        let metadata = WavMetadata::read(file.as_slice()).unwrap();
+        assert_eq!(metadata.channel_count, 1);
+        assert_eq!(metadata.frame_count, 100);
+        assert_eq!(
+            metadata.loops,
+            vec![LoopRegion {
+                cue_id: 7,
+                kind: LoopKind::Forward,
+                start: 20,
+                end: 80,
+                play_count: 0
+            }]
+        );
+        assert_eq!(
+            metadata.cues,
+            vec![CuePoint {
+                id: 7,
+                position: 20,
+                label: "loop".to_owned()
+            }]
+        );
+        assert!((metadata.frame_to_seconds(44100) - 1.0).abs() < std::f32::EPSILON);
+    }
+}
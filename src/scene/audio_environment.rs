@@ -0,0 +1,387 @@
+//! Contains audio environment volumes.
+//!
+//! Sound of the same source must be different in a small room, in a cathedral and outdoors.
+//! Environment volume is a box attached to a scene node (usually to some piece of level
+//! geometry) which defines acoustic properties of space inside of it - reverberation and
+//! muffling. Each frame engine finds volumes around the listener, blends their environments
+//! and drives parameters of a reverb effect of sound context. So instead of switching reverb
+//! parameters from gameplay code, level designer just places volumes on level.
+//!
+//! # Priority and blending
+//!
+//! Volume fully affects the listener when listener is inside of its box, and its influence
+//! fades out linearly over `blend_distance` outside of the box, which gives smooth transitions
+//! between environments. Volumes can overlap (a small room inside of a big hall), then volumes
+//! with higher priority are applied on top of volumes with lower priority. When there is no
+//! volume around the listener, default environment of container is used.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{math::vec3::Vec3, pool::Handle},
+//!     scene::{
+//!         audio_environment::{AudioEnvironment, EnvironmentVolume},
+//!         node::Node,
+//!         Scene,
+//!     },
+//!     sound::{
+//!         context::Context,
+//!         effects::{reverb::Reverb, BaseEffect, Effect},
+//!     },
+//! };
+//!
+//! fn setup(scene: &mut Scene, context: &mut Context, cave: Handle<Node>) {
+//!     let reverb = context.add_effect(Effect::Reverb(Reverb::new(BaseEffect::default())));
+//!     scene.audio_environment.set_reverb(reverb);
+//!     scene.audio_environment.add(EnvironmentVolume {
+//!         node: cave,
+//!         half_extents: Vec3::new(20.0, 5.0, 20.0),
+//!         environment: AudioEnvironment::CAVE,
+//!         ..Default::default()
+//!     });
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::vec3::Vec3,
+        pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{graph::Graph, node::Node},
+    sound::{context::Context, effects::Effect},
+};
+use std::{collections::HashMap, time::Duration};
+
+/// Acoustic properties of some space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AudioEnvironment {
+    /// Time (in seconds) of reverberation decay.
+    pub reverb_decay_time: f32,
+    /// Amount of direct (not reverberated) signal in [0; 1] range.
+    pub reverb_dry: f32,
+    /// Amount of reverberated signal in [0; 1] range.
+    pub reverb_wet: f32,
+    /// Amount of low-pass filtering of reverberated signal in [0; 1] range, zero means no
+    /// filtering, greater values gives more muffled sound.
+    pub lowpass: f32,
+}
+
+impl AudioEnvironment {
+    /// Open space, almost no reverberation.
+    pub const OUTDOORS: Self = Self {
+        reverb_decay_time: 0.5,
+        reverb_dry: 1.0,
+        reverb_wet: 0.05,
+        lowpass: 0.0,
+    };
+
+    /// Small room.
+    pub const ROOM: Self = Self {
+        reverb_decay_time: 0.8,
+        reverb_dry: 1.0,
+        reverb_wet: 0.3,
+        lowpass: 0.2,
+    };
+
+    /// Big hall with hard walls.
+    pub const HALL: Self = Self {
+        reverb_decay_time: 3.0,
+        reverb_dry: 0.9,
+        reverb_wet: 0.5,
+        lowpass: 0.1,
+    };
+
+    /// Cave, long and dark reverberation.
+    pub const CAVE: Self = Self {
+        reverb_decay_time: 4.5,
+        reverb_dry: 0.8,
+        reverb_wet: 0.7,
+        lowpass: 0.4,
+    };
+
+    /// Listener is under water, everything is muffled.
+    pub const UNDERWATER: Self = Self {
+        reverb_decay_time: 1.5,
+        reverb_dry: 0.3,
+        reverb_wet: 0.8,
+        lowpass: 0.9,
+    };
+
+    /// Linearly interpolates every property of environments.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp = |a: f32, b: f32| a * (1.0 - t) + b * t;
+        Self {
+            reverb_decay_time: lerp(self.reverb_decay_time, other.reverb_decay_time),
+            reverb_dry: lerp(self.reverb_dry, other.reverb_dry),
+            reverb_wet: lerp(self.reverb_wet, other.reverb_wet),
+            lowpass: lerp(self.lowpass, other.lowpass),
+        }
+    }
+}
+
+impl Default for AudioEnvironment {
+    fn default() -> Self {
+        Self::OUTDOORS
+    }
+}
+
+impl Visit for AudioEnvironment {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.reverb_decay_time.visit("ReverbDecayTime", visitor)?;
+        self.reverb_dry.visit("ReverbDry", visitor)?;
+        self.reverb_wet.visit("ReverbWet", visitor)?;
+        self.lowpass.visit("Lowpass", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Box-shaped volume of space with some acoustic properties. See module docs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EnvironmentVolume {
+    /// Node to which volume is attached. Volume is centered at the node and follows its
+    /// transform, including rotation and scale.
+    pub node: Handle<Node>,
+    /// Half extents of box in local coordinates of the node.
+    pub half_extents: Vec3,
+    /// Distance (in local coordinates of the node) outside of the box at which volume
+    /// stops affecting the listener.
+    pub blend_distance: f32,
+    /// Volumes with higher priority are applied on top of volumes with lower priority.
+    pub priority: i32,
+    /// Acoustic properties of the volume.
+    pub environment: AudioEnvironment,
+}
+
+impl Default for EnvironmentVolume {
+    fn default() -> Self {
+        Self {
+            node: Handle::NONE,
+            half_extents: Vec3::new(1.0, 1.0, 1.0),
+            blend_distance: 2.0,
+            priority: 0,
+            environment: Default::default(),
+        }
+    }
+}
+
+impl EnvironmentVolume {
+    /// Returns influence of volume at given point in world coordinates in [0; 1] range.
+    pub fn weight(&self, graph: &Graph, point: Vec3) -> f32 {
+        if !graph.is_valid_handle(self.node) {
+            return 0.0;
+        }
+        let inv_transform = graph[self.node]
+            .global_transform()
+            .inverse()
+            .unwrap_or_default();
+        let local = inv_transform.transform_vector(point);
+        let outside = Vec3::new(
+            (local.x.abs() - self.half_extents.x).max(0.0),
+            (local.y.abs() - self.half_extents.y).max(0.0),
+            (local.z.abs() - self.half_extents.z).max(0.0),
+        );
+        let distance = outside.len();
+        if distance <= 0.0 {
+            1.0
+        } else if distance >= self.blend_distance {
+            0.0
+        } else {
+            1.0 - distance / self.blend_distance
+        }
+    }
+}
+
+impl Visit for EnvironmentVolume {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.node.visit("Node", visitor)?;
+        self.half_extents.visit("HalfExtents", visitor)?;
+        self.blend_distance.visit("BlendDistance", visitor)?;
+        self.priority.visit("Priority", visitor)?;
+        self.environment.visit("Environment", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Container of environment volumes of a scene. See module docs.
+#[derive(Clone, Debug, Default)]
+pub struct AudioEnvironmentContainer {
+    volumes: Vec<EnvironmentVolume>,
+    default_environment: AudioEnvironment,
+    reverb: Handle<Effect>,
+    current: AudioEnvironment,
+}
+
+impl AudioEnvironmentContainer {
+    /// Adds new volume to container.
+    pub fn add(&mut self, volume: EnvironmentVolume) {
+        self.volumes.push(volume);
+    }
+
+    /// Removes every volume attached to given node.
+    pub fn remove_volumes_of(&mut self, node: Handle<Node>) {
+        self.volumes.retain(|volume| volume.node != node);
+    }
+
+    /// Removes every volume.
+    pub fn clear(&mut self) {
+        self.volumes.clear();
+    }
+
+    /// Returns shared reference to volumes.
+    pub fn volumes(&self) -> &[EnvironmentVolume] {
+        &self.volumes
+    }
+
+    /// Returns mutable reference to volumes.
+    pub fn volumes_mut(&mut self) -> &mut [EnvironmentVolume] {
+        &mut self.volumes
+    }
+
+    /// Sets environment which is used when there is no volume around the listener.
+    pub fn set_default_environment(&mut self, environment: AudioEnvironment) {
+        self.default_environment = environment;
+    }
+
+    /// Returns environment which is used when there is no volume around the listener.
+    pub fn default_environment(&self) -> AudioEnvironment {
+        self.default_environment
+    }
+
+    /// Sets reverb effect of sound context which will be driven by volumes. Volumes are
+    /// not applied until reverb is set. Only one scene should drive the same effect.
+    pub fn set_reverb(&mut self, reverb: Handle<Effect>) {
+        self.reverb = reverb;
+    }
+
+    /// Returns handle of reverb effect which is driven by volumes.
+    pub fn reverb(&self) -> Handle<Effect> {
+        self.reverb
+    }
+
+    /// Returns environment that was applied to sound context last time.
+    pub fn current(&self) -> AudioEnvironment {
+        self.current
+    }
+
+    /// Calculates blended environment at given point in world coordinates.
+    pub fn evaluate(&self, graph: &Graph, point: Vec3) -> AudioEnvironment {
+        let mut affecting = self
+            .volumes
+            .iter()
+            .filter_map(|volume| {
+                let weight = volume.weight(graph, point);
+                if weight > 0.0 {
+                    Some((volume.priority, weight, volume.environment))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        // Sort is stable, so volumes with same priority are applied in order of addition.
+        affecting.sort_by_key(|(priority, ..)| *priority);
+
+        affecting
+            .iter()
+            .fold(self.default_environment, |environment, (_, weight, other)| {
+                environment.lerp(other, *weight)
+            })
+    }
+
+    pub(in crate) fn apply(&mut self, graph: &Graph, context: &mut Context) {
+        if self.reverb.is_none() {
+            return;
+        }
+
+        let environment = self.evaluate(graph, context.listener().position());
+        self.current = environment;
+
+        if let Effect::Reverb(reverb) = context.effect_mut(self.reverb) {
+            reverb.set_decay_time(Duration::from_secs_f32(environment.reverb_decay_time));
+            reverb.set_dry(environment.reverb_dry);
+            reverb.set_wet(environment.reverb_wet);
+            // Reverb uses coefficient of one-pole low-pass filter, one means no filtering.
+            reverb.set_fc(1.0 - environment.lowpass.max(0.0).min(1.0));
+        }
+    }
+
+    pub(in crate) fn remap(&mut self, old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {
+        self.volumes = std::mem::take(&mut self.volumes)
+            .into_iter()
+            .filter_map(|mut volume| {
+                volume.node = *old_new_mapping.get(&volume.node)?;
+                Some(volume)
+            })
+            .collect();
+    }
+}
+
+impl Visit for AudioEnvironmentContainer {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.volumes.visit("Volumes", visitor)?;
+        self.default_environment.visit("DefaultEnvironment", visitor)?;
+        self.reverb.visit("Reverb", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::vec3::Vec3,
+        scene::{
+            audio_environment::{AudioEnvironment, AudioEnvironmentContainer, EnvironmentVolume},
+            base::BaseBuilder,
+            graph::Graph,
+        },
+    };
+
+    #[test]
+    fn audio_environment_blending_test() {
+        let mut graph = Graph::new();
+        let hall = graph.add_node(BaseBuilder::new().build_node());
+        let room = graph.add_node(BaseBuilder::new().build_node());
+        graph.update_hierachical_data();
+
+        let mut container = AudioEnvironmentContainer::default();
+        container.add(EnvironmentVolume {
+            node: room,
+            half_extents: Vec3::new(1.0, 1.0, 1.0),
+            priority: 1,
+            environment: AudioEnvironment::ROOM,
+            ..Default::default()
+        });
+        container.add(EnvironmentVolume {
+            node: hall,
+            half_extents: Vec3::new(10.0, 10.0, 10.0),
+            blend_distance: 10.0,
+            environment: AudioEnvironment::HALL,
+            ..Default::default()
+        });
+
+        // Room has higher priority, so it wins over hall.
+        assert_eq!(container.evaluate(&graph, Vec3::ZERO), AudioEnvironment::ROOM);
+        assert_eq!(
+            container.evaluate(&graph, Vec3::new(5.0, 0.0, 0.0)),
+            AudioEnvironment::HALL
+        );
+        assert_eq!(
+            container.evaluate(&graph, Vec3::new(15.0, 0.0, 0.0)),
+            AudioEnvironment::OUTDOORS.lerp(&AudioEnvironment::HALL, 0.5)
+        );
+        assert_eq!(
+            container.evaluate(&graph, Vec3::new(100.0, 0.0, 0.0)),
+            AudioEnvironment::OUTDOORS
+        );
+    }
+}
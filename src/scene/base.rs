@@ -5,17 +5,139 @@
 //! nodes, like local and global transforms, name, lifetime, etc. Base node is a building
 //! block for all complex node hierarchies - it contains list of children and handle to
 //! parent node.
+//!
+//! # Property overrides
+//!
+//! Nodes instantiated from a prefab or a model resource remember which of their properties
+//! were changed after instantiation - such properties are called overrides. When a scene
+//! is saved, every property that differs from the property of original node in resource
+//! is marked as overridden. When a scene is loaded, every property that is not overridden
+//! is taken from the resource, so changes in a prefab are propagated to every instance,
+//! while changes made to particular instance are kept.
 
 use crate::{
     core::{
+        color::Color,
         math::{mat4::Mat4, vec3::Vec3},
         pool::Handle,
         visitor::{Visit, VisitResult, Visitor},
     },
-    resource::model::Model,
-    scene::{node::Node, transform::Transform},
+    engine::resource_manager::ResourceManager,
+    resource::{model::Model, prefab::Prefab},
+    scene::{
+        component::{self, Component},
+        light::LocalLightProbe,
+        node::Node,
+        transform::Transform,
+    },
 };
-use std::sync::{Arc, Mutex};
+use std::{
+    any::TypeId,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Property of a node which can be overridden in an instance of a prefab. See module docs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OverridableProperty {
+    /// Local position.
+    Position,
+    /// Local rotation.
+    Rotation,
+    /// Local scale.
+    Scale,
+    /// Visibility flag.
+    Visibility,
+    /// Depth offset.
+    DepthOffset,
+    /// Outline color.
+    Outline,
+    /// Bit mask of render layers.
+    Layers,
+}
+
+impl OverridableProperty {
+    /// Array of every overridable property.
+    pub const ALL: [OverridableProperty; 7] = [
+        OverridableProperty::Position,
+        OverridableProperty::Rotation,
+        OverridableProperty::Scale,
+        OverridableProperty::Visibility,
+        OverridableProperty::DepthOffset,
+        OverridableProperty::Outline,
+        OverridableProperty::Layers,
+    ];
+
+    fn bit(self) -> u32 {
+        1 << (self as u32)
+    }
+}
+
+/// Set of overridden properties of a node.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PropertyOverrides {
+    bits: u32,
+}
+
+impl PropertyOverrides {
+    /// Returns set that contains every property.
+    pub fn all() -> Self {
+        let mut overrides = Self::default();
+        for &property in OverridableProperty::ALL.iter() {
+            overrides.insert(property);
+        }
+        overrides
+    }
+
+    /// Returns true if given property is overridden.
+    pub fn contains(&self, property: OverridableProperty) -> bool {
+        self.bits & property.bit() != 0
+    }
+
+    /// Marks given property as overridden.
+    pub fn insert(&mut self, property: OverridableProperty) {
+        self.bits |= property.bit();
+    }
+
+    /// Marks given property as not overridden.
+    pub fn remove(&mut self, property: OverridableProperty) {
+        self.bits &= !property.bit();
+    }
+
+    /// Returns true if there is no overridden properties.
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+}
+
+impl Visit for PropertyOverrides {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        self.bits.visit(name, visitor)
+    }
+}
+
+/// Property of a node which can be either inherited from parent node or overridden by
+/// node. Inherited property is combined with final value of the property of parent node:
+/// flags are combined using logical AND, layer masks are combined using bitwise AND, so
+/// invisible parent hides its descendants. Overridden property uses only value of node
+/// itself. Final values are evaluated once per frame during graph update.
+///
+/// By default visibility and shadow casting are inherited, and layers are overridden.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InheritableProperty {
+    /// Visibility flag, see `Base::global_visibility`.
+    Visibility,
+    /// Shadow casting flag, see `Base::global_cast_shadows`.
+    CastShadows,
+    /// Bit mask of render layers, see `Base::global_layers`.
+    Layers,
+}
+
+impl InheritableProperty {
+    fn bit(self) -> u32 {
+        1 << (self as u32)
+    }
+}
 
 /// See module docs.
 #[derive(Debug)]
@@ -39,13 +161,47 @@ pub struct Base {
     /// More precisely - this node is root of whole descendant nodes
     /// hierarchy which was instantiated from resource.
     pub(in crate) is_resource_instance: bool,
+    /// A prefab from which this node was instantiated from. Prefab takes precedence over
+    /// model resource when syncing properties, see `Prefab` docs.
+    pub(in crate) prefab: Option<Arc<Mutex<Prefab>>>,
+    /// Handle to node in scene of prefab from which this node was instantiated from.
+    pub(in crate) prefab_original: Handle<Node>,
     /// Maximum amount of Some(time) that node will "live" or None
     /// if node has undefined lifetime.
     lifetime: Option<f32>,
     depth_offset: f32,
+    outline: Option<Color>,
+    layers: u32,
+    overrides: PropertyOverrides,
+    tags: Vec<String>,
+    time_scale: f32,
+    pub(in crate) global_time_scale: f32,
+    local_probe: Option<LocalLightProbe>,
+    enabled: bool,
+    pub(in crate) global_enabled: bool,
+    /// Update stamp of graph at which global transform was changed last time. Non-serializable.
+    pub(in crate) transform_stamp: u64,
+    /// Name of bone to which node is attached, see `Graph::attach_to_bone`.
+    pub(in crate) socket: Option<String>,
+    /// Bit mask of inherited properties, see `InheritableProperty`.
+    inheritance: u32,
+    cast_shadows: bool,
+    pub(in crate) global_cast_shadows: bool,
+    pub(in crate) global_layers: u32,
+    script: Option<PathBuf>,
+    components: Vec<Box<dyn Component>>,
 }
 
 impl Base {
+    /// Layer mask which is assigned to every node by default, it contains only first layer.
+    pub const DEFAULT_LAYERS: u32 = 1;
+
+    /// Properties which are inherited from parent node by default.
+    pub const DEFAULT_INHERITANCE: [InheritableProperty; 2] = [
+        InheritableProperty::Visibility,
+        InheritableProperty::CastShadows,
+    ];
+
     /// Sets name of node. Can be useful to mark a node to be able to find it later on.
     pub fn set_name<N: AsRef<str>>(&mut self, name: N) -> &mut Self {
         self.name = name.as_ref().to_owned();
@@ -130,6 +286,16 @@ impl Base {
         self.resource.clone()
     }
 
+    /// Returns prefab from which this node was instantiated from.
+    pub fn prefab(&self) -> Option<Arc<Mutex<Prefab>>> {
+        self.prefab.clone()
+    }
+
+    /// Handle to node in scene of prefab from which this node was instantiated from.
+    pub fn prefab_original_handle(&self) -> Handle<Node> {
+        self.prefab_original
+    }
+
     /// Sets local visibility of a node.
     pub fn set_visibility(&mut self, visibility: bool) -> &mut Self {
         self.visibility = visibility;
@@ -144,10 +310,11 @@ impl Base {
     /// Returns combined visibility of an node. This is the final visibility of a node.
     /// Global visibility calculated using visibility of all parent nodes until root one,
     /// so if some parent node upper on tree is invisible then all its children will be
-    /// invisible. It defines if object will be rendered. It is *not* the same as real
-    /// visibility point of view of some camera. To check if object is visible from some
-    /// camera, use frustum visibility check. However this still can't tell you if object
-    /// is behind obstacle or not.
+    /// invisible, unless a child overrides visibility (see `InheritableProperty`). It
+    /// defines if object will be rendered. It is *not* the same as real visibility point
+    /// of view of some camera. To check if object is visible from some camera, use frustum
+    /// visibility check. However this still can't tell you if object is behind obstacle
+    /// or not.
     pub fn global_visibility(&self) -> bool {
         self.global_visibility
     }
@@ -198,6 +365,383 @@ impl Base {
     pub fn depth_offset_factor(&self) -> f32 {
         self.depth_offset
     }
+
+    /// Sets color of outline which will be drawn around node on screen, or None to
+    /// disable outline. Outline is drawn on top of everything, so it is visible even
+    /// if node is occluded. It can be used to highlight interactable objects or to
+    /// show selection in an editor.
+    ///
+    /// # Notes
+    ///
+    /// Only meshes can be outlined for now.
+    pub fn set_outline(&mut self, outline: Option<Color>) -> &mut Self {
+        self.outline = outline;
+        self
+    }
+
+    /// Returns current outline color, if any.
+    pub fn outline(&self) -> Option<Color> {
+        self.outline
+    }
+
+    /// Sets local light probe which overrides ambient lighting inside a volume attached to
+    /// the node. See `LocalLightProbe` docs for more info.
+    pub fn set_local_probe(&mut self, probe: Option<LocalLightProbe>) -> &mut Self {
+        self.local_probe = probe;
+        self
+    }
+
+    /// Returns local light probe of node, if any.
+    pub fn local_probe(&self) -> Option<&LocalLightProbe> {
+        self.local_probe.as_ref()
+    }
+
+    /// Returns local light probe of node, if any.
+    pub fn local_probe_mut(&mut self) -> Option<&mut LocalLightProbe> {
+        self.local_probe.as_mut()
+    }
+
+    /// Sets new bit mask of render layers to which node belongs to. Node will be rendered
+    /// only by cameras which have at least one of these layers in their cull mask. This
+    /// can be used to draw first-person weapon only by weapon camera, or to show some
+    /// markers only on minimap camera. See `Camera::set_cull_mask`.
+    pub fn set_layers(&mut self, layers: u32) -> &mut Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Returns bit mask of render layers to which node belongs to.
+    pub fn layers(&self) -> u32 {
+        self.layers
+    }
+
+    /// Returns final bit mask of render layers with respect to inheritance of layers, this
+    /// mask is used by renderer. See `InheritableProperty`.
+    pub fn global_layers(&self) -> u32 {
+        self.global_layers
+    }
+
+    /// Sets whether node should cast shadows or not. Only meshes are drawn into shadow
+    /// maps, for other nodes this flag is only passed to descendants.
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) -> &mut Self {
+        self.cast_shadows = cast_shadows;
+        self
+    }
+
+    /// Returns local shadow casting flag of node.
+    pub fn cast_shadows(&self) -> bool {
+        self.cast_shadows
+    }
+
+    /// Returns final shadow casting flag with respect to inheritance of the flag. See
+    /// `InheritableProperty`.
+    pub fn global_cast_shadows(&self) -> bool {
+        self.global_cast_shadows
+    }
+
+    /// Sets whether given property should be inherited from parent node or overridden by
+    /// this node. See `InheritableProperty`.
+    pub fn set_inherits(&mut self, property: InheritableProperty, inherits: bool) -> &mut Self {
+        if inherits {
+            self.inheritance |= property.bit();
+        } else {
+            self.inheritance &= !property.bit();
+        }
+        self
+    }
+
+    /// Returns true if given property is inherited from parent node.
+    pub fn inherits(&self, property: InheritableProperty) -> bool {
+        self.inheritance & property.bit() != 0
+    }
+
+    /// Enables or disables node and its descendants. Disabled node is not updated (particle
+    /// systems are not simulated, lifetime is not decreased, animations and coroutines of
+    /// the node are paused) and not rendered (including lights and shadows). This differs
+    /// from visibility which affects only rendering.
+    ///
+    /// Named this way to not collide with `Camera::set_enabled` which only switches
+    /// rendering from a camera.
+    pub fn set_node_enabled(&mut self, enabled: bool) -> &mut Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Returns local enabled flag of node.
+    pub fn is_node_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns true if node and every of its ancestors are enabled.
+    pub fn is_globally_enabled(&self) -> bool {
+        self.global_enabled
+    }
+
+    /// Returns update stamp of graph at which global transform of node was changed last
+    /// time, zero means that node wasn't updated yet. See `Graph::update_stamp`.
+    pub fn transform_stamp(&self) -> u64 {
+        self.transform_stamp
+    }
+
+    /// Sets time scale of node and its descendants. Time scale is a multiplier of time
+    /// delta which is used to update particle systems, lifetimes, coroutines and animations
+    /// of the node and its descendants, so it can be used to make slow-motion effects for
+    /// part of a scene. Time scales are multiplied through hierarchy, zero time scale
+    /// pauses sub-tree. Negative values are clamped to zero.
+    ///
+    /// Sounds are not part of scene graph, so they are not affected, use `global_time_scale`
+    /// of some node to adjust pitch of sounds manually.
+    pub fn set_time_scale(&mut self, time_scale: f32) -> &mut Self {
+        self.time_scale = time_scale.max(0.0);
+        self
+    }
+
+    /// Returns local time scale of node.
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Returns time scale of node multiplied by time scales of every ancestor.
+    pub fn global_time_scale(&self) -> f32 {
+        self.global_time_scale
+    }
+
+    /// Adds tag to node. Tags can be used to find nodes by their purpose, for example
+    /// "spawn_point" or "pickup", see `Graph::nodes_with_tag`. Adding existing tag does
+    /// nothing.
+    pub fn add_tag<T: AsRef<str>>(&mut self, tag: T) -> &mut Self {
+        if !self.has_tag(tag.as_ref()) {
+            self.tags.push(tag.as_ref().to_owned());
+        }
+        self
+    }
+
+    /// Removes tag from node. Returns true if node had such tag.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        if let Some(index) = self.tags.iter().position(|t| t == tag) {
+            self.tags.remove(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if node has given tag.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Returns list of tags of node.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Returns name of bone to which node is attached as to a socket, see
+    /// `Graph::attach_to_bone`.
+    pub fn socket(&self) -> Option<&str> {
+        self.socket.as_deref()
+    }
+
+    /// Sets path to script which drives node, `None` detaches script. Scripts are run by
+    /// scripting subsystem (`engine::scripting`, requires `scripting` feature), node itself
+    /// only stores the path.
+    pub fn set_script<P: AsRef<Path>>(&mut self, script: Option<P>) -> &mut Self {
+        self.script = script.map(|path| path.as_ref().to_owned());
+        self
+    }
+
+    /// Returns path to script which drives node.
+    pub fn script(&self) -> Option<&Path> {
+        self.script.as_deref()
+    }
+
+    fn add_boxed_component(&mut self, component: Box<dyn Component>) {
+        let type_id = component.as_any().type_id();
+        match self
+            .components
+            .iter()
+            .position(|c| c.as_any().type_id() == type_id)
+        {
+            Some(index) => self.components[index] = component,
+            None => self.components.push(component),
+        }
+    }
+
+    /// Adds component to node, existing component of the same type is replaced. See
+    /// `component` module docs.
+    pub fn add_component<C: Component>(&mut self, component: C) -> &mut Self {
+        self.add_boxed_component(Box::new(component));
+        self
+    }
+
+    /// Removes component of given type from node. Returns true if node had such component.
+    pub fn remove_component<C: Component>(&mut self) -> bool {
+        let count = self.components.len();
+        self.components.retain(|c| !c.as_any().is::<C>());
+        self.components.len() != count
+    }
+
+    /// Returns reference to component of given type.
+    pub fn component<C: Component>(&self) -> Option<&C> {
+        self.components
+            .iter()
+            .find_map(|c| c.as_any().downcast_ref::<C>())
+    }
+
+    /// Returns mutable reference to component of given type.
+    pub fn component_mut<C: Component>(&mut self) -> Option<&mut C> {
+        self.components
+            .iter_mut()
+            .find_map(|c| c.as_any_mut().downcast_mut::<C>())
+    }
+
+    /// Returns true if node has component of given type.
+    pub fn has_component<C: Component>(&self) -> bool {
+        self.component::<C>().is_some()
+    }
+
+    /// Returns every component of node.
+    pub fn components(&self) -> &[Box<dyn Component>] {
+        &self.components
+    }
+
+    /// Updates components of node, components are taken out of node while updating, so they
+    /// can modify node. Components added during update are kept.
+    pub(in crate) fn update_components(&mut self, dt: f32) {
+        if self.components.is_empty() {
+            return;
+        }
+        let mut components = std::mem::take(&mut self.components);
+        for component in components.iter_mut() {
+            component.update(self, dt);
+        }
+        let added = std::mem::replace(&mut self.components, components);
+        for component in added {
+            self.add_boxed_component(component);
+        }
+    }
+
+    /// Restores pointers to resources. Save files contain only paths to resources, so real
+    /// resources must be requested from resource manager after loading.
+    pub(in crate) fn restore_resources(&mut self, resource_manager: &mut ResourceManager) {
+        if let Some(shallow_resource) = self.resource.clone() {
+            self.resource = resource_manager.request_model(&shallow_resource.lock().unwrap().path);
+        }
+        if let Some(shallow_prefab) = self.prefab.clone() {
+            self.prefab = resource_manager.request_prefab(&shallow_prefab.lock().unwrap().path);
+        }
+    }
+
+    /// Returns set of properties that are overridden in this instance of a prefab.
+    /// See module docs for more info.
+    pub fn property_overrides(&self) -> PropertyOverrides {
+        self.overrides
+    }
+
+    /// Explicitly marks property as overridden or not overridden. Normally there is no
+    /// need to call this method, overrides are detected automatically when scene is saved.
+    pub fn set_property_overridden(
+        &mut self,
+        property: OverridableProperty,
+        overridden: bool,
+    ) -> &mut Self {
+        if overridden {
+            self.overrides.insert(property);
+        } else {
+            self.overrides.remove(property);
+        }
+        self
+    }
+
+    /// Takes value of property from original node in resource and removes override flag.
+    /// Does nothing if node was not instantiated from a resource.
+    pub fn revert_property_override(&mut self, property: OverridableProperty) -> &mut Self {
+        self.with_original(|node, original| {
+            node.copy_property(original, property);
+            node.overrides.remove(property);
+        });
+        self
+    }
+
+    /// Calls given closure with original node of this node. Original node is taken from
+    /// prefab if node was instantiated from prefab, otherwise from model resource.
+    fn with_original<F: FnOnce(&mut Base, &Base)>(&mut self, func: F) {
+        if let Some(prefab) = self.prefab.clone() {
+            let prefab = prefab.lock().unwrap();
+            let graph = &prefab.get_scene().graph;
+            if graph.is_valid_handle(self.prefab_original) {
+                func(self, &graph[self.prefab_original]);
+            }
+        } else if let Some(model) = self.resource.clone() {
+            let model = model.lock().unwrap();
+            let graph = &model.get_scene().graph;
+            if graph.is_valid_handle(self.original) {
+                func(self, &graph[self.original]);
+            }
+        }
+    }
+
+    fn property_differs(&self, other: &Base, property: OverridableProperty) -> bool {
+        match property {
+            OverridableProperty::Position => {
+                self.local_transform.position() != other.local_transform.position()
+            }
+            OverridableProperty::Rotation => {
+                self.local_transform.rotation() != other.local_transform.rotation()
+            }
+            OverridableProperty::Scale => {
+                self.local_transform.scale() != other.local_transform.scale()
+            }
+            OverridableProperty::Visibility => self.visibility != other.visibility,
+            OverridableProperty::DepthOffset => self.depth_offset != other.depth_offset,
+            OverridableProperty::Outline => self.outline != other.outline,
+            OverridableProperty::Layers => self.layers != other.layers,
+        }
+    }
+
+    fn copy_property(&mut self, other: &Base, property: OverridableProperty) {
+        match property {
+            OverridableProperty::Position => {
+                self.local_transform
+                    .set_position(other.local_transform.position());
+            }
+            OverridableProperty::Rotation => {
+                self.local_transform
+                    .set_rotation(other.local_transform.rotation());
+            }
+            OverridableProperty::Scale => {
+                self.local_transform.set_scale(other.local_transform.scale());
+            }
+            OverridableProperty::Visibility => self.visibility = other.visibility,
+            OverridableProperty::DepthOffset => self.depth_offset = other.depth_offset,
+            OverridableProperty::Outline => self.outline = other.outline,
+            OverridableProperty::Layers => self.layers = other.layers,
+        }
+    }
+
+    /// Marks every property that differs from property of original node in resource
+    /// as overridden. Overrides are never removed here, property stays overridden even
+    /// if it was set back to original value.
+    pub(in crate) fn update_property_overrides(&mut self) {
+        self.with_original(|node, original| {
+            for &property in OverridableProperty::ALL.iter() {
+                if node.property_differs(original, property) {
+                    node.overrides.insert(property);
+                }
+            }
+        });
+    }
+
+    /// Takes every property which is not overridden from original node in resource.
+    pub(in crate) fn sync_with_resource(&mut self) {
+        self.with_original(|node, original| {
+            for &property in OverridableProperty::ALL.iter() {
+                if !node.overrides.contains(property) {
+                    node.copy_property(original, property);
+                }
+            }
+        });
+    }
 }
 
 impl Clone for Base {
@@ -213,7 +757,25 @@ impl Clone for Base {
             inv_bind_pose_transform: self.inv_bind_pose_transform,
             resource: self.resource.clone(),
             is_resource_instance: self.is_resource_instance,
+            prefab: self.prefab.clone(),
+            prefab_original: self.prefab_original,
             lifetime: self.lifetime,
+            outline: self.outline,
+            layers: self.layers,
+            overrides: self.overrides,
+            tags: self.tags.clone(),
+            time_scale: self.time_scale,
+            global_time_scale: self.global_time_scale,
+            local_probe: self.local_probe.clone(),
+            enabled: self.enabled,
+            global_enabled: self.global_enabled,
+            socket: self.socket.clone(),
+            inheritance: self.inheritance,
+            cast_shadows: self.cast_shadows,
+            global_cast_shadows: self.global_cast_shadows,
+            global_layers: self.global_layers,
+            script: self.script.clone(),
+            components: self.components.clone(),
             // Rest of data is *not* copied!
             ..Default::default()
         }
@@ -240,6 +802,24 @@ impl Visit for Base {
             .visit("IsResourceInstance", visitor)?;
         self.lifetime.visit("Lifetime", visitor)?;
         self.depth_offset.visit("DepthOffset", visitor)?;
+        let _ = self.outline.visit("Outline", visitor);
+        let _ = self.layers.visit("Layers", visitor);
+        let _ = self.tags.visit("Tags", visitor);
+        let _ = self.time_scale.visit("TimeScale", visitor);
+        let _ = self.local_probe.visit("LocalProbe", visitor);
+        let _ = self.enabled.visit("Enabled", visitor);
+        let _ = self.socket.visit("Socket", visitor);
+        let _ = self.inheritance.visit("Inheritance", visitor);
+        let _ = self.cast_shadows.visit("CastShadows", visitor);
+        let _ = self.prefab.visit("Prefab", visitor);
+        let _ = self.prefab_original.visit("PrefabOriginal", visitor);
+        let _ = self.script.visit("Script", visitor);
+        let _ = component::visit_components(&mut self.components, "Components", visitor);
+        if self.overrides.visit("PropertyOverrides", visitor).is_err() && visitor.is_reading() {
+            // Older versions have no overrides, treat every property as overridden to keep
+            // saved state of instances intact.
+            self.overrides = PropertyOverrides::all();
+        }
 
         visitor.leave_region()
     }
@@ -253,6 +833,16 @@ pub struct BaseBuilder {
     children: Option<Vec<Handle<Node>>>,
     lifetime: Option<f32>,
     depth_offset: f32,
+    outline: Option<Color>,
+    layers: u32,
+    tags: Vec<String>,
+    time_scale: f32,
+    local_probe: Option<LocalLightProbe>,
+    enabled: bool,
+    inheritance: u32,
+    cast_shadows: bool,
+    script: Option<PathBuf>,
+    components: Vec<Box<dyn Component>>,
 }
 
 impl Default for BaseBuilder {
@@ -271,6 +861,18 @@ impl BaseBuilder {
             children: None,
             lifetime: None,
             depth_offset: 0.0,
+            outline: None,
+            layers: Base::DEFAULT_LAYERS,
+            tags: Default::default(),
+            time_scale: 1.0,
+            local_probe: None,
+            enabled: true,
+            inheritance: Base::DEFAULT_INHERITANCE
+                .iter()
+                .fold(0, |bits, property| bits | property.bit()),
+            cast_shadows: true,
+            script: None,
+            components: Default::default(),
         }
     }
 
@@ -310,6 +912,74 @@ impl BaseBuilder {
         self
     }
 
+    /// Sets desired outline color.
+    pub fn with_outline(mut self, color: Color) -> Self {
+        self.outline = Some(color);
+        self
+    }
+
+    /// Sets desired bit mask of render layers.
+    pub fn with_layers(mut self, layers: u32) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Adds tag to node, see `Base::add_tag`.
+    pub fn with_tag<T: AsRef<str>>(mut self, tag: T) -> Self {
+        if !self.tags.iter().any(|t| t == tag.as_ref()) {
+            self.tags.push(tag.as_ref().to_owned());
+        }
+        self
+    }
+
+    /// Sets desired time scale, see `Base::set_time_scale`.
+    pub fn with_time_scale(mut self, time_scale: f32) -> Self {
+        self.time_scale = time_scale.max(0.0);
+        self
+    }
+
+    /// Sets desired local light probe.
+    pub fn with_local_probe(mut self, probe: LocalLightProbe) -> Self {
+        self.local_probe = Some(probe);
+        self
+    }
+
+    /// Sets whether node is enabled or not, see `Base::set_node_enabled`.
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Sets whether node should cast shadows or not, see `Base::set_cast_shadows`.
+    pub fn with_cast_shadows(mut self, cast_shadows: bool) -> Self {
+        self.cast_shadows = cast_shadows;
+        self
+    }
+
+    /// Sets path to script which drives node, see `Base::set_script`.
+    pub fn with_script<P: AsRef<Path>>(mut self, script: P) -> Self {
+        self.script = Some(script.as_ref().to_owned());
+        self
+    }
+
+    /// Adds component to node, existing component of the same type is replaced.
+    pub fn with_component<C: Component>(mut self, component: C) -> Self {
+        self.components
+            .retain(|c| c.as_any().type_id() != TypeId::of::<C>());
+        self.components.push(Box::new(component));
+        self
+    }
+
+    /// Sets whether given property should be inherited or not, see `Base::set_inherits`.
+    pub fn with_inherits(mut self, property: InheritableProperty, inherits: bool) -> Self {
+        if inherits {
+            self.inheritance |= property.bit();
+        } else {
+            self.inheritance &= !property.bit();
+        }
+        self
+    }
+
     /// Creates new instance of base scene node. Do not forget to add
     /// node to scene or pass to other nodes as base.
     pub fn build(self) -> Base {
@@ -326,7 +996,26 @@ impl BaseBuilder {
             resource: None,
             original: Handle::NONE,
             is_resource_instance: false,
+            prefab: None,
+            prefab_original: Handle::NONE,
             depth_offset: self.depth_offset,
+            outline: self.outline,
+            layers: self.layers,
+            overrides: Default::default(),
+            tags: self.tags,
+            time_scale: self.time_scale,
+            global_time_scale: self.time_scale,
+            local_probe: self.local_probe,
+            enabled: self.enabled,
+            global_enabled: self.enabled,
+            transform_stamp: 0,
+            socket: None,
+            inheritance: self.inheritance,
+            cast_shadows: self.cast_shadows,
+            global_cast_shadows: self.cast_shadows,
+            global_layers: self.layers,
+            script: self.script,
+            components: self.components,
         }
     }
 
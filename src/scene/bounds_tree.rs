@@ -0,0 +1,149 @@
+//! Contains tree of world-space bounds of nodes of a graph.
+//!
+//! Graph keeps bounds of meshes, terrains and sprites in `AabbTree` and updates it together
+//! with global transforms of nodes (see `Graph::update_hierachical_data`), so queries like
+//! "which meshes can be hit by this ray" or "which meshes are in this frustum" check only
+//! nodes near the ray or the frustum instead of every node of the graph. Renderer uses it
+//! for frustum culling and graph ray casts use it to find candidate nodes.
+//!
+//! Bounds of skinned meshes depend on their bones and are expensive to calculate every
+//! frame, so such meshes are not put in the tree, instead they're returned by every query.
+//! Queries are conservative: every node that actually intersects a query is returned, but
+//! some returned nodes may not intersect it, exact checks must be done by caller.
+
+use crate::{
+    core::{
+        math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, mat4::Mat4, vec3::Vec3},
+        pool::{Handle, Pool},
+    },
+    scene::node::Node,
+    utils::aabb_tree::{AabbTree, AabbTreeLeaf},
+};
+use std::collections::HashMap;
+
+/// See module docs.
+#[derive(Debug, Default)]
+pub struct GraphBoundsTree {
+    tree: AabbTree<Handle<Node>>,
+    leaves: HashMap<Handle<Node>, (AabbTreeLeaf, u64)>,
+    unbounded: Vec<Handle<Node>>,
+    stamp: u64,
+}
+
+fn transform_aabb(aabb: &AxisAlignedBoundingBox, transform: &Mat4) -> AxisAlignedBoundingBox {
+    let mut result = AxisAlignedBoundingBox::default();
+    for i in 0..8 {
+        let corner = Vec3::new(
+            if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+            if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+            if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+        );
+        result.add_point(transform.transform_vector(corner));
+    }
+    result
+}
+
+fn sphere_aabb(center: Vec3, radius: f32) -> AxisAlignedBoundingBox {
+    let radius = Vec3::new(radius, radius, radius);
+    let mut result = AxisAlignedBoundingBox::default();
+    result.add_point(center - radius);
+    result.add_point(center + radius);
+    result
+}
+
+enum NodeBounds {
+    None,
+    Bounded(AxisAlignedBoundingBox),
+    Unbounded,
+}
+
+fn node_bounds(node: &Node) -> NodeBounds {
+    match node {
+        Node::Mesh(mesh) => {
+            if mesh.surfaces().iter().any(|surface| !surface.bones().is_empty()) {
+                NodeBounds::Unbounded
+            } else if mesh.surfaces().is_empty() {
+                NodeBounds::None
+            } else {
+                NodeBounds::Bounded(transform_aabb(&mesh.bounding_box(), &mesh.global_transform()))
+            }
+        }
+        Node::Terrain(terrain) => NodeBounds::Bounded(transform_aabb(
+            &terrain.bounding_box(),
+            &terrain.global_transform(),
+        )),
+        Node::Sprite(sprite) => {
+            NodeBounds::Bounded(sphere_aabb(sprite.global_position(), sprite.size()))
+        }
+        _ => NodeBounds::None,
+    }
+}
+
+impl GraphBoundsTree {
+    /// Updates bounds of every node of given pool, adds new nodes and removes nodes which
+    /// no longer exist or no longer have bounds.
+    pub(in crate) fn update(&mut self, pool: &Pool<Node>) {
+        self.stamp += 1;
+        self.unbounded.clear();
+
+        for (handle, node) in pool.pair_iter() {
+            match node_bounds(node) {
+                NodeBounds::Bounded(bounds) => match self.leaves.get_mut(&handle) {
+                    Some((leaf, stamp)) => {
+                        self.tree.update(*leaf, &bounds);
+                        *stamp = self.stamp;
+                    }
+                    None => {
+                        let leaf = self.tree.insert(&bounds, handle);
+                        self.leaves.insert(handle, (leaf, self.stamp));
+                    }
+                },
+                NodeBounds::Unbounded => self.unbounded.push(handle),
+                NodeBounds::None => (),
+            }
+        }
+
+        let stamp = self.stamp;
+        let tree = &mut self.tree;
+        self.leaves.retain(|_, (leaf, leaf_stamp)| {
+            if *leaf_stamp == stamp {
+                true
+            } else {
+                tree.remove(*leaf);
+                false
+            }
+        });
+    }
+
+    fn add_unbounded(&self, result: &mut Vec<Handle<Node>>) {
+        result.extend_from_slice(&self.unbounded);
+    }
+
+    /// Collects nodes whose bounds may intersect given frustum into `result`. Result is
+    /// cleared first.
+    pub fn query_frustum(&self, frustum: &Frustum, result: &mut Vec<Handle<Node>>) {
+        self.tree.query_frustum(frustum, result);
+        self.add_unbounded(result);
+    }
+
+    /// Collects nodes whose bounds may intersect segment from `origin` to `origin + dir`
+    /// into `result`. Result is cleared first.
+    pub fn query_segment(&self, origin: Vec3, dir: Vec3, result: &mut Vec<Handle<Node>>) {
+        self.tree.query_segment(origin, dir, result);
+        self.add_unbounded(result);
+    }
+
+    /// Collects nodes whose bounds may intersect given sphere into `result`. Result is
+    /// cleared first.
+    pub fn query_sphere(&self, center: Vec3, radius: f32, result: &mut Vec<Handle<Node>>) {
+        self.tree.query_sphere(center, radius, result);
+        self.add_unbounded(result);
+    }
+
+    /// Collects nodes whose bounds may intersect given box into `result`. Result is
+    /// cleared first.
+    pub fn query_aabb(&self, aabb: &AxisAlignedBoundingBox, result: &mut Vec<Handle<Node>>) {
+        self.tree.query_aabb(aabb, result);
+        self.add_unbounded(result);
+    }
+}
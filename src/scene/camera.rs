@@ -1,7 +1,8 @@
 //! Contains all methods and structures to create and manage cameras.
 //!
-//! Camera allows you to see world from specific point in world. Currently only
-//! perspective projection is supported.
+//! Camera allows you to see world from specific point in world. Camera can use perspective
+//! or orthographic projection, see `Projection` docs. Orthographic projection is mostly used
+//! for 2D games, see `dim2` module docs.
 //!
 //! # Multiple cameras
 //!
@@ -9,6 +10,32 @@
 //! screen games, make picture-in-picture insertions in your main camera view and
 //! any other combinations you need.
 //!
+//! Cameras are rendered in ascending order of their render order, so camera with
+//! greater render order will be drawn on top of cameras with lesser order. Clear
+//! mode of a camera defines what will be visible in areas of its viewport where
+//! there is no geometry, this allows you to make overlays (like rear-view mirror)
+//! which does not hide views of other cameras.
+//!
+//! Each camera has cull mask which defines render layers that camera can see, node
+//! will be rendered by a camera only if it belongs to at least one of such layers.
+//! This allows you to make cameras that see only particular set of objects, for
+//! example weapon camera or minimap camera.
+//!
+//! # Automatic exposure
+//!
+//! Camera can adapt its exposure to brightness of what it sees, like human eye does. When
+//! automatic exposure is enabled, camera renders scene in high dynamic range, measures
+//! brightness of the frame using luminance histogram and smoothly changes exposure towards
+//! value that makes average brightness of the frame look like middle grey. See
+//! `AutoExposure` docs for available settings. Automatic exposure is applied only when
+//! scene is rendered on screen, render targets are left as is.
+//!
+//! # Effects
+//!
+//! Camera has built-in effects which are often needed by games: trauma-based shake, field of
+//! view kick and smooth follow of a node. Effects are updated by scene, see `camera_effects`
+//! module docs for details.
+//!
 //! ## Performance
 //!
 //! Each camera forces engine to re-render same scene one more time, which may cause
@@ -17,17 +44,217 @@
 use crate::scene::node::Node;
 use crate::{
     core::{
-        math::{mat4::Mat4, ray::Ray, vec2::Vec2, vec3::Vec3, vec4::Vec4, Rect},
+        color::Color,
+        math::{
+            aabb::AxisAlignedBoundingBox, frustum::Frustum, mat4::Mat4, ray::Ray, vec2::Vec2,
+            vec3::Vec3, vec4::Vec4, Rect,
+        },
         visitor::{Visit, VisitResult, Visitor},
     },
-    scene::base::{Base, BaseBuilder},
+    resource::texture::Texture,
+    scene::{
+        base::{Base, BaseBuilder},
+        camera_effects::CameraEffects,
+    },
+};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
 };
-use std::ops::{Deref, DerefMut};
+
+/// Clear mode defines what will be visible in areas of camera's viewport which are not
+/// covered by any geometry.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClearMode {
+    /// Frame of camera will completely overwrite everything in its viewport, empty areas
+    /// will be black. This is default mode.
+    Overwrite,
+
+    /// Empty areas of camera's frame will be filled with given color.
+    Color(Color),
+
+    /// Empty areas of camera's frame will be transparent, so views of cameras that were
+    /// rendered earlier will be visible through them.
+    Transparent,
+}
+
+impl Default for ClearMode {
+    fn default() -> Self {
+        ClearMode::Overwrite
+    }
+}
+
+impl ClearMode {
+    fn id(self) -> u32 {
+        match self {
+            ClearMode::Overwrite => 0,
+            ClearMode::Color(_) => 1,
+            ClearMode::Transparent => 2,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(ClearMode::Overwrite),
+            1 => Ok(ClearMode::Color(Color::opaque(0, 0, 0))),
+            2 => Ok(ClearMode::Transparent),
+            _ => Err(format!("Invalid clear mode {}", id)),
+        }
+    }
+}
+
+impl Visit for ClearMode {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        if let ClearMode::Color(color) = self {
+            color.visit("Color", visitor)?;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Projection defines how camera maps world space to screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Distant objects look smaller, field of view of camera is used. This is default mode.
+    Perspective,
+
+    /// Size of objects does not depend on distance to camera, given amount of world units
+    /// fits vertically into the viewport, horizontal size depends on aspect ratio.
+    Orthographic {
+        /// Height of visible area in world units.
+        vertical_size: f32,
+    },
+
+    /// Orthographic projection where one world unit is one pixel of viewport, so visible
+    /// area changes with size of viewport. This is the most convenient mode for 2D games
+    /// with pixel art.
+    Pixel,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Projection::Perspective
+    }
+}
+
+impl Projection {
+    fn id(self) -> u32 {
+        match self {
+            Projection::Perspective => 0,
+            Projection::Orthographic { .. } => 1,
+            Projection::Pixel => 2,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Projection::Perspective),
+            1 => Ok(Projection::Orthographic {
+                vertical_size: 10.0,
+            }),
+            2 => Ok(Projection::Pixel),
+            _ => Err(format!("Invalid projection {}", id)),
+        }
+    }
+}
+
+impl Visit for Projection {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        if let Projection::Orthographic { vertical_size } = self {
+            vertical_size.visit("VerticalSize", visitor)?;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+fn orthographic_matrix(width: f32, height: f32, z_near: f32, z_far: f32) -> Mat4 {
+    let half_width = width * 0.5;
+    let half_height = height * 0.5;
+    Mat4::ortho(
+        -half_width,
+        half_width,
+        -half_height,
+        half_height,
+        z_near,
+        z_far,
+    )
+}
+
+/// Settings of automatic exposure (eye adaptation). Brightness is measured in exposure
+/// values (EV) - base 2 logarithm of luminance, so each step of EV means twice brighter
+/// or darker scene.
+#[derive(Clone, Debug)]
+pub struct AutoExposure {
+    /// Defines how fast exposure adapts to new brightness, greater values mean faster
+    /// adaptation. Value of 1.0 means that exposure covers ~63% of difference between
+    /// current and target values in one second.
+    pub adaptation_speed: f32,
+
+    /// Minimal average brightness (in EV) to which exposure can adapt. Scenes darker than
+    /// this will look darker.
+    pub min_ev: f32,
+
+    /// Maximal average brightness (in EV) to which exposure can adapt. Scenes brighter than
+    /// this will look brighter.
+    pub max_ev: f32,
+
+    /// Brightness to which average brightness of a frame will be mapped. Typical value is
+    /// 0.18 (middle grey).
+    pub key_value: f32,
+
+    /// Optional metering mask, red channel of the texture defines how much every part of
+    /// the frame affects measured brightness. It is stretched over whole viewport. If not
+    /// set, every part of the frame has same weight.
+    pub metering_mask: Option<Arc<Mutex<Texture>>>,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            adaptation_speed: 1.5,
+            min_ev: -8.0,
+            max_ev: 4.0,
+            key_value: 0.18,
+            metering_mask: None,
+        }
+    }
+}
+
+impl Visit for AutoExposure {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.adaptation_speed.visit("AdaptationSpeed", visitor)?;
+        self.min_ev.visit("MinEv", visitor)?;
+        self.max_ev.visit("MaxEv", visitor)?;
+        self.key_value.visit("KeyValue", visitor)?;
+        self.metering_mask.visit("MeteringMask", visitor)?;
+
+        visitor.leave_region()
+    }
+}
 
 /// See module docs.
 #[derive(Clone, Debug)]
 pub struct Camera {
     base: Base,
+    projection: Projection,
     fov: f32,
     z_near: f32,
     z_far: f32,
@@ -35,6 +262,11 @@ pub struct Camera {
     view_matrix: Mat4,
     projection_matrix: Mat4,
     enabled: bool,
+    render_order: i32,
+    clear_mode: ClearMode,
+    cull_mask: u32,
+    auto_exposure: Option<AutoExposure>,
+    effects: CameraEffects,
 }
 
 impl Deref for Camera {
@@ -66,6 +298,12 @@ impl Visit for Camera {
         self.viewport.visit("Viewport", visitor)?;
         self.base.visit("Base", visitor)?;
         self.enabled.visit("Enabled", visitor)?;
+        let _ = self.render_order.visit("RenderOrder", visitor);
+        let _ = self.clear_mode.visit("ClearMode", visitor);
+        let _ = self.cull_mask.visit("CullMask", visitor);
+        let _ = self.auto_exposure.visit("AutoExposure", visitor);
+        let _ = self.projection.visit("Projection", visitor);
+        let _ = self.effects.visit("Effects", visitor);
         visitor.leave_region()
     }
 }
@@ -80,13 +318,66 @@ impl Camera {
         let up = self.base.up_vector();
 
         if let Some(view_matrix) = Mat4::look_at(pos, pos + look, up) {
-            self.view_matrix = view_matrix;
+            // Shake is applied in camera space, so it does not depend on camera orientation.
+            let shake = self.effects.shake_matrix().inverse().unwrap_or_default();
+            self.view_matrix = shake * view_matrix;
         } else {
             self.view_matrix = Mat4::IDENTITY;
         }
         let viewport = self.viewport_pixels(frame_size);
         let aspect = viewport.w as f32 / viewport.h as f32;
-        self.projection_matrix = Mat4::perspective(self.fov, aspect, self.z_near, self.z_far);
+        self.projection_matrix = match self.projection {
+            Projection::Perspective => {
+                let fov = (self.fov + self.effects.fov_offset())
+                    .max(1.0f32.to_radians())
+                    .min(179.0f32.to_radians());
+                Mat4::perspective(fov, aspect, self.z_near, self.z_far)
+            }
+            Projection::Orthographic { vertical_size } => orthographic_matrix(
+                vertical_size * aspect,
+                vertical_size,
+                self.z_near,
+                self.z_far,
+            ),
+            Projection::Pixel => orthographic_matrix(
+                viewport.w as f32,
+                viewport.h as f32,
+                self.z_near,
+                self.z_far,
+            ),
+        };
+    }
+
+    /// Creates copy of the camera mirrored relative to horizontal plane at given height. It
+    /// is used to render planar reflections, image of such camera is flipped vertically.
+    pub(in crate) fn make_reflection(&self, height: f32) -> Camera {
+        let mut reflection = self.clone();
+
+        let position = self.base.global_position();
+        let look = self.base.look_vector();
+        let position = Vec3::new(position.x, 2.0 * height - position.y, position.z);
+        let look = Vec3::new(look.x, -look.y, look.z);
+
+        reflection.view_matrix = Mat4::look_at(position, position + look, Vec3::UP)
+            .or_else(|| Mat4::look_at(position, position + look, Vec3::LOOK))
+            .unwrap_or(Mat4::IDENTITY);
+        // Lighting uses position of camera, so it must be mirrored too.
+        reflection.base.global_transform.f[13] = position.y;
+
+        reflection
+    }
+
+    /// Sets new projection mode. Field of view is used only by perspective projection.
+    /// Near and far planes of orthographic projections can be negative, so objects behind
+    /// camera position can be visible too.
+    pub fn set_projection(&mut self, projection: Projection) -> &mut Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Returns current projection mode.
+    pub fn projection(&self) -> Projection {
+        self.projection
     }
 
     /// Sets new viewport in resolution-independent format. In other words
@@ -191,6 +482,90 @@ impl Camera {
         self
     }
 
+    /// Sets new render order of camera. Cameras with lesser order will be rendered first,
+    /// so camera with greatest order will be on top of others. Cameras with same order
+    /// will be rendered in order of their appearance in graph.
+    #[inline]
+    pub fn set_render_order(&mut self, render_order: i32) -> &mut Self {
+        self.render_order = render_order;
+        self
+    }
+
+    /// Returns current render order of camera.
+    #[inline]
+    pub fn render_order(&self) -> i32 {
+        self.render_order
+    }
+
+    /// Sets new clear mode of camera. See `ClearMode` docs for more info.
+    #[inline]
+    pub fn set_clear_mode(&mut self, clear_mode: ClearMode) -> &mut Self {
+        self.clear_mode = clear_mode;
+        self
+    }
+
+    /// Returns current clear mode of camera.
+    #[inline]
+    pub fn clear_mode(&self) -> ClearMode {
+        self.clear_mode
+    }
+
+    /// Sets new bit mask of render layers that camera can see. By default camera
+    /// sees all layers. See `Base::set_layers` for more info.
+    #[inline]
+    pub fn set_cull_mask(&mut self, cull_mask: u32) -> &mut Self {
+        self.cull_mask = cull_mask;
+        self
+    }
+
+    /// Returns current cull mask of camera.
+    #[inline]
+    pub fn cull_mask(&self) -> u32 {
+        self.cull_mask
+    }
+
+    /// Returns true if camera can see at least one of given render layers.
+    #[inline]
+    pub fn sees_layers(&self, layers: u32) -> bool {
+        self.cull_mask & layers != 0
+    }
+
+    /// Enables or disables automatic exposure. See module docs for more info.
+    #[inline]
+    pub fn set_auto_exposure(&mut self, auto_exposure: Option<AutoExposure>) -> &mut Self {
+        self.auto_exposure = auto_exposure;
+        self
+    }
+
+    /// Returns settings of automatic exposure, if it is enabled.
+    #[inline]
+    pub fn auto_exposure(&self) -> Option<&AutoExposure> {
+        self.auto_exposure.as_ref()
+    }
+
+    /// Returns mutable reference to settings of automatic exposure, if it is enabled.
+    #[inline]
+    pub fn auto_exposure_mut(&mut self) -> Option<&mut AutoExposure> {
+        self.auto_exposure.as_mut()
+    }
+
+    /// Returns camera effects. See `camera_effects` module docs for more info.
+    #[inline]
+    pub fn effects(&self) -> &CameraEffects {
+        &self.effects
+    }
+
+    /// Returns mutable reference to camera effects, use it to trigger shake or field of view
+    /// kick and to set up follow.
+    #[inline]
+    pub fn effects_mut(&mut self) -> &mut CameraEffects {
+        &mut self.effects
+    }
+
+    pub(in crate) fn update_effects(&mut self, dt: f32) {
+        self.effects.update(dt);
+    }
+
     /// Creates picking ray from given screen coordinates.
     pub fn make_ray(&self, screen_coord: Vec2, screen_size: Vec2) -> Ray {
         let viewport = self.viewport_pixels(screen_size);
@@ -222,17 +597,42 @@ impl Camera {
             None
         }
     }
+
+    /// Returns view frustum of camera in world coordinates. It is built from current
+    /// view-projection matrix, so it is valid only after matrices were calculated by scene
+    /// update. Renderer uses exactly the same frustum to cull objects. Frustum is built on
+    /// each call, so prefer to get it once if you need to check many objects.
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from(self.view_projection_matrix()).unwrap_or_default()
+    }
+
+    /// Returns true if given bounding box (in local coordinates of an object with given
+    /// world transform) intersects view frustum of camera.
+    pub fn is_aabb_visible(&self, aabb: &AxisAlignedBoundingBox, transform: &Mat4) -> bool {
+        self.frustum().is_intersects_aabb_transform(aabb, transform)
+    }
+
+    /// Returns true if given sphere (in world coordinates) intersects view frustum of camera.
+    pub fn is_sphere_visible(&self, center: Vec3, radius: f32) -> bool {
+        self.frustum().is_intersects_sphere(center, radius)
+    }
 }
 
 /// Camera builder is used to create new camera in declarative manner.
 /// This is typical implementation of Builder pattern.
 pub struct CameraBuilder {
     base_builder: BaseBuilder,
+    projection: Projection,
     fov: f32,
     z_near: f32,
     z_far: f32,
     viewport: Rect<f32>,
     enabled: bool,
+    render_order: i32,
+    clear_mode: ClearMode,
+    cull_mask: u32,
+    auto_exposure: Option<AutoExposure>,
+    effects: CameraEffects,
 }
 
 impl CameraBuilder {
@@ -241,6 +641,7 @@ impl CameraBuilder {
         Self {
             enabled: true,
             base_builder,
+            projection: Default::default(),
             fov: 75.0f32.to_radians(),
             z_near: 0.025,
             z_far: 2048.0,
@@ -250,9 +651,30 @@ impl CameraBuilder {
                 w: 1.0,
                 h: 1.0,
             },
+            render_order: 0,
+            clear_mode: Default::default(),
+            cull_mask: std::u32::MAX,
+            auto_exposure: None,
+            effects: Default::default(),
         }
     }
 
+    /// Creates new camera builder preset for 2D games: pixel projection, which means that
+    /// one unit of world space is one pixel of viewport, and near and far planes at -1024
+    /// and 1024 relative to camera, so camera sees everything in front of it and behind it.
+    pub fn new_2d(base_builder: BaseBuilder) -> Self {
+        Self::new(base_builder)
+            .with_projection(Projection::Pixel)
+            .with_z_near(-1024.0)
+            .with_z_far(1024.0)
+    }
+
+    /// Sets desired projection.
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
     /// Sets desired field of view in radians.
     pub fn with_fov(mut self, fov: f32) -> Self {
         self.fov = fov;
@@ -283,16 +705,52 @@ impl CameraBuilder {
         self
     }
 
+    /// Sets desired render order.
+    pub fn with_render_order(mut self, render_order: i32) -> Self {
+        self.render_order = render_order;
+        self
+    }
+
+    /// Sets desired clear mode.
+    pub fn with_clear_mode(mut self, clear_mode: ClearMode) -> Self {
+        self.clear_mode = clear_mode;
+        self
+    }
+
+    /// Sets desired cull mask.
+    pub fn with_cull_mask(mut self, cull_mask: u32) -> Self {
+        self.cull_mask = cull_mask;
+        self
+    }
+
+    /// Enables automatic exposure with given settings.
+    pub fn with_auto_exposure(mut self, auto_exposure: AutoExposure) -> Self {
+        self.auto_exposure = Some(auto_exposure);
+        self
+    }
+
+    /// Sets desired camera effects.
+    pub fn with_effects(mut self, effects: CameraEffects) -> Self {
+        self.effects = effects;
+        self
+    }
+
     /// Creates new instance of camera node. Do not forget to add node to scene,
     /// otherwise it is useless.
     pub fn build(self) -> Camera {
         Camera {
             enabled: self.enabled,
             base: self.base_builder.build(),
+            projection: self.projection,
             fov: self.fov,
             z_near: self.z_near,
             z_far: self.z_far,
             viewport: self.viewport,
+            render_order: self.render_order,
+            clear_mode: self.clear_mode,
+            cull_mask: self.cull_mask,
+            auto_exposure: self.auto_exposure,
+            effects: self.effects,
             // No need to calculate these matrices - they'll be automatically
             // recalculated before rendering.
             view_matrix: Mat4::IDENTITY,
@@ -0,0 +1,418 @@
+//! Contains camera effects - shake, field of view kick and smooth follow.
+//!
+//! Every camera has a set of effects, by default all of them are inactive. Effects are
+//! updated by scene together with the camera, so game code only triggers them.
+//!
+//! # Shake
+//!
+//! Shake is driven by "trauma" - a value in [0; 1] range which is increased by game events
+//! (explosions, hits, landings) and decays over time. Strength of shake is proportional to
+//! squared trauma, so small hits give barely noticeable shake and several hits in a row
+//! quickly make it strong. Camera is offset and rotated by smooth noise, which looks much
+//! better than random jitter. Shake affects only view matrix, transform of camera node is
+//! left untouched, so it does not break gameplay code that uses position of camera.
+//!
+//! # Field of view kick
+//!
+//! Kick instantly changes field of view of camera by some amount, which then smoothly
+//! returns back to normal. It is useful for sprint, dash, impacts and so on. Like shake,
+//! it does not change field of view of the camera itself.
+//!
+//! # Follow
+//!
+//! Camera can follow a node keeping given offset from it and optionally look at it. Motion
+//! is critically damped, so camera smoothly catches up with its target without any
+//! overshoot. Unlike shake, follow moves camera node itself. Offset is in world coordinates
+//! and camera should be attached to a node without rotation and scale (root, for example),
+//! because position and rotation of camera are set as is.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{math::vec3::Vec3, pool::Handle},
+//!     scene::{camera_effects::CameraFollow, node::Node, Scene},
+//! };
+//!
+//! fn setup_camera(scene: &mut Scene, camera: Handle<Node>, player: Handle<Node>) {
+//!     scene.graph[camera]
+//!         .as_camera_mut()
+//!         .effects_mut()
+//!         .set_follow(Some(CameraFollow {
+//!             offset: Vec3::new(0.0, 4.0, -6.0),
+//!             look_at: true,
+//!             ..CameraFollow::new(player)
+//!         }));
+//! }
+//!
+//! fn on_explosion(scene: &mut Scene, camera: Handle<Node>) {
+//!     let effects = scene.graph[camera].as_camera_mut().effects_mut();
+//!     effects.add_trauma(0.6);
+//!     effects.kick_fov(10.0f32.to_radians());
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::{mat4::Mat4, quat::Quat, vec3::Vec3},
+        pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::node::Node,
+};
+
+/// Moves `current` towards `target` like critically damped spring with given approximate
+/// time to reach the target. `velocity` keeps state of the spring between calls.
+fn smooth_damp(current: f32, target: f32, velocity: &mut f32, smooth_time: f32, dt: f32) -> f32 {
+    let omega = 2.0 / smooth_time.max(0.0001);
+    let x = omega * dt;
+    // Cheap approximation of exp(-x), precise enough for reasonable time steps.
+    let exp = 1.0 / (1.0 + x + 0.48 * x * x + 0.235 * x * x * x);
+    let change = current - target;
+    let temp = (*velocity + omega * change) * dt;
+    *velocity = (*velocity - omega * temp) * exp;
+    target + (change + temp) * exp
+}
+
+/// Returns pseudo-random value in [-1; 1] range for given integer point of given channel.
+fn hash(channel: u32, i: i32) -> f32 {
+    let mut x = (i as u32).wrapping_mul(0x9E37_79B1) ^ channel.wrapping_mul(0x85EB_CA77);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x2C1B_3C6D);
+    x ^= x >> 12;
+    x = x.wrapping_mul(0x297A_2D39);
+    x ^= x >> 15;
+    (x as f32 / std::u32::MAX as f32) * 2.0 - 1.0
+}
+
+/// Smooth one-dimensional value noise in [-1; 1] range, each channel is independent.
+fn noise(channel: u32, t: f32) -> f32 {
+    let i = t.floor();
+    let f = t - i;
+    let a = hash(channel, i as i32);
+    let b = hash(channel, i as i32 + 1);
+    a + (b - a) * f * f * (3.0 - 2.0 * f)
+}
+
+/// Settings of follow effect, see module docs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraFollow {
+    /// Node to follow.
+    pub target: Handle<Node>,
+    /// Desired offset of camera from target in world coordinates.
+    pub offset: Vec3,
+    /// Approximate time (in seconds) which camera needs to catch up with its target, zero
+    /// means that camera is rigidly attached to target.
+    pub smooth_time: f32,
+    /// Whether camera should look at target or not.
+    pub look_at: bool,
+    /// Offset (in world coordinates) of point at which camera looks from position of
+    /// target, for example to look at head of a character instead of its feet.
+    pub look_at_offset: Vec3,
+}
+
+impl Default for CameraFollow {
+    fn default() -> Self {
+        Self::new(Handle::NONE)
+    }
+}
+
+impl CameraFollow {
+    /// Creates follow settings for given target with default offset and smoothing.
+    pub fn new(target: Handle<Node>) -> Self {
+        Self {
+            target,
+            offset: Vec3::new(0.0, 2.0, -5.0),
+            smooth_time: 0.3,
+            look_at: true,
+            look_at_offset: Vec3::new(0.0, 1.0, 0.0),
+        }
+    }
+}
+
+impl Visit for CameraFollow {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.target.visit("Target", visitor)?;
+        self.offset.visit("Offset", visitor)?;
+        self.smooth_time.visit("SmoothTime", visitor)?;
+        self.look_at.visit("LookAt", visitor)?;
+        self.look_at_offset.visit("LookAtOffset", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct CameraEffects {
+    trauma: f32,
+    trauma_decay: f32,
+    max_shake_offset: f32,
+    max_shake_angle: f32,
+    shake_frequency: f32,
+    fov_offset: f32,
+    fov_velocity: f32,
+    fov_recovery_time: f32,
+    follow: Option<CameraFollow>,
+    follow_velocity: Vec3,
+    time: f32,
+}
+
+impl Default for CameraEffects {
+    fn default() -> Self {
+        Self {
+            trauma: 0.0,
+            trauma_decay: 0.8,
+            max_shake_offset: 0.15,
+            max_shake_angle: 3.0f32.to_radians(),
+            shake_frequency: 15.0,
+            fov_offset: 0.0,
+            fov_velocity: 0.0,
+            fov_recovery_time: 0.25,
+            follow: None,
+            follow_velocity: Vec3::ZERO,
+            time: 0.0,
+        }
+    }
+}
+
+impl CameraEffects {
+    /// Adds trauma which drives camera shake, total trauma is clamped to [0; 1] range.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).max(0.0).min(1.0);
+    }
+
+    /// Returns current trauma.
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Sets amount of trauma which is removed each second.
+    pub fn set_trauma_decay(&mut self, decay: f32) {
+        self.trauma_decay = decay.max(0.0);
+    }
+
+    /// Returns amount of trauma which is removed each second.
+    pub fn trauma_decay(&self) -> f32 {
+        self.trauma_decay
+    }
+
+    /// Sets max offset of camera (in world units) when trauma is at its maximum.
+    pub fn set_max_shake_offset(&mut self, offset: f32) {
+        self.max_shake_offset = offset.max(0.0);
+    }
+
+    /// Returns max offset of camera.
+    pub fn max_shake_offset(&self) -> f32 {
+        self.max_shake_offset
+    }
+
+    /// Sets max rotation (in radians) around each axis when trauma is at its maximum.
+    pub fn set_max_shake_angle(&mut self, angle: f32) {
+        self.max_shake_angle = angle.max(0.0);
+    }
+
+    /// Returns max rotation around each axis.
+    pub fn max_shake_angle(&self) -> f32 {
+        self.max_shake_angle
+    }
+
+    /// Sets how fast camera shakes, roughly amount of direction changes per second.
+    pub fn set_shake_frequency(&mut self, frequency: f32) {
+        self.shake_frequency = frequency.max(0.0);
+    }
+
+    /// Returns how fast camera shakes.
+    pub fn shake_frequency(&self) -> f32 {
+        self.shake_frequency
+    }
+
+    /// Instantly changes field of view by given amount of radians (negative values narrow
+    /// field of view), it then smoothly returns back.
+    pub fn kick_fov(&mut self, amount: f32) {
+        self.fov_offset += amount;
+    }
+
+    /// Returns current change of field of view.
+    pub fn fov_offset(&self) -> f32 {
+        self.fov_offset
+    }
+
+    /// Sets approximate time (in seconds) which field of view needs to return back after
+    /// kick.
+    pub fn set_fov_recovery_time(&mut self, time: f32) {
+        self.fov_recovery_time = time.max(0.0);
+    }
+
+    /// Returns approximate time which field of view needs to return back after kick.
+    pub fn fov_recovery_time(&self) -> f32 {
+        self.fov_recovery_time
+    }
+
+    /// Sets follow settings, `None` stops following.
+    pub fn set_follow(&mut self, follow: Option<CameraFollow>) {
+        self.follow = follow;
+        self.follow_velocity = Vec3::ZERO;
+    }
+
+    /// Returns current follow settings.
+    pub fn follow(&self) -> Option<&CameraFollow> {
+        self.follow.as_ref()
+    }
+
+    /// Returns current follow settings.
+    pub fn follow_mut(&mut self) -> Option<&mut CameraFollow> {
+        self.follow.as_mut()
+    }
+
+    /// Removes trauma and kick of field of view instantly.
+    pub fn reset(&mut self) {
+        self.trauma = 0.0;
+        self.fov_offset = 0.0;
+        self.fov_velocity = 0.0;
+    }
+
+    pub(in crate) fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - self.trauma_decay * dt).max(0.0);
+        self.fov_offset = smooth_damp(
+            self.fov_offset,
+            0.0,
+            &mut self.fov_velocity,
+            self.fov_recovery_time,
+            dt,
+        );
+    }
+
+    /// Returns transform of shake relative to camera, identity if there is no trauma.
+    pub(in crate) fn shake_matrix(&self) -> Mat4 {
+        if self.trauma <= 0.0 {
+            return Mat4::IDENTITY;
+        }
+
+        let strength = self.trauma * self.trauma;
+        let t = self.time * self.shake_frequency;
+        let offset = Vec3::new(noise(0, t), noise(1, t), noise(2, t))
+            .scale(strength * self.max_shake_offset);
+        let angle = strength * self.max_shake_angle;
+        let rotation = Quat::from_axis_angle(Vec3::UP, angle * noise(3, t))
+            * Quat::from_axis_angle(Vec3::X, angle * noise(4, t))
+            * Quat::from_axis_angle(Vec3::LOOK, angle * noise(5, t));
+
+        Mat4::translate(offset) * Mat4::from_quat(rotation)
+    }
+
+    /// Returns new position of following camera and its rotation (if it should look at
+    /// target).
+    pub(in crate) fn follow_step(
+        &mut self,
+        position: Vec3,
+        target_position: Vec3,
+        dt: f32,
+    ) -> Option<(Vec3, Option<Quat>)> {
+        let follow = self.follow.as_ref()?;
+
+        let desired = target_position + follow.offset;
+        let new_position = if follow.smooth_time > 0.0 {
+            Vec3::new(
+                smooth_damp(
+                    position.x,
+                    desired.x,
+                    &mut self.follow_velocity.x,
+                    follow.smooth_time,
+                    dt,
+                ),
+                smooth_damp(
+                    position.y,
+                    desired.y,
+                    &mut self.follow_velocity.y,
+                    follow.smooth_time,
+                    dt,
+                ),
+                smooth_damp(
+                    position.z,
+                    desired.z,
+                    &mut self.follow_velocity.z,
+                    follow.smooth_time,
+                    dt,
+                ),
+            )
+        } else {
+            desired
+        };
+
+        let rotation = if follow.look_at {
+            (target_position + follow.look_at_offset - new_position)
+                .normalized()
+                .map(|dir| {
+                    let yaw = Quat::from_axis_angle(Vec3::UP, dir.x.atan2(dir.z));
+                    let pitch = Quat::from_axis_angle(Vec3::X, (-dir.y).asin());
+                    yaw * pitch
+                })
+        } else {
+            None
+        };
+
+        Some((new_position, rotation))
+    }
+}
+
+impl Visit for CameraEffects {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.trauma_decay.visit("TraumaDecay", visitor)?;
+        self.max_shake_offset.visit("MaxShakeOffset", visitor)?;
+        self.max_shake_angle.visit("MaxShakeAngle", visitor)?;
+        self.shake_frequency.visit("ShakeFrequency", visitor)?;
+        self.fov_recovery_time.visit("FovRecoveryTime", visitor)?;
+        self.follow.visit("Follow", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{math::vec3::Vec3, pool::Handle},
+        scene::camera_effects::{CameraEffects, CameraFollow},
+    };
+
+    #[test]
+    fn trauma_and_fov_kick_decay() {
+        let mut effects = CameraEffects::default();
+        effects.add_trauma(2.0);
+        assert_eq!(effects.trauma(), 1.0);
+        effects.kick_fov(0.5);
+        for _ in 0..120 {
+            effects.update(1.0 / 60.0);
+        }
+        assert!(effects.trauma() < 1.0);
+        assert!(effects.fov_offset().abs() < 0.01);
+    }
+
+    #[test]
+    fn follow_converges_without_overshoot() {
+        let mut effects = CameraEffects::default();
+        effects.set_follow(Some(CameraFollow {
+            offset: Vec3::new(0.0, 0.0, -5.0),
+            smooth_time: 0.2,
+            look_at: false,
+            ..CameraFollow::new(Handle::NONE)
+        }));
+        let target = Vec3::new(10.0, 0.0, 0.0);
+        let mut position = Vec3::ZERO;
+        for _ in 0..300 {
+            let (new_position, rotation) = effects
+                .follow_step(position, target, 1.0 / 60.0)
+                .unwrap();
+            assert!(rotation.is_none());
+            assert!(new_position.x <= 10.0 + 0.0001);
+            position = new_position;
+        }
+        assert!((position - Vec3::new(10.0, 0.0, -5.0)).len() < 0.01);
+    }
+}
@@ -0,0 +1,364 @@
+//! Contains components - pieces of user-defined state and behavior attached to nodes.
+//!
+//! Component is a lightweight alternative to custom nodes (see `node` module docs): instead of
+//! making new node type, gameplay state (health, inventory, door state and so on) can be
+//! attached to any node including built-in ones. Node can have any amount of components, but
+//! only one component of each type. Components are cloned together with their nodes (so they
+//! are copied into instances of models and prefabs) and saved together with the scene.
+//!
+//! Component must implement `Component` trait and a constructor for it must be registered in
+//! `ComponentRegistry`. Registry is used to re-create components when a scene is loaded, so
+//! registration must be done before loading any scene that contains components.
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::visitor::{Visit, VisitResult, Visitor},
+//!     scene::{
+//!         base::{Base, BaseBuilder},
+//!         component::{Component, ComponentRegistry},
+//!     },
+//! };
+//! use std::any::Any;
+//!
+//! #[derive(Clone, Debug, Default)]
+//! struct Health {
+//!     value: f32,
+//!     regeneration: f32,
+//! }
+//!
+//! impl Visit for Health {
+//!     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+//!         visitor.enter_region(name)?;
+//!
+//!         self.value.visit("Value", visitor)?;
+//!         self.regeneration.visit("Regeneration", visitor)?;
+//!
+//!         visitor.leave_region()
+//!     }
+//! }
+//!
+//! impl Component for Health {
+//!     fn box_clone(&self) -> Box<dyn Component> {
+//!         Box::new(self.clone())
+//!     }
+//!
+//!     fn kind(&self) -> u32 {
+//!         0
+//!     }
+//!
+//!     fn as_any(&self) -> &dyn Any {
+//!         self
+//!     }
+//!
+//!     fn as_any_mut(&mut self) -> &mut dyn Any {
+//!         self
+//!     }
+//!
+//!     fn update(&mut self, _node: &mut Base, dt: f32) {
+//!         self.value = (self.value + self.regeneration * dt).min(100.0);
+//!     }
+//! }
+//!
+//! fn register() {
+//!     ComponentRegistry::get()
+//!         .unwrap()
+//!         .register(0, Box::new(|| Box::new(Health::default())))
+//!         .unwrap();
+//! }
+//!
+//! fn make_player() -> Base {
+//!     BaseBuilder::new()
+//!         .with_component(Health {
+//!             value: 100.0,
+//!             regeneration: 1.0,
+//!         })
+//!         .build()
+//! }
+//!
+//! fn is_alive(node: &Base) -> bool {
+//!     node.component::<Health>().map_or(false, |health| health.value > 0.0)
+//! }
+//! ```
+
+use crate::{
+    core::visitor::{Visit, VisitResult, Visitor},
+    scene::base::Base,
+    utils::log::{Log, LogCategory},
+};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Debug,
+    sync::{LockResult, Mutex, MutexGuard},
+};
+
+/// Callback that creates new instance of component with default state. Loaded state will be
+/// applied to the instance afterwards.
+pub type ComponentConstructor = dyn Fn() -> Box<dyn Component> + Send + 'static;
+
+/// Component registry holds constructors of every component type, it is used to create
+/// components by their kind when a scene is loading. See module docs.
+#[derive(Default)]
+pub struct ComponentRegistry {
+    constructors: HashMap<u32, Box<ComponentConstructor>>,
+}
+
+impl ComponentRegistry {
+    /// Locks registry singleton and returns lock result.
+    pub fn get() -> LockResult<MutexGuard<'static, Self>> {
+        COMPONENT_REGISTRY_INSTANCE.lock()
+    }
+
+    /// Registers constructor for given kind of components. Returns error if there is already
+    /// a constructor for the kind.
+    pub fn register(
+        &mut self,
+        kind: u32,
+        constructor: Box<ComponentConstructor>,
+    ) -> Result<(), String> {
+        if self.constructors.contains_key(&kind) {
+            Err(format!("Component kind {} is already registered!", kind))
+        } else {
+            self.constructors.insert(kind, constructor);
+            Ok(())
+        }
+    }
+
+    /// Removes constructor of given kind of components. Returns true if there was such
+    /// constructor.
+    pub fn unregister(&mut self, kind: u32) -> bool {
+        self.constructors.remove(&kind).is_some()
+    }
+
+    /// Returns true if there is a constructor for given kind of components.
+    pub fn is_registered(&self, kind: u32) -> bool {
+        self.constructors.contains_key(&kind)
+    }
+
+    /// Creates new component of given kind.
+    pub fn spawn(&self, kind: u32) -> Result<Box<dyn Component>, String> {
+        match self.constructors.get(&kind) {
+            Some(constructor) => Ok(constructor()),
+            None => Err(format!("Component kind {} is not registered!", kind)),
+        }
+    }
+}
+
+lazy_static! {
+    static ref COMPONENT_REGISTRY_INSTANCE: Mutex<ComponentRegistry> =
+        Mutex::new(Default::default());
+}
+
+/// Component is user-defined state and behavior of a node. It can be implemented on
+/// serializable types only! See module docs for example.
+pub trait Component: Any + Visit + Send + Debug {
+    /// Creates boxed copy of component.
+    fn box_clone(&self) -> Box<dyn Component>;
+
+    /// Returns unique kind of component, it is used to create correct component type on load,
+    /// so it must match kind that was used to register the component in `ComponentRegistry`.
+    fn kind(&self) -> u32;
+
+    /// Returns reference to self as `Any`, it is used for downcasting.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns mutable reference to self as `Any`, it is used for downcasting.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// Called once per frame for every component of enabled node. `dt` is already scaled by
+    /// time scale of node. Nodes can be updated in parallel, so component must not rely on
+    /// state of other nodes.
+    fn update(&mut self, _node: &mut Base, _dt: f32) {}
+}
+
+impl Clone for Box<dyn Component> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+fn spawn_component(kind: u32) -> Result<Box<dyn Component>, String> {
+    ComponentRegistry::get()
+        .map_err(|_| String::from("Failed to get component registry!"))?
+        .spawn(kind)
+}
+
+// Components are saved together with their kinds, so they can be re-created by registry.
+pub(in crate) fn visit_components(
+    components: &mut Vec<Box<dyn Component>>,
+    name: &str,
+    visitor: &mut Visitor,
+) -> VisitResult {
+    visitor.enter_region(name)?;
+
+    let mut count = components.len() as u32;
+    count.visit("Count", visitor)?;
+    if visitor.is_reading() {
+        components.clear();
+        for i in 0..count {
+            visitor.enter_region(&format!("Item{}", i))?;
+            let mut kind = 0u32;
+            kind.visit("Kind", visitor)?;
+            match spawn_component(kind) {
+                Ok(mut component) => {
+                    component.visit("Data", visitor)?;
+                    components.push(component);
+                }
+                // Component is skipped instead of failing, otherwise rest of the node would
+                // be read from wrong region.
+                Err(e) => Log::warn(
+                    LogCategory::Scene,
+                    format!("Unable to load component, it is skipped. Reason: {}", e),
+                ),
+            }
+            visitor.leave_region()?;
+        }
+    } else {
+        for (i, component) in components.iter_mut().enumerate() {
+            visitor.enter_region(&format!("Item{}", i))?;
+            let mut kind = component.kind();
+            kind.visit("Kind", visitor)?;
+            component.visit("Data", visitor)?;
+            visitor.leave_region()?;
+        }
+    }
+
+    visitor.leave_region()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::visitor::{Visit, VisitResult, Visitor},
+        scene::{
+            base::{Base, BaseBuilder, OverridableProperty},
+            component::{Component, ComponentRegistry},
+        },
+    };
+    use std::any::Any;
+
+    #[derive(Clone, Debug, Default)]
+    struct Timer {
+        time: f32,
+    }
+
+    impl Visit for Timer {
+        fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+            visitor.enter_region(name)?;
+
+            self.time.visit("Time", visitor)?;
+
+            visitor.leave_region()
+        }
+    }
+
+    impl Component for Timer {
+        fn box_clone(&self) -> Box<dyn Component> {
+            Box::new(self.clone())
+        }
+
+        fn kind(&self) -> u32 {
+            1000
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+
+        fn update(&mut self, node: &mut Base, dt: f32) {
+            self.time += dt;
+            node.set_name("Updated");
+        }
+    }
+
+    #[test]
+    fn components_are_updated_and_saved() {
+        ComponentRegistry::get()
+            .unwrap()
+            .register(1000, Box::new(|| Box::new(Timer::default())))
+            .unwrap();
+
+        let mut base = BaseBuilder::new()
+            .with_component(Timer { time: 1.0 })
+            .build();
+        base.add_component(Timer { time: 2.0 });
+        assert_eq!(base.components().len(), 1);
+        base.update_components(0.5);
+        assert_eq!(base.name(), "Updated");
+        assert_eq!(base.component::<Timer>().unwrap().time, 2.5);
+
+        let path = std::env::temp_dir().join("rg3d-component-round-trip-test");
+        let mut visitor = Visitor::new();
+        base.visit("Base", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = Base::default();
+        loaded.visit("Base", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert_eq!(loaded.clone().component::<Timer>().unwrap().time, 2.5);
+        assert!(loaded.remove_component::<Timer>());
+        assert!(!loaded.has_component::<Timer>());
+    }
+
+    // Kind of this component is never registered.
+    #[derive(Clone, Debug, Default)]
+    struct Unknown {
+        value: u32,
+    }
+
+    impl Visit for Unknown {
+        fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+            visitor.enter_region(name)?;
+
+            self.value.visit("Value", visitor)?;
+
+            visitor.leave_region()
+        }
+    }
+
+    impl Component for Unknown {
+        fn box_clone(&self) -> Box<dyn Component> {
+            Box::new(self.clone())
+        }
+
+        fn kind(&self) -> u32 {
+            1001
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn unregistered_components_are_skipped() {
+        let mut base = BaseBuilder::new()
+            .with_name("Node")
+            .with_component(Unknown { value: 42 })
+            .build();
+        base.set_property_overridden(OverridableProperty::Position, true);
+
+        let path = std::env::temp_dir().join("rg3d-unregistered-component-test");
+        let mut visitor = Visitor::new();
+        base.visit("Base", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = Base::default();
+        loaded.visit("Base", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(path);
+        assert!(loaded.components().is_empty());
+        assert_eq!(loaded.name(), "Node");
+        // Fields after components must be read from correct region.
+        assert_eq!(loaded.property_overrides(), base.property_overrides());
+    }
+}
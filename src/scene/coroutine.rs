@@ -0,0 +1,507 @@
+//! Contains lightweight coroutines which are owned by scene nodes.
+//!
+//! Coroutine is a piece of logic which is executed over multiple frames, for example
+//! "flash light three times then explode". Every coroutine has an owner node, when owner
+//! is removed from the graph all its coroutines are cancelled automatically, so there is
+//! no need to track delayed actions manually and there is no chance to touch a dangling
+//! handle from some timer.
+//!
+//! Coroutines are not serialized, they must be restarted after scene was loaded.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::scene::{
+//!     Scene,
+//!     node::Node,
+//!     coroutine::Sequence,
+//! };
+//! use rg3d::core::pool::Handle;
+//!
+//! fn flash_and_explode(scene: &mut Scene, light: Handle<Node>) {
+//!     let sequence = Sequence::new()
+//!         .repeat(3, |flash| {
+//!             flash
+//!                 .then(|ctx| {
+//!                     ctx.graph[ctx.owner].set_visibility(false);
+//!                 })
+//!                 .wait(0.1)
+//!                 .then(|ctx| {
+//!                     ctx.graph[ctx.owner].set_visibility(true);
+//!                 })
+//!                 .wait(0.1)
+//!         })
+//!         .then(|ctx| ctx.graph.remove_node(ctx.owner));
+//!     scene.coroutines.start(light, sequence);
+//! }
+//! ```
+
+use crate::{
+    core::pool::{Handle, Pool},
+    scene::{graph::Graph, node::Node},
+};
+use std::{
+    collections::HashMap,
+    fmt::{Debug, Formatter},
+};
+
+/// Context of a coroutine, it is passed to coroutine on each resume.
+pub struct CoroutineContext<'a> {
+    /// Handle of the node that owns the coroutine. It is always valid at the moment
+    /// when coroutine is resumed.
+    pub owner: Handle<Node>,
+
+    /// Graph of the scene, owner node could be borrowed from it.
+    pub graph: &'a mut Graph,
+
+    /// Time in seconds passed from last resume.
+    pub dt: f32,
+}
+
+/// Defines what should be done with coroutine after it was resumed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CoroutineState {
+    /// Coroutine will be resumed next frame.
+    Running,
+
+    /// Coroutine will be resumed after given amount of seconds. If coroutine was resumed
+    /// later than requested by previous sleep, the difference is subtracted from this delay.
+    Sleep(f32),
+
+    /// Coroutine is done and will be removed.
+    Finished,
+}
+
+/// Coroutine is a resumable piece of logic. It is implemented for every closure with
+/// suitable signature, so there is no need to implement it manually in simple cases.
+pub trait Coroutine: Send {
+    /// Continues execution of coroutine.
+    fn resume(&mut self, context: &mut CoroutineContext) -> CoroutineState;
+}
+
+impl<F> Coroutine for F
+where
+    F: FnMut(&mut CoroutineContext) -> CoroutineState + Send,
+{
+    fn resume(&mut self, context: &mut CoroutineContext) -> CoroutineState {
+        self(context)
+    }
+}
+
+type Action = dyn FnMut(&mut CoroutineContext) + Send;
+
+enum Step {
+    Action(Box<Action>),
+    Wait(f32),
+    Repeat {
+        start: usize,
+        times: u32,
+        remaining: u32,
+    },
+}
+
+/// Sequence is a coroutine made of actions and delays between them. It covers most of
+/// common cases and allows to avoid writing state machines by hand.
+pub struct Sequence {
+    steps: Vec<Step>,
+    current: usize,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sequence {
+    /// Creates new empty sequence.
+    pub fn new() -> Self {
+        Self {
+            steps: Vec::new(),
+            current: 0,
+        }
+    }
+
+    /// Adds an action which will be executed once at its turn.
+    pub fn then<F>(mut self, action: F) -> Self
+    where
+        F: FnMut(&mut CoroutineContext) + Send + 'static,
+    {
+        self.steps.push(Step::Action(Box::new(action)));
+        self
+    }
+
+    /// Adds delay in seconds before next step.
+    pub fn wait(mut self, seconds: f32) -> Self {
+        self.steps.push(Step::Wait(seconds.max(0.0)));
+        self
+    }
+
+    /// Adds a block of steps that will be executed given amount of times in a row.
+    /// Block is built by given closure.
+    pub fn repeat<F>(mut self, times: u32, build: F) -> Self
+    where
+        F: FnOnce(Sequence) -> Sequence,
+    {
+        let start = self.steps.len();
+        for step in build(Sequence::new()).steps {
+            self.steps.push(match step {
+                Step::Repeat {
+                    start: inner_start,
+                    times,
+                    remaining,
+                } => Step::Repeat {
+                    start: start + inner_start,
+                    times,
+                    remaining,
+                },
+                other => other,
+            });
+        }
+        if times > 1 {
+            self.steps.push(Step::Repeat {
+                start,
+                times,
+                remaining: times - 1,
+            });
+        } else if times == 0 {
+            self.steps.truncate(start);
+        }
+        self
+    }
+
+    /// Returns true if every step was executed.
+    pub fn is_finished(&self) -> bool {
+        self.current >= self.steps.len()
+    }
+}
+
+impl Coroutine for Sequence {
+    fn resume(&mut self, context: &mut CoroutineContext) -> CoroutineState {
+        while let Some(step) = self.steps.get_mut(self.current) {
+            match step {
+                Step::Action(action) => {
+                    self.current += 1;
+                    action(context);
+                    // Action could remove owner node, there is no point to continue.
+                    if !context.graph.is_valid_handle(context.owner) {
+                        return CoroutineState::Finished;
+                    }
+                }
+                &mut Step::Wait(seconds) => {
+                    self.current += 1;
+                    return CoroutineState::Sleep(seconds);
+                }
+                Step::Repeat {
+                    start,
+                    times,
+                    remaining,
+                } => {
+                    if *remaining > 0 {
+                        *remaining -= 1;
+                        self.current = *start;
+                    } else {
+                        // Reset counter so outer block could run this one again.
+                        *remaining = *times - 1;
+                        self.current += 1;
+                    }
+                }
+            }
+        }
+        CoroutineState::Finished
+    }
+}
+
+/// Coroutine scheduled for execution, it is stored in the coroutine container.
+pub struct ScheduledCoroutine {
+    owner: Handle<Node>,
+    sleep: f32,
+    elapsed: f32,
+    coroutine: Box<dyn Coroutine>,
+}
+
+impl ScheduledCoroutine {
+    /// Returns handle of owner node.
+    pub fn owner(&self) -> Handle<Node> {
+        self.owner
+    }
+}
+
+/// Container for all coroutines of a scene. See module docs.
+pub struct CoroutineContainer {
+    pool: Pool<ScheduledCoroutine>,
+    finished: Vec<Handle<ScheduledCoroutine>>,
+}
+
+impl Default for CoroutineContainer {
+    fn default() -> Self {
+        Self {
+            pool: Pool::new(),
+            finished: Vec::new(),
+        }
+    }
+}
+
+impl Debug for CoroutineContainer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CoroutineContainer: {} coroutines", self.pool.alive_count())
+    }
+}
+
+impl CoroutineContainer {
+    /// Creates new empty container.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Starts new coroutine owned by given node. Coroutine will be resumed first time
+    /// at next scene update. Returned handle can be used to stop coroutine manually.
+    pub fn start<C: Coroutine + 'static>(
+        &mut self,
+        owner: Handle<Node>,
+        coroutine: C,
+    ) -> Handle<ScheduledCoroutine> {
+        self.pool.spawn(ScheduledCoroutine {
+            owner,
+            sleep: 0.0,
+            elapsed: 0.0,
+            coroutine: Box::new(coroutine),
+        })
+    }
+
+    /// Stops coroutine. Does nothing if coroutine is already finished.
+    pub fn stop(&mut self, handle: Handle<ScheduledCoroutine>) {
+        if self.pool.is_valid_handle(handle) {
+            self.pool.free(handle);
+        }
+    }
+
+    /// Stops every coroutine owned by given node.
+    pub fn stop_all_of(&mut self, owner: Handle<Node>) {
+        self.finished.clear();
+        for (handle, scheduled) in self.pool.pair_iter() {
+            if scheduled.owner == owner {
+                self.finished.push(handle);
+            }
+        }
+        for handle in self.finished.drain(..) {
+            self.pool.free(handle);
+        }
+    }
+
+    /// Returns true if coroutine is still running.
+    pub fn is_running(&self, handle: Handle<ScheduledCoroutine>) -> bool {
+        self.pool.is_valid_handle(handle)
+    }
+
+    /// Returns total amount of running coroutines.
+    pub fn count(&self) -> usize {
+        self.pool.alive_count()
+    }
+
+    /// Stops every coroutine.
+    pub fn clear(&mut self) {
+        self.pool.clear();
+    }
+
+    pub(in crate) fn update(&mut self, graph: &mut Graph, dt: f32) {
+        self.finished.clear();
+        for (handle, scheduled) in self.pool.pair_iter_mut() {
+            // Owner could be removed by graph directly or by other coroutine.
+            if !graph.is_valid_handle(scheduled.owner) {
+                self.finished.push(handle);
+                continue;
+            }
+
+            // Coroutines follow time scale of their owners and paused while owner is disabled.
+            let owner = &graph[scheduled.owner];
+            if !owner.is_globally_enabled() {
+                continue;
+            }
+            let dt = dt * owner.global_time_scale();
+
+            scheduled.elapsed += dt;
+            if scheduled.sleep > 0.0 {
+                scheduled.sleep -= dt;
+                if scheduled.sleep > 0.0 {
+                    continue;
+                }
+            }
+
+            let mut context = CoroutineContext {
+                owner: scheduled.owner,
+                graph,
+                dt: scheduled.elapsed,
+            };
+            scheduled.elapsed = 0.0;
+
+            match scheduled.coroutine.resume(&mut context) {
+                CoroutineState::Running => scheduled.sleep = 0.0,
+                // Time that was overslept is taken from next delay, otherwise sequences of
+                // short delays would be stretched at low frame rate.
+                CoroutineState::Sleep(seconds) => scheduled.sleep += seconds,
+                CoroutineState::Finished => self.finished.push(handle),
+            }
+        }
+        for handle in self.finished.drain(..) {
+            self.pool.free(handle);
+        }
+    }
+
+    pub(in crate) fn remap_owners(
+        &mut self,
+        old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>,
+    ) {
+        self.finished.clear();
+        for (handle, scheduled) in self.pool.pair_iter_mut() {
+            match old_new_mapping.get(&scheduled.owner) {
+                Some(&new_owner) => scheduled.owner = new_owner,
+                None => self.finished.push(handle),
+            }
+        }
+        for handle in self.finished.drain(..) {
+            self.pool.free(handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::pool::Handle,
+        scene::{
+            base::BaseBuilder,
+            coroutine::{CoroutineContainer, CoroutineContext, CoroutineState, Sequence},
+            graph::Graph,
+            node::Node,
+        },
+    };
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    fn push(log: &Log, entry: &'static str) -> impl FnMut(&mut CoroutineContext) + Send {
+        let log = log.clone();
+        move |_| log.lock().unwrap().push(entry)
+    }
+
+    fn add_node(graph: &mut Graph) -> Handle<Node> {
+        graph.add_node(BaseBuilder::new().build_node())
+    }
+
+    fn running(_: &mut CoroutineContext) -> CoroutineState {
+        CoroutineState::Running
+    }
+
+    #[test]
+    fn nested_repeat() {
+        let mut graph = Graph::new();
+        let owner = add_node(&mut graph);
+        let log = Log::default();
+
+        let sequence = Sequence::new()
+            .repeat(2, |outer| {
+                outer
+                    .then(push(&log, "A"))
+                    .repeat(3, |inner| inner.then(push(&log, "B")))
+            })
+            .then(push(&log, "C"));
+        let mut coroutines = CoroutineContainer::new();
+        let handle = coroutines.start(owner, sequence);
+        coroutines.update(&mut graph, 0.1);
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec!["A", "B", "B", "B", "A", "B", "B", "B", "C"]
+        );
+        assert!(!coroutines.is_running(handle));
+    }
+
+    #[test]
+    fn oversleep_is_carried_over() {
+        let mut graph = Graph::new();
+        let owner = add_node(&mut graph);
+        let log = Log::default();
+
+        let sequence = Sequence::new()
+            .then(push(&log, "1"))
+            .wait(0.1)
+            .then(push(&log, "2"))
+            .wait(0.1)
+            .then(push(&log, "3"));
+        let mut coroutines = CoroutineContainer::new();
+        coroutines.start(owner, sequence);
+
+        // Second step is done at third update 0.05 seconds late, so the last one is done
+        // at fourth update instead of fifth.
+        for _ in 0..3 {
+            coroutines.update(&mut graph, 0.075);
+        }
+        assert_eq!(*log.lock().unwrap(), vec!["1", "2"]);
+        coroutines.update(&mut graph, 0.075);
+        assert_eq!(*log.lock().unwrap(), vec!["1", "2", "3"]);
+        assert_eq!(coroutines.count(), 0);
+    }
+
+    #[test]
+    fn stop_all_of_owner() {
+        let mut graph = Graph::new();
+        let a = add_node(&mut graph);
+        let b = add_node(&mut graph);
+
+        let mut coroutines = CoroutineContainer::new();
+        let a1 = coroutines.start(a, running);
+        let a2 = coroutines.start(a, running);
+        let b1 = coroutines.start(b, running);
+        coroutines.stop_all_of(a);
+        coroutines.update(&mut graph, 0.1);
+
+        assert!(!coroutines.is_running(a1));
+        assert!(!coroutines.is_running(a2));
+        assert!(coroutines.is_running(b1));
+    }
+
+    #[test]
+    fn coroutines_of_removed_owner_are_cancelled() {
+        let mut graph = Graph::new();
+        let a = add_node(&mut graph);
+        let b = add_node(&mut graph);
+        let log = Log::default();
+
+        let mut coroutines = CoroutineContainer::new();
+        coroutines.start(a, Sequence::new().then(push(&log, "removed")));
+        // Coroutine that removes its owner stops right after that.
+        coroutines.start(
+            b,
+            Sequence::new()
+                .then(|ctx| ctx.graph.remove_node(ctx.owner))
+                .then(push(&log, "after removal")),
+        );
+        graph.remove_node(a);
+        coroutines.update(&mut graph, 0.1);
+
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(coroutines.count(), 0);
+    }
+
+    #[test]
+    fn remap_owners() {
+        let mut graph = Graph::new();
+        let a = add_node(&mut graph);
+        let b = add_node(&mut graph);
+        let c = add_node(&mut graph);
+
+        let mut coroutines = CoroutineContainer::new();
+        let of_a = coroutines.start(a, running);
+        let of_c = coroutines.start(c, running);
+        let mut old_new_mapping = HashMap::new();
+        old_new_mapping.insert(a, b);
+        coroutines.remap_owners(&old_new_mapping);
+
+        assert_eq!(coroutines.pool[of_a].owner(), b);
+        assert!(!coroutines.is_running(of_c));
+    }
+}
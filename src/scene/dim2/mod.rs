@@ -0,0 +1,115 @@
+//! Contains helpers for 2D games.
+//!
+//! 2D scenes use the same graph, nodes and renderer as 3D scenes, 2D is just a special case
+//! of 3D where everything lies in XY plane and camera looks along Z axis without perspective.
+//! This module contains pieces that make such setup convenient:
+//!
+//! - `CameraBuilder::new_2d` creates camera with pixel projection, one world unit is one
+//! pixel of viewport, so positions of nodes are given in pixels.
+//! - `Transform2D` trait adds 2D accessors to transforms - position in XY plane, rotation
+//! around Z axis and depth.
+//! - Sprites have sort layers which define order of drawing, see `sprite` module docs.
+//! - `physics::Physics2D` is simple 2D physics with circle and rectangle colliders, every
+//! scene has its own instance in `Scene::physics2d`.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{math::vec2::Vec2, pool::Handle},
+//!     scene::{
+//!         base::BaseBuilder,
+//!         camera::CameraBuilder,
+//!         dim2::{
+//!             physics::{RigidBody2D, Shape2D},
+//!             Transform2D,
+//!         },
+//!         node::Node,
+//!         sprite::SpriteBuilder,
+//!         Scene,
+//!     },
+//! };
+//!
+//! fn create_2d_scene() -> (Scene, Handle<Node>) {
+//!     let mut scene = Scene::new();
+//!     scene.physics2d.gravity = Vec2::new(0.0, -980.0);
+//!
+//!     scene
+//!         .graph
+//!         .add_node(CameraBuilder::new_2d(BaseBuilder::new()).build_node());
+//!
+//!     let player = scene.graph.add_node(
+//!         SpriteBuilder::new(BaseBuilder::new())
+//!             .with_size(16.0)
+//!             .with_sort_layer(1)
+//!             .build_node(),
+//!     );
+//!     scene.graph[player]
+//!         .local_transform_mut()
+//!         .set_position_2d(Vec2::new(0.0, 100.0));
+//!     let shape = Shape2D::Circle { radius: 16.0 };
+//!     scene.physics2d.add_body(RigidBody2D::new(player, shape));
+//!
+//!     (scene, player)
+//! }
+//! ```
+
+pub mod physics;
+
+use crate::{
+    core::math::{quat::Quat, vec2::Vec2, vec3::Vec3},
+    scene::transform::Transform,
+};
+
+/// Extension of transforms for 2D games. Position of node in 2D is given by X and Y
+/// coordinates, Z coordinate is called depth. Rotation in 2D is rotation around Z axis.
+pub trait Transform2D {
+    /// Returns position in XY plane.
+    fn position_2d(&self) -> Vec2;
+
+    /// Sets position in XY plane, depth is left unchanged.
+    fn set_position_2d(&mut self, position: Vec2) -> &mut Self;
+
+    /// Returns Z coordinate of position.
+    fn depth(&self) -> f32;
+
+    /// Sets Z coordinate of position, position in XY plane is left unchanged.
+    fn set_depth(&mut self, depth: f32) -> &mut Self;
+
+    /// Returns rotation around Z axis in radians. Result makes sense only if transform
+    /// was rotated only around Z axis.
+    fn rotation_2d(&self) -> f32;
+
+    /// Sets rotation around Z axis in radians, replacing current rotation.
+    fn set_rotation_2d(&mut self, angle: f32) -> &mut Self;
+}
+
+impl Transform2D for Transform {
+    fn position_2d(&self) -> Vec2 {
+        let position = self.position();
+        Vec2::new(position.x, position.y)
+    }
+
+    fn set_position_2d(&mut self, position: Vec2) -> &mut Self {
+        let depth = self.position().z;
+        self.set_position(Vec3::new(position.x, position.y, depth))
+    }
+
+    fn depth(&self) -> f32 {
+        self.position().z
+    }
+
+    fn set_depth(&mut self, depth: f32) -> &mut Self {
+        let position = self.position();
+        self.set_position(Vec3::new(position.x, position.y, depth))
+    }
+
+    fn rotation_2d(&self) -> f32 {
+        let rotation = self.rotation();
+        2.0 * rotation.z.atan2(rotation.w)
+    }
+
+    fn set_rotation_2d(&mut self, angle: f32) -> &mut Self {
+        self.set_rotation(Quat::from_axis_angle(Vec3::new(0.0, 0.0, 1.0), angle))
+    }
+}
@@ -0,0 +1,540 @@
+//! Contains simple 2D physics.
+//!
+//! 2D physics moves nodes in XY plane and resolves collisions between them. Each rigid body
+//! is bound to a node, position of body is taken from local position of node at the beginning
+//! of each step and written back at the end, so nodes can be teleported by changing their
+//! local position directly. Z coordinate (depth) of nodes is left untouched, so nodes with
+//! rigid bodies should be attached to root or to nodes that are not moved.
+//!
+//! Bodies do not rotate and colliders are not rotated with nodes - rectangles are always
+//! axis-aligned. This is enough for most platformers and top-down games.
+//!
+//! # Performance
+//!
+//! Collisions are checked for each pair of bodies, so physics is fine for hundreds of bodies,
+//! but not for thousands.
+
+use crate::{
+    core::{
+        math::vec2::Vec2,
+        pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{graph::Graph, node::Node},
+};
+use std::collections::HashMap;
+
+/// Shape of 2D collider, shape is centered at position of body.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape2D {
+    /// Circle with given radius.
+    Circle {
+        /// Radius of circle.
+        radius: f32,
+    },
+    /// Axis-aligned rectangle.
+    Rectangle {
+        /// Half of width and height of rectangle.
+        half_extents: Vec2,
+    },
+}
+
+impl Default for Shape2D {
+    fn default() -> Self {
+        Shape2D::Circle { radius: 0.5 }
+    }
+}
+
+impl Shape2D {
+    fn id(self) -> u32 {
+        match self {
+            Shape2D::Circle { .. } => 0,
+            Shape2D::Rectangle { .. } => 1,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(Shape2D::Circle { radius: 0.5 }),
+            1 => Ok(Shape2D::Rectangle {
+                half_extents: Vec2::new(0.5, 0.5),
+            }),
+            _ => Err(format!("Invalid 2D shape {}", id)),
+        }
+    }
+}
+
+impl Visit for Shape2D {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        match self {
+            Shape2D::Circle { radius } => radius.visit("Radius", visitor)?,
+            Shape2D::Rectangle { half_extents } => half_extents.visit("HalfExtents", visitor)?,
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Kind of rigid body defines how it is moved.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BodyKind2D {
+    /// Body is moved by velocity, gravity and collisions.
+    Dynamic,
+    /// Body is never moved by physics, but dynamic bodies collide with it. Use it for
+    /// level geometry.
+    Static,
+    /// Body is moved only by its velocity, collisions do not affect it, but it pushes
+    /// dynamic bodies. Use it for moving platforms.
+    Kinematic,
+}
+
+impl Default for BodyKind2D {
+    fn default() -> Self {
+        BodyKind2D::Dynamic
+    }
+}
+
+impl BodyKind2D {
+    fn id(self) -> u32 {
+        match self {
+            BodyKind2D::Dynamic => 0,
+            BodyKind2D::Static => 1,
+            BodyKind2D::Kinematic => 2,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(BodyKind2D::Dynamic),
+            1 => Ok(BodyKind2D::Static),
+            2 => Ok(BodyKind2D::Kinematic),
+            _ => Err(format!("Invalid 2D body kind {}", id)),
+        }
+    }
+}
+
+impl Visit for BodyKind2D {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        let mut id = self.id();
+        id.visit(name, visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        Ok(())
+    }
+}
+
+/// Rigid body of 2D physics, it is bound to a node.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RigidBody2D {
+    /// Node which is moved by the body.
+    pub node: Handle<Node>,
+    /// Kind of body.
+    pub kind: BodyKind2D,
+    /// Shape of collider.
+    pub shape: Shape2D,
+    /// Linear velocity in units per second.
+    pub velocity: Vec2,
+    /// Mass of body, it is used only by dynamic bodies.
+    pub mass: f32,
+    /// Bounciness of body in [0; 1] range, smallest restitution of two colliding bodies
+    /// is used.
+    pub restitution: f32,
+    /// Multiplier of gravity for this body.
+    pub gravity_scale: f32,
+    /// Sensor bodies report contacts, but do not collide with other bodies. Use them for
+    /// triggers.
+    pub sensor: bool,
+    /// Groups to which body belongs to, bit mask.
+    pub collision_group: u32,
+    /// Groups with which body can collide, bit mask. Two bodies collide only if group of
+    /// each body is in mask of other body.
+    pub collision_mask: u32,
+}
+
+impl Default for RigidBody2D {
+    fn default() -> Self {
+        Self::new(Handle::NONE, Default::default())
+    }
+}
+
+impl RigidBody2D {
+    /// Creates new dynamic body with unit mass, no restitution, which collides with
+    /// everything.
+    pub fn new(node: Handle<Node>, shape: Shape2D) -> Self {
+        Self {
+            node,
+            kind: BodyKind2D::Dynamic,
+            shape,
+            velocity: Vec2::ZERO,
+            mass: 1.0,
+            restitution: 0.0,
+            gravity_scale: 1.0,
+            sensor: false,
+            collision_group: 1,
+            collision_mask: std::u32::MAX,
+        }
+    }
+
+    /// Sets desired kind of body.
+    pub fn with_kind(mut self, kind: BodyKind2D) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    fn inv_mass(&self) -> f32 {
+        if self.kind == BodyKind2D::Dynamic && self.mass > 0.0 {
+            1.0 / self.mass
+        } else {
+            0.0
+        }
+    }
+
+    fn can_collide_with(&self, other: &RigidBody2D) -> bool {
+        (self.kind == BodyKind2D::Dynamic || other.kind == BodyKind2D::Dynamic)
+            && self.collision_group & other.collision_mask != 0
+            && other.collision_group & self.collision_mask != 0
+    }
+}
+
+impl Visit for RigidBody2D {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.node.visit("Node", visitor)?;
+        self.kind.visit("Kind", visitor)?;
+        self.shape.visit("Shape", visitor)?;
+        self.velocity.visit("Velocity", visitor)?;
+        self.mass.visit("Mass", visitor)?;
+        self.restitution.visit("Restitution", visitor)?;
+        self.gravity_scale.visit("GravityScale", visitor)?;
+        self.sensor.visit("Sensor", visitor)?;
+        self.collision_group.visit("CollisionGroup", visitor)?;
+        self.collision_mask.visit("CollisionMask", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Contact between two bodies which was found during last step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact2D {
+    /// Node of first body.
+    pub a: Handle<Node>,
+    /// Node of second body.
+    pub b: Handle<Node>,
+    /// Direction from first body to second.
+    pub normal: Vec2,
+    /// Penetration depth before resolution.
+    pub depth: f32,
+}
+
+fn sign(value: f32) -> f32 {
+    if value < 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
+// Returns normal that points from rectangle to circle and penetration depth.
+fn rectangle_circle(
+    rectangle: Vec2,
+    half_extents: Vec2,
+    circle: Vec2,
+    radius: f32,
+) -> Option<(Vec2, f32)> {
+    let local = circle - rectangle;
+    if local.x.abs() <= half_extents.x && local.y.abs() <= half_extents.y {
+        // Center of circle is inside of rectangle, push it out through closest side.
+        let dx = half_extents.x - local.x.abs();
+        let dy = half_extents.y - local.y.abs();
+        if dx < dy {
+            Some((Vec2::new(sign(local.x), 0.0), radius + dx))
+        } else {
+            Some((Vec2::new(0.0, sign(local.y)), radius + dy))
+        }
+    } else {
+        let closest = Vec2::new(
+            local.x.max(-half_extents.x).min(half_extents.x),
+            local.y.max(-half_extents.y).min(half_extents.y),
+        );
+        let delta = local - closest;
+        let distance = delta.len();
+        if distance < radius {
+            Some((delta.scale(1.0 / distance), radius - distance))
+        } else {
+            None
+        }
+    }
+}
+
+// Returns normal that points from first shape to second and penetration depth.
+fn collide(a: Shape2D, a_position: Vec2, b: Shape2D, b_position: Vec2) -> Option<(Vec2, f32)> {
+    match (a, b) {
+        (Shape2D::Circle { radius: a_radius }, Shape2D::Circle { radius: b_radius }) => {
+            let delta = b_position - a_position;
+            let distance = delta.len();
+            let radius = a_radius + b_radius;
+            if distance >= radius {
+                None
+            } else if distance > std::f32::EPSILON {
+                Some((delta.scale(1.0 / distance), radius - distance))
+            } else {
+                Some((Vec2::new(0.0, 1.0), radius))
+            }
+        }
+        (Shape2D::Rectangle { half_extents }, Shape2D::Circle { radius }) => {
+            rectangle_circle(a_position, half_extents, b_position, radius)
+        }
+        (Shape2D::Circle { radius }, Shape2D::Rectangle { half_extents }) => {
+            rectangle_circle(b_position, half_extents, a_position, radius)
+                .map(|(normal, depth)| (normal.scale(-1.0), depth))
+        }
+        (
+            Shape2D::Rectangle {
+                half_extents: a_half_extents,
+            },
+            Shape2D::Rectangle {
+                half_extents: b_half_extents,
+            },
+        ) => {
+            let delta = b_position - a_position;
+            let overlap_x = a_half_extents.x + b_half_extents.x - delta.x.abs();
+            let overlap_y = a_half_extents.y + b_half_extents.y - delta.y.abs();
+            if overlap_x <= 0.0 || overlap_y <= 0.0 {
+                None
+            } else if overlap_x < overlap_y {
+                Some((Vec2::new(sign(delta.x), 0.0), overlap_x))
+            } else {
+                Some((Vec2::new(0.0, sign(delta.y)), overlap_y))
+            }
+        }
+    }
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct Physics2D {
+    /// Global gravity, it is applied to every dynamic body scaled by its gravity scale.
+    pub gravity: Vec2,
+    bodies: Vec<RigidBody2D>,
+    contacts: Vec<Contact2D>,
+    positions: Vec<Vec2>,
+}
+
+impl Default for Physics2D {
+    fn default() -> Self {
+        Self {
+            gravity: Vec2::new(0.0, -9.81),
+            bodies: Default::default(),
+            contacts: Default::default(),
+            positions: Default::default(),
+        }
+    }
+}
+
+impl Physics2D {
+    /// Adds new body, previous body of the same node is replaced.
+    pub fn add_body(&mut self, body: RigidBody2D) {
+        self.remove_of(body.node);
+        self.bodies.push(body);
+    }
+
+    /// Returns shared reference to body of given node, if any.
+    pub fn body_of(&self, node: Handle<Node>) -> Option<&RigidBody2D> {
+        self.bodies.iter().find(|body| body.node == node)
+    }
+
+    /// Returns mutable reference to body of given node, if any.
+    pub fn body_of_mut(&mut self, node: Handle<Node>) -> Option<&mut RigidBody2D> {
+        self.bodies.iter_mut().find(|body| body.node == node)
+    }
+
+    /// Returns slice with every body.
+    pub fn bodies(&self) -> &[RigidBody2D] {
+        &self.bodies
+    }
+
+    /// Removes body of given node.
+    pub fn remove_of(&mut self, node: Handle<Node>) {
+        self.bodies.retain(|body| body.node != node);
+    }
+
+    /// Removes every body.
+    pub fn clear(&mut self) {
+        self.bodies.clear();
+        self.contacts.clear();
+    }
+
+    /// Returns contacts that were found during last step, including contacts of sensors.
+    pub fn contacts(&self) -> &[Contact2D] {
+        &self.contacts
+    }
+
+    /// Performs single simulation step. There is no need to call it manually, scene
+    /// does it automatically.
+    pub fn step(&mut self, graph: &mut Graph, dt: f32) {
+        self.bodies.retain(|body| graph.is_valid_handle(body.node));
+
+        self.positions.clear();
+        for body in self.bodies.iter_mut() {
+            let position = graph[body.node].local_transform().position();
+            let mut position = Vec2::new(position.x, position.y);
+            if body.kind == BodyKind2D::Dynamic {
+                body.velocity += self.gravity.scale(body.gravity_scale * dt);
+            }
+            if body.kind != BodyKind2D::Static {
+                position += body.velocity.scale(dt);
+            }
+            self.positions.push(position);
+        }
+
+        self.contacts.clear();
+        for i in 0..self.bodies.len() {
+            for j in (i + 1)..self.bodies.len() {
+                let a = self.bodies[i];
+                let b = self.bodies[j];
+                if !a.can_collide_with(&b) {
+                    continue;
+                }
+
+                let (normal, depth) =
+                    match collide(a.shape, self.positions[i], b.shape, self.positions[j]) {
+                        Some(contact) => contact,
+                        None => continue,
+                    };
+
+                self.contacts.push(Contact2D {
+                    a: a.node,
+                    b: b.node,
+                    normal,
+                    depth,
+                });
+
+                let a_inv_mass = a.inv_mass();
+                let b_inv_mass = b.inv_mass();
+                let total_inv_mass = a_inv_mass + b_inv_mass;
+                if a.sensor || b.sensor || total_inv_mass <= 0.0 {
+                    continue;
+                }
+
+                // Push bodies apart proportionally to their inverse masses.
+                let correction = normal.scale(depth / total_inv_mass);
+                self.positions[i] -= correction.scale(a_inv_mass);
+                self.positions[j] += correction.scale(b_inv_mass);
+
+                // Remove approaching velocity.
+                let relative_velocity = b.velocity - a.velocity;
+                let approach = relative_velocity.x * normal.x + relative_velocity.y * normal.y;
+                if approach < 0.0 {
+                    let restitution = a.restitution.min(b.restitution);
+                    let impulse = normal.scale(-(1.0 + restitution) * approach / total_inv_mass);
+                    self.bodies[i].velocity -= impulse.scale(a_inv_mass);
+                    self.bodies[j].velocity += impulse.scale(b_inv_mass);
+                }
+            }
+        }
+
+        for (body, position) in self.bodies.iter().zip(self.positions.iter()) {
+            if body.kind != BodyKind2D::Static {
+                let transform = graph[body.node].local_transform_mut();
+                let mut new_position = transform.position();
+                new_position.x = position.x;
+                new_position.y = position.y;
+                transform.set_position(new_position);
+            }
+        }
+    }
+
+    /// Remaps nodes of bodies using given old-to-new mapping, bodies of nodes that are not
+    /// in the mapping are removed.
+    pub fn remap(&mut self, old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {
+        self.bodies.retain(|body| old_new_mapping.contains_key(&body.node));
+        for body in self.bodies.iter_mut() {
+            body.node = old_new_mapping[&body.node];
+        }
+        self.contacts.clear();
+    }
+}
+
+impl Visit for Physics2D {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.gravity.visit("Gravity", visitor)?;
+        self.bodies.visit("Bodies", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::{vec2::Vec2, vec3::Vec3},
+        scene::{
+            base::BaseBuilder,
+            dim2::physics::{BodyKind2D, Physics2D, RigidBody2D, Shape2D},
+            graph::Graph,
+            transform::TransformBuilder,
+        },
+    };
+
+    #[test]
+    fn physics_2d_test() {
+        let mut graph = Graph::new();
+        let ground = graph.add_node(
+            BaseBuilder::new()
+                .with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(Vec3::new(0.0, 0.0, 5.0))
+                        .build(),
+                )
+                .build_node(),
+        );
+        let ball = graph.add_node(
+            BaseBuilder::new()
+                .with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(Vec3::new(0.0, 3.0, 5.0))
+                        .build(),
+                )
+                .build_node(),
+        );
+
+        let mut physics = Physics2D::default();
+        physics.add_body(
+            RigidBody2D::new(
+                ground,
+                Shape2D::Rectangle {
+                    half_extents: Vec2::new(10.0, 1.0),
+                },
+            )
+            .with_kind(BodyKind2D::Static),
+        );
+        physics.add_body(RigidBody2D::new(ball, Shape2D::Circle { radius: 0.5 }));
+
+        for _ in 0..300 {
+            physics.step(&mut graph, 1.0 / 60.0);
+        }
+
+        // Ball must rest on top of ground, depth must be untouched.
+        let position = graph[ball].local_transform().position();
+        assert!((position.y - 1.5).abs() < 0.05);
+        assert_eq!(position.z, 5.0);
+        assert_eq!(graph[ground].local_transform().position().y, 0.0);
+        assert_eq!(physics.contacts().len(), 1);
+
+        graph.remove_node(ball);
+        physics.step(&mut graph, 1.0 / 60.0);
+        assert_eq!(physics.bodies().len(), 1);
+    }
+}
@@ -0,0 +1,147 @@
+//! Contains per-scene environment settings.
+//!
+//! Environment is a set of global properties of a scene - ambient lighting, fog, sky and
+//! wind. They're stored in scene and saved with it, so every scene can have its own look,
+//! and renderer and particle systems take them from scene being processed.
+//!
+//! Sky can be either an equirectangular texture (`skybox`) or a cube map (`sky_cube_map`), see
+//! `cube_map` module docs. Sky cube map is also used as source of distant lighting when light
+//! probes are baked.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{color::Color, math::vec3::Vec3},
+//!     scene::{environment::Fog, Scene},
+//! };
+//!
+//! fn make_foggy(scene: &mut Scene) {
+//!     scene.environment.ambient_color = Color::opaque(120, 120, 140);
+//!     scene.environment.fog = Some(Fog {
+//!         color: Color::opaque(160, 160, 170),
+//!         start: 5.0,
+//!         end: 60.0,
+//!     });
+//!     scene.environment.wind = Vec3::new(1.5, 0.0, 0.5);
+//! }
+//! ```
+
+use crate::{
+    core::{
+        color::Color,
+        math::{vec3::Vec3, vec4::Vec4},
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    engine::resource_manager::ResourceManager,
+    resource::{cube_map::CubeMap, texture::Texture},
+};
+use std::sync::{Arc, Mutex};
+
+/// Linear distance fog.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Fog {
+    /// Color of fog, alpha defines maximum density of fog.
+    pub color: Color,
+    /// Distance from camera at which fog starts.
+    pub start: f32,
+    /// Distance from camera at which fog reaches its maximum density.
+    pub end: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            color: Color::opaque(128, 128, 128),
+            start: 10.0,
+            end: 100.0,
+        }
+    }
+}
+
+impl Visit for Fog {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.color.visit("Color", visitor)?;
+        self.start.visit("Start", visitor)?;
+        self.end.visit("End", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct SceneEnvironment {
+    /// Color of ambient light, it is used to imitate indirect lighting.
+    pub ambient_color: Color,
+    /// Multiplier of ambient color, can be greater than one for HDR rendering.
+    pub ambient_intensity: f32,
+    /// Distance fog, None means no fog.
+    pub fog: Option<Fog>,
+    /// Equirectangular (panoramic) texture of sky, it is drawn on every pixel which is not
+    /// covered by geometry. None means that background is defined by clear mode of camera.
+    pub skybox: Option<Arc<Mutex<Texture>>>,
+    /// Cube map of sky, it is used instead of `skybox` if both are set.
+    pub sky_cube_map: Option<Arc<Mutex<CubeMap>>>,
+    /// Global wind vector, it is applied to every particle system as acceleration scaled
+    /// by wind influence of particle system.
+    pub wind: Vec3,
+}
+
+impl Default for SceneEnvironment {
+    fn default() -> Self {
+        Self {
+            ambient_color: Color::opaque(100, 100, 100),
+            ambient_intensity: 1.0,
+            fog: None,
+            skybox: None,
+            sky_cube_map: None,
+            wind: Vec3::ZERO,
+        }
+    }
+}
+
+impl SceneEnvironment {
+    /// Returns ambient color multiplied by intensity as a vector with components in [0; 1]
+    /// range, components could be greater than one if intensity is greater than one.
+    pub fn ambient_light(&self) -> Vec4 {
+        let k = self.ambient_intensity / 255.0;
+        Vec4::new(
+            self.ambient_color.r as f32 * k,
+            self.ambient_color.g as f32 * k,
+            self.ambient_color.b as f32 * k,
+            self.ambient_color.a as f32 / 255.0,
+        )
+    }
+
+    /// Replaces sky cube map, which was loaded from a save file and has no faces, with the one
+    /// loaded by resource manager.
+    pub(in crate) fn restore_resources(&mut self, resource_manager: &mut ResourceManager) {
+        let source = self
+            .sky_cube_map
+            .as_ref()
+            .map(|cube_map| cube_map.lock().unwrap().source().clone());
+        if let Some(source) = source {
+            if let Some(cube_map) = resource_manager.request_cube_map(source) {
+                self.sky_cube_map = Some(cube_map);
+            }
+        }
+    }
+}
+
+impl Visit for SceneEnvironment {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.ambient_color.visit("AmbientColor", visitor)?;
+        self.ambient_intensity.visit("AmbientIntensity", visitor)?;
+        self.fog.visit("Fog", visitor)?;
+        self.skybox.visit("Skybox", visitor)?;
+        let _ = self.sky_cube_map.visit("SkyCubeMap", visitor);
+        self.wind.visit("Wind", visitor)?;
+
+        visitor.leave_region()
+    }
+}
@@ -0,0 +1,305 @@
+//! Contains partial serialization of scenes.
+//!
+//! Scene fragment is a copy of a sub-graph of a scene together with everything that belongs to
+//! nodes of the sub-graph: animation tracks, physical bodies bound to nodes (both 3D and 2D),
+//! audio environment volumes, zones and portals, and lightmap entries. Fragment can be pasted
+//! into any scene, or saved to a file and loaded later. This allows tools to copy and paste
+//! parts of levels between scenes, and games to build levels from modular chunks.
+//!
+//! # External references
+//!
+//! Nodes of a sub-graph can reference nodes outside of it, for example clothes of a character
+//! can be skinned meshes which use bones of character's skeleton. Fragment remembers names of
+//! such bones, and `ExternalReferences` passed to `SceneFragment::paste` defines what to do with
+//! them: they're either remapped to nodes with the same names in destination scene, or nulled.
+//! Nulled bone is replaced with its mesh, so surface stays valid and can be rendered. Bones
+//! which are not found by name are nulled too. Animation tracks, lights of lightmap entries and
+//! portals that use nodes outside of the sub-graph are dropped.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::pool::Handle,
+//!     scene::{
+//!         fragment::{ExternalReferences, SceneFragment},
+//!         node::Node,
+//!         Scene,
+//!     },
+//! };
+//!
+//! fn copy_paste(from: &Scene, node: Handle<Node>, to: &mut Scene) -> Handle<Node> {
+//!     let fragment = SceneFragment::from_scene(from, node);
+//!     let root = fragment.root();
+//!     let old_new_map = fragment.paste(to, Handle::NONE, ExternalReferences::RemapByName);
+//!     old_new_map[&root]
+//! }
+//! ```
+
+use crate::{
+    core::{
+        pool::Handle,
+        visitor::{Visit, VisitError, VisitResult, Visitor},
+    },
+    engine::resource_manager::ResourceManager,
+    scene::{node::Node, Scene},
+    utils::lightmap::Lightmap,
+    Visit,
+};
+use std::{collections::HashMap, path::Path};
+
+/// Defines what to do with handles of pasted nodes that point outside of fragment, see module
+/// docs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExternalReferences {
+    /// Handles are replaced with handles of nodes with the same name in destination scene.
+    RemapByName,
+    /// Handles are nulled.
+    Null,
+}
+
+/// Bone of a surface which is not part of fragment.
+#[derive(Clone, Debug, Default, Visit)]
+struct ExternalBone {
+    mesh: Handle<Node>,
+    surface: u32,
+    bone: u32,
+    name: String,
+}
+
+/// See module docs.
+#[derive(Default, Visit)]
+pub struct SceneFragment {
+    scene: Scene,
+    external_bones: Vec<ExternalBone>,
+}
+
+impl SceneFragment {
+    /// Copies sub-graph starting from given node with everything that belongs to it.
+    pub fn from_scene(scene: &Scene, root: Handle<Node>) -> Self {
+        let (mut fragment, old_new_map) = scene.copy_subgraph(root);
+
+        let mut external_bones = Vec::new();
+        for (&old, &new) in old_new_map.iter() {
+            if let Node::Mesh(mesh) = &scene.graph[old] {
+                for (surface_index, surface) in mesh.surfaces().iter().enumerate() {
+                    for (bone_index, bone) in surface.bones().iter().enumerate() {
+                        if old_new_map.contains_key(bone) {
+                            continue;
+                        }
+                        let name = if scene.graph.is_valid_handle(*bone) {
+                            scene.graph[*bone].name().to_owned()
+                        } else {
+                            String::new()
+                        };
+                        external_bones.push(ExternalBone {
+                            mesh: new,
+                            surface: surface_index as u32,
+                            bone: bone_index as u32,
+                            name,
+                        });
+                    }
+                }
+            }
+        }
+        for external_bone in external_bones.iter() {
+            set_bone(&mut fragment, external_bone, external_bone.mesh);
+        }
+
+        for (&old, &new) in old_new_map.iter() {
+            let body = scene.physics_binder.body_of(old);
+            if body.is_some() && scene.physics.is_valid_body_handle(body) {
+                let body = fragment.physics.add_body(scene.physics.borrow_body(body).clone());
+                fragment.physics_binder.bind(new, body);
+            }
+        }
+        let mut physics2d = scene.physics2d.clone();
+        physics2d.remap(&old_new_map);
+        for body in physics2d.bodies() {
+            fragment.physics2d.add_body(*body);
+        }
+
+        fragment.audio_environment = scene.audio_environment.clone();
+        fragment.audio_environment.remap(&old_new_map);
+        fragment.portals = scene.portals.clone();
+        fragment.portals.remap(&old_new_map);
+
+        if let Some(lightmap) = scene.lightmap.as_ref() {
+            let mut map = HashMap::new();
+            for (node, entries) in lightmap.map.iter() {
+                if let Some(&new_node) = old_new_map.get(node) {
+                    let mut entries = entries.clone();
+                    for entry in entries.iter_mut() {
+                        entry.lights.retain(|light| old_new_map.contains_key(light));
+                        for light in entry.lights.iter_mut() {
+                            *light = old_new_map[light];
+                        }
+                    }
+                    map.insert(new_node, entries);
+                }
+            }
+            if !map.is_empty() {
+                fragment.lightmap = Some(Lightmap { map });
+            }
+        }
+
+        Self {
+            scene: fragment,
+            external_bones,
+        }
+    }
+
+    /// Loads fragment from file that was written by `save`.
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        resource_manager: &mut ResourceManager,
+    ) -> Result<Self, VisitError> {
+        let mut fragment = Self::default();
+        let mut visitor = resource_manager.vfs().load_visitor(path.as_ref())?;
+        fragment.visit("SceneFragment", &mut visitor)?;
+        fragment.scene.restore_resources(resource_manager);
+        fragment.scene.resolve();
+        Ok(fragment)
+    }
+
+    /// Writes fragment to file with given path.
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> VisitResult {
+        let mut visitor = Visitor::new();
+        self.visit("SceneFragment", &mut visitor)?;
+        visitor.save_binary(path.as_ref())
+    }
+
+    /// Returns scene which contains nodes of the fragment, copy of root of sub-graph is
+    /// attached to root of the scene.
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    /// Returns handle of copy of root of sub-graph in scene of the fragment.
+    pub fn root(&self) -> Handle<Node> {
+        let graph = &self.scene.graph;
+        graph[graph.get_root()]
+            .children()
+            .first()
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Moves contents of the fragment into given scene and attaches root of the fragment to
+    /// given parent, or to root of graph if parent is `Handle::NONE`. Returns old-to-new node
+    /// mapping, handles of nodes of the fragment scene must be remapped using it.
+    pub fn paste(
+        self,
+        scene: &mut Scene,
+        parent: Handle<Node>,
+        external_references: ExternalReferences,
+    ) -> HashMap<Handle<Node>, Handle<Node>> {
+        let root = self.root();
+        let old_new_map = scene.merge(self.scene);
+
+        if parent.is_some() {
+            if let Some(&new_root) = old_new_map.get(&root) {
+                scene.graph.link_nodes(new_root, parent);
+            }
+        }
+
+        if external_references == ExternalReferences::RemapByName {
+            for external_bone in self.external_bones.iter() {
+                if external_bone.name.is_empty() {
+                    continue;
+                }
+                let bone = scene.graph.find_by_name_from_root(&external_bone.name);
+                if let Some(&mesh) = old_new_map.get(&external_bone.mesh) {
+                    if bone.is_some() {
+                        let mut external_bone = external_bone.clone();
+                        external_bone.mesh = mesh;
+                        set_bone(scene, &external_bone, bone);
+                    }
+                }
+            }
+        }
+
+        old_new_map
+    }
+}
+
+fn set_bone(scene: &mut Scene, external_bone: &ExternalBone, bone: Handle<Node>) {
+    if let Node::Mesh(mesh) = &mut scene.graph[external_bone.mesh] {
+        if let Some(surface) = mesh.surfaces_mut().get_mut(external_bone.surface as usize) {
+            if let Some(handle) = surface.bones.get_mut(external_bone.bone as usize) {
+                *handle = bone;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::{math::mat4::Mat4, pool::Handle},
+        renderer::surface::{Surface, SurfaceSharedData},
+        scene::{
+            base::BaseBuilder,
+            fragment::{ExternalReferences, SceneFragment},
+            mesh::MeshBuilder,
+            node::Node,
+            Scene,
+        },
+    };
+    use std::sync::{Arc, Mutex};
+
+    fn add(scene: &mut Scene, name: &str, parent: Handle<Node>) -> Handle<Node> {
+        let node = scene
+            .graph
+            .add_node(BaseBuilder::new().with_name(name).build_node());
+        if parent.is_some() {
+            scene.graph.link_nodes(node, parent);
+        }
+        node
+    }
+
+    fn pasted_bone(scene: &Scene) -> (Handle<Node>, Handle<Node>) {
+        let mesh = scene.graph.find_by_name_from_root("Shirt");
+        match &scene.graph[mesh] {
+            Node::Mesh(shirt) => (mesh, shirt.surfaces()[0].bones()[0]),
+            _ => panic!("Mesh must be pasted as mesh!"),
+        }
+    }
+
+    #[test]
+    fn paste_fragment_with_external_bones() {
+        let mut source = Scene::new();
+        let skeleton = add(&mut source, "Skeleton", Handle::NONE);
+        let bone = add(&mut source, "Spine", skeleton);
+        let clothes = add(&mut source, "Clothes", Handle::NONE);
+        let mut surface = Surface::new(Arc::new(Mutex::new(SurfaceSharedData::make_cube(
+            Mat4::IDENTITY,
+        ))));
+        surface.bones = vec![bone];
+        let mesh = source.graph.add_node(
+            MeshBuilder::new(BaseBuilder::new().with_name("Shirt"))
+                .with_surfaces(vec![surface])
+                .build_node(),
+        );
+        source.graph.link_nodes(mesh, clothes);
+
+        let mut remapped = Scene::new();
+        let character = add(&mut remapped, "Character", Handle::NONE);
+        let spine = add(&mut remapped, "Spine", character);
+        let fragment = SceneFragment::from_scene(&source, clothes);
+        let root = fragment.root();
+        let map = fragment.paste(&mut remapped, character, ExternalReferences::RemapByName);
+        assert_eq!(remapped.graph[map[&root]].parent(), character);
+        assert_eq!(pasted_bone(&remapped).1, spine);
+
+        let mut nulled = Scene::new();
+        add(&mut nulled, "Spine", Handle::NONE);
+        SceneFragment::from_scene(&source, clothes).paste(
+            &mut nulled,
+            Handle::NONE,
+            ExternalReferences::Null,
+        );
+        let (pasted_mesh, pasted_bone) = pasted_bone(&nulled);
+        assert_eq!(pasted_bone, pasted_mesh);
+    }
+}
@@ -21,6 +21,16 @@
 //! is global transform calculation - it allows you to produce complex movements
 //! just by linking nodes to each other. Good example of this is skeleton which
 //! is used in skinning (animating 3d model by set of bones).
+//!
+//! # Handles and saved games
+//!
+//! Graph is saved together with free slots and generations of its pool, so after loading
+//! every node has exactly the same handle as before saving, and handles of removed nodes
+//! stay invalid. This means that handles stored by game code (target of an enemy, for
+//! example) can be saved together with the scene and used after loading as is. Handles
+//! are changed only by operations which move nodes to other slots - `Graph::compact`,
+//! copying and merging of graphs and scenes - and all of them return old-to-new mapping
+//! which must be used to remap stored handles.
 
 use crate::{
     core::{
@@ -31,12 +41,26 @@ use crate::{
         },
         visitor::{Visit, VisitResult, Visitor},
     },
-    scene::node::Node,
-    utils::log::Log,
+    engine::determinism,
+    profile_scope,
+    scene::{
+        base::InheritableProperty,
+        bounds_tree::GraphBoundsTree,
+        camera::Camera,
+        interpolation::TransformInterpolation,
+        journal::{GraphChange, GraphJournal},
+        node::Node,
+        typed_handle::{NodeVariant, TypedHandle},
+    },
+    utils::{
+        jobs::JobSystem,
+        log::{Log, LogCategory},
+    },
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     ops::{Index, IndexMut},
+    sync::mpsc::{self, Receiver, Sender},
 };
 
 /// See module docs.
@@ -45,6 +69,15 @@ pub struct Graph {
     root: Handle<Node>,
     pool: Pool<Node>,
     stack: Vec<Handle<Node>>,
+    journal: Option<GraphJournal>,
+    update_stamp: u64,
+    changed_transforms: Vec<Handle<Node>>,
+    delete_queue: Vec<Handle<Node>>,
+    removal_events: Option<VecDeque<RemovalEvent>>,
+    parallel_update: ParallelUpdateBuffers,
+    subscribers: Vec<Sender<GraphEvent>>,
+    bounds_tree: GraphBoundsTree,
+    interpolation: TransformInterpolation,
 }
 
 impl Default for Graph {
@@ -53,10 +86,235 @@ impl Default for Graph {
             root: Handle::NONE,
             pool: Pool::new(),
             stack: Vec::new(),
+            journal: None,
+            update_stamp: 0,
+            changed_transforms: Vec::new(),
+            delete_queue: Vec::new(),
+            removal_events: None,
+            parallel_update: Default::default(),
+            subscribers: Default::default(),
+            bounds_tree: Default::default(),
+            interpolation: Default::default(),
+        }
+    }
+}
+
+/// Decision of filter of `Graph::copy_node_with_filter` about a node.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CopyFilterResult {
+    /// Node will be copied.
+    Copy,
+    /// Node won't be copied, but its descendants will be processed as usual and will be
+    /// attached to closest copied ancestor.
+    SkipNode,
+    /// Neither node nor its descendants will be copied.
+    SkipHierarchy,
+}
+
+/// Minimal amount of nodes in graph at which update is done in parallel, smaller graphs
+/// are updated faster on a single thread.
+const PARALLEL_UPDATE_THRESHOLD: usize = 1024;
+
+/// Maximal depth of hierarchy which is processed serially before splitting graph into
+/// subtrees for parallel update.
+const MAX_TRUNK_DEPTH: usize = 4;
+
+/// Properties of parent node that affect its children.
+#[derive(Copy, Clone, Debug)]
+struct HierarchicalData {
+    global_transform: Mat4,
+    visibility: bool,
+    time_scale: f32,
+    enabled: bool,
+    cast_shadows: bool,
+    layers: u32,
+}
+
+impl HierarchicalData {
+    /// Data of "parent" of root node.
+    const ROOT: Self = Self {
+        global_transform: Mat4::IDENTITY,
+        visibility: true,
+        time_scale: 1.0,
+        enabled: true,
+        cast_shadows: true,
+        layers: std::u32::MAX,
+    };
+
+    fn of(node: &Node) -> Self {
+        Self {
+            global_transform: node.global_transform(),
+            visibility: node.global_visibility(),
+            time_scale: node.global_time_scale(),
+            enabled: node.is_globally_enabled(),
+            cast_shadows: node.global_cast_shadows(),
+            layers: node.global_layers(),
+        }
+    }
+
+    /// Calculates global properties of given child node. Returns true if global transform
+    /// of the node has changed.
+    fn apply(&self, node: &mut Node, stamp: u64) -> bool {
+        let global_transform = self.global_transform * node.local_transform().matrix();
+        // Nodes that were never updated are treated as changed, they could be just added
+        // or loaded.
+        let changed = node.transform_stamp == 0 || global_transform.f != node.global_transform.f;
+        if changed {
+            node.transform_stamp = stamp;
+        }
+        node.global_transform = global_transform;
+        node.global_enabled = self.enabled && node.is_node_enabled();
+        let visibility = if node.inherits(InheritableProperty::Visibility) {
+            self.visibility && node.visibility()
+        } else {
+            node.visibility()
+        };
+        // Disabled nodes are not rendered, so they're treated as invisible.
+        node.global_visibility = visibility && node.global_enabled;
+        node.global_cast_shadows = if node.inherits(InheritableProperty::CastShadows) {
+            self.cast_shadows && node.cast_shadows()
+        } else {
+            node.cast_shadows()
+        };
+        node.global_layers = if node.inherits(InheritableProperty::Layers) {
+            self.layers & node.layers()
+        } else {
+            node.layers()
+        };
+        node.global_time_scale = self.time_scale * node.time_scale();
+        changed
+    }
+}
+
+impl Default for HierarchicalData {
+    fn default() -> Self {
+        Self::ROOT
+    }
+}
+
+/// Buffers of parallel update, they're kept between updates to avoid allocations.
+#[derive(Debug, Default)]
+struct ParallelUpdateBuffers {
+    level: Vec<Handle<Node>>,
+    next_level: Vec<Handle<Node>>,
+    /// Position of each node (by its index in pool) in depth-first order of subtrees,
+    /// `usize::MAX` for nodes of trunk.
+    order: Vec<usize>,
+    subtrees: Vec<Subtree>,
+}
+
+/// Part of graph which is updated independently of other parts.
+#[derive(Debug, Default)]
+struct Subtree {
+    /// Data of parent of subtree root, it is a node of trunk.
+    parent: HierarchicalData,
+    /// Amount of nodes in subtree.
+    len: usize,
+    changed: Vec<Handle<Node>>,
+    /// Ancestors of current node within subtree.
+    parents: Vec<(Handle<Node>, HierarchicalData)>,
+}
+
+impl Subtree {
+    /// Updates given nodes of the subtree, nodes must be in depth-first order.
+    fn update(&mut self, nodes: &mut [Option<(Handle<Node>, &mut Node)>], stamp: u64) {
+        self.changed.clear();
+        self.parents.clear();
+        for (handle, node) in nodes.iter_mut().flatten() {
+            // Parent of a node is either previous node or one of its ancestors, so nodes
+            // that aren't ancestors of current node can be dropped. Subtree root drops
+            // every node, its parent is in trunk.
+            while let Some(&(ancestor, _)) = self.parents.last() {
+                if ancestor == node.parent() {
+                    break;
+                }
+                self.parents.pop();
+            }
+            let parent = self.parents.last().map_or(self.parent, |&(_, data)| data);
+            if parent.apply(node, stamp) {
+                self.changed.push(*handle);
+            }
+            if !node.children().is_empty() {
+                self.parents.push((*handle, HierarchicalData::of(node)));
+            }
         }
     }
 }
 
+fn update_node(node: &mut Node, frame_size: Vec2, dt: f32) {
+    if !node.is_globally_enabled() {
+        return;
+    }
+
+    let scaled_dt = dt * node.global_time_scale();
+
+    if let Some(lifetime) = node.lifetime() {
+        node.set_lifetime(lifetime - scaled_dt);
+    }
+
+    node.update_components(scaled_dt);
+
+    match node {
+        Node::Camera(camera) => {
+            camera.update_effects(scaled_dt);
+            camera.calculate_matrices(frame_size)
+        }
+        Node::ParticleSystem(particle_system) => particle_system.update(scaled_dt),
+        Node::Sprite(sprite) => sprite.update(scaled_dt),
+        Node::Water(water) => water.update(scaled_dt),
+        Node::Custom(custom) => custom.update(scaled_dt),
+        _ => (),
+    }
+}
+
+/// Remaps target of follow effect of copied camera. Target that wasn't copied is kept as is.
+fn remap_camera_follow(camera: &mut Camera, old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {
+    if let Some(follow) = camera.effects_mut().follow_mut() {
+        if let Some(&target) = old_new_mapping.get(&follow.target) {
+            follow.target = target;
+        }
+    }
+}
+
+/// Reason of node removal, see `RemovalEvent`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// Lifetime of node or lifetime of one of its ancestors has expired.
+    Expired,
+    /// Node or one of its ancestors was removed using `remove_node` or `queue_delete`.
+    Removed,
+}
+
+/// Notification about removed node, see `Graph::poll_removal_event`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemovalEvent {
+    /// Handle of removed node, it is already invalid.
+    pub handle: Handle<Node>,
+    /// Name of removed node.
+    pub name: String,
+    /// Why node was removed.
+    pub reason: RemovalReason,
+}
+
+/// Structural change of a graph, see `Graph::subscribe`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GraphEvent {
+    /// Node was added to the graph.
+    Added(Handle<Node>),
+    /// Node was removed from the graph, handle is not valid anymore. Every descendant of
+    /// removed node produces its own event.
+    Removed(Handle<Node>),
+    /// Node was attached to other parent.
+    Reparented {
+        /// Handle of moved node.
+        node: Handle<Node>,
+        /// Handle of previous parent, it can be invalid if previous parent was removed.
+        old_parent: Handle<Node>,
+        /// Handle of new parent.
+        new_parent: Handle<Node>,
+    },
+}
+
 /// Sub-graph is a piece of graph that was extracted from a graph. It has ownership
 /// over its nodes. It is used to temporarily take ownership of a sub-graph. This could
 /// be used if you making a scene editor with a command stack - once you reverted a command,
@@ -83,6 +341,15 @@ impl Graph {
             stack: Vec::new(),
             root,
             pool,
+            journal: None,
+            update_stamp: 0,
+            changed_transforms: Vec::new(),
+            delete_queue: Vec::new(),
+            removal_events: None,
+            parallel_update: Default::default(),
+            subscribers: Default::default(),
+            bounds_tree: Default::default(),
+            interpolation: Default::default(),
         }
     }
 
@@ -93,8 +360,10 @@ impl Graph {
     pub fn add_node(&mut self, node: Node) -> Handle<Node> {
         let handle = self.pool.spawn(node);
         if self.root.is_some() {
-            self.link_nodes(handle, self.root);
+            self.link_nodes_internal(handle, self.root);
         }
+        self.record(GraphChange::NodeAdded(handle));
+        self.emit(GraphEvent::Added(handle));
         handle
     }
 
@@ -128,8 +397,16 @@ impl Graph {
     }
 
     /// Destroys node and its children recursively.
+    ///
+    /// Removing nodes while iterating over graph or inside of some gameplay logic which
+    /// still uses handles of nodes could be error-prone, use `queue_delete` to defer
+    /// removal to the end of next update.
     #[inline]
     pub fn remove_node(&mut self, node_handle: Handle<Node>) {
+        self.remove_node_internal(node_handle, RemovalReason::Removed);
+    }
+
+    fn remove_node_internal(&mut self, node_handle: Handle<Node>, reason: RemovalReason) {
         self.unlink_internal(node_handle);
 
         self.stack.clear();
@@ -138,10 +415,56 @@ impl Graph {
             for &child in self.pool[handle].children().iter() {
                 self.stack.push(child);
             }
+            if let Some(events) = self.removal_events.as_mut() {
+                events.push_back(RemovalEvent {
+                    handle,
+                    name: self.pool[handle].name().to_owned(),
+                    reason,
+                });
+            }
             self.pool.free(handle);
+            if let Some(journal) = self.journal.as_mut() {
+                journal.record(GraphChange::NodeRemoved(handle));
+            }
+            self.emit(GraphEvent::Removed(handle));
+        }
+    }
+
+    /// Queues node for removal, node with its descendants will be removed at the end of
+    /// next `update_nodes` call (after removal of expired nodes). This is safe way to
+    /// destroy nodes from gameplay code - handle stays valid until the end of frame, so
+    /// other systems can still access the node in current frame. It is fine to queue
+    /// same node multiple times or to queue descendants of queued node.
+    pub fn queue_delete(&mut self, node_handle: Handle<Node>) {
+        self.delete_queue.push(node_handle);
+    }
+
+    /// Returns true if node was queued for removal using `queue_delete`.
+    pub fn is_queued_for_deletion(&self, node_handle: Handle<Node>) -> bool {
+        self.delete_queue.contains(&node_handle)
+    }
+
+    /// Enables or disables removal events. Events are disabled by default, because they
+    /// are stored until polled and memory of queue would grow infinitely if nobody polls
+    /// them. Disabling drops every queued event.
+    pub fn set_removal_events_enabled(&mut self, enabled: bool) {
+        if enabled {
+            if self.removal_events.is_none() {
+                self.removal_events = Some(Default::default());
+            }
+        } else {
+            self.removal_events = None;
         }
     }
 
+    /// Pops next removal event from queue. Every removed node (including descendants of
+    /// removed node) produces an event, no matter if it was removed directly, by queue
+    /// or because its lifetime expired. Events must be enabled first using
+    /// `set_removal_events_enabled`.
+    pub fn poll_removal_event(&mut self) -> Option<RemovalEvent> {
+        self.removal_events.as_mut()?.pop_front()
+    }
+
     fn unlink_internal(&mut self, node_handle: Handle<Node>) {
         // Replace parent handle of child
         let parent_handle = std::mem::replace(&mut self.pool[node_handle].parent, Handle::NONE);
@@ -158,21 +481,169 @@ impl Graph {
     /// Links specified child with specified parent.
     #[inline]
     pub fn link_nodes(&mut self, child: Handle<Node>, parent: Handle<Node>) {
+        let old_parent = self.pool[child].parent;
+        self.link_nodes_internal(child, parent);
+        self.record(GraphChange::NodeMoved {
+            node: child,
+            parent,
+        });
+        if old_parent != parent {
+            self.emit(GraphEvent::Reparented {
+                node: child,
+                old_parent,
+                new_parent: parent,
+            });
+        }
+    }
+
+    fn link_nodes_internal(&mut self, child: Handle<Node>, parent: Handle<Node>) {
         self.unlink_internal(child);
         self.pool[child].parent = parent;
         self.pool[parent].children.push(child);
     }
 
+    fn record(&mut self, change: GraphChange) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.record(change);
+        }
+    }
+
+    /// Creates new subscription to structural changes of the graph (added, removed and
+    /// reparented nodes). Every subscriber receives its own copy of each event, events are
+    /// delivered immediately when a change happens, so they can be received from any other
+    /// thread. Subscription is cancelled when receiver is dropped.
+    ///
+    /// Unlike journal, subscriptions are cheap when nobody listens and do not track
+    /// properties of nodes. Handles in events are not remapped by `compact`, so events must
+    /// be received before compaction.
+    pub fn subscribe(&mut self) -> Receiver<GraphEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    fn emit(&mut self, event: GraphEvent) {
+        // Drop subscribers whose receivers were dropped.
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event).is_ok());
+    }
+
+    /// Enables or disables change journal. Journal is disabled by default, because it
+    /// has some overhead. Disabling journal drops every recorded change. See `journal`
+    /// module docs for more info.
+    pub fn set_journal_enabled(&mut self, enabled: bool) {
+        if enabled {
+            if self.journal.is_none() {
+                self.journal = Some(Default::default());
+            }
+        } else {
+            self.journal = None;
+        }
+    }
+
+    /// Returns change journal if it is enabled.
+    pub fn journal(&self) -> Option<&GraphJournal> {
+        self.journal.as_ref()
+    }
+
+    /// Returns change journal if it is enabled.
+    pub fn journal_mut(&mut self) -> Option<&mut GraphJournal> {
+        self.journal.as_mut()
+    }
+
     /// Unlinks specified node from its parent and attaches it to root graph node.
     #[inline]
     pub fn unlink_node(&mut self, node_handle: Handle<Node>) {
-        self.unlink_internal(node_handle);
         self.link_nodes(node_handle, self.root);
         self.pool[node_handle]
             .local_transform_mut()
             .set_position(Vec3::ZERO);
     }
 
+    /// Attaches node to a bone of model instance, so node will follow the bone when model
+    /// is animated. This is typical way to put weapons in hands of characters, hats on their
+    /// heads and so on. Bone is searched by name in hierarchy of given model instance. Local
+    /// position and rotation of node are reset, so it will be placed exactly at the bone, use
+    /// `attach_to_bone_with_offset` to set an offset. Returns false if there is no such bone,
+    /// node is left untouched in this case.
+    ///
+    /// Node remembers name of the bone, so it can be moved to the same bone of other model
+    /// instance (for example when model is re-instantiated) using `reattach_sockets`. Since
+    /// node is linked to the bone, retargeted animations move it as well.
+    pub fn attach_to_bone(&mut self, child: Handle<Node>, model: Handle<Node>, bone: &str) -> bool {
+        self.attach_to_bone_with_offset(child, model, bone, Vec3::ZERO, Quat::IDENTITY)
+    }
+
+    /// Same as `attach_to_bone`, but also sets position and rotation of node relative to
+    /// the bone.
+    pub fn attach_to_bone_with_offset(
+        &mut self,
+        child: Handle<Node>,
+        model: Handle<Node>,
+        bone: &str,
+        position: Vec3,
+        rotation: Quat,
+    ) -> bool {
+        let bone_handle = self.find_by_name(model, bone);
+        if bone_handle.is_none() {
+            return false;
+        }
+        self.link_nodes(child, bone_handle);
+        let node = &mut self.pool[child];
+        node.socket = Some(bone.to_owned());
+        node.local_transform_mut()
+            .set_position(position)
+            .set_rotation(rotation);
+        true
+    }
+
+    /// Detaches node from a bone and attaches it to root of graph.
+    pub fn detach_from_bone(&mut self, child: Handle<Node>) {
+        self.pool[child].socket = None;
+        self.unlink_node(child);
+    }
+
+    /// Moves every node attached to bones of `old_model` instance to bones with same names
+    /// of `new_model` instance. Offsets of nodes are preserved. Must be called before old
+    /// instance is removed, otherwise attached nodes will be removed together with it.
+    /// Nodes for which there is no bone in new instance are detached. Returns amount of
+    /// reattached nodes.
+    pub fn reattach_sockets(&mut self, old_model: Handle<Node>, new_model: Handle<Node>) -> usize {
+        let mut sockets = Vec::new();
+        let mut stack = vec![old_model];
+        while let Some(handle) = stack.pop() {
+            let node = &self.pool[handle];
+            for &child in node.children() {
+                match self.pool[child].socket.as_ref() {
+                    // Descendants of attached node are moved with it. Sockets of nodes that
+                    // were relinked manually are stale and ignored.
+                    Some(bone) if bone == node.name() => sockets.push((child, bone.clone())),
+                    _ => stack.push(child),
+                }
+            }
+        }
+
+        let mut count = 0;
+        for (child, bone) in sockets {
+            let bone_handle = self.find_by_name(new_model, &bone);
+            if bone_handle.is_some() {
+                self.link_nodes(child, bone_handle);
+                count += 1;
+            } else {
+                Log::warn(
+                    LogCategory::Scene,
+                    format!(
+                        "Unable to reattach {} to bone {}, there is no such bone in new instance!",
+                        self.pool[child].name(),
+                        bone
+                    ),
+                );
+                self.detach_from_bone(child);
+            }
+        }
+        count
+    }
+
     /// Tries to find a copy of `node_handle` in hierarchy tree starting from `root_handle`.
     pub fn find_copy_of(
         &self,
@@ -219,6 +690,78 @@ impl Graph {
         self.find_by_name(self.root, name)
     }
 
+    /// Searches node by path of names separated by `/`, starting from children of given
+    /// node. For example "Arm/Hand/WeaponSocket" will find node with name "WeaponSocket"
+    /// which is a child of "Hand" which is a child of "Arm" which is a child of `from`.
+    /// If there are multiple children with same name, first one is taken. Empty path
+    /// returns `from`. If nothing was found, `Handle::NONE` is returned.
+    pub fn find_by_path_from(&self, from: Handle<Node>, path: &str) -> Handle<Node> {
+        let mut current = from;
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            match self.pool[current]
+                .children()
+                .iter()
+                .find(|&&child| self.pool[child].name() == name)
+            {
+                Some(&child) => current = child,
+                None => return Handle::NONE,
+            }
+        }
+        current
+    }
+
+    /// Searches node by path of names separated by `/` starting from root of graph, root
+    /// itself must not be included in path. See `find_by_path_from` for more info.
+    pub fn find_by_path(&self, path: &str) -> Handle<Node> {
+        self.find_by_path_from(self.root, path)
+    }
+
+    /// Returns path of names of node and its ancestors separated by `/`, which can be
+    /// used in `find_by_path`. Root of graph is not included in path.
+    pub fn node_path(&self, node: Handle<Node>) -> String {
+        let mut names = Vec::new();
+        let mut current = node;
+        while current.is_some() && current != self.root {
+            let node = &self.pool[current];
+            names.push(node.name());
+            current = node.parent();
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    /// Searches node with specified tag starting from specified node. If nothing was found,
+    /// [`Handle::NONE`] is returned.
+    pub fn find_by_tag(&self, root_node: Handle<Node>, tag: &str) -> Handle<Node> {
+        let root = &self.pool[root_node];
+        if root.has_tag(tag) {
+            root_node
+        } else {
+            for child in root.children() {
+                let child_handle = self.find_by_tag(*child, tag);
+                if child_handle.is_some() {
+                    return child_handle;
+                }
+            }
+            Handle::NONE
+        }
+    }
+
+    /// Searches node with specified tag starting from root. If nothing was found,
+    /// `Handle::NONE` is returned.
+    pub fn find_by_tag_from_root(&self, tag: &str) -> Handle<Node> {
+        self.find_by_tag(self.root, tag)
+    }
+
+    /// Returns iterator over handles of every node with given tag. Order of nodes is not
+    /// defined.
+    pub fn nodes_with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = Handle<Node>> + 'a {
+        self.pool
+            .pair_iter()
+            .filter(move |(_, node)| node.has_tag(tag))
+            .map(|(handle, _)| handle)
+    }
+
     /// Creates deep copy of node with all children. This is relatively heavy operation!
     /// In case if any error happened it returns `Handle::NONE`. This method can be used
     /// to create exact copy of given node hierarchy. For example you can prepare rocket
@@ -253,16 +796,20 @@ impl Graph {
         let mut old_new_mapping = HashMap::new();
         let root_handle = self.copy_node_raw(node_handle, dest_graph, &mut old_new_mapping, filter);
 
-        // Iterate over instantiated nodes and remap bones handles.
+        // Iterate over instantiated nodes and remap bones handles and targets of cameras.
         for (_, &new_node_handle) in old_new_mapping.iter() {
-            if let Node::Mesh(mesh) = &mut dest_graph.pool[new_node_handle] {
-                for surface in mesh.surfaces_mut() {
-                    for bone_handle in surface.bones.iter_mut() {
-                        if let Some(entry) = old_new_mapping.get(bone_handle) {
-                            *bone_handle = *entry;
+            match &mut dest_graph.pool[new_node_handle] {
+                Node::Mesh(mesh) => {
+                    for surface in mesh.surfaces_mut() {
+                        for bone_handle in surface.bones.iter_mut() {
+                            if let Some(entry) = old_new_mapping.get(bone_handle) {
+                                *bone_handle = *entry;
+                            }
                         }
                     }
                 }
+                Node::Camera(camera) => remap_camera_follow(camera, &old_new_mapping),
+                _ => (),
             }
         }
 
@@ -296,6 +843,158 @@ impl Graph {
         dest_copy_handle
     }
 
+    /// Creates deep copy of node with all children, just like `copy_node`, but filter can
+    /// skip single nodes without losing their descendants, this allows to strip certain
+    /// kinds of nodes (lights, particle systems, etc.) from a copy. Descendants of skipped
+    /// node are attached to closest copied ancestor. Filter is *not* applied to given node,
+    /// it is always copied.
+    ///
+    /// # Handle remapping
+    ///
+    /// Targets of camera follow effects are remapped to their copies if they were copied.
+    /// Bones of every copied surface are remapped to their copies. If a bone was skipped,
+    /// it is remapped to closest copied ancestor of the bone. If a bone is not in copied
+    /// hierarchy at all, it is searched by name in destination graph, and if there is no
+    /// such node, surface is attached to its mesh. So copied skinned meshes are always
+    /// valid and can be rendered, even if their skeleton was not copied.
+    ///
+    /// Returns tuple where first element is handle to copy of node, and second element -
+    /// old-to-new hash map. Skipped nodes are not in the map.
+    pub fn copy_node_with_filter<F>(
+        &self,
+        node_handle: Handle<Node>,
+        dest_graph: &mut Graph,
+        filter: &mut F,
+    ) -> (Handle<Node>, HashMap<Handle<Node>, Handle<Node>>)
+    where
+        F: FnMut(Handle<Node>, &Node) -> CopyFilterResult,
+    {
+        let mut old_new_mapping = HashMap::new();
+        let mut skipped = HashMap::new();
+        let root_handle = self.copy_node_with_filter_raw(
+            node_handle,
+            Handle::NONE,
+            dest_graph,
+            &mut old_new_mapping,
+            &mut skipped,
+            filter,
+        );
+
+        for &new_node_handle in old_new_mapping.values() {
+            if let Node::Camera(camera) = &mut dest_graph.pool[new_node_handle] {
+                remap_camera_follow(camera, &old_new_mapping);
+                continue;
+            }
+
+            let bones = if let Node::Mesh(mesh) = &dest_graph.pool[new_node_handle] {
+                mesh.surfaces()
+                    .iter()
+                    .map(|surface| {
+                        surface
+                            .bones()
+                            .iter()
+                            .map(|bone| {
+                                self.remap_bone(
+                                    *bone,
+                                    new_node_handle,
+                                    dest_graph,
+                                    &old_new_mapping,
+                                    &skipped,
+                                )
+                            })
+                            .collect::<Vec<Handle<Node>>>()
+                    })
+                    .collect::<Vec<Vec<Handle<Node>>>>()
+            } else {
+                continue;
+            };
+
+            if let Node::Mesh(mesh) = &mut dest_graph.pool[new_node_handle] {
+                for (surface, bones) in mesh.surfaces_mut().iter_mut().zip(bones) {
+                    surface.bones = bones;
+                }
+            }
+        }
+
+        (root_handle, old_new_mapping)
+    }
+
+    fn remap_bone(
+        &self,
+        bone: Handle<Node>,
+        mesh: Handle<Node>,
+        dest_graph: &Graph,
+        old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>,
+        skipped: &HashMap<Handle<Node>, Handle<Node>>,
+    ) -> Handle<Node> {
+        if let Some(&new_bone) = old_new_mapping.get(&bone) {
+            new_bone
+        } else if let Some(&ancestor) = skipped.get(&bone) {
+            ancestor
+        } else if self.is_valid_handle(bone) {
+            let same_name = dest_graph.find_by_name_from_root(self.pool[bone].name());
+            if same_name.is_some() {
+                same_name
+            } else {
+                mesh
+            }
+        } else {
+            mesh
+        }
+    }
+
+    fn copy_node_with_filter_raw<F>(
+        &self,
+        src_handle: Handle<Node>,
+        dest_parent: Handle<Node>,
+        dest_graph: &mut Graph,
+        old_new_mapping: &mut HashMap<Handle<Node>, Handle<Node>>,
+        skipped: &mut HashMap<Handle<Node>, Handle<Node>>,
+        filter: &mut F,
+    ) -> Handle<Node>
+    where
+        F: FnMut(Handle<Node>, &Node) -> CopyFilterResult,
+    {
+        let src_node = &self.pool[src_handle];
+
+        let action = if dest_parent.is_none() {
+            CopyFilterResult::Copy
+        } else {
+            filter(src_handle, src_node)
+        };
+
+        let dest_handle = match action {
+            CopyFilterResult::Copy => {
+                let mut dest_node = src_node.clone();
+                dest_node.original = src_handle;
+                let dest_handle = dest_graph.add_node(dest_node);
+                if dest_parent.is_some() {
+                    dest_graph.link_nodes(dest_handle, dest_parent);
+                }
+                old_new_mapping.insert(src_handle, dest_handle);
+                dest_handle
+            }
+            CopyFilterResult::SkipNode => {
+                skipped.insert(src_handle, dest_parent);
+                dest_parent
+            }
+            CopyFilterResult::SkipHierarchy => return Handle::NONE,
+        };
+
+        for &src_child_handle in src_node.children() {
+            self.copy_node_with_filter_raw(
+                src_child_handle,
+                dest_handle,
+                dest_graph,
+                old_new_mapping,
+                skipped,
+                filter,
+            );
+        }
+
+        dest_handle
+    }
+
     /// Searches root node in given hierarchy starting from given node. This method is used
     /// when you need to find a root node of a model in complex graph.
     fn find_model_root(&self, from: Handle<Node>) -> Handle<Node> {
@@ -318,8 +1017,14 @@ impl Graph {
         model_root_handle
     }
 
+    pub(in crate) fn update_property_overrides(&mut self) {
+        for node in self.pool.iter_mut() {
+            node.update_property_overrides();
+        }
+    }
+
     pub(in crate) fn resolve(&mut self) {
-        Log::writeln("Resolving graph...".to_owned());
+        Log::debug(LogCategory::Scene, "Resolving graph...");
 
         self.update_hierachical_data();
 
@@ -341,7 +1046,32 @@ impl Graph {
             }
         }
 
-        Log::writeln("Original handles resolved!".to_owned());
+        // Prefab originals are stored as handles, but prefab could be changed since scene
+        // was saved, so fallback to search by name if handle became invalid.
+        for node in self.pool.iter_mut() {
+            if let Some(prefab) = node.prefab() {
+                let prefab = prefab.lock().unwrap();
+                let prefab_graph = &prefab.get_scene().graph;
+                let valid = prefab_graph.is_valid_handle(node.prefab_original)
+                    && prefab_graph[node.prefab_original].name() == node.name();
+                if !valid {
+                    node.prefab_original = prefab_graph
+                        .pair_iter()
+                        .find(|(_, prefab_node)| prefab_node.name() == node.name())
+                        .map(|(handle, _)| handle)
+                        .unwrap_or(Handle::NONE);
+                }
+            }
+        }
+
+        Log::debug(LogCategory::Scene, "Original handles resolved!");
+
+        // Take every property which was not overridden in instance from resource, this
+        // will propagate changes made in resource to every instance.
+        for node in self.pool.iter_mut() {
+            node.sync_with_resource();
+        }
+        self.update_hierachical_data();
 
         // Taking second reference to self is safe here because we need it only
         // to iterate over graph and find copy of bone node. We won't modify pool
@@ -383,7 +1113,7 @@ impl Graph {
             }
         }
 
-        Log::writeln("Graph resolved successfully!".to_owned());
+        Log::debug(LogCategory::Scene, "Graph resolved successfully!");
     }
 
     /// Calculates local and global transform, global visibility for each node in graph.
@@ -391,48 +1121,274 @@ impl Graph {
     /// on each frame. However there is one use case - when you setup complex hierarchy and
     /// need to know global transform of nodes before entering update loop, then you can call
     /// this method.
+    ///
+    /// Large graphs are split into independent subtrees which are processed in parallel,
+    /// unless `serial_update` feature or deterministic mode (see `engine::determinism`) is
+    /// enabled.
     pub fn update_hierachical_data(&mut self) {
+        self.update_stamp += 1;
+        self.changed_transforms.clear();
+        self.interpolation.discard_simulated();
+
+        if self.is_parallel_update() {
+            self.update_hierachical_data_parallel();
+        } else {
+            self.update_hierachical_data_serial();
+        }
+
+        self.bounds_tree.update(&self.pool);
+    }
+
+    /// Returns tree of world-space bounds of nodes, see `bounds_tree` module docs. Tree is
+    /// updated by `update_hierachical_data`.
+    pub fn bounds_tree(&self) -> &GraphBoundsTree {
+        &self.bounds_tree
+    }
+
+    /// Restores simulated global transforms if they were interpolated and remembers them as
+    /// transforms of previous fixed step. Must be called before every fixed step, see
+    /// `interpolation` module docs. Engine calls it automatically for its scenes.
+    pub fn save_previous_transforms(&mut self) {
+        self.interpolation.restore(&mut self.pool);
+        self.interpolation.save_previous(&self.pool);
+    }
+
+    /// Replaces global transforms of nodes with transforms between previous and current
+    /// fixed step, `alpha` is in [0; 1] range. View matrices of moved cameras are recalculated
+    /// for given frame size. Simulated transforms are restored by `restore_simulated_transforms`,
+    /// `save_previous_transforms` or next update of hierarchical data.
+    pub fn interpolate_transforms(&mut self, alpha: f32, frame_size: Vec2) {
+        self.interpolation.interpolate(&mut self.pool, alpha, frame_size);
+    }
+
+    /// Restores global transforms which were replaced by `interpolate_transforms`.
+    pub fn restore_simulated_transforms(&mut self) {
+        self.interpolation.restore(&mut self.pool);
+    }
+
+    fn is_parallel_update(&self) -> bool {
+        cfg!(not(feature = "serial_update"))
+            && !determinism::is_enabled()
+            && self.pool.alive_count() >= PARALLEL_UPDATE_THRESHOLD
+    }
+
+    fn update_hierachical_data_serial(&mut self) {
         // Calculate transforms on nodes
         self.stack.clear();
         self.stack.push(self.root);
         while let Some(node_handle) = self.stack.pop() {
-            // Calculate local transform and get parent handle
             let parent_handle = self.pool[node_handle].parent();
-
-            let (parent_global_transform, parent_visibility) = if parent_handle.is_some() {
-                let parent = &self.pool[parent_handle];
-                (parent.global_transform(), parent.global_visibility())
+            let parent = if parent_handle.is_some() {
+                HierarchicalData::of(&self.pool[parent_handle])
             } else {
-                (Mat4::IDENTITY, true)
+                HierarchicalData::ROOT
             };
 
             let node = &mut self.pool[node_handle];
-            node.global_transform = parent_global_transform * node.local_transform().matrix();
-            node.global_visibility = parent_visibility && node.visibility();
+            if parent.apply(node, self.update_stamp) {
+                self.changed_transforms.push(node_handle);
+            }
 
             // Queue children and continue traversal on them
             self.stack.extend_from_slice(node.children());
         }
     }
 
+    fn update_hierachical_data_parallel(&mut self) {
+        let stamp = self.update_stamp;
+        let mut buffers = std::mem::take(&mut self.parallel_update);
+
+        // Upper levels of hierarchy ("trunk") are processed serially, level by level, until
+        // there are enough independent subtrees to keep every thread busy. Usually there
+        // are only a few levels, because scenes consist of many models attached to root.
+        let jobs = JobSystem::global();
+        let min_subtrees = jobs.thread_count() * 4;
+        if HierarchicalData::ROOT.apply(&mut self.pool[self.root], stamp) {
+            self.changed_transforms.push(self.root);
+        }
+        buffers.level.clear();
+        buffers.level.push(self.root);
+        let mut depth = 0;
+        loop {
+            buffers.next_level.clear();
+            for &handle in buffers.level.iter() {
+                buffers
+                    .next_level
+                    .extend_from_slice(self.pool[handle].children());
+            }
+            std::mem::swap(&mut buffers.level, &mut buffers.next_level);
+            if buffers.level.is_empty()
+                || buffers.level.len() >= min_subtrees
+                || depth == MAX_TRUNK_DEPTH
+            {
+                break;
+            }
+            depth += 1;
+            for &handle in buffers.level.iter() {
+                let parent = HierarchicalData::of(&self.pool[self.pool[handle].parent()]);
+                if parent.apply(&mut self.pool[handle], stamp) {
+                    self.changed_transforms.push(handle);
+                }
+            }
+        }
+
+        // Every node of current level is root of a subtree which wasn't processed yet. Nodes
+        // are numbered in depth-first order, so nodes of each subtree are contiguous.
+        buffers.order.clear();
+        buffers.order.resize(self.pool.get_capacity(), usize::MAX);
+        buffers
+            .subtrees
+            .resize_with(buffers.level.len(), Default::default);
+        let mut count = 0;
+        for (subtree, &subtree_root) in buffers.subtrees.iter_mut().zip(buffers.level.iter()) {
+            subtree.parent = HierarchicalData::of(&self.pool[self.pool[subtree_root].parent()]);
+            let first = count;
+            self.stack.clear();
+            self.stack.push(subtree_root);
+            while let Some(handle) = self.stack.pop() {
+                buffers.order[handle.index() as usize] = count;
+                count += 1;
+                self.stack.extend_from_slice(self.pool[handle].children());
+            }
+            subtree.len = count - first;
+        }
+
+        // Nodes of different subtrees are disjoint, so each subtree can take exclusive
+        // references to its nodes.
+        let mut nodes = Vec::new();
+        nodes.resize_with(count, || None);
+        for (handle, node) in self.pool.pair_iter_mut() {
+            let position = buffers.order[handle.index() as usize];
+            if position != usize::MAX {
+                nodes[position] = Some((handle, node));
+            }
+        }
+        let mut rest = nodes.as_mut_slice();
+        let mut work = Vec::with_capacity(buffers.subtrees.len());
+        for subtree in buffers.subtrees.iter_mut() {
+            let (subtree_nodes, tail) = std::mem::take(&mut rest).split_at_mut(subtree.len);
+            work.push((subtree, subtree_nodes));
+            rest = tail;
+        }
+        jobs.parallel_for(&mut work, 1, |(subtree, subtree_nodes)| {
+            subtree.update(subtree_nodes, stamp)
+        });
+
+        for subtree in buffers.subtrees.iter() {
+            self.changed_transforms.extend_from_slice(&subtree.changed);
+        }
+        self.parallel_update = buffers;
+    }
+
+    /// Returns number of calls of `update_hierachical_data`, graph calls it once per frame
+    /// so it can be used as frame counter. Node's global transform was changed at last update
+    /// if its `transform_stamp` is equal to this number.
+    pub fn update_stamp(&self) -> u64 {
+        self.update_stamp
+    }
+
+    /// Returns handles of nodes whose global transforms were changed at last update. This
+    /// includes nodes that were moved by themselves, nodes whose ancestors were moved, and
+    /// nodes that were just added to graph. Systems that process node positions (sound,
+    /// network replication, custom culling, etc.) can use it to process only moved nodes.
+    /// Handles of nodes that were removed after last update are still in the list.
+    pub fn changed_transforms(&self) -> &[Handle<Node>] {
+        &self.changed_transforms
+    }
+
+    /// Returns true if global transform of given node was changed at last update.
+    ///
+    /// # Panics
+    ///
+    /// Panics if handle is invalid.
+    pub fn is_transform_changed(&self, node: Handle<Node>) -> bool {
+        self.pool[node].transform_stamp == self.update_stamp
+    }
+
     /// Checks whether given node handle is valid or not.
     pub fn is_valid_handle(&self, node_handle: Handle<Node>) -> bool {
         self.pool.is_valid_handle(node_handle)
     }
 
+    /// Tries to convert given handle into typed handle, returns None if handle is invalid
+    /// or node has different type.
+    pub fn typed_handle<T: NodeVariant>(&self, handle: Handle<Node>) -> Option<TypedHandle<T>> {
+        self.try_get_as::<T>(handle)
+            .map(|_| TypedHandle::new_unchecked(handle))
+    }
+
+    /// Returns reference to node of given type, None if handle is invalid or node has
+    /// different type.
+    pub fn try_get_as<T: NodeVariant>(&self, handle: Handle<Node>) -> Option<&T> {
+        if self.pool.is_valid_handle(handle) {
+            T::from_node(&self.pool[handle])
+        } else {
+            None
+        }
+    }
+
+    /// Returns mutable reference to node of given type, None if handle is invalid or node
+    /// has different type.
+    pub fn try_get_as_mut<T: NodeVariant>(&mut self, handle: Handle<Node>) -> Option<&mut T> {
+        if self.pool.is_valid_handle(handle) {
+            T::from_node_mut(&mut self.pool[handle])
+        } else {
+            None
+        }
+    }
+
+    /// Returns reference to node by typed handle, None if node was removed or replaced by
+    /// a node of other type.
+    pub fn get_typed<T: NodeVariant>(&self, handle: TypedHandle<T>) -> Option<&T> {
+        self.try_get_as(handle.handle())
+    }
+
+    /// Returns mutable reference to node by typed handle, None if node was removed or
+    /// replaced by a node of other type.
+    pub fn get_typed_mut<T: NodeVariant>(&mut self, handle: TypedHandle<T>) -> Option<&mut T> {
+        self.try_get_as_mut(handle.handle())
+    }
+
+    /// Adds new node to the graph and returns typed handle of it. Node is given back if it
+    /// has type other than `T`.
+    pub fn add_typed_node<T: NodeVariant>(&mut self, node: Node) -> Result<TypedHandle<T>, Node> {
+        if T::from_node(&node).is_some() {
+            Ok(TypedHandle::new_unchecked(self.add_node(node)))
+        } else {
+            Err(node)
+        }
+    }
+
+    /// Returns iterator over typed handles of every node of given type.
+    pub fn typed_handles<T: NodeVariant>(&self) -> impl Iterator<Item = TypedHandle<T>> + '_ {
+        self.pool
+            .pair_iter()
+            .filter(|(_, node)| T::from_node(node).is_some())
+            .map(|(handle, _)| TypedHandle::new_unchecked(handle))
+    }
+
     /// Updates nodes in graph using given delta time. There is no need to call it manually.
+    /// Nodes of large graphs are updated in parallel by global job system (see `utils::jobs`),
+    /// unless `serial_update` feature or deterministic mode (see `engine::determinism`) is
+    /// enabled.
     pub fn update_nodes(&mut self, frame_size: Vec2, dt: f32) {
-        self.update_hierachical_data();
+        profile_scope!("Graph");
 
-        for node in self.pool.iter_mut() {
-            if let Some(lifetime) = node.lifetime() {
-                node.set_lifetime(lifetime - dt);
-            }
-
-            match node {
-                Node::Camera(camera) => camera.calculate_matrices(frame_size),
-                Node::ParticleSystem(particle_system) => particle_system.update(dt),
-                _ => (),
+        {
+            profile_scope!("HierarchicalData");
+            self.update_hierachical_data();
+        }
+        self.update_camera_follow(dt);
+
+        profile_scope!("Nodes");
+        // Nodes are updated independently of each other, so they can be updated in parallel.
+        if self.is_parallel_update() {
+            let mut nodes = self.pool.iter_mut().collect::<Vec<_>>();
+            JobSystem::global()
+                .parallel_for(&mut nodes, 1, |node| update_node(node, frame_size, dt));
+        } else {
+            for node in self.pool.iter_mut() {
+                update_node(node, frame_size, dt);
             }
         }
 
@@ -448,11 +1404,92 @@ impl Graph {
             };
 
             if remove {
-                self.remove_node(self.pool.handle_from_index(i));
+                self.remove_node_internal(self.pool.handle_from_index(i), RemovalReason::Expired);
+            }
+        }
+
+        // Node could be already removed together with its ancestor.
+        for handle in std::mem::take(&mut self.delete_queue) {
+            if self.pool.is_valid_handle(handle) {
+                self.remove_node_internal(handle, RemovalReason::Removed);
+            }
+        }
+
+        if let Some(journal) = self.journal.as_mut() {
+            journal.end_frame(&self.pool);
+        }
+    }
+
+    /// Moves cameras that follow other nodes, see `camera_effects` module docs. Global
+    /// transforms of moved cameras and their descendants are updated right away, so
+    /// matrices of cameras and everything attached to them are correct in the same frame.
+    fn update_camera_follow(&mut self, dt: f32) {
+        let followers = self
+            .pool
+            .pair_iter()
+            .filter_map(|(handle, node)| match node {
+                Node::Camera(camera) if camera.is_globally_enabled() => camera
+                    .effects()
+                    .follow()
+                    .map(|follow| (handle, follow.target)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for (camera_handle, target) in followers {
+            if !self.pool.is_valid_handle(target) {
+                continue;
+            }
+            let target_position = self.pool[target].global_position();
+            let parent_handle = self.pool[camera_handle].parent();
+            let parent_inv_transform = if parent_handle.is_some() {
+                self.pool[parent_handle]
+                    .global_transform()
+                    .inverse()
+                    .unwrap_or_default()
+            } else {
+                Mat4::IDENTITY
+            };
+
+            let camera = self.pool[camera_handle].as_camera_mut();
+            let position = camera.global_position();
+            let scaled_dt = dt * camera.global_time_scale();
+            if let Some((new_position, rotation)) = camera
+                .effects_mut()
+                .follow_step(position, target_position, scaled_dt)
+            {
+                let local_position = parent_inv_transform.transform_vector(new_position);
+                let transform = camera.local_transform_mut();
+                transform.set_position(local_position);
+                if let Some(rotation) = rotation {
+                    transform.set_rotation(rotation);
+                }
+                self.update_subtree_hierarchical_data(camera_handle);
             }
         }
     }
 
+    fn update_subtree_hierarchical_data(&mut self, root: Handle<Node>) {
+        self.stack.clear();
+        self.stack.push(root);
+        while let Some(node_handle) = self.stack.pop() {
+            let parent_handle = self.pool[node_handle].parent();
+            let parent = if parent_handle.is_some() {
+                HierarchicalData::of(&self.pool[parent_handle])
+            } else {
+                HierarchicalData::ROOT
+            };
+
+            let node = &mut self.pool[node_handle];
+            let already_changed = node.transform_stamp == self.update_stamp;
+            if parent.apply(node, self.update_stamp) && !already_changed {
+                self.changed_transforms.push(node_handle);
+            }
+
+            self.stack.extend_from_slice(node.children());
+        }
+    }
+
     /// Returns capacity of internal pool. Can be used to iterate over all **potentially**
     /// available indices and try to convert them to handles.
     ///
@@ -621,6 +1658,148 @@ impl Graph {
         (copy, old_new_map)
     }
 
+    /// Defragments internal pool of nodes - every alive node is moved into a new pool
+    /// without vacant entries between nodes. Returns old-to-new node mapping, every handle
+    /// to a node that was obtained before compaction must be remapped using it. Parent-child
+    /// relations, bones of surfaces and targets of camera follow effects are remapped
+    /// automatically.
+    ///
+    /// # Notes
+    ///
+    /// Nodes that were extracted by `take_reserve` or `take_reserve_sub_graph` must be put
+    /// back before compaction, otherwise their tickets will become invalid.
+    pub fn compact(&mut self) -> HashMap<Handle<Node>, Handle<Node>> {
+        let mut old_pool = std::mem::replace(&mut self.pool, Pool::new());
+
+        let mut old_new_mapping = HashMap::new();
+        for i in 0..old_pool.get_capacity() {
+            let old_handle = old_pool.handle_from_index(i);
+            if old_handle.is_some() {
+                let (ticket, node) = old_pool.take_reserve(old_handle);
+                old_pool.forget_ticket(ticket);
+                old_new_mapping.insert(old_handle, self.pool.spawn(node));
+            }
+        }
+
+        let remap = |handle: &mut Handle<Node>| {
+            *handle = old_new_mapping.get(handle).copied().unwrap_or_default();
+        };
+
+        for node in self.pool.iter_mut() {
+            remap(&mut node.parent);
+            for child in node.children.iter_mut() {
+                remap(child);
+            }
+            match node {
+                Node::Mesh(mesh) => {
+                    for surface in mesh.surfaces_mut() {
+                        for bone_handle in surface.bones.iter_mut() {
+                            remap(bone_handle);
+                        }
+                    }
+                }
+                Node::Camera(camera) => {
+                    if let Some(follow) = camera.effects_mut().follow_mut() {
+                        remap(&mut follow.target);
+                    }
+                }
+                _ => (),
+            }
+        }
+        remap(&mut self.root);
+
+        if let Some(journal) = self.journal.as_mut() {
+            journal.remap(&old_new_mapping);
+        }
+
+        self.delete_queue = std::mem::take(&mut self.delete_queue)
+            .into_iter()
+            .filter_map(|handle| old_new_mapping.get(&handle).copied())
+            .collect();
+        self.changed_transforms = std::mem::take(&mut self.changed_transforms)
+            .into_iter()
+            .filter_map(|handle| old_new_mapping.get(&handle).copied())
+            .collect();
+        self.interpolation.remap(&old_new_mapping);
+
+        // Leaves of bounds tree are keyed by old handles, tree must be rebuilt, otherwise
+        // ray casts and culling would get wrong nodes until next update.
+        self.bounds_tree = Default::default();
+        self.bounds_tree.update(&self.pool);
+
+        // Traversal stack and buffers of parallel update could grow a lot on large graphs,
+        // release their memory too.
+        self.stack = Vec::new();
+        self.parallel_update = Default::default();
+
+        old_new_mapping
+    }
+
+    /// Moves every node of other graph into this graph, except root of other graph. Nodes
+    /// that were attached to root of other graph will be attached to root of this graph.
+    /// Returns old-to-new node mapping, every handle to a node of other graph must be
+    /// remapped using it. Unlike `copy_node` nodes are not cloned, so every property of
+    /// nodes is preserved.
+    pub fn merge(&mut self, mut other: Graph) -> HashMap<Handle<Node>, Handle<Node>> {
+        let mut old_new_mapping = HashMap::new();
+        for i in 0..other.pool.get_capacity() {
+            let old_handle = other.pool.handle_from_index(i);
+            if old_handle.is_some() && old_handle != other.root {
+                let (ticket, mut node) = other.pool.take_reserve(old_handle);
+                other.pool.forget_ticket(ticket);
+                // Update stamps of other graph are meaningless here.
+                node.transform_stamp = 0;
+                let new_handle = self.pool.spawn(node);
+                old_new_mapping.insert(old_handle, new_handle);
+            }
+        }
+
+        let remap = |handle: &mut Handle<Node>| {
+            *handle = old_new_mapping.get(handle).copied().unwrap_or_default();
+        };
+
+        for &handle in old_new_mapping.values() {
+            let node = &mut self.pool[handle];
+            remap(&mut node.parent);
+            for child in node.children.iter_mut() {
+                remap(child);
+            }
+            match node {
+                Node::Mesh(mesh) => {
+                    for surface in mesh.surfaces_mut() {
+                        for bone_handle in surface.bones.iter_mut() {
+                            remap(bone_handle);
+                        }
+                    }
+                }
+                Node::Camera(camera) => {
+                    if let Some(follow) = camera.effects_mut().follow_mut() {
+                        remap(&mut follow.target);
+                    }
+                }
+                _ => (),
+            }
+            if let Some(journal) = self.journal.as_mut() {
+                journal.record(GraphChange::NodeAdded(handle));
+            }
+        }
+
+        for &handle in old_new_mapping.values() {
+            self.emit(GraphEvent::Added(handle));
+        }
+
+        // Keep order of top-level nodes.
+        if other.root.is_some() {
+            for child in other.pool[other.root].children() {
+                if let Some(&handle) = old_new_mapping.get(child) {
+                    self.link_nodes(handle, self.root);
+                }
+            }
+        }
+
+        old_new_mapping
+    }
+
     /// Returns local transformation matrix of a node without scale.
     pub fn local_transform_no_scale(&self, node: Handle<Node>) -> Mat4 {
         let mut transform = self[node].local_transform().clone();
@@ -663,6 +1842,20 @@ impl IndexMut<Handle<Node>> for Graph {
     }
 }
 
+impl<T: NodeVariant> Index<TypedHandle<T>> for Graph {
+    type Output = T;
+
+    fn index(&self, index: TypedHandle<T>) -> &Self::Output {
+        T::from_node(&self.pool[index.handle()]).expect("Node type mismatch!")
+    }
+}
+
+impl<T: NodeVariant> IndexMut<TypedHandle<T>> for Graph {
+    fn index_mut(&mut self, index: TypedHandle<T>) -> &mut Self::Output {
+        T::from_node_mut(&mut self.pool[index.handle()]).expect("Node type mismatch!")
+    }
+}
+
 /// Iterator that traverses tree in depth and returns shared references to nodes.
 pub struct GraphTraverseIterator<'a> {
     graph: &'a Graph,
@@ -727,9 +1920,27 @@ impl Visit for Graph {
 #[cfg(test)]
 mod test {
     use crate::{
-        core::pool::Handle,
-        scene::{base::Base, graph::Graph, node::Node},
+        core::{
+            math::{mat4::Mat4, quat::Quat, vec2::Vec2, vec3::Vec3},
+            pool::Handle,
+            visitor::{Visit, Visitor},
+        },
+        engine::determinism,
+        renderer::surface::{Surface, SurfaceSharedData},
+        scene::{
+            base::{Base, BaseBuilder, InheritableProperty, OverridableProperty},
+            camera::{Camera, CameraBuilder},
+            camera_effects::CameraFollow,
+            graph::{CopyFilterResult, Graph, GraphEvent, RemovalReason},
+            journal::GraphChange,
+            mesh::{Mesh, MeshBuilder},
+            node::Node,
+            sprite::SpriteBuilder,
+            transform::TransformBuilder,
+            typed_handle::TypedHandle,
+        },
     };
+    use std::sync::{Arc, Mutex};
 
     #[test]
     fn graph_init_test() {
@@ -746,4 +1957,547 @@ mod test {
         graph.add_node(Node::Base(Base::default()));
         assert_eq!(graph.pool.alive_count(), 4);
     }
+
+    #[test]
+    fn graph_compact_test() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(Node::Base(Base::default()));
+        let b = graph.add_node(Node::Base(Base::default()));
+        let c = graph.add_node(Node::Base(Base::default()));
+        graph.link_nodes(c, a);
+        graph.remove_node(b);
+
+        let old_new_mapping = graph.compact();
+        assert_eq!(graph.capacity(), graph.node_count());
+        assert!(!old_new_mapping.contains_key(&b));
+
+        let (a, c) = (old_new_mapping[&a], old_new_mapping[&c]);
+        assert_eq!(graph[c].parent(), a);
+        assert_eq!(graph[a].children(), &[c]);
+        assert_eq!(graph[a].parent(), graph.root);
+    }
+
+    #[test]
+    fn graph_compact_remaps_caches_test() {
+        let mut graph = Graph::new();
+        let removed = graph.add_node(Node::Base(Base::default()));
+        let sprite = graph.add_node(
+            SpriteBuilder::new(
+                BaseBuilder::new().with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(Vec3::new(10.0, 0.0, 0.0))
+                        .build(),
+                ),
+            )
+            .with_size(1.0)
+            .build_node(),
+        );
+        graph.remove_node(removed);
+        graph.update_hierachical_data();
+        graph.save_previous_transforms();
+        graph[sprite]
+            .local_transform_mut()
+            .set_position(Vec3::new(20.0, 0.0, 0.0));
+        graph.update_hierachical_data();
+
+        let sprite = graph.compact()[&sprite];
+        assert_eq!(graph.changed_transforms(), &[sprite]);
+        let mut result = Vec::new();
+        graph
+            .bounds_tree()
+            .query_sphere(Vec3::new(20.0, 0.0, 0.0), 0.5, &mut result);
+        assert_eq!(result, vec![sprite]);
+        graph.interpolate_transforms(0.5, Vec2::new(1.0, 1.0));
+        assert!((graph[sprite].global_position().x - 15.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn graph_merge_test() {
+        let mut graph = Graph::new();
+        graph.add_node(Node::Base(Base::default()));
+
+        let mut chunk = Graph::new();
+        let a = chunk.add_node(BaseBuilder::new().with_name("A").build_node());
+        let b = chunk.add_node(BaseBuilder::new().with_name("B").build_node());
+        chunk.link_nodes(b, a);
+
+        let old_new_mapping = graph.merge(chunk);
+        assert_eq!(graph.node_count(), 4);
+
+        let (a, b) = (old_new_mapping[&a], old_new_mapping[&b]);
+        assert_eq!(graph[a].parent(), graph.root);
+        assert_eq!(graph[b].parent(), a);
+        assert_eq!(graph.find_by_path("A/B"), b);
+    }
+
+    #[test]
+    fn graph_camera_follow_remap_test() {
+        let mut graph = Graph::new();
+        let removed = graph.add_node(Node::Base(Base::default()));
+        let target = graph.add_node(Node::Base(Base::default()));
+        let camera = graph.add_node(CameraBuilder::new(BaseBuilder::new()).build_node());
+        graph[camera]
+            .as_camera_mut()
+            .effects_mut()
+            .set_follow(Some(CameraFollow::new(target)));
+        let follow_target = |graph: &Graph, camera: Handle<Node>| {
+            graph[camera].as_camera().effects().follow().unwrap().target
+        };
+
+        graph.remove_node(removed);
+        let old_new_mapping = graph.compact();
+        let (target, camera) = (old_new_mapping[&target], old_new_mapping[&camera]);
+        assert_eq!(follow_target(&graph, camera), target);
+
+        let (copy, copy_mapping) = graph.clone(&mut |_, _| true);
+        assert_eq!(
+            follow_target(&copy, copy_mapping[&camera]),
+            copy_mapping[&target]
+        );
+
+        let mut other = Graph::new();
+        other.add_node(Node::Base(Base::default()));
+        let merge_mapping = other.merge(copy);
+        let (target, camera) = (
+            merge_mapping[&copy_mapping[&target]],
+            merge_mapping[&copy_mapping[&camera]],
+        );
+        assert_eq!(follow_target(&other, camera), target);
+    }
+
+    #[test]
+    fn graph_enabled_test() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(BaseBuilder::new().with_enabled(false).build_node());
+        let b = graph.add_node(BaseBuilder::new().with_lifetime(1.0).build_node());
+        graph.link_nodes(b, a);
+        graph.update_nodes(Vec2::new(1.0, 1.0), 0.5);
+
+        assert!(!graph[b].is_globally_enabled());
+        assert!(!graph[b].global_visibility());
+        assert_eq!(graph[b].lifetime(), Some(1.0));
+
+        graph[a].set_node_enabled(true);
+        graph.update_nodes(Vec2::new(1.0, 1.0), 0.5);
+        assert!(graph[b].is_globally_enabled());
+        assert_eq!(graph[b].lifetime(), Some(0.5));
+    }
+
+    #[test]
+    fn graph_path_test() {
+        let mut graph = Graph::new();
+        let arm = graph.add_node(BaseBuilder::new().with_name("Arm").build_node());
+        let hand = graph.add_node(BaseBuilder::new().with_name("Hand").build_node());
+        let socket = graph.add_node(BaseBuilder::new().with_name("Socket").build_node());
+        graph.link_nodes(hand, arm);
+        graph.link_nodes(socket, hand);
+
+        assert_eq!(graph.node_path(socket), "Arm/Hand/Socket");
+        assert_eq!(graph.find_by_path("Arm/Hand/Socket"), socket);
+        assert_eq!(graph.find_by_path_from(arm, "Hand/Socket"), socket);
+        assert_eq!(graph.find_by_path(&graph.node_path(hand)), hand);
+        assert!(graph.find_by_path("Arm/Socket").is_none());
+    }
+
+    #[test]
+    fn graph_tags_test() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(BaseBuilder::new().with_tag("enemy").build_node());
+        let b = graph.add_node(BaseBuilder::new().with_tag("pickup").build_node());
+        let c = graph.add_node(Node::Base(Base::default()));
+        graph.link_nodes(c, a);
+        graph[c].add_tag("enemy");
+
+        assert_eq!(graph.find_by_tag_from_root("pickup"), b);
+        assert_eq!(graph.find_by_tag(c, "enemy"), c);
+        assert!(graph.find_by_tag_from_root("spawn_point").is_none());
+
+        let mut enemies = graph.nodes_with_tag("enemy").collect::<Vec<_>>();
+        enemies.sort_by_key(|h| h.index());
+        assert_eq!(enemies, vec![a, c]);
+
+        assert!(graph[c].remove_tag("enemy"));
+        assert!(!graph[c].has_tag("enemy"));
+    }
+
+    #[test]
+    fn graph_journal_test() {
+        let mut graph = Graph::new();
+        graph.set_journal_enabled(true);
+        let a = graph.add_node(Node::Base(Base::default()));
+        let b = graph.add_node(Node::Base(Base::default()));
+        graph.link_nodes(b, a);
+        graph.update_nodes(Vec2::new(1.0, 1.0), 0.0);
+
+        graph[b].set_visibility(false);
+        graph.update_nodes(Vec2::new(1.0, 1.0), 0.0);
+        graph.remove_node(a);
+
+        let changes = graph
+            .journal_mut()
+            .unwrap()
+            .drain()
+            .into_iter()
+            .map(|entry| entry.change)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                GraphChange::NodeAdded(a),
+                GraphChange::NodeAdded(b),
+                GraphChange::NodeMoved { node: b, parent: a },
+                GraphChange::PropertyChanged {
+                    node: b,
+                    property: OverridableProperty::Visibility,
+                },
+                GraphChange::NodeRemoved(a),
+                GraphChange::NodeRemoved(b),
+            ]
+        );
+    }
+
+    #[test]
+    fn graph_copy_node_with_filter_test() {
+        let mut graph = Graph::new();
+        let mut add = |name: &str, parent: Handle<Node>| {
+            let node = graph.add_node(BaseBuilder::new().with_name(name).build_node());
+            if parent.is_some() {
+                graph.link_nodes(node, parent);
+            }
+            node
+        };
+        let root = add("Root", Handle::NONE);
+        let skipped = add("Skipped", root);
+        let bone = add("Bone", skipped);
+        let hidden_bone = add("HiddenBone", root);
+        let hidden = add("Hidden", hidden_bone);
+
+        let mut surface = Surface::new(Arc::new(Mutex::new(SurfaceSharedData::make_cube(
+            Mat4::IDENTITY,
+        ))));
+        surface.bones = vec![bone, skipped, hidden_bone];
+        let mesh = MeshBuilder::new(BaseBuilder::new().with_name("Mesh"))
+            .with_surfaces(vec![surface])
+            .build_node();
+        let mesh = graph.add_node(mesh);
+        graph.link_nodes(mesh, root);
+
+        let mut dest = Graph::new();
+        let (copy, map) = graph.copy_node_with_filter(root, &mut dest, &mut |_, node| {
+            match node.name() {
+                "Skipped" => CopyFilterResult::SkipNode,
+                "HiddenBone" => CopyFilterResult::SkipHierarchy,
+                _ => CopyFilterResult::Copy,
+            }
+        });
+
+        assert_eq!(map.len(), 3);
+        assert!(!map.contains_key(&skipped));
+        assert!(!map.contains_key(&hidden));
+        // Child of skipped node must be attached to closest copied ancestor.
+        assert_eq!(dest[map[&bone]].parent(), copy);
+        if let Node::Mesh(mesh_copy) = &dest[map[&mesh]] {
+            assert_eq!(mesh_copy.surfaces()[0].bones(), &[map[&bone], copy, map[&mesh]]);
+        } else {
+            panic!("Mesh must be copied as mesh!");
+        }
+    }
+
+    #[test]
+    fn graph_transform_changes_test() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(Node::Base(Base::default()));
+        let b = graph.add_node(Node::Base(Base::default()));
+        let c = graph.add_node(Node::Base(Base::default()));
+        graph.link_nodes(b, a);
+
+        // Just added nodes are always treated as changed.
+        graph.update_hierachical_data();
+        assert!(graph.is_transform_changed(a));
+        assert!(graph.is_transform_changed(c));
+
+        graph.update_hierachical_data();
+        assert!(graph.changed_transforms().is_empty());
+
+        // Moving of a node must mark its descendants too.
+        graph[a]
+            .local_transform_mut()
+            .set_position(Vec3::new(1.0, 0.0, 0.0));
+        graph.update_hierachical_data();
+        assert_eq!(graph.changed_transforms().len(), 2);
+        assert!(graph.is_transform_changed(a));
+        assert!(graph.is_transform_changed(b));
+        assert!(!graph.is_transform_changed(c));
+        assert_eq!(graph[b].transform_stamp(), graph.update_stamp());
+    }
+
+    #[test]
+    fn graph_reattach_sockets_test() {
+        let mut graph = Graph::new();
+        let make_model = |graph: &mut Graph| {
+            let model = graph.add_node(BaseBuilder::new().with_name("Model").build_node());
+            let hand = graph.add_node(BaseBuilder::new().with_name("RightHand").build_node());
+            graph.link_nodes(hand, model);
+            (model, hand)
+        };
+        let (old_model, old_hand) = make_model(&mut graph);
+        let (new_model, new_hand) = make_model(&mut graph);
+        let weapon = graph.add_node(BaseBuilder::new().with_name("Weapon").build_node());
+
+        assert!(!graph.attach_to_bone(weapon, old_model, "Head"));
+        assert!(graph.attach_to_bone_with_offset(
+            weapon,
+            old_model,
+            "RightHand",
+            Vec3::new(0.0, 0.1, 0.0),
+            Quat::IDENTITY
+        ));
+        assert_eq!(graph[weapon].parent(), old_hand);
+        assert_eq!(graph[weapon].socket(), Some("RightHand"));
+
+        assert_eq!(graph.reattach_sockets(old_model, new_model), 1);
+        assert_eq!(graph[weapon].parent(), new_hand);
+        assert_eq!(
+            graph[weapon].local_transform().position(),
+            Vec3::new(0.0, 0.1, 0.0)
+        );
+    }
+
+    #[test]
+    fn graph_deferred_removal_test() {
+        let mut graph = Graph::new();
+        graph.set_removal_events_enabled(true);
+        let a = graph.add_node(BaseBuilder::new().with_name("A").build_node());
+        let b = graph.add_node(BaseBuilder::new().with_name("B").build_node());
+        let c = graph.add_node(BaseBuilder::new().with_lifetime(0.5).build_node());
+        graph.link_nodes(b, a);
+
+        graph.queue_delete(a);
+        graph.queue_delete(b);
+        assert!(graph.is_valid_handle(a));
+        assert!(graph.poll_removal_event().is_none());
+
+        graph.update_nodes(Vec2::new(1.0, 1.0), 1.0);
+        assert!(!graph.is_valid_handle(a));
+        assert!(!graph.is_valid_handle(b));
+        assert!(!graph.is_valid_handle(c));
+
+        let mut events = Vec::new();
+        while let Some(event) = graph.poll_removal_event() {
+            events.push((event.handle, event.reason));
+        }
+        assert_eq!(
+            events,
+            vec![
+                (c, RemovalReason::Expired),
+                (a, RemovalReason::Removed),
+                (b, RemovalReason::Removed)
+            ]
+        );
+    }
+
+    #[test]
+    fn graph_parallel_update_test() {
+        // Deterministic mode enabled by other test would force serial update.
+        let _lock = determinism::test_lock();
+        let mut graph = Graph::new();
+        // Make graph large enough to be updated in parallel, with single long chain and
+        // lots of small subtrees.
+        let mut parent = graph.get_root();
+        for i in 0..super::PARALLEL_UPDATE_THRESHOLD {
+            let node = graph.add_node(
+                BaseBuilder::new()
+                    .with_local_transform(
+                        TransformBuilder::new()
+                            .with_local_position(Vec3::new(1.0, 0.0, 0.0))
+                            .build(),
+                    )
+                    .build_node(),
+            );
+            graph.link_nodes(node, parent);
+            if i % 8 == 0 {
+                parent = node;
+            }
+        }
+        graph.update_hierachical_data();
+
+        for (handle, node) in graph.pair_iter() {
+            let parent = node.parent();
+            let expected = if parent.is_some() {
+                graph[parent].global_transform() * node.local_transform().matrix()
+            } else {
+                node.local_transform().matrix()
+            };
+            assert_eq!(node.global_transform().f, expected.f);
+            assert!(graph.is_transform_changed(handle));
+        }
+        assert_eq!(graph.changed_transforms().len(), graph.node_count());
+    }
+
+    #[test]
+    fn graph_deterministic_update_is_serial_test() {
+        let _lock = determinism::test_lock();
+        let mut graph = Graph::new();
+        for _ in 0..super::PARALLEL_UPDATE_THRESHOLD {
+            graph.add_node(Node::Base(Base::default()));
+        }
+        assert_eq!(
+            graph.is_parallel_update(),
+            cfg!(not(feature = "serial_update"))
+        );
+
+        determinism::set_enabled(true);
+        let parallel = graph.is_parallel_update();
+        determinism::set_enabled(false);
+        assert!(!parallel);
+    }
+
+    #[test]
+    fn graph_property_inheritance_test() {
+        let mut graph = Graph::new();
+        let parent = graph.add_node(
+            BaseBuilder::new()
+                .with_visibility(false)
+                .with_cast_shadows(false)
+                .with_layers(0b011)
+                .build_node(),
+        );
+        let inheriting = graph.add_node(
+            BaseBuilder::new()
+                .with_layers(0b110)
+                .with_inherits(InheritableProperty::Layers, true)
+                .build_node(),
+        );
+        let overriding = graph.add_node(
+            BaseBuilder::new()
+                .with_inherits(InheritableProperty::Visibility, false)
+                .with_inherits(InheritableProperty::CastShadows, false)
+                .build_node(),
+        );
+        graph.link_nodes(inheriting, parent);
+        graph.link_nodes(overriding, parent);
+        graph.update_hierachical_data();
+
+        assert!(!graph[inheriting].global_visibility());
+        assert!(!graph[inheriting].global_cast_shadows());
+        assert_eq!(graph[inheriting].global_layers(), 0b010);
+
+        assert!(graph[overriding].global_visibility());
+        assert!(graph[overriding].global_cast_shadows());
+        assert_eq!(graph[overriding].global_layers(), Base::DEFAULT_LAYERS);
+    }
+
+    #[test]
+    fn graph_typed_handle_test() {
+        let mut graph = Graph::new();
+        let base = graph.add_node(Node::Base(Base::default()));
+        let camera = graph
+            .add_typed_node::<Camera>(CameraBuilder::new(BaseBuilder::new()).build_node())
+            .unwrap();
+        assert!(graph
+            .add_typed_node::<Camera>(Node::Base(Base::default()))
+            .is_err());
+
+        assert!(graph.typed_handle::<Camera>(base).is_none());
+        assert!(graph.typed_handle::<Mesh>(camera.handle()).is_none());
+        assert!(graph.typed_handle::<Base>(camera.handle()).is_some());
+        assert_eq!(graph.typed_handle::<Camera>(camera.handle()), Some(camera));
+        assert_eq!(graph.typed_handles::<Camera>().collect::<Vec<_>>(), vec![camera]);
+
+        graph[camera].set_z_far(123.0);
+        assert_eq!(graph.get_typed(camera).unwrap().z_far(), 123.0);
+
+        graph.remove_node(camera.into());
+        assert!(graph.get_typed(camera).is_none());
+        assert!(graph.get_typed(TypedHandle::<Camera>::NONE).is_none());
+    }
+
+    #[test]
+    fn graph_event_test() {
+        let mut graph = Graph::new();
+        let root = graph.get_root();
+        let events = graph.subscribe();
+        let dropped = graph.subscribe();
+        drop(dropped);
+
+        let a = graph.add_node(Node::Base(Base::default()));
+        let b = graph.add_node(Node::Base(Base::default()));
+        graph.link_nodes(b, a);
+        graph.remove_node(a);
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                GraphEvent::Added(a),
+                GraphEvent::Added(b),
+                GraphEvent::Reparented {
+                    node: b,
+                    old_parent: root,
+                    new_parent: a,
+                },
+                GraphEvent::Removed(a),
+                GraphEvent::Removed(b),
+            ]
+        );
+        assert_eq!(graph.subscribers.len(), 1);
+
+        drop(events);
+        graph.add_node(Node::Base(Base::default()));
+        assert!(graph.subscribers.is_empty());
+    }
+
+    #[test]
+    fn unlink_reports_actual_parent() {
+        let mut graph = Graph::new();
+        let root = graph.get_root();
+        let a = graph.add_node(Node::Base(Base::default()));
+        let b = graph.add_node(Node::Base(Base::default()));
+        graph.link_nodes(b, a);
+        let events = graph.subscribe();
+
+        graph.unlink_node(b);
+        // Node is already a child of root, nothing is changed.
+        graph.unlink_node(b);
+
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![GraphEvent::Reparented {
+                node: b,
+                old_parent: a,
+                new_parent: root,
+            }]
+        );
+        assert_eq!(graph[b].parent(), root);
+        assert!(graph[a].children().is_empty());
+    }
+
+    #[test]
+    fn graph_handles_survive_save_load() {
+        let mut graph = Graph::new();
+        let a = graph.add_node(BaseBuilder::new().with_name("A").build_node());
+        let removed = graph.add_node(BaseBuilder::new().with_name("Removed").build_node());
+        graph.remove_node(removed);
+        // Takes free slot of removed node with next generation.
+        let b = graph.add_node(BaseBuilder::new().with_name("B").build_node());
+        graph.link_nodes(b, a);
+
+        let path = std::env::temp_dir().join("rg3d-graph-handles-test");
+        let mut visitor = Visitor::new();
+        graph.visit("Graph", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = Graph::default();
+        loaded.visit("Graph", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(loaded.get_root(), graph.get_root());
+        assert_eq!(loaded[a].name(), "A");
+        assert_eq!(loaded[b].name(), "B");
+        assert_eq!(loaded[b].parent(), a);
+        assert!(!loaded.is_valid_handle(removed));
+        // New nodes must not reuse handles of nodes that existed before saving.
+        let c = loaded.add_node(BaseBuilder::new().with_name("C").build_node());
+        assert!(c != a && c != b && c != removed);
+    }
 }
@@ -0,0 +1,141 @@
+//! Contains interpolation of global transforms of nodes between fixed simulation steps.
+//!
+//! When scenes are simulated with fixed time step (see `Engine::set_fixed_update_rate`), a
+//! frame can contain zero, one or several steps, so objects would move in jerks if frames were
+//! rendered with transforms of last step. Instead, global transforms of nodes before last step
+//! are remembered, and before rendering every node gets a transform between previous and current
+//! step (position and scale are interpolated linearly, rotation - spherically). Simulated
+//! transforms are restored before next step, so simulation never sees interpolated transforms.
+//!
+//! Nodes that were added during last step have no previous transform and are not interpolated.
+//! Teleported nodes are interpolated between old and new location during one frame, which is
+//! barely noticeable at usual rates of simulation.
+
+use crate::{
+    core::{
+        math::{mat4::Mat4, quat::Quat, vec2::Vec2, vec3::Vec3},
+        pool::{Handle, Pool},
+    },
+    scene::node::Node,
+};
+use std::collections::HashMap;
+
+/// Returns transform between `from` and `to`, `t` is in [0; 1] range. Transforms must not
+/// have shear, which is true for global transforms of nodes unless they're children of
+/// non-uniformly scaled and rotated nodes.
+pub fn interpolate_transform(from: &Mat4, to: &Mat4, t: f32) -> Mat4 {
+    let decompose = |m: &Mat4| {
+        let scale = Vec3::new(m.side().len(), m.up().len(), m.look().len());
+        let inv_scale = |s: f32| if s > std::f32::EPSILON { 1.0 / s } else { 0.0 };
+        let inv_scale = Vec3::new(inv_scale(scale.x), inv_scale(scale.y), inv_scale(scale.z));
+        let rotation = Quat::from((*m * Mat4::scale(inv_scale)).basis());
+        (m.position(), rotation, scale)
+    };
+    let (from_position, from_rotation, from_scale) = decompose(from);
+    let (to_position, to_rotation, to_scale) = decompose(to);
+    let lerp = |a: Vec3, b: Vec3| a + (b - a).scale(t);
+    Mat4::translate(lerp(from_position, to_position))
+        * Mat4::from_quat(from_rotation.slerp(&to_rotation, t))
+        * Mat4::scale(lerp(from_scale, to_scale))
+}
+
+/// Previous and simulated global transforms of nodes of a graph, see module docs.
+#[derive(Debug, Default)]
+pub(in crate) struct TransformInterpolation {
+    previous: HashMap<Handle<Node>, Mat4>,
+    simulated: HashMap<Handle<Node>, Mat4>,
+}
+
+impl TransformInterpolation {
+    /// Remembers current global transforms as transforms of previous step.
+    pub(in crate) fn save_previous(&mut self, pool: &Pool<Node>) {
+        self.previous.clear();
+        for (handle, node) in pool.pair_iter() {
+            self.previous.insert(handle, node.global_transform());
+        }
+    }
+
+    /// Replaces global transforms with transforms between previous and current step.
+    pub(in crate) fn interpolate(&mut self, pool: &mut Pool<Node>, alpha: f32, frame_size: Vec2) {
+        self.restore(pool);
+        for (handle, node) in pool.pair_iter_mut() {
+            if let Some(previous) = self.previous.get(&handle) {
+                let current = node.global_transform;
+                if previous.f != current.f {
+                    node.global_transform = interpolate_transform(previous, &current, alpha);
+                    self.simulated.insert(handle, current);
+                }
+            }
+        }
+        // View matrices are calculated from global transforms at update.
+        for (handle, node) in pool.pair_iter_mut() {
+            if let Node::Camera(camera) = node {
+                if self.simulated.contains_key(&handle) {
+                    camera.calculate_matrices(frame_size);
+                }
+            }
+        }
+    }
+
+    /// Forgets simulated transforms, must be called when global transforms are calculated
+    /// again, otherwise `restore` would replace them with outdated ones.
+    pub(in crate) fn discard_simulated(&mut self) {
+        self.simulated.clear();
+    }
+
+    /// Replaces handles of nodes using mapping made by compaction of graph, transforms of
+    /// nodes that are not in the mapping are dropped.
+    pub(in crate) fn remap(&mut self, old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {
+        let remap = |transforms: &mut HashMap<Handle<Node>, Mat4>| {
+            *transforms = transforms
+                .drain()
+                .filter_map(|(handle, transform)| {
+                    old_new_mapping
+                        .get(&handle)
+                        .map(|&handle| (handle, transform))
+                })
+                .collect();
+        };
+        remap(&mut self.previous);
+        remap(&mut self.simulated);
+    }
+
+    /// Restores global transforms which were replaced by `interpolate`.
+    pub(in crate) fn restore(&mut self, pool: &mut Pool<Node>) {
+        if self.simulated.is_empty() {
+            return;
+        }
+        for (handle, node) in pool.pair_iter_mut() {
+            if let Some(simulated) = self.simulated.get(&handle) {
+                node.global_transform = *simulated;
+            }
+        }
+        self.simulated.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::{mat4::Mat4, quat::Quat, vec3::Vec3},
+        scene::interpolation::interpolate_transform,
+    };
+
+    #[test]
+    fn transform_is_interpolated() {
+        let from = Mat4::translate(Vec3::new(0.0, 0.0, 0.0));
+        let to = Mat4::translate(Vec3::new(2.0, 4.0, 0.0))
+            * Mat4::from_quat(Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 1.0))
+            * Mat4::scale(Vec3::new(3.0, 3.0, 3.0));
+        let half = interpolate_transform(&from, &to, 0.5);
+        let position = half.position();
+        assert!((position.x - 1.0).abs() < 1e-5 && (position.y - 2.0).abs() < 1e-5);
+        assert!((half.side().len() - 2.0).abs() < 1e-4);
+        let end = interpolate_transform(&from, &to, 1.0);
+        assert!(end
+            .f
+            .iter()
+            .zip(to.f.iter())
+            .all(|(a, b)| (a - b).abs() < 1e-4));
+    }
+}
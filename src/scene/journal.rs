@@ -0,0 +1,181 @@
+//! Contains change journal of a graph.
+//!
+//! Journal is an opt-in log of changes that were made to a graph - added, removed and moved
+//! nodes and changed properties of nodes. It is a single feed of changes that can be used
+//! by external tools: scene editors can use it to refresh their views, network code can
+//! replicate changes to clients, undo systems can record what was done.
+//!
+//! Structural changes (add, remove, link) are recorded immediately. Properties cannot
+//! be tracked on each write, because nodes can be freely modified through mutable
+//! references, so instead journal keeps snapshot of overridable properties (see
+//! `OverridableProperty`) of each node and compares nodes with snapshots at the end of each
+//! frame (in `Graph::update_nodes`).
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::scene::{graph::Graph, journal::GraphChange};
+//!
+//! fn replicate(graph: &mut Graph) {
+//!     if let Some(journal) = graph.journal_mut() {
+//!         for entry in journal.drain() {
+//!             match entry.change {
+//!                 GraphChange::NodeAdded(_) => { /* Send new node to clients. */ }
+//!                 GraphChange::PropertyChanged { .. } => { /* Send new value. */ }
+//!                 _ => (),
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::{
+    core::{
+        color::Color,
+        math::{quat::Quat, vec3::Vec3},
+        pool::{Handle, Pool},
+    },
+    scene::{base::OverridableProperty, node::Node},
+};
+use std::collections::HashMap;
+
+/// Single change of a graph.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GraphChange {
+    /// Node was added to graph.
+    NodeAdded(Handle<Node>),
+    /// Node was removed from graph. Handle is not valid anymore.
+    NodeRemoved(Handle<Node>),
+    /// Node was attached to new parent.
+    NodeMoved {
+        /// Handle of moved node.
+        node: Handle<Node>,
+        /// Handle of new parent.
+        parent: Handle<Node>,
+    },
+    /// Property of node was changed.
+    PropertyChanged {
+        /// Handle of changed node.
+        node: Handle<Node>,
+        /// Changed property, use node getters to fetch new value.
+        property: OverridableProperty,
+    },
+}
+
+/// Change with index of frame at which change was made.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct JournalEntry {
+    /// Index of frame, starting from the moment when journal was enabled.
+    pub frame: u64,
+    /// Actual change.
+    pub change: GraphChange,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct PropertySnapshot {
+    position: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+    visibility: bool,
+    depth_offset: f32,
+    outline: Option<Color>,
+    layers: u32,
+}
+
+impl PropertySnapshot {
+    fn from_node(node: &Node) -> Self {
+        let transform = node.local_transform();
+        Self {
+            position: transform.position(),
+            rotation: transform.rotation(),
+            scale: transform.scale(),
+            visibility: node.visibility(),
+            depth_offset: node.depth_offset_factor(),
+            outline: node.outline(),
+            layers: node.layers(),
+        }
+    }
+
+    fn differs(&self, other: &Self, property: OverridableProperty) -> bool {
+        match property {
+            OverridableProperty::Position => self.position != other.position,
+            OverridableProperty::Rotation => self.rotation != other.rotation,
+            OverridableProperty::Scale => self.scale != other.scale,
+            OverridableProperty::Visibility => self.visibility != other.visibility,
+            OverridableProperty::DepthOffset => self.depth_offset != other.depth_offset,
+            OverridableProperty::Outline => self.outline != other.outline,
+            OverridableProperty::Layers => self.layers != other.layers,
+        }
+    }
+}
+
+/// See module docs.
+#[derive(Default, Debug)]
+pub struct GraphJournal {
+    frame: u64,
+    entries: Vec<JournalEntry>,
+    snapshots: HashMap<Handle<Node>, PropertySnapshot>,
+}
+
+impl GraphJournal {
+    /// Returns index of current frame.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Returns every change that was recorded since last drain.
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries
+    }
+
+    /// Takes every recorded change out of journal. Should be called by consumer once
+    /// per frame, otherwise journal will grow infinitely.
+    pub fn drain(&mut self) -> Vec<JournalEntry> {
+        std::mem::take(&mut self.entries)
+    }
+
+    pub(in crate) fn record(&mut self, change: GraphChange) {
+        if let GraphChange::NodeRemoved(node) = change {
+            self.snapshots.remove(&node);
+        }
+        self.entries.push(JournalEntry {
+            frame: self.frame,
+            change,
+        });
+    }
+
+    pub(in crate) fn remap(&mut self, old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {
+        self.snapshots = std::mem::take(&mut self.snapshots)
+            .into_iter()
+            .filter_map(|(node, snapshot)| {
+                old_new_mapping.get(&node).map(|&node| (node, snapshot))
+            })
+            .collect();
+    }
+
+    /// Compares nodes with their snapshots, records changed properties and advances
+    /// frame counter. Nodes that have no snapshot yet are not reported, they were added
+    /// during this frame and `NodeAdded` change is already recorded for them.
+    pub(in crate) fn end_frame(&mut self, pool: &Pool<Node>) {
+        for (handle, node) in pool.pair_iter() {
+            let snapshot = PropertySnapshot::from_node(node);
+            if let Some(previous) = self.snapshots.get(&handle) {
+                if *previous != snapshot {
+                    for &property in OverridableProperty::ALL.iter() {
+                        if previous.differs(&snapshot, property) {
+                            self.entries.push(JournalEntry {
+                                frame: self.frame,
+                                change: GraphChange::PropertyChanged {
+                                    node: handle,
+                                    property,
+                                },
+                            });
+                        }
+                    }
+                }
+            }
+            self.snapshots.insert(handle, snapshot);
+        }
+        self.frame += 1;
+    }
+}
@@ -417,6 +417,18 @@ impl Light {
         }
     }
 
+    /// Returns radius of sphere around light source (in world units, scale of light is
+    /// taken into account) outside of which light has no effect. Directional lights affect
+    /// everything, so their radius is `f32::MAX`.
+    pub fn bounding_radius(&self) -> f32 {
+        let raw_radius = match self {
+            Self::Spot(spot_light) => spot_light.distance(),
+            Self::Point(point_light) => point_light.radius(),
+            Self::Directional(_) => return std::f32::MAX,
+        };
+        self.local_transform().scale().max_value() * raw_radius
+    }
+
     define_is_as!(Light : Directional -> ref DirectionalLight => fn is_directional, fn as_directional, fn as_directional_mut);
     define_is_as!(Light : Spot -> ref SpotLight => fn is_spot, fn as_spot, fn as_spot_mut);
     define_is_as!(Light : Point -> ref PointLight => fn is_point, fn as_point, fn as_point_mut);
@@ -635,3 +647,85 @@ impl BaseLightBuilder {
         }
     }
 }
+
+/// Local light probe overrides ambient lighting inside a box volume which moves together
+/// with node which carries the probe. It is useful for interiors of moving objects (train
+/// cars, elevators, ships) - global ambient lighting does not know anything about such
+/// interiors and they will look too bright. Probe is set on a node by
+/// `Base::set_local_probe`, so its volume is defined in local coordinates of the node.
+///
+/// Ambient lighting is blended with global ambient lighting near the boundaries of the
+/// volume, blend distance defines width of such transition zone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalLightProbe {
+    ambient_color: Color,
+    half_extents: Vec3,
+    blend_distance: f32,
+}
+
+impl Default for LocalLightProbe {
+    fn default() -> Self {
+        Self {
+            ambient_color: Color::opaque(100, 100, 100),
+            half_extents: Vec3::new(1.0, 1.0, 1.0),
+            blend_distance: 0.25,
+        }
+    }
+}
+
+impl LocalLightProbe {
+    /// Creates new probe with given ambient color and half extents of box volume.
+    pub fn new(ambient_color: Color, half_extents: Vec3) -> Self {
+        Self {
+            ambient_color,
+            half_extents,
+            ..Default::default()
+        }
+    }
+
+    /// Sets ambient color which will be used inside volume.
+    pub fn set_ambient_color(&mut self, color: Color) -> &mut Self {
+        self.ambient_color = color;
+        self
+    }
+
+    /// Returns ambient color of probe.
+    pub fn ambient_color(&self) -> Color {
+        self.ambient_color
+    }
+
+    /// Sets half extents of box volume in local coordinates of node.
+    pub fn set_half_extents(&mut self, half_extents: Vec3) -> &mut Self {
+        self.half_extents = half_extents;
+        self
+    }
+
+    /// Returns half extents of box volume.
+    pub fn half_extents(&self) -> Vec3 {
+        self.half_extents
+    }
+
+    /// Sets width of transition zone outside of volume where probe is blended with global
+    /// ambient lighting.
+    pub fn set_blend_distance(&mut self, distance: f32) -> &mut Self {
+        self.blend_distance = distance.max(0.0);
+        self
+    }
+
+    /// Returns width of transition zone.
+    pub fn blend_distance(&self) -> f32 {
+        self.blend_distance
+    }
+}
+
+impl Visit for LocalLightProbe {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.ambient_color.visit("AmbientColor", visitor)?;
+        self.half_extents.visit("HalfExtents", visitor)?;
+        self.blend_distance.visit("BlendDistance", visitor)?;
+
+        visitor.leave_region()
+    }
+}
@@ -0,0 +1,658 @@
+//! Contains all structures and methods to create and bake light probe grids.
+//!
+//! Light probe grid is a box volume filled with regularly placed probes, each probe stores
+//! diffuse lighting which comes to its position from every direction. Lighting is stored as
+//! first two bands of spherical harmonics (4 coefficients per color channel), this is enough
+//! to represent soft directional ambient lighting - lit side of a room is brighter than
+//! the other one, floor is lit by sky, but ceiling is not and so on.
+//!
+//! Grid is meant for dynamic objects (characters, props, items) which can't have lightmaps:
+//! renderer blends eight closest probes for every pixel inside the volume and uses result
+//! instead of ambient color of scene. Lightmapped surfaces are modulated by their lightmaps
+//! as usual.
+//!
+//! # Baking
+//!
+//! Probes are baked offline by `LightProbeGrid::bake`, it traces rays from every probe
+//! against visual geometry of scene (see `picking` module), adds contribution of each
+//! unoccluded light source and ambient lighting for rays that escaped the scene (or radiance of
+//! sky cube map of scene environment, if there is one, see `cube_map` module). Rays that
+//! hit geometry carry no light - there is no light bounce, so probes in enclosed spaces get
+//! direct light only. Custom bakers can fill probes via `LightProbeGrid::bake_with`.
+//!
+//! Probes are stored in world space, so grid must be baked again after it was moved or
+//! rotated.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{math::vec3::Vec3, pool::Handle},
+//!     scene::{
+//!         base::BaseBuilder,
+//!         light_probe::{LightProbeBakeSettings, LightProbeGridBuilder},
+//!         node::Node,
+//!         Scene,
+//!     },
+//! };
+//!
+//! fn add_probes(scene: &mut Scene) -> Handle<Node> {
+//!     let handle = scene.graph.add_node(
+//!         LightProbeGridBuilder::new(BaseBuilder::new())
+//!             .with_size(Vec3::new(20.0, 5.0, 20.0))
+//!             .with_resolution(11, 3, 11)
+//!             .build_node(),
+//!     );
+//!     // Global transforms must be valid before baking.
+//!     scene.graph.update_hierachical_data();
+//!     let grid = scene.graph[handle].as_light_probe_grid();
+//!     let probes = grid.bake(scene, &LightProbeBakeSettings::default());
+//!     scene.graph[handle]
+//!         .as_light_probe_grid_mut()
+//!         .set_probes(probes);
+//!     handle
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::{ray::Ray, vec3::Vec3},
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{
+        base::{Base, BaseBuilder},
+        node::Node,
+        picking::GraphRayCastOptions,
+        Scene,
+    },
+    utils::lightmap::{self, LightDefinition},
+};
+use std::{
+    f32::consts::PI,
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// Constant factors of real spherical harmonics basis of band 0 and band 1.
+const Y0: f32 = 0.282_095;
+const Y1: f32 = 0.488_603;
+
+/// Spherical harmonics of first two bands for RGB radiance. Coefficients are ordered as
+/// `Y(0,0)`, `Y(1,-1)` (y), `Y(1,0)` (z), `Y(1,1)` (x), each one holds red, green and blue
+/// channels in x, y and z components respectively.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SphericalHarmonics {
+    /// Raw coefficients of projected radiance.
+    pub coefficients: [Vec3; 4],
+}
+
+impl Default for SphericalHarmonics {
+    fn default() -> Self {
+        Self {
+            coefficients: [Vec3::ZERO; 4],
+        }
+    }
+}
+
+impl SphericalHarmonics {
+    /// Creates harmonics of radiance which is the same in every direction. Such harmonics
+    /// evaluate to given radiance for any normal.
+    pub fn uniform(radiance: Vec3) -> Self {
+        let mut sh = Self::default();
+        sh.coefficients[0] = radiance.scale(Y0 * 4.0 * PI);
+        sh
+    }
+
+    /// Adds radiance which comes from given (normalized) direction. `weight` is a solid
+    /// angle which is covered by sample, for N uniformly distributed samples it is
+    /// `4 * PI / N`.
+    pub fn add_radiance(&mut self, direction: Vec3, radiance: Vec3, weight: f32) {
+        let basis = [Y0, Y1 * direction.y, Y1 * direction.z, Y1 * direction.x];
+        for (coefficient, k) in self.coefficients.iter_mut().zip(basis.iter()) {
+            *coefficient = *coefficient + radiance.scale(k * weight);
+        }
+    }
+
+    /// Adds light which comes from infinitely small source in given (normalized) direction.
+    /// Scale is the same as in renderer's diffuse lighting of light sources, but first two
+    /// bands can't represent such sharp lighting, so a normal looking at the source gets
+    /// only about 3/4 of `color` and the opposite side gets a bit of light too.
+    pub fn add_directional_light(&mut self, direction: Vec3, color: Vec3) {
+        self.add_radiance(direction, color, PI);
+    }
+
+    /// Returns coefficients convolved with cosine lobe and divided by PI, result of
+    /// evaluation then becomes dot product of these coefficients with `(1, n.y, n.z, n.x)`.
+    /// Renderer uploads them to GPU.
+    pub(in crate) fn irradiance_coefficients(&self) -> [Vec3; 4] {
+        // Convolution factors are PI for band 0 and 2PI/3 for band 1.
+        let k0 = Y0;
+        let k1 = Y1 * 2.0 / 3.0;
+        [
+            self.coefficients[0].scale(k0),
+            self.coefficients[1].scale(k1),
+            self.coefficients[2].scale(k1),
+            self.coefficients[3].scale(k1),
+        ]
+    }
+
+    /// Returns diffuse lighting for a surface with given (normalized) normal. Result is in
+    /// the same units as ambient color of scene - it is multiplied by albedo of a surface.
+    pub fn evaluate(&self, normal: Vec3) -> Vec3 {
+        let c = self.irradiance_coefficients();
+        let result = c[0] + c[1].scale(normal.y) + c[2].scale(normal.z) + c[3].scale(normal.x);
+        // First two bands may ring to negative values for very directional lighting.
+        Vec3::new(result.x.max(0.0), result.y.max(0.0), result.z.max(0.0))
+    }
+}
+
+impl Visit for SphericalHarmonics {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        for (i, coefficient) in self.coefficients.iter_mut().enumerate() {
+            coefficient.visit(&format!("C{}", i), visitor)?;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Settings of light probe baking, see `LightProbeGrid::bake`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LightProbeBakeSettings {
+    /// Amount of rays traced from every probe to gather ambient lighting. Default is 64.
+    pub sample_count: usize,
+    /// Max length of rays, geometry which is further away does not occlude anything.
+    /// Default is 100.0.
+    pub max_distance: f32,
+    /// Whether to test visibility of light sources or not. Default is `true`.
+    pub shadows: bool,
+    /// Whether ambient lighting (or sky cube map) of scene should be added to probes or not.
+    /// Default is `true`.
+    pub include_ambient: bool,
+}
+
+impl Default for LightProbeBakeSettings {
+    fn default() -> Self {
+        Self {
+            sample_count: 64,
+            max_distance: 100.0,
+            shadows: true,
+            include_ambient: true,
+        }
+    }
+}
+
+static REVISION: AtomicU64 = AtomicU64::new(1);
+
+// Same as for point clouds - renderer keys GPU textures of grids by revision.
+fn next_revision() -> u64 {
+    REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Returns i-th of `count` directions evenly distributed over unit sphere (Fibonacci
+/// sphere).
+fn sphere_direction(i: usize, count: usize) -> Vec3 {
+    let golden_angle = PI * (3.0 - 5.0f32.sqrt());
+    let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+    let r = (1.0 - y * y).max(0.0).sqrt();
+    let phi = golden_angle * i as f32;
+    Vec3::new(phi.cos() * r, y, phi.sin() * r)
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct LightProbeGrid {
+    base: Base,
+    size: Vec3,
+    resolution: [usize; 3],
+    probes: Vec<SphericalHarmonics>,
+    intensity: f32,
+    blend_distance: f32,
+    revision: u64,
+}
+
+impl Deref for LightProbeGrid {
+    type Target = Base;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for LightProbeGrid {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Default for LightProbeGrid {
+    fn default() -> Self {
+        LightProbeGridBuilder::new(BaseBuilder::new()).build()
+    }
+}
+
+impl LightProbeGrid {
+    /// Returns size of box volume in local coordinates, volume is centered at origin of node.
+    pub fn size(&self) -> Vec3 {
+        self.size
+    }
+
+    /// Sets size of box volume. Probes are not moved, so grid should be baked again.
+    pub fn set_size(&mut self, size: Vec3) {
+        self.size = size;
+        self.revision = next_revision();
+    }
+
+    /// Returns amount of probes along X, Y and Z axes.
+    pub fn resolution(&self) -> [usize; 3] {
+        self.resolution
+    }
+
+    /// Sets amount of probes along each axis, every probe is reset to black. At least one
+    /// probe per axis is used.
+    pub fn set_resolution(&mut self, x: usize, y: usize, z: usize) {
+        self.resolution = [x.max(1), y.max(1), z.max(1)];
+        self.probes = vec![Default::default(); self.probe_count()];
+        self.revision = next_revision();
+    }
+
+    /// Returns total amount of probes.
+    pub fn probe_count(&self) -> usize {
+        self.resolution[0] * self.resolution[1] * self.resolution[2]
+    }
+
+    /// Returns index of a probe in probes array.
+    pub fn probe_index(&self, x: usize, y: usize, z: usize) -> usize {
+        (z * self.resolution[1] + y) * self.resolution[0] + x
+    }
+
+    /// Returns position of a probe in local coordinates of grid.
+    pub fn probe_local_position(&self, x: usize, y: usize, z: usize) -> Vec3 {
+        let coordinate = |i: usize, count: usize, size: f32| {
+            if count > 1 {
+                size * (i as f32 / (count - 1) as f32 - 0.5)
+            } else {
+                0.0
+            }
+        };
+        Vec3::new(
+            coordinate(x, self.resolution[0], self.size.x),
+            coordinate(y, self.resolution[1], self.size.y),
+            coordinate(z, self.resolution[2], self.size.z),
+        )
+    }
+
+    /// Returns shared reference to probes, see `probe_index` for layout.
+    pub fn probes(&self) -> &[SphericalHarmonics] {
+        &self.probes
+    }
+
+    /// Returns mutable reference to probes. Probes will be uploaded to GPU again, even if
+    /// nothing was changed.
+    pub fn probes_mut(&mut self) -> &mut [SphericalHarmonics] {
+        self.revision = next_revision();
+        &mut self.probes
+    }
+
+    /// Replaces every probe, usually with result of `bake`. Does nothing if amount of
+    /// probes does not match resolution of grid.
+    pub fn set_probes(&mut self, probes: Vec<SphericalHarmonics>) {
+        if probes.len() == self.probe_count() {
+            self.probes = probes;
+            self.revision = next_revision();
+        }
+    }
+
+    /// Sets multiplier of lighting stored in probes.
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity.max(0.0);
+    }
+
+    /// Returns multiplier of lighting stored in probes.
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    /// Sets width of transition zone outside of volume where grid is blended with global
+    /// ambient lighting.
+    pub fn set_blend_distance(&mut self, distance: f32) {
+        self.blend_distance = distance.max(0.0);
+    }
+
+    /// Returns width of transition zone.
+    pub fn blend_distance(&self) -> f32 {
+        self.blend_distance
+    }
+
+    pub(in crate) fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Returns diffuse lighting at given point in world coordinates for a surface with
+    /// given normal, eight closest probes are blended. This is exactly what renderer does
+    /// for pixels inside of volume, so it can be used to light objects which are drawn by
+    /// other means.
+    pub fn sample(&self, position: Vec3, normal: Vec3) -> Vec3 {
+        let local = self
+            .global_transform()
+            .inverse()
+            .unwrap_or_default()
+            .transform_vector(position);
+
+        // Continuous probe coordinates, clamped to the volume.
+        let grid_coordinate = |value: f32, size: f32, count: usize| {
+            if count > 1 && size > 0.0 {
+                ((value / size + 0.5) * (count - 1) as f32)
+                    .max(0.0)
+                    .min((count - 1) as f32)
+            } else {
+                0.0
+            }
+        };
+        let p = [
+            grid_coordinate(local.x, self.size.x, self.resolution[0]),
+            grid_coordinate(local.y, self.size.y, self.resolution[1]),
+            grid_coordinate(local.z, self.size.z, self.resolution[2]),
+        ];
+
+        let mut result = Vec3::ZERO;
+        for corner in 0..8 {
+            let mut index = [0; 3];
+            let mut weight = 1.0;
+            for axis in 0..3 {
+                let low = p[axis].floor();
+                let t = p[axis] - low;
+                let high = corner & (1 << axis) != 0;
+                index[axis] = (low as usize + high as usize).min(self.resolution[axis] - 1);
+                weight *= if high { t } else { 1.0 - t };
+            }
+            if weight > 0.0 {
+                let probe = &self.probes[self.probe_index(index[0], index[1], index[2])];
+                result = result + probe.evaluate(normal).scale(weight);
+            }
+        }
+        result.scale(self.intensity)
+    }
+
+    /// Fills probes using given function, which is called with world position of a probe
+    /// and must return projected lighting at that position. Returns new probes, they can
+    /// be assigned to the grid by `set_probes`.
+    pub fn bake_with<F>(&self, mut func: F) -> Vec<SphericalHarmonics>
+    where
+        F: FnMut(Vec3) -> SphericalHarmonics,
+    {
+        let transform = self.global_transform();
+        let mut probes = Vec::with_capacity(self.probe_count());
+        for z in 0..self.resolution[2] {
+            for y in 0..self.resolution[1] {
+                for x in 0..self.resolution[0] {
+                    let local = self.probe_local_position(x, y, z);
+                    probes.push(func(transform.transform_vector(local)));
+                }
+            }
+        }
+        probes
+    }
+
+    /// Bakes lighting of given scene into probes, see module docs. Global transforms of
+    /// scene nodes must be up to date. This is slow, probes should be baked offline and
+    /// saved together with scene.
+    pub fn bake(
+        &self,
+        scene: &Scene,
+        settings: &LightProbeBakeSettings,
+    ) -> Vec<SphericalHarmonics> {
+        let lights = lightmap::collect_lights(scene);
+        let ambient = scene.environment.ambient_light();
+        let ambient = Vec3::new(ambient.x, ambient.y, ambient.z);
+        let sky = scene
+            .environment
+            .sky_cube_map
+            .as_ref()
+            .map(|sky| sky.lock().unwrap());
+        let options = GraphRayCastOptions {
+            include_terrains: true,
+            sort_results: false,
+            ..Default::default()
+        };
+        let mut hits = Vec::new();
+        let mut is_occluded = |from: Vec3, to: Vec3| {
+            hits.clear();
+            match Ray::from_two_points(&from, &to) {
+                Some(ray) => scene.graph.ray_cast(&ray, options, &mut hits),
+                None => false,
+            }
+        };
+
+        self.bake_with(|position| {
+            let mut sh = SphericalHarmonics::default();
+
+            if settings.include_ambient && settings.sample_count > 0 {
+                let weight = 4.0 * PI / settings.sample_count as f32;
+                for i in 0..settings.sample_count {
+                    let direction = sphere_direction(i, settings.sample_count);
+                    let end = position + direction.scale(settings.max_distance);
+                    if !is_occluded(position, end) {
+                        let radiance = match sky.as_ref() {
+                            Some(sky) if sky.is_loaded() => sky.sample(direction),
+                            _ => ambient,
+                        };
+                        sh.add_radiance(direction, radiance, weight);
+                    }
+                }
+            }
+
+            for (_, light) in lights.iter() {
+                let (light_vec, color, attenuation, light_position) = match light {
+                    LightDefinition::Directional(directional) => {
+                        let end = position + directional.direction.scale(settings.max_distance);
+                        (
+                            directional.direction,
+                            directional.color,
+                            directional.intensity,
+                            end,
+                        )
+                    }
+                    LightDefinition::Spot(spot) => {
+                        let d = spot.position - position;
+                        let distance = d.len();
+                        let light_vec = d.normalized().unwrap_or(Vec3::UP);
+                        let cone_factor = lightmap::smoothstep(
+                            ((spot.hotspot_cone_angle + spot.falloff_angle_delta) * 0.5).cos(),
+                            (spot.hotspot_cone_angle * 0.5).cos(),
+                            light_vec.dot(&spot.direction),
+                        );
+                        let attenuation = cone_factor
+                            * spot.intensity
+                            * lightmap::distance_attenuation(distance, spot.distance);
+                        (light_vec, spot.color, attenuation, spot.position)
+                    }
+                    LightDefinition::Point(point) => {
+                        let d = point.position - position;
+                        let distance = d.len();
+                        let light_vec = d.normalized().unwrap_or(Vec3::UP);
+                        let attenuation = point.intensity
+                            * lightmap::distance_attenuation(distance, point.radius);
+                        (light_vec, point.color, attenuation, point.position)
+                    }
+                };
+
+                if attenuation <= 0.0
+                    || settings.shadows && is_occluded(position, light_position)
+                {
+                    continue;
+                }
+
+                let color = Vec3::new(
+                    color.r as f32 / 255.0,
+                    color.g as f32 / 255.0,
+                    color.b as f32 / 255.0,
+                );
+                sh.add_directional_light(light_vec, color.scale(attenuation));
+            }
+
+            sh
+        })
+    }
+}
+
+impl Visit for LightProbeGrid {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Base", visitor)?;
+        self.size.visit("Size", visitor)?;
+        let mut resolution_x = self.resolution[0] as u32;
+        resolution_x.visit("ResolutionX", visitor)?;
+        let mut resolution_y = self.resolution[1] as u32;
+        resolution_y.visit("ResolutionY", visitor)?;
+        let mut resolution_z = self.resolution[2] as u32;
+        resolution_z.visit("ResolutionZ", visitor)?;
+        self.probes.visit("Probes", visitor)?;
+        self.intensity.visit("Intensity", visitor)?;
+        self.blend_distance.visit("BlendDistance", visitor)?;
+
+        if visitor.is_reading() {
+            self.resolution = [
+                (resolution_x as usize).max(1),
+                (resolution_y as usize).max(1),
+                (resolution_z as usize).max(1),
+            ];
+            // Product of resolutions can overflow usize on 32-bit targets.
+            let probe_count = self.resolution[0]
+                .checked_mul(self.resolution[1])
+                .and_then(|count| count.checked_mul(self.resolution[2]));
+            if probe_count != Some(self.probes.len()) {
+                return Err("Light probe grid is corrupted!".to_owned().into());
+            }
+            self.revision = next_revision();
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Light probe grid builder allows you to construct grid in declarative manner.
+pub struct LightProbeGridBuilder {
+    base_builder: BaseBuilder,
+    size: Vec3,
+    resolution: [usize; 3],
+    probes: Vec<SphericalHarmonics>,
+    intensity: f32,
+    blend_distance: f32,
+}
+
+impl LightProbeGridBuilder {
+    /// Creates new builder of 10x10x10 meters grid with 4 probes along each axis.
+    pub fn new(base_builder: BaseBuilder) -> Self {
+        Self {
+            base_builder,
+            size: Vec3::new(10.0, 10.0, 10.0),
+            resolution: [4, 4, 4],
+            probes: Default::default(),
+            intensity: 1.0,
+            blend_distance: 1.0,
+        }
+    }
+
+    /// Sets desired size of box volume.
+    pub fn with_size(mut self, size: Vec3) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets desired amount of probes along each axis.
+    pub fn with_resolution(mut self, x: usize, y: usize, z: usize) -> Self {
+        self.resolution = [x.max(1), y.max(1), z.max(1)];
+        self
+    }
+
+    /// Sets baked probes, they're ignored if their amount does not match resolution.
+    pub fn with_probes(mut self, probes: Vec<SphericalHarmonics>) -> Self {
+        self.probes = probes;
+        self
+    }
+
+    /// Sets desired multiplier of lighting stored in probes.
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity.max(0.0);
+        self
+    }
+
+    /// Sets desired width of transition zone outside of volume.
+    pub fn with_blend_distance(mut self, distance: f32) -> Self {
+        self.blend_distance = distance.max(0.0);
+        self
+    }
+
+    /// Creates new light probe grid instance.
+    pub fn build(self) -> LightProbeGrid {
+        let probe_count = self.resolution[0] * self.resolution[1] * self.resolution[2];
+        LightProbeGrid {
+            base: self.base_builder.build(),
+            size: self.size,
+            resolution: self.resolution,
+            probes: if self.probes.len() == probe_count {
+                self.probes
+            } else {
+                vec![Default::default(); probe_count]
+            },
+            intensity: self.intensity,
+            blend_distance: self.blend_distance,
+            revision: next_revision(),
+        }
+    }
+
+    /// Creates new node instance.
+    pub fn build_node(self) -> Node {
+        Node::LightProbeGrid(self.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::vec3::Vec3,
+        scene::{
+            base::BaseBuilder,
+            light_probe::{LightProbeGridBuilder, SphericalHarmonics},
+        },
+    };
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).len() < 0.001, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn uniform_radiance_is_preserved() {
+        let radiance = Vec3::new(0.2, 0.4, 0.6);
+        let sh = SphericalHarmonics::uniform(radiance);
+        assert_close(sh.evaluate(Vec3::UP), radiance);
+        assert_close(sh.evaluate(Vec3::new(1.0, 0.0, 0.0)), radiance);
+    }
+
+    #[test]
+    fn directional_light_lights_facing_side() {
+        let mut sh = SphericalHarmonics::default();
+        sh.add_directional_light(Vec3::UP, Vec3::new(1.0, 1.0, 1.0));
+        let up = sh.evaluate(Vec3::UP);
+        let down = sh.evaluate(Vec3::new(0.0, -1.0, 0.0));
+        assert!(up.x > 0.7 && up.x < 0.8);
+        assert!(down.x < 0.01);
+    }
+
+    #[test]
+    fn grid_sample_blends_probes() {
+        let mut grid = LightProbeGridBuilder::new(BaseBuilder::new())
+            .with_size(Vec3::new(2.0, 2.0, 2.0))
+            .with_resolution(2, 1, 1)
+            .build();
+        grid.probes_mut()[0] = SphericalHarmonics::uniform(Vec3::ZERO);
+        grid.probes_mut()[1] = SphericalHarmonics::uniform(Vec3::new(1.0, 1.0, 1.0));
+        assert_close(grid.sample(Vec3::new(0.0, 0.0, 0.0), Vec3::UP), Vec3::new(0.5, 0.5, 0.5));
+        // Outside of volume closest probes are used.
+        assert_close(grid.sample(Vec3::new(5.0, 0.0, 0.0), Vec3::UP), Vec3::new(1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,137 @@
+//! Contains background scene loader.
+//!
+//! Loading of big scenes can take seconds, which is unacceptable if game must stay
+//! responsive (show loading screen with animations, for example). `SceneLoader` loads a
+//! scene as a job of global job system, main thread just polls loader each frame to check progress
+//! and to take loaded scene. Loaded scene can be added to engine as usual, or it can be
+//! attached to existing scene as a chunk using `Scene::attach_chunk` which allows to stream
+//! big levels piece by piece.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     engine::resource_manager::ResourceManager,
+//!     scene::{loader::SceneLoader, Scene},
+//! };
+//! use std::sync::{Arc, Mutex};
+//!
+//! fn start_loading(resource_manager: Arc<Mutex<ResourceManager>>) -> SceneLoader {
+//!     SceneLoader::new("data/level_chunk_2.rgs", resource_manager)
+//! }
+//!
+//! // Called each frame.
+//! fn poll(loader: &mut SceneLoader, level: &mut Scene) {
+//!     println!("Loading: {}%", loader.progress() * 100.0);
+//!     if let Some(Ok(chunk)) = loader.try_take() {
+//!         level.attach_chunk(chunk);
+//!     }
+//! }
+//! ```
+
+use crate::{
+    core::visitor::{Visit, VisitError},
+    engine::resource_manager::ResourceManager,
+    scene::Scene,
+    utils::{
+        jobs::JobSystem,
+        log::{Log, LogCategory},
+    },
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time,
+};
+
+#[derive(Default)]
+struct LoaderState {
+    progress: f32,
+    result: Option<Result<Scene, VisitError>>,
+}
+
+/// See module docs.
+pub struct SceneLoader {
+    path: PathBuf,
+    state: Arc<Mutex<LoaderState>>,
+}
+
+fn set_progress(state: &Mutex<LoaderState>, progress: f32) {
+    state.lock().unwrap().progress = progress;
+}
+
+fn load(
+    path: &Path,
+    resource_manager: &Mutex<ResourceManager>,
+    state: &Mutex<LoaderState>,
+) -> Result<Scene, VisitError> {
+    let mut scene = Scene::default();
+    let vfs = resource_manager.lock().unwrap().vfs().clone();
+    let mut visitor = vfs.load_visitor(path)?;
+    scene.visit("Scene", &mut visitor)?;
+    set_progress(state, 0.2);
+
+    // Resource manager is locked per node, so main thread won't be blocked for a long
+    // time if it needs resource manager too.
+    let node_count = scene.graph.node_count().max(1) as f32;
+    for (i, node) in scene.graph.linear_iter_mut().enumerate() {
+        node.restore_resources(&mut resource_manager.lock().unwrap());
+        set_progress(state, 0.2 + 0.7 * (i + 1) as f32 / node_count);
+    }
+
+    scene.resolve();
+
+    Ok(scene)
+}
+
+impl SceneLoader {
+    /// Starts loading of scene from given file in background using global job system, see
+    /// `utils::jobs`.
+    pub fn new<P: AsRef<Path>>(path: P, resource_manager: Arc<Mutex<ResourceManager>>) -> Self {
+        let path = path.as_ref().to_owned();
+        let state = Arc::new(Mutex::new(LoaderState::default()));
+
+        let thread_state = state.clone();
+        let thread_path = path.clone();
+        JobSystem::global().spawn(move || {
+            let time = time::Instant::now();
+            let result = load(&thread_path, &resource_manager, &thread_state);
+            match result.as_ref() {
+                Ok(_) => Log::info(
+                    LogCategory::Scene,
+                    format!("Scene {:?} is loaded in {:?}!", thread_path, time.elapsed()),
+                ),
+                Err(e) => Log::err(
+                    LogCategory::Scene,
+                    format!("Unable to load scene {:?}! Reason: {:?}", thread_path, e),
+                ),
+            }
+            let mut state = thread_state.lock().unwrap();
+            state.progress = 1.0;
+            state.result = Some(result);
+        });
+
+        Self { path, state }
+    }
+
+    /// Returns path of scene file.
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Returns approximate loading progress in [0; 1] range.
+    pub fn progress(&self) -> f32 {
+        self.state.lock().unwrap().progress
+    }
+
+    /// Returns true if loading is finished (successfully or not) and result wasn't taken.
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().unwrap().result.is_some()
+    }
+
+    /// Returns loading result if loading is finished, otherwise returns None. Result can
+    /// be taken only once, every next call will return None.
+    pub fn try_take(&mut self) -> Option<Result<Scene, VisitError>> {
+        self.state.lock().unwrap().result.take()
+    }
+}
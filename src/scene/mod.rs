@@ -4,34 +4,70 @@
 //!
 //! Scene is container for graph nodes, animations and physics.
 
+pub mod audio_environment;
 pub mod base;
+pub mod bounds_tree;
 pub mod camera;
+pub mod camera_effects;
+pub mod component;
+pub mod coroutine;
+pub mod dim2;
+pub mod environment;
+pub mod fragment;
 pub mod graph;
+pub mod interpolation;
+pub mod journal;
 pub mod light;
+pub mod light_probe;
+pub mod loader;
 pub mod mesh;
 pub mod node;
 pub mod particle_system;
+pub mod picking;
+pub mod point_cloud;
+pub mod portal;
+pub mod scatter;
+pub mod spatial_hash;
+pub mod spline;
 pub mod sprite;
+pub mod terrain;
 pub mod transform;
+pub mod typed_handle;
+pub mod visibility;
+pub mod water;
 
 use crate::{
-    animation::AnimationContainer,
+    animation::{machine::MachineContainer, AnimationContainer},
     core::{
-        math::vec2::Vec2,
-        pool::{Handle, Pool, PoolIterator, PoolIteratorMut},
+        math::{ray::Ray, vec2::Vec2, vec3::Vec3},
+        pool::{Handle, Pool, PoolIterator, PoolIteratorMut, PoolPairIterator},
         visitor::{Visit, VisitError, VisitResult, Visitor},
     },
-    engine::resource_manager::ResourceManager,
-    physics::{rigid_body::RigidBody, Physics},
-    resource::texture::Texture,
-    scene::{graph::Graph, node::Node},
-    utils::{lightmap::Lightmap, log::Log},
+    engine::{
+        dependencies::{DependencyCollector, ResourceId},
+        resource_manager::ResourceManager,
+    },
+    physics::{rigid_body::RigidBody, HitKind, Physics, RayCastOptions, RayCastResult},
+    profile_scope,
+    resource::{prefab::Prefab, texture::Texture},
+    scene::{
+        audio_environment::AudioEnvironmentContainer, coroutine::CoroutineContainer,
+        dim2::physics::Physics2D, environment::SceneEnvironment, graph::Graph, node::Node,
+        portal::PortalSystem, spatial_hash::SpatialHash,
+    },
+    utils::{
+        jobs::JobSystem,
+        lightmap::Lightmap,
+        log::{Log, LogCategory},
+    },
 };
+use rayon::prelude::*;
 use std::{
     collections::HashMap,
     ops::{Index, IndexMut},
     path::Path,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
 /// Physics binder is used to link graph nodes with rigid bodies. Scene will
@@ -100,6 +136,24 @@ impl PhysicsBinder {
             .copied()
             .unwrap_or_default()
     }
+
+    fn body_node_map(&self) -> HashMap<Handle<RigidBody>, Handle<Node>> {
+        self.node_rigid_body_map
+            .iter()
+            .map(|(&node, &body)| (body, node))
+            .collect()
+    }
+}
+
+fn node_of_hit(
+    body_node_map: &HashMap<Handle<RigidBody>, Handle<Node>>,
+    result: &RayCastResult,
+) -> Handle<Node> {
+    if let HitKind::Body(body) = result.kind {
+        body_node_map.get(&body).copied().unwrap_or_default()
+    } else {
+        Handle::NONE
+    }
 }
 
 impl Visit for PhysicsBinder {
@@ -112,6 +166,47 @@ impl Visit for PhysicsBinder {
     }
 }
 
+/// Result of scene compaction, see `Scene::compact` for more info.
+#[derive(Clone, Debug, Default)]
+pub struct CompactionReport {
+    /// Old-to-new node mapping. Every handle to a node that was obtained before
+    /// compaction must be remapped using this map.
+    pub old_new_mapping: HashMap<Handle<Node>, Handle<Node>>,
+
+    /// Amount of vacant entries that were removed from graph's pool.
+    pub removed_vacant_entries: usize,
+
+    /// Approximate amount of memory (in bytes) that was given back to allocator.
+    pub reclaimed_bytes: usize,
+}
+
+/// Closest hit of a ray from a batch, see `Scene::ray_cast_batch` for more info.
+#[derive(Clone, Debug)]
+pub struct BatchRayHit {
+    /// Ray cast result from physics.
+    pub result: RayCastResult,
+
+    /// Node that is linked with hit rigid body. It is `Handle::NONE` if static geometry
+    /// was hit or if hit body is not linked with any node.
+    pub node: Handle<Node>,
+}
+
+/// Line of sight query, see `Scene::line_of_sight_batch` for more info.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LineOfSightQuery {
+    /// Position of eyes of observer.
+    pub begin: Vec3,
+
+    /// Position of the point that observer is looking at.
+    pub end: Vec3,
+
+    /// Node of observer, its rigid body (if any) won't block line of sight.
+    pub observer: Handle<Node>,
+
+    /// Node of target, its rigid body (if any) won't block line of sight.
+    pub target: Handle<Node>,
+}
+
 /// See module docs.
 #[derive(Debug)]
 pub struct Scene {
@@ -124,6 +219,10 @@ pub struct Scene {
     /// has handles to graph nodes. See `animation` module docs for more info.
     pub animations: AnimationContainer,
 
+    /// Animation blending state machines. Scene evaluates every machine and applies its pose
+    /// to the graph on every update. See `animation::machine` module docs for more info.
+    pub animation_machines: MachineContainer,
+
     /// Physics world. Allows you create various physics objects such as static geometries and
     /// rigid bodies. Rigid bodies then should be linked with graph nodes using binder.
     pub physics: Physics,
@@ -132,6 +231,10 @@ pub struct Scene {
     /// to a graph node, then rigid body will control local transform of node.
     pub physics_binder: PhysicsBinder,
 
+    /// Simple 2D physics, its rigid bodies are bound to nodes directly. See `dim2` module
+    /// docs for more info.
+    pub physics2d: Physics2D,
+
     /// Texture to draw scene to. If empty, scene will be drawn on screen directly.
     /// It is useful to "embed" some scene into other by drawing a quad with this
     /// texture. This can be used to make in-game video conference - you can make
@@ -141,7 +244,28 @@ pub struct Scene {
     /// in real-time strategies, in other words there are plenty of possible uses.
     pub render_target: Option<Arc<Mutex<Texture>>>,
 
+    /// Coroutines owned by scene nodes. Coroutine is cancelled automatically when its
+    /// owner is removed. See `coroutine` module docs for more info.
+    pub coroutines: CoroutineContainer,
+
+    /// Spatial hash for fast proximity queries between registered nodes. Scene updates it
+    /// automatically. See `spatial_hash` module docs for more info.
+    pub spatial_hash: SpatialHash,
+
+    /// Audio environment volumes which drive reverb of sound context depending on position
+    /// of listener. See `audio_environment` module docs for more info.
+    pub audio_environment: AudioEnvironmentContainer,
+
+    /// Zones and portals which are used by renderer to cull rooms of indoor scenes that
+    /// can't be seen from camera. See `portal` module docs for more info.
+    pub portals: PortalSystem,
+
+    /// Global settings of scene, like ambient lighting, fog, sky and wind. See
+    /// `environment` module docs for more info.
+    pub environment: SceneEnvironment,
+
     lightmap: Option<Lightmap>,
+    physics_time: Duration,
 }
 
 impl Default for Scene {
@@ -149,10 +273,18 @@ impl Default for Scene {
         Self {
             graph: Default::default(),
             animations: Default::default(),
+            animation_machines: Default::default(),
             physics: Default::default(),
             physics_binder: Default::default(),
+            physics2d: Default::default(),
             render_target: None,
+            coroutines: Default::default(),
+            spatial_hash: Default::default(),
+            audio_environment: Default::default(),
+            portals: Default::default(),
+            environment: Default::default(),
             lightmap: None,
+            physics_time: Default::default(),
         }
     }
 }
@@ -171,9 +303,17 @@ impl Scene {
             graph: Graph::new(),
             physics: Default::default(),
             animations: Default::default(),
+            animation_machines: Default::default(),
             physics_binder: Default::default(),
+            physics2d: Default::default(),
             render_target: None,
+            coroutines: Default::default(),
+            spatial_hash: Default::default(),
+            audio_environment: Default::default(),
+            portals: Default::default(),
+            environment: Default::default(),
             lightmap: None,
+            physics_time: Default::default(),
         }
     }
 
@@ -184,17 +324,10 @@ impl Scene {
         resource_manager: &mut ResourceManager,
     ) -> Result<Self, VisitError> {
         let mut scene = Scene::default();
-        let mut visitor = Visitor::load_binary(path.as_ref())?;
+        let mut visitor = resource_manager.vfs().load_visitor(path.as_ref())?;
         scene.visit("Scene", &mut visitor)?;
 
-        // Restore pointers to resources. Scene saves only paths to resources, here we must
-        // find real resources instead.
-        for node in scene.graph.linear_iter_mut() {
-            if let Some(shallow_resource) = node.resource.clone() {
-                node.resource =
-                    resource_manager.request_model(&shallow_resource.lock().unwrap().path);
-            }
-        }
+        scene.restore_resources(resource_manager);
 
         // And do resolve to extract correct graphical data and so on.
         scene.resolve();
@@ -202,6 +335,135 @@ impl Scene {
         Ok(scene)
     }
 
+    /// Restores pointers to resources. Scene saves only paths to resources, here we must
+    /// find real resources instead.
+    pub(in crate) fn restore_resources(&mut self, resource_manager: &mut ResourceManager) {
+        for node in self.graph.linear_iter_mut() {
+            node.restore_resources(resource_manager);
+        }
+        self.environment.restore_resources(resource_manager);
+    }
+
+    /// Returns every resource used by the scene: models and prefabs of instances, textures,
+    /// gradients and sky cube map. Each resource is listed once. See `dependencies` module
+    /// docs for more info.
+    pub fn resource_dependencies(&self) -> Vec<ResourceId> {
+        let mut collector = DependencyCollector::default();
+        collector.add_graph(&self.graph);
+        collector.add_environment(&self.environment);
+        if let Some(lightmap) = self.lightmap.as_ref() {
+            for entries in lightmap.map.values() {
+                for entry in entries {
+                    collector.add_texture(entry.texture.clone());
+                }
+            }
+        }
+        collector.into_ids()
+    }
+
+    /// Moves every node and animation of given scene into this scene. This is intended
+    /// to be used for level streaming - big level can be split into chunks that are loaded
+    /// in background by `SceneLoader` and attached when they're ready. Top-level nodes of
+    /// chunk are attached to root of graph. Returns old-to-new node mapping.
+    ///
+    /// # Notes
+    ///
+    /// Physics and lightmap of chunk are *not* transferred, use `merge` if you need them.
+    pub fn attach_chunk(&mut self, mut chunk: Scene) -> HashMap<Handle<Node>, Handle<Node>> {
+        let old_new_map = self.graph.merge(std::mem::take(&mut chunk.graph));
+
+        for animation in chunk.animations.iter() {
+            let mut animation = animation.clone();
+            animation.retain_tracks(|track| old_new_map.contains_key(&track.get_node()));
+            for track in animation.get_tracks_mut() {
+                track.set_node(old_new_map[&track.get_node()]);
+            }
+            animation
+                .retain_parameter_tracks(|track| old_new_map.contains_key(&track.binding().node));
+            for track in animation.get_parameter_tracks_mut() {
+                let mut binding = track.binding();
+                binding.node = old_new_map[&binding.node];
+                track.set_binding(binding);
+            }
+            animation.remap_root_motion_node(&old_new_map);
+            self.animations.add(animation);
+        }
+
+        old_new_map
+    }
+
+    /// Moves every node, animation, physical body (both 3D and 2D), static geometry, audio
+    /// environment volume, portal and lightmap of other scene into this scene. This is intended
+    /// to be used by tools to compose levels from multiple scenes that were authored separately.
+    /// Top-level nodes of other scene are attached to root of graph. Returns old-to-new node
+    /// mapping, every handle to a node of other scene that is stored somewhere else must be
+    /// remapped using it.
+    ///
+    /// # Notes
+    ///
+    /// Handles of rigid bodies and static geometries of other scene are changed too, bodies
+    /// stay bound to their (remapped) nodes. Coroutines, render target and environment
+    /// settings of other scene are dropped.
+    pub fn merge(&mut self, mut other: Scene) -> HashMap<Handle<Node>, Handle<Node>> {
+        let physics = std::mem::take(&mut other.physics);
+        let physics_binder = std::mem::take(&mut other.physics_binder);
+        let mut physics2d = std::mem::take(&mut other.physics2d);
+        let mut audio_environment = std::mem::take(&mut other.audio_environment);
+        let mut portals = std::mem::take(&mut other.portals);
+        let lightmap = other.lightmap.take();
+
+        let old_new_map = self.attach_chunk(other);
+
+        let body_node_map = physics_binder.body_node_map();
+        for (body_handle, body) in physics.bodies().pair_iter() {
+            let new_body = self.physics.add_body(body.clone());
+            if let Some(new_node) = body_node_map
+                .get(&body_handle)
+                .and_then(|node| old_new_map.get(node))
+            {
+                self.physics_binder.bind(*new_node, new_body);
+            }
+        }
+        for static_geometry in physics.static_geoms().iter() {
+            self.physics.add_static_geometry(static_geometry.clone());
+        }
+
+        physics2d.remap(&old_new_map);
+        for body in physics2d.bodies() {
+            self.physics2d.add_body(*body);
+        }
+
+        audio_environment.remap(&old_new_map);
+        for volume in audio_environment.volumes() {
+            self.audio_environment.add(*volume);
+        }
+
+        portals.remap(&old_new_map);
+        for zone in portals.zones() {
+            self.portals.add_zone(*zone);
+        }
+        for portal in portals.portals() {
+            self.portals.add_portal(*portal);
+        }
+
+        if let Some(lightmap) = lightmap {
+            let map = &mut self.lightmap.get_or_insert_with(Default::default).map;
+            for (node, mut entries) in lightmap.map {
+                if let Some(&new_node) = old_new_map.get(&node) {
+                    for entry in entries.iter_mut() {
+                        entry.lights.retain(|light| old_new_map.contains_key(light));
+                        for light in entry.lights.iter_mut() {
+                            *light = old_new_map[light];
+                        }
+                    }
+                    map.insert(new_node, entries);
+                }
+            }
+        }
+
+        old_new_map
+    }
+
     fn update_physics(&mut self, dt: f32) {
         self.physics.step(dt);
 
@@ -223,7 +485,7 @@ impl Scene {
         }
     }
 
-    /// Removes node from scene with all associated entities, like animations etc.
+    /// Removes node from scene with all associated entities, like animations, coroutines etc.
     ///
     /// # Panics
     ///
@@ -239,16 +501,22 @@ impl Scene {
                 }
                 true
             });
+
+            self.coroutines.stop_all_of(descendant);
+            self.spatial_hash.unregister(descendant);
+            self.audio_environment.remove_volumes_of(descendant);
+            self.portals.remove_of(descendant);
+            self.physics2d.remove_of(descendant);
         }
 
         self.graph.remove_node(handle)
     }
 
     pub(in crate) fn resolve(&mut self) {
-        Log::writeln("Starting resolve...".to_owned());
+        Log::debug(LogCategory::Scene, "Starting resolve...");
         self.graph.resolve();
         self.animations.resolve(&self.graph);
-        Log::writeln("Resolve succeeded!".to_owned());
+        Log::debug(LogCategory::Scene, "Resolve succeeded!");
     }
 
     /// Tries to set new lightmap to scene.
@@ -272,12 +540,275 @@ impl Scene {
     }
 
     /// Performs single update tick with given delta time from last frame. Internally
-    /// it updates physics, animations, and each graph node. In most cases there is
+    /// it updates physics, animations, each graph node and coroutines. In most cases there is
     /// no need to call it directly, engine automatically updates all available scenes.
     pub fn update(&mut self, frame_size: Vec2, dt: f32) {
-        self.update_physics(dt);
-        self.animations.update_animations(dt);
+        self.update_with_physics(frame_size, dt, true)
+    }
+
+    /// Same as `update`, but physics is simulated only if `physics` is true. Engine uses it
+    /// when physics is disabled, see `EngineBuilder::with_physics`.
+    pub(in crate) fn update_with_physics(&mut self, frame_size: Vec2, dt: f32, physics: bool) {
+        profile_scope!("SceneUpdate");
+
+        let physics_start = Instant::now();
+        if physics {
+            {
+                profile_scope!("Physics");
+                self.update_physics(dt);
+            }
+            {
+                profile_scope!("Physics2D");
+                self.physics2d.step(&mut self.graph, dt);
+            }
+        }
+        self.physics_time = physics_start.elapsed();
+        {
+            profile_scope!("Animations");
+            self.animations.update_animations_scaled(dt, &self.graph);
+            self.animation_machines
+                .update(&self.animations, &mut self.graph, dt);
+        }
+        for node in self.graph.linear_iter_mut() {
+            if let Node::ParticleSystem(particle_system) = node {
+                particle_system.wind = self.environment.wind;
+            }
+        }
         self.graph.update_nodes(frame_size, dt);
+        {
+            profile_scope!("SpatialHash");
+            self.spatial_hash.update(&self.graph);
+        }
+        {
+            profile_scope!("Coroutines");
+            self.coroutines.update(&mut self.graph, dt);
+        }
+    }
+
+    /// Returns time spent by last update on 3D and 2D physics.
+    pub fn physics_time(&self) -> Duration {
+        self.physics_time
+    }
+
+    /// Defragments graph's pool of nodes, removes dead particles and releases excessive
+    /// memory of particle systems and meshes. It is useful for long-running applications
+    /// that adds and removes lots of nodes, like servers or streaming worlds. Animations,
+    /// physics binder, 2D physics and lightmap will be remapped to new handles automatically,
+    /// but every other handle to a node must be remapped using `old_new_mapping` of returned
+    /// report.
+    ///
+    /// # Performance
+    ///
+    /// This method moves every node of a graph, so it should not be called on each frame.
+    pub fn compact(&mut self) -> CompactionReport {
+        let old_capacity = self.graph.capacity();
+        let old_new_mapping = self.graph.compact();
+        let removed_vacant_entries = old_capacity - self.graph.capacity();
+
+        for animation in self.animations.iter_mut() {
+            animation.retain_tracks(|track| old_new_mapping.contains_key(&track.get_node()));
+            for track in animation.get_tracks_mut() {
+                track.set_node(old_new_mapping[&track.get_node()]);
+            }
+            animation.retain_parameter_tracks(|track| {
+                old_new_mapping.contains_key(&track.binding().node)
+            });
+            for track in animation.get_parameter_tracks_mut() {
+                let mut binding = track.binding();
+                binding.node = old_new_mapping[&binding.node];
+                track.set_binding(binding);
+            }
+            animation.remap_root_motion_node(&old_new_mapping);
+        }
+
+        self.physics_binder.node_rigid_body_map = self
+            .physics_binder
+            .node_rigid_body_map
+            .iter()
+            .filter_map(|(node, &body)| old_new_mapping.get(node).map(|&node| (node, body)))
+            .collect();
+
+        self.coroutines.remap_owners(&old_new_mapping);
+        self.spatial_hash.remap(&old_new_mapping);
+        self.audio_environment.remap(&old_new_mapping);
+        self.portals.remap(&old_new_mapping);
+        self.physics2d.remap(&old_new_mapping);
+
+        if let Some(lightmap) = self.lightmap.as_mut() {
+            lightmap.map = std::mem::take(&mut lightmap.map)
+                .into_iter()
+                .filter_map(|(node, mut entries)| {
+                    let node = *old_new_mapping.get(&node)?;
+                    for entry in entries.iter_mut() {
+                        entry.lights.retain(|light| old_new_mapping.contains_key(light));
+                        for light in entry.lights.iter_mut() {
+                            *light = old_new_mapping[light];
+                        }
+                    }
+                    Some((node, entries))
+                })
+                .collect();
+        }
+
+        let mut reclaimed_bytes = removed_vacant_entries * std::mem::size_of::<Node>();
+        for node in self.graph.linear_iter_mut() {
+            match node {
+                Node::ParticleSystem(particle_system) => {
+                    reclaimed_bytes += particle_system.compact();
+                }
+                Node::Mesh(mesh) => {
+                    // Surface data can be shared between many meshes, it is fine to shrink
+                    // it multiple times - next attempts will just reclaim nothing.
+                    for surface in mesh.surfaces() {
+                        reclaimed_bytes += surface.data().lock().unwrap().shrink_to_fit();
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        Log::info(
+            LogCategory::Scene,
+            format!(
+                "Scene compacted: {} vacant entries removed, {} bytes reclaimed.",
+                removed_vacant_entries, reclaimed_bytes
+            ),
+        );
+
+        CompactionReport {
+            old_new_mapping,
+            removed_vacant_entries,
+            reclaimed_bytes,
+        }
+    }
+
+    /// Casts many rays at once and returns closest hit (if any) for each ray, results
+    /// are in the same order as rays. Rays are processed in parallel by global job system
+    /// (see `utils::jobs`), so this method is much faster than casting rays one-by-one when
+    /// there are hundreds of them, which is typical for AI perception. `sort_results` flag of
+    /// options is ignored, hits are always sorted to find closest one.
+    pub fn ray_cast_batch(
+        &self,
+        rays: &[Ray],
+        options: RayCastOptions,
+    ) -> Vec<Option<BatchRayHit>> {
+        let body_node_map = self.physics_binder.body_node_map();
+        let physics = &self.physics;
+
+        JobSystem::global().install(|| {
+            rays.par_iter()
+                .map_init(Vec::new, |results, ray| {
+                    results.clear();
+                    physics.ray_cast(
+                        ray,
+                        RayCastOptions {
+                            ignore_bodies: options.ignore_bodies,
+                            ignore_static_geometries: options.ignore_static_geometries,
+                            sort_results: true,
+                        },
+                        results,
+                    );
+                    results.first().map(|result| BatchRayHit {
+                        node: node_of_hit(&body_node_map, result),
+                        result: result.clone(),
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Checks line of sight for many observers at once, returns true for each query if
+    /// nothing blocks line between its points. Queries are processed in parallel, see
+    /// `ray_cast_batch` for more info.
+    pub fn line_of_sight_batch(&self, queries: &[LineOfSightQuery]) -> Vec<bool> {
+        let body_node_map = self.physics_binder.body_node_map();
+        let physics = &self.physics;
+
+        JobSystem::global().install(|| {
+            queries
+                .par_iter()
+                .map_init(Vec::new, |results, query| {
+                    let ray = match Ray::from_two_points(&query.begin, &query.end) {
+                        Some(ray) => ray,
+                        // Points are the same, nothing could be in between.
+                        None => return true,
+                    };
+                    let sqr_length = query.begin.sqr_distance(&query.end);
+
+                    results.clear();
+                    physics.ray_cast(
+                        &ray,
+                        RayCastOptions {
+                            ignore_bodies: false,
+                            ignore_static_geometries: false,
+                            sort_results: true,
+                        },
+                        results,
+                    );
+                    !results.iter().any(|result| {
+                        let node = node_of_hit(&body_node_map, result);
+                        result.sqr_distance < sqr_length
+                            && (node.is_none()
+                                || (node != query.observer && node != query.target))
+                    })
+                })
+                .collect()
+        })
+    }
+
+    /// Creates new scene which contains copy of a sub-graph starting from given node and
+    /// every animation that animates nodes of the sub-graph. Such scene can be saved as
+    /// prefab, see `save_prefab`. Physics is *not* copied.
+    pub fn make_prefab(&self, root: Handle<Node>) -> Scene {
+        self.copy_subgraph(root).0
+    }
+
+    /// Copies sub-graph and its animations into new scene, see `make_prefab`. Returns new scene
+    /// and old-to-new node mapping.
+    pub(in crate) fn copy_subgraph(
+        &self,
+        root: Handle<Node>,
+    ) -> (Scene, HashMap<Handle<Node>, Handle<Node>>) {
+        let mut prefab = Scene::new();
+
+        let (copy, old_new_map) = self.graph.copy_node(root, &mut prefab.graph, &mut |_, _| true);
+        let prefab_root = prefab.graph.get_root();
+        prefab.graph.link_nodes(copy, prefab_root);
+
+        for animation in self.animations.iter() {
+            let animates_subgraph = animation
+                .get_tracks()
+                .iter()
+                .any(|track| old_new_map.contains_key(&track.get_node()));
+            if !animates_subgraph {
+                continue;
+            }
+
+            let mut animation = animation.clone();
+            animation.retain_tracks(|track| old_new_map.contains_key(&track.get_node()));
+            for track in animation.get_tracks_mut() {
+                track.set_node(old_new_map[&track.get_node()]);
+            }
+            animation
+                .retain_parameter_tracks(|track| old_new_map.contains_key(&track.binding().node));
+            for track in animation.get_parameter_tracks_mut() {
+                let mut binding = track.binding();
+                binding.node = old_new_map[&binding.node];
+                track.set_binding(binding);
+            }
+            animation.remap_root_motion_node(&old_new_map);
+            prefab.animations.add(animation);
+        }
+
+        (prefab, old_new_map)
+    }
+
+    /// Saves sub-graph starting from given node as prefab file. Saved prefab can be loaded
+    /// using `ResourceManager::request_prefab` and instantiated using `Prefab::instantiate`.
+    /// Every instance of prefab will keep its overridden properties when prefab file is
+    /// changed. See `make_prefab` for more info.
+    pub fn save_prefab<P: AsRef<Path>>(&self, root: Handle<Node>, path: P) -> VisitResult {
+        Prefab::save(&mut self.make_prefab(root), path)
     }
 
     /// Creates deep copy of a scene, filter predicate allows you to filter out nodes
@@ -295,6 +826,14 @@ impl Scene {
             for track in animation.get_tracks_mut() {
                 track.set_node(old_new_map[&track.get_node()]);
             }
+            animation
+                .retain_parameter_tracks(|track| old_new_map.contains_key(&track.binding().node));
+            for track in animation.get_parameter_tracks_mut() {
+                let mut binding = track.binding();
+                binding.node = old_new_map[&binding.node];
+                track.set_binding(binding);
+            }
+            animation.remap_root_motion_node(&old_new_map);
         }
         let physics = self.physics.clone();
         let mut physics_binder = PhysicsBinder::default();
@@ -309,10 +848,36 @@ impl Scene {
         Self {
             graph,
             animations,
+            // Machines cannot be copied.
+            animation_machines: Default::default(),
             physics,
             physics_binder,
+            physics2d: {
+                let mut physics2d = self.physics2d.clone();
+                physics2d.remap(&old_new_map);
+                physics2d
+            },
             render_target: Default::default(),
+            // Coroutines cannot be copied.
+            coroutines: Default::default(),
+            spatial_hash: {
+                let mut spatial_hash = self.spatial_hash.clone();
+                spatial_hash.remap(&old_new_map);
+                spatial_hash
+            },
+            audio_environment: {
+                let mut audio_environment = self.audio_environment.clone();
+                audio_environment.remap(&old_new_map);
+                audio_environment
+            },
+            portals: {
+                let mut portals = self.portals.clone();
+                portals.remap(&old_new_map);
+                portals
+            },
+            environment: self.environment.clone(),
             lightmap: self.lightmap.clone(),
+            physics_time: Default::default(),
         }
     }
 }
@@ -321,10 +886,20 @@ impl Visit for Scene {
     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
         visitor.enter_region(name)?;
         self.physics_binder.visit("PhysicsBinder", visitor)?;
+        if !visitor.is_reading() {
+            // Remember which properties of prefab instances were changed, so they won't be
+            // taken from resource on load.
+            self.graph.update_property_overrides();
+        }
         self.graph.visit("Graph", visitor)?;
         self.animations.visit("Animations", visitor)?;
         self.physics.visit("Physics", visitor)?;
         let _ = self.lightmap.visit("Lightmap", visitor);
+        let _ = self.audio_environment.visit("AudioEnvironment", visitor);
+        let _ = self.portals.visit("Portals", visitor);
+        let _ = self.environment.visit("Environment", visitor);
+        let _ = self.physics2d.visit("Physics2D", visitor);
+        let _ = self.animation_machines.visit("AnimationMachines", visitor);
         visitor.leave_region()
     }
 }
@@ -351,6 +926,12 @@ impl SceneContainer {
         self.pool.iter_mut()
     }
 
+    /// Creates new iterator over scenes and their handles.
+    #[inline]
+    pub fn pair_iter(&self) -> PoolPairIterator<Scene> {
+        self.pool.pair_iter()
+    }
+
     /// Adds new scene into container.
     #[inline]
     pub fn add(&mut self, scene: Scene) -> Handle<Scene> {
@@ -368,6 +949,12 @@ impl SceneContainer {
     pub fn remove(&mut self, handle: Handle<Scene>) {
         self.pool.free(handle);
     }
+
+    /// Checks if given handle is valid.
+    #[inline]
+    pub fn is_valid_handle(&self, handle: Handle<Scene>) -> bool {
+        self.pool.is_valid_handle(handle)
+    }
 }
 
 impl Index<Handle<Scene>> for SceneContainer {
@@ -401,3 +988,149 @@ impl Visit for SceneContainer {
         visitor.leave_region()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        animation::{Animation, Track},
+        core::{
+            math::{ray::Ray, vec3::Vec3},
+            pool::Handle,
+        },
+        physics::{
+            convex_shape::{ConvexShape, SphereShape},
+            rigid_body::RigidBody,
+            static_geometry::{StaticGeometry, StaticTriangle},
+            HitKind, RayCastOptions,
+        },
+        scene::{base::BaseBuilder, node::Node, LineOfSightQuery, Scene},
+    };
+
+    fn add_node(scene: &mut Scene, name: &str) -> Handle<Node> {
+        scene
+            .graph
+            .add_node(BaseBuilder::new().with_name(name).build_node())
+    }
+
+    fn add_sphere_body(scene: &mut Scene, position: Vec3) -> Handle<RigidBody> {
+        let mut body = RigidBody::new(ConvexShape::Sphere(SphereShape::new(0.5)));
+        body.set_position(position);
+        scene.physics.add_body(body)
+    }
+
+    fn cast(scene: &Scene, end: Vec3) -> Option<super::BatchRayHit> {
+        let ray = Ray::from_two_points(&Vec3::ZERO, &end).unwrap();
+        let options = RayCastOptions {
+            ignore_bodies: false,
+            ignore_static_geometries: false,
+            sort_results: true,
+        };
+        scene.ray_cast_batch(&[ray], options).remove(0)
+    }
+
+    #[test]
+    fn merge_moves_nodes_animations_and_physics() {
+        let mut scene = Scene::new();
+        add_node(&mut scene, "Existing");
+        add_sphere_body(&mut scene, Vec3::new(0.0, 5.0, 0.0));
+
+        let mut other = Scene::new();
+        let pivot = add_node(&mut other, "Pivot");
+        let body = add_sphere_body(&mut other, Vec3::new(0.0, 0.0, 5.0));
+        other.physics_binder.bind(pivot, body);
+        // Free body.
+        add_sphere_body(&mut other, Vec3::new(5.0, 0.0, 0.0));
+        other.physics.add_static_geometry(StaticGeometry::new(vec![
+            StaticTriangle::from_points(
+                &Vec3::new(-1.0, -5.0, -1.0),
+                &Vec3::new(1.0, -5.0, -1.0),
+                &Vec3::new(0.0, -5.0, 1.0),
+            )
+            .unwrap(),
+        ]));
+        let mut animation = Animation::default();
+        let mut track = Track::new();
+        track.set_node(pivot);
+        animation.add_track(track);
+        other.animations.add(animation);
+
+        let old_new_map = scene.merge(other);
+
+        let new_pivot = old_new_map[&pivot];
+        assert_ne!(new_pivot, pivot);
+        assert_eq!(scene.graph[new_pivot].name(), "Pivot");
+        assert_eq!(scene.graph[new_pivot].parent(), scene.graph.get_root());
+        assert_eq!(
+            scene.animations.iter().next().unwrap().get_tracks()[0].get_node(),
+            new_pivot
+        );
+
+        // Bound body is still bound to its node.
+        let hit = cast(&scene, Vec3::new(0.0, 0.0, 10.0)).unwrap();
+        assert_eq!(hit.node, new_pivot);
+        assert!(scene.physics_binder.body_of(new_pivot).is_some());
+        // Body of this scene is still here.
+        assert!(cast(&scene, Vec3::new(0.0, 10.0, 0.0)).is_some());
+        // Free body and static geometry are moved too.
+        let hit = cast(&scene, Vec3::new(10.0, 0.0, 0.0)).unwrap();
+        assert!(hit.node.is_none());
+        assert!(matches!(hit.result.kind, HitKind::Body(_)));
+        let hit = cast(&scene, Vec3::new(0.0, -10.0, 0.0)).unwrap();
+        assert!(!matches!(hit.result.kind, HitKind::Body(_)));
+    }
+
+    #[test]
+    fn batched_queries_ignore_observer_and_target_and_keep_order() {
+        let mut scene = Scene::new();
+        let mut add_bound_sphere = |name: &str, position: Vec3| {
+            let node = add_node(&mut scene, name);
+            let body = add_sphere_body(&mut scene, position);
+            scene.physics_binder.bind(node, body);
+            node
+        };
+        let observer = add_bound_sphere("Observer", Vec3::ZERO);
+        let blocker = add_bound_sphere("Blocker", Vec3::new(0.0, 0.0, 5.0));
+        let target = add_bound_sphere("Target", Vec3::new(0.0, 0.0, 10.0));
+        let visible_target = add_bound_sphere("VisibleTarget", Vec3::new(10.0, 0.0, 0.0));
+        // Free body between observer and a point above it.
+        add_sphere_body(&mut scene, Vec3::new(0.0, 5.0, 0.0));
+
+        let query = |end: Vec3, target: Handle<Node>| LineOfSightQuery {
+            begin: Vec3::ZERO,
+            end,
+            observer,
+            target,
+        };
+        let visibility = scene.line_of_sight_batch(&[
+            query(Vec3::new(0.0, 0.0, 10.0), target),
+            query(Vec3::new(10.0, 0.0, 0.0), visible_target),
+            query(Vec3::new(0.0, 10.0, 0.0), Handle::NONE),
+            query(Vec3::new(0.0, 0.0, 5.0), blocker),
+            query(Vec3::ZERO, target),
+        ]);
+        assert_eq!(visibility, vec![false, true, false, true, true]);
+
+        let begin = Vec3::new(0.0, 0.0, 2.0);
+        let rays = [
+            Ray::from_two_points(&begin, &Vec3::new(0.0, 0.0, 20.0)).unwrap(),
+            Ray::from_two_points(&begin, &Vec3::new(0.0, 0.0, -10.0)).unwrap(),
+            Ray::from_two_points(&begin, &Vec3::new(0.0, -10.0, 2.0)).unwrap(),
+            Ray::from_two_points(&begin, &Vec3::new(10.0, 0.0, 0.0)).unwrap(),
+        ];
+        let options = RayCastOptions {
+            ignore_bodies: false,
+            ignore_static_geometries: false,
+            sort_results: false,
+        };
+        let hits = scene
+            .ray_cast_batch(&rays, options)
+            .into_iter()
+            .map(|hit| hit.map(|hit| hit.node))
+            .collect::<Vec<_>>();
+        // Closest hit is reported even if physics is asked not to sort results.
+        assert_eq!(
+            hits,
+            vec![Some(blocker), Some(observer), None, Some(visible_target)]
+        );
+    }
+}
@@ -1,16 +1,201 @@
 //! Contains all structures and methods to create and manage scene graph nodes.
 //!
 //! Node is enumeration of possible types of scene nodes.
+//!
+//! # Custom nodes
+//!
+//! If none of built-in nodes suits your needs, you can make your own node type by implementing
+//! `CustomNode` trait and registering a constructor for it in `CustomNodeRegistry`. Registry
+//! is used to re-create custom nodes when a scene is loaded, so registration must be done
+//! before loading any scene that contains custom nodes.
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::visitor::{Visit, VisitResult, Visitor},
+//!     scene::{
+//!         base::Base,
+//!         node::{CustomNode, CustomNodeRegistry, Node},
+//!     },
+//! };
+//! use std::{
+//!     any::Any,
+//!     ops::{Deref, DerefMut},
+//! };
+//!
+//! #[derive(Clone, Debug, Default)]
+//! struct Rotor {
+//!     base: Base,
+//!     angle: f32,
+//! }
+//!
+//! impl Deref for Rotor {
+//!     type Target = Base;
+//!
+//!     fn deref(&self) -> &Self::Target {
+//!         &self.base
+//!     }
+//! }
+//!
+//! impl DerefMut for Rotor {
+//!     fn deref_mut(&mut self) -> &mut Self::Target {
+//!         &mut self.base
+//!     }
+//! }
+//!
+//! impl Visit for Rotor {
+//!     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+//!         visitor.enter_region(name)?;
+//!
+//!         self.base.visit("Base", visitor)?;
+//!         self.angle.visit("Angle", visitor)?;
+//!
+//!         visitor.leave_region()
+//!     }
+//! }
+//!
+//! impl CustomNode for Rotor {
+//!     fn box_clone(&self) -> Box<dyn CustomNode> {
+//!         Box::new(self.clone())
+//!     }
+//!
+//!     fn kind(&self) -> u32 {
+//!         0
+//!     }
+//!
+//!     fn as_any(&self) -> &dyn Any {
+//!         self
+//!     }
+//!
+//!     fn as_any_mut(&mut self) -> &mut dyn Any {
+//!         self
+//!     }
+//!
+//!     fn update(&mut self, dt: f32) {
+//!         self.angle += dt;
+//!     }
+//! }
+//!
+//! fn register() {
+//!     CustomNodeRegistry::get()
+//!         .unwrap()
+//!         .register(0, Box::new(|| Box::new(Rotor::default())))
+//!         .unwrap();
+//! }
+//!
+//! fn make_rotor() -> Node {
+//!     Node::Custom(Box::new(Rotor::default()))
+//! }
+//! ```
 
 use crate::{
     core::define_is_as,
     core::visitor::{Visit, VisitResult, Visitor},
+    engine::resource_manager::ResourceManager,
+    renderer::debug_renderer::DebugRenderer,
     scene::{
-        base::Base, camera::Camera, light::Light, mesh::Mesh, particle_system::ParticleSystem,
-        sprite::Sprite,
+        base::Base, camera::Camera, light::Light, light_probe::LightProbeGrid, mesh::Mesh,
+        particle_system::ParticleSystem, point_cloud::PointCloud, scatter::Scatter,
+        spline::Spline, sprite::Sprite, terrain::Terrain, water::Water,
     },
 };
-use std::ops::{Deref, DerefMut};
+use std::{
+    any::Any,
+    collections::HashMap,
+    fmt::Debug,
+    ops::{Deref, DerefMut},
+    sync::{LockResult, Mutex, MutexGuard},
+};
+
+/// Callback that creates new instance of custom node with default state. Loaded state will
+/// be applied to the instance afterwards.
+pub type CustomNodeConstructor = dyn Fn() -> Box<dyn CustomNode> + Send + 'static;
+
+/// Custom node registry holds constructors of every custom node type, it is used to create
+/// custom nodes by their kind when a scene is loading. See module docs.
+#[derive(Default)]
+pub struct CustomNodeRegistry {
+    constructors: HashMap<u32, Box<CustomNodeConstructor>>,
+}
+
+impl CustomNodeRegistry {
+    /// Locks registry singleton and returns lock result.
+    pub fn get() -> LockResult<MutexGuard<'static, Self>> {
+        CUSTOM_NODE_REGISTRY_INSTANCE.lock()
+    }
+
+    /// Registers constructor for given kind of custom nodes. Returns error if there is
+    /// already a constructor for the kind.
+    pub fn register(
+        &mut self,
+        kind: u32,
+        constructor: Box<CustomNodeConstructor>,
+    ) -> Result<(), String> {
+        if self.constructors.contains_key(&kind) {
+            Err(format!("Custom node kind {} is already registered!", kind))
+        } else {
+            self.constructors.insert(kind, constructor);
+            Ok(())
+        }
+    }
+
+    /// Removes constructor of given kind of custom nodes. Returns true if there was such
+    /// constructor.
+    pub fn unregister(&mut self, kind: u32) -> bool {
+        self.constructors.remove(&kind).is_some()
+    }
+
+    /// Returns true if there is a constructor for given kind of custom nodes.
+    pub fn is_registered(&self, kind: u32) -> bool {
+        self.constructors.contains_key(&kind)
+    }
+
+    /// Creates new custom node of given kind.
+    pub fn spawn(&self, kind: u32) -> Result<Box<dyn CustomNode>, String> {
+        match self.constructors.get(&kind) {
+            Some(constructor) => Ok(constructor()),
+            None => Err(format!("Custom node kind {} is not registered!", kind)),
+        }
+    }
+}
+
+lazy_static! {
+    static ref CUSTOM_NODE_REGISTRY_INSTANCE: Mutex<CustomNodeRegistry> =
+        Mutex::new(Default::default());
+}
+
+/// Custom node allows you to make your own scene nodes without modifying `Node` enum. Custom
+/// node must contain `Base` and give access to it via `Deref` and `DerefMut`, so it will be
+/// treated as any other node by the graph (transform, hierarchy, visibility and so on). It
+/// can be implemented on serializable types only! See module docs for example.
+pub trait CustomNode: Any + Visit + Send + Debug + Deref<Target = Base> + DerefMut {
+    /// Creates boxed copy of custom node.
+    fn box_clone(&self) -> Box<dyn CustomNode>;
+
+    /// Returns unique kind of custom node, it is used to create correct node type on load,
+    /// so it must match kind that was used to register the node in `CustomNodeRegistry`.
+    fn kind(&self) -> u32;
+
+    /// Returns reference to self as `Any`, it is used for downcasting.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns mutable reference to self as `Any`, it is used for downcasting.
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// Called once per frame for every enabled custom node. `dt` is already scaled by time
+    /// scale of node.
+    fn update(&mut self, _dt: f32) {}
+
+    /// Called once per frame for every camera that sees the node. Custom node can push any
+    /// wireframe geometry into given debug renderer, its content is discarded after each
+    /// camera, so geometry must be pushed every frame.
+    fn render(&self, _camera: &Camera, _debug_renderer: &mut DebugRenderer) {}
+}
+
+impl Clone for Box<dyn CustomNode> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
 
 /// Helper macros to reduce code bloat - its purpose it to dispatch
 /// specified call by actual enum variant.
@@ -23,6 +208,13 @@ macro_rules! static_dispatch {
             Node::Light(v) => v.$func($($args),*),
             Node::ParticleSystem(v) => v.$func($($args),*),
             Node::Sprite(v) => v.$func($($args),*),
+            Node::Terrain(v) => v.$func($($args),*),
+            Node::Spline(v) => v.$func($($args),*),
+            Node::PointCloud(v) => v.$func($($args),*),
+            Node::Water(v) => v.$func($($args),*),
+            Node::Scatter(v) => v.$func($($args),*),
+            Node::LightProbeGrid(v) => v.$func($($args),*),
+            Node::Custom(v) => v.$func($($args),*),
         }
     };
 }
@@ -31,7 +223,19 @@ impl Visit for Node {
     fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
         let mut kind_id = self.id();
         kind_id.visit("KindId", visitor)?;
-        if visitor.is_reading() {
+        if kind_id == Self::CUSTOM_ID {
+            let mut custom_kind = match self {
+                Node::Custom(custom) => custom.kind(),
+                _ => 0,
+            };
+            custom_kind.visit("CustomKindId", visitor)?;
+            if visitor.is_reading() {
+                let custom = CustomNodeRegistry::get()
+                    .map_err(|_| String::from("Failed to get custom node registry!"))?
+                    .spawn(custom_kind)?;
+                *self = Node::Custom(custom);
+            }
+        } else if visitor.is_reading() {
             *self = Node::from_id(kind_id)?;
         }
 
@@ -54,6 +258,20 @@ pub enum Node {
     Sprite(Sprite),
     /// See ParticleSystem node docs.
     ParticleSystem(ParticleSystem),
+    /// See Terrain node docs.
+    Terrain(Terrain),
+    /// See Spline node docs.
+    Spline(Spline),
+    /// See PointCloud node docs.
+    PointCloud(PointCloud),
+    /// See Water node docs.
+    Water(Water),
+    /// See Scatter node docs.
+    Scatter(Scatter),
+    /// See LightProbeGrid node docs.
+    LightProbeGrid(LightProbeGrid),
+    /// User-defined node, see module docs.
+    Custom(Box<dyn CustomNode>),
 }
 
 macro_rules! static_dispatch_deref {
@@ -65,6 +283,13 @@ macro_rules! static_dispatch_deref {
             Node::Light(v) => v,
             Node::ParticleSystem(v) => v,
             Node::Sprite(v) => v,
+            Node::Terrain(v) => v,
+            Node::Spline(v) => v,
+            Node::PointCloud(v) => v,
+            Node::Water(v) => v,
+            Node::Scatter(v) => v,
+            Node::LightProbeGrid(v) => v,
+            Node::Custom(v) => v,
         }
     };
 }
@@ -90,6 +315,10 @@ impl Default for Node {
 }
 
 impl Node {
+    /// Variant id of custom nodes. Custom nodes cannot be created by `from_id`, because
+    /// their actual type is defined by their kind, see `CustomNodeRegistry`.
+    pub const CUSTOM_ID: u8 = 255;
+
     /// Creates new Node based on variant id.
     pub fn from_id(id: u8) -> Result<Self, String> {
         match id {
@@ -99,6 +328,12 @@ impl Node {
             3 => Ok(Self::Mesh(Default::default())),
             4 => Ok(Self::Sprite(Default::default())),
             5 => Ok(Self::ParticleSystem(Default::default())),
+            6 => Ok(Self::Terrain(Default::default())),
+            7 => Ok(Self::Spline(Default::default())),
+            8 => Ok(Self::PointCloud(Default::default())),
+            9 => Ok(Self::Water(Default::default())),
+            10 => Ok(Self::Scatter(Default::default())),
+            11 => Ok(Self::LightProbeGrid(Default::default())),
             _ => Err(format!("Invalid node kind {}", id)),
         }
     }
@@ -112,6 +347,45 @@ impl Node {
             Self::Mesh(_) => 3,
             Self::Sprite(_) => 4,
             Self::ParticleSystem(_) => 5,
+            Self::Terrain(_) => 6,
+            Self::Spline(_) => 7,
+            Self::PointCloud(_) => 8,
+            Self::Water(_) => 9,
+            Self::Scatter(_) => 10,
+            Self::LightProbeGrid(_) => 11,
+            Self::Custom(_) => Self::CUSTOM_ID,
+        }
+    }
+
+    /// Restores pointers to resources of node. Scene saves only paths to resources, here
+    /// real resources are requested from resource manager instead.
+    pub(in crate) fn restore_resources(&mut self, resource_manager: &mut ResourceManager) {
+        Base::restore_resources(self, resource_manager);
+        if let Node::ParticleSystem(particle_system) = self {
+            particle_system.restore_resources(resource_manager);
+        }
+    }
+
+    /// Returns true if node is a custom node.
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Node::Custom(_))
+    }
+
+    /// Returns reference to custom node of given type, None if node is not a custom node
+    /// or it has different type.
+    pub fn as_custom<T: CustomNode>(&self) -> Option<&T> {
+        match self {
+            Node::Custom(custom) => custom.as_any().downcast_ref::<T>(),
+            _ => None,
+        }
+    }
+
+    /// Returns mutable reference to custom node of given type, None if node is not a custom
+    /// node or it has different type.
+    pub fn as_custom_mut<T: CustomNode>(&mut self) -> Option<&mut T> {
+        match self {
+            Node::Custom(custom) => custom.as_any_mut().downcast_mut::<T>(),
+            _ => None,
         }
     }
 
@@ -120,4 +394,137 @@ impl Node {
     define_is_as!(Node : Light -> ref Light => fn is_light, fn as_light, fn as_light_mut);
     define_is_as!(Node : ParticleSystem -> ref ParticleSystem => fn is_particle_system, fn as_particle_system, fn as_particle_system_mut);
     define_is_as!(Node : Sprite -> ref Sprite => fn is_sprite, fn as_sprite, fn as_sprite_mut);
+    define_is_as!(Node : Terrain -> ref Terrain => fn is_terrain, fn as_terrain, fn as_terrain_mut);
+    define_is_as!(Node : Spline -> ref Spline => fn is_spline, fn as_spline, fn as_spline_mut);
+    define_is_as!(Node : PointCloud -> ref PointCloud => fn is_point_cloud, fn as_point_cloud, fn as_point_cloud_mut);
+    define_is_as!(Node : Water -> ref Water => fn is_water, fn as_water, fn as_water_mut);
+    define_is_as!(Node : Scatter -> ref Scatter => fn is_scatter, fn as_scatter, fn as_scatter_mut);
+    define_is_as!(Node : LightProbeGrid -> ref LightProbeGrid => fn is_light_probe_grid, fn as_light_probe_grid, fn as_light_probe_grid_mut);
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::visitor::{Visit, VisitResult, Visitor},
+        scene::{
+            base::{Base, BaseBuilder},
+            node::{CustomNode, CustomNodeRegistry, Node},
+            terrain::TerrainBuilder,
+        },
+    };
+    use std::{
+        any::Any,
+        ops::{Deref, DerefMut},
+    };
+
+    #[derive(Clone, Debug, Default)]
+    struct Counter {
+        base: Base,
+        ticks: u32,
+    }
+
+    impl Deref for Counter {
+        type Target = Base;
+
+        fn deref(&self) -> &Self::Target {
+            &self.base
+        }
+    }
+
+    impl DerefMut for Counter {
+        fn deref_mut(&mut self) -> &mut Self::Target {
+            &mut self.base
+        }
+    }
+
+    impl Visit for Counter {
+        fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+            visitor.enter_region(name)?;
+
+            self.base.visit("Base", visitor)?;
+            self.ticks.visit("Ticks", visitor)?;
+
+            visitor.leave_region()
+        }
+    }
+
+    impl CustomNode for Counter {
+        fn box_clone(&self) -> Box<dyn CustomNode> {
+            Box::new(self.clone())
+        }
+
+        fn kind(&self) -> u32 {
+            1000
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+
+        fn update(&mut self, _dt: f32) {
+            self.ticks += 1;
+        }
+    }
+
+    #[test]
+    fn custom_node_test() {
+        let mut registry = CustomNodeRegistry::get().unwrap();
+        registry
+            .register(1000, Box::new(|| Box::new(Counter::default())))
+            .unwrap();
+        assert!(registry
+            .register(1000, Box::new(|| Box::new(Counter::default())))
+            .is_err());
+
+        let mut node = Node::Custom(registry.spawn(1000).unwrap());
+        assert!(registry.spawn(1001).is_err());
+        assert!(registry.unregister(1000));
+        drop(registry);
+
+        assert_eq!(node.id(), Node::CUSTOM_ID);
+        if let Node::Custom(custom) = &mut node {
+            custom.update(0.1);
+        }
+        node.set_name("Counter");
+
+        let copy = node.clone();
+        assert_eq!(copy.name(), "Counter");
+        assert_eq!(copy.as_custom::<Counter>().unwrap().ticks, 1);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        // Ids of variants are written instead of discriminants, so they must stay the same.
+        for id in 0..12 {
+            assert_eq!(Node::from_id(id).unwrap().id(), id);
+        }
+
+        let path = std::env::temp_dir().join("rg3d-node-round-trip-test");
+        let mut terrain = TerrainBuilder::new(BaseBuilder::new().with_name("Terrain"))
+            .with_resolution(5, 3)
+            .with_heights((0..15).map(|i| i as f32).collect())
+            .with_chunk_size(2)
+            .build_node();
+        let mut visitor = Visitor::new();
+        terrain.visit("Node", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = Node::default();
+        loaded.visit("Node", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(path);
+        match (&terrain, &loaded) {
+            (Node::Terrain(terrain), Node::Terrain(loaded)) => {
+                assert_eq!(loaded.name(), "Terrain");
+                assert_eq!(loaded.resolution(), (5, 3));
+                assert_eq!(loaded.chunk_size(), 2);
+                assert_eq!(loaded.heights(), terrain.heights());
+            }
+            _ => panic!("Terrain must be loaded as terrain!"),
+        }
+    }
 }
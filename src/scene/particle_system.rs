@@ -7,10 +7,19 @@
 //! to control separate particles, all particles controlled by parameters of particle
 //! emitters.
 //!
+//! # Custom behavior
+//!
+//! If emitters are not enough, particle system can have custom update callback which
+//! has access to every particle after each update. Each particle also has `user_data`
+//! field which can be used to store gameplay-specific data for such callback.
+//!
 //! # Emitters
 //!
 //! Particle system can contain multiple particle emitters, each emitter has its own
 //! set of properties and it defines law of change of particle parameters over time.
+//! Initial parameters of particles are random values in ranges of emitter, by default
+//! they're spread uniformly, use `BaseEmitter::set_distribution` to make them cluster
+//! around center of a range or to follow a curve, see `RandomDistribution`.
 //!
 //! # Performance
 //!
@@ -18,7 +27,12 @@
 //! on performance defined by amount of particles and amount of pixels they take to render.
 //! A rule of thumb will be to decrease amount of particles until effect will look good
 //! enough, alternatively amount of particles can be defined by some coefficient based on
-//! graphics quality settings.
+//! graphics quality settings. Particles of systems with at least `PARALLEL_UPDATE_THRESHOLD`
+//! particles are updated in parallel by global job system, see `utils::jobs`, unless
+//! deterministic mode is enabled (see `engine::determinism`).
+//!
+//! Particles are emitted using engine-wide random number generator (`utils::random`), custom
+//! emitters should use it too, so particles are emitted the same way in deterministic mode.
 //!
 //! # Example
 //!
@@ -77,19 +91,30 @@ use crate::{
         numeric_range::NumericRange,
         visitor::{Visit, VisitResult, Visitor},
     },
-    resource::texture::Texture,
+    engine::{determinism, resource_manager::ResourceManager},
+    resource::{
+        gradient::{self, GradientColorSpace, GradientResource},
+        texture::Texture,
+    },
+    profile_scope,
     scene::base::{Base, BaseBuilder},
+    utils::{distribution::RandomDistribution, jobs::JobSystem, random},
+    Visit,
 };
 use rand::Rng;
 use std::{
     any::Any,
     cell::Cell,
     cmp::Ordering,
-    fmt::Debug,
+    fmt::{Debug, Formatter},
     ops::{Deref, DerefMut},
     sync::{Arc, LockResult, Mutex, MutexGuard},
 };
 
+/// Minimal amount of particles at which particles are updated in parallel by global job
+/// system, smaller systems are cheaper to update on a single thread.
+pub const PARALLEL_UPDATE_THRESHOLD: usize = 4096;
+
 /// OpenGL expects this structure packed as in C.
 #[repr(C)]
 #[derive(Debug)]
@@ -102,9 +127,11 @@ pub struct Vertex {
 }
 
 /// Particle system is "rendered" into special buffer, which contains vertices and faces.
+/// In case of point sprites render mode, there are no faces, but points.
 pub struct DrawData {
     vertices: Vec<Vertex>,
     triangles: Vec<TriangleDefinition>,
+    points: Vec<u32>,
 }
 
 impl Default for DrawData {
@@ -112,6 +139,7 @@ impl Default for DrawData {
         Self {
             vertices: Vec::new(),
             triangles: Vec::new(),
+            points: Vec::new(),
         }
     }
 }
@@ -120,6 +148,13 @@ impl DrawData {
     fn clear(&mut self) {
         self.vertices.clear();
         self.triangles.clear();
+        self.points.clear();
+    }
+
+    /// Returns shared reference to array of point indices. It is empty if particle system
+    /// is rendered as billboards.
+    pub fn points(&self) -> &[u32] {
+        &self.points
     }
 
     /// Returns shared reference to array of vertices.
@@ -156,6 +191,10 @@ pub struct Particle {
     pub rotation: f32,
     /// Color of particle.
     pub color: Color,
+    /// Arbitrary user-defined data, engine does not use it. It can be used to store
+    /// gameplay-specific info (index of target, some flags, etc.) which then can be read
+    /// in custom update callback. See `ParticleSystem::set_update_callback`.
+    pub user_data: u64,
     emitter_index: u32,
     sqr_distance_to_camera: Cell<f32>,
 }
@@ -174,6 +213,7 @@ impl Default for Particle {
             rotation: 0.0,
             emitter_index: 0,
             color: Color::WHITE,
+            user_data: 0,
             sqr_distance_to_camera: Cell::new(0.0),
         }
     }
@@ -194,11 +234,42 @@ impl Visit for Particle {
         self.rotation.visit("Rotation", visitor)?;
         self.color.visit("Color", visitor)?;
         self.emitter_index.visit("EmitterIndex", visitor)?;
+        let _ = self.user_data.visit("UserData", visitor);
 
         visitor.leave_region()
     }
 }
 
+impl Particle {
+    /// Returns true if particle is alive. Dead particles are kept in particle buffer for
+    /// reuse, so custom update callback must skip them.
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+
+    /// Returns amount of time (in seconds) that particle has lived so far.
+    pub fn lifetime(&self) -> f32 {
+        self.lifetime
+    }
+}
+
+/// Callback that is called each update of particle system after all built-in
+/// integration is done. It receives every particle of particle system (including
+/// dead ones) and time step. Can be used to implement gameplay-specific behavior,
+/// such as homing sparks or particles that follow a target.
+pub type ParticleUpdateCallback = dyn FnMut(&mut [Particle], f32) + Send + 'static;
+
+/// Shared wrapper around update callback. Callback can't be cloned, so clones of
+/// particle system share the same callback.
+#[derive(Clone)]
+struct UpdateCallback(Arc<Mutex<Box<ParticleUpdateCallback>>>);
+
+impl Debug for UpdateCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UpdateCallback")
+    }
+}
+
 /// Emit trait must be implemented for any particle system emitter.
 pub trait Emit {
     /// Initializes state of particle using given emitter and particle system.
@@ -207,11 +278,16 @@ pub trait Emit {
 
 /// Box emitter emits particles uniformly in its volume. Can be used to create simple fog
 /// layer.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Visit)]
 pub struct BoxEmitter {
+    // Base emitter was not saved by earlier versions.
+    #[visit(rename = "Emitter", since = 1)]
     emitter: BaseEmitter,
+    #[visit(rename = "HalfWidth")]
     half_width: f32,
+    #[visit(rename = "HalfHeight")]
     half_height: f32,
+    #[visit(rename = "HalfDepth")]
     half_depth: f32,
 }
 
@@ -255,7 +331,7 @@ impl Default for BoxEmitter {
 impl Emit for BoxEmitter {
     fn emit(&self, _particle_system: &ParticleSystem, particle: &mut Particle) {
         self.emitter.emit(particle);
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         particle.position = Vec3::new(
             self.position.x + rng.gen_range(-self.half_width, self.half_width),
             self.position.y + rng.gen_range(-self.half_height, self.half_height),
@@ -264,18 +340,6 @@ impl Emit for BoxEmitter {
     }
 }
 
-impl Visit for BoxEmitter {
-    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
-        visitor.enter_region(name)?;
-
-        self.half_width.visit("HalfWidth", visitor)?;
-        self.half_height.visit("HalfHeight", visitor)?;
-        self.half_depth.visit("HalfDepth", visitor)?;
-
-        visitor.leave_region()
-    }
-}
-
 /// Box emitter builder allows you to construct box emitter in declarative manner.
 /// This is typical implementation of Builder pattern.
 pub struct BoxEmitterBuilder {
@@ -327,9 +391,12 @@ impl BoxEmitterBuilder {
 
 /// Sphere emitter uniformly places particles in spherical volume. Can be used with
 /// radius = 0, then it represents point emitter.   
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Visit)]
 pub struct SphereEmitter {
+    // Base emitter was not saved by earlier versions.
+    #[visit(rename = "Emitter", since = 1)]
     emitter: BaseEmitter,
+    #[visit(rename = "Radius")]
     radius: f32,
 }
 
@@ -363,20 +430,10 @@ impl SphereEmitter {
     }
 }
 
-impl Visit for SphereEmitter {
-    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
-        visitor.enter_region(name)?;
-
-        self.radius.visit("Radius", visitor)?;
-
-        visitor.leave_region()
-    }
-}
-
 impl Emit for SphereEmitter {
     fn emit(&self, _particle_system: &ParticleSystem, particle: &mut Particle) {
         self.emitter.emit(particle);
-        let mut rng = rand::thread_rng();
+        let mut rng = random::rng();
         let phi = rng.gen_range(0.0, std::f32::consts::PI);
         let theta = rng.gen_range(0.0, 2.0 * std::f32::consts::PI);
         let radius = rng.gen_range(0.0, self.radius);
@@ -616,6 +673,98 @@ impl Visit for ParticleLimit {
     }
 }
 
+/// Defines the way how particles will be rendered.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RenderMode {
+    /// Each particle is rendered as camera-facing quad. This is default mode.
+    Billboards,
+
+    /// Each particle is rendered as single point with size attenuation. It needs four
+    /// times less vertices and two times less triangles than billboards, so it is
+    /// preferable for systems with lots of tiny particles like rain, snow or dust.
+    /// Particles are limited by maximum point size of a video driver, so big particles
+    /// may be clamped.
+    PointSprites,
+}
+
+impl Default for RenderMode {
+    fn default() -> Self {
+        Self::Billboards
+    }
+}
+
+impl Visit for RenderMode {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id: u32 = match self {
+            Self::Billboards => 0,
+            Self::PointSprites => 1,
+        };
+
+        id.visit("Id", visitor)?;
+
+        if visitor.is_reading() {
+            *self = match id {
+                0 => Self::Billboards,
+                1 => Self::PointSprites,
+                _ => return Err(format!("Invalid render mode {}", id).into()),
+            };
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Parameter of a particle which is generated by emitter using a range and a distribution.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EmitterParameter {
+    /// Initial lifetime.
+    Lifetime,
+    /// Initial size.
+    Size,
+    /// Size modifier.
+    SizeModifier,
+    /// X-component of initial velocity.
+    XVelocity,
+    /// Y-component of initial velocity.
+    YVelocity,
+    /// Z-component of initial velocity.
+    ZVelocity,
+    /// Rotation speed.
+    RotationSpeed,
+    /// Initial rotation.
+    Rotation,
+}
+
+impl EmitterParameter {
+    const COUNT: usize = 8;
+
+    const ALL: [EmitterParameter; EmitterParameter::COUNT] = [
+        EmitterParameter::Lifetime,
+        EmitterParameter::Size,
+        EmitterParameter::SizeModifier,
+        EmitterParameter::XVelocity,
+        EmitterParameter::YVelocity,
+        EmitterParameter::ZVelocity,
+        EmitterParameter::RotationSpeed,
+        EmitterParameter::Rotation,
+    ];
+
+    fn distribution_name(self) -> &'static str {
+        match self {
+            EmitterParameter::Lifetime => "LifeTimeDistribution",
+            EmitterParameter::Size => "SizeDistribution",
+            EmitterParameter::SizeModifier => "SizeModifierDistribution",
+            EmitterParameter::XVelocity => "XVelocityDistribution",
+            EmitterParameter::YVelocity => "YVelocityDistribution",
+            EmitterParameter::ZVelocity => "ZVelocityDistribution",
+            EmitterParameter::RotationSpeed => "RotationSpeedDistribution",
+            EmitterParameter::Rotation => "RotationDistribution",
+        }
+    }
+}
+
 /// Base emitter contains properties for all other "derived" emitters.
 #[derive(Debug)]
 pub struct BaseEmitter {
@@ -642,6 +791,8 @@ pub struct BaseEmitter {
     rotation_speed: NumericRange<f32>,
     /// Range of initial rotation for a particle
     rotation: NumericRange<f32>,
+    /// Distributions of values in ranges, indexed by `EmitterParameter`.
+    distributions: [RandomDistribution; EmitterParameter::COUNT],
     alive_particles: Cell<u32>,
     time: f32,
     particles_to_spawn: usize,
@@ -663,6 +814,7 @@ pub struct BaseEmitterBuilder {
     z_velocity: Option<NumericRange<f32>>,
     rotation_speed: Option<NumericRange<f32>>,
     rotation: Option<NumericRange<f32>>,
+    distributions: [RandomDistribution; EmitterParameter::COUNT],
     resurrect_particles: bool,
 }
 
@@ -687,6 +839,7 @@ impl BaseEmitterBuilder {
             z_velocity: None,
             rotation_speed: None,
             rotation: None,
+            distributions: Default::default(),
             resurrect_particles: true,
         }
     }
@@ -757,6 +910,17 @@ impl BaseEmitterBuilder {
         self
     }
 
+    /// Sets desired distribution of values of given parameter in its range, by default values
+    /// are distributed uniformly.
+    pub fn with_distribution(
+        mut self,
+        parameter: EmitterParameter,
+        distribution: RandomDistribution,
+    ) -> Self {
+        self.distributions[parameter as usize] = distribution;
+        self
+    }
+
     /// Sets whether to resurrect dead particle or not.
     pub fn resurrect_particles(mut self, value: bool) -> Self {
         self.resurrect_particles = value;
@@ -793,6 +957,7 @@ impl BaseEmitterBuilder {
             rotation: self
                 .rotation
                 .unwrap_or_else(|| NumericRange::new(-std::f32::consts::PI, std::f32::consts::PI)),
+            distributions: self.distributions,
             alive_particles: Cell::new(0),
             time: 0.0,
             particles_to_spawn: 0,
@@ -827,18 +992,22 @@ impl BaseEmitter {
     /// Initializes particle with new state. Every custom emitter must call this method,
     /// otherwise you will get weird behavior of emitted particles.
     pub fn emit(&self, particle: &mut Particle) {
+        let mut rng = random::rng();
+        let mut sample = |parameter: EmitterParameter, range: &NumericRange<f32>| {
+            self.distributions[parameter as usize].sample(range, &mut rng)
+        };
         particle.lifetime = 0.0;
-        particle.initial_lifetime = self.lifetime.random();
+        particle.initial_lifetime = sample(EmitterParameter::Lifetime, &self.lifetime);
         particle.color = Color::WHITE;
-        particle.size = self.size.random();
-        particle.size_modifier = self.size_modifier.random();
+        particle.size = sample(EmitterParameter::Size, &self.size);
+        particle.size_modifier = sample(EmitterParameter::SizeModifier, &self.size_modifier);
         particle.velocity = Vec3::new(
-            self.x_velocity.random(),
-            self.y_velocity.random(),
-            self.z_velocity.random(),
+            sample(EmitterParameter::XVelocity, &self.x_velocity),
+            sample(EmitterParameter::YVelocity, &self.y_velocity),
+            sample(EmitterParameter::ZVelocity, &self.z_velocity),
         );
-        particle.rotation = self.rotation.random();
-        particle.rotation_speed = self.rotation_speed.random();
+        particle.rotation = sample(EmitterParameter::Rotation, &self.rotation);
+        particle.rotation_speed = sample(EmitterParameter::RotationSpeed, &self.rotation_speed);
     }
 
     /// Sets new position of emitter in local coordinates.
@@ -975,6 +1144,22 @@ impl BaseEmitter {
         self.rotation
     }
 
+    /// Sets distribution of values of given parameter in its range. For example normal
+    /// distribution of size makes most particles of average size.
+    pub fn set_distribution(
+        &mut self,
+        parameter: EmitterParameter,
+        distribution: RandomDistribution,
+    ) -> &mut Self {
+        self.distributions[parameter as usize] = distribution;
+        self
+    }
+
+    /// Returns distribution of values of given parameter in its range.
+    pub fn distribution(&self, parameter: EmitterParameter) -> &RandomDistribution {
+        &self.distributions[parameter as usize]
+    }
+
     /// Enables or disables automatic particle resurrection. Setting this option to
     /// true is useful for "endless" effects.
     pub fn enable_particle_resurrection(&mut self, state: bool) -> &mut Self {
@@ -1008,6 +1193,10 @@ impl Visit for BaseEmitter {
         self.z_velocity.visit("ZVelocity", visitor)?;
         self.rotation_speed.visit("RotationSpeed", visitor)?;
         self.rotation.visit("Rotation", visitor)?;
+        for parameter in EmitterParameter::ALL.iter() {
+            let distribution = &mut self.distributions[*parameter as usize];
+            let _ = distribution.visit(parameter.distribution_name(), visitor);
+        }
         self.alive_particles.visit("AliveParticles", visitor)?;
         self.time.visit("Time", visitor)?;
         self.resurrect_particles
@@ -1032,6 +1221,7 @@ impl Clone for BaseEmitter {
             z_velocity: self.z_velocity,
             rotation_speed: self.rotation_speed,
             rotation: self.rotation,
+            distributions: self.distributions.clone(),
             alive_particles: self.alive_particles.clone(),
             time: self.time,
             particles_to_spawn: 0,
@@ -1055,6 +1245,7 @@ impl Default for BaseEmitter {
             z_velocity: NumericRange::new(-0.001, 0.001),
             rotation_speed: NumericRange::new(-0.02, 0.02),
             rotation: NumericRange::new(-std::f32::consts::PI, std::f32::consts::PI),
+            distributions: Default::default(),
             alive_particles: Cell::new(0),
             time: 0.0,
             particles_to_spawn: 0,
@@ -1074,6 +1265,13 @@ pub struct ParticleSystem {
     texture: Option<Arc<Mutex<Texture>>>,
     acceleration: Vec3,
     color_over_lifetime: Option<ColorGradient>,
+    color_over_lifetime_resource: Option<Arc<Mutex<GradientResource>>>,
+    gradient_color_space: GradientColorSpace,
+    render_mode: RenderMode,
+    update_callback: Option<UpdateCallback>,
+    wind_influence: f32,
+    /// Wind of scene, it is set by scene before each update. Non-serializable.
+    pub(in crate) wind: Vec3,
 }
 
 impl Deref for ParticleSystem {
@@ -1096,6 +1294,12 @@ impl ParticleSystem {
         self.emitters.push(emitter)
     }
 
+    /// Returns every particle of particle system, including dead ones which are waiting
+    /// to be reused.
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
     /// Returns current acceleration for particles in particle system.
     pub fn acceleration(&self) -> Vec3 {
         self.acceleration
@@ -1107,15 +1311,91 @@ impl ParticleSystem {
         self.acceleration = accel;
     }
 
+    /// Sets how much wind of scene affects particles, zero means that particles are not
+    /// affected by wind at all. Wind is applied as additional acceleration. See
+    /// `SceneEnvironment::wind`.
+    pub fn set_wind_influence(&mut self, influence: f32) {
+        self.wind_influence = influence;
+    }
+
+    /// Returns how much wind of scene affects particles.
+    pub fn wind_influence(&self) -> f32 {
+        self.wind_influence
+    }
+
+    /// Sets new render mode of particle system. See `RenderMode` docs for more info.
+    pub fn set_render_mode(&mut self, render_mode: RenderMode) {
+        self.render_mode = render_mode;
+    }
+
+    /// Returns current render mode of particle system.
+    pub fn render_mode(&self) -> RenderMode {
+        self.render_mode
+    }
+
     /// Sets new "color curve" that will evaluate color over lifetime.
     pub fn set_color_over_lifetime_gradient(&mut self, gradient: ColorGradient) {
         self.color_over_lifetime = Some(gradient)
     }
 
+    /// Sets shared gradient resource that will evaluate color over lifetime. Resource has
+    /// priority over gradient set by `set_color_over_lifetime_gradient`. Use it when the
+    /// same color ramp should be shared across many particle systems.
+    pub fn set_color_over_lifetime_resource(
+        &mut self,
+        resource: Option<Arc<Mutex<GradientResource>>>,
+    ) {
+        self.color_over_lifetime_resource = resource;
+    }
+
+    /// Returns current shared gradient resource used to evaluate color over lifetime.
+    pub fn color_over_lifetime_resource(&self) -> Option<Arc<Mutex<GradientResource>>> {
+        self.color_over_lifetime_resource.clone()
+    }
+
+    // Saved gradient resource contains only path, actual resource must be requested.
+    pub(in crate) fn restore_resources(&mut self, resource_manager: &mut ResourceManager) {
+        if let Some(shallow_resource) = self.color_over_lifetime_resource.clone() {
+            let path = shallow_resource.lock().unwrap().path.clone();
+            self.color_over_lifetime_resource = resource_manager.request_gradient(path);
+        }
+    }
+
+    /// Sets color space in which colors of color over lifetime gradient are blended. HSV
+    /// and OkLab spaces don't produce muddy colors between saturated colors of different
+    /// hues, see `GradientColorSpace`.
+    pub fn set_gradient_color_space(&mut self, color_space: GradientColorSpace) {
+        self.gradient_color_space = color_space;
+    }
+
+    /// Returns color space in which colors of color over lifetime gradient are blended.
+    pub fn gradient_color_space(&self) -> GradientColorSpace {
+        self.gradient_color_space
+    }
+
+    /// Sets custom update callback which will be called at the end of each update. Callback
+    /// is not serialized, so it must be set again after loading a save. Clones of particle
+    /// system share the same callback.
+    pub fn set_update_callback(&mut self, callback: Box<ParticleUpdateCallback>) {
+        self.update_callback = Some(UpdateCallback(Arc::new(Mutex::new(callback))));
+    }
+
+    /// Removes custom update callback.
+    pub fn clear_update_callback(&mut self) {
+        self.update_callback = None;
+    }
+
+    /// Returns true if particle system has custom update callback.
+    pub fn has_update_callback(&self) -> bool {
+        self.update_callback.is_some()
+    }
+
     /// Updates state of particle system, this means that it moves particles,
     /// changes their color, size, rotation, etc. This method should not be
     /// used directly, it will be automatically called by scene update.
     pub fn update(&mut self, dt: f32) {
+        profile_scope!("ParticleSystem");
+
         for emitter in self.emitters.iter_mut() {
             emitter.tick(dt);
         }
@@ -1136,21 +1416,23 @@ impl ParticleSystem {
             }
         }
 
-        let acceleration_offset = self.acceleration.scale(dt * dt);
+        let acceleration = self.acceleration + self.wind.scale(self.wind_influence);
+        let acceleration_offset = acceleration.scale(dt * dt);
 
-        for (i, particle) in self.particles.iter_mut().enumerate() {
+        let resource = self
+            .color_over_lifetime_resource
+            .as_ref()
+            .map(|resource| resource.lock().unwrap());
+        let color_over_lifetime = match resource.as_ref() {
+            Some(resource) => Some(resource.gradient()),
+            None => self.color_over_lifetime.as_ref(),
+        };
+
+        let color_space = self.gradient_color_space;
+        let update_particle = |particle: &mut Particle| {
             if particle.alive {
                 particle.lifetime += dt;
-                if particle.lifetime >= particle.initial_lifetime {
-                    self.free_particles.push(i as u32);
-                    if let Some(emitter) = self.emitters.get(particle.emitter_index as usize) {
-                        emitter
-                            .alive_particles
-                            .set(emitter.alive_particles.get() - 1);
-                    }
-                    particle.alive = false;
-                    particle.lifetime = particle.initial_lifetime;
-                } else {
+                if particle.lifetime < particle.initial_lifetime {
                     particle.velocity += acceleration_offset;
                     particle.position += particle.velocity;
                     particle.size += particle.size_modifier * dt;
@@ -1158,14 +1440,43 @@ impl ParticleSystem {
                         particle.size = 0.0;
                     }
                     particle.rotation += particle.rotation_speed * dt;
-                    if let Some(color_over_lifetime) = &self.color_over_lifetime {
+                    if let Some(color_over_lifetime) = color_over_lifetime {
                         let k = particle.lifetime / particle.initial_lifetime;
-                        particle.color = color_over_lifetime.get_color(k);
+                        particle.color = gradient::color_at(color_over_lifetime, k, color_space);
                     } else {
                         particle.color = Color::WHITE;
                     }
                 }
             }
+        };
+        if self.particles.len() >= PARALLEL_UPDATE_THRESHOLD && !determinism::is_enabled() {
+            JobSystem::global().parallel_for(
+                &mut self.particles,
+                PARALLEL_UPDATE_THRESHOLD / 4,
+                update_particle,
+            );
+        } else {
+            self.particles.iter_mut().for_each(update_particle);
+        }
+
+        for (i, particle) in self.particles.iter_mut().enumerate() {
+            if particle.alive && particle.lifetime >= particle.initial_lifetime {
+                self.free_particles.push(i as u32);
+                if let Some(emitter) = self.emitters.get(particle.emitter_index as usize) {
+                    emitter
+                        .alive_particles
+                        .set(emitter.alive_particles.get() - 1);
+                }
+                particle.alive = false;
+                particle.lifetime = particle.initial_lifetime;
+            }
+        }
+
+        // Release gradient before calling user code, it may want to lock it too.
+        drop(resource);
+
+        if let Some(callback) = self.update_callback.as_ref() {
+            (callback.0.lock().unwrap())(&mut self.particles, dt);
         }
     }
 
@@ -1206,6 +1517,24 @@ impl ParticleSystem {
 
         draw_data.clear();
 
+        if self.render_mode == RenderMode::PointSprites {
+            for (i, particle_index) in sorted_particles.iter().enumerate() {
+                let particle = self.particles.get(*particle_index as usize).unwrap();
+
+                draw_data.vertices.push(Vertex {
+                    position: particle.position,
+                    tex_coord: Vec2::ZERO,
+                    size: particle.size,
+                    rotation: particle.rotation,
+                    color: particle.color,
+                });
+
+                draw_data.points.push(i as u32);
+            }
+
+            return;
+        }
+
         for (i, particle_index) in sorted_particles.iter().enumerate() {
             let particle = self.particles.get(*particle_index as usize).unwrap();
 
@@ -1256,6 +1585,17 @@ impl ParticleSystem {
         }
     }
 
+    /// Removes dead particles and releases excessive memory of internal buffers. Returns
+    /// amount of bytes that were given back to allocator.
+    pub fn compact(&mut self) -> usize {
+        let old_size = self.particles.capacity() * std::mem::size_of::<Particle>()
+            + self.free_particles.capacity() * std::mem::size_of::<u32>();
+        self.particles.retain(|particle| particle.alive);
+        self.particles.shrink_to_fit();
+        self.free_particles = Vec::new();
+        old_size - self.particles.capacity() * std::mem::size_of::<Particle>()
+    }
+
     /// Sets new texture for particle system.
     pub fn set_texture(&mut self, texture: Arc<Mutex<Texture>>) {
         self.texture = Some(texture)
@@ -1278,6 +1618,14 @@ impl Visit for ParticleSystem {
         self.acceleration.visit("Acceleration", visitor)?;
         self.color_over_lifetime.visit("ColorGradient", visitor)?;
         self.base.visit("Base", visitor)?;
+        let _ = self.render_mode.visit("RenderMode", visitor);
+        let _ = self
+            .color_over_lifetime_resource
+            .visit("ColorGradientResource", visitor);
+        let _ = self.wind_influence.visit("WindInfluence", visitor);
+        let _ = self
+            .gradient_color_space
+            .visit("GradientColorSpace", visitor);
 
         visitor.leave_region()
     }
@@ -1297,6 +1645,11 @@ pub struct ParticleSystemBuilder {
     texture: Option<Arc<Mutex<Texture>>>,
     acceleration: Vec3,
     color_over_lifetime: Option<ColorGradient>,
+    color_over_lifetime_resource: Option<Arc<Mutex<GradientResource>>>,
+    gradient_color_space: GradientColorSpace,
+    render_mode: RenderMode,
+    update_callback: Option<Box<ParticleUpdateCallback>>,
+    wind_influence: f32,
 }
 
 impl ParticleSystemBuilder {
@@ -1308,6 +1661,11 @@ impl ParticleSystemBuilder {
             texture: None,
             acceleration: Vec3::new(0.0, -9.81, 0.0),
             color_over_lifetime: None,
+            color_over_lifetime_resource: None,
+            gradient_color_space: Default::default(),
+            render_mode: Default::default(),
+            update_callback: None,
+            wind_influence: 1.0,
         }
     }
 
@@ -1341,6 +1699,41 @@ impl ParticleSystemBuilder {
         self
     }
 
+    /// Sets shared color gradient resource over lifetime for particle system.
+    pub fn with_color_over_lifetime_resource(
+        mut self,
+        resource: Arc<Mutex<GradientResource>>,
+    ) -> Self {
+        self.color_over_lifetime_resource = Some(resource);
+        self
+    }
+
+    /// Sets desired color space of color over lifetime gradient, see
+    /// `ParticleSystem::set_gradient_color_space`.
+    pub fn with_gradient_color_space(mut self, color_space: GradientColorSpace) -> Self {
+        self.gradient_color_space = color_space;
+        self
+    }
+
+    /// Sets desired render mode for particle system.
+    pub fn with_render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.render_mode = render_mode;
+        self
+    }
+
+    /// Sets custom update callback for particle system. See
+    /// `ParticleSystem::set_update_callback` docs for more info.
+    pub fn with_update_callback(mut self, callback: Box<ParticleUpdateCallback>) -> Self {
+        self.update_callback = Some(callback);
+        self
+    }
+
+    /// Sets desired wind influence, see `ParticleSystem::set_wind_influence`.
+    pub fn with_wind_influence(mut self, influence: f32) -> Self {
+        self.wind_influence = influence;
+        self
+    }
+
     /// Creates new instance of particle system.
     pub fn build(self) -> ParticleSystem {
         ParticleSystem {
@@ -1351,6 +1744,14 @@ impl ParticleSystemBuilder {
             texture: self.texture.clone(),
             acceleration: self.acceleration,
             color_over_lifetime: self.color_over_lifetime,
+            color_over_lifetime_resource: self.color_over_lifetime_resource,
+            gradient_color_space: self.gradient_color_space,
+            render_mode: self.render_mode,
+            update_callback: self
+                .update_callback
+                .map(|callback| UpdateCallback(Arc::new(Mutex::new(callback)))),
+            wind_influence: self.wind_influence,
+            wind: Vec3::ZERO,
         }
     }
 
@@ -1359,3 +1760,70 @@ impl ParticleSystemBuilder {
         Node::ParticleSystem(self.build())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::visitor::{Visit, Visitor},
+        scene::particle_system::{
+            BaseEmitterBuilder, BoxEmitter, BoxEmitterBuilder, Emitter, SphereEmitterBuilder,
+        },
+    };
+
+    #[test]
+    fn base_emitter_is_saved() {
+        let path = std::env::temp_dir().join("rg3d-emitters-round-trip.bin");
+        let mut emitters = vec![
+            BoxEmitterBuilder::new(BaseEmitterBuilder::new().with_spawn_rate(12))
+                .with_width(4.0)
+                .build(),
+            SphereEmitterBuilder::new(BaseEmitterBuilder::new().with_spawn_rate(34))
+                .with_radius(2.0)
+                .build(),
+        ];
+        let mut visitor = Visitor::new();
+        emitters.visit("Emitters", &mut visitor).unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = Vec::<Emitter>::new();
+        loaded.visit("Emitters", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        match &loaded[0] {
+            Emitter::Box(box_emitter) => assert_eq!(box_emitter.half_width, 2.0),
+            _ => panic!("Box emitter must be loaded as box emitter!"),
+        }
+        assert_eq!(loaded[0].spawn_rate(), 12);
+        match &loaded[1] {
+            Emitter::Sphere(sphere_emitter) => assert_eq!(sphere_emitter.radius, 2.0),
+            _ => panic!("Sphere emitter must be loaded as sphere emitter!"),
+        }
+        assert_eq!(loaded[1].spawn_rate(), 34);
+    }
+
+    #[test]
+    fn emitter_without_base_is_loaded() {
+        let path = std::env::temp_dir().join("rg3d-legacy-box-emitter.bin");
+        // Box emitter as it was saved before base emitter was added to its data.
+        let mut visitor = Visitor::new();
+        visitor.enter_region("Box").unwrap();
+        let (mut half_width, mut half_height, mut half_depth) = (1.0f32, 2.0f32, 3.0f32);
+        half_width.visit("HalfWidth", &mut visitor).unwrap();
+        half_height.visit("HalfHeight", &mut visitor).unwrap();
+        half_depth.visit("HalfDepth", &mut visitor).unwrap();
+        visitor.leave_region().unwrap();
+        visitor.save_binary(&path).unwrap();
+
+        let mut visitor = Visitor::load_binary(&path).unwrap();
+        let mut loaded = BoxEmitter::default();
+        loaded.visit("Box", &mut visitor).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(
+            (loaded.half_width, loaded.half_height, loaded.half_depth),
+            (1.0, 2.0, 3.0)
+        );
+        assert_eq!(loaded.spawn_rate(), 0);
+    }
+}
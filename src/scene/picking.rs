@@ -0,0 +1,476 @@
+//! Contains ray casting against visual geometry of a graph.
+//!
+//! Physics ray casts see only rigid bodies and static geometry, which are usually much
+//! coarser than what is actually drawn on screen. Graph ray cast intersects rays with
+//! triangles of meshes (and optionally with sprites and terrains), so it is suitable for
+//! precise mouse picking in editors and for hitscan weapons. It does not depend on physics
+//! at all.
+//!
+//! Skinned surfaces are tested in their current pose, bones are applied on CPU for that.
+//! Sprites are tested as discs which always face the ray, the same way they face camera
+//! while rendering.
+//!
+//! # Performance
+//!
+//! Candidate nodes are taken from bounds tree of graph (see `bounds_tree` module), so only
+//! nodes near the ray are checked, then bounding box of each mesh is checked and then every
+//! triangle of a mesh is checked. Bounds tree is updated together with global transforms,
+//! so nodes added after last `Graph::update_hierachical_data` are not checked. Triangles are
+//! not organized in any structure, so ray cast is fine for occasional queries (clicks,
+//! shots), but not for hundreds of rays per frame against high-poly meshes.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::math::vec2::Vec2,
+//!     scene::{camera::Camera, picking::GraphRayCastOptions, Scene},
+//! };
+//!
+//! fn pick(scene: &Scene, camera: &Camera, cursor: Vec2, screen_size: Vec2) {
+//!     let ray = camera.make_ray(cursor, screen_size);
+//!     let mut hits = Vec::new();
+//!     if scene.graph.ray_cast(&ray, GraphRayCastOptions::default(), &mut hits) {
+//!         println!("Picked {}", scene.graph[hits[0].node].name());
+//!     }
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::{aabb::AxisAlignedBoundingBox, mat4::Mat4, ray::Ray, vec3::Vec3},
+        pool::Handle,
+    },
+    scene::{graph::Graph, mesh::Mesh, node::Node, sprite::Sprite, terrain::Terrain},
+};
+use std::cmp::Ordering;
+
+/// Options of graph ray cast, see `Graph::ray_cast`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GraphRayCastOptions {
+    /// Whether to check sprites or not. Default is `false`.
+    pub include_sprites: bool,
+    /// Whether to check terrains or not. Default is `false`.
+    pub include_terrains: bool,
+    /// Whether to skip nodes that are not globally visible or not. Default is `true`.
+    pub ignore_invisible: bool,
+    /// Only nodes which have at least one common layer with this mask will be checked.
+    /// Default is `std::u32::MAX` (every node).
+    pub layer_mask: u32,
+    /// Whether to skip triangles which are facing away from ray or not. Default is `false`.
+    pub cull_back_faces: bool,
+    /// Whether to sort hits by distance (closest first) or not. Default is `true`.
+    pub sort_results: bool,
+}
+
+impl Default for GraphRayCastOptions {
+    fn default() -> Self {
+        Self {
+            include_sprites: false,
+            include_terrains: false,
+            ignore_invisible: true,
+            layer_mask: std::u32::MAX,
+            cull_back_faces: false,
+            sort_results: true,
+        }
+    }
+}
+
+/// Single intersection of a ray with geometry of a node.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GraphRayHit {
+    /// Handle of node which was hit.
+    pub node: Handle<Node>,
+    /// Position of intersection in world coordinates.
+    pub position: Vec3,
+    /// Normal of hit triangle in world coordinates.
+    pub normal: Vec3,
+    /// Squared distance from origin of ray to intersection point.
+    pub sqr_distance: f32,
+    /// Index of surface of mesh. Always zero for sprites and terrains.
+    pub surface_index: usize,
+    /// Index of hit triangle in surface. For terrains it is `2 * cell_index + n` where
+    /// `cell_index = z * (width_points - 1) + x` and `n` is 0 or 1. Always zero for sprites.
+    pub triangle_index: usize,
+}
+
+/// Returns parameters at which given segment enters and leaves given box.
+fn ray_aabb_intersection(
+    origin: Vec3,
+    dir: Vec3,
+    aabb: &AxisAlignedBoundingBox,
+) -> Option<(f32, f32)> {
+    let origin = [origin.x, origin.y, origin.z];
+    let dir = [dir.x, dir.y, dir.z];
+    let min = [aabb.min.x, aabb.min.y, aabb.min.z];
+    let max = [aabb.max.x, aabb.max.y, aabb.max.z];
+
+    let mut t_min = 0.0f32;
+    let mut t_max = 1.0f32;
+    for i in 0..3 {
+        if dir[i].abs() < std::f32::EPSILON {
+            if origin[i] < min[i] || origin[i] > max[i] {
+                return None;
+            }
+        } else {
+            let inv_dir = 1.0 / dir[i];
+            let mut t0 = (min[i] - origin[i]) * inv_dir;
+            let mut t1 = (max[i] - origin[i]) * inv_dir;
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_min > t_max {
+                return None;
+            }
+        }
+    }
+    Some((t_min, t_max))
+}
+
+/// Möller–Trumbore intersection, returns parameter of segment at intersection point.
+fn ray_triangle_intersection(
+    origin: Vec3,
+    dir: Vec3,
+    triangle: &[Vec3; 3],
+    cull_back_faces: bool,
+) -> Option<f32> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+    let p = dir.cross(&edge2);
+    let det = edge1.dot(&p);
+    if (cull_back_faces && det < std::f32::EPSILON) || det.abs() < std::f32::EPSILON {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let s = origin - triangle[0];
+    let u = s.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&edge1);
+    let v = dir.dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = edge2.dot(&q) * inv_det;
+    if (0.0..=1.0).contains(&t) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+fn triangle_normal(triangle: &[Vec3; 3]) -> Vec3 {
+    (triangle[1] - triangle[0])
+        .cross(&(triangle[2] - triangle[0]))
+        .normalized()
+        .unwrap_or(Vec3::UP)
+}
+
+struct RayCastContext<'a> {
+    ray: &'a Ray,
+    options: GraphRayCastOptions,
+    hits: &'a mut Vec<GraphRayHit>,
+}
+
+impl<'a> RayCastContext<'a> {
+    /// Transforms ray into local coordinates of a node. Transformation is affine, so
+    /// parameter of intersection in local space is the same as in world space.
+    fn local_ray(&self, global_transform: &Mat4) -> (Vec3, Vec3) {
+        let inv_transform = global_transform.inverse().unwrap_or_default();
+        let origin = inv_transform.transform_vector(self.ray.origin);
+        let end = inv_transform.transform_vector(self.ray.origin + self.ray.dir);
+        (origin, end - origin)
+    }
+
+    fn add_hit(
+        &mut self,
+        node: Handle<Node>,
+        t: f32,
+        world_triangle: &[Vec3; 3],
+        surface_index: usize,
+        triangle_index: usize,
+    ) {
+        let position = self.ray.origin + self.ray.dir.scale(t);
+        self.hits.push(GraphRayHit {
+            node,
+            position,
+            normal: triangle_normal(world_triangle),
+            sqr_distance: self.ray.origin.sqr_distance(&position),
+            surface_index,
+            triangle_index,
+        });
+    }
+
+    fn check_mesh(&mut self, handle: Handle<Node>, mesh: &Mesh, graph: &Graph) {
+        let global_transform = mesh.global_transform();
+        let (local_origin, local_dir) = self.local_ray(&global_transform);
+
+        for (surface_index, surface) in mesh.surfaces().iter().enumerate() {
+            let data = surface.data();
+            let data = data.lock().unwrap();
+            let vertices = data.get_vertices();
+
+            if surface.bones().is_empty() {
+                // Bounding box of mesh does not include bones, so it can be used only for
+                // static surfaces.
+                if ray_aabb_intersection(local_origin, local_dir, &mesh.bounding_box()).is_none()
+                {
+                    continue;
+                }
+
+                for (triangle_index, triangle) in data.triangles().iter().enumerate() {
+                    let local_triangle = [
+                        vertices[triangle[0] as usize].position,
+                        vertices[triangle[1] as usize].position,
+                        vertices[triangle[2] as usize].position,
+                    ];
+                    if let Some(t) = ray_triangle_intersection(
+                        local_origin,
+                        local_dir,
+                        &local_triangle,
+                        self.options.cull_back_faces,
+                    ) {
+                        let world_triangle = [
+                            global_transform.transform_vector(local_triangle[0]),
+                            global_transform.transform_vector(local_triangle[1]),
+                            global_transform.transform_vector(local_triangle[2]),
+                        ];
+                        self.add_hit(handle, t, &world_triangle, surface_index, triangle_index);
+                    }
+                }
+            } else {
+                // Skinned surface, apply bones on CPU and check triangles in world space.
+                let bone_matrices = surface
+                    .bones()
+                    .iter()
+                    .map(|&b| {
+                        let bone_node = &graph[b];
+                        bone_node.global_transform() * bone_node.inv_bind_pose_transform()
+                    })
+                    .collect::<Vec<Mat4>>();
+
+                let world_positions = vertices
+                    .iter()
+                    .map(|vertex| {
+                        let mut position = Vec3::ZERO;
+                        for (&bone_index, &weight) in
+                            vertex.bone_indices.iter().zip(vertex.bone_weights.iter())
+                        {
+                            position += bone_matrices[bone_index as usize]
+                                .transform_vector(vertex.position)
+                                .scale(weight);
+                        }
+                        position
+                    })
+                    .collect::<Vec<Vec3>>();
+
+                for (triangle_index, triangle) in data.triangles().iter().enumerate() {
+                    let world_triangle = [
+                        world_positions[triangle[0] as usize],
+                        world_positions[triangle[1] as usize],
+                        world_positions[triangle[2] as usize],
+                    ];
+                    if let Some(t) = ray_triangle_intersection(
+                        self.ray.origin,
+                        self.ray.dir,
+                        &world_triangle,
+                        self.options.cull_back_faces,
+                    ) {
+                        self.add_hit(handle, t, &world_triangle, surface_index, triangle_index);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_terrain(&mut self, handle: Handle<Node>, terrain: &Terrain) {
+        let global_transform = terrain.global_transform();
+        let (local_origin, local_dir) = self.local_ray(&global_transform);
+
+        let (t_enter, t_exit) =
+            match ray_aabb_intersection(local_origin, local_dir, &terrain.bounding_box()) {
+                Some(range) => range,
+                None => return,
+            };
+
+        // Check only cells under the part of the ray that is inside of bounds.
+        let (width_points, length_points) = terrain.resolution();
+        let cell = terrain.cell_size();
+        let enter = local_origin + local_dir.scale(t_enter);
+        let exit = local_origin + local_dir.scale(t_exit);
+        let to_index = |coordinate: f32, cell_size: f32, points: usize| {
+            ((coordinate / cell_size).floor().max(0.0) as usize).min(points - 2)
+        };
+        let x_begin = to_index(enter.x.min(exit.x), cell.x, width_points);
+        let x_end = to_index(enter.x.max(exit.x), cell.x, width_points);
+        let z_begin = to_index(enter.z.min(exit.z), cell.y, length_points);
+        let z_end = to_index(enter.z.max(exit.z), cell.y, length_points);
+
+        let point = |x: usize, z: usize| {
+            Vec3::new(x as f32 * cell.x, terrain.height(x, z), z as f32 * cell.y)
+        };
+
+        for z in z_begin..=z_end {
+            for x in x_begin..=x_end {
+                let cell_triangles = [
+                    [point(x, z), point(x, z + 1), point(x + 1, z + 1)],
+                    [point(x, z), point(x + 1, z + 1), point(x + 1, z)],
+                ];
+                for (n, local_triangle) in cell_triangles.iter().enumerate() {
+                    if let Some(t) = ray_triangle_intersection(
+                        local_origin,
+                        local_dir,
+                        local_triangle,
+                        self.options.cull_back_faces,
+                    ) {
+                        let world_triangle = [
+                            global_transform.transform_vector(local_triangle[0]),
+                            global_transform.transform_vector(local_triangle[1]),
+                            global_transform.transform_vector(local_triangle[2]),
+                        ];
+                        let triangle_index = 2 * (z * (width_points - 1) + x) + n;
+                        self.add_hit(handle, t, &world_triangle, 0, triangle_index);
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_sprite(&mut self, handle: Handle<Node>, sprite: &Sprite) {
+        let dir_sqr_len = self.ray.dir.dot(&self.ray.dir);
+        if dir_sqr_len < std::f32::EPSILON {
+            return;
+        }
+        // Closest point of the ray to center of sprite lies on the plane of sprite disc,
+        // because disc is always facing the ray.
+        let center = sprite.global_position();
+        let t = (center - self.ray.origin).dot(&self.ray.dir) / dir_sqr_len;
+        if !(0.0..=1.0).contains(&t) {
+            return;
+        }
+        let position = self.ray.origin + self.ray.dir.scale(t);
+        if position.sqr_distance(&center) <= sprite.size() * sprite.size() {
+            self.hits.push(GraphRayHit {
+                node: handle,
+                position,
+                normal: self.ray.dir.scale(-1.0).normalized().unwrap_or(Vec3::UP),
+                sqr_distance: self.ray.origin.sqr_distance(&position),
+                surface_index: 0,
+                triangle_index: 0,
+            });
+        }
+    }
+}
+
+impl Graph {
+    /// Casts a ray and collects every intersection with visual geometry of nodes, see
+    /// `picking` module docs. Like in physics ray cast, ray is a segment which starts at
+    /// `ray.origin` and ends at `ray.origin + ray.dir`. Hits are appended to given
+    /// vector, it is *not* cleared. Returns true if there was at least one hit.
+    pub fn ray_cast(
+        &self,
+        ray: &Ray,
+        options: GraphRayCastOptions,
+        hits: &mut Vec<GraphRayHit>,
+    ) -> bool {
+        let first_hit = hits.len();
+
+        let mut candidates = Vec::new();
+        self.bounds_tree().query_segment(ray.origin, ray.dir, &mut candidates);
+        // Order of hits must not depend on layout of the tree.
+        candidates.sort_unstable_by_key(|handle| handle.index());
+
+        let mut context = RayCastContext { ray, options, hits };
+        for handle in candidates {
+            if !self.is_valid_handle(handle) {
+                continue;
+            }
+            let node = &self[handle];
+            if (options.ignore_invisible && !node.global_visibility())
+                || node.layers() & options.layer_mask == 0
+            {
+                continue;
+            }
+
+            match node {
+                Node::Mesh(mesh) => context.check_mesh(handle, mesh, self),
+                Node::Terrain(terrain) if options.include_terrains => {
+                    context.check_terrain(handle, terrain)
+                }
+                Node::Sprite(sprite) if options.include_sprites => {
+                    context.check_sprite(handle, sprite)
+                }
+                _ => (),
+            }
+        }
+
+        if options.sort_results {
+            hits[first_hit..].sort_by(|a, b| {
+                a.sqr_distance
+                    .partial_cmp(&b.sqr_distance)
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+
+        hits.len() > first_hit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::{mat4::Mat4, ray::Ray, vec3::Vec3},
+        renderer::surface::{Surface, SurfaceSharedData},
+        scene::{
+            base::BaseBuilder,
+            graph::Graph,
+            mesh::MeshBuilder,
+            picking::GraphRayCastOptions,
+            transform::TransformBuilder,
+        },
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn graph_ray_cast_test() {
+        let mut graph = Graph::new();
+        let make_cube = |position| {
+            MeshBuilder::new(
+                BaseBuilder::new().with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(position)
+                        .build(),
+                ),
+            )
+            .with_surfaces(vec![Surface::new(Arc::new(Mutex::new(
+                SurfaceSharedData::make_cube(Mat4::IDENTITY),
+            )))])
+            .build_node()
+        };
+        let near = graph.add_node(make_cube(Vec3::new(0.0, 0.0, 5.0)));
+        let far = graph.add_node(make_cube(Vec3::new(0.0, 0.0, 10.0)));
+        graph.add_node(make_cube(Vec3::new(5.0, 0.0, 5.0)));
+        graph.update_hierachical_data();
+
+        let ray = Ray::from_two_points(&Vec3::ZERO, &Vec3::new(0.0, 0.0, 20.0)).unwrap();
+        let mut hits = Vec::new();
+        assert!(graph.ray_cast(&ray, GraphRayCastOptions::default(), &mut hits));
+        // Each cube is hit twice - on enter and on exit.
+        assert_eq!(hits.len(), 4);
+        assert_eq!(hits[0].node, near);
+        assert_eq!(hits[3].node, far);
+        assert!((hits[0].position.z - 4.5).abs() < 0.001);
+        assert!((hits[0].normal.z.abs() - 1.0).abs() < 0.001);
+
+        hits.clear();
+        let options = GraphRayCastOptions {
+            cull_back_faces: true,
+            ..Default::default()
+        };
+        let short_ray = Ray::from_two_points(&Vec3::ZERO, &Vec3::new(0.0, 0.0, 2.0)).unwrap();
+        assert!(!graph.ray_cast(&short_ray, options, &mut hits));
+    }
+}
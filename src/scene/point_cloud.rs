@@ -0,0 +1,230 @@
+//! Contains all structures and methods to create and manage point clouds.
+//!
+//! Point cloud is a large set of colored points, which is drawn in a single draw call. It
+//! can be used to visualize results of 3D scanning, samples of AI queries, procedural data
+//! and so on. Points are given in local coordinates of the node and have fixed size on
+//! screen (in pixels), they are not lit.
+//!
+//! # Performance
+//!
+//! Points are uploaded to GPU only when they're changed, so static clouds with millions of
+//! points are cheap. Each change (even of a single point) leads to upload of whole cloud,
+//! so frequently changing data is better split into multiple clouds.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{color::Color, math::vec3::Vec3, pool::Handle},
+//!     scene::{
+//!         base::BaseBuilder,
+//!         node::Node,
+//!         point_cloud::{CloudPoint, PointCloudBuilder},
+//!         Scene,
+//!     },
+//! };
+//!
+//! fn create_cloud(scene: &mut Scene, positions: &[Vec3]) -> Handle<Node> {
+//!     let points = positions
+//!         .iter()
+//!         .map(|&position| CloudPoint {
+//!             position,
+//!             color: Color::opaque(255, 0, 0),
+//!         })
+//!         .collect();
+//!     scene.graph.add_node(
+//!         PointCloudBuilder::new(BaseBuilder::new())
+//!             .with_points(points)
+//!             .with_point_size(3.0)
+//!             .build_node(),
+//!     )
+//! }
+//! ```
+
+use crate::{
+    core::{
+        color::Color,
+        math::vec3::Vec3,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{
+        base::{Base, BaseBuilder},
+        node::Node,
+    },
+};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Single point of a cloud. Layout of this structure matches layout of vertex buffer, so
+/// points are uploaded to GPU as is.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+pub struct CloudPoint {
+    /// Position in local coordinates of point cloud.
+    pub position: Vec3,
+    /// Color of point.
+    pub color: Color,
+}
+
+impl Default for CloudPoint {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            color: Color::WHITE,
+        }
+    }
+}
+
+impl Visit for CloudPoint {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.position.visit("Position", visitor)?;
+        self.color.visit("Color", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+static REVISION: AtomicU64 = AtomicU64::new(1);
+
+// Every change of any point cloud gets unique revision, renderer uses revisions as keys of
+// GPU buffers, so clones of a cloud share the buffer until one of them is changed.
+fn next_revision() -> u64 {
+    REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct PointCloud {
+    base: Base,
+    points: Vec<CloudPoint>,
+    point_size: f32,
+    revision: u64,
+}
+
+impl Deref for PointCloud {
+    type Target = Base;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for PointCloud {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Default for PointCloud {
+    fn default() -> Self {
+        PointCloudBuilder::new(BaseBuilder::new()).build()
+    }
+}
+
+impl PointCloud {
+    /// Returns shared reference to points.
+    pub fn points(&self) -> &[CloudPoint] {
+        &self.points
+    }
+
+    /// Returns mutable reference to points. Cloud will be uploaded to GPU again, even if
+    /// nothing was changed.
+    pub fn points_mut(&mut self) -> &mut Vec<CloudPoint> {
+        self.revision = next_revision();
+        &mut self.points
+    }
+
+    /// Replaces every point of the cloud.
+    pub fn set_points(&mut self, points: Vec<CloudPoint>) {
+        *self.points_mut() = points;
+    }
+
+    /// Adds new point to the cloud.
+    pub fn add_point(&mut self, point: CloudPoint) {
+        self.points_mut().push(point);
+    }
+
+    /// Removes every point.
+    pub fn clear(&mut self) {
+        self.points_mut().clear();
+    }
+
+    /// Sets size of points in pixels.
+    pub fn set_point_size(&mut self, size: f32) {
+        self.point_size = size.max(1.0);
+    }
+
+    /// Returns size of points in pixels.
+    pub fn point_size(&self) -> f32 {
+        self.point_size
+    }
+
+    pub(in crate) fn revision(&self) -> u64 {
+        self.revision
+    }
+}
+
+impl Visit for PointCloud {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Base", visitor)?;
+        self.points.visit("Points", visitor)?;
+        self.point_size.visit("PointSize", visitor)?;
+
+        if visitor.is_reading() {
+            self.revision = next_revision();
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Point cloud builder allows you to construct point cloud in declarative manner.
+pub struct PointCloudBuilder {
+    base_builder: BaseBuilder,
+    points: Vec<CloudPoint>,
+    point_size: f32,
+}
+
+impl PointCloudBuilder {
+    /// Creates new builder with empty set of points and 2 pixels point size.
+    pub fn new(base_builder: BaseBuilder) -> Self {
+        Self {
+            base_builder,
+            points: Default::default(),
+            point_size: 2.0,
+        }
+    }
+
+    /// Sets desired points.
+    pub fn with_points(mut self, points: Vec<CloudPoint>) -> Self {
+        self.points = points;
+        self
+    }
+
+    /// Sets desired size of points in pixels.
+    pub fn with_point_size(mut self, size: f32) -> Self {
+        self.point_size = size.max(1.0);
+        self
+    }
+
+    /// Creates new point cloud instance.
+    pub fn build(self) -> PointCloud {
+        PointCloud {
+            base: self.base_builder.build(),
+            points: self.points,
+            point_size: self.point_size,
+            revision: next_revision(),
+        }
+    }
+
+    /// Creates new node instance.
+    pub fn build_node(self) -> Node {
+        Node::PointCloud(self.build())
+    }
+}
@@ -0,0 +1,494 @@
+//! Contains zones and portals for visibility culling of indoor scenes.
+//!
+//! Indoor levels consist of rooms connected by doors, windows and corridors, and most of
+//! the rooms are not visible from any given point. Zone is a box attached to a scene node
+//! which describes a room, every descendant of the zone node is considered to be inside
+//! of the room. Portal is a rectangle attached to a scene node which connects two zones
+//! (or a zone with outside world), usually it is placed in a doorway.
+//!
+//! Each frame renderer finds zone in which camera is and walks through open portals that
+//! are visible from camera, narrowing visible area of screen at each portal. Every zone
+//! that wasn't reached this way is culled with all of its content. Nodes which are not
+//! inside of any zone are never culled by portals.
+//!
+//! # Outside
+//!
+//! Space outside of every zone is treated as one more zone, which can be referenced by
+//! `Handle::NONE` in portals. When camera is outside, only zones visible through portals
+//! that lead outside are drawn.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{
+//!         math::{vec2::Vec2, vec3::Vec3},
+//!         pool::Handle,
+//!     },
+//!     scene::{
+//!         node::Node,
+//!         portal::{Portal, Zone},
+//!         Scene,
+//!     },
+//! };
+//!
+//! fn setup(scene: &mut Scene, hall: Handle<Node>, room: Handle<Node>, door: Handle<Node>) {
+//!     scene.portals.add_zone(Zone {
+//!         node: hall,
+//!         half_extents: Vec3::new(10.0, 3.0, 10.0),
+//!     });
+//!     scene.portals.add_zone(Zone {
+//!         node: room,
+//!         half_extents: Vec3::new(3.0, 3.0, 3.0),
+//!     });
+//!     scene.portals.add_portal(Portal {
+//!         node: door,
+//!         half_size: Vec2::new(0.5, 1.0),
+//!         zones: [hall, room],
+//!         open: true,
+//!     });
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::{mat4::Mat4, vec2::Vec2, vec3::Vec3},
+        pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{camera::Camera, graph::Graph, node::Node},
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Maximum amount of portals in a chain from camera to a zone. Prevents endless walking
+/// in case of degenerated portal configurations.
+const MAX_PORTAL_DEPTH: usize = 32;
+
+/// Box-shaped room. See module docs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Zone {
+    /// Node to which zone is attached. Zone is centered at the node and follows its
+    /// transform. Every descendant of the node is inside of the zone.
+    pub node: Handle<Node>,
+    /// Half extents of box in local coordinates of the node.
+    pub half_extents: Vec3,
+}
+
+impl Default for Zone {
+    fn default() -> Self {
+        Self {
+            node: Handle::NONE,
+            half_extents: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Zone {
+    /// Returns true if given point in world coordinates is inside of the zone.
+    pub fn contains(&self, graph: &Graph, point: Vec3) -> bool {
+        if !graph.is_valid_handle(self.node) {
+            return false;
+        }
+
+        let inv_transform = graph[self.node]
+            .global_transform()
+            .inverse()
+            .unwrap_or_default();
+        let local = inv_transform.transform_vector(point);
+        local.x.abs() <= self.half_extents.x
+            && local.y.abs() <= self.half_extents.y
+            && local.z.abs() <= self.half_extents.z
+    }
+
+    fn volume(&self) -> f32 {
+        self.half_extents.x * self.half_extents.y * self.half_extents.z
+    }
+}
+
+impl Visit for Zone {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.node.visit("Node", visitor)?;
+        self.half_extents.visit("HalfExtents", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Rectangular opening between two zones. See module docs.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Portal {
+    /// Node to which portal is attached. Portal is a rectangle in XY plane of the node.
+    pub node: Handle<Node>,
+    /// Half size of rectangle in local coordinates of the node.
+    pub half_size: Vec2,
+    /// Nodes of zones connected by portal, `Handle::NONE` means outside.
+    pub zones: [Handle<Node>; 2],
+    /// Closed portal blocks visibility, it can be used for closed doors.
+    pub open: bool,
+}
+
+impl Default for Portal {
+    fn default() -> Self {
+        Self {
+            node: Handle::NONE,
+            half_size: Vec2::new(1.0, 1.0),
+            zones: [Handle::NONE, Handle::NONE],
+            open: true,
+        }
+    }
+}
+
+impl Portal {
+    /// Returns zone on other side of portal, or None if portal does not lead out of given
+    /// zone.
+    pub fn other_zone(&self, zone: Handle<Node>) -> Option<Handle<Node>> {
+        if self.zones[0] == zone {
+            Some(self.zones[1])
+        } else if self.zones[1] == zone {
+            Some(self.zones[0])
+        } else {
+            None
+        }
+    }
+
+    /// Returns corners of portal in world coordinates.
+    pub fn corners(&self, graph: &Graph) -> [Vec3; 4] {
+        let transform = graph[self.node].global_transform();
+        let (x, y) = (self.half_size.x, self.half_size.y);
+        [
+            transform.transform_vector(Vec3::new(-x, -y, 0.0)),
+            transform.transform_vector(Vec3::new(x, -y, 0.0)),
+            transform.transform_vector(Vec3::new(x, y, 0.0)),
+            transform.transform_vector(Vec3::new(-x, y, 0.0)),
+        ]
+    }
+}
+
+impl Visit for Portal {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.node.visit("Node", visitor)?;
+        self.half_size.visit("HalfSize", visitor)?;
+        self.zones[0].visit("ZoneA", visitor)?;
+        self.zones[1].visit("ZoneB", visitor)?;
+        self.open.visit("Open", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Rectangle in normalized device coordinates.
+#[derive(Copy, Clone, Debug)]
+struct ScreenRect {
+    min: Vec2,
+    max: Vec2,
+}
+
+impl ScreenRect {
+    const FULL: Self = Self {
+        min: Vec2 { x: -1.0, y: -1.0 },
+        max: Vec2 { x: 1.0, y: 1.0 },
+    };
+
+    fn intersection(&self, other: &Self) -> Option<Self> {
+        let min = Vec2::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y));
+        let max = Vec2::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y));
+        if min.x < max.x && min.y < max.y {
+            Some(Self { min, max })
+        } else {
+            None
+        }
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Vec2::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Vec2::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    fn contains(&self, other: &Self) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+    }
+
+    /// Calculates screen-space bounds of given points. Returns None if every point is
+    /// behind camera, and full screen if only some of them are behind camera - clipping
+    /// is not worth it here, such portals are very close to camera anyway.
+    fn from_points(view_projection: &Mat4, points: &[Vec3]) -> Option<Self> {
+        let m = &view_projection.f;
+        let mut rect = Self {
+            min: Vec2::new(std::f32::MAX, std::f32::MAX),
+            max: Vec2::new(-std::f32::MAX, -std::f32::MAX),
+        };
+        let mut behind = 0;
+        for p in points {
+            let w = m[3] * p.x + m[7] * p.y + m[11] * p.z + m[15];
+            if w <= std::f32::EPSILON {
+                behind += 1;
+                continue;
+            }
+            let x = (m[0] * p.x + m[4] * p.y + m[8] * p.z + m[12]) / w;
+            let y = (m[1] * p.x + m[5] * p.y + m[9] * p.z + m[13]) / w;
+            rect.min = Vec2::new(rect.min.x.min(x), rect.min.y.min(y));
+            rect.max = Vec2::new(rect.max.x.max(x), rect.max.y.max(y));
+        }
+        if behind == points.len() {
+            None
+        } else if behind > 0 {
+            Some(Self::FULL)
+        } else {
+            Some(rect)
+        }
+    }
+}
+
+/// Container of zones and portals of a scene. See module docs.
+#[derive(Clone, Debug, Default)]
+pub struct PortalSystem {
+    zones: Vec<Zone>,
+    portals: Vec<Portal>,
+}
+
+impl PortalSystem {
+    /// Adds new zone to container.
+    pub fn add_zone(&mut self, zone: Zone) {
+        self.zones.push(zone);
+    }
+
+    /// Adds new portal to container.
+    pub fn add_portal(&mut self, portal: Portal) {
+        self.portals.push(portal);
+    }
+
+    /// Returns shared reference to zones.
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    /// Returns shared reference to portals.
+    pub fn portals(&self) -> &[Portal] {
+        &self.portals
+    }
+
+    /// Returns mutable reference to portals, can be used to open or close portals.
+    pub fn portals_mut(&mut self) -> &mut [Portal] {
+        &mut self.portals
+    }
+
+    /// Removes every zone and portal attached to given node, as well as every portal that
+    /// leads to zone attached to given node.
+    pub fn remove_of(&mut self, node: Handle<Node>) {
+        self.zones.retain(|zone| zone.node != node);
+        self.portals
+            .retain(|portal| portal.node != node && !portal.zones.contains(&node));
+    }
+
+    /// Removes every zone and portal.
+    pub fn clear(&mut self) {
+        self.zones.clear();
+        self.portals.clear();
+    }
+
+    /// Returns node of smallest zone which contains given point, or `Handle::NONE` if
+    /// point is outside of every zone.
+    pub fn zone_at(&self, graph: &Graph, point: Vec3) -> Handle<Node> {
+        self.zones
+            .iter()
+            .filter(|zone| zone.contains(graph, point))
+            .min_by(|a, b| a.volume().partial_cmp(&b.volume()).unwrap())
+            .map_or(Handle::NONE, |zone| zone.node)
+    }
+
+    /// Returns set of zones visible from given position with given view-projection
+    /// matrix. Set contains `Handle::NONE` if outside is visible.
+    pub fn visible_zones(
+        &self,
+        graph: &Graph,
+        position: Vec3,
+        view_projection: &Mat4,
+    ) -> HashSet<Handle<Node>> {
+        let start = self.zone_at(graph, position);
+
+        // Each zone can be reached through different portal chains, so remember visible
+        // area of each zone and walk from it again only if area grows.
+        let mut areas = HashMap::new();
+        areas.insert(start, ScreenRect::FULL);
+        let mut queue = VecDeque::new();
+        queue.push_back((start, ScreenRect::FULL, None, 0));
+        while let Some((zone, rect, came_through, depth)) = queue.pop_front() {
+            if depth >= MAX_PORTAL_DEPTH {
+                continue;
+            }
+            for (i, portal) in self.portals.iter().enumerate() {
+                if !portal.open || came_through == Some(i) || !graph.is_valid_handle(portal.node)
+                {
+                    continue;
+                }
+                let next = match portal.other_zone(zone) {
+                    Some(next) => next,
+                    None => continue,
+                };
+                let corners = portal.corners(graph);
+                let portal_rect = match ScreenRect::from_points(view_projection, &corners)
+                    .and_then(|portal_rect| portal_rect.intersection(&rect))
+                {
+                    Some(portal_rect) => portal_rect,
+                    None => continue,
+                };
+                let area = match areas.get(&next) {
+                    Some(area) if area.contains(&portal_rect) => continue,
+                    Some(area) => area.union(&portal_rect),
+                    None => portal_rect,
+                };
+                areas.insert(next, area);
+                queue.push_back((next, portal_rect, Some(i), depth + 1));
+            }
+        }
+
+        areas.keys().cloned().collect()
+    }
+
+    /// Returns set of nodes which are inside of zones that are not visible from given
+    /// camera. Set is empty if there is no zones.
+    pub fn culled_nodes(&self, graph: &Graph, camera: &Camera) -> HashSet<Handle<Node>> {
+        let mut culled = HashSet::new();
+        if self.zones.is_empty() {
+            return culled;
+        }
+
+        let visible = self.visible_zones(
+            graph,
+            camera.global_position(),
+            &camera.view_projection_matrix(),
+        );
+        for zone in self.zones.iter() {
+            if graph.is_valid_handle(zone.node) && !visible.contains(&zone.node) {
+                culled.extend(graph.traverse_handle_iter(zone.node));
+            }
+        }
+        // Zones can be nested, content of visible inner zone must be drawn even if outer
+        // zone is not visible.
+        for zone in self.zones.iter() {
+            if visible.contains(&zone.node) && culled.contains(&zone.node) {
+                for handle in graph.traverse_handle_iter(zone.node) {
+                    culled.remove(&handle);
+                }
+            }
+        }
+        culled
+    }
+
+    pub(in crate) fn remap(&mut self, old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {
+        let remap_zone = |zone: Handle<Node>| {
+            if zone.is_none() {
+                Some(zone)
+            } else {
+                old_new_mapping.get(&zone).cloned()
+            }
+        };
+        self.zones = std::mem::take(&mut self.zones)
+            .into_iter()
+            .filter_map(|mut zone| {
+                zone.node = *old_new_mapping.get(&zone.node)?;
+                Some(zone)
+            })
+            .collect();
+        self.portals = std::mem::take(&mut self.portals)
+            .into_iter()
+            .filter_map(|mut portal| {
+                portal.node = *old_new_mapping.get(&portal.node)?;
+                portal.zones = [remap_zone(portal.zones[0])?, remap_zone(portal.zones[1])?];
+                Some(portal)
+            })
+            .collect();
+    }
+}
+
+impl Visit for PortalSystem {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.zones.visit("Zones", visitor)?;
+        self.portals.visit("Portals", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::{mat4::Mat4, quat::Quat, vec2::Vec2, vec3::Vec3},
+        scene::{
+            base::BaseBuilder,
+            graph::Graph,
+            portal::{Portal, PortalSystem, Zone},
+            transform::TransformBuilder,
+        },
+    };
+
+    #[test]
+    fn portal_visibility_test() {
+        let mut graph = Graph::new();
+        let mut add = |x: f32, rotation: Quat| {
+            graph.add_node(
+                BaseBuilder::new()
+                    .with_local_transform(
+                        TransformBuilder::new()
+                            .with_local_position(Vec3::new(x, 0.0, 0.0))
+                            .with_local_rotation(rotation)
+                            .build(),
+                    )
+                    .build_node(),
+            )
+        };
+        // Three rooms in a row along X axis, connected by two doors facing X axis.
+        let a = add(0.0, Quat::IDENTITY);
+        let b = add(10.0, Quat::IDENTITY);
+        let c = add(20.0, Quat::IDENTITY);
+        let door_rotation = Quat::from_axis_angle(Vec3::UP, std::f32::consts::FRAC_PI_2);
+        let door_ab = add(5.0, door_rotation);
+        let door_bc = add(15.0, door_rotation);
+        graph.update_hierachical_data();
+
+        let mut portals = PortalSystem::default();
+        for &node in [a, b, c].iter() {
+            portals.add_zone(Zone {
+                node,
+                half_extents: Vec3::new(5.0, 5.0, 5.0),
+            });
+        }
+        for &(node, zones) in [(door_ab, [a, b]), (door_bc, [b, c])].iter() {
+            portals.add_portal(Portal {
+                node,
+                half_size: Vec2::new(1.0, 1.0),
+                zones,
+                open: true,
+            });
+        }
+
+        assert_eq!(portals.zone_at(&graph, Vec3::new(1.0, 0.0, 0.0)), a);
+
+        // Camera in first room looks along X axis and sees every room through doors.
+        let projection = Mat4::perspective(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0);
+        let view = Mat4::look_at(Vec3::ZERO, Vec3::new(1.0, 0.0, 0.0), Vec3::UP).unwrap();
+        let visible = portals.visible_zones(&graph, Vec3::ZERO, &(projection * view));
+        assert!(visible.contains(&a) && visible.contains(&b) && visible.contains(&c));
+
+        // Closed door hides last room.
+        portals.portals_mut()[1].open = false;
+        let visible = portals.visible_zones(&graph, Vec3::ZERO, &(projection * view));
+        assert!(visible.contains(&b) && !visible.contains(&c));
+
+        // Looking away from doors hides every other room.
+        let view = Mat4::look_at(Vec3::ZERO, Vec3::new(-1.0, 0.0, 0.0), Vec3::UP).unwrap();
+        let visible = portals.visible_zones(&graph, Vec3::ZERO, &(projection * view));
+        assert!(visible.contains(&a) && !visible.contains(&b));
+    }
+}
@@ -0,0 +1,516 @@
+//! Contains all structures and methods to create and manage scatter nodes.
+//!
+//! Scatter is a set of instances of the same mesh (grass, bushes, rocks and so on) which
+//! are stored as light-weight transforms instead of separate scene nodes. Thousands of
+//! instances can be placed in a scene this way - they're rendered using hardware instancing,
+//! so there is no need to have a node per blade of grass.
+//!
+//! # Placement
+//!
+//! Instances can be added one by one, painted with a circle brush (`paint`) or scattered
+//! over a terrain (`paint_on_terrain`, `scatter_on_terrain`). Terrain placement puts each
+//! instance on terrain surface and can take density from a texture layer of terrain, so
+//! grass will grow only where grass texture is painted. Instances are defined in local
+//! coordinates of scatter node, so for terrain placement scatter node must be a child of the
+//! terrain node with identity local transform.
+//!
+//! # Distance fade
+//!
+//! Instances are smoothly dissolved between `fade_start` and `fade_end` distances from
+//! camera and not rendered at all beyond `fade_end`.
+//!
+//! # Limitations
+//!
+//! Scatters do not cast shadows, skinned surfaces are rendered in bind pose. Just like with
+//! meshes, only procedural surfaces are saved together with scene, surfaces taken from model
+//! resources must be assigned again after loading.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{math::vec2::Vec2, pool::Handle},
+//!     renderer::surface::Surface,
+//!     scene::{
+//!         base::BaseBuilder,
+//!         node::Node,
+//!         scatter::{ScatterBuilder, ScatterSettings},
+//!         Scene,
+//!     },
+//! };
+//!
+//! fn grow_grass(scene: &mut Scene, terrain: Handle<Node>, grass: Vec<Surface>) {
+//!     let mut scatter = ScatterBuilder::new(BaseBuilder::new())
+//!         .with_surfaces(grass)
+//!         .with_fade_distance(30.0, 40.0)
+//!         .build();
+//!
+//!     // Grass grows on first texture layer of terrain only.
+//!     let settings = ScatterSettings {
+//!         density: 4.0,
+//!         ..Default::default()
+//!     };
+//!     let mut rng = rand::thread_rng();
+//!     let terrain_ref = scene.graph[terrain].as_terrain();
+//!     scatter.scatter_on_terrain(terrain_ref, &settings, Some(0), &mut rng);
+//!
+//!     let scatter = scene.graph.add_node(Node::Scatter(scatter));
+//!     scene.graph.link_nodes(scatter, terrain);
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::{mat4::Mat4, quat::Quat, vec2::Vec2, vec3::Vec3},
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    renderer::surface::Surface,
+    scene::{
+        base::{Base, BaseBuilder},
+        node::Node,
+        terrain::Terrain,
+    },
+};
+use rand::Rng;
+use std::{
+    cell::Cell,
+    f32::consts::PI,
+    ops::{Deref, DerefMut},
+};
+
+/// Single instance of scatter.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScatterInstance {
+    /// Position in local coordinates of scatter node.
+    pub position: Vec3,
+    /// Rotation around vertical axis in radians.
+    pub rotation: f32,
+    /// Uniform scale.
+    pub scale: f32,
+}
+
+impl Default for ScatterInstance {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            rotation: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl ScatterInstance {
+    /// Returns transform of instance relative to scatter node.
+    pub fn local_transform(&self) -> Mat4 {
+        Mat4::translate(self.position)
+            * Mat4::from_quat(Quat::from_axis_angle(Vec3::UP, self.rotation))
+            * Mat4::scale(Vec3::new(self.scale, self.scale, self.scale))
+    }
+}
+
+impl Visit for ScatterInstance {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.position.visit("Position", visitor)?;
+        self.rotation.visit("Rotation", visitor)?;
+        self.scale.visit("Scale", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Defines how instances are generated by painting methods.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScatterSettings {
+    /// Average amount of instances per square unit.
+    pub density: f32,
+    /// Minimum scale of instance.
+    pub min_scale: f32,
+    /// Maximum scale of instance.
+    pub max_scale: f32,
+    /// Whether instances should get random rotation around vertical axis or not.
+    pub random_rotation: bool,
+}
+
+impl Default for ScatterSettings {
+    fn default() -> Self {
+        Self {
+            density: 1.0,
+            min_scale: 0.8,
+            max_scale: 1.2,
+            random_rotation: true,
+        }
+    }
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct Scatter {
+    base: Base,
+    surfaces: Vec<Surface>,
+    instances: Vec<ScatterInstance>,
+    fade_start: f32,
+    fade_end: f32,
+    bounding_radius: Cell<Option<f32>>,
+}
+
+impl Deref for Scatter {
+    type Target = Base;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for Scatter {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Default for Scatter {
+    fn default() -> Self {
+        ScatterBuilder::new(BaseBuilder::new()).build()
+    }
+}
+
+impl Scatter {
+    /// Returns shared reference to surfaces which are drawn for each instance.
+    pub fn surfaces(&self) -> &[Surface] {
+        &self.surfaces
+    }
+
+    /// Sets new surfaces which are drawn for each instance. Surfaces can be taken from any
+    /// mesh, they're shared so there is no need to copy vertices.
+    pub fn set_surfaces(&mut self, surfaces: Vec<Surface>) {
+        self.surfaces = surfaces;
+        self.bounding_radius.set(None);
+    }
+
+    /// Returns shared reference to instances.
+    pub fn instances(&self) -> &[ScatterInstance] {
+        &self.instances
+    }
+
+    /// Returns mutable reference to instances.
+    pub fn instances_mut(&mut self) -> &mut Vec<ScatterInstance> {
+        &mut self.instances
+    }
+
+    /// Adds new instance.
+    pub fn add_instance(&mut self, instance: ScatterInstance) {
+        self.instances.push(instance);
+    }
+
+    /// Removes every instance.
+    pub fn clear_instances(&mut self) {
+        self.instances.clear();
+    }
+
+    /// Removes every instance within given circle in XZ plane of scatter node. Returns
+    /// amount of removed instances.
+    pub fn remove_instances_in_circle(&mut self, center: Vec2, radius: f32) -> usize {
+        let count = self.instances.len();
+        self.instances.retain(|instance| {
+            let x = instance.position.x - center.x;
+            let z = instance.position.z - center.y;
+            x * x + z * z > radius * radius
+        });
+        count - self.instances.len()
+    }
+
+    /// Sets distances from camera at which instances start to fade out and at which they
+    /// disappear completely.
+    pub fn set_fade_distance(&mut self, start: f32, end: f32) {
+        self.fade_start = start.max(0.0);
+        self.fade_end = end.max(self.fade_start);
+    }
+
+    /// Returns distance at which instances start to fade out.
+    pub fn fade_start(&self) -> f32 {
+        self.fade_start
+    }
+
+    /// Returns distance at which instances disappear completely.
+    pub fn fade_end(&self) -> f32 {
+        self.fade_end
+    }
+
+    /// Returns radius of sphere around origin of unscaled instance which contains every
+    /// vertex of every surface.
+    pub fn bounding_radius(&self) -> f32 {
+        if let Some(radius) = self.bounding_radius.get() {
+            return radius;
+        }
+        let mut radius = 0.0f32;
+        for surface in self.surfaces.iter() {
+            let data = surface.data();
+            let data = data.lock().unwrap();
+            for vertex in data.get_vertices() {
+                radius = radius.max(vertex.position.len());
+            }
+        }
+        self.bounding_radius.set(Some(radius));
+        radius
+    }
+
+    /// Adds instances in given circle in XZ plane of scatter node, instances are placed at
+    /// zero height. Returns amount of added instances.
+    pub fn paint<R: Rng>(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        settings: &ScatterSettings,
+        rng: &mut R,
+    ) -> usize {
+        let count = random_count(settings.density * PI * radius * radius, rng);
+        self.scatter_points(
+            count,
+            settings,
+            rng,
+            |rng| random_point_in_circle(center, radius, rng),
+            |_, _| Some(0.0),
+        )
+    }
+
+    /// Adds instances in given circle on surface of given terrain. If layer is specified,
+    /// density is multiplied by weight of the layer, so instances will appear only where
+    /// the layer is painted. Returns amount of added instances. See module docs for more
+    /// info about coordinate systems.
+    pub fn paint_on_terrain<R: Rng>(
+        &mut self,
+        terrain: &Terrain,
+        center: Vec2,
+        radius: f32,
+        settings: &ScatterSettings,
+        layer: Option<usize>,
+        rng: &mut R,
+    ) -> usize {
+        let count = random_count(settings.density * PI * radius * radius, rng);
+        self.scatter_points(
+            count,
+            settings,
+            rng,
+            |rng| random_point_in_circle(center, radius, rng),
+            |point, rng| place_on_terrain(terrain, point, layer, rng),
+        )
+    }
+
+    /// Adds instances over whole terrain, see `paint_on_terrain`.
+    pub fn scatter_on_terrain<R: Rng>(
+        &mut self,
+        terrain: &Terrain,
+        settings: &ScatterSettings,
+        layer: Option<usize>,
+        rng: &mut R,
+    ) -> usize {
+        let (width, length) = (terrain.width(), terrain.length());
+        let count = random_count(settings.density * width * length, rng);
+        self.scatter_points(
+            count,
+            settings,
+            rng,
+            |rng| Vec2::new(rng.gen_range(0.0, width), rng.gen_range(0.0, length)),
+            |point, rng| place_on_terrain(terrain, point, layer, rng),
+        )
+    }
+
+    // Generates given amount of candidate points, each accepted point becomes an instance.
+    // Placement returns height of instance or None if point must be skipped.
+    fn scatter_points<R, S, P>(
+        &mut self,
+        count: usize,
+        settings: &ScatterSettings,
+        rng: &mut R,
+        mut sample: S,
+        mut placement: P,
+    ) -> usize
+    where
+        R: Rng,
+        S: FnMut(&mut R) -> Vec2,
+        P: FnMut(Vec2, &mut R) -> Option<f32>,
+    {
+        let min_scale = settings.min_scale.min(settings.max_scale);
+        let max_scale = settings.min_scale.max(settings.max_scale);
+        let mut added = 0;
+        for _ in 0..count {
+            let point = sample(rng);
+            if let Some(height) = placement(point, rng) {
+                let rotation = if settings.random_rotation {
+                    rng.gen_range(0.0, 2.0 * PI)
+                } else {
+                    0.0
+                };
+                let scale = if max_scale > min_scale {
+                    rng.gen_range(min_scale, max_scale)
+                } else {
+                    min_scale
+                };
+                self.instances.push(ScatterInstance {
+                    position: Vec3::new(point.x, height, point.y),
+                    rotation,
+                    scale,
+                });
+                added += 1;
+            }
+        }
+        added
+    }
+}
+
+fn random_count<R: Rng>(expected: f32, rng: &mut R) -> usize {
+    let expected = expected.max(0.0);
+    let whole = expected.floor();
+    // Fractional part becomes probability of one more instance, so small brushes with low
+    // density still produce instances on average.
+    if rng.gen::<f32>() < expected - whole {
+        whole as usize + 1
+    } else {
+        whole as usize
+    }
+}
+
+fn random_point_in_circle<R: Rng>(center: Vec2, radius: f32, rng: &mut R) -> Vec2 {
+    // Square root gives uniform distribution over area of circle.
+    let distance = radius * rng.gen::<f32>().sqrt();
+    let angle = rng.gen_range(0.0, 2.0 * PI);
+    Vec2::new(
+        center.x + distance * angle.cos(),
+        center.y + distance * angle.sin(),
+    )
+}
+
+fn place_on_terrain<R: Rng>(
+    terrain: &Terrain,
+    point: Vec2,
+    layer: Option<usize>,
+    rng: &mut R,
+) -> Option<f32> {
+    let inside = point.x >= 0.0
+        && point.y >= 0.0
+        && point.x <= terrain.width()
+        && point.y <= terrain.length();
+    if !inside {
+        return None;
+    }
+    if let Some(layer) = layer {
+        // Weight of layer is used as probability to keep the point.
+        if rng.gen::<f32>() >= terrain.layer_weight_at(point, layer) {
+            return None;
+        }
+    }
+    Some(terrain.height_at(point))
+}
+
+impl Visit for Scatter {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Base", visitor)?;
+        self.surfaces.visit("Surfaces", visitor)?;
+        self.instances.visit("Instances", visitor)?;
+        self.fade_start.visit("FadeStart", visitor)?;
+        self.fade_end.visit("FadeEnd", visitor)?;
+
+        if visitor.is_reading() {
+            self.bounding_radius.set(None);
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Scatter builder allows you to construct scatter in declarative manner.
+pub struct ScatterBuilder {
+    base_builder: BaseBuilder,
+    surfaces: Vec<Surface>,
+    instances: Vec<ScatterInstance>,
+    fade_start: f32,
+    fade_end: f32,
+}
+
+impl ScatterBuilder {
+    /// Creates new builder without surfaces and instances, instances will fade out between
+    /// 50 and 60 units from camera.
+    pub fn new(base_builder: BaseBuilder) -> Self {
+        Self {
+            base_builder,
+            surfaces: Default::default(),
+            instances: Default::default(),
+            fade_start: 50.0,
+            fade_end: 60.0,
+        }
+    }
+
+    /// Sets desired surfaces.
+    pub fn with_surfaces(mut self, surfaces: Vec<Surface>) -> Self {
+        self.surfaces = surfaces;
+        self
+    }
+
+    /// Sets desired instances.
+    pub fn with_instances(mut self, instances: Vec<ScatterInstance>) -> Self {
+        self.instances = instances;
+        self
+    }
+
+    /// Sets desired fade distances, see `Scatter::set_fade_distance`.
+    pub fn with_fade_distance(mut self, start: f32, end: f32) -> Self {
+        self.fade_start = start.max(0.0);
+        self.fade_end = end.max(self.fade_start);
+        self
+    }
+
+    /// Creates new scatter instance.
+    pub fn build(self) -> Scatter {
+        Scatter {
+            base: self.base_builder.build(),
+            surfaces: self.surfaces,
+            instances: self.instances,
+            fade_start: self.fade_start,
+            fade_end: self.fade_end,
+            bounding_radius: Cell::new(None),
+        }
+    }
+
+    /// Creates new node instance.
+    pub fn build_node(self) -> Node {
+        Node::Scatter(self.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::vec2::Vec2,
+        scene::{
+            base::BaseBuilder,
+            scatter::{ScatterBuilder, ScatterSettings},
+        },
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn paint_and_erase() {
+        let mut scatter = ScatterBuilder::new(BaseBuilder::new()).build();
+        let mut rng = StdRng::seed_from_u64(42);
+        let settings = ScatterSettings {
+            density: 4.0,
+            ..Default::default()
+        };
+        let center = Vec2::new(10.0, -5.0);
+
+        let count = scatter.paint(center, 3.0, &settings, &mut rng);
+        assert!(count > 0);
+        assert_eq!(scatter.instances().len(), count);
+        for instance in scatter.instances() {
+            let offset = Vec2::new(instance.position.x - center.x, instance.position.z - center.y);
+            assert!(offset.len() <= 3.0 + std::f32::EPSILON);
+            assert!(instance.scale >= settings.min_scale && instance.scale <= settings.max_scale);
+        }
+
+        assert_eq!(scatter.remove_instances_in_circle(center, 3.5), count);
+        assert!(scatter.instances().is_empty());
+    }
+}
@@ -0,0 +1,326 @@
+//! Contains spatial hash for gameplay proximity queries.
+//!
+//! Spatial hash splits space into cubic cells of equal size and remembers which nodes are
+//! in each cell. This allows to answer questions like "which enemies are closer than 10
+//! meters to the player" without checking every node of a scene. Only registered nodes
+//! are tracked; scene updates their cells incrementally each frame using global positions
+//! of nodes and automatically unregisters removed nodes.
+//!
+//! # Cell size
+//!
+//! Cell size should be about the same as typical query radius. Too small cells will make
+//! queries visit many empty cells, too big cells will make queries check many far nodes.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::scene::{Scene, node::Node};
+//! use rg3d::core::{pool::Handle, math::vec3::Vec3};
+//!
+//! fn enemies_in_aggro_range(scene: &Scene, player_position: Vec3) -> Vec<Handle<Node>> {
+//!     let mut enemies = Vec::new();
+//!     scene.spatial_hash.query_radius(player_position, 10.0, &mut enemies);
+//!     enemies
+//! }
+//! ```
+
+use crate::{
+    core::{math::vec3::Vec3, pool::Handle},
+    scene::{graph::Graph, node::Node},
+};
+use std::collections::HashMap;
+
+type Cell = (i32, i32, i32);
+
+#[derive(Copy, Clone, Debug)]
+struct Entry {
+    cell: Cell,
+    position: Vec3,
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<Handle<Node>>>,
+    entries: HashMap<Handle<Node>, Entry>,
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(SpatialHash::DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialHash {
+    /// Default size of cell in meters.
+    pub const DEFAULT_CELL_SIZE: f32 = 8.0;
+
+    /// Creates new empty spatial hash with given size of cell.
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(std::f32::EPSILON),
+            cells: Default::default(),
+            entries: Default::default(),
+        }
+    }
+
+    /// Returns size of cell.
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Sets new size of cell, every registered node will be rehashed.
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.cell_size = cell_size.max(std::f32::EPSILON);
+        for entry in self.entries.values_mut() {
+            entry.cell = cell_of(self.cell_size, entry.position);
+        }
+        self.rebuild_cells();
+    }
+
+    /// Starts tracking of given node. Node must belong to graph of the scene which owns
+    /// the spatial hash. Does nothing if node is already registered.
+    pub fn register(&mut self, graph: &Graph, handle: Handle<Node>) {
+        if self.entries.contains_key(&handle) || !graph.is_valid_handle(handle) {
+            return;
+        }
+        let position = graph[handle].global_position();
+        let cell = cell_of(self.cell_size, position);
+        self.cells.entry(cell).or_default().push(handle);
+        self.entries.insert(handle, Entry { cell, position });
+    }
+
+    /// Stops tracking of given node. Returns true if node was registered.
+    pub fn unregister(&mut self, handle: Handle<Node>) -> bool {
+        if let Some(entry) = self.entries.remove(&handle) {
+            self.remove_from_cell(entry.cell, handle);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns true if node is tracked by spatial hash.
+    pub fn is_registered(&self, handle: Handle<Node>) -> bool {
+        self.entries.contains_key(&handle)
+    }
+
+    /// Returns amount of tracked nodes.
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Stops tracking of every node.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+    }
+
+    /// Collects every node which is closer than `radius` to given position into `result`.
+    /// Result is cleared first. Positions of nodes are taken from last scene update.
+    pub fn query_radius(&self, position: Vec3, radius: f32, result: &mut Vec<Handle<Node>>) {
+        result.clear();
+
+        let min = cell_of(self.cell_size, position - Vec3::new(radius, radius, radius));
+        let max = cell_of(self.cell_size, position + Vec3::new(radius, radius, radius));
+        let sqr_radius = radius * radius;
+
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    if let Some(handles) = self.cells.get(&(x, y, z)) {
+                        for handle in handles {
+                            let entry = &self.entries[handle];
+                            if entry.position.sqr_distance(&position) <= sqr_radius {
+                                result.push(*handle);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collects up to `k` nearest nodes to given position into `result` together with
+    /// distances to them, closest nodes go first. Result is cleared first.
+    pub fn k_nearest(&self, position: Vec3, k: usize, result: &mut Vec<(Handle<Node>, f32)>) {
+        result.clear();
+
+        if k == 0 || self.entries.is_empty() {
+            return;
+        }
+
+        let center = cell_of(self.cell_size, position);
+        let mut visited = 0;
+        let mut ring = 0;
+        loop {
+            // Visit cells which are exactly at `ring` cells away from center cell.
+            for x in -ring..=ring {
+                for y in -ring..=ring {
+                    for z in -ring..=ring {
+                        if x.abs().max(y.abs()).max(z.abs()) != ring {
+                            continue;
+                        }
+                        let cell = (center.0 + x, center.1 + y, center.2 + z);
+                        if let Some(handles) = self.cells.get(&cell) {
+                            for handle in handles {
+                                let entry = &self.entries[handle];
+                                result.push((*handle, entry.position.sqr_distance(&position)));
+                            }
+                            visited += handles.len();
+                        }
+                    }
+                }
+            }
+
+            result.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            result.truncate(k);
+
+            // Every node closer than this distance was already visited.
+            let covered = ring as f32 * self.cell_size;
+            if visited == self.entries.len()
+                || (result.len() == k && result[k - 1].1 <= covered * covered)
+            {
+                break;
+            }
+
+            ring += 1;
+        }
+
+        for (_, distance) in result.iter_mut() {
+            *distance = distance.sqrt();
+        }
+    }
+
+    fn remove_from_cell(&mut self, cell: Cell, handle: Handle<Node>) {
+        if let Some(handles) = self.cells.get_mut(&cell) {
+            if let Some(index) = handles.iter().position(|h| *h == handle) {
+                handles.swap_remove(index);
+            }
+            if handles.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    pub(in crate) fn update(&mut self, graph: &Graph) {
+        let cell_size = self.cell_size;
+        let mut moved = Vec::new();
+        let mut removed = Vec::new();
+        for (&handle, entry) in self.entries.iter_mut() {
+            if !graph.is_valid_handle(handle) {
+                removed.push(handle);
+                continue;
+            }
+            entry.position = graph[handle].global_position();
+            let cell = cell_of(cell_size, entry.position);
+            if cell != entry.cell {
+                moved.push((handle, entry.cell, cell));
+                entry.cell = cell;
+            }
+        }
+
+        // Order of hash map is random, handles are sorted so content of cells (and order of
+        // query results) is the same in every run, which is required by deterministic mode.
+        moved.sort_unstable_by_key(|(handle, ..)| handle.index());
+        removed.sort_unstable_by_key(|handle| handle.index());
+
+        for (handle, old, new) in moved {
+            self.remove_from_cell(old, handle);
+            self.cells.entry(new).or_default().push(handle);
+        }
+
+        for handle in removed {
+            self.unregister(handle);
+        }
+    }
+
+    pub(in crate) fn remap(&mut self, old_new_mapping: &HashMap<Handle<Node>, Handle<Node>>) {
+        self.entries = std::mem::take(&mut self.entries)
+            .into_iter()
+            .filter_map(|(handle, entry)| Some((*old_new_mapping.get(&handle)?, entry)))
+            .collect();
+        self.rebuild_cells();
+    }
+
+    fn rebuild_cells(&mut self) {
+        let mut handles = self.entries.keys().cloned().collect::<Vec<_>>();
+        handles.sort_unstable_by_key(|handle| handle.index());
+        self.cells.clear();
+        for handle in handles {
+            let cell = self.entries[&handle].cell;
+            self.cells.entry(cell).or_default().push(handle);
+        }
+    }
+}
+
+fn cell_of(cell_size: f32, position: Vec3) -> Cell {
+    (
+        (position.x / cell_size).floor() as i32,
+        (position.y / cell_size).floor() as i32,
+        (position.z / cell_size).floor() as i32,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::vec3::Vec3,
+        scene::{
+            base::BaseBuilder, graph::Graph, spatial_hash::SpatialHash,
+            transform::TransformBuilder,
+        },
+    };
+
+    #[test]
+    fn spatial_hash_query_test() {
+        let mut graph = Graph::new();
+        let mut spatial_hash = SpatialHash::new(2.0);
+        let nodes = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.5, 0.0, 0.0),
+            Vec3::new(-5.0, 0.0, 0.0),
+            Vec3::new(20.0, 0.0, 0.0),
+        ]
+        .iter()
+        .map(|&position| {
+            graph.add_node(
+                BaseBuilder::new()
+                    .with_local_transform(
+                        TransformBuilder::new()
+                            .with_local_position(position)
+                            .build(),
+                    )
+                    .build_node(),
+            )
+        })
+        .collect::<Vec<_>>();
+        graph.update_hierachical_data();
+        for &node in nodes.iter() {
+            spatial_hash.register(&graph, node);
+        }
+
+        let mut result = Vec::new();
+        spatial_hash.query_radius(Vec3::new(0.5, 0.0, 0.0), 2.0, &mut result);
+        result.sort_by_key(|h| h.index());
+        assert_eq!(result, vec![nodes[0], nodes[1]]);
+
+        let mut nearest = Vec::new();
+        spatial_hash.k_nearest(Vec3::new(19.0, 0.0, 0.0), 2, &mut nearest);
+        assert_eq!(nearest.len(), 2);
+        assert_eq!(nearest[0].0, nodes[3]);
+        assert_eq!(nearest[1].0, nodes[1]);
+
+        // Moved node must be found in new cell, removed node must be unregistered.
+        graph[nodes[2]]
+            .local_transform_mut()
+            .set_position(Vec3::new(19.0, 1.0, 0.0));
+        graph.remove_node(nodes[3]);
+        graph.update_hierachical_data();
+        spatial_hash.update(&graph);
+        assert!(!spatial_hash.is_registered(nodes[3]));
+        spatial_hash.query_radius(Vec3::new(19.0, 0.0, 0.0), 2.0, &mut result);
+        assert_eq!(result, vec![nodes[2]]);
+    }
+}
@@ -0,0 +1,494 @@
+//! Contains all structures and methods to create and manage splines.
+//!
+//! Spline is a smooth curve defined by a set of control points in local coordinates of
+//! spline node. Splines are invisible, they're used to define paths for cameras, moving
+//! platforms, particles and so on.
+//!
+//! # Kinds
+//!
+//! Catmull-Rom spline passes through every control point, which makes it very easy to
+//! author - just place points along desired path. Bezier spline is made of cubic segments,
+//! each segment has two end points and two tangent handles between them, so points go in
+//! order `point, handle, handle, point, handle, handle, point, ...` and only every third
+//! point lies on the curve. Bezier splines give more control over shape of curve.
+//!
+//! # Parametrization
+//!
+//! `point_at` and `tangent_at` take parameter in [0; 1] range which is proportional to
+//! distance along the curve (arc length), not to index of segment. This means that object
+//! that moves with constant speed of parameter moves with constant speed in space, no
+//! matter how points are distributed.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{math::vec3::Vec3, pool::Handle},
+//!     scene::{base::BaseBuilder, node::Node, spline::SplineBuilder, Scene},
+//! };
+//!
+//! fn create_path(scene: &mut Scene) -> Handle<Node> {
+//!     scene.graph.add_node(
+//!         SplineBuilder::new(BaseBuilder::new())
+//!             .with_points(vec![
+//!                 Vec3::new(0.0, 0.0, 0.0),
+//!                 Vec3::new(10.0, 2.0, 0.0),
+//!                 Vec3::new(10.0, 2.0, 10.0),
+//!                 Vec3::new(0.0, 0.0, 10.0),
+//!             ])
+//!             .with_closed(true)
+//!             .build_node(),
+//!     )
+//! }
+//!
+//! fn move_along_path(scene: &mut Scene, path: Handle<Node>, object: Handle<Node>, t: f32) {
+//!     let position = scene.graph[path].as_spline().global_point_at(t);
+//!     scene.graph[object].local_transform_mut().set_position(position);
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::vec3::Vec3,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{
+        base::{Base, BaseBuilder},
+        node::Node,
+    },
+};
+use std::ops::{Deref, DerefMut};
+
+/// Defines how curve is built from control points. See module docs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SplineKind {
+    /// Curve passes through every control point.
+    CatmullRom,
+    /// Curve is made of cubic Bezier segments.
+    Bezier,
+}
+
+impl Default for SplineKind {
+    fn default() -> Self {
+        SplineKind::CatmullRom
+    }
+}
+
+impl SplineKind {
+    fn id(self) -> u32 {
+        match self {
+            SplineKind::CatmullRom => 0,
+            SplineKind::Bezier => 1,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(SplineKind::CatmullRom),
+            1 => Ok(SplineKind::Bezier),
+            _ => Err(format!("Invalid spline kind {}", id)),
+        }
+    }
+}
+
+impl Visit for SplineKind {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+
+        visitor.leave_region()
+    }
+}
+
+/// Amount of samples per segment in arc length table.
+const SAMPLES_PER_SEGMENT: usize = 16;
+
+/// See module docs.
+#[derive(Clone, Debug, Default)]
+pub struct Spline {
+    base: Base,
+    kind: SplineKind,
+    points: Vec<Vec3>,
+    closed: bool,
+    // Distances along the curve of uniformly distributed (by raw parameter) samples,
+    // used for arc length parametrization. Not serialized, rebuilt on load.
+    arc_lengths: Vec<f32>,
+}
+
+impl Deref for Spline {
+    type Target = Base;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for Spline {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Visit for Spline {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Common", visitor)?;
+        self.kind.visit("Kind", visitor)?;
+        self.points.visit("Points", visitor)?;
+        self.closed.visit("Closed", visitor)?;
+
+        if visitor.is_reading() {
+            self.rebuild_arc_lengths();
+        }
+
+        visitor.leave_region()
+    }
+}
+
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1.scale(2.0)
+        + (p2 - p0).scale(t)
+        + (p0.scale(2.0) - p1.scale(5.0) + p2.scale(4.0) - p3).scale(t2)
+        + (p1.scale(3.0) - p0 - p2.scale(3.0) + p3).scale(t3))
+    .scale(0.5)
+}
+
+fn catmull_rom_derivative(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let t2 = t * t;
+    ((p2 - p0)
+        + (p0.scale(2.0) - p1.scale(5.0) + p2.scale(4.0) - p3).scale(2.0 * t)
+        + (p1.scale(3.0) - p0 - p2.scale(3.0) + p3).scale(3.0 * t2))
+    .scale(0.5)
+}
+
+fn bezier(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let s = 1.0 - t;
+    p0.scale(s * s * s)
+        + p1.scale(3.0 * s * s * t)
+        + p2.scale(3.0 * s * t * t)
+        + p3.scale(t * t * t)
+}
+
+fn bezier_derivative(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    let s = 1.0 - t;
+    (p1 - p0).scale(3.0 * s * s) + (p2 - p1).scale(6.0 * s * t) + (p3 - p2).scale(3.0 * t * t)
+}
+
+impl Spline {
+    /// Returns kind of spline.
+    pub fn kind(&self) -> SplineKind {
+        self.kind
+    }
+
+    /// Sets new kind of spline.
+    pub fn set_kind(&mut self, kind: SplineKind) {
+        self.kind = kind;
+        self.rebuild_arc_lengths();
+    }
+
+    /// Returns control points in local coordinates.
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    /// Sets new control points in local coordinates.
+    pub fn set_points(&mut self, points: Vec<Vec3>) {
+        self.points = points;
+        self.rebuild_arc_lengths();
+    }
+
+    /// Moves control point with given index. Does nothing if index is out of bounds.
+    pub fn set_point(&mut self, index: usize, point: Vec3) {
+        if let Some(p) = self.points.get_mut(index) {
+            *p = point;
+            self.rebuild_arc_lengths();
+        }
+    }
+
+    /// Adds new control point to the end of spline.
+    pub fn add_point(&mut self, point: Vec3) {
+        self.points.push(point);
+        self.rebuild_arc_lengths();
+    }
+
+    /// Removes control point with given index. Does nothing if index is out of bounds.
+    pub fn remove_point(&mut self, index: usize) {
+        if index < self.points.len() {
+            self.points.remove(index);
+            self.rebuild_arc_lengths();
+        }
+    }
+
+    /// Returns true if end of spline is connected with its beginning.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Connects or disconnects end of spline with its beginning.
+    pub fn set_closed(&mut self, closed: bool) {
+        self.closed = closed;
+        self.rebuild_arc_lengths();
+    }
+
+    /// Returns amount of curve segments.
+    pub fn segment_count(&self) -> usize {
+        let count = self.points.len();
+        match self.kind {
+            SplineKind::CatmullRom => {
+                if count < 2 {
+                    0
+                } else if self.closed {
+                    count
+                } else {
+                    count - 1
+                }
+            }
+            SplineKind::Bezier => {
+                if count < 4 {
+                    0
+                } else if self.closed {
+                    count / 3
+                } else {
+                    (count - 1) / 3
+                }
+            }
+        }
+    }
+
+    fn segment_points(&self, segment: usize) -> (Vec3, Vec3, Vec3, Vec3) {
+        let count = self.points.len();
+        match self.kind {
+            SplineKind::CatmullRom => {
+                let index = |i: isize| {
+                    if self.closed {
+                        i.rem_euclid(count as isize) as usize
+                    } else {
+                        i.max(0).min(count as isize - 1) as usize
+                    }
+                };
+                let i = segment as isize;
+                (
+                    self.points[index(i - 1)],
+                    self.points[index(i)],
+                    self.points[index(i + 1)],
+                    self.points[index(i + 2)],
+                )
+            }
+            SplineKind::Bezier => {
+                let i = segment * 3;
+                (
+                    self.points[i],
+                    self.points[i + 1],
+                    self.points[i + 2],
+                    self.points[(i + 3) % count],
+                )
+            }
+        }
+    }
+
+    /// Splits raw parameter in [0; segment_count] range into segment index and local
+    /// parameter of the segment.
+    fn split_raw(&self, raw: f32) -> (usize, f32) {
+        let last = self.segment_count() - 1;
+        let segment = (raw.max(0.0).floor() as usize).min(last);
+        (segment, (raw - segment as f32).max(0.0).min(1.0))
+    }
+
+    fn raw_point(&self, raw: f32) -> Vec3 {
+        let (segment, t) = self.split_raw(raw);
+        let (p0, p1, p2, p3) = self.segment_points(segment);
+        match self.kind {
+            SplineKind::CatmullRom => catmull_rom(p0, p1, p2, p3, t),
+            SplineKind::Bezier => bezier(p0, p1, p2, p3, t),
+        }
+    }
+
+    fn raw_derivative(&self, raw: f32) -> Vec3 {
+        let (segment, t) = self.split_raw(raw);
+        let (p0, p1, p2, p3) = self.segment_points(segment);
+        match self.kind {
+            SplineKind::CatmullRom => catmull_rom_derivative(p0, p1, p2, p3, t),
+            SplineKind::Bezier => bezier_derivative(p0, p1, p2, p3, t),
+        }
+    }
+
+    fn rebuild_arc_lengths(&mut self) {
+        self.arc_lengths.clear();
+        let sample_count = self.segment_count() * SAMPLES_PER_SEGMENT;
+        if sample_count == 0 {
+            return;
+        }
+        let mut length = 0.0;
+        let mut prev = self.raw_point(0.0);
+        self.arc_lengths.push(0.0);
+        for i in 1..=sample_count {
+            let point = self.raw_point(i as f32 / SAMPLES_PER_SEGMENT as f32);
+            length += (point - prev).len();
+            self.arc_lengths.push(length);
+            prev = point;
+        }
+    }
+
+    /// Converts parameter proportional to arc length into raw parameter.
+    fn arc_to_raw(&self, t: f32) -> f32 {
+        let total = self.length();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let distance = t.max(0.0).min(1.0) * total;
+        // Find last sample which is not further than desired distance.
+        let index = match self
+            .arc_lengths
+            .binary_search_by(|l| l.partial_cmp(&distance).unwrap())
+        {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        };
+        let index = index.min(self.arc_lengths.len() - 2);
+        let begin = self.arc_lengths[index];
+        let span = self.arc_lengths[index + 1] - begin;
+        let k = if span > 0.0 {
+            (distance - begin) / span
+        } else {
+            0.0
+        };
+        (index as f32 + k) / SAMPLES_PER_SEGMENT as f32
+    }
+
+    /// Returns approximate length of curve in local coordinates.
+    pub fn length(&self) -> f32 {
+        self.arc_lengths.last().copied().unwrap_or_default()
+    }
+
+    /// Returns point on curve in local coordinates, `t` is a fraction of curve length in
+    /// [0; 1] range. Returns first control point (or zero vector if there is no control
+    /// points) if spline has not enough points to form a segment.
+    pub fn point_at(&self, t: f32) -> Vec3 {
+        if self.segment_count() == 0 {
+            return self.points.first().copied().unwrap_or(Vec3::ZERO);
+        }
+        self.raw_point(self.arc_to_raw(t))
+    }
+
+    /// Returns normalized tangent of curve in local coordinates, `t` is a fraction of curve
+    /// length in [0; 1] range. Returns zero vector if spline has not enough points to form a
+    /// segment.
+    pub fn tangent_at(&self, t: f32) -> Vec3 {
+        if self.segment_count() == 0 {
+            return Vec3::ZERO;
+        }
+        self.raw_derivative(self.arc_to_raw(t))
+            .normalized()
+            .unwrap_or(Vec3::ZERO)
+    }
+
+    /// Same as `point_at`, but returns point in world coordinates. Global transform of
+    /// node is calculated at graph update, so it lags one frame for moving splines.
+    pub fn global_point_at(&self, t: f32) -> Vec3 {
+        self.global_transform().transform_vector(self.point_at(t))
+    }
+
+    /// Same as `tangent_at`, but returns tangent in world coordinates.
+    pub fn global_tangent_at(&self, t: f32) -> Vec3 {
+        let transform = self.global_transform();
+        let origin = transform.transform_vector(Vec3::ZERO);
+        (transform.transform_vector(self.tangent_at(t)) - origin)
+            .normalized()
+            .unwrap_or(Vec3::ZERO)
+    }
+}
+
+/// Spline builder allows you to construct spline in declarative manner.
+/// This is typical implementation of Builder pattern.
+pub struct SplineBuilder {
+    base_builder: BaseBuilder,
+    kind: SplineKind,
+    points: Vec<Vec3>,
+    closed: bool,
+}
+
+impl SplineBuilder {
+    /// Creates new builder of open Catmull-Rom spline without points.
+    pub fn new(base_builder: BaseBuilder) -> Self {
+        Self {
+            base_builder,
+            kind: SplineKind::CatmullRom,
+            points: Default::default(),
+            closed: false,
+        }
+    }
+
+    /// Sets desired kind of spline.
+    pub fn with_kind(mut self, kind: SplineKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Sets desired control points in local coordinates.
+    pub fn with_points(mut self, points: Vec<Vec3>) -> Self {
+        self.points = points;
+        self
+    }
+
+    /// Sets whether end of spline should be connected with its beginning or not.
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+
+    /// Creates new spline.
+    pub fn build(self) -> Spline {
+        let mut spline = Spline {
+            base: self.base_builder.build(),
+            kind: self.kind,
+            points: self.points,
+            closed: self.closed,
+            arc_lengths: Default::default(),
+        };
+        spline.rebuild_arc_lengths();
+        spline
+    }
+
+    /// Creates new spline instance wrapped into Node.
+    pub fn build_node(self) -> Node {
+        Node::Spline(self.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::vec3::Vec3,
+        scene::{
+            base::BaseBuilder,
+            spline::{SplineBuilder, SplineKind},
+        },
+    };
+
+    #[test]
+    fn spline_arc_length_test() {
+        // Points are distributed non-uniformly, but parametrization must be uniform.
+        let spline = SplineBuilder::new(BaseBuilder::new())
+            .with_kind(SplineKind::Bezier)
+            .with_points(vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(2.0, 0.0, 0.0),
+                Vec3::new(9.0, 0.0, 0.0),
+            ])
+            .build();
+
+        assert!((spline.length() - 9.0).abs() < 0.001);
+        assert!((spline.point_at(0.5).x - 4.5).abs() < 0.05);
+        assert!((spline.point_at(1.0).x - 9.0).abs() < 0.001);
+        assert!((spline.tangent_at(0.25).x - 1.0).abs() < 0.001);
+    }
+}
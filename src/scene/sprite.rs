@@ -7,11 +7,41 @@
 //!
 //! Huge amount of sprites may cause performance issues, also uou should
 //! not use sprites to make particle systems, use ParticleSystem instead.
+//!
+//! # Animation
+//!
+//! Sprite can play a sequence of frames from an atlas texture (sprite sheet), which is
+//! enough for simple animations like explosions, flames or blinking markers. Frames are
+//! regions of texture, animation is updated by scene. See `SpriteAnimation`.
+//!
+//! ```no_run
+//! use rg3d::scene::{
+//!     base::BaseBuilder,
+//!     node::Node,
+//!     sprite::{AtlasRegion, SpriteAnimation, SpriteBuilder},
+//! };
+//!
+//! fn make_explosion() -> Node {
+//!     // Sprite sheet with 4x4 frames.
+//!     let animation = SpriteAnimation::new(AtlasRegion::grid(4, 4), 24.0).with_looped(false);
+//!     SpriteBuilder::new(BaseBuilder::new().with_lifetime(16.0 / 24.0))
+//!         .with_animation(animation)
+//!         .build_node()
+//! }
+//! ```
+//!
+//! # Sorting
+//!
+//! Sprites are semi-transparent and do not write depth, so the order in which they are drawn
+//! matters when they overlap. Every sprite has sort layer, sprites with lesser layer are
+//! drawn first, so sprites with greater layer are drawn on top of them. This is main way of
+//! ordering sprites in 2D games, see `dim2` module docs.
 
 use crate::scene::node::Node;
 use crate::{
     core::{
         color::Color,
+        math::vec2::Vec2,
         visitor::{Visit, VisitResult, Visitor},
     },
     resource::texture::Texture,
@@ -22,6 +52,227 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+/// Rectangular region of texture in normalized texture coordinates.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    /// Top left corner of region.
+    pub min: Vec2,
+    /// Bottom right corner of region.
+    pub max: Vec2,
+}
+
+impl Default for AtlasRegion {
+    fn default() -> Self {
+        Self {
+            min: Vec2::ZERO,
+            max: Vec2::new(1.0, 1.0),
+        }
+    }
+}
+
+impl AtlasRegion {
+    /// Splits whole texture into grid of regions of equal size. Regions are ordered row
+    /// by row, from left to right.
+    pub fn grid(columns: usize, rows: usize) -> Vec<AtlasRegion> {
+        let (columns, rows) = (columns.max(1), rows.max(1));
+        let width = 1.0 / columns as f32;
+        let height = 1.0 / rows as f32;
+        let mut regions = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let min = Vec2::new(column as f32 * width, row as f32 * height);
+                regions.push(AtlasRegion {
+                    min,
+                    max: Vec2::new(min.x + width, min.y + height),
+                });
+            }
+        }
+        regions
+    }
+
+    /// Maps given texture coordinates in [0; 1] range into this region.
+    pub fn map(&self, tex_coord: Vec2) -> Vec2 {
+        Vec2::new(
+            self.min.x + (self.max.x - self.min.x) * tex_coord.x,
+            self.min.y + (self.max.y - self.min.y) * tex_coord.y,
+        )
+    }
+}
+
+impl Visit for AtlasRegion {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.min.visit("Min", visitor)?;
+        self.max.visit("Max", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Sequence of atlas regions which are shown one after another with fixed rate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpriteAnimation {
+    frames: Vec<AtlasRegion>,
+    fps: f32,
+    looped: bool,
+    playing: bool,
+    time: f32,
+}
+
+impl Default for SpriteAnimation {
+    fn default() -> Self {
+        Self::new(Vec::new(), 30.0)
+    }
+}
+
+impl SpriteAnimation {
+    /// Creates new looped animation with given frames and frame rate. Animation starts
+    /// playing immediately.
+    pub fn new(frames: Vec<AtlasRegion>, fps: f32) -> Self {
+        Self {
+            frames,
+            fps: fps.max(0.0),
+            looped: true,
+            playing: true,
+            time: 0.0,
+        }
+    }
+
+    /// Sets whether animation should start over when it reaches last frame or not.
+    pub fn with_looped(mut self, looped: bool) -> Self {
+        self.looped = looped;
+        self
+    }
+
+    /// Returns shared reference to frames.
+    pub fn frames(&self) -> &[AtlasRegion] {
+        &self.frames
+    }
+
+    /// Sets new frames and rewinds animation.
+    pub fn set_frames(&mut self, frames: Vec<AtlasRegion>) {
+        self.frames = frames;
+        self.time = 0.0;
+    }
+
+    /// Sets new frame rate.
+    pub fn set_fps(&mut self, fps: f32) {
+        self.fps = fps.max(0.0);
+    }
+
+    /// Returns frame rate.
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    /// Sets whether animation should start over when it reaches last frame or not.
+    pub fn set_looped(&mut self, looped: bool) {
+        self.looped = looped;
+    }
+
+    /// Returns true if animation is looped.
+    pub fn is_looped(&self) -> bool {
+        self.looped
+    }
+
+    /// Starts or resumes playback. Finished animation is played from the beginning.
+    pub fn play(&mut self) {
+        if self.is_finished() {
+            self.time = 0.0;
+        }
+        self.playing = true;
+    }
+
+    /// Pauses playback at current frame.
+    pub fn pause(&mut self) {
+        self.playing = false;
+    }
+
+    /// Stops playback and rewinds animation to first frame.
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.time = 0.0;
+    }
+
+    /// Returns true if animation is playing.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Returns true if not looped animation has reached its last frame.
+    pub fn is_finished(&self) -> bool {
+        !self.looped && self.time >= self.duration()
+    }
+
+    /// Returns duration of single playback in seconds.
+    pub fn duration(&self) -> f32 {
+        if self.fps > 0.0 {
+            self.frames.len() as f32 / self.fps
+        } else {
+            0.0
+        }
+    }
+
+    /// Sets current frame.
+    pub fn set_current_frame(&mut self, frame: usize) {
+        if self.fps > 0.0 {
+            self.time = frame.min(self.frames.len().saturating_sub(1)) as f32 / self.fps;
+        }
+    }
+
+    /// Returns index of current frame.
+    pub fn current_frame(&self) -> usize {
+        let frame = (self.time * self.fps) as usize;
+        frame.min(self.frames.len().saturating_sub(1))
+    }
+
+    /// Returns region of current frame, or whole texture if there is no frames.
+    pub fn current_region(&self) -> AtlasRegion {
+        self.frames
+            .get(self.current_frame())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Advances animation by given amount of time. Animation is updated automatically
+    /// by scene, there is no need to call this method manually.
+    pub fn update(&mut self, dt: f32) {
+        if !self.playing {
+            return;
+        }
+
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return;
+        }
+
+        self.time += dt;
+        if self.time >= duration {
+            if self.looped {
+                self.time %= duration;
+            } else {
+                self.time = duration;
+                self.playing = false;
+            }
+        }
+    }
+}
+
+impl Visit for SpriteAnimation {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.frames.visit("Frames", visitor)?;
+        self.fps.visit("Fps", visitor)?;
+        self.looped.visit("Looped", visitor)?;
+        self.playing.visit("Playing", visitor)?;
+        self.time.visit("Time", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
 /// See module docs.
 #[derive(Clone, Debug)]
 pub struct Sprite {
@@ -30,6 +281,9 @@ pub struct Sprite {
     color: Color,
     size: f32,
     rotation: f32,
+    animation: Option<SpriteAnimation>,
+    region: AtlasRegion,
+    sort_layer: i32,
 }
 
 impl Deref for Sprite {
@@ -84,6 +338,17 @@ impl Sprite {
         self.rotation
     }
 
+    /// Sets new sort layer, sprites with greater layer are drawn on top of sprites with
+    /// lesser layer. See module docs.
+    pub fn set_sort_layer(&mut self, sort_layer: i32) {
+        self.sort_layer = sort_layer;
+    }
+
+    /// Returns current sort layer.
+    pub fn sort_layer(&self) -> i32 {
+        self.sort_layer
+    }
+
     /// Sets new texture for sprite.
     pub fn set_texture(&mut self, texture: Arc<Mutex<Texture>>) {
         self.texture = Some(texture);
@@ -94,6 +359,47 @@ impl Sprite {
     pub fn texture(&self) -> Option<Arc<Mutex<Texture>>> {
         self.texture.clone()
     }
+
+    /// Sets new frame animation, None means that whole texture is shown.
+    pub fn set_animation(&mut self, animation: Option<SpriteAnimation>) {
+        self.animation = animation;
+    }
+
+    /// Returns shared reference to frame animation, if any.
+    pub fn animation(&self) -> Option<&SpriteAnimation> {
+        self.animation.as_ref()
+    }
+
+    /// Returns mutable reference to frame animation, if any. Can be used to play or stop
+    /// animation.
+    pub fn animation_mut(&mut self) -> Option<&mut SpriteAnimation> {
+        self.animation.as_mut()
+    }
+
+    /// Sets region of texture which is shown when sprite has no animation. It allows sprites
+    /// to use textures packed into atlas, see `atlas` module docs.
+    pub fn set_region(&mut self, region: AtlasRegion) {
+        self.region = region;
+    }
+
+    /// Returns region of texture which is shown when sprite has no animation.
+    pub fn region(&self) -> AtlasRegion {
+        self.region
+    }
+
+    /// Returns region of texture which should be shown at the moment.
+    pub fn current_region(&self) -> AtlasRegion {
+        self.animation
+            .as_ref()
+            .map(|animation| animation.current_region())
+            .unwrap_or(self.region)
+    }
+
+    pub(in crate) fn update(&mut self, dt: f32) {
+        if let Some(animation) = self.animation.as_mut() {
+            animation.update(dt);
+        }
+    }
 }
 
 impl Visit for Sprite {
@@ -105,6 +411,9 @@ impl Visit for Sprite {
         self.size.visit("Size", visitor)?;
         self.rotation.visit("Rotation", visitor)?;
         self.base.visit("Base", visitor)?;
+        let _ = self.animation.visit("Animation", visitor);
+        let _ = self.sort_layer.visit("SortLayer", visitor);
+        let _ = self.region.visit("Region", visitor);
 
         visitor.leave_region()
     }
@@ -118,10 +427,14 @@ pub struct SpriteBuilder {
     color: Color,
     size: f32,
     rotation: f32,
+    animation: Option<SpriteAnimation>,
+    region: AtlasRegion,
+    sort_layer: i32,
 }
 
 impl SpriteBuilder {
-    /// Creates new builder with default state (white opaque color, 0.2 size, zero rotation).
+    /// Creates new builder with default state (white opaque color, 0.2 size, zero rotation,
+    /// zero sort layer).
     pub fn new(base_builder: BaseBuilder) -> Self {
         Self {
             base_builder,
@@ -129,6 +442,9 @@ impl SpriteBuilder {
             color: Color::WHITE,
             size: 0.2,
             rotation: 0.0,
+            animation: None,
+            region: Default::default(),
+            sort_layer: 0,
         }
     }
 
@@ -162,6 +478,24 @@ impl SpriteBuilder {
         self
     }
 
+    /// Sets desired frame animation.
+    pub fn with_animation(mut self, animation: SpriteAnimation) -> Self {
+        self.animation = Some(animation);
+        self
+    }
+
+    /// Sets desired region of texture, see `Sprite::set_region`.
+    pub fn with_region(mut self, region: AtlasRegion) -> Self {
+        self.region = region;
+        self
+    }
+
+    /// Sets desired sort layer.
+    pub fn with_sort_layer(mut self, sort_layer: i32) -> Self {
+        self.sort_layer = sort_layer;
+        self
+    }
+
     /// Creates new sprite instance.
     pub fn build(self) -> Sprite {
         Sprite {
@@ -170,6 +504,9 @@ impl SpriteBuilder {
             color: self.color,
             size: self.size,
             rotation: self.rotation,
+            animation: self.animation,
+            region: self.region,
+            sort_layer: self.sort_layer,
         }
     }
 
@@ -178,3 +515,33 @@ impl SpriteBuilder {
         Node::Sprite(self.build())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::scene::sprite::{AtlasRegion, SpriteAnimation};
+
+    #[test]
+    fn sprite_animation_test() {
+        let mut animation = SpriteAnimation::new(AtlasRegion::grid(2, 2), 4.0);
+        assert_eq!(animation.duration(), 1.0);
+
+        animation.update(0.6);
+        assert_eq!(animation.current_frame(), 2);
+        assert_eq!(animation.current_region(), AtlasRegion::grid(2, 2)[2]);
+
+        // Looped animation starts over.
+        animation.update(0.5);
+        assert_eq!(animation.current_frame(), 0);
+
+        // Not looped animation stops at last frame.
+        animation.set_looped(false);
+        animation.update(2.0);
+        assert_eq!(animation.current_frame(), 3);
+        assert!(animation.is_finished() && !animation.is_playing());
+
+        animation.play();
+        assert_eq!(animation.current_frame(), 0);
+        animation.stop();
+        assert!(!animation.is_playing());
+    }
+}
@@ -0,0 +1,763 @@
+//! Contains all structures and methods to create and manage terrains.
+//!
+//! Terrain is a height map based surface which is suitable for large outdoor levels. Height
+//! map is a regular grid of height samples stretched over rectangle in XZ plane of local
+//! coordinate system of terrain node. Heights can be modified at runtime using brushes or
+//! sample-by-sample, which makes terrain suitable for in-game editors or deformable worlds.
+//!
+//! # Chunks and LOD
+//!
+//! Terrain is split into square chunks, each chunk is culled and rendered separately. Each
+//! chunk has multiple levels of detail (LOD): level N uses every 2^N-th height sample, so
+//! distant chunks are made of fewer triangles. Chunk switches to next LOD every time when
+//! distance to camera doubles, starting from `lod_distance`. Cracks between chunks with
+//! different LOD are hidden by skirts - vertical strips along chunk borders.
+//!
+//! # Texturing
+//!
+//! Terrain uses up to four texture layers which are blended using splat map. Splat map has
+//! the same resolution as height map and stores weight of each layer for each sample. Use
+//! `paint_layer` or `set_layer_weight` to modify it. Layer textures are tiled over terrain,
+//! one tile covers `tile_size` meters.
+//!
+//! # Limitations
+//!
+//! Terrains do not cast shadows and are not included in lightmaps.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::scene::{
+//!     base::BaseBuilder,
+//!     node::Node,
+//!     terrain::{TerrainBuilder, TerrainLayer},
+//!     Scene,
+//! };
+//! use rg3d::core::math::vec2::Vec2;
+//!
+//! fn create_terrain(scene: &mut Scene) {
+//!     let mut terrain = TerrainBuilder::new(BaseBuilder::new())
+//!         .with_size(256.0, 256.0)
+//!         .with_resolution(257, 257)
+//!         .with_texture_layers(vec![TerrainLayer::default()])
+//!         .build();
+//!
+//!     // Make a hill in the middle.
+//!     terrain.raise(Vec2::new(128.0, 128.0), 40.0, 15.0);
+//!
+//!     scene.graph.add_node(Node::Terrain(terrain));
+//! }
+//! ```
+
+use crate::{
+    core::{
+        math::{
+            aabb::AxisAlignedBoundingBox, vec2::Vec2, vec3::Vec3, vec4::Vec4, TriangleDefinition,
+        },
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    renderer::surface::{SurfaceSharedData, Vertex},
+    resource::texture::Texture,
+    scene::{
+        base::{Base, BaseBuilder},
+        node::Node,
+    },
+};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// Every terrain and every modification gets unique number, this allows renderer to
+/// detect changes without any risk of mixing data of different terrains.
+fn next_unique_id() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(1);
+    COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Texture layer of a terrain.
+#[derive(Clone, Debug)]
+pub struct TerrainLayer {
+    /// Diffuse texture of layer.
+    pub diffuse_texture: Option<Arc<Mutex<Texture>>>,
+    /// Normal texture of layer.
+    pub normal_texture: Option<Arc<Mutex<Texture>>>,
+    /// Size of one texture tile in meters.
+    pub tile_size: f32,
+}
+
+impl Default for TerrainLayer {
+    fn default() -> Self {
+        Self {
+            diffuse_texture: None,
+            normal_texture: None,
+            tile_size: 4.0,
+        }
+    }
+}
+
+impl Visit for TerrainLayer {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.diffuse_texture.visit("DiffuseTexture", visitor)?;
+        self.normal_texture.visit("NormalTexture", visitor)?;
+        self.tile_size.visit("TileSize", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub(in crate) struct Chunk {
+    /// First sample of chunk along X and Z axes.
+    pub(in crate) origin: (usize, usize),
+    /// Amount of cells along X and Z axes.
+    pub(in crate) size: (usize, usize),
+    pub(in crate) min_height: f32,
+    pub(in crate) max_height: f32,
+    /// Changes each time when any height sample that affects chunk is modified.
+    pub(in crate) version: u64,
+}
+
+/// See module docs.
+#[derive(Debug)]
+pub struct Terrain {
+    base: Base,
+    width: f32,
+    length: f32,
+    resolution: (usize, usize),
+    heights: Vec<f32>,
+    chunk_size: usize,
+    lod_distance: f32,
+    texture_layers: Vec<TerrainLayer>,
+    // Four weights per sample, one for each layer.
+    splat_map: Vec<u8>,
+    splat_map_version: u64,
+    chunks: Vec<Chunk>,
+    id: u64,
+}
+
+impl Clone for Terrain {
+    fn clone(&self) -> Self {
+        Self {
+            base: self.base.clone(),
+            width: self.width,
+            length: self.length,
+            resolution: self.resolution,
+            heights: self.heights.clone(),
+            chunk_size: self.chunk_size,
+            lod_distance: self.lod_distance,
+            texture_layers: self.texture_layers.clone(),
+            splat_map: self.splat_map.clone(),
+            splat_map_version: self.splat_map_version,
+            chunks: self.chunks.clone(),
+            // Copy must not share GPU data with original terrain.
+            id: next_unique_id(),
+        }
+    }
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        TerrainBuilder::new(BaseBuilder::new()).build()
+    }
+}
+
+impl Deref for Terrain {
+    type Target = Base;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for Terrain {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Visit for Terrain {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Common", visitor)?;
+        self.width.visit("Width", visitor)?;
+        self.length.visit("Length", visitor)?;
+        let mut width_points = self.resolution.0 as u32;
+        width_points.visit("WidthPoints", visitor)?;
+        let mut length_points = self.resolution.1 as u32;
+        length_points.visit("LengthPoints", visitor)?;
+        self.heights.visit("Heights", visitor)?;
+        let mut chunk_size = self.chunk_size as u32;
+        chunk_size.visit("ChunkSize", visitor)?;
+        self.lod_distance.visit("LodDistance", visitor)?;
+        self.texture_layers.visit("TextureLayers", visitor)?;
+        self.splat_map.visit("SplatMap", visitor)?;
+
+        if visitor.is_reading() {
+            self.resolution = (width_points as usize, length_points as usize);
+            self.chunk_size = (chunk_size as usize).max(1);
+            // Resolution is stored as two u32, its product can overflow usize on 32-bit targets.
+            let sample_count = self.resolution.0.checked_mul(self.resolution.1);
+            if self.resolution.0 < 2
+                || self.resolution.1 < 2
+                || sample_count != Some(self.heights.len())
+                || sample_count.and_then(|count| count.checked_mul(4))
+                    != Some(self.splat_map.len())
+            {
+                return Err("Terrain height map or splat map is corrupted!"
+                    .to_owned()
+                    .into());
+            }
+            self.splat_map_version = next_unique_id();
+            self.rebuild_chunks();
+        }
+
+        visitor.leave_region()
+    }
+}
+
+fn smooth_falloff(distance: f32, radius: f32) -> f32 {
+    let k = (1.0 - distance / radius).max(0.0).min(1.0);
+    k * k * (3.0 - 2.0 * k)
+}
+
+impl Terrain {
+    /// Maximum amount of texture layers.
+    pub const MAX_LAYERS: usize = 4;
+
+    /// Returns size of terrain along local X axis.
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Returns size of terrain along local Z axis.
+    pub fn length(&self) -> f32 {
+        self.length
+    }
+
+    /// Returns amount of height samples along local X and Z axes.
+    pub fn resolution(&self) -> (usize, usize) {
+        self.resolution
+    }
+
+    /// Returns distance between adjacent samples along local X and Z axes.
+    pub fn cell_size(&self) -> Vec2 {
+        Vec2::new(
+            self.width / (self.resolution.0 - 1) as f32,
+            self.length / (self.resolution.1 - 1) as f32,
+        )
+    }
+
+    /// Returns height samples, row by row along X axis.
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    /// Returns height of sample at given indices. Panics if indices are out of bounds.
+    pub fn height(&self, x: usize, z: usize) -> f32 {
+        self.heights[z * self.resolution.0 + x]
+    }
+
+    /// Sets new height for sample at given indices. Panics if indices are out of bounds.
+    pub fn set_height(&mut self, x: usize, z: usize, height: f32) {
+        self.heights[z * self.resolution.0 + x] = height;
+        self.invalidate_region(x, z, x, z);
+    }
+
+    /// Returns interpolated height at given point in local coordinates of terrain. Points
+    /// outside of terrain will get height of nearest border.
+    pub fn height_at(&self, position: Vec2) -> f32 {
+        self.interpolate(position, |x, z| self.height(x, z))
+    }
+
+    // Bilinear interpolation of values defined at each sample.
+    fn interpolate<F>(&self, position: Vec2, sample: F) -> f32
+    where
+        F: Fn(usize, usize) -> f32,
+    {
+        let cell = self.cell_size();
+        let fx = (position.x / cell.x).max(0.0).min((self.resolution.0 - 1) as f32);
+        let fz = (position.y / cell.y).max(0.0).min((self.resolution.1 - 1) as f32);
+        let x0 = fx.floor() as usize;
+        let z0 = fz.floor() as usize;
+        let x1 = (x0 + 1).min(self.resolution.0 - 1);
+        let z1 = (z0 + 1).min(self.resolution.1 - 1);
+        let tx = fx - x0 as f32;
+        let tz = fz - z0 as f32;
+        let a = sample(x0, z0) + (sample(x1, z0) - sample(x0, z0)) * tx;
+        let b = sample(x0, z1) + (sample(x1, z1) - sample(x0, z1)) * tx;
+        a + (b - a) * tz
+    }
+
+    /// Returns normal at given sample, calculated from neighbour samples.
+    pub fn normal(&self, x: usize, z: usize) -> Vec3 {
+        let cell = self.cell_size();
+        let left = self.height(x.saturating_sub(1), z);
+        let right = self.height((x + 1).min(self.resolution.0 - 1), z);
+        let back = self.height(x, z.saturating_sub(1));
+        let front = self.height(x, (z + 1).min(self.resolution.1 - 1));
+        Vec3::new((left - right) / (2.0 * cell.x), 1.0, (back - front) / (2.0 * cell.y))
+            .normalized()
+            .unwrap_or(Vec3::UP)
+    }
+
+    /// Calls given function for each sample inside a circle with given center (in local
+    /// coordinates) and radius. Function receives old height and influence of brush at the
+    /// sample (1.0 at center, smoothly going to 0.0 at radius) and must return new height.
+    pub fn modify_heights<F>(&mut self, center: Vec2, radius: f32, mut func: F)
+    where
+        F: FnMut(f32, f32) -> f32,
+    {
+        if radius <= 0.0 {
+            return;
+        }
+        let ((min_x, min_z), (max_x, max_z)) = self.samples_in_circle(center, radius);
+        let cell = self.cell_size();
+        for z in min_z..=max_z {
+            for x in min_x..=max_x {
+                let position = Vec2::new(x as f32 * cell.x, z as f32 * cell.y);
+                let distance = (position - center).len();
+                if distance <= radius {
+                    let height = &mut self.heights[z * self.resolution.0 + x];
+                    *height = func(*height, smooth_falloff(distance, radius));
+                }
+            }
+        }
+        self.invalidate_region(min_x, min_z, max_x, max_z);
+    }
+
+    /// Raises (or lowers, if amount is negative) terrain in given circle. Amount is applied
+    /// fully at center of circle and smoothly fades towards its border.
+    pub fn raise(&mut self, center: Vec2, radius: f32, amount: f32) {
+        self.modify_heights(center, radius, |height, k| height + amount * k)
+    }
+
+    /// Moves heights in given circle towards given height, `strength` in [0; 1] range
+    /// defines how much heights will be changed at center of circle.
+    pub fn flatten(&mut self, center: Vec2, radius: f32, height: f32, strength: f32) {
+        let strength = strength.max(0.0).min(1.0);
+        self.modify_heights(center, radius, |old, k| old + (height - old) * k * strength)
+    }
+
+    /// Returns shared reference to texture layers.
+    pub fn texture_layers(&self) -> &[TerrainLayer] {
+        &self.texture_layers
+    }
+
+    /// Returns mutable reference to texture layers.
+    pub fn texture_layers_mut(&mut self) -> &mut [TerrainLayer] {
+        &mut self.texture_layers
+    }
+
+    /// Adds new layer. Returns layer back if there is already maximum amount of layers.
+    pub fn add_texture_layer(&mut self, layer: TerrainLayer) -> Result<usize, TerrainLayer> {
+        if self.texture_layers.len() < Self::MAX_LAYERS {
+            self.texture_layers.push(layer);
+            Ok(self.texture_layers.len() - 1)
+        } else {
+            Err(layer)
+        }
+    }
+
+    /// Returns weight of layer at given sample. Panics if indices are out of bounds.
+    pub fn layer_weight(&self, x: usize, z: usize, layer: usize) -> u8 {
+        self.splat_map[(z * self.resolution.0 + x) * 4 + layer]
+    }
+
+    /// Returns weight of layer in [0; 1] range at given point in local coordinates of
+    /// terrain, interpolated between samples. Weights are normalized the same way as in
+    /// shader, so weights of all layers sum up to one. Panics if layer index is equal or
+    /// greater than `MAX_LAYERS`.
+    pub fn layer_weight_at(&self, position: Vec2, layer: usize) -> f32 {
+        assert!(layer < Self::MAX_LAYERS);
+        self.interpolate(position, |x, z| {
+            let offset = (z * self.resolution.0 + x) * 4;
+            let weights = &self.splat_map[offset..offset + 4];
+            let sum = weights.iter().map(|&weight| weight as f32).sum::<f32>();
+            if sum > 0.0 {
+                weights[layer] as f32 / sum
+            } else {
+                0.0
+            }
+        })
+    }
+
+    /// Sets weight of layer at given sample. Weights of all layers are normalized in shader,
+    /// so there is no need to keep their sum equal 255. Panics if indices are out of
+    /// bounds or layer index is equal or greater than `MAX_LAYERS`.
+    pub fn set_layer_weight(&mut self, x: usize, z: usize, layer: usize, weight: u8) {
+        assert!(layer < Self::MAX_LAYERS);
+        self.splat_map[(z * self.resolution.0 + x) * 4 + layer] = weight;
+        self.splat_map_version = next_unique_id();
+    }
+
+    /// Paints layer in given circle, other layers are faded out proportionally. `strength`
+    /// in [0; 1] range defines how much weights will be changed at center of circle.
+    pub fn paint_layer(&mut self, center: Vec2, radius: f32, layer: usize, strength: f32) {
+        assert!(layer < Self::MAX_LAYERS);
+        if radius <= 0.0 {
+            return;
+        }
+        let strength = strength.max(0.0).min(1.0);
+        let ((min_x, min_z), (max_x, max_z)) = self.samples_in_circle(center, radius);
+        let cell = self.cell_size();
+        for z in min_z..=max_z {
+            for x in min_x..=max_x {
+                let position = Vec2::new(x as f32 * cell.x, z as f32 * cell.y);
+                let distance = (position - center).len();
+                if distance <= radius {
+                    let k = smooth_falloff(distance, radius) * strength;
+                    let offset = (z * self.resolution.0 + x) * 4;
+                    let weights = &mut self.splat_map[offset..offset + 4];
+                    for (i, weight) in weights.iter_mut().enumerate() {
+                        let target = if i == layer { 255.0 } else { 0.0 };
+                        let current = *weight as f32;
+                        *weight = (current + (target - current) * k).round() as u8;
+                    }
+                }
+            }
+        }
+        self.splat_map_version = next_unique_id();
+    }
+
+    /// Returns splat map, four weights for each sample.
+    pub fn splat_map(&self) -> &[u8] {
+        &self.splat_map
+    }
+
+    /// Returns size of chunk in cells.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Sets distance from camera at which chunks will start to use lower level of detail.
+    pub fn set_lod_distance(&mut self, distance: f32) {
+        self.lod_distance = distance.max(0.0);
+    }
+
+    /// Returns distance from camera at which chunks start to use lower level of detail.
+    pub fn lod_distance(&self) -> f32 {
+        self.lod_distance
+    }
+
+    /// Returns bounding box of terrain in local coordinates.
+    pub fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        let mut bounding_box = AxisAlignedBoundingBox::default();
+        for chunk in self.chunks.iter() {
+            let chunk_bounds = self.chunk_bounding_box(chunk);
+            bounding_box.add_point(chunk_bounds.min);
+            bounding_box.add_point(chunk_bounds.max);
+        }
+        bounding_box
+    }
+
+    pub(in crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(in crate) fn splat_map_version(&self) -> u64 {
+        self.splat_map_version
+    }
+
+    pub(in crate) fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    pub(in crate) fn chunk_bounding_box(&self, chunk: &Chunk) -> AxisAlignedBoundingBox {
+        let cell = self.cell_size();
+        let mut bounding_box = AxisAlignedBoundingBox::default();
+        bounding_box.add_point(Vec3::new(
+            chunk.origin.0 as f32 * cell.x,
+            chunk.min_height - self.skirt_depth(chunk),
+            chunk.origin.1 as f32 * cell.y,
+        ));
+        bounding_box.add_point(Vec3::new(
+            (chunk.origin.0 + chunk.size.0) as f32 * cell.x,
+            chunk.max_height,
+            (chunk.origin.1 + chunk.size.1) as f32 * cell.y,
+        ));
+        bounding_box
+    }
+
+    /// Returns level of detail for chunk at given distance from camera.
+    pub(in crate) fn lod_at_distance(&self, distance: f32) -> usize {
+        let mut lod = 0;
+        let mut lod_distance = self.lod_distance;
+        while lod_distance > 0.0 && distance > lod_distance && (2 << lod) <= self.chunk_size {
+            lod += 1;
+            lod_distance *= 2.0;
+        }
+        lod
+    }
+
+    fn skirt_depth(&self, chunk: &Chunk) -> f32 {
+        let cell = self.cell_size();
+        (chunk.max_height - chunk.min_height) + cell.x.max(cell.y)
+    }
+
+    /// Generates geometry of chunk for given level of detail. Geometry is made in local
+    /// coordinates of terrain.
+    pub(in crate) fn build_chunk_geometry(&self, chunk: &Chunk, lod: usize) -> SurfaceSharedData {
+        let step = 1 << lod;
+        let cell = self.cell_size();
+
+        // Sample indices used by LOD, last sample is always included so chunks with
+        // different LOD meet at same border.
+        let samples = |origin: usize, size: usize| {
+            let mut samples = (origin..origin + size).step_by(step).collect::<Vec<_>>();
+            samples.push(origin + size);
+            samples
+        };
+        let xs = samples(chunk.origin.0, chunk.size.0);
+        let zs = samples(chunk.origin.1, chunk.size.1);
+
+        let make_vertex = |x: usize, z: usize, depth: f32| Vertex {
+            position: Vec3::new(
+                x as f32 * cell.x,
+                self.height(x, z) - depth,
+                z as f32 * cell.y,
+            ),
+            // Layers are tiled in shader, so texture coordinates are in meters.
+            tex_coord: Vec2::new(x as f32 * cell.x, z as f32 * cell.y),
+            // Points to center of splat map texel.
+            second_tex_coord: Vec2::new(
+                (x as f32 + 0.5) / self.resolution.0 as f32,
+                (z as f32 + 0.5) / self.resolution.1 as f32,
+            ),
+            normal: self.normal(x, z),
+            tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
+            bone_weights: [0.0; 4],
+            bone_indices: [0; 4],
+        };
+
+        let mut vertices = Vec::with_capacity(xs.len() * zs.len());
+        for &z in zs.iter() {
+            for &x in xs.iter() {
+                vertices.push(make_vertex(x, z, 0.0));
+            }
+        }
+
+        let row = xs.len() as u32;
+        let mut triangles = Vec::new();
+        for j in 0..zs.len() as u32 - 1 {
+            for i in 0..row - 1 {
+                let a = j * row + i;
+                let b = a + 1;
+                let c = a + row;
+                let d = c + 1;
+                triangles.push(TriangleDefinition([a, c, b]));
+                triangles.push(TriangleDefinition([b, c, d]));
+            }
+        }
+
+        // Skirts go along each border of chunk, each border is a list of surface vertices
+        // ordered so skirt faces outside.
+        let depth = self.skirt_depth(chunk);
+        let last_row = (zs.len() as u32 - 1) * row;
+        let borders = [
+            (0..row).collect::<Vec<_>>(),
+            (0..zs.len() as u32).map(|j| j * row + row - 1).collect(),
+            (0..row).rev().map(|i| last_row + i).collect(),
+            (0..zs.len() as u32).rev().map(|j| j * row).collect(),
+        ];
+        for border in borders.iter() {
+            let first_skirt_vertex = vertices.len() as u32;
+            for &index in border.iter() {
+                let mut vertex = vertices[index as usize];
+                vertex.position.y -= depth;
+                vertices.push(vertex);
+            }
+            for (k, pair) in border.windows(2).enumerate() {
+                let top_a = pair[0];
+                let top_b = pair[1];
+                let bottom_a = first_skirt_vertex + k as u32;
+                let bottom_b = bottom_a + 1;
+                triangles.push(TriangleDefinition([top_a, top_b, bottom_a]));
+                triangles.push(TriangleDefinition([top_b, bottom_b, bottom_a]));
+            }
+        }
+
+        SurfaceSharedData::new(vertices, triangles, true)
+    }
+
+    fn samples_in_circle(&self, center: Vec2, radius: f32) -> ((usize, usize), (usize, usize)) {
+        let cell = self.cell_size();
+        let clamp = |v: f32, max: usize| v.max(0.0).min(max as f32) as usize;
+        let max_x = self.resolution.0 - 1;
+        let max_z = self.resolution.1 - 1;
+        (
+            (
+                clamp(((center.x - radius) / cell.x).floor(), max_x),
+                clamp(((center.y - radius) / cell.y).floor(), max_z),
+            ),
+            (
+                clamp(((center.x + radius) / cell.x).ceil(), max_x),
+                clamp(((center.y + radius) / cell.y).ceil(), max_z),
+            ),
+        )
+    }
+
+    fn rebuild_chunks(&mut self) {
+        self.chunks.clear();
+        let cells_x = self.resolution.0 - 1;
+        let cells_z = self.resolution.1 - 1;
+        let mut z = 0;
+        while z < cells_z {
+            let mut x = 0;
+            while x < cells_x {
+                self.chunks.push(Chunk {
+                    origin: (x, z),
+                    size: (self.chunk_size.min(cells_x - x), self.chunk_size.min(cells_z - z)),
+                    min_height: 0.0,
+                    max_height: 0.0,
+                    version: 0,
+                });
+                x += self.chunk_size;
+            }
+            z += self.chunk_size;
+        }
+        self.invalidate_region(0, 0, cells_x, cells_z);
+    }
+
+    /// Updates chunks which are using given samples. Normals depend on adjacent samples,
+    /// so region is expanded by one sample.
+    fn invalidate_region(&mut self, min_x: usize, min_z: usize, max_x: usize, max_z: usize) {
+        let min_x = min_x.saturating_sub(1);
+        let min_z = min_z.saturating_sub(1);
+        let max_x = max_x + 1;
+        let max_z = max_z + 1;
+        for i in 0..self.chunks.len() {
+            let chunk = self.chunks[i];
+            if chunk.origin.0 > max_x
+                || chunk.origin.1 > max_z
+                || chunk.origin.0 + chunk.size.0 < min_x
+                || chunk.origin.1 + chunk.size.1 < min_z
+            {
+                continue;
+            }
+            let mut min_height = std::f32::MAX;
+            let mut max_height = -std::f32::MAX;
+            for z in chunk.origin.1..=chunk.origin.1 + chunk.size.1 {
+                for x in chunk.origin.0..=chunk.origin.0 + chunk.size.0 {
+                    let height = self.height(x, z);
+                    min_height = min_height.min(height);
+                    max_height = max_height.max(height);
+                }
+            }
+            let chunk = &mut self.chunks[i];
+            chunk.min_height = min_height;
+            chunk.max_height = max_height;
+            chunk.version = next_unique_id();
+        }
+    }
+}
+
+/// Terrain builder allows you to create terrains in declarative manner.
+pub struct TerrainBuilder {
+    base_builder: BaseBuilder,
+    width: f32,
+    length: f32,
+    resolution: (usize, usize),
+    heights: Option<Vec<f32>>,
+    chunk_size: usize,
+    lod_distance: f32,
+    texture_layers: Vec<TerrainLayer>,
+}
+
+impl TerrainBuilder {
+    /// Creates new builder instance. By default it creates flat 64x64 meters terrain
+    /// with one sample per meter and without layers.
+    pub fn new(base_builder: BaseBuilder) -> Self {
+        Self {
+            base_builder,
+            width: 64.0,
+            length: 64.0,
+            resolution: (65, 65),
+            heights: None,
+            chunk_size: 32,
+            lod_distance: 64.0,
+            texture_layers: Default::default(),
+        }
+    }
+
+    /// Sets desired size of terrain along local X and Z axes.
+    pub fn with_size(mut self, width: f32, length: f32) -> Self {
+        self.width = width.max(std::f32::EPSILON);
+        self.length = length.max(std::f32::EPSILON);
+        self
+    }
+
+    /// Sets desired amount of height samples along local X and Z axes. There must be at
+    /// least two samples along each axis.
+    pub fn with_resolution(mut self, width_points: usize, length_points: usize) -> Self {
+        self.resolution = (width_points.max(2), length_points.max(2));
+        self
+    }
+
+    /// Sets initial heights. Heights must be stored row by row along X axis, amount of
+    /// heights must match resolution, otherwise terrain will be flat.
+    pub fn with_heights(mut self, heights: Vec<f32>) -> Self {
+        self.heights = Some(heights);
+        self
+    }
+
+    /// Sets desired size of chunk in cells.
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Sets distance from camera at which chunks will start to use lower level of detail.
+    pub fn with_lod_distance(mut self, distance: f32) -> Self {
+        self.lod_distance = distance.max(0.0);
+        self
+    }
+
+    /// Sets texture layers, only first `Terrain::MAX_LAYERS` will be used. First layer
+    /// will cover whole terrain initially.
+    pub fn with_texture_layers(mut self, mut layers: Vec<TerrainLayer>) -> Self {
+        layers.truncate(Terrain::MAX_LAYERS);
+        self.texture_layers = layers;
+        self
+    }
+
+    /// Creates new terrain.
+    pub fn build(self) -> Terrain {
+        let sample_count = self.resolution.0 * self.resolution.1;
+        let heights = match self.heights {
+            Some(heights) if heights.len() == sample_count => heights,
+            _ => vec![0.0; sample_count],
+        };
+        let mut splat_map = vec![0; sample_count * 4];
+        for weights in splat_map.chunks_mut(4) {
+            weights[0] = 255;
+        }
+        let mut terrain = Terrain {
+            base: self.base_builder.build(),
+            width: self.width,
+            length: self.length,
+            resolution: self.resolution,
+            heights,
+            chunk_size: self.chunk_size,
+            lod_distance: self.lod_distance,
+            texture_layers: self.texture_layers,
+            splat_map,
+            splat_map_version: next_unique_id(),
+            chunks: Default::default(),
+            id: next_unique_id(),
+        };
+        terrain.rebuild_chunks();
+        terrain
+    }
+
+    /// Creates new terrain instance and wraps it into node.
+    pub fn build_node(self) -> Node {
+        Node::Terrain(self.build())
+    }
+}
@@ -51,7 +51,7 @@ use crate::{
         math::{mat4::Mat4, quat::Quat, vec3::Vec3},
         visitor::{Visit, VisitResult, Visitor},
     },
-    utils::log::Log,
+    utils::log::{Log, LogCategory},
 };
 use std::cell::Cell;
 
@@ -269,9 +269,9 @@ impl Transform {
         let post_rotation = Mat4::from_quat(self.post_rotation)
             .inverse()
             .unwrap_or_else(|_| {
-                Log::writeln(
-                    "Unable to inverse post rotation matrix! Fallback to identity matrix."
-                        .to_owned(),
+                Log::warn(
+                    LogCategory::Scene,
+                    "Unable to inverse post rotation matrix! Fallback to identity matrix.",
                 );
                 Mat4::IDENTITY
             });
@@ -281,16 +281,18 @@ impl Transform {
         let rotation_offset = Mat4::translate(self.rotation_offset);
         let rotation_pivot = Mat4::translate(self.rotation_pivot);
         let rotation_pivot_inv = rotation_pivot.inverse().unwrap_or_else(|_| {
-            Log::writeln(
-                "Unable to inverse rotation pivot matrix! Fallback to identity matrix.".to_owned(),
+            Log::warn(
+                LogCategory::Scene,
+                "Unable to inverse rotation pivot matrix! Fallback to identity matrix.",
             );
             Mat4::IDENTITY
         });
         let scale_offset = Mat4::translate(self.scaling_offset);
         let scale_pivot = Mat4::translate(self.scaling_pivot);
         let scale_pivot_inv = scale_pivot.inverse().unwrap_or_else(|_| {
-            Log::writeln(
-                "Unable to inverse scale pivot matrix! Fallback to identity matrix.".to_owned(),
+            Log::warn(
+                LogCategory::Scene,
+                "Unable to inverse scale pivot matrix! Fallback to identity matrix.",
             );
             Mat4::IDENTITY
         });
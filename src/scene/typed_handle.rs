@@ -0,0 +1,183 @@
+//! Contains typed handles of scene nodes.
+//!
+//! `Handle<Node>` says nothing about actual type of node it points to, so every access to
+//! type-specific data requires matching on `Node` enum. Typed handle remembers type of node
+//! and can be created only after a check, so access through it cannot fail because of wrong
+//! node type.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::scene::{camera::Camera, graph::Graph, typed_handle::TypedHandle};
+//!
+//! fn setup_camera(graph: &mut Graph, camera: TypedHandle<Camera>) {
+//!     graph[camera].set_z_far(500.0);
+//! }
+//!
+//! fn find_camera(graph: &Graph) -> Option<TypedHandle<Camera>> {
+//!     graph.typed_handle(graph.find_by_name_from_root("Camera"))
+//! }
+//! ```
+//!
+//! # Notes
+//!
+//! Typed handle does not prevent node from being removed or replaced, if a node is replaced by
+//! a node of other type in the same slot of graph, accessor methods of graph will return None
+//! and index operator will panic.
+
+use crate::{
+    core::{
+        pool::Handle,
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    scene::{
+        base::Base, camera::Camera, light::Light, light_probe::LightProbeGrid, mesh::Mesh,
+        node::Node, particle_system::ParticleSystem, point_cloud::PointCloud, scatter::Scatter,
+        spline::Spline, sprite::Sprite, terrain::Terrain, water::Water,
+    },
+};
+use std::{
+    fmt::{Debug, Formatter},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+/// Node variant is a type which can be extracted from `Node`, it is implemented for every
+/// built-in node type and for `Base` which is part of any node.
+pub trait NodeVariant: Sized {
+    /// Returns reference to data of node if it has this type.
+    fn from_node(node: &Node) -> Option<&Self>;
+
+    /// Returns mutable reference to data of node if it has this type.
+    fn from_node_mut(node: &mut Node) -> Option<&mut Self>;
+}
+
+impl NodeVariant for Base {
+    fn from_node(node: &Node) -> Option<&Self> {
+        Some(node)
+    }
+
+    fn from_node_mut(node: &mut Node) -> Option<&mut Self> {
+        Some(node)
+    }
+}
+
+macro_rules! impl_node_variant {
+    ($($variant:ident),* $(,)?) => {
+        $(
+            impl NodeVariant for $variant {
+                fn from_node(node: &Node) -> Option<&Self> {
+                    match node {
+                        Node::$variant(v) => Some(v),
+                        _ => None,
+                    }
+                }
+
+                fn from_node_mut(node: &mut Node) -> Option<&mut Self> {
+                    match node {
+                        Node::$variant(v) => Some(v),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_node_variant!(
+    Camera,
+    Light,
+    Mesh,
+    Sprite,
+    ParticleSystem,
+    Terrain,
+    Spline,
+    PointCloud,
+    Water,
+    Scatter,
+    LightProbeGrid,
+);
+
+/// Handle of a node of specific type. See module docs.
+pub struct TypedHandle<T> {
+    handle: Handle<Node>,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedHandle<T> {
+    /// Handle which points to nothing.
+    pub const NONE: Self = Self {
+        handle: Handle::NONE,
+        phantom: PhantomData,
+    };
+
+    /// Creates typed handle without checking type of node. Use `Graph::typed_handle` to
+    /// create checked handles.
+    pub fn new_unchecked(handle: Handle<Node>) -> Self {
+        Self {
+            handle,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns untyped handle of node.
+    pub fn handle(&self) -> Handle<Node> {
+        self.handle
+    }
+
+    /// Returns true if handle points to nothing.
+    pub fn is_none(&self) -> bool {
+        self.handle.is_none()
+    }
+
+    /// Returns true if handle points to something.
+    pub fn is_some(&self) -> bool {
+        self.handle.is_some()
+    }
+}
+
+impl<T> Clone for TypedHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TypedHandle<T> {}
+
+impl<T> PartialEq for TypedHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.handle == other.handle
+    }
+}
+
+impl<T> Eq for TypedHandle<T> {}
+
+impl<T> Hash for TypedHandle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.handle.hash(state)
+    }
+}
+
+impl<T> Debug for TypedHandle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TypedHandle({:?})", self.handle)
+    }
+}
+
+impl<T> Default for TypedHandle<T> {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+impl<T> From<TypedHandle<T>> for Handle<Node> {
+    fn from(typed: TypedHandle<T>) -> Self {
+        typed.handle
+    }
+}
+
+impl<T> Visit for TypedHandle<T> {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        self.handle.visit(name, visitor)
+    }
+}
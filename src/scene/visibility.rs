@@ -0,0 +1,136 @@
+//! Contains visibility queries against view frustum of a camera.
+//!
+//! Gameplay systems often need to know what a camera (or an eye of a character) can see:
+//! AI vision, culling of sounds and effects, activation of objects near player and so on.
+//! Functions of this module use the same bounds and the same frustum tests as renderer, so
+//! results match what is actually drawn (except occlusion, see below).
+//!
+//! Nodes with known bounds are tested precisely: meshes by their bounding boxes (and
+//! positions of bones for skinned meshes), terrains by bounding boxes of their chunks,
+//! lights by spheres of influence, sprites, water and scatters by their bounding spheres.
+//! Every other node is considered visible if its position is inside the frustum.
+//!
+//! # Limitations
+//!
+//! Queries do not take occlusion into account - object behind a wall is visible if it is in
+//! the frustum. Portal culling is applied by scene, not by graph, so it is not used here
+//! either; combine query results with ray casts (see `picking` module) for line-of-sight
+//! checks.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::scene::{camera::Camera, Scene};
+//!
+//! fn count_visible_enemies(scene: &Scene, eye: &Camera) -> usize {
+//!     scene
+//!         .graph
+//!         .query_visible(eye)
+//!         .filter(|&handle| scene.graph[handle].has_tag("Enemy"))
+//!         .count()
+//! }
+//! ```
+
+use crate::{
+    core::{math::frustum::Frustum, pool::Handle},
+    scene::{camera::Camera, graph::Graph, node::Node},
+};
+
+/// Returns true if bounds of given node intersect given frustum. Visibility flags and
+/// render layers are *not* checked, see `Graph::query_visible` for complete check.
+pub fn is_node_in_frustum(graph: &Graph, node: &Node, frustum: &Frustum) -> bool {
+    match node {
+        Node::Mesh(mesh) => mesh.is_intersect_frustum(graph, frustum),
+        Node::Terrain(terrain) => {
+            let transform = terrain.global_transform();
+            terrain.chunks().iter().any(|chunk| {
+                let bounding_box = terrain.chunk_bounding_box(chunk);
+                frustum.is_intersects_aabb_transform(&bounding_box, &transform)
+            })
+        }
+        Node::Light(light) => {
+            frustum.is_intersects_sphere(light.global_position(), light.bounding_radius())
+        }
+        Node::Sprite(sprite) => {
+            frustum.is_intersects_sphere(sprite.global_position(), sprite.size())
+        }
+        Node::Water(water) => {
+            let (half_width, half_length) = water.world_half_extents();
+            frustum.is_intersects_sphere(water.global_position(), half_width.hypot(half_length))
+        }
+        Node::Scatter(scatter) => {
+            let transform = scatter.global_transform();
+            let radius = scatter.bounding_radius();
+            scatter.instances().iter().any(|instance| {
+                let position = (transform * instance.local_transform()).position();
+                frustum.is_intersects_sphere(position, radius * instance.scale)
+            })
+        }
+        _ => frustum.is_contains_point(node.global_position()),
+    }
+}
+
+impl Graph {
+    /// Returns iterator over handles of nodes that can be seen by given camera - nodes which
+    /// are globally visible, belong to render layers that camera can see and whose bounds
+    /// intersect view frustum of the camera. Camera itself is never returned. See
+    /// `visibility` module docs for details and limitations.
+    ///
+    /// Camera does not have to be a node of this graph, so any camera can be used as "eye"
+    /// as long as its matrices were calculated (see `Camera::calculate_matrices`).
+    pub fn query_visible<'a>(
+        &'a self,
+        camera: &'a Camera,
+    ) -> impl Iterator<Item = Handle<Node>> + 'a {
+        let frustum = camera.frustum();
+        self.pair_iter().filter_map(move |(handle, node)| {
+            let is_visible = !node.is_camera()
+                && node.global_visibility()
+                && camera.sees_layers(node.global_layers())
+                && is_node_in_frustum(self, node, &frustum);
+            if is_visible {
+                Some(handle)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::{vec2::Vec2, vec3::Vec3},
+        scene::{
+            base::BaseBuilder, camera::CameraBuilder, graph::Graph, sprite::SpriteBuilder,
+            transform::TransformBuilder,
+        },
+    };
+
+    #[test]
+    fn query_visible_uses_camera_frustum() {
+        let mut graph = Graph::new();
+        let make_sprite = |z: f32| {
+            SpriteBuilder::new(BaseBuilder::new().with_local_transform(
+                TransformBuilder::new()
+                    .with_local_position(Vec3::new(0.0, 0.0, z))
+                    .build(),
+            ))
+            .build_node()
+        };
+        // Camera looks along positive Z axis.
+        let in_front = graph.add_node(make_sprite(10.0));
+        let behind = graph.add_node(make_sprite(-10.0));
+        graph.update_hierachical_data();
+
+        let mut camera = CameraBuilder::new(BaseBuilder::new()).build();
+        camera.calculate_matrices(Vec2::new(800.0, 600.0));
+
+        assert!(camera.is_sphere_visible(Vec3::new(0.0, 0.0, 10.0), 1.0));
+        assert!(!camera.is_sphere_visible(Vec3::new(0.0, 0.0, -10.0), 1.0));
+
+        let visible = graph.query_visible(&camera).collect::<Vec<_>>();
+        assert!(visible.contains(&in_front));
+        assert!(!visible.contains(&behind));
+    }
+}
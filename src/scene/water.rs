@@ -0,0 +1,549 @@
+//! Contains all structures and methods to create and manage water surfaces.
+//!
+//! Water is a horizontal rectangle in XZ plane of the node which is displaced by a set of
+//! sine waves and shaded with two scrolling normal maps. Renderer draws it after opaque
+//! geometry, so it can take refraction from the frame behind the surface, tint it by
+//! depth of water (deeper parts absorb more light and get closer to deep color) and blend
+//! it with planar reflection of the scene.
+//!
+//! # Buoyancy
+//!
+//! Waves are computed on CPU in exactly the same way as on GPU, so `height_at` can be used
+//! to make objects float: compare height of the surface with position of an object and
+//! push the object up when it is below the surface. Waves are animated by scene update, so
+//! both sides stay in sync.
+//!
+//! # Limitations
+//!
+//! - Surface is always horizontal, rotation of the node is ignored (scale is not).
+//! - Objects below the surface are not clipped from reflection, so place reflective water
+//! at some distance from large underwater objects.
+//! - Reflection is rendered once per camera for the closest visible reflective water, other
+//! water surfaces at different heights will reuse it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::{
+//!         math::{vec2::Vec2, vec3::Vec3},
+//!         pool::Handle,
+//!     },
+//!     engine::resource_manager::{ResourceManager, TextureRequestOptions},
+//!     resource::texture::TextureKind,
+//!     scene::{
+//!         base::BaseBuilder,
+//!         node::Node,
+//!         transform::TransformBuilder,
+//!         water::{Wave, WaterBuilder},
+//!         Scene,
+//!     },
+//! };
+//!
+//! fn create_lake(scene: &mut Scene, resource_manager: &mut ResourceManager) -> Handle<Node> {
+//!     // Normal map stores directions, not colors, so it must not be treated as sRGB texture.
+//!     let normal_map = resource_manager.request_texture_with_options(
+//!         "data/water_normal.png",
+//!         TextureKind::RGBA8,
+//!         TextureRequestOptions {
+//!             srgb: false,
+//!             ..Default::default()
+//!         },
+//!     );
+//!     scene.graph.add_node(
+//!         WaterBuilder::new(
+//!             BaseBuilder::new().with_local_transform(
+//!                 TransformBuilder::new()
+//!                     .with_local_position(Vec3::new(0.0, -1.0, 0.0))
+//!                     .build(),
+//!             ),
+//!         )
+//!         .with_size(Vec2::new(100.0, 100.0))
+//!         .with_opt_normal_map(normal_map)
+//!         .with_waves(vec![Wave {
+//!             direction: Vec2::new(1.0, 0.3),
+//!             amplitude: 0.1,
+//!             wavelength: 8.0,
+//!             speed: 1.5,
+//!         }])
+//!         .build_node(),
+//!     )
+//! }
+//! ```
+
+use crate::{
+    core::{
+        color::Color,
+        math::{vec2::Vec2, vec3::Vec3},
+        visitor::{Visit, VisitResult, Visitor},
+    },
+    resource::texture::Texture,
+    scene::{
+        base::{Base, BaseBuilder},
+        node::Node,
+    },
+};
+use std::{
+    f32::consts::PI,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+/// Maximum amount of waves that will be taken into account. Rest of waves are ignored both
+/// by renderer and by `height_at`.
+pub const MAX_WAVES: usize = 4;
+
+/// Single sine wave travelling across water surface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Wave {
+    /// Direction of travel in XZ plane, it does not have to be normalized.
+    pub direction: Vec2,
+    /// Half of distance between crest and trough of wave.
+    pub amplitude: f32,
+    /// Distance between two crests of wave.
+    pub wavelength: f32,
+    /// Speed of wave in units per second.
+    pub speed: f32,
+}
+
+impl Default for Wave {
+    fn default() -> Self {
+        Self {
+            direction: Vec2::new(1.0, 0.0),
+            amplitude: 0.05,
+            wavelength: 4.0,
+            speed: 1.0,
+        }
+    }
+}
+
+impl Wave {
+    /// Returns normalized direction of wave, zero direction is treated as X axis.
+    pub fn normalized_direction(&self) -> Vec2 {
+        let len = self.direction.x.hypot(self.direction.y);
+        if len > std::f32::EPSILON {
+            Vec2::new(self.direction.x / len, self.direction.y / len)
+        } else {
+            Vec2::new(1.0, 0.0)
+        }
+    }
+
+    /// Returns offset of surface produced by the wave at given point of XZ plane at given
+    /// moment of time.
+    pub fn offset_at(&self, x: f32, z: f32, time: f32) -> f32 {
+        let direction = self.normalized_direction();
+        let k = 2.0 * PI / self.wavelength.max(std::f32::EPSILON);
+        let phase = direction.x * x + direction.y * z - self.speed * time;
+        self.amplitude * (k * phase).sin()
+    }
+}
+
+impl Visit for Wave {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.direction.visit("Direction", visitor)?;
+        self.amplitude.visit("Amplitude", visitor)?;
+        self.wavelength.visit("Wavelength", visitor)?;
+        self.speed.visit("Speed", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct Water {
+    base: Base,
+    size: Vec2,
+    normal_map: Option<Arc<Mutex<Texture>>>,
+    normal_map_tiling: f32,
+    flow: Vec2,
+    waves: Vec<Wave>,
+    shallow_color: Color,
+    deep_color: Color,
+    absorption_depth: f32,
+    reflection_strength: f32,
+    distortion: f32,
+    reflections: bool,
+    time: f32,
+}
+
+impl Deref for Water {
+    type Target = Base;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for Water {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl Default for Water {
+    fn default() -> Self {
+        WaterBuilder::new(BaseBuilder::new()).build()
+    }
+}
+
+impl Water {
+    /// Sets width (along X axis) and depth (along Z axis) of surface in local coordinates.
+    /// Surface is centered at position of the node.
+    pub fn set_size(&mut self, size: Vec2) {
+        self.size = size;
+    }
+
+    /// Returns size of surface in local coordinates.
+    pub fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    /// Sets normal map which is used to add small ripples to the surface.
+    pub fn set_normal_map(&mut self, normal_map: Option<Arc<Mutex<Texture>>>) {
+        self.normal_map = normal_map;
+    }
+
+    /// Returns current normal map, if any.
+    pub fn normal_map(&self) -> Option<Arc<Mutex<Texture>>> {
+        self.normal_map.clone()
+    }
+
+    /// Sets size of a single tile of normal map in world units.
+    pub fn set_normal_map_tiling(&mut self, tiling: f32) {
+        self.normal_map_tiling = tiling.max(std::f32::EPSILON);
+    }
+
+    /// Returns size of a single tile of normal map in world units.
+    pub fn normal_map_tiling(&self) -> f32 {
+        self.normal_map_tiling
+    }
+
+    /// Sets speed of normal map scrolling in world units per second. Second layer of normal
+    /// map scrolls in a slightly different direction to hide repetition.
+    pub fn set_flow(&mut self, flow: Vec2) {
+        self.flow = flow;
+    }
+
+    /// Returns speed of normal map scrolling.
+    pub fn flow(&self) -> Vec2 {
+        self.flow
+    }
+
+    /// Sets new set of waves. Only first `MAX_WAVES` waves are used.
+    pub fn set_waves(&mut self, waves: Vec<Wave>) {
+        self.waves = waves;
+    }
+
+    /// Returns shared reference to waves.
+    pub fn waves(&self) -> &[Wave] {
+        &self.waves
+    }
+
+    /// Returns mutable reference to waves.
+    pub fn waves_mut(&mut self) -> &mut Vec<Wave> {
+        &mut self.waves
+    }
+
+    /// Sets color of shallow water, it is mixed with refracted image of underwater objects.
+    pub fn set_shallow_color(&mut self, color: Color) {
+        self.shallow_color = color;
+    }
+
+    /// Returns color of shallow water.
+    pub fn shallow_color(&self) -> Color {
+        self.shallow_color
+    }
+
+    /// Sets color of deep water, alpha defines opacity of water at absorption depth.
+    pub fn set_deep_color(&mut self, color: Color) {
+        self.deep_color = color;
+    }
+
+    /// Returns color of deep water.
+    pub fn deep_color(&self) -> Color {
+        self.deep_color
+    }
+
+    /// Sets depth of water (distance from surface to geometry below it) at which water
+    /// reaches deep color.
+    pub fn set_absorption_depth(&mut self, depth: f32) {
+        self.absorption_depth = depth.max(std::f32::EPSILON);
+    }
+
+    /// Returns absorption depth.
+    pub fn absorption_depth(&self) -> f32 {
+        self.absorption_depth
+    }
+
+    /// Sets strength of reflection in [0; 1] range, actual amount of reflection also depends
+    /// on angle between view direction and surface.
+    pub fn set_reflection_strength(&mut self, strength: f32) {
+        self.reflection_strength = strength.max(0.0).min(1.0);
+    }
+
+    /// Returns strength of reflection.
+    pub fn reflection_strength(&self) -> f32 {
+        self.reflection_strength
+    }
+
+    /// Sets strength of distortion of refraction and reflection by normal map, in fractions
+    /// of screen size.
+    pub fn set_distortion(&mut self, distortion: f32) {
+        self.distortion = distortion;
+    }
+
+    /// Returns strength of distortion.
+    pub fn distortion(&self) -> f32 {
+        self.distortion
+    }
+
+    /// Enables or disables planar reflection. Rendering reflection requires additional pass
+    /// over the scene, so it is worth to disable it for small or distant water surfaces.
+    pub fn set_reflections_enabled(&mut self, enabled: bool) {
+        self.reflections = enabled;
+    }
+
+    /// Returns true if water has planar reflection.
+    pub fn reflections_enabled(&self) -> bool {
+        self.reflections
+    }
+
+    /// Returns time which is used to animate waves and normal maps.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Returns height of the surface (in world coordinates) at given point. Only X and Z
+    /// coordinates of the point are used. Point is not checked to be inside the surface,
+    /// use `contains` for that.
+    pub fn height_at(&self, point: Vec3) -> f32 {
+        let origin = self.global_position();
+        self.waves
+            .iter()
+            .take(MAX_WAVES)
+            .fold(origin.y, |height, wave| {
+                height + wave.offset_at(point.x, point.z, self.time)
+            })
+    }
+
+    /// Returns true if given point projected on XZ plane lies inside the surface.
+    pub fn contains(&self, point: Vec3) -> bool {
+        let origin = self.global_position();
+        let (half_width, half_depth) = self.world_half_extents();
+        (point.x - origin.x).abs() <= half_width && (point.z - origin.z).abs() <= half_depth
+    }
+
+    /// Returns depth of given point below the surface, or zero if point is above the surface
+    /// or outside of it. Can be used to calculate buoyancy force.
+    pub fn submersion(&self, point: Vec3) -> f32 {
+        if self.contains(point) {
+            (self.height_at(point) - point.y).max(0.0)
+        } else {
+            0.0
+        }
+    }
+
+    pub(in crate) fn world_half_extents(&self) -> (f32, f32) {
+        let transform = &self.global_transform;
+        let scale_x = Vec3::new(transform.f[0], transform.f[1], transform.f[2]).len();
+        let scale_z = Vec3::new(transform.f[8], transform.f[9], transform.f[10]).len();
+        (self.size.x * 0.5 * scale_x, self.size.y * 0.5 * scale_z)
+    }
+
+    pub(in crate) fn update(&mut self, dt: f32) {
+        self.time += dt;
+    }
+}
+
+impl Visit for Water {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        self.base.visit("Base", visitor)?;
+        self.size.visit("Size", visitor)?;
+        self.normal_map.visit("NormalMap", visitor)?;
+        self.normal_map_tiling.visit("NormalMapTiling", visitor)?;
+        self.flow.visit("Flow", visitor)?;
+        self.waves.visit("Waves", visitor)?;
+        self.shallow_color.visit("ShallowColor", visitor)?;
+        self.deep_color.visit("DeepColor", visitor)?;
+        self.absorption_depth.visit("AbsorptionDepth", visitor)?;
+        self.reflection_strength.visit("ReflectionStrength", visitor)?;
+        self.distortion.visit("Distortion", visitor)?;
+        self.reflections.visit("Reflections", visitor)?;
+        self.time.visit("Time", visitor)?;
+
+        visitor.leave_region()
+    }
+}
+
+/// Water builder allows you to construct water surface in declarative manner.
+pub struct WaterBuilder {
+    base_builder: BaseBuilder,
+    size: Vec2,
+    normal_map: Option<Arc<Mutex<Texture>>>,
+    normal_map_tiling: f32,
+    flow: Vec2,
+    waves: Vec<Wave>,
+    shallow_color: Color,
+    deep_color: Color,
+    absorption_depth: f32,
+    reflection_strength: f32,
+    distortion: f32,
+    reflections: bool,
+}
+
+impl WaterBuilder {
+    /// Creates new builder with default state: 10x10 surface with single gentle wave, no
+    /// normal map and enabled reflections.
+    pub fn new(base_builder: BaseBuilder) -> Self {
+        Self {
+            base_builder,
+            size: Vec2::new(10.0, 10.0),
+            normal_map: None,
+            normal_map_tiling: 4.0,
+            flow: Vec2::new(0.05, 0.03),
+            waves: vec![Wave::default()],
+            shallow_color: Color::opaque(40, 120, 130),
+            deep_color: Color::opaque(5, 30, 50),
+            absorption_depth: 4.0,
+            reflection_strength: 0.8,
+            distortion: 0.02,
+            reflections: true,
+        }
+    }
+
+    /// Sets desired size of surface, see `Water::set_size`.
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.size = size;
+        self
+    }
+
+    /// Sets desired normal map.
+    pub fn with_normal_map(mut self, normal_map: Arc<Mutex<Texture>>) -> Self {
+        self.normal_map = Some(normal_map);
+        self
+    }
+
+    /// Sets desired normal map.
+    pub fn with_opt_normal_map(mut self, normal_map: Option<Arc<Mutex<Texture>>>) -> Self {
+        self.normal_map = normal_map;
+        self
+    }
+
+    /// Sets desired size of normal map tile in world units.
+    pub fn with_normal_map_tiling(mut self, tiling: f32) -> Self {
+        self.normal_map_tiling = tiling.max(std::f32::EPSILON);
+        self
+    }
+
+    /// Sets desired speed of normal map scrolling.
+    pub fn with_flow(mut self, flow: Vec2) -> Self {
+        self.flow = flow;
+        self
+    }
+
+    /// Sets desired waves.
+    pub fn with_waves(mut self, waves: Vec<Wave>) -> Self {
+        self.waves = waves;
+        self
+    }
+
+    /// Sets desired color of shallow water.
+    pub fn with_shallow_color(mut self, color: Color) -> Self {
+        self.shallow_color = color;
+        self
+    }
+
+    /// Sets desired color of deep water.
+    pub fn with_deep_color(mut self, color: Color) -> Self {
+        self.deep_color = color;
+        self
+    }
+
+    /// Sets desired absorption depth.
+    pub fn with_absorption_depth(mut self, depth: f32) -> Self {
+        self.absorption_depth = depth.max(std::f32::EPSILON);
+        self
+    }
+
+    /// Sets desired strength of reflection.
+    pub fn with_reflection_strength(mut self, strength: f32) -> Self {
+        self.reflection_strength = strength.max(0.0).min(1.0);
+        self
+    }
+
+    /// Sets desired strength of distortion.
+    pub fn with_distortion(mut self, distortion: f32) -> Self {
+        self.distortion = distortion;
+        self
+    }
+
+    /// Enables or disables planar reflection.
+    pub fn with_reflections(mut self, enabled: bool) -> Self {
+        self.reflections = enabled;
+        self
+    }
+
+    /// Creates new water instance.
+    pub fn build(self) -> Water {
+        Water {
+            base: self.base_builder.build(),
+            size: self.size,
+            normal_map: self.normal_map,
+            normal_map_tiling: self.normal_map_tiling,
+            flow: self.flow,
+            waves: self.waves,
+            shallow_color: self.shallow_color,
+            deep_color: self.deep_color,
+            absorption_depth: self.absorption_depth,
+            reflection_strength: self.reflection_strength,
+            distortion: self.distortion,
+            reflections: self.reflections,
+            time: 0.0,
+        }
+    }
+
+    /// Creates new node instance.
+    pub fn build_node(self) -> Node {
+        Node::Water(self.build())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::{vec2::Vec2, vec3::Vec3},
+        scene::{
+            base::BaseBuilder,
+            water::{Wave, WaterBuilder},
+        },
+    };
+
+    #[test]
+    fn water_height_follows_waves() {
+        let mut water = WaterBuilder::new(BaseBuilder::new())
+            .with_waves(vec![Wave {
+                direction: Vec2::new(2.0, 0.0),
+                amplitude: 0.5,
+                wavelength: 4.0,
+                speed: 1.0,
+            }])
+            .build();
+
+        // Quarter of wavelength is a crest at zero time.
+        assert!((water.height_at(Vec3::new(1.0, 0.0, 0.0)) - 0.5).abs() < 1.0e-5);
+        assert!((water.height_at(Vec3::new(3.0, 0.0, 0.0)) + 0.5).abs() < 1.0e-5);
+
+        // After one second crest moves by one unit.
+        water.update(1.0);
+        assert!((water.height_at(Vec3::new(2.0, 0.0, 0.0)) - 0.5).abs() < 1.0e-5);
+
+        assert!(water.submersion(Vec3::new(2.0, -1.0, 0.0)) > 1.4);
+        assert_eq!(water.submersion(Vec3::new(2.0, 1.0, 0.0)), 0.0);
+        assert_eq!(water.submersion(Vec3::new(100.0, -1.0, 0.0)), 0.0);
+    }
+}
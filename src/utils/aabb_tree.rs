@@ -0,0 +1,479 @@
+//! Contains dynamic bounding volume hierarchy of axis-aligned bounding boxes.
+//!
+//! Tree stores arbitrary values together with their bounds and answers which values have
+//! bounds that overlap a box, a sphere, a frustum or a segment without checking every value.
+//! Values can be inserted, removed and moved at any time, tree is rebalanced incrementally
+//! (new leaves are inserted next to leaves that give the smallest growth of total surface
+//! area of the tree).
+//!
+//! # Fat bounds
+//!
+//! Bounds of leaves are enlarged by a margin, so small movement of a value does not require
+//! any changes in the tree: `update` only checks that new bounds are still inside of fat
+//! bounds. Because of this, queries return values whose fat bounds overlap a query, callers
+//! must do exact checks themselves if they need them.
+//!
+//! # Example
+//!
+//! ```
+//! use rg3d::{
+//!     core::math::{aabb::AxisAlignedBoundingBox, vec3::Vec3},
+//!     utils::aabb_tree::AabbTree,
+//! };
+//!
+//! let mut tree = AabbTree::new(0.1);
+//! let mut bounds = AxisAlignedBoundingBox::default();
+//! bounds.add_point(Vec3::new(-1.0, -1.0, -1.0));
+//! bounds.add_point(Vec3::new(1.0, 1.0, 1.0));
+//! let leaf = tree.insert(&bounds, "Crate");
+//!
+//! let mut result = Vec::new();
+//! tree.query_sphere(Vec3::new(2.0, 0.0, 0.0), 1.5, &mut result);
+//! assert_eq!(result, vec!["Crate"]);
+//!
+//! tree.remove(leaf);
+//! assert!(tree.is_empty());
+//! ```
+
+use crate::core::math::{aabb::AxisAlignedBoundingBox, frustum::Frustum, mat4::Mat4, vec3::Vec3};
+
+const NULL: u32 = std::u32::MAX;
+
+/// Identifier of a value in a tree, it stays the same while value is in the tree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AabbTreeLeaf(u32);
+
+#[derive(Copy, Clone, Debug)]
+struct Bounds {
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Bounds {
+    fn from_aabb(aabb: &AxisAlignedBoundingBox, margin: f32) -> Self {
+        let margin = Vec3::new(margin, margin, margin);
+        Self {
+            min: aabb.min - margin,
+            max: aabb.max + margin,
+        }
+    }
+
+    fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: Vec3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Vec3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    fn contains(&self, other: &Bounds) -> bool {
+        self.min.x <= other.min.x
+            && self.min.y <= other.min.y
+            && self.min.z <= other.min.z
+            && self.max.x >= other.max.x
+            && self.max.y >= other.max.y
+            && self.max.z >= other.max.z
+    }
+
+    fn overlaps(&self, other: &Bounds) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    fn sqr_distance_to(&self, point: Vec3) -> f32 {
+        let clamped = Vec3::new(
+            point.x.max(self.min.x).min(self.max.x),
+            point.y.max(self.min.y).min(self.max.y),
+            point.z.max(self.min.z).min(self.max.z),
+        );
+        clamped.sqr_distance(&point)
+    }
+
+    fn to_aabb(&self) -> AxisAlignedBoundingBox {
+        let mut aabb = AxisAlignedBoundingBox::default();
+        aabb.add_point(self.min);
+        aabb.add_point(self.max);
+        aabb
+    }
+
+    /// Slab test of segment from `origin` to `origin + dir`.
+    fn intersects_segment(&self, origin: Vec3, dir: Vec3) -> bool {
+        let origin = [origin.x, origin.y, origin.z];
+        let dir = [dir.x, dir.y, dir.z];
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+
+        let mut t_min = 0.0f32;
+        let mut t_max = 1.0f32;
+        for i in 0..3 {
+            if dir[i].abs() < std::f32::EPSILON {
+                if origin[i] < min[i] || origin[i] > max[i] {
+                    return false;
+                }
+            } else {
+                let inv_dir = 1.0 / dir[i];
+                let t0 = (min[i] - origin[i]) * inv_dir;
+                let t1 = (max[i] - origin[i]) * inv_dir;
+                t_min = t_min.max(t0.min(t1));
+                t_max = t_max.min(t0.max(t1));
+                if t_min > t_max {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+#[derive(Clone, Debug)]
+struct TreeNode<T> {
+    bounds: Bounds,
+    parent: u32,
+    children: [u32; 2],
+    // Some for leaves and None for internal nodes and free slots.
+    value: Option<T>,
+}
+
+/// See module docs.
+#[derive(Clone, Debug)]
+pub struct AabbTree<T> {
+    nodes: Vec<TreeNode<T>>,
+    free: Vec<u32>,
+    root: u32,
+    margin: f32,
+    leaf_count: usize,
+}
+
+impl<T> Default for AabbTree<T> {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_MARGIN)
+    }
+}
+
+impl<T> AabbTree<T> {
+    /// Default margin of fat bounds in meters.
+    pub const DEFAULT_MARGIN: f32 = 0.2;
+
+    /// Creates new empty tree with given margin of fat bounds, see module docs.
+    pub fn new(margin: f32) -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: NULL,
+            margin: margin.max(0.0),
+            leaf_count: 0,
+        }
+    }
+
+    /// Returns amount of values in the tree.
+    pub fn len(&self) -> usize {
+        self.leaf_count
+    }
+
+    /// Returns true if there are no values in the tree.
+    pub fn is_empty(&self) -> bool {
+        self.leaf_count == 0
+    }
+
+    /// Removes every value from the tree.
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.root = NULL;
+        self.leaf_count = 0;
+    }
+
+    /// Adds new value with given bounds to the tree.
+    pub fn insert(&mut self, bounds: &AxisAlignedBoundingBox, value: T) -> AabbTreeLeaf {
+        let leaf = self.allocate(Bounds::from_aabb(bounds, self.margin), Some(value));
+        self.insert_leaf(leaf);
+        self.leaf_count += 1;
+        AabbTreeLeaf(leaf)
+    }
+
+    /// Removes value from the tree and returns it. Returns None if there is no such leaf.
+    pub fn remove(&mut self, leaf: AabbTreeLeaf) -> Option<T> {
+        let value = self.nodes.get_mut(leaf.0 as usize)?.value.take()?;
+        self.remove_leaf(leaf.0);
+        self.free.push(leaf.0);
+        self.leaf_count -= 1;
+        Some(value)
+    }
+
+    /// Sets new bounds of given value. Returns true if tree was changed, false if new bounds
+    /// are inside of current fat bounds or if there is no such leaf.
+    pub fn update(&mut self, leaf: AabbTreeLeaf, bounds: &AxisAlignedBoundingBox) -> bool {
+        let is_leaf = self
+            .nodes
+            .get(leaf.0 as usize)
+            .map_or(false, |node| node.value.is_some());
+        let tight = Bounds::from_aabb(bounds, 0.0);
+        if !is_leaf || self.nodes[leaf.0 as usize].bounds.contains(&tight) {
+            return false;
+        }
+        self.remove_leaf(leaf.0);
+        self.nodes[leaf.0 as usize].bounds = Bounds::from_aabb(bounds, self.margin);
+        self.insert_leaf(leaf.0);
+        true
+    }
+
+    /// Returns value of given leaf.
+    pub fn get(&self, leaf: AabbTreeLeaf) -> Option<&T> {
+        self.nodes.get(leaf.0 as usize)?.value.as_ref()
+    }
+
+    /// Returns fat bounds of given leaf, see module docs.
+    pub fn fat_bounds(&self, leaf: AabbTreeLeaf) -> Option<AxisAlignedBoundingBox> {
+        let node = self.nodes.get(leaf.0 as usize)?;
+        node.value.as_ref().map(|_| node.bounds.to_aabb())
+    }
+
+    fn allocate(&mut self, bounds: Bounds, value: Option<T>) -> u32 {
+        let node = TreeNode {
+            bounds,
+            parent: NULL,
+            children: [NULL, NULL],
+            value,
+        };
+        if let Some(index) = self.free.pop() {
+            self.nodes[index as usize] = node;
+            index
+        } else {
+            self.nodes.push(node);
+            (self.nodes.len() - 1) as u32
+        }
+    }
+
+    fn is_leaf(&self, index: u32) -> bool {
+        self.nodes[index as usize].children[0] == NULL
+    }
+
+    fn insert_leaf(&mut self, leaf: u32) {
+        if self.root == NULL {
+            self.root = leaf;
+            self.nodes[leaf as usize].parent = NULL;
+            return;
+        }
+
+        // Find the best sibling using surface area heuristic.
+        let leaf_bounds = self.nodes[leaf as usize].bounds;
+        let mut index = self.root;
+        while !self.is_leaf(index) {
+            let node = &self.nodes[index as usize];
+            let area = node.bounds.surface_area();
+            let combined_area = node.bounds.union(&leaf_bounds).surface_area();
+            // Cost of making new parent for this node and the leaf.
+            let cost = 2.0 * combined_area;
+            // Minimum cost of pushing the leaf further down the tree.
+            let inheritance_cost = 2.0 * (combined_area - area);
+            let child_cost = |child: u32| {
+                let child_bounds = &self.nodes[child as usize].bounds;
+                let enlarged = child_bounds.union(&leaf_bounds).surface_area();
+                if self.is_leaf(child) {
+                    enlarged + inheritance_cost
+                } else {
+                    enlarged - child_bounds.surface_area() + inheritance_cost
+                }
+            };
+            let [left, right] = node.children;
+            let (left_cost, right_cost) = (child_cost(left), child_cost(right));
+            if cost < left_cost && cost < right_cost {
+                break;
+            }
+            index = if left_cost < right_cost { left } else { right };
+        }
+
+        let sibling = index;
+        let old_parent = self.nodes[sibling as usize].parent;
+        let bounds = self.nodes[sibling as usize].bounds.union(&leaf_bounds);
+        let new_parent = self.allocate(bounds, None);
+        self.nodes[new_parent as usize].parent = old_parent;
+        self.nodes[new_parent as usize].children = [sibling, leaf];
+        self.nodes[sibling as usize].parent = new_parent;
+        self.nodes[leaf as usize].parent = new_parent;
+        if old_parent == NULL {
+            self.root = new_parent;
+        } else {
+            let children = &mut self.nodes[old_parent as usize].children;
+            if children[0] == sibling {
+                children[0] = new_parent;
+            } else {
+                children[1] = new_parent;
+            }
+        }
+
+        self.refit(old_parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: u32) {
+        if leaf == self.root {
+            self.root = NULL;
+            return;
+        }
+
+        let parent = self.nodes[leaf as usize].parent;
+        let grand_parent = self.nodes[parent as usize].parent;
+        let [left, right] = self.nodes[parent as usize].children;
+        let sibling = if left == leaf { right } else { left };
+
+        if grand_parent == NULL {
+            self.root = sibling;
+            self.nodes[sibling as usize].parent = NULL;
+        } else {
+            let children = &mut self.nodes[grand_parent as usize].children;
+            if children[0] == parent {
+                children[0] = sibling;
+            } else {
+                children[1] = sibling;
+            }
+            self.nodes[sibling as usize].parent = grand_parent;
+        }
+        self.nodes[parent as usize].children = [NULL, NULL];
+        self.free.push(parent);
+        self.nodes[leaf as usize].parent = NULL;
+
+        self.refit(grand_parent);
+    }
+
+    fn refit(&mut self, mut index: u32) {
+        while index != NULL {
+            let [left, right] = self.nodes[index as usize].children;
+            let bounds = self.nodes[left as usize]
+                .bounds
+                .union(&self.nodes[right as usize].bounds);
+            self.nodes[index as usize].bounds = bounds;
+            index = self.nodes[index as usize].parent;
+        }
+    }
+
+    fn query<F>(&self, mut overlaps: F, result: &mut Vec<T>)
+    where
+        F: FnMut(&Bounds) -> bool,
+        T: Clone,
+    {
+        result.clear();
+
+        if self.root == NULL {
+            return;
+        }
+        let mut stack = vec![self.root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index as usize];
+            if !overlaps(&node.bounds) {
+                continue;
+            }
+            match node.value.as_ref() {
+                Some(value) => result.push(value.clone()),
+                None => stack.extend_from_slice(&node.children),
+            }
+        }
+    }
+
+    /// Collects values whose fat bounds overlap given box into `result`. Result is cleared
+    /// first.
+    pub fn query_aabb(&self, aabb: &AxisAlignedBoundingBox, result: &mut Vec<T>)
+    where
+        T: Clone,
+    {
+        let bounds = Bounds::from_aabb(aabb, 0.0);
+        self.query(|node| node.overlaps(&bounds), result)
+    }
+
+    /// Collects values whose fat bounds overlap given sphere into `result`. Result is cleared
+    /// first.
+    pub fn query_sphere(&self, center: Vec3, radius: f32, result: &mut Vec<T>)
+    where
+        T: Clone,
+    {
+        let sqr_radius = radius * radius;
+        self.query(|node| node.sqr_distance_to(center) <= sqr_radius, result)
+    }
+
+    /// Collects values whose fat bounds intersect given frustum into `result`. Result is
+    /// cleared first.
+    pub fn query_frustum(&self, frustum: &Frustum, result: &mut Vec<T>)
+    where
+        T: Clone,
+    {
+        self.query(
+            |node| frustum.is_intersects_aabb_transform(&node.to_aabb(), &Mat4::IDENTITY),
+            result,
+        )
+    }
+
+    /// Collects values whose fat bounds intersect segment from `origin` to `origin + dir`
+    /// into `result`, like in ray casts. Result is cleared first.
+    pub fn query_segment(&self, origin: Vec3, dir: Vec3, result: &mut Vec<T>)
+    where
+        T: Clone,
+    {
+        self.query(|node| node.intersects_segment(origin, dir), result)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        core::math::{aabb::AxisAlignedBoundingBox, vec3::Vec3},
+        utils::aabb_tree::AabbTree,
+    };
+
+    fn cube(center: Vec3, half_size: f32) -> AxisAlignedBoundingBox {
+        let half_size = Vec3::new(half_size, half_size, half_size);
+        let mut aabb = AxisAlignedBoundingBox::default();
+        aabb.add_point(center - half_size);
+        aabb.add_point(center + half_size);
+        aabb
+    }
+
+    fn sorted(mut values: Vec<usize>) -> Vec<usize> {
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn aabb_tree_queries() {
+        let mut tree = AabbTree::new(0.0);
+        // Row of unit cubes along X axis with centers at 0, 3, 6, ...
+        let leaves = (0..50)
+            .map(|i| tree.insert(&cube(Vec3::new(i as f32 * 3.0, 0.0, 0.0), 0.5), i))
+            .collect::<Vec<_>>();
+        assert_eq!(tree.len(), 50);
+
+        let mut result = Vec::new();
+        tree.query_aabb(&cube(Vec3::new(4.5, 0.0, 0.0), 1.2), &mut result);
+        assert_eq!(sorted(result.clone()), vec![1, 2]);
+
+        tree.query_sphere(Vec3::new(30.0, 2.0, 0.0), 1.6, &mut result);
+        assert_eq!(result, vec![10]);
+
+        tree.query_segment(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(8.0, 0.0, 0.0), &mut result);
+        assert_eq!(sorted(result.clone()), vec![0, 1, 2]);
+
+        // Moved value must be found at new place only, removed value must not be found.
+        assert!(tree.update(leaves[0], &cube(Vec3::new(0.0, 10.0, 0.0), 0.5)));
+        assert_eq!(tree.remove(leaves[1]), Some(1));
+        assert_eq!(tree.remove(leaves[1]), None);
+        tree.query_segment(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(8.0, 0.0, 0.0), &mut result);
+        assert_eq!(result, vec![2]);
+        tree.query_sphere(Vec3::new(0.0, 10.0, 0.0), 0.1, &mut result);
+        assert_eq!(result, vec![0]);
+        assert_eq!(tree.len(), 49);
+    }
+}
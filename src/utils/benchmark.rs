@@ -0,0 +1,459 @@
+//! Contains procedural stress scenes and benchmark runner.
+//!
+//! To be able to compare performance between versions of engine (or between different
+//! machines) there must be some standardized workload. This module generates stress scenes
+//! with desired amount of lights, particle systems and skinned characters, flies camera
+//! along fixed circular path around the scene and collects renderer statistics of every
+//! frame. Once benchmark is finished, it produces a report which can be printed as JSON,
+//! so it can be stored by CI and compared with reports of previous runs.
+//!
+//! Stress scenes do not use any external resources, so benchmark gives same results on
+//! any machine with same hardware.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     renderer::Statistics,
+//!     scene::Scene,
+//!     utils::benchmark::{Benchmark, BenchmarkSettings},
+//! };
+//!
+//! fn run_frame(
+//!     benchmark: &mut Benchmark,
+//!     scene: &mut Scene,
+//!     statistics: &Statistics,
+//!     dt: f32,
+//! ) {
+//!     if benchmark.update(scene, statistics, dt) {
+//!         println!("{}", benchmark.report().to_json());
+//!     }
+//! }
+//! ```
+
+use crate::{
+    core::{
+        color::Color,
+        math::{mat4::Mat4, quat::Quat, vec3::Vec3},
+        numeric_range::NumericRange,
+        pool::Handle,
+    },
+    renderer::{
+        surface::{SurfaceBuilder, SurfaceSharedData},
+        Statistics,
+    },
+    scene::{
+        base::BaseBuilder,
+        camera::CameraBuilder,
+        light::{BaseLightBuilder, PointLightBuilder},
+        mesh::MeshBuilder,
+        node::Node,
+        particle_system::{BaseEmitterBuilder, ParticleSystemBuilder, SphereEmitterBuilder},
+        transform::TransformBuilder,
+        Scene,
+    },
+};
+use std::{
+    f32::consts::PI,
+    fmt::Write,
+    sync::{Arc, Mutex},
+};
+
+/// Defines workload of stress scene and length of benchmark.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BenchmarkSettings {
+    /// Amount of point lights.
+    pub light_count: usize,
+    /// Amount of particle systems.
+    pub particle_system_count: usize,
+    /// Amount of skinned characters.
+    pub skinned_character_count: usize,
+    /// Size of square area (in meters) on which every object will be placed.
+    pub area_size: f32,
+    /// Time (in seconds) which camera needs to fly along its path. Benchmark is finished
+    /// when camera reaches end of the path.
+    pub duration: f32,
+}
+
+impl Default for BenchmarkSettings {
+    fn default() -> Self {
+        Self {
+            light_count: 32,
+            particle_system_count: 16,
+            skinned_character_count: 64,
+            area_size: 40.0,
+            duration: 20.0,
+        }
+    }
+}
+
+struct SkinnedCharacter {
+    spine: Handle<Node>,
+    chest: Handle<Node>,
+    phase: f32,
+}
+
+/// Runs benchmark in a stress scene. See module docs.
+pub struct Benchmark {
+    settings: BenchmarkSettings,
+    camera: Handle<Node>,
+    characters: Vec<SkinnedCharacter>,
+    time: f32,
+    frames: Vec<FrameSample>,
+}
+
+/// Statistics of single frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameSample {
+    /// Time (in seconds) renderer spent to render frame.
+    pub frame_time: f32,
+    /// Amount of draw calls in frame.
+    pub draw_calls: usize,
+    /// Amount of rendered triangles in frame.
+    pub triangles_rendered: usize,
+}
+
+/// Result of benchmark. All times are in milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BenchmarkReport {
+    /// Settings of benchmark.
+    pub settings: BenchmarkSettings,
+    /// Version of engine which was used to run benchmark.
+    pub engine_version: &'static str,
+    /// Total amount of collected frames.
+    pub frame_count: usize,
+    /// Average frame time.
+    pub average_frame_time: f32,
+    /// Minimal frame time.
+    pub min_frame_time: f32,
+    /// Maximal frame time.
+    pub max_frame_time: f32,
+    /// 95% of frames were rendered faster than this time.
+    pub percentile_95_frame_time: f32,
+    /// 99% of frames were rendered faster than this time.
+    pub percentile_99_frame_time: f32,
+    /// Average amount of draw calls per frame.
+    pub average_draw_calls: f32,
+    /// Average amount of rendered triangles per frame.
+    pub average_triangles_rendered: f32,
+}
+
+impl BenchmarkReport {
+    /// Returns report as JSON object.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        json.push_str("{\n");
+        let _ = writeln!(json, "  \"engine_version\": \"{}\",", self.engine_version);
+        let _ = writeln!(json, "  \"light_count\": {},", self.settings.light_count);
+        let _ = writeln!(
+            json,
+            "  \"particle_system_count\": {},",
+            self.settings.particle_system_count
+        );
+        let _ = writeln!(
+            json,
+            "  \"skinned_character_count\": {},",
+            self.settings.skinned_character_count
+        );
+        let _ = writeln!(json, "  \"area_size\": {},", self.settings.area_size);
+        let _ = writeln!(json, "  \"duration\": {},", self.settings.duration);
+        let _ = writeln!(json, "  \"frame_count\": {},", self.frame_count);
+        let _ = writeln!(json, "  \"average_frame_time\": {},", self.average_frame_time);
+        let _ = writeln!(json, "  \"min_frame_time\": {},", self.min_frame_time);
+        let _ = writeln!(json, "  \"max_frame_time\": {},", self.max_frame_time);
+        let _ = writeln!(
+            json,
+            "  \"percentile_95_frame_time\": {},",
+            self.percentile_95_frame_time
+        );
+        let _ = writeln!(
+            json,
+            "  \"percentile_99_frame_time\": {},",
+            self.percentile_99_frame_time
+        );
+        let _ = writeln!(json, "  \"average_draw_calls\": {},", self.average_draw_calls);
+        let _ = writeln!(
+            json,
+            "  \"average_triangles_rendered\": {}",
+            self.average_triangles_rendered
+        );
+        json.push('}');
+        json
+    }
+}
+
+fn make_skinned_character(scene: &mut Scene, position: Vec3) -> SkinnedCharacter {
+    const HEIGHT: f32 = 2.0;
+    const CHEST_HEIGHT: f32 = 1.0;
+
+    let mut data = SurfaceSharedData::make_cylinder(16, 0.3, HEIGHT, true, Mat4::IDENTITY);
+    // Lower part of cylinder is bound to spine, upper part to chest.
+    for vertex in data.get_vertices_mut() {
+        let k = (vertex.position.y / HEIGHT).max(0.0).min(1.0);
+        vertex.bone_indices = [0, 1, 0, 0];
+        vertex.bone_weights = [1.0 - k, k, 0.0, 0.0];
+    }
+
+    let mut spine = BaseBuilder::new()
+        .with_name("Spine")
+        .with_local_transform(
+            TransformBuilder::new()
+                .with_local_position(position)
+                .build(),
+        )
+        .build_node();
+    spine.inv_bind_pose_transform = Mat4::IDENTITY;
+    let spine = scene.graph.add_node(spine);
+
+    let mut chest = BaseBuilder::new()
+        .with_name("Chest")
+        .with_local_transform(
+            TransformBuilder::new()
+                .with_local_position(Vec3::new(0.0, CHEST_HEIGHT, 0.0))
+                .build(),
+        )
+        .build_node();
+    chest.inv_bind_pose_transform = Mat4::translate(Vec3::new(0.0, -CHEST_HEIGHT, 0.0));
+    let chest = scene.graph.add_node(chest);
+    scene.graph.link_nodes(chest, spine);
+
+    let mesh = MeshBuilder::new(BaseBuilder::new().with_name("Body"))
+        .with_surfaces(vec![SurfaceBuilder::new(Arc::new(Mutex::new(data)))
+            .with_color(Color::opaque(200, 180, 160))
+            .with_bones(vec![spine, chest])
+            .build()])
+        .build_node();
+    let mesh = scene.graph.add_node(mesh);
+    scene.graph.link_nodes(mesh, spine);
+
+    SkinnedCharacter {
+        spine,
+        chest,
+        phase: position.x + position.z,
+    }
+}
+
+/// Returns position of i-th object of total count, objects are placed on uniform grid.
+fn grid_position(i: usize, count: usize, area_size: f32, height: f32) -> Vec3 {
+    let side = (count as f32).sqrt().ceil().max(1.0) as usize;
+    let step = area_size / side as f32;
+    let half_size = area_size * 0.5;
+    Vec3::new(
+        (i % side) as f32 * step - half_size + step * 0.5,
+        height,
+        (i / side) as f32 * step - half_size + step * 0.5,
+    )
+}
+
+impl Benchmark {
+    /// Creates new benchmark and stress scene for it. Scene must be added to engine and
+    /// passed to `update` every frame.
+    pub fn new(settings: BenchmarkSettings) -> (Self, Scene) {
+        let mut scene = Scene::new();
+
+        let ground = SurfaceSharedData::make_cube(Mat4::scale(Vec3::new(
+            settings.area_size,
+            0.1,
+            settings.area_size,
+        )));
+        scene.graph.add_node(
+            MeshBuilder::new(BaseBuilder::new().with_name("Ground"))
+                .with_surfaces(vec![SurfaceBuilder::new(Arc::new(Mutex::new(ground))).build()])
+                .build_node(),
+        );
+
+        for i in 0..settings.light_count {
+            let position = grid_position(i, settings.light_count, settings.area_size, 3.0);
+            // Colors are distributed over hue circle, so overlapping lights are noticeable.
+            let hue = 2.0 * PI * i as f32 / settings.light_count as f32;
+            let channel = |offset: f32| (255.0 * (0.5 + 0.5 * (hue + offset).cos())) as u8;
+            let color = Color::opaque(
+                channel(0.0),
+                channel(2.0 * PI / 3.0),
+                channel(4.0 * PI / 3.0),
+            );
+            scene.graph.add_node(
+                PointLightBuilder::new(
+                    BaseLightBuilder::new(BaseBuilder::new().with_local_transform(
+                        TransformBuilder::new()
+                            .with_local_position(position)
+                            .build(),
+                    ))
+                    .with_color(color),
+                )
+                .with_radius(settings.area_size / (settings.light_count as f32).sqrt().max(1.0))
+                .build_node(),
+            );
+        }
+
+        for i in 0..settings.particle_system_count {
+            let position =
+                grid_position(i, settings.particle_system_count, settings.area_size, 0.5);
+            scene.graph.add_node(
+                ParticleSystemBuilder::new(BaseBuilder::new().with_local_transform(
+                    TransformBuilder::new()
+                        .with_local_position(position)
+                        .build(),
+                ))
+                .with_emitters(vec![SphereEmitterBuilder::new(
+                    BaseEmitterBuilder::new()
+                        .with_max_particles(500)
+                        .with_spawn_rate(100)
+                        .with_lifetime_range(NumericRange::new(2.0, 4.0))
+                        .with_size_range(NumericRange::new(0.05, 0.1))
+                        .with_x_velocity_range(NumericRange::new(-0.01, 0.01))
+                        .with_y_velocity_range(NumericRange::new(0.02, 0.03))
+                        .with_z_velocity_range(NumericRange::new(-0.01, 0.01)),
+                )
+                .with_radius(0.5)
+                .build()])
+                .build_node(),
+            );
+        }
+
+        let characters = (0..settings.skinned_character_count)
+            .map(|i| {
+                let position = grid_position(
+                    i,
+                    settings.skinned_character_count,
+                    settings.area_size,
+                    0.0,
+                );
+                make_skinned_character(&mut scene, position)
+            })
+            .collect();
+
+        let camera = scene
+            .graph
+            .add_node(CameraBuilder::new(BaseBuilder::new().with_name("Camera")).build_node());
+
+        let mut benchmark = Self {
+            settings,
+            camera,
+            characters,
+            time: 0.0,
+            frames: Vec::new(),
+        };
+        benchmark.animate(&mut scene);
+
+        (benchmark, scene)
+    }
+
+    /// Returns settings of benchmark.
+    pub fn settings(&self) -> BenchmarkSettings {
+        self.settings
+    }
+
+    /// Returns camera which flies along the path.
+    pub fn camera(&self) -> Handle<Node> {
+        self.camera
+    }
+
+    /// Returns true if camera has reached end of its path.
+    pub fn is_finished(&self) -> bool {
+        self.time >= self.settings.duration
+    }
+
+    fn animate(&mut self, scene: &mut Scene) {
+        let t = (self.time / self.settings.duration.max(std::f32::EPSILON)).min(1.0);
+
+        // Camera makes full circle around the scene and slowly moves down, so every
+        // object is visible from different distances.
+        let angle = t * 2.0 * PI;
+        let radius = self.settings.area_size * 0.75;
+        let height = self.settings.area_size * (0.5 - 0.3 * t);
+        let position = Vec3::new(radius * angle.cos(), height, radius * angle.sin());
+        let dir = (Vec3::ZERO - position).normalized().unwrap_or(Vec3::LOOK);
+        let yaw = Quat::from_axis_angle(Vec3::UP, dir.x.atan2(dir.z));
+        let pitch = Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), (-dir.y).asin());
+        scene.graph[self.camera]
+            .local_transform_mut()
+            .set_position(position)
+            .set_rotation(yaw * pitch);
+
+        for character in self.characters.iter() {
+            let phase = self.time * 2.0 + character.phase;
+            scene.graph[character.spine]
+                .local_transform_mut()
+                .set_rotation(Quat::from_axis_angle(Vec3::UP, phase));
+            scene.graph[character.chest]
+                .local_transform_mut()
+                .set_rotation(Quat::from_axis_angle(
+                    Vec3::new(0.0, 0.0, 1.0),
+                    0.5 * phase.sin(),
+                ));
+        }
+    }
+
+    /// Moves camera along the path, animates characters and collects statistics of
+    /// previous frame. Must be called once per frame with the scene that was created in
+    /// `new`. Returns true when benchmark has just finished, statistics are not collected
+    /// after that.
+    pub fn update(&mut self, scene: &mut Scene, statistics: &Statistics, dt: f32) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+
+        // Very first frame has no statistics yet.
+        if self.time > 0.0 {
+            self.frames.push(FrameSample {
+                frame_time: statistics.pure_frame_time,
+                draw_calls: statistics.geometry.draw_calls,
+                triangles_rendered: statistics.geometry.triangles_rendered,
+            });
+        }
+
+        self.time += dt;
+        self.animate(scene);
+
+        self.is_finished()
+    }
+
+    /// Returns statistics of every collected frame.
+    pub fn frames(&self) -> &[FrameSample] {
+        &self.frames
+    }
+
+    /// Makes report using collected statistics. Can be called before benchmark is
+    /// finished to get intermediate results.
+    pub fn report(&self) -> BenchmarkReport {
+        let mut frame_times = self
+            .frames
+            .iter()
+            .map(|frame| frame.frame_time * 1000.0)
+            .collect::<Vec<_>>();
+        frame_times.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+        let count = self.frames.len();
+        let average = |values: &mut dyn Iterator<Item = f32>| {
+            if count > 0 {
+                values.sum::<f32>() / count as f32
+            } else {
+                0.0
+            }
+        };
+        let percentile = |p: f32| {
+            if frame_times.is_empty() {
+                0.0
+            } else {
+                let index = ((frame_times.len() - 1) as f32 * p).round() as usize;
+                frame_times[index]
+            }
+        };
+
+        BenchmarkReport {
+            settings: self.settings,
+            engine_version: env!("CARGO_PKG_VERSION"),
+            frame_count: count,
+            average_frame_time: average(&mut frame_times.iter().copied()),
+            min_frame_time: frame_times.first().copied().unwrap_or_default(),
+            max_frame_time: frame_times.last().copied().unwrap_or_default(),
+            percentile_95_frame_time: percentile(0.95),
+            percentile_99_frame_time: percentile(0.99),
+            average_draw_calls: average(&mut self.frames.iter().map(|f| f.draw_calls as f32)),
+            average_triangles_rendered: average(
+                &mut self.frames.iter().map(|f| f.triangles_rendered as f32),
+            ),
+        }
+    }
+}
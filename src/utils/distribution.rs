@@ -0,0 +1,174 @@
+//! Contains random distributions of values in a numeric range.
+//!
+//! `NumericRange::random` gives every value of a range the same probability, this makes
+//! effects look noisy: for example particles of smoke have sizes that are spread evenly
+//! between min and max, while real smoke has most puffs of average size and only few tiny or
+//! huge ones. `RandomDistribution` defines how samples are spread in a range:
+//!
+//! - `Uniform` - every value has the same probability, same as `NumericRange::random`.
+//! - `Normal` - values cluster around center of the range (gaussian distribution), samples
+//!   out of the range are generated again, so range is still respected.
+//! - `Curve` - probability is defined by weights at evenly spaced points of the range (first
+//!   weight is at min, last is at max), probability between points is interpolated linearly.
+//! - `Constant` - always center of the range.
+//!
+//! Distributions are used by particle emitters, see `BaseEmitter::set_distribution`.
+
+use crate::core::{
+    numeric_range::NumericRange,
+    visitor::{Visit, VisitResult, Visitor},
+};
+use rand::Rng;
+
+// Normal samples out of range are rare, but a range can be much smaller than deviation.
+const MAX_NORMAL_ATTEMPTS: usize = 8;
+
+/// See module docs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RandomDistribution {
+    /// Every value has the same probability.
+    Uniform,
+    /// Values cluster around center of the range.
+    Normal {
+        /// Standard deviation relative to half of the range, `1.0` means that deviation is
+        /// the distance from center to bounds of the range.
+        deviation: f32,
+    },
+    /// Probability is defined by weights, see module docs. Negative weights are treated as
+    /// zero, less than two weights or zero sum of weights gives uniform distribution.
+    Curve(Vec<f32>),
+    /// Always center of the range.
+    Constant,
+}
+
+impl Default for RandomDistribution {
+    fn default() -> Self {
+        RandomDistribution::Uniform
+    }
+}
+
+impl RandomDistribution {
+    fn id(&self) -> u32 {
+        match self {
+            RandomDistribution::Uniform => 0,
+            RandomDistribution::Normal { .. } => 1,
+            RandomDistribution::Curve(_) => 2,
+            RandomDistribution::Constant => 3,
+        }
+    }
+
+    fn from_id(id: u32) -> Result<Self, String> {
+        match id {
+            0 => Ok(RandomDistribution::Uniform),
+            1 => Ok(RandomDistribution::Normal { deviation: 0.5 }),
+            2 => Ok(RandomDistribution::Curve(Vec::new())),
+            3 => Ok(RandomDistribution::Constant),
+            _ => Err(format!("Invalid random distribution {}", id)),
+        }
+    }
+
+    /// Returns random value in given range.
+    pub fn sample<R: Rng>(&self, range: &NumericRange<f32>, rng: &mut R) -> f32 {
+        let (min, max) = (range.bounds[0], range.bounds[1]);
+        min + (max - min) * self.sample_unit(rng)
+    }
+
+    // Returns random value in [0; 1] range.
+    fn sample_unit<R: Rng>(&self, rng: &mut R) -> f32 {
+        match self {
+            RandomDistribution::Uniform => rng.gen::<f32>(),
+            RandomDistribution::Normal { deviation } => {
+                let deviation = deviation.abs() * 0.5;
+                for _ in 0..MAX_NORMAL_ATTEMPTS {
+                    // Box-Muller transform.
+                    let u = 1.0 - rng.gen::<f32>();
+                    let v = rng.gen::<f32>();
+                    let normal = (-2.0 * u.ln()).sqrt() * (2.0 * std::f32::consts::PI * v).cos();
+                    let value = 0.5 + normal * deviation;
+                    if value >= 0.0 && value <= 1.0 {
+                        return value;
+                    }
+                }
+                0.5
+            }
+            RandomDistribution::Curve(weights) => sample_curve(weights, rng),
+            RandomDistribution::Constant => 0.5,
+        }
+    }
+}
+
+fn sample_curve<R: Rng>(weights: &[f32], rng: &mut R) -> f32 {
+    let segment_count = weights.len().saturating_sub(1);
+    let weight = |i: usize| weights[i].max(0.0);
+    let area = |i: usize| (weight(i) + weight(i + 1)) * 0.5;
+    let total_area = (0..segment_count).map(area).sum::<f32>();
+    if segment_count == 0 || total_area <= 0.0 {
+        return rng.gen::<f32>();
+    }
+
+    let mut target = rng.gen::<f32>() * total_area;
+    let mut segment = segment_count - 1;
+    for i in 0..segment_count {
+        if target < area(i) {
+            segment = i;
+            break;
+        }
+        target -= area(i);
+    }
+
+    // Invert integral of linear density a + (b - a) * t over the segment.
+    let (a, b) = (weight(segment), weight(segment + 1));
+    let k = (target / area(segment)).max(0.0).min(1.0);
+    let t = if (b - a).abs() < std::f32::EPSILON {
+        k
+    } else {
+        ((a * a + (b - a) * (a + b) * k).max(0.0).sqrt() - a) / (b - a)
+    };
+
+    (segment as f32 + t.max(0.0).min(1.0)) / segment_count as f32
+}
+
+impl Visit for RandomDistribution {
+    fn visit(&mut self, name: &str, visitor: &mut Visitor) -> VisitResult {
+        visitor.enter_region(name)?;
+
+        let mut id = self.id();
+        id.visit("Id", visitor)?;
+        if visitor.is_reading() {
+            *self = Self::from_id(id)?;
+        }
+        match self {
+            RandomDistribution::Uniform | RandomDistribution::Constant => (),
+            RandomDistribution::Normal { deviation } => deviation.visit("Deviation", visitor)?,
+            RandomDistribution::Curve(weights) => weights.visit("Weights", visitor)?,
+        }
+
+        visitor.leave_region()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{core::numeric_range::NumericRange, utils::distribution::RandomDistribution};
+
+    fn mean(distribution: &RandomDistribution, range: &NumericRange<f32>) -> f32 {
+        let mut rng = rand::thread_rng();
+        let count = 10_000;
+        let samples = (0..count)
+            .map(|_| distribution.sample(range, &mut rng))
+            .collect::<Vec<_>>();
+        assert!(samples.iter().all(|&s| s >= 2.0 && s <= 4.0));
+        samples.iter().sum::<f32>() / count as f32
+    }
+
+    #[test]
+    fn samples_are_distributed_in_range() {
+        let range = NumericRange::new(2.0, 4.0);
+        assert!((mean(&RandomDistribution::Uniform, &range) - 3.0).abs() < 0.05);
+        assert!((mean(&RandomDistribution::Normal { deviation: 0.3 }, &range) - 3.0).abs() < 0.05);
+        assert_eq!(mean(&RandomDistribution::Constant, &range), 3.0);
+        // Density grows linearly from min to max, so mean is at 2/3 of the range.
+        let curve = RandomDistribution::Curve(vec![0.0, 1.0]);
+        assert!((mean(&curve, &range) - 3.333).abs() < 0.05);
+    }
+}
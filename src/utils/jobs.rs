@@ -0,0 +1,214 @@
+//! Contains work-stealing job system which is shared by engine subsystems.
+//!
+//! Job system is a thread pool (rayon's work-stealing pool) which executes short tasks -
+//! jobs. Every idle thread steals jobs from busy ones, so work is balanced automatically.
+//! Engine uses global job system (`JobSystem::global`) for parallel update of graph nodes,
+//! parallel update of particles of large particle systems, batched ray casts of scenes and
+//! background loading of scenes, so these subsystems share the same threads instead of
+//! competing with each other using their own threads. Game code can use the same system to
+//! offload its work:
+//!
+//! - `JobSystem::spawn` - runs a job in background, result can be obtained using returned
+//!   `JobHandle`, for example to bake navmesh or generate a level chunk while game is running.
+//! - `JobSystem::parallel_for` - calls a function for every item of a slice using every
+//!   thread of the system, current thread waits until all items are processed.
+//! - `JobSystem::install` - runs a closure in the system, so every parallel iterator of rayon
+//!   used by the closure is executed by threads of the system.
+//!
+//! Job system implements `LoaderExecutor`, so it can load resources in background too (see
+//! `ResourceManager::set_loader_executor`), but it ignores load priorities.
+//!
+//! Long jobs occupy a thread until they're finished and make parallel work of other subsystems
+//! slower, so heavy work should be split into multiple jobs where possible.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{
+//!     core::math::{vec3::Vec3, TriangleDefinition},
+//!     utils::{jobs::JobSystem, navmesh::Navmesh},
+//! };
+//!
+//! fn bake(triangles: Vec<TriangleDefinition>, vertices: Vec<Vec3>) -> Navmesh {
+//!     let job = JobSystem::global().spawn(move || Navmesh::new(&triangles, &vertices));
+//!     // Do something useful while navmesh is baking.
+//!     job.wait()
+//! }
+//! ```
+
+use crate::engine::loader_pool::{LoadPriority, LoaderExecutor, LoaderTask};
+use rayon::prelude::*;
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+};
+
+lazy_static! {
+    static ref GLOBAL: JobSystem = JobSystem::new(0);
+}
+
+/// See module docs.
+#[derive(Clone)]
+pub struct JobSystem {
+    pool: Arc<rayon::ThreadPool>,
+}
+
+impl JobSystem {
+    /// Creates new job system with given amount of threads, zero means one thread per
+    /// logical core.
+    pub fn new(thread_count: usize) -> Self {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(thread_count)
+            .thread_name(|i| format!("rg3d-job-{}", i))
+            .build()
+            .expect("Unable to create job system threads!");
+        Self {
+            pool: Arc::new(pool),
+        }
+    }
+
+    /// Returns job system shared by engine subsystems, it has one thread per logical core.
+    pub fn global() -> &'static JobSystem {
+        &GLOBAL
+    }
+
+    /// Returns amount of threads of the system.
+    pub fn thread_count(&self) -> usize {
+        self.pool.current_num_threads()
+    }
+
+    /// Runs given job on some thread of the system. Panic of the job is passed to the thread
+    /// that waits for its result.
+    pub fn spawn<T, F>(&self, job: F) -> JobHandle<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let state = Arc::new(JobState {
+            result: Mutex::new(None),
+            condvar: Condvar::new(),
+        });
+        let job_state = state.clone();
+        self.pool.spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(job));
+            *job_state.result.lock().unwrap() = Some(result);
+            job_state.condvar.notify_all();
+        });
+        JobHandle {
+            state,
+            taken: false,
+        }
+    }
+
+    /// Calls given function for every item of given slice using every thread of the system and
+    /// waits until all items are processed. Slice is split into batches of at least
+    /// `min_batch_size` items, small batches give better balance but larger overhead.
+    pub fn parallel_for<T, F>(&self, items: &mut [T], min_batch_size: usize, func: F)
+    where
+        T: Send,
+        F: Fn(&mut T) + Send + Sync,
+    {
+        self.pool.install(|| {
+            items
+                .par_iter_mut()
+                .with_min_len(min_batch_size.max(1))
+                .for_each(func)
+        })
+    }
+
+    /// Runs given closure on a thread of the system and waits for its result. Parallel
+    /// iterators of rayon used inside of the closure are executed by the system.
+    pub fn install<R, F>(&self, func: F) -> R
+    where
+        R: Send,
+        F: FnOnce() -> R + Send,
+    {
+        self.pool.install(func)
+    }
+}
+
+impl LoaderExecutor for JobSystem {
+    fn execute(&self, task: LoaderTask, _priority: LoadPriority) {
+        self.pool.spawn(task);
+    }
+}
+
+struct JobState<T> {
+    result: Mutex<Option<thread::Result<T>>>,
+    condvar: Condvar,
+}
+
+/// Result of a job started by `JobSystem::spawn`. Job keeps running if handle is dropped.
+pub struct JobHandle<T> {
+    state: Arc<JobState<T>>,
+    taken: bool,
+}
+
+impl<T> JobHandle<T> {
+    /// Returns true if job is finished and result wasn't taken.
+    pub fn is_finished(&self) -> bool {
+        self.state.result.lock().unwrap().is_some()
+    }
+
+    /// Returns result of the job if it is finished, otherwise returns None. Result can be
+    /// taken only once, every next call will return None.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the job has panicked.
+    pub fn try_take(&mut self) -> Option<T> {
+        let result = self.state.result.lock().unwrap().take()?;
+        self.taken = true;
+        Some(result.unwrap_or_else(|payload| panic::resume_unwind(payload)))
+    }
+
+    /// Blocks current thread until job is finished and returns its result. Must not be
+    /// called from a job of the same system with one thread, it will never finish.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the job has panicked or its result was already taken by `try_take`.
+    pub fn wait(self) -> T {
+        assert!(!self.taken, "Result of the job was already taken!");
+        let mut result = self.state.result.lock().unwrap();
+        loop {
+            if let Some(result) = result.take() {
+                return result.unwrap_or_else(|payload| panic::resume_unwind(payload));
+            }
+            result = self.state.condvar.wait(result).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::jobs::JobSystem;
+
+    #[test]
+    fn jobs_are_executed() {
+        let jobs = JobSystem::new(2);
+        assert_eq!(jobs.thread_count(), 2);
+
+        let handles = (0..8u64)
+            .map(|i| jobs.spawn(move || i * i))
+            .collect::<Vec<_>>();
+        let sum = handles.into_iter().map(|handle| handle.wait()).sum::<u64>();
+        assert_eq!(sum, 140);
+
+        let mut items = (0..1000).collect::<Vec<u32>>();
+        jobs.parallel_for(&mut items, 16, |item| *item *= 2);
+        assert!(items
+            .iter()
+            .enumerate()
+            .all(|(i, &item)| item == 2 * i as u32));
+
+        let mut panicking = jobs.spawn(|| -> u32 { panic!("Job failure") });
+        while !panicking.is_finished() {
+            std::thread::yield_now();
+        }
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| panicking.try_take()));
+        assert!(result.is_err());
+    }
+}
@@ -17,6 +17,7 @@ use crate::{
     renderer::{surface::SurfaceSharedData, surface::Vertex},
     resource::texture::{Texture, TextureKind},
     scene::{light::Light, node::Node, Scene},
+    utils::log::{Log, LogCategory},
 };
 use image::ImageError;
 use std::{
@@ -77,42 +78,7 @@ impl Lightmap {
         // Extract info about lights first. We need it to be in separate array because
         // it won't be possible to store immutable references to light sources and at the
         // same time modify meshes.
-        let mut lights = Vec::new();
-        for (handle, node) in scene.graph.pair_iter() {
-            if let Node::Light(light) = node {
-                match light {
-                    Light::Directional(_) => lights.push((
-                        handle,
-                        LightDefinition::Directional(DirectionalLightDefinition {
-                            intensity: 1.0,
-                            direction: light.up_vector().normalized().unwrap_or(Vec3::UP),
-                            color: light.color(),
-                        }),
-                    )),
-                    Light::Spot(spot) => lights.push((
-                        handle,
-                        LightDefinition::Spot(SpotLightDefinition {
-                            intensity: 1.0,
-                            hotspot_cone_angle: spot.hotspot_cone_angle(),
-                            falloff_angle_delta: spot.falloff_angle_delta(),
-                            color: light.color(),
-                            direction: light.up_vector().normalized().unwrap_or(Vec3::UP),
-                            position: light.global_position(),
-                            distance: spot.distance(),
-                        }),
-                    )),
-                    Light::Point(point) => lights.push((
-                        handle,
-                        LightDefinition::Point(PointLightDefinition {
-                            intensity: 1.0,
-                            position: light.global_position(),
-                            color: light.color(),
-                            radius: point.radius(),
-                        }),
-                    )),
-                }
-            }
-        }
+        let lights = collect_lights(scene);
         let mut map = HashMap::new();
         for (handle, node) in scene.graph.pair_iter() {
             if let Node::Mesh(mesh) = node {
@@ -152,14 +118,56 @@ impl Lightmap {
                 let file_path = handle_path.clone() + "_" + i.to_string().as_str() + ".png";
                 let texture = entry.texture.clone().unwrap();
                 let mut texture = texture.lock().unwrap();
-                texture.set_path(&base_path.as_ref().join(file_path));
-                texture.save()?;
+                let file_path = base_path.as_ref().join(file_path);
+                texture.set_path(&file_path);
+                texture.save(&file_path)?;
             }
         }
         Ok(())
     }
 }
 
+/// Extracts definitions of every light in given scene.
+pub(in crate) fn collect_lights(scene: &Scene) -> Vec<(Handle<Node>, LightDefinition)> {
+    let mut lights = Vec::new();
+    for (handle, node) in scene.graph.pair_iter() {
+        if let Node::Light(light) = node {
+            match light {
+                Light::Directional(_) => lights.push((
+                    handle,
+                    LightDefinition::Directional(DirectionalLightDefinition {
+                        intensity: 1.0,
+                        direction: light.up_vector().normalized().unwrap_or(Vec3::UP),
+                        color: light.color(),
+                    }),
+                )),
+                Light::Spot(spot) => lights.push((
+                    handle,
+                    LightDefinition::Spot(SpotLightDefinition {
+                        intensity: 1.0,
+                        hotspot_cone_angle: spot.hotspot_cone_angle(),
+                        falloff_angle_delta: spot.falloff_angle_delta(),
+                        color: light.color(),
+                        direction: light.up_vector().normalized().unwrap_or(Vec3::UP),
+                        position: light.global_position(),
+                        distance: spot.distance(),
+                    }),
+                )),
+                Light::Point(point) => lights.push((
+                    handle,
+                    LightDefinition::Point(PointLightDefinition {
+                        intensity: 1.0,
+                        position: light.global_position(),
+                        color: light.color(),
+                        radius: point.radius(),
+                    }),
+                )),
+            }
+        }
+    }
+    lights
+}
+
 /// Directional light is a light source with parallel rays. Example: Sun.
 pub struct DirectionalLightDefinition {
     /// Intensity is how bright light is. Default is 1.0.
@@ -226,7 +234,7 @@ fn estimate_size(vertices: &[Vec3], triangles: &[TriangleDefinition], texels_per
 
 /// Calculates distance attenuation for a point using given distance to the point and
 /// radius of a light.
-fn distance_attenuation(distance: f32, radius: f32) -> f32 {
+pub(in crate) fn distance_attenuation(distance: f32, radius: f32) -> f32 {
     let attenuation = (1.0 - distance * distance / (radius * radius))
         .max(0.0)
         .min(1.0);
@@ -363,7 +371,7 @@ fn lambertian(light_vec: Vec3, normal: Vec3) -> f32 {
 }
 
 /// https://en.wikipedia.org/wiki/Smoothstep
-fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+pub(in crate) fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
     let k = ((x - edge0) / (edge1 - edge0)).max(0.0).min(1.0);
     k * k * (3.0 - 2.0 * k)
 }
@@ -391,7 +399,10 @@ fn generate_lightmap<'a, I: IntoIterator<Item = &'a LightDefinition>>(
 
     let grid = Grid::new(data, (size / 16).max(4) as usize);
 
-    println!("Step 0: {:?}", time::Instant::now() - last_time);
+    Log::debug(
+        LogCategory::Renderer,
+        format!("Lightmap step 0: {:?}", last_time.elapsed()),
+    );
 
     // TODO: Must be inverse transposed to eliminate scale/shear.
     let normal_matrix = transform.basis();
@@ -424,7 +435,10 @@ fn generate_lightmap<'a, I: IntoIterator<Item = &'a LightDefinition>>(
         }
     }
 
-    println!("Step 1: {:?}", time::Instant::now() - last_time);
+    Log::debug(
+        LogCategory::Renderer,
+        format!("Lightmap step 1: {:?}", last_time.elapsed()),
+    );
 
     let last_time = time::Instant::now();
 
@@ -480,7 +494,10 @@ fn generate_lightmap<'a, I: IntoIterator<Item = &'a LightDefinition>>(
         }
     }
 
-    println!("Step 2: {:?}", time::Instant::now() - last_time);
+    Log::debug(
+        LogCategory::Renderer,
+        format!("Lightmap step 2: {:?}", last_time.elapsed()),
+    );
 
     let mut bytes = Vec::with_capacity((size * size * 4) as usize);
     for pixel in pixels {
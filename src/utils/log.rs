@@ -1,28 +1,328 @@
-//! Simple logger, it writes in file and in console at the same time.
+//! Engine-wide logger with levels, categories and pluggable sinks.
+//!
+//! Every message has a level (`LogLevel`) and a category (`LogCategory`) which tells which part
+//! of the engine has written it. Messages are passed to sinks, by default messages are written
+//! to console and to `rg3d.log` file. Sinks can be replaced, for example shipped game can write
+//! log to a file in user's directory and show last messages in in-game console using
+//! `ConsoleBuffer`. Messages below minimal level (`Information` by default) and messages of
+//! disabled categories are dropped before they reach sinks.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::utils::log::{ConsoleBuffer, FileSink, Log, LogCategory, LogLevel};
+//!
+//! let console = ConsoleBuffer::new(100);
+//! Log::clear_sinks();
+//! Log::add_sink(Box::new(FileSink::new("game.log").unwrap()));
+//! Log::add_sink(Box::new(console.clone()));
+//! Log::set_level(LogLevel::Warning);
+//!
+//! Log::warn(LogCategory::General, "Save file is outdated!");
+//! for record in console.records() {
+//!     println!("{}", record);
+//! }
+//! ```
 
 use std::{
+    collections::VecDeque,
+    fmt::{Display, Formatter},
     fs::File,
     io::{self, Write},
-    sync::Mutex,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
 
+/// Importance of a message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    /// Detailed messages which are useful only for debugging of the engine.
+    Debug,
+    /// Regular messages, like "resource is loaded".
+    Information,
+    /// Something went wrong, but engine was able to continue using some fallback.
+    Warning,
+    /// Operation has failed.
+    Error,
+}
+
+impl Display for LogLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Information => "INFO",
+            LogLevel::Warning => "WARNING",
+            LogLevel::Error => "ERROR",
+        })
+    }
+}
+
+/// Part of the engine which has written a message.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LogCategory {
+    /// Messages of game code and messages without specific category.
+    General,
+    /// Renderer and OpenGL messages.
+    Renderer,
+    /// Loading and reloading of resources.
+    Resource,
+    /// Sound buffers and sound sources.
+    Sound,
+    /// Scenes, graphs and nodes.
+    Scene,
+    /// Animations and animation machines.
+    Animation,
+}
+
+impl LogCategory {
+    /// Amount of categories.
+    pub const COUNT: usize = 6;
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl Display for LogCategory {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LogCategory::General => "General",
+            LogCategory::Renderer => "Renderer",
+            LogCategory::Resource => "Resource",
+            LogCategory::Sound => "Sound",
+            LogCategory::Scene => "Scene",
+            LogCategory::Animation => "Animation",
+        })
+    }
+}
+
+/// Single message of the log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRecord {
+    /// Importance of the message.
+    pub level: LogLevel,
+    /// Part of the engine which has written the message.
+    pub category: LogCategory,
+    /// Time from start of logging.
+    pub time: Duration,
+    /// Text of the message.
+    pub message: String,
+}
+
+impl Display for LogRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{:.3}] [{}] [{}] {}",
+            self.time.as_secs_f64(),
+            self.level,
+            self.category,
+            self.message
+        )
+    }
+}
+
+/// Destination of log messages, see module docs.
+pub trait LogSink: Send {
+    /// Writes given record. Sink must not write messages to log itself.
+    fn write(&mut self, record: &LogRecord);
+}
+
+/// Writes messages to standard output.
+#[derive(Default)]
+pub struct StdoutSink;
+
+impl LogSink for StdoutSink {
+    fn write(&mut self, record: &LogRecord) {
+        let _ = writeln!(io::stdout(), "{}", record);
+    }
+}
+
+/// Writes messages to a file.
+pub struct FileSink {
+    file: File,
+}
+
+impl FileSink {
+    /// Creates new file with given path, existing file is overwritten.
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            file: File::create(path)?,
+        })
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&mut self, record: &LogRecord) {
+        let _ = writeln!(self.file, "{}", record);
+    }
+}
+
+/// Keeps last messages in memory, so they can be shown in in-game console. Buffer is shared
+/// between its clones, so one clone can be given to logger and other one can be used to read
+/// messages.
+#[derive(Clone)]
+pub struct ConsoleBuffer {
+    records: Arc<Mutex<VecDeque<LogRecord>>>,
+    capacity: usize,
+}
+
+impl ConsoleBuffer {
+    /// Creates new buffer which keeps given amount of last messages.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: Default::default(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Returns kept messages from oldest to newest.
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Removes every kept message.
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+}
+
+impl LogSink for ConsoleBuffer {
+    fn write(&mut self, record: &LogRecord) {
+        let mut records = self.records.lock().unwrap();
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record.clone());
+    }
+}
+
+struct Logger {
+    start: Instant,
+    level: LogLevel,
+    enabled_categories: [bool; LogCategory::COUNT],
+    sinks: Vec<Box<dyn LogSink>>,
+}
+
 lazy_static! {
-    static ref LOG_FILE: Mutex<File> = Mutex::new(File::create("rg3d.log").unwrap());
+    static ref LOGGER: Mutex<Logger> = {
+        let mut sinks: Vec<Box<dyn LogSink>> = vec![Box::new(StdoutSink)];
+        if let Ok(file) = FileSink::new("rg3d.log") {
+            sinks.push(Box::new(file));
+        }
+        Mutex::new(Logger {
+            start: Instant::now(),
+            level: LogLevel::Information,
+            enabled_categories: [true; LogCategory::COUNT],
+            sinks,
+        })
+    };
 }
 
 /// See module docs.
 pub struct Log {}
 
 impl Log {
-    /// Writes string into console and into file.
+    /// Writes message with given level and category to every sink.
+    pub fn log<S: Into<String>>(level: LogLevel, category: LogCategory, message: S) {
+        let mut logger = LOGGER.lock().unwrap();
+        if level < logger.level || !logger.enabled_categories[category.index()] {
+            return;
+        }
+        let record = LogRecord {
+            level,
+            category,
+            time: logger.start.elapsed(),
+            message: message.into(),
+        };
+        for sink in logger.sinks.iter_mut() {
+            sink.write(&record);
+        }
+    }
+
+    /// Writes debug message.
+    pub fn debug<S: Into<String>>(category: LogCategory, message: S) {
+        Self::log(LogLevel::Debug, category, message)
+    }
+
+    /// Writes information message.
+    pub fn info<S: Into<String>>(category: LogCategory, message: S) {
+        Self::log(LogLevel::Information, category, message)
+    }
+
+    /// Writes warning message.
+    pub fn warn<S: Into<String>>(category: LogCategory, message: S) {
+        Self::log(LogLevel::Warning, category, message)
+    }
+
+    /// Writes error message.
+    pub fn err<S: Into<String>>(category: LogCategory, message: S) {
+        Self::log(LogLevel::Error, category, message)
+    }
+
+    /// Writes information message of general category. Kept for compatibility, trailing new
+    /// line is removed because sinks write every message on its own line.
     pub fn write(msg: String) {
-        let _ = io::stdout().write_all(msg.as_bytes());
-        let _ = LOG_FILE.lock().unwrap().write_all(msg.as_bytes());
+        Self::info(LogCategory::General, msg.trim_end_matches('\n'))
+    }
+
+    /// Writes information message of general category.
+    pub fn writeln(msg: String) {
+        Self::info(LogCategory::General, msg)
+    }
+
+    /// Sets minimal level of messages, messages with lower level are dropped.
+    pub fn set_level(level: LogLevel) {
+        LOGGER.lock().unwrap().level = level;
+    }
+
+    /// Returns minimal level of messages.
+    pub fn level() -> LogLevel {
+        LOGGER.lock().unwrap().level
+    }
+
+    /// Enables or disables messages of given category.
+    pub fn set_category_enabled(category: LogCategory, enabled: bool) {
+        LOGGER.lock().unwrap().enabled_categories[category.index()] = enabled;
+    }
+
+    /// Returns true if messages of given category are written.
+    pub fn is_category_enabled(category: LogCategory) -> bool {
+        LOGGER.lock().unwrap().enabled_categories[category.index()]
+    }
+
+    /// Adds new sink, every next message will be written to it too.
+    pub fn add_sink(sink: Box<dyn LogSink>) {
+        LOGGER.lock().unwrap().sinks.push(sink);
     }
 
-    /// Writes line into console and into file.
-    pub fn writeln(mut msg: String) {
-        msg.push('\n');
-        Self::write(msg)
+    /// Removes every sink including default ones, messages are dropped until new sink is added.
+    pub fn clear_sinks() {
+        LOGGER.lock().unwrap().sinks.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::log::{ConsoleBuffer, LogCategory, LogLevel, LogRecord, LogSink};
+    use std::time::Duration;
+
+    #[test]
+    fn console_buffer_keeps_last_records() {
+        let buffer = ConsoleBuffer::new(2);
+        let mut sink = buffer.clone();
+        for message in ["first", "second", "third"].iter() {
+            sink.write(&LogRecord {
+                level: LogLevel::Warning,
+                category: LogCategory::Sound,
+                time: Duration::from_millis(1500),
+                message: message.to_string(),
+            });
+        }
+        let records = buffer.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].message, "second");
+        assert_eq!(records[1].to_string(), "[1.500] [WARNING] [Sound] third");
     }
 }
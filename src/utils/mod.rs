@@ -2,11 +2,18 @@
 
 //! Utilities module provides set of commonly used algorithms.
 
+pub mod aabb_tree;
 pub mod astar;
+pub mod benchmark;
+pub mod distribution;
+pub mod jobs;
 pub mod lightmap;
 pub mod log;
 pub mod navmesh;
+pub mod profiler;
+pub mod random;
 pub mod raw_mesh;
+pub mod sdf;
 pub mod uvgen;
 
 use crate::gui::draw;
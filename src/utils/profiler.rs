@@ -0,0 +1,371 @@
+//! Contains instrumentation profiler with per-frame hierarchical timings.
+//!
+//! Profiler measures time of scopes marked with `profile_scope!` macro. Scopes can be nested,
+//! every scope knows its depth, so timings of a frame form a tree: for example "Update" contains
+//! "SceneUpdate" which contains "Physics", "Animations" and so on. Engine marks its main stages
+//! (update of resources, scenes, physics, particles, user interface and rendering), game code
+//! can mark its own scopes the same way and they will be shown in the same tree.
+//!
+//! Profiler is disabled by default and disabled scopes cost only a check of a flag, enable it
+//! with `set_enabled`. Frame is finished by `Engine::render`, call `next_frame` manually if
+//! engine is not used to render frames. Profiler keeps last `history_size` frames, they can be
+//! queried with `last_frame` and `frames` or written in Chrome trace format (which can be opened
+//! in `chrome://tracing` or https://ui.perfetto.dev) with `save_chrome_trace`.
+//!
+//! This profiler is independent from `scope_profile!` of rg3d-core, which collects overall
+//! statistics of functions (renderer uses it) and is enabled by `enable_profiler` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use rg3d::{profile_scope, utils::profiler};
+//!
+//! fn update_ai() {
+//!     profile_scope!("AI");
+//!     // ...
+//! }
+//!
+//! profiler::set_enabled(true);
+//! update_ai();
+//! profiler::next_frame();
+//! if let Some(frame) = profiler::last_frame() {
+//!     println!("{}", frame.print());
+//! }
+//! profiler::save_chrome_trace("trace.json").unwrap();
+//! ```
+
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    fmt::Write as FmtWrite,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Amount of frames kept by profiler by default.
+pub const DEFAULT_HISTORY_SIZE: usize = 120;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static THREAD_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+    static THREAD_ID: u32 = THREAD_COUNTER.fetch_add(1, Ordering::Relaxed);
+    static DEPTH: Cell<u32> = Cell::new(0);
+}
+
+struct Profiler {
+    origin: Instant,
+    frame_start: Instant,
+    frame_index: u64,
+    scopes: Vec<ScopeTiming>,
+    frames: VecDeque<FrameProfile>,
+    history_size: usize,
+}
+
+lazy_static! {
+    static ref PROFILER: Mutex<Profiler> = {
+        let now = Instant::now();
+        Mutex::new(Profiler {
+            origin: now,
+            frame_start: now,
+            frame_index: 0,
+            scopes: Vec::new(),
+            frames: VecDeque::new(),
+            history_size: DEFAULT_HISTORY_SIZE,
+        })
+    };
+}
+
+/// Timing of a single scope.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeTiming {
+    /// Name of the scope passed to `profile_scope!`.
+    pub name: &'static str,
+    /// Index of a thread the scope was measured on, threads are numbered in order of their
+    /// first measured scope.
+    pub thread: u32,
+    /// Amount of scopes the scope is nested in, top-level scopes have zero depth.
+    pub depth: u32,
+    /// Time from start of profiling to start of the scope.
+    pub start: Duration,
+    /// Time spent in the scope, including nested scopes.
+    pub duration: Duration,
+}
+
+/// Timings of every scope measured during a frame.
+#[derive(Clone, Debug, Default)]
+pub struct FrameProfile {
+    /// Index of the frame, increased by `next_frame`.
+    pub index: u64,
+    /// Time from start of profiling to start of the frame.
+    pub start: Duration,
+    /// Duration of the frame.
+    pub duration: Duration,
+    /// Scopes sorted by thread and start time, so children of a scope follow it.
+    pub scopes: Vec<ScopeTiming>,
+}
+
+impl FrameProfile {
+    /// Returns total time of every scope with given name in the frame.
+    pub fn total(&self, name: &str) -> Duration {
+        self.scopes
+            .iter()
+            .filter(|scope| scope.name == name)
+            .map(|scope| scope.duration)
+            .sum()
+    }
+
+    /// Returns amount of times scope with given name was entered during the frame.
+    pub fn count(&self, name: &str) -> usize {
+        self.scopes.iter().filter(|scope| scope.name == name).count()
+    }
+
+    /// Returns human-readable tree of scopes with their durations in milliseconds.
+    pub fn print(&self) -> String {
+        let mut result = format!(
+            "Frame {} - {:.3} ms\n",
+            self.index,
+            self.duration.as_secs_f64() * 1000.0
+        );
+        let mut thread = None;
+        for scope in self.scopes.iter() {
+            if thread != Some(scope.thread) {
+                thread = Some(scope.thread);
+                let _ = writeln!(result, "Thread {}", scope.thread);
+            }
+            let _ = writeln!(
+                result,
+                "{:indent$}{} - {:.3} ms",
+                "",
+                scope.name,
+                scope.duration.as_secs_f64() * 1000.0,
+                indent = 2 * (scope.depth as usize + 1)
+            );
+        }
+        result
+    }
+}
+
+/// Measures time from its creation to drop, use `profile_scope!` macro instead of creating it
+/// directly.
+pub struct ProfileScope {
+    name: &'static str,
+    start: Option<Instant>,
+    depth: u32,
+}
+
+impl ProfileScope {
+    /// Starts measuring of scope with given name, does nothing if profiler is disabled.
+    #[inline]
+    pub fn new(name: &'static str) -> Self {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return Self {
+                name,
+                start: None,
+                depth: 0,
+            };
+        }
+        let depth = DEPTH.with(|depth| {
+            let value = depth.get();
+            depth.set(value + 1);
+            value
+        });
+        Self {
+            name,
+            start: Some(Instant::now()),
+            depth,
+        }
+    }
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            let end = Instant::now();
+            DEPTH.with(|depth| depth.set(self.depth));
+            let thread = THREAD_ID.with(|id| *id);
+            let mut profiler = PROFILER.lock().unwrap();
+            let scope = ScopeTiming {
+                name: self.name,
+                thread,
+                depth: self.depth,
+                start: start.saturating_duration_since(profiler.origin),
+                duration: end - start,
+            };
+            profiler.scopes.push(scope);
+        }
+    }
+}
+
+/// Measures time until end of current block and records it under given name, see module docs.
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::utils::profiler::ProfileScope::new($name);
+    };
+}
+
+/// Enables or disables profiler. Scopes that were entered while profiler was disabled are not
+/// measured even if it is enabled before they end.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true if profiler is enabled.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Sets amount of frames kept by profiler, older frames are discarded.
+pub fn set_history_size(history_size: usize) {
+    let mut profiler = PROFILER.lock().unwrap();
+    profiler.history_size = history_size.max(1);
+    while profiler.frames.len() > profiler.history_size {
+        profiler.frames.pop_front();
+    }
+}
+
+/// Finishes current frame and starts new one. Called by `Engine::render`. Frames without
+/// measured scopes are not stored.
+pub fn next_frame() {
+    let now = Instant::now();
+    let mut profiler = PROFILER.lock().unwrap();
+    let mut scopes = std::mem::take(&mut profiler.scopes);
+    if !scopes.is_empty() {
+        scopes.sort_by(|a, b| a.thread.cmp(&b.thread).then(a.start.cmp(&b.start)));
+        let frame = FrameProfile {
+            index: profiler.frame_index,
+            start: profiler.frame_start.saturating_duration_since(profiler.origin),
+            duration: now - profiler.frame_start,
+            scopes,
+        };
+        profiler.frames.push_back(frame);
+        while profiler.frames.len() > profiler.history_size {
+            profiler.frames.pop_front();
+        }
+    }
+    profiler.frame_index += 1;
+    profiler.frame_start = now;
+}
+
+/// Returns last finished frame.
+pub fn last_frame() -> Option<FrameProfile> {
+    PROFILER.lock().unwrap().frames.back().cloned()
+}
+
+/// Returns every kept frame, from oldest to newest.
+pub fn frames() -> Vec<FrameProfile> {
+    PROFILER.lock().unwrap().frames.iter().cloned().collect()
+}
+
+/// Removes every kept frame.
+pub fn clear() {
+    PROFILER.lock().unwrap().frames.clear();
+}
+
+fn write_event<W: Write>(
+    writer: &mut W,
+    first: &mut bool,
+    name: &str,
+    tid: u64,
+    start: Duration,
+    duration: Duration,
+) -> io::Result<()> {
+    writer.write_all(if *first { b"[\n" } else { b",\n" })?;
+    *first = false;
+    writer.write_all(b"{\"name\":")?;
+    write_json_string(writer, name)?;
+    write!(
+        writer,
+        ",\"ph\":\"X\",\"pid\":0,\"tid\":{},\"ts\":{},\"dur\":{}}}",
+        tid,
+        start.as_micros(),
+        duration.as_micros()
+    )
+}
+
+fn write_json_string<W: Write>(writer: &mut W, string: &str) -> io::Result<()> {
+    writer.write_all(b"\"")?;
+    for c in string.chars() {
+        match c {
+            '"' => writer.write_all(b"\\\"")?,
+            '\\' => writer.write_all(b"\\\\")?,
+            c if (c as u32) < 0x20 => write!(writer, "\\u{:04x}", c as u32)?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+    writer.write_all(b"\"")
+}
+
+/// Writes every kept frame in Chrome trace format (JSON array of complete events) to given
+/// writer. Every frame is an event named "Frame N" on its own track.
+pub fn write_chrome_trace<W: Write>(writer: &mut W) -> io::Result<()> {
+    let frames = frames();
+    let mut first = true;
+    // Frames are put on a track after tracks of threads.
+    let frame_track = frames
+        .iter()
+        .flat_map(|frame| frame.scopes.iter())
+        .map(|scope| scope.thread as u64 + 1)
+        .max()
+        .unwrap_or(0);
+    for frame in frames.iter() {
+        let name = format!("Frame {}", frame.index);
+        write_event(writer, &mut first, &name, frame_track, frame.start, frame.duration)?;
+        for scope in frame.scopes.iter() {
+            let thread = scope.thread as u64;
+            write_event(writer, &mut first, scope.name, thread, scope.start, scope.duration)?;
+        }
+    }
+    writer.write_all(if first { b"[]\n" } else { b"\n]\n" })
+}
+
+/// Writes every kept frame in Chrome trace format to file with given path, see
+/// `write_chrome_trace`.
+pub fn save_chrome_trace<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_chrome_trace(&mut writer)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::profiler;
+
+    fn work() {
+        profile_scope!("Outer");
+        {
+            profile_scope!("Inner");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn profiler_records_nested_scopes() {
+        profiler::set_enabled(true);
+        profiler::next_frame();
+        work();
+        profiler::next_frame();
+
+        let frame = profiler::last_frame().unwrap();
+        let outer = frame.scopes.iter().find(|s| s.name == "Outer").unwrap();
+        let inner = frame.scopes.iter().find(|s| s.name == "Inner").unwrap();
+        assert_eq!(inner.depth, outer.depth + 1);
+        assert!(inner.start >= outer.start);
+        assert!(frame.total("Outer") >= frame.total("Inner"));
+        assert_eq!(frame.count("Inner"), 1);
+        assert!(frame.print().contains("Inner"));
+
+        let mut trace = Vec::new();
+        profiler::write_chrome_trace(&mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        assert!(trace.starts_with('[') && trace.trim_end().ends_with(']'));
+        assert!(trace.contains("{\"name\":\"Inner\",\"ph\":\"X\""));
+    }
+}
@@ -0,0 +1,97 @@
+//! Contains engine-wide random number generator.
+//!
+//! Engine uses this generator for everything that affects state of a simulation, for example
+//! for particle emission. By default it is thread-local generator seeded by operating system,
+//! so every run of a game is different. When seed is set (`set_seed`, it is done by
+//! deterministic mode of engine, see `engine::determinism`), every thread uses single shared
+//! generator with given seed, so same sequence of calls gives same numbers in every run. Game
+//! code should use the same generator for its gameplay randomness to keep runs reproducible.
+//!
+//! # Example
+//!
+//! ```
+//! use rand::Rng;
+//! use rg3d::utils::random;
+//!
+//! random::set_seed(Some(42));
+//! let a = random::rng().gen_range(0, 100);
+//! random::set_seed(Some(42));
+//! let b = random::rng().gen_range(0, 100);
+//! assert_eq!(a, b);
+//! random::set_seed(None);
+//! ```
+
+use rand::{rngs::StdRng, Error, RngCore, SeedableRng};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+
+struct SeededRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+// Allows to skip locking when generator is not seeded.
+static IS_SEEDED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref SEEDED: Mutex<Option<SeededRng>> = Mutex::new(None);
+}
+
+/// Sets seed of engine-wide generator, `None` switches back to thread-local generator seeded
+/// by operating system. Setting the same seed again restarts the sequence of numbers.
+pub fn set_seed(seed: Option<u64>) {
+    let mut seeded = SEEDED.lock().unwrap();
+    *seeded = seed.map(|seed| SeededRng {
+        seed,
+        rng: StdRng::seed_from_u64(seed),
+    });
+    IS_SEEDED.store(seeded.is_some(), Ordering::SeqCst);
+}
+
+/// Returns seed of engine-wide generator, if it was set.
+pub fn seed() -> Option<u64> {
+    SEEDED.lock().unwrap().as_ref().map(|seeded| seeded.seed)
+}
+
+/// Returns engine-wide generator, see module docs.
+pub fn rng() -> EngineRng {
+    EngineRng { _private: () }
+}
+
+/// Handle to engine-wide generator, every call is passed to seeded generator if seed is set
+/// or to thread-local generator otherwise.
+#[derive(Debug)]
+pub struct EngineRng {
+    _private: (),
+}
+
+impl EngineRng {
+    fn with<T>(&mut self, func: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+        if IS_SEEDED.load(Ordering::SeqCst) {
+            if let Some(seeded) = SEEDED.lock().unwrap().as_mut() {
+                return func(&mut seeded.rng);
+            }
+        }
+        func(&mut rand::thread_rng())
+    }
+}
+
+impl RngCore for EngineRng {
+    fn next_u32(&mut self) -> u32 {
+        self.with(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.with(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.with(|rng| rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.with(|rng| rng.try_fill_bytes(dest))
+    }
+}
@@ -0,0 +1,163 @@
+//! Contains generation of signed distance fields from coverage images.
+//!
+//! Signed distance field (SDF) stores distance to nearest edge of a shape instead of
+//! coverage of a pixel. When such image is magnified, bilinear filtering interpolates
+//! distances, so edge of the shape stays sharp at any scale, while plain coverage image
+//! becomes blurry. This is used for glyph atlases of fonts, so text stays crisp when UI is
+//! scaled.
+//!
+//! Distances are measured in pixels of source image and encoded into one byte per pixel:
+//! `0.5` (128) is the edge, values greater than `0.5` are inside of the shape, and `spread`
+//! defines distance (in pixels) at which values reach 0 or 1. Distances are computed using
+//! 8SSEDT (eight-points signed sequential Euclidean distance transform) algorithm, pixels on
+//! edges of the shape use their coverage to get sub-pixel precision.
+
+// Larger than any real offset, but small enough to not overflow squared length.
+const FAR: i32 = 10_000;
+
+#[derive(Copy, Clone)]
+struct Offset {
+    x: i32,
+    y: i32,
+}
+
+impl Offset {
+    fn sqr_len(self) -> i32 {
+        self.x * self.x + self.y * self.y
+    }
+}
+
+struct Grid {
+    width: usize,
+    height: usize,
+    offsets: Vec<Offset>,
+}
+
+impl Grid {
+    fn compare(&mut self, x: usize, y: usize, dx: i32, dy: i32) {
+        let nx = x as i32 + dx;
+        let ny = y as i32 + dy;
+        if nx < 0 || ny < 0 || nx >= self.width as i32 || ny >= self.height as i32 {
+            return;
+        }
+        let neighbour = self.offsets[ny as usize * self.width + nx as usize];
+        if neighbour.x == FAR {
+            return;
+        }
+        let candidate = Offset {
+            x: neighbour.x + dx,
+            y: neighbour.y + dy,
+        };
+        let current = &mut self.offsets[y * self.width + x];
+        if candidate.sqr_len() < current.sqr_len() {
+            *current = candidate;
+        }
+    }
+
+    /// Returns distance from every pixel to nearest pixel for which `is_seed` returns true.
+    fn distances(width: usize, height: usize, is_seed: impl Fn(usize) -> bool) -> Vec<f32> {
+        let mut grid = Grid {
+            width,
+            height,
+            offsets: (0..width * height)
+                .map(|i| {
+                    if is_seed(i) {
+                        Offset { x: 0, y: 0 }
+                    } else {
+                        Offset { x: FAR, y: FAR }
+                    }
+                })
+                .collect(),
+        };
+
+        for y in 0..height {
+            for x in 0..width {
+                grid.compare(x, y, -1, 0);
+                grid.compare(x, y, 0, -1);
+                grid.compare(x, y, -1, -1);
+                grid.compare(x, y, 1, -1);
+            }
+            for x in (0..width).rev() {
+                grid.compare(x, y, 1, 0);
+            }
+        }
+        for y in (0..height).rev() {
+            for x in (0..width).rev() {
+                grid.compare(x, y, 1, 0);
+                grid.compare(x, y, 0, 1);
+                grid.compare(x, y, -1, 1);
+                grid.compare(x, y, 1, 1);
+            }
+            for x in 0..width {
+                grid.compare(x, y, -1, 0);
+            }
+        }
+
+        grid.offsets
+            .iter()
+            .map(|offset| (offset.sqr_len() as f32).sqrt())
+            .collect()
+    }
+}
+
+/// Converts coverage image (one byte per pixel) of given size to signed distance field of
+/// the same size, see module docs.
+pub fn coverage_to_sdf(coverage: &[u8], width: usize, height: usize, spread: f32) -> Vec<u8> {
+    assert_eq!(coverage.len(), width * height);
+
+    let is_inside = |i: usize| coverage[i] >= 128;
+    let outside_distances = Grid::distances(width, height, is_inside);
+    let inside_distances = Grid::distances(width, height, |i| !is_inside(i));
+
+    let spread = spread.max(std::f32::EPSILON);
+    (0..width * height)
+        .map(|i| {
+            let inside = is_inside(i);
+            let distance = if inside {
+                inside_distances[i]
+            } else {
+                outside_distances[i]
+            };
+            // Positive outside of the shape.
+            let signed_distance = if distance <= 1.0 {
+                // Pixel is on the edge, its coverage is a good estimation of position of
+                // the edge inside the pixel.
+                0.5 - coverage[i] as f32 / 255.0
+            } else if inside {
+                0.5 - distance
+            } else {
+                distance - 0.5
+            };
+            let value = 0.5 - signed_distance / (2.0 * spread);
+            (value.max(0.0).min(1.0) * 255.0 + 0.5) as u8
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::utils::sdf::coverage_to_sdf;
+
+    #[test]
+    fn square_distance_field() {
+        // 7x7 image with 3x3 square in the center.
+        let coverage = (0..49)
+            .map(|i| {
+                if (2..5).contains(&(i % 7)) && (2..5).contains(&(i / 7)) {
+                    255
+                } else {
+                    0
+                }
+            })
+            .collect::<Vec<u8>>();
+        let sdf = coverage_to_sdf(&coverage, 7, 7, 2.0);
+        let at = |x: usize, y: usize| sdf[y * 7 + x];
+
+        // Deeper inside - greater values, further outside - smaller values.
+        assert!(at(3, 3) > at(2, 3) && at(2, 3) >= 128);
+        assert!(at(1, 3) < 128 && at(0, 3) < at(1, 3));
+        // Field is symmetric for symmetric shape.
+        assert_eq!(at(0, 3), at(6, 3));
+        assert_eq!(at(3, 0), at(3, 6));
+    }
+}
@@ -0,0 +1,129 @@
+//! Tests of `Visit` derive macro, written the way games use it from outside of the engine.
+
+use rg3d::{
+    core::visitor::{Visit, VisitResult, Visitor},
+    Visit,
+};
+
+fn round_trip<S: Visit, D: Visit>(name: &str, source: &mut S, dest: &mut D) -> VisitResult {
+    let path = std::env::temp_dir().join(format!("rg3d-derive-{}.bin", name));
+    let mut visitor = Visitor::new();
+    source.visit("Data", &mut visitor)?;
+    visitor.save_binary(&path)?;
+
+    let mut visitor = Visitor::load_binary(&path)?;
+    let result = dest.visit("Data", &mut visitor);
+    let _ = std::fs::remove_file(path);
+    result
+}
+
+#[derive(Default, Debug, PartialEq, Visit)]
+struct Player {
+    health: f32,
+    #[visit(rename = "Ammo")]
+    ammo_count: u32,
+    #[visit(skip)]
+    frames_since_last_shot: u32,
+}
+
+#[derive(Default, Debug, PartialEq, Visit)]
+struct Pair(u32, f32);
+
+#[derive(Default, Debug, PartialEq, Visit)]
+struct Wrapper<T> {
+    value: T,
+    #[visit(optional)]
+    extra: u32,
+}
+
+#[derive(Default, Debug, PartialEq, Visit)]
+struct PlayerWithArmor {
+    health: f32,
+    #[visit(rename = "Ammo")]
+    ammo_count: u32,
+    #[visit(since = 1)]
+    armor: f32,
+}
+
+#[test]
+fn named_struct_round_trip() {
+    let mut player = Player {
+        health: 50.0,
+        ammo_count: 20,
+        frames_since_last_shot: 3,
+    };
+    let mut loaded = Player::default();
+    round_trip("named", &mut player, &mut loaded).unwrap();
+    assert_eq!(
+        loaded,
+        Player {
+            health: 50.0,
+            ammo_count: 20,
+            frames_since_last_shot: 0,
+        }
+    );
+}
+
+#[test]
+fn tuple_and_generic_struct_round_trip() {
+    let mut pair = Pair(1, 2.0);
+    let mut loaded = Pair::default();
+    round_trip("tuple", &mut pair, &mut loaded).unwrap();
+    assert_eq!(loaded, pair);
+
+    let mut wrapper = Wrapper {
+        value: Pair(3, 4.0),
+        extra: 5,
+    };
+    let mut loaded = Wrapper::<Pair>::default();
+    round_trip("generic", &mut wrapper, &mut loaded).unwrap();
+    assert_eq!(loaded, wrapper);
+}
+
+#[test]
+fn versioned_field_round_trip() {
+    // Data without armor was written before versioning, armor keeps its value.
+    let mut old = Player {
+        health: 10.0,
+        ammo_count: 5,
+        frames_since_last_shot: 0,
+    };
+    let mut loaded = PlayerWithArmor {
+        armor: 7.0,
+        ..Default::default()
+    };
+    round_trip("version-0", &mut old, &mut loaded).unwrap();
+    assert_eq!(
+        loaded,
+        PlayerWithArmor {
+            health: 10.0,
+            ammo_count: 5,
+            armor: 7.0,
+        }
+    );
+
+    let mut new = PlayerWithArmor {
+        health: 1.0,
+        ammo_count: 2,
+        armor: 3.0,
+    };
+    let mut loaded = PlayerWithArmor::default();
+    round_trip("version-1", &mut new, &mut loaded).unwrap();
+    assert_eq!(loaded, new);
+
+    // Versioned field is not optional in data of its version.
+    let (mut version, mut health, mut ammo_count) = (1u32, 1.0f32, 2u32);
+    let path = std::env::temp_dir().join("rg3d-derive-missing-field.bin");
+    let mut visitor = Visitor::new();
+    visitor.enter_region("Data").unwrap();
+    version.visit("Version", &mut visitor).unwrap();
+    health.visit("health", &mut visitor).unwrap();
+    ammo_count.visit("Ammo", &mut visitor).unwrap();
+    visitor.leave_region().unwrap();
+    visitor.save_binary(&path).unwrap();
+    let mut visitor = Visitor::load_binary(&path).unwrap();
+    let mut loaded = PlayerWithArmor::default();
+    let result = loaded.visit("Data", &mut visitor);
+    let _ = std::fs::remove_file(path);
+    assert!(result.is_err());
+}